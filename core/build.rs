@@ -0,0 +1,321 @@
+// Generates ml_core.pyi, the Python type stubs, from the #[pyfunction] definitions. Only the
+// functions lib.rs registers under the features being built are listed. The stub is written to
+// OUT_DIR only, which setup.py finds in cargo's build messages
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+struct Param {
+    name: String,
+    rust_type: String,
+    default: Option<String>,
+}
+
+struct Function {
+    name: String,
+    doc: Vec<String>,
+    params: Vec<Param>,
+    returns: String,
+}
+
+// A pyo3::create_exception! class: name and Python base class
+struct Exception {
+    name: String,
+    base: String,
+}
+
+fn rust_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut entries: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            rust_files(&path, files);
+        } else if path.extension().is_some_and(|extension| extension == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+// Splits on commas outside brackets, parentheses and string literals
+fn split_top_level(list: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0i32;
+    let mut in_string = false;
+    for c in list.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '<' | '(' | '[' if !in_string => depth += 1,
+            '>' | ')' | ']' if !in_string => depth -= 1,
+            ',' if !in_string && depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+// The text inside the parentheses opening at `open`, and the index after the closing one
+fn parenthesized(text: &str, open: usize) -> Option<(&str, usize)> {
+    let mut depth = 0;
+    let mut in_string = false;
+    for (index, c) in text[open..].char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&text[open + 1..open + index], open + index + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// `type TextReport = (...);` aliases, so return types resolve to what Python receives
+fn type_aliases(source: &str, aliases: &mut HashMap<String, String>) {
+    for line in source.lines() {
+        let line = line.trim().trim_start_matches("pub ");
+        if let Some(rest) = line.strip_prefix("type ") {
+            if let Some((name, target)) = rest.split_once('=') {
+                aliases.insert(name.trim().to_string(), target.trim().trim_end_matches(';').trim().to_string());
+            }
+        }
+    }
+}
+
+fn pyfunctions(source: &str) -> Vec<Function> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut functions = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim() != "#[pyfunction]" {
+            continue;
+        }
+        let doc: Vec<String> = lines[..index].iter().rev()
+            .take_while(|line| line.trim_start().starts_with("//"))
+            .map(|line| line.trim_start().trim_start_matches('/').trim().to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        let rest = lines[index + 1..].join("\n");
+
+        let Some(fn_at) = rest.find("fn ") else { continue };
+        // Only the attributes before the fn, so a function without a signature never takes
+        // the defaults of the next one
+        let mut defaults = HashMap::new();
+        if let Some(at) = rest[..fn_at].find("#[pyo3(signature = ") {
+            let open = at + "#[pyo3(signature = ".len();
+            if let Some((signature, _)) = parenthesized(&rest, open) {
+                for part in split_top_level(signature) {
+                    if let Some((name, default)) = part.split_once('=') {
+                        defaults.insert(name.trim().to_string(), default.trim().to_string());
+                    }
+                }
+            }
+        }
+        let Some(open) = rest[fn_at..].find('(').map(|open| fn_at + open) else { continue };
+        let name = rest[fn_at + 3..open].trim().to_string();
+        let Some((params, after)) = parenthesized(&rest, open) else { continue };
+        let body = rest[after..].find('{').map(|brace| after + brace).unwrap_or(rest.len());
+        let returns = rest[after..body].trim().strip_prefix("->").unwrap_or("()").trim().to_string();
+        let params = split_top_level(params).into_iter()
+            .filter_map(|param| {
+                let (name, rust_type) = param.split_once(':')?;
+                let name = name.trim().trim_start_matches("mut ").to_string();
+                let rust_type = rust_type.trim().to_string();
+                if rust_type == "Python" || rust_type.starts_with("Python<") {
+                    return None;
+                }
+                let default = defaults.get(&name).cloned();
+                Some(Param { name, rust_type, default })
+            })
+            .collect();
+        functions.push(Function { name, doc, params, returns });
+    }
+    functions
+}
+
+// `Vec<...>` -> ("Vec", "...")
+fn generic(rust_type: &str) -> Option<(&str, &str)> {
+    let open = rust_type.find('<')?;
+    rust_type.ends_with('>').then(|| (rust_type[..open].trim(), &rust_type[open + 1..rust_type.len() - 1]))
+}
+
+fn python_type(rust_type: &str, aliases: &HashMap<String, String>) -> String {
+    let rust_type = rust_type.trim().trim_start_matches('&').trim_start_matches("'_ ").trim();
+    if let Some(target) = aliases.get(rust_type) {
+        return python_type(target, aliases);
+    }
+    if rust_type == "()" {
+        return "None".to_string();
+    }
+    if let Some(inner) = rust_type.strip_prefix('(').and_then(|inner| inner.strip_suffix(')')) {
+        let items: Vec<String> = split_top_level(inner).iter().map(|item| python_type(item, aliases)).collect();
+        return format!("tuple[{}]", items.join(", "));
+    }
+    if let Some((outer, inner)) = generic(rust_type) {
+        let args: Vec<String> = split_top_level(inner).iter().map(|arg| python_type(arg, aliases)).collect();
+        return match outer {
+            "PyResult" => args.join(", "),
+            "Option" => format!("{} | None", args.join(", ")),
+            "Vec" if inner.trim() == "u8" => "bytes".to_string(),
+            "Vec" => format!("list[{}]", args.join(", ")),
+            "HashMap" | "BTreeMap" => format!("dict[{}]", args.join(", ")),
+            "HashSet" | "BTreeSet" => format!("set[{}]", args.join(", ")),
+            _ => "Any".to_string(),
+        };
+    }
+    match rust_type {
+        "str" | "String" => "str",
+        "[u8]" => "bytes",
+        "bool" => "bool",
+        "f32" | "f64" => "float",
+        "u8" | "u16" | "u32" | "u64" | "usize" | "i8" | "i16" | "i32" | "i64" | "isize" => "int",
+        _ => "Any",
+    }
+    .to_string()
+}
+
+// Literal defaults as Python writes them; anything else is left as `...`
+fn python_default(default: &str) -> String {
+    match default {
+        "None" => "None".to_string(),
+        "true" => "True".to_string(),
+        "false" => "False".to_string(),
+        _ if default.starts_with('"') || default.parse::<f64>().is_ok() => default.to_string(),
+        _ => "...".to_string(),
+    }
+}
+
+// Exception classes created for ml_core, e.g.
+// "create_exception!(ml_core, PdfPasswordError, pyo3::exceptions::PyValueError)"
+fn exceptions(source: &str) -> Vec<Exception> {
+    source.match_indices("create_exception!(ml_core,")
+        .filter_map(|(at, _)| {
+            let (arguments, _) = parenthesized(source, at + source[at..].find('(')?)?;
+            let arguments = split_top_level(arguments);
+            let base = arguments.get(2)?.rsplit("::").next()?.trim();
+            Some(Exception { name: arguments.get(1)?.trim().to_string(), base: base.trim_start_matches("Py").to_string() })
+        })
+        .collect()
+}
+
+// Functions lib.rs registers, by name and source path, with the feature each needs
+fn registered(lib: &str) -> Vec<(String, PathBuf, Option<String>)> {
+    let mut registered = Vec::new();
+    let mut feature = None;
+    for line in lib.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("#[cfg(feature = \"") {
+            feature = rest.split('"').next().map(str::to_string);
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("m.add_function(wrap_pyfunction!(") {
+            let path = rest.split(',').next().unwrap_or_default();
+            let mut segments: Vec<&str> = path.split("::").collect();
+            let name = segments.pop().unwrap_or_default().to_string();
+            let module = Path::new("src").join(segments.join("/"));
+            let file = if module.with_extension("rs").exists() { module.with_extension("rs") } else { module.join("mod.rs") };
+            registered.push((name, file, feature.take()));
+            continue;
+        }
+        feature = None;
+    }
+    registered
+}
+
+fn stub(functions: &[&Function], exceptions: &[Exception], aliases: &HashMap<String, String>, features: &[String]) -> String {
+    let mut stub = String::from("# Type stubs for ml_core, generated by build.rs from the #[pyfunction] definitions - do not edit.\n");
+    stub.push_str(&format!("# Features: {}\n\n", if features.is_empty() { "none".to_string() } else { features.join(", ") }));
+    stub.push_str("from typing import Any\n");
+    for exception in exceptions {
+        stub.push_str(&format!("\nclass {}({}): ...\n", exception.name, exception.base));
+    }
+    for function in functions {
+        let params: Vec<String> = function.params.iter()
+            .map(|param| {
+                let annotation = format!("{}: {}", param.name, python_type(&param.rust_type, aliases));
+                match &param.default {
+                    Some(default) => format!("{} = {}", annotation, python_default(default)),
+                    None => annotation,
+                }
+            })
+            .collect();
+        stub.push_str(&format!("\ndef {}({}) -> {}:", function.name, params.join(", "), python_type(&function.returns, aliases)));
+        // "// Python binding - writes the page" documents the function as "Writes the page"
+        let doc = function.doc.join(" ");
+        let doc = doc.strip_prefix("Python bindings").or_else(|| doc.strip_prefix("Python binding")).unwrap_or(&doc);
+        let doc = doc.trim_start_matches([' ', '-', ':']).trim();
+        if doc.is_empty() {
+            stub.push_str(" ...\n");
+        } else {
+            // Sentence case, unless the comment starts with an argument name such as rules_path
+            let mut chars = doc.chars();
+            let doc: String = match doc.split(' ').next().is_some_and(|word| word.contains('_')) {
+                true => doc.to_string(),
+                false => chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default(),
+            };
+            stub.push_str(&format!("\n    \"\"\"{}\"\"\"\n", doc.replace('\\', "\\\\").replace('"', "\\\"")));
+        }
+    }
+    stub
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=build.rs");
+    // security::vendor reads the key with option_env!, so a malformed one is caught here
+    println!("cargo:rerun-if-env-changed=ML_CORE_VENDOR_PUBLIC_KEY");
+    if let Ok(key) = std::env::var("ML_CORE_VENDOR_PUBLIC_KEY") {
+        let key = key.trim();
+        if key.len() != 64 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            panic!("ML_CORE_VENDOR_PUBLIC_KEY must be an Ed25519 public key as 64 hex digits");
+        }
+    }
+
+    let mut files = Vec::new();
+    rust_files(Path::new("src"), &mut files);
+    let mut aliases = HashMap::new();
+    let mut definitions: HashMap<(PathBuf, String), Function> = HashMap::new();
+    let mut classes = Vec::new();
+    for file in &files {
+        let Ok(source) = fs::read_to_string(file) else { continue };
+        type_aliases(&source, &mut aliases);
+        classes.extend(exceptions(&source));
+        for function in pyfunctions(&source) {
+            definitions.insert((file.clone(), function.name.clone()), function);
+        }
+    }
+
+    let lib = fs::read_to_string("src/lib.rs").unwrap_or_default();
+    let enabled = |feature: &str| std::env::var_os(format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"))).is_some();
+    let mut functions: Vec<&Function> = registered(&lib).into_iter()
+        .filter(|(_, _, feature)| feature.as_deref().is_none_or(enabled))
+        .filter_map(|(name, file, _)| definitions.get(&(file, name)))
+        .collect();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    // Exceptions are registered with m.add("Name", ...), whatever the features
+    classes.retain(|exception| lib.contains(&format!("m.add(\"{}\"", exception.name)));
+    classes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|feature| feature.to_lowercase().replace('_', "-")))
+        .filter(|feature| feature != "default")
+        .collect();
+    features.sort();
+
+    let stub = stub(&functions, &classes, &aliases, &features);
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").expect("cargo sets OUT_DIR"));
+    fs::write(out_dir.join("ml_core.pyi"), &stub).expect("the stub is written to OUT_DIR");
+}
@@ -5,6 +5,7 @@ Setup script for ML Core - High-performance extraction engine
 
 from setuptools import setup, Extension
 from setuptools.command.build_ext import build_ext
+import json
 import subprocess
 import sys
 import os
@@ -18,10 +19,22 @@ class RustExtension(Extension):
 class RustBuildExt(build_ext):
     def build_extension(self, ext):
         if isinstance(ext, RustExtension):
-            # Build the Rust library
-            subprocess.check_call([
-                "cargo", "build", "--release", "--manifest-path", ext.path
-            ])
+            # Build the Rust library - ML_CORE_FEATURES selects optional features, e.g. "search,graphql",
+            # and ML_CORE_NO_DEFAULT_FEATURES=1 leaves out the default extractors
+            command = ["cargo", "build", "--release", "--manifest-path", os.path.join(ext.path, "Cargo.toml")]
+            if os.environ.get("ML_CORE_NO_DEFAULT_FEATURES", "").strip() not in ("", "0"):
+                command.append("--no-default-features")
+            features = os.environ.get("ML_CORE_FEATURES", "").strip()
+            if features:
+                command += ["--features", features]
+            # Cargo's JSON messages name the build script's OUT_DIR, where build.rs writes the stub
+            command += ["--message-format", "json-render-diagnostics"]
+            build = subprocess.run(command, stdout=subprocess.PIPE, text=True, check=True)
+            stub_dir = None
+            for line in build.stdout.splitlines():
+                message = json.loads(line) if line.startswith("{") else {}
+                if message.get("reason") == "build-script-executed" and "ml_core" in message.get("package_id", ""):
+                    stub_dir = message["out_dir"]
             
             # Copy the built library
             target_dir = os.path.dirname(self.get_ext_fullpath(ext.name))
@@ -44,6 +57,16 @@ class RustBuildExt(build_ext):
                 print(f"Built: {target}")
             else:
                 raise FileNotFoundError(f"Library not found at {source}")
+
+            # build.rs writes the type stubs for the features built. Next to the library they
+            # serve pyright and IDEs; mypy reads single-module stubs from a PEP 561 stub package
+            stub = os.path.join(stub_dir, f"{ext.name}.pyi") if stub_dir else None
+            if stub and os.path.exists(stub):
+                import shutil
+                shutil.copy2(stub, os.path.join(target_dir, f"{ext.name}.pyi"))
+                stub_package = os.path.join(target_dir, f"{ext.name}-stubs")
+                os.makedirs(stub_package, exist_ok=True)
+                shutil.copy2(stub, os.path.join(stub_package, "__init__.pyi"))
         else:
             super().build_extension(ext)
 
@@ -84,6 +107,10 @@ def main():
             "build_ext": RustBuildExt,
         },
         packages=[],
+        py_modules=["spp"],
+        entry_points={
+            "console_scripts": ["spp=spp:main"],
+        },
         install_requires=[
             "numpy>=1.20.0",
             "pandas>=1.3.0",
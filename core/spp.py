@@ -0,0 +1,862 @@
+#!/usr/bin/env python3
+"""
+spp - command line interface for ML Core
+Thin wrapper over the ml_core extension module for shell use and scripts.
+"""
+
+import argparse
+import fnmatch
+import glob
+import importlib
+import json
+import os
+import sys
+import tempfile
+import time
+from datetime import datetime, timezone
+from typing import Dict, List
+
+import ml_core
+
+# Index location when neither --index nor SPP_INDEX is given
+DEFAULT_INDEX_DIR = ".spp/index"
+
+# Run profile file read when neither --config nor SPP_CONFIG is given
+DEFAULT_CONFIG_FILE = "spp.toml"
+
+# Where spp run --reproducible writes its manifest when --manifest is not given
+DEFAULT_MANIFEST_FILE = "manifest.json"
+
+# Where spp diff-rules writes its report when --report is not given
+DEFAULT_SNAPSHOT_REPORT = "rules-diff.json"
+
+# Exit statuses - part of the CLI contract, so orchestrators can branch on them
+EXIT_OK = 0
+EXIT_FAILED = 1          # the command failed outright
+EXIT_PARTIAL = 2         # some jobs failed, the rest completed
+EXIT_LICENSE = 3         # license missing, invalid, expired, deactivated or bound elsewhere
+EXIT_INVALID_INPUT = 4   # inputs a run would fail on (pre-flight errors)
+EXIT_CONFIG = 5          # unreadable or invalid run profile config
+EXIT_USAGE = 64          # bad command line
+
+EXIT_STATUS_NAMES = {
+    EXIT_OK: "ok",
+    EXIT_FAILED: "failed",
+    EXIT_PARTIAL: "partial",
+    EXIT_LICENSE: "license_error",
+    EXIT_INVALID_INPUT: "invalid_input",
+    EXIT_CONFIG: "config_error",
+    EXIT_USAGE: "usage_error",
+}
+
+
+class CliError(Exception):
+    def __init__(self, message: str, exit_code: int):
+        super().__init__(message)
+        self.exit_code = exit_code
+
+
+class _Parser(argparse.ArgumentParser):
+    # argparse exits with 2 on bad arguments, which here means partial failure
+    def error(self, message):
+        self.print_usage(sys.stderr)
+        self.exit(EXIT_USAGE, f"{self.prog}: error: {message}\n")
+
+
+def _index_dir(args) -> str:
+    return args.index or os.environ.get("SPP_INDEX", DEFAULT_INDEX_DIR)
+
+
+def _parse_filters(values: List[str]) -> Dict[str, str]:
+    filters = {}
+    for value in values:
+        key, sep, term = value.partition("=")
+        if not sep or not key:
+            raise CliError(f"invalid filter {value!r} (expected key=value)", EXIT_USAGE)
+        filters[key] = term
+    return filters
+
+
+def _require(name: str, feature: str):
+    if not hasattr(ml_core, name):
+        raise CliError(f"ml_core was built without the {feature} feature", EXIT_FAILED)
+    return getattr(ml_core, name)
+
+
+def _summary(result: Dict[str, str]) -> str:
+    # The most readable field a record has
+    for key in ("text", "pattern", "value", "output"):
+        if result.get(key):
+            return result[key].replace("\n", " ")[:100]
+    return ""
+
+
+def cmd_search(args) -> int:
+    search = _require("search", "search")
+    results = search(_index_dir(args), args.query, _parse_filters(args.filter), args.limit)
+    args.report["results"] = len(results)
+    if args.json:
+        print(json.dumps(results, indent=2, sort_keys=True))
+        return 0
+    for result in results:
+        print(f"{result['score']:>8}  {result.get('collection', ''):<11} {result['record_id']:<28} {_summary(result)}")
+        print(f"{'':>8}  {result.get('source', '')}")
+    return 0
+
+
+def cmd_index(args) -> int:
+    index_outputs = _require("index_outputs", "search")
+    count = index_outputs(_index_dir(args), args.paths)
+    args.report.update(outputs=len(args.paths), records=count)
+    print(f"Indexed {count} records from {len(args.paths)} outputs")
+    return 0
+
+
+def iter_records(path: str, filters: Dict[str, str] = None, page_size: int = 1000):
+    """Yields the records of an output or JSONL file a page at a time, however large the file."""
+    cursor = None
+    while True:
+        records, cursor = ml_core.read_records(path, filters, page_size, cursor)
+        yield from records
+        if cursor is None:
+            return
+
+
+def cmd_records(args) -> int:
+    if args.limit is not None and args.limit < 1:
+        raise CliError("--limit must be at least 1", EXIT_USAGE)
+    filters = _parse_filters(args.filter)
+    count = 0
+    # A page never holds more than --limit records, so a small limit stops early
+    for record in iter_records(args.path, filters, min(args.limit or 1000, 1000)):
+        print(json.dumps(record, sort_keys=True))
+        count += 1
+        if count == args.limit:
+            break
+    args.report["records"] = count
+    return 0
+
+
+# Decoded watermark fields, in display order
+WATERMARK_FIELDS = ("format", "license_id", "customer_id_hash", "issued_at", "input_hash")
+
+
+def _print_fields(report: Dict[str, str], fields) -> None:
+    for key in fields:
+        if key in report:
+            print(f"{key:<17} {report[key]}")
+
+
+def cmd_watermark_verify(args) -> int:
+    report = ml_core.verify_watermark(args.path)
+    args.report.update(path=args.path, valid=report["valid"] == "true")
+    if args.json:
+        print(json.dumps(report, indent=2, sort_keys=True))
+    else:
+        print(f"{args.path}: {'valid' if report['valid'] == 'true' else 'INVALID'}")
+        _print_fields(report, WATERMARK_FIELDS + ("signature_valid", "input_matches"))
+    return EXIT_OK if report["valid"] == "true" else EXIT_FAILED
+
+
+def cmd_watermark_extract(args) -> int:
+    token = ml_core.extract_watermark(args.path)
+    args.report.update(path=args.path, found=token is not None)
+    if token is None:
+        raise CliError(f"no watermark found in {args.path}", EXIT_FAILED)
+    # Decoding does not require a valid signature - extract reports what is embedded
+    report = ml_core.verify_watermark(args.path)
+    report = {key: value for key, value in report.items() if key in WATERMARK_FIELDS}
+    report["token"] = token
+    if args.json:
+        print(json.dumps(report, indent=2, sort_keys=True))
+    else:
+        _print_fields(report, WATERMARK_FIELDS + ("token",))
+    return EXIT_OK
+
+
+def _load_plugins(args):
+    # Plugins register their exporters with ml_core.register_exporter when imported, so they
+    # are loaded before a run profile's or --format's format is checked
+    modules = getattr(args, "plugin", None) or list(filter(None, os.environ.get("SPP_PLUGINS", "").split(",")))
+    for module in modules:
+        try:
+            importlib.import_module(module.strip())
+        except ImportError as e:
+            raise CliError(f"plugin {module}: {e}", EXIT_USAGE)
+
+
+def _config_path(args):
+    path = args.config or os.environ.get("SPP_CONFIG")
+    if path:
+        return path
+    return DEFAULT_CONFIG_FILE if os.path.exists(DEFAULT_CONFIG_FILE) else None
+
+
+def _run_profile(args) -> Dict[str, str]:
+    # Defaults, the selected profile and SPP_* overrides; command line options win over all of them
+    path = _config_path(args)
+    if path is None:
+        if args.profile:
+            raise CliError(f"--profile needs a config file ({DEFAULT_CONFIG_FILE}, --config or $SPP_CONFIG)", EXIT_CONFIG)
+        return {}
+    try:
+        return ml_core.load_run_profile(path, args.profile)
+    except ValueError as e:
+        raise CliError(str(e), EXIT_CONFIG)
+
+
+def _option(value, settings: Dict[str, str], key: str, flag: str):
+    value = value if value is not None else settings.get(key)
+    if value is None:
+        raise CliError(f"{flag} is required (or set {key} in the run profile)", EXIT_USAGE)
+    return value
+
+
+def _count(settings: Dict[str, str], key: str):
+    return int(settings[key]) if key in settings else None
+
+
+def _page_range(spec):
+    # "120-180" or a single page, "12"
+    if spec is None:
+        return None
+    start, _, end = spec.partition("-")
+    try:
+        return int(start), int(end or start)
+    except ValueError:
+        raise CliError(f"invalid page range {spec!r} (expected START-END, e.g. 120-180)", EXIT_USAGE)
+
+
+def _expand_inputs(settings: Dict[str, str]) -> List[str]:
+    inputs = []
+    for pattern in filter(None, settings.get("inputs", "").split(",")):
+        matches = sorted(glob.glob(pattern))
+        if matches:
+            inputs.extend(matches)
+        elif not glob.has_magic(pattern):
+            inputs.append(pattern)  # Reported as missing by the pre-flight checks
+    return inputs
+
+
+def _input_policies(settings: Dict[str, str], inputs: List[str]) -> Dict[str, str]:
+    # The first profile pattern an input matches sets its policy; inputs matching none run in full
+    patterns = [pair.rsplit("=", 1) for pair in filter(None, settings.get("policies", "").split(","))]
+    policies = {}
+    for path in inputs:
+        policy = next((policy for pattern, policy in patterns if fnmatch.fnmatch(path, pattern)), None)
+        if policy:
+            policies[path] = policy
+    return policies
+
+
+def _quality_policies(args, inputs: List[str], rules: str, policies: Dict[str, str]) -> Dict[str, str]:
+    # Inputs without a profile policy take the one their quality grade recommends. Those that
+    # need a person are skipped and listed in the summary and on stderr
+    _, checks = ml_core.validate_inputs(inputs, rules, None)
+    routed = dict(policies)
+    manual = []
+    for check in checks:
+        if "quality_grade" not in check or check["path"] in policies:
+            continue
+        routed[check["path"]] = check["recommended_policy"]
+        if check["route"] == "manual":
+            manual.append(check["path"])
+            print(f"spp: {check['path']} needs manual handling (grade {check['quality_grade']}: "
+                  f"{check['quality_reasons']})", file=sys.stderr)
+    args.report["manual_inputs"] = manual
+    return routed
+
+
+def _print_preflight(summary: Dict[str, str], checks: List[Dict[str, str]]) -> None:
+    for check in checks:
+        job = f"[{check['job_id'][:8]}] " if "job_id" in check else ""
+        encoding = f", {check['encoding']}" if check.get("encoding", "utf-8") != "utf-8" else ""
+        grade = f", grade {check['quality_grade']} -> {check['recommended_policy']}" if "quality_grade" in check else ""
+        print(f"{check['status'].upper():<8} {job}{check['path']}  ({check['pages']} pages, {check['bytes']} bytes{encoding}{grade})")
+        if check.get("error"):
+            print(f"{'':<8} error: {check['error']}")
+        for warning in filter(None, check["warnings"].split("; ")):
+            print(f"{'':<8} warning: {warning}")
+    if "license_valid" in summary:
+        print(f"License: {'valid' if summary['license_valid'] == 'true' else summary['license_error']}")
+    print(f"{summary['files']} files: {summary['ok']} ok, {summary['warnings']} with warnings, {summary['errors']} with errors")
+    if int(summary.get("manual", 0)):
+        print(f"{summary['manual']} files need manual handling")
+
+
+def _typed(fields: Dict[str, str]) -> Dict[str, object]:
+    # Counts and flags as JSON numbers and booleans
+    typed = {}
+    for key, value in fields.items():
+        if value in ("true", "false"):
+            typed[key] = value == "true"
+        elif value.isdigit():
+            typed[key] = int(value)
+        else:
+            typed[key] = value
+    return typed
+
+
+def _report_preflight(args, summary: Dict[str, str], checks: List[Dict[str, str]]) -> int:
+    args.report.update(_typed(summary))
+    if args.json:
+        print(json.dumps({"summary": summary, "files": checks}, indent=2, sort_keys=True))
+    else:
+        _print_preflight(summary, checks)
+    if summary["ready"] == "true":
+        return EXIT_OK
+    # A license problem stops the whole run, so it outranks bad inputs
+    if summary.get("license_valid") == "false":
+        return EXIT_LICENSE
+    return EXIT_INVALID_INPUT
+
+
+def cmd_validate(args) -> int:
+    settings = _run_profile(args)
+    paths = args.paths or _expand_inputs(settings)
+    if not paths:
+        raise CliError("no input files given or matched by the run profile", EXIT_USAGE)
+    summary, checks = ml_core.validate_inputs(paths, args.rules or settings.get("rules"),
+                                              args.license or settings.get("license"))
+    return _report_preflight(args, summary, checks)
+
+
+# Statuses run_jobs can return, and those that count as failures
+JOB_STATUSES = ("completed", "failed", "cancelled", "expired")
+FAILED_JOB_STATUSES = ("failed", "expired")
+
+
+def cmd_run(args) -> int:
+    settings = _run_profile(args)
+    db = _option(args.db, settings, "db", "--db")
+    rules = _option(args.rules, settings, "rules", "--rules")
+    license_path = args.license or settings.get("license")
+    # Checked before any job is submitted; run profiles check their own format when loaded
+    if args.format and args.format != "json" and args.format not in ml_core.get_export_formats():
+        formats = ", ".join(["json"] + ml_core.get_export_formats())
+        raise CliError(f"unknown --format {args.format} (expected {formats})", EXIT_USAGE)
+    # A profile with inputs submits them as a new job before running the queue
+    inputs = _expand_inputs(settings)
+    if "inputs" in settings and not inputs:
+        print(f"spp: no files match the profile inputs {settings['inputs']}", file=sys.stderr)
+
+    if args.dry_run:
+        if inputs:
+            summary, checks = ml_core.validate_inputs(inputs, rules, license_path)
+        else:
+            summary, checks = ml_core.validate_jobs(db, rules, license_path)
+        return _report_preflight(args, summary, checks)
+    if license_path:
+        problem = ml_core.check_license(license_path)
+        if problem:
+            raise CliError(f"license {license_path}: {problem}", EXIT_LICENSE)
+    if inputs:
+        sections = args.sections or settings.get("sections")
+        policies = _input_policies(settings, inputs)
+        if args.route_by_quality:
+            policies = _quality_policies(args, inputs, rules, policies)
+        args.report["submitted_job_id"] = ml_core.submit_job(
+            db, inputs, settings["output_dir"], _count(settings, "priority") or 0, None,
+            _page_range(args.pages or settings.get("pages")),
+            [section.strip() for section in sections.split(",")] if sections else None,
+            policies or None, args.tenant or settings.get("tenant"))
+    # Span timings are written next to the summary, as <summary>.folded and <summary>.pprof
+    profile_path = None
+    if args.cpu_profile:
+        if not args.summary_json or args.summary_json == "-":
+            raise CliError("--cpu-profile needs --summary-json PATH to write the profile next to", EXIT_USAGE)
+        profile_path = os.path.splitext(args.summary_json)[0]
+    # The summary carries the run's CPU time, peak memory and I/O, which ml_core writes to a file
+    usage_path = None
+    if args.summary_json:
+        fd, usage_path = tempfile.mkstemp(prefix="spp-usage-", suffix=".json")
+        os.close(fd)
+    try:
+        jobs = ml_core.run_jobs(
+            db,
+            rules,
+            args.max_jobs or _count(settings, "max_jobs"),
+            args.index or settings.get("index"),
+            license_path,
+            args.concurrency or _count(settings, "concurrency") or 1,
+            args.format or settings.get("format"),
+            (args.manifest or DEFAULT_MANIFEST_FILE) if args.reproducible else None,
+            args.llm or settings.get("llm"),
+            usage_path,
+            profile_path,
+            args.webhooks or settings.get("webhooks"),
+        )
+        if usage_path:
+            with open(usage_path, encoding="utf-8") as f:
+                args.report["resources"] = json.load(f)
+        if profile_path:
+            args.report["profile"] = {"folded": profile_path + ".folded", "pprof": profile_path + ".pprof"}
+    finally:
+        if usage_path:
+            os.unlink(usage_path)
+    if args.reproducible:
+        args.report["manifest"] = args.manifest or DEFAULT_MANIFEST_FILE
+    if args.json:
+        print(json.dumps(jobs, indent=2, sort_keys=True))
+    else:
+        for job in jobs:
+            print(f"{job['status']:<10} {job['job_id']}  {job.get('error', '')}".rstrip())
+
+    counts = {status: 0 for status in JOB_STATUSES}
+    for job in jobs:
+        counts[job["status"]] = counts.get(job["status"], 0) + 1
+    args.report.update(
+        jobs=len(jobs),
+        documents=sum(int(job["progress"].split("/")[0]) for job in jobs),
+        failed_job_ids=[job["job_id"] for job in jobs if job["status"] in FAILED_JOB_STATUSES],
+        **counts,
+    )
+    # Cancelled jobs were stopped on purpose and count as neither
+    failed = sum(counts[status] for status in FAILED_JOB_STATUSES)
+    if not failed:
+        return EXIT_OK
+    return EXIT_PARTIAL if counts["completed"] else EXIT_FAILED
+
+
+def cmd_replay(args) -> int:
+    summary, checks = ml_core.replay_manifest(args.manifest, args.rules)
+    args.report.update(_typed(summary))
+    args.report["failed_inputs"] = [check["input"] for check in checks if check["status"] != "match"]
+    if args.json:
+        print(json.dumps({"summary": summary, "outputs": checks}, indent=2, sort_keys=True))
+    else:
+        for check in checks:
+            detail = check.get("error") or (f"expected {check['expected_hash']}, got {check['actual_hash']}"
+                                            if check["status"] == "mismatch" else "")
+            print(f"{check['status'].upper():<9} [{check['job_id'][:8]}] {check['input']}  {detail}".rstrip())
+        for problem in filter(None, summary["environment_problems"].split("; ")):
+            print(f"warning: {problem}")
+        result = "reproduced" if summary["reproduced"] == "true" else "NOT reproduced"
+        print(f"{args.manifest} {result}: {summary['matched']} of {summary['outputs']} outputs match")
+    return EXIT_OK if summary["reproduced"] == "true" else EXIT_FAILED
+
+
+def cmd_eval(args) -> int:
+    summary, scores = ml_core.evaluate_extraction(args.gold, args.pred, args.match, args.min_overlap, args.type or None)
+    args.report.update(_typed(summary))
+    if args.json:
+        print(json.dumps({"summary": summary, "record_types": scores}, indent=2, sort_keys=True))
+    else:
+        print(f"{'type':<12} {'gold':>6} {'pred':>6} {'tp':>6} {'precision':>9} {'recall':>7} {'f1':>6}")
+        for score in scores:
+            print(f"{score['record_type']:<12} {score['gold']:>6} {score['predicted']:>6} {score['true_positives']:>6} "
+                  f"{score['precision']:>9} {score['recall']:>7} {score['f1']:>6}")
+        print(f"{'all':<12} {summary['gold']:>6} {summary['predicted']:>6} {summary['true_positives']:>6} "
+              f"{summary['precision']:>9} {summary['recall']:>7} {summary['f1']:>6}  ({summary['criterion']})")
+    # --min-f1 lets a CI job fail on a regression
+    if args.min_f1 is not None and float(summary["f1"]) < args.min_f1:
+        print(f"spp: F1 {summary['f1']} is below --min-f1 {args.min_f1}", file=sys.stderr)
+        return EXIT_FAILED
+    return EXIT_OK
+
+
+def cmd_tune(args) -> int:
+    settings = _run_profile(args)
+    rules = _option(args.rules, settings, "rules", "--rules")
+    summary, categories = ml_core.tune_thresholds(rules, args.labels, args.output)
+    args.report.update(_typed(summary))
+    if args.json:
+        print(json.dumps({"summary": summary, "categories": categories}, indent=2, sort_keys=True))
+        return EXIT_OK
+    for tuning in categories:
+        print(f"{tuning['category']:<10} {tuning['current_threshold']} -> {tuning['threshold']}  "
+              f"F1 {tuning['current_f1']} -> {tuning['f1']}, precision {tuning['current_precision']} -> {tuning['precision']} "
+              f"({tuning['records']} records, {tuning['correct']} correct)")
+    written = f", written to {args.output}" if args.output else ""
+    print(f"{summary['changed']} of {summary['categories']} thresholds changed{written}")
+    return EXIT_OK
+
+
+def cmd_verify_determinism(args) -> int:
+    settings = _run_profile(args)
+    rules = _option(args.rules, settings, "rules", "--rules")
+    threads = None
+    if args.threads:
+        try:
+            threads = [int(count) for count in args.threads.split(",")]
+        except ValueError:
+            raise CliError(f"invalid --threads {args.threads!r} (expected counts, e.g. 1,2,8)", EXIT_USAGE)
+    sections = args.sections
+    summary, runs = ml_core.verify_determinism(
+        args.input, rules, args.runs, threads, _page_range(args.pages),
+        [section.strip() for section in sections.split(",")] if sections else None)
+    args.report.update(_typed(summary))
+    if args.json:
+        print(json.dumps({"summary": summary, "runs": runs}, indent=2, sort_keys=True))
+    elif summary["deterministic"] == "true":
+        print(f"{args.input}: {summary['extractions']} extractions in {summary['runs']} runs are identical ({summary['output_hash']})")
+    else:
+        print(f"{args.input}: {summary['diverged']} of {summary['extractions']} extractions differ from the first")
+        print(f"first divergence in run {summary['run']} ({summary['threads']} threads) at {summary['path']}")
+        print(f"  expected: {summary['expected']}")
+        print(f"  actual:   {summary['actual']}")
+    return EXIT_OK if summary["deterministic"] == "true" else EXIT_FAILED
+
+
+def cmd_diff_rules(args) -> int:
+    settings = _run_profile(args)
+    inputs = args.paths or _expand_inputs(settings)
+    if not inputs:
+        raise CliError("no input files given or matched by the run profile", EXIT_USAGE)
+    report_path = args.report_path or DEFAULT_SNAPSHOT_REPORT
+    summary, documents = ml_core.diff_rules_snapshot(args.before, args.after, inputs, report_path, args.cache)
+    args.report.update(_typed(summary))
+    args.report["changed_inputs"] = [document["input"] for document in documents if document["status"] == "changed"]
+    if args.json:
+        print(json.dumps({"summary": summary, "documents": documents}, indent=2, sort_keys=True))
+    else:
+        for document in documents:
+            detail = document.get("error") or (f"+{document['added']} ~{document['modified']} -{document['removed']} "
+                                               f"={document['unchanged']}")
+            print(f"{document['status'].upper():<9} {document['input']}  {detail}")
+        print(f"{summary['before_version']} -> {summary['after_version']}: {summary['documents_changed']} of "
+              f"{summary['documents']} documents changed, {summary['added']} records added, {summary['modified']} modified, "
+              f"{summary['removed']} removed; report written to {report_path}")
+    return EXIT_OK if summary["errors"] == "0" else EXIT_FAILED
+
+
+def cmd_profiles(args) -> int:
+    path = _config_path(args)
+    if path is None:
+        raise CliError(f"no config file ({DEFAULT_CONFIG_FILE}, --config or $SPP_CONFIG)", EXIT_CONFIG)
+    try:
+        profiles = {name: ml_core.load_run_profile(path, name) for name in ml_core.list_run_profiles(path)}
+    except ValueError as e:
+        raise CliError(str(e), EXIT_CONFIG)
+    args.report["profiles"] = sorted(profiles)
+    if args.json:
+        print(json.dumps(profiles, indent=2, sort_keys=True))
+        return 0
+    for name, settings in profiles.items():
+        print(name)
+        for key in sorted(settings):
+            print(f"  {key:<12} {settings[key]}")
+    return 0
+
+
+def cmd_doctor(args) -> int:
+    settings = _run_profile(args)
+    summary, checks = ml_core.run_self_test(args.license or settings.get("license"),
+                                            args.rules or settings.get("rules"))
+    args.report.update(_typed(summary))
+    args.report["failed_checks"] = [check["check"] for check in checks if check["status"] == "fail"]
+    _print_checks(args, summary, checks, f"ml_core {summary['engine_version']} is "
+                  + ("healthy" if summary["healthy"] == "true" else "NOT healthy"))
+    if summary["healthy"] == "true":
+        return EXIT_OK
+    if "license" in args.report["failed_checks"]:
+        return EXIT_LICENSE
+    return EXIT_FAILED
+
+
+def cmd_diagnose_payload(args) -> int:
+    summary, checks = ml_core.diagnose_payload(args.path, args.identity)
+    args.report.update(_typed(summary))
+    args.report["failed_checks"] = [check["check"] for check in checks if check["status"] == "fail"]
+    _print_checks(args, summary, checks, f"{args.path} " + ("opens" if summary["healthy"] == "true" else "does NOT open"))
+    return EXIT_OK if summary["healthy"] == "true" else EXIT_FAILED
+
+
+def _print_checks(args, summary, checks, result: str) -> None:
+    if args.json:
+        print(json.dumps({"summary": summary, "checks": checks}, indent=2, sort_keys=True))
+        return
+    for check in checks:
+        print(f"{check['status'].upper():<6} {check['check']:<12} {check['detail']}")
+    print(f"{result}: {summary['passed']} passed, {summary['failed']} failed, {summary['skipped']} skipped")
+
+
+def cmd_support_bundle(args) -> int:
+    settings = _run_profile(args)
+    recipients = args.recipient or list(filter(None, os.environ.get("SPP_SUPPORT_RECIPIENTS", "").split(",")))
+    if not recipients:
+        raise CliError("--recipient is required (or set $SPP_SUPPORT_RECIPIENTS to the vendor's age key)", EXIT_USAGE)
+    output = args.output or f"spp-support-{datetime.now(timezone.utc):%Y%m%dT%H%M%SZ}.zip.age"
+    summary, contents = ml_core.create_support_bundle(
+        output, recipients, args.license or settings.get("license"), args.rules or settings.get("rules"),
+        args.db or settings.get("db"), args.document, args.log, args.warnings)
+    args.report.update(_typed(summary))
+    args.report["contents"] = contents
+    if args.json:
+        print(json.dumps({"summary": summary, "contents": contents}, indent=2, sort_keys=True))
+    else:
+        for name in contents:
+            print(f"  {name}")
+        print(f"{output}: {summary['entries']} entries encrypted to {summary['recipients']} recipient(s)")
+    return EXIT_OK
+
+
+def cmd_fixtures(args) -> int:
+    summary = ml_core.generate_manual(args.output, args.spec, args.seed, args.pdf, args.rules)
+    args.report.update(_typed(summary))
+    if args.json:
+        print(json.dumps(summary, indent=2, sort_keys=True))
+    else:
+        written = ", ".join(filter(None, (args.output, args.pdf, args.rules)))
+        print(f"{written}: {summary['tasks']} tasks, {summary['steps']} steps, {summary['warnings']} warnings, "
+              f"{summary['tables']} tables, {summary['figures']} figures on {summary['pages']} pages (seed {summary['seed']})")
+    return EXIT_OK
+
+
+def _add_profile_options(command, select: bool = True) -> None:
+    command.add_argument("--config", help=f"Run profile file (default: $SPP_CONFIG or ./{DEFAULT_CONFIG_FILE})")
+    if select:
+        command.add_argument("--profile", help="Named profile in the config file (default: $SPP_PROFILE)")
+
+
+EXIT_STATUS_HELP = """exit statuses:
+  0   success
+  1   the command failed
+  2   partial failure - some jobs failed, the rest completed
+  3   license missing, invalid, expired, deactivated or bound to another machine
+  4   inputs a run would fail on (pre-flight errors)
+  5   invalid run profile config
+  64  bad command line"""
+
+
+def build_parser() -> argparse.ArgumentParser:
+    parser = _Parser(prog="spp", description="Structured PDF parser tools", epilog=EXIT_STATUS_HELP,
+                     formatter_class=argparse.RawDescriptionHelpFormatter)
+    commands = parser.add_subparsers(dest="command", required=True)
+
+    # Every command can leave a summary for orchestrators, whatever its output format
+    common = _Parser(add_help=False)
+    common.add_argument("--summary-json", metavar="PATH",
+                        help="Write a machine-readable summary with the exit status to PATH ('-' for stdout)")
+    common.add_argument("--plugin", action="append", default=[], metavar="MODULE",
+                        help="Python module to import first, e.g. one registering exporters, repeatable (default: $SPP_PLUGINS)")
+
+    search = commands.add_parser("search", parents=[common], help="Full-text search over indexed records")
+    search.add_argument("query", help='Query text, e.g. "bleed valve" or collection:steps valve')
+    search.add_argument("--index", help=f"Index directory (default: $SPP_INDEX or {DEFAULT_INDEX_DIR})")
+    search.add_argument("--filter", action="append", default=[], metavar="KEY=VALUE",
+                        help="Exact-match filter on collection, source, job_id, entity_type or kind")
+    search.add_argument("--limit", type=int, default=20)
+    search.add_argument("--json", action="store_true", help="Print results as JSON")
+    search.set_defaults(func=cmd_search)
+
+    index = commands.add_parser("index", parents=[common], help="Add output files to the search index")
+    index.add_argument("paths", nargs="+", help="Job, split or merge output files")
+    index.add_argument("--index", help=f"Index directory (default: $SPP_INDEX or {DEFAULT_INDEX_DIR})")
+    index.set_defaults(func=cmd_index)
+
+    records = commands.add_parser("records", parents=[common],
+                                  help="Print the records of an output, JSONL or Parquet file as JSON lines, without loading it")
+    records.add_argument("path", help="Job, split or merge output, a JSONL file, or a Parquet table")
+    records.add_argument("--filter", action="append", default=[], metavar="KEY=VALUE",
+                         help="Exact-match filter on collection or any record field, e.g. module_id=module_3f2a")
+    records.add_argument("--limit", type=int, help="Stop after this many records")
+    records.set_defaults(func=cmd_records)
+
+    watermark = commands.add_parser("watermark", help="Check the license watermark of an output or export")
+    watermark_commands = watermark.add_subparsers(dest="watermark_command", required=True)
+    for name, func, help_text in (
+        ("verify", cmd_watermark_verify, "Validate the watermark signature (exit status 1 if invalid)"),
+        ("extract", cmd_watermark_extract, "Print the decoded watermark"),
+    ):
+        command = watermark_commands.add_parser(name, parents=[common], help=help_text)
+        command.add_argument("path", help="JSON output, delta report, Cypher script, Turtle file, HTML redline, spoken export, highlighted PDF or bulk CSV directory")
+        command.add_argument("--json", action="store_true", help="Print the fields as JSON")
+        command.set_defaults(func=func)
+
+    validate = commands.add_parser("validate", parents=[common],
+                                   help="Pre-flight input files without extracting (exit status 3 or 4 unless ready)")
+    validate.add_argument("paths", nargs="*", help="Text layers of the input PDFs (default: the profile's inputs)")
+    validate.add_argument("--rules", help="Rules file to try against each input")
+    validate.add_argument("--license", help="License file to check")
+    validate.add_argument("--json", action="store_true", help="Print the report as JSON")
+    _add_profile_options(validate)
+    validate.set_defaults(func=cmd_validate)
+
+    run = commands.add_parser("run", parents=[common], help="Run queued jobs, after submitting the profile's inputs")
+    run.add_argument("--db", help="Job database")
+    run.add_argument("--rules", help="Rules file")
+    run.add_argument("--license", help="License file outputs are produced under")
+    run.add_argument("--index", help="Search index to add outputs to")
+    run.add_argument("--max-jobs", type=int)
+    run.add_argument("--concurrency", type=int, help="Jobs run side by side (default 1)")
+    run.add_argument("--format",
+                     help="Also export each output to this format: cypher, csv, rdf or a plugin's (default: json, the output only)")
+    run.add_argument("--pages", metavar="START-END",
+                     help="Extract only these pages of the profile's inputs, e.g. 120-180")
+    run.add_argument("--sections", metavar="LIST",
+                     help="Extract only modules of these chapters or sections, e.g. 32,TASK 32-11-00")
+    run.add_argument("--tenant", metavar="ID",
+                     help="Server tenant whose GraphQL and taxonomy queries see the submitted job (default: none, local only)")
+    run.add_argument("--llm", metavar="CONFIG",
+                     help="LLM config that assists inputs with the full policy (default: none, rules only)")
+    run.add_argument("--webhooks", metavar="PATH",
+                     help="JSON list of webhooks told of each output and of each job that completes or fails")
+    run.add_argument("--route-by-quality", action="store_true",
+                     help="Give the profile's inputs the policy their quality grade recommends, skipping those that need manual handling")
+    run.add_argument("--dry-run", action="store_true",
+                     help="Validate the license and the inputs of queued jobs without running them")
+    run.add_argument("--reproducible", action="store_true",
+                     help="Record input, rules and output hashes in a manifest for spp replay")
+    run.add_argument("--manifest", metavar="PATH", help=f"Manifest --reproducible writes (default: {DEFAULT_MANIFEST_FILE})")
+    run.add_argument("--cpu-profile", action="store_true",
+                     help="Write span timings next to --summary-json, for flamegraphs and pprof (profiling builds)")
+    run.add_argument("--json", action="store_true", help="Print the results as JSON")
+    _add_profile_options(run)
+    run.set_defaults(func=cmd_run)
+
+    replay = commands.add_parser("replay", parents=[common],
+                                 help="Re-run a reproducible run's extraction and check its output hashes (exit status 1 unless identical)")
+    replay.add_argument("manifest", help="Manifest written by spp run --reproducible")
+    replay.add_argument("--rules", help="Rules file, when it has moved since the run")
+    replay.add_argument("--json", action="store_true", help="Print the report as JSON")
+    replay.set_defaults(func=cmd_replay)
+
+    evaluate = commands.add_parser("eval", parents=[common],
+                                   help="Precision, recall and F1 of an output against gold annotations, per record type")
+    evaluate.add_argument("--gold", required=True, help="Gold annotations (JSON): record type -> records with offsets")
+    evaluate.add_argument("--pred", required=True, help="Job, split or merge output to score")
+    evaluate.add_argument("--match", choices=("exact", "overlap"), default="overlap",
+                          help="Span matching: identical offsets, or an overlap of at least --min-overlap (default)")
+    evaluate.add_argument("--min-overlap", type=float, default=0.5,
+                          help="Intersection over union a match needs with --match overlap (default 0.5)")
+    evaluate.add_argument("--type", action="append", metavar="TYPE", help="Score only this record type, e.g. steps")
+    evaluate.add_argument("--min-f1", type=float, help="Exit with status 1 when the overall F1 is below this")
+    evaluate.add_argument("--json", action="store_true", help="Print the report as JSON")
+    evaluate.set_defaults(func=cmd_eval)
+
+    tune = commands.add_parser("tune", parents=[common],
+                               help="Search per-category confidence thresholds that maximize F1 on reviewed records")
+    tune.add_argument("labels", help="Reviewed records (JSON): category -> records with confidence and correct")
+    tune.add_argument("--rules", help="Rules file the records were extracted with")
+    tune.add_argument("--output", metavar="PATH", help="Write the thresholds as a rules overlay extending --rules")
+    tune.add_argument("--json", action="store_true", help="Print the report as JSON")
+    _add_profile_options(tune)
+    tune.set_defaults(func=cmd_tune)
+
+    determinism = commands.add_parser("verify-determinism", parents=[common],
+                                      help="Extract an input repeatedly on varying thread counts and check the outputs are identical (exit status 1 unless they are)")
+    determinism.add_argument("input", help="Text layer of the input PDF")
+    determinism.add_argument("--rules", help="Rules file")
+    determinism.add_argument("--runs", type=int, default=5, help="Runs, each with freshly loaded rules (default 5)")
+    determinism.add_argument("--threads", metavar="LIST",
+                             help="Extractions run side by side in each run, cycled, e.g. 1,2,8 (default: 1, 2 and the core count)")
+    determinism.add_argument("--pages", metavar="START-END", help="Extract only these pages")
+    determinism.add_argument("--sections", metavar="LIST", help="Extract only modules of these chapters or sections")
+    determinism.add_argument("--json", action="store_true", help="Print every extraction as JSON")
+    _add_profile_options(determinism)
+    determinism.set_defaults(func=cmd_verify_determinism)
+
+    diff_rules = commands.add_parser("diff-rules", parents=[common],
+                                     help="Compare the records two rules versions extract from a corpus, to approve a rule-pack release")
+    diff_rules.add_argument("before", help="Rules file of the released version")
+    diff_rules.add_argument("after", help="Rules file of the candidate version")
+    diff_rules.add_argument("paths", nargs="*", help="Inputs to compare (default: the run profile's inputs)")
+    diff_rules.add_argument("--report", dest="report_path", metavar="PATH",
+                            help=f"Report of every added, modified and removed record (default: {DEFAULT_SNAPSHOT_REPORT})")
+    diff_rules.add_argument("--cache", metavar="DIR", help="Reuse and keep extractions here, keyed by engine, rules and input")
+    diff_rules.add_argument("--json", action="store_true", help="Print the summary and documents as JSON")
+    _add_profile_options(diff_rules)
+    diff_rules.set_defaults(func=cmd_diff_rules)
+
+    fixtures = commands.add_parser("fixtures", parents=[common],
+                                   help="Generate a synthetic manual to test extraction without proprietary documents")
+    fixtures.add_argument("output", help="Text layer to write")
+    fixtures.add_argument("--spec", metavar="PATH", help="Fixture settings (JSON): chapters, steps, numbering, warnings, tables")
+    fixtures.add_argument("--seed", type=int, help="Seed, overriding the spec's (the same seed gives the same manual)")
+    fixtures.add_argument("--pdf", metavar="PATH", help="Also write the manual as a PDF")
+    fixtures.add_argument("--rules", metavar="PATH", help="Also write a rules file that extracts the manual's tasks and steps")
+    fixtures.add_argument("--json", action="store_true", help="Print the counts as JSON")
+    fixtures.set_defaults(func=cmd_fixtures)
+
+    profiles = commands.add_parser("profiles", parents=[common], help="List the run profiles in the config file")
+    profiles.add_argument("--json", action="store_true", help="Print the resolved settings as JSON")
+    _add_profile_options(profiles, select=False)
+    profiles.set_defaults(func=cmd_profiles)
+
+    doctor = commands.add_parser("doctor", parents=[common],
+                                 help="Self-test the install, license and rules before contacting support")
+    doctor.add_argument("--license", help="License file to check (default: the run profile's)")
+    doctor.add_argument("--rules", help="Rules file to load and compile (default: the run profile's)")
+    doctor.add_argument("--json", action="store_true", help="Print the checks as JSON")
+    _add_profile_options(doctor)
+    doctor.set_defaults(func=cmd_doctor)
+
+    diagnose = commands.add_parser("diagnose-payload", parents=[common],
+                                   help="Find out why an encrypted output or support bundle will not decrypt")
+    diagnose.add_argument("path", help="The .age file")
+    diagnose.add_argument("--identity", metavar="KEY_FILE",
+                          help="age identity file it should decrypt with (without it only the format and header are checked)")
+    diagnose.add_argument("--json", action="store_true", help="Print the checks as JSON")
+    diagnose.set_defaults(func=cmd_diagnose_payload)
+
+    bundle = commands.add_parser("support-bundle", parents=[common],
+                                 help="Collect sanitized diagnostics into one encrypted archive to attach to a support ticket")
+    bundle.add_argument("--recipient", action="append", metavar="AGE_KEY",
+                        help="Vendor public key the archive is encrypted to, repeatable (default: $SPP_SUPPORT_RECIPIENTS)")
+    bundle.add_argument("--output", metavar="PATH", help="Archive to write (default: spp-support-<time>.zip.age)")
+    bundle.add_argument("--license", help="License file (default: the run profile's)")
+    bundle.add_argument("--rules", help="Rules file whose effective config is included (default: the run profile's)")
+    bundle.add_argument("--db", help="Job database whose recent failures are included (default: the run profile's)")
+    bundle.add_argument("--document", metavar="PATH", help="Failing document, included as its anonymized structure")
+    bundle.add_argument("--log", action="append", metavar="PATH", help="Log file whose tail is included, repeatable")
+    bundle.add_argument("--warnings", type=int, default=50, help="Most recent warnings to include (default 50)")
+    bundle.add_argument("--json", action="store_true", help="Print the summary and contents as JSON")
+    _add_profile_options(bundle)
+    bundle.set_defaults(func=cmd_support_bundle)
+
+    return parser
+
+
+def _write_summary(path: str, summary: Dict[str, object]) -> None:
+    text = json.dumps(summary, indent=2, sort_keys=True)
+    if path == "-":
+        print(text)
+        return
+    # Written beside the target and renamed over it, like the engine's own outputs, so an
+    # interrupted run never leaves a truncated report for a pipeline to choke on
+    temp = os.path.join(os.path.dirname(path) or ".", ".%s.%d.spp-tmp" % (os.path.basename(path), os.getpid()))
+    try:
+        with open(temp, "w", encoding="utf-8") as f:
+            f.write(text + "\n")
+        os.replace(temp, path)
+    finally:
+        if os.path.exists(temp):
+            os.remove(temp)
+
+
+def main(argv=None) -> int:
+    args = build_parser().parse_args(argv)
+    # Commands add their counts here; main adds the outcome
+    args.report = {}
+    started_at = datetime.now(timezone.utc)
+    started = time.monotonic()
+    error = None
+    try:
+        _load_plugins(args)
+        exit_code = args.func(args)
+    except CliError as e:
+        error, exit_code = str(e), e.exit_code
+    except (RuntimeError, ValueError, OSError) as e:
+        error, exit_code = str(e), EXIT_FAILED
+    if error:
+        print(f"spp: {error}", file=sys.stderr)
+
+    if args.summary_json:
+        command = " ".join(filter(None, (args.command, getattr(args, "watermark_command", None))))
+        summary = dict(args.report)
+        summary.update(
+            command=command,
+            exit_code=exit_code,
+            status=EXIT_STATUS_NAMES[exit_code],
+            started_at=started_at.isoformat(),
+            duration_seconds=round(time.monotonic() - started, 3),
+        )
+        if error:
+            summary["error"] = error
+        _write_summary(args.summary_json, summary)
+    return exit_code
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::engine::entities::record_extents;
+
+// Record field holding a record's tags, comma-separated like step_ids
+pub const ACCESS_TAGS_FIELD: &str = "access_tags";
+
+// Tags assigned to the records of every module whose title contains `section`,
+// e.g. {"section": "TASK 32-", "tags": ["export-controlled"]}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SectionTags {
+    pub section: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccessConfig {
+    // Tags for records outside every tagged section, e.g. ["proprietary"]
+    #[serde(default)]
+    pub default_tags: Vec<String>,
+    #[serde(default)]
+    pub sections: Vec<SectionTags>,
+}
+
+impl AccessConfig {
+    pub fn is_empty(&self) -> bool {
+        self.default_tags.is_empty() && self.sections.iter().all(|rule| rule.tags.is_empty())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for tag in self.default_tags.iter().chain(self.sections.iter().flat_map(|rule| &rule.tags)) {
+            if tag.trim().is_empty() || tag.contains(',') {
+                return Err(format!("Invalid access tag {:?}: tags must be non-empty and contain no commas", tag));
+            }
+        }
+        if let Some(rule) = self.sections.iter().find(|rule| rule.section.is_empty()) {
+            return Err(format!("Access section for tags {:?} has an empty section title", rule.tags));
+        }
+        Ok(())
+    }
+
+    fn section_tags(&self, title: &str) -> BTreeSet<String> {
+        self.sections.iter()
+            .filter(|rule| title.contains(&rule.section))
+            .flat_map(|rule| rule.tags.iter().map(|tag| tag.trim().to_string()))
+            .collect()
+    }
+}
+
+// Tags of each module extent, resolved once per document
+pub struct AccessMap<'a> {
+    config: &'a AccessConfig,
+    // (start, end, module record_id, tags) - a module runs to the next module's match
+    sections: Vec<(usize, usize, String, BTreeSet<String>)>,
+    // Step record_id -> offset, so entities inherit the tags of the steps mentioning them
+    step_offsets: HashMap<String, usize>,
+}
+
+impl<'a> AccessMap<'a> {
+    pub fn new(
+        config: &'a AccessConfig,
+        modules: &[HashMap<String, String>],
+        steps: &[HashMap<String, String>],
+        text_len: usize,
+    ) -> Self {
+        let titles: HashMap<&str, &str> = modules.iter()
+            .filter_map(|module| Some((module.get("record_id")?.as_str(), module.get("pattern")?.as_str())))
+            .collect();
+        let sections = record_extents(modules, text_len).into_iter()
+            .map(|(start, end, module_id)| {
+                let tags = config.section_tags(titles.get(module_id.as_str()).copied().unwrap_or(""));
+                (start, end, module_id, tags)
+            })
+            .collect();
+        let step_offsets = steps.iter()
+            .filter_map(|step| Some((step.get("record_id")?.clone(), step.get("original_start")?.parse().ok()?)))
+            .collect();
+        Self { config, sections, step_offsets }
+    }
+
+    fn section_at(&self, offset: usize) -> Option<&BTreeSet<String>> {
+        self.sections.iter()
+            .find(|(start, end, _, _)| offset >= *start && offset < *end)
+            .map(|(_, _, _, tags)| tags)
+    }
+
+    // Union of the sections a record belongs to: its module, its own offset and the steps it
+    // is linked to. Untagged records fall back to the default tags
+    pub fn tags_for(&self, record: &HashMap<String, String>) -> BTreeSet<String> {
+        let mut tags = BTreeSet::new();
+        if let Some(module_id) = record.get("module_id") {
+            for (_, _, id, section) in &self.sections {
+                if id == module_id {
+                    tags.extend(section.iter().cloned());
+                }
+            }
+        }
+        let offset = record.get("original_start").or_else(|| record.get("start_offset"));
+        if let Some(section) = offset.and_then(|offset| offset.parse().ok()).and_then(|offset| self.section_at(offset)) {
+            tags.extend(section.iter().cloned());
+        }
+        for step_id in record.get("step_ids").into_iter().flat_map(|ids| ids.split(',')) {
+            if let Some(section) = self.step_offsets.get(step_id).and_then(|offset| self.section_at(*offset)) {
+                tags.extend(section.iter().cloned());
+            }
+        }
+        if tags.is_empty() {
+            tags.extend(self.config.default_tags.iter().map(|tag| tag.trim().to_string()));
+        }
+        tags
+    }
+
+    // Extents of the text carrying `tag`. Text outside every tagged section - including any
+    // before the first module - carries the default tags
+    pub fn extents_tagged(&self, tag: &str, text_len: usize) -> Vec<(usize, usize)> {
+        let is_default = self.config.default_tags.iter().any(|default| default.trim() == tag);
+        let first_start = self.sections.first().map(|(start, _, _, _)| *start).unwrap_or(text_len);
+        let mut extents = Vec::new();
+        if is_default && first_start > 0 {
+            extents.push((0, first_start));
+        }
+        for (start, end, _, tags) in &self.sections {
+            if tags.contains(tag) || (tags.is_empty() && is_default) {
+                extents.push((*start, *end));
+            }
+        }
+        extents
+    }
+
+    pub fn tag(&self, records: &mut [HashMap<String, String>]) {
+        for record in records.iter_mut() {
+            let tags = self.tags_for(record);
+            if !tags.is_empty() {
+                record.insert(ACCESS_TAGS_FIELD.to_string(), tags.into_iter().collect::<Vec<_>>().join(","));
+            }
+        }
+    }
+}
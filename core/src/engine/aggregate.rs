@@ -0,0 +1,186 @@
+use pyo3::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::engine::entities::{record_extents, Entity};
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::ids::stable_record_id;
+use crate::engine::scope::scope_from_args;
+use crate::engine::segment::sentence_bounds;
+
+// Verbs that decide whether a mention asks for a panel/breaker to be opened or closed
+pub const OPEN_VERBS: &[&str] = &["open", "opens", "remove", "removes", "pull", "pulls", "trip", "disengage"];
+pub const CLOSE_VERBS: &[&str] = &["close", "closes", "install", "installs", "reinstall", "reset", "push", "engage"];
+
+// Words that make a sentence a decision point in the procedure
+pub const BRANCH_WORDS: &[&str] = &["if", "unless", "otherwise", "alternatively"];
+
+// Gazetteer type listing the tools and ground equipment a procedure needs
+pub const TOOL_ENTITY_TYPE: &str = "tool";
+
+// Labels of a declared labour estimate, e.g. "MAN-HOURS: 2.5" or "1.5 man hours"
+pub const MAN_HOUR_LABELS: &[&str] = &["man-hours", "man hours", "manhours", "man-hrs", "mhrs"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Open,
+    Close,
+}
+
+// Nearest open/close verb before the mention within its sentence; no verb counts as open
+fn action_for(text: &str, sentences: &[(usize, &str)], entity: &Entity) -> Action {
+    let sentence_start = sentences.iter()
+        .rev()
+        .find(|(start, _)| *start <= entity.start)
+        .map(|(start, _)| *start)
+        .unwrap_or(0);
+    text[sentence_start..entity.start]
+        .unicode_words()
+        .rev()
+        .find_map(|word| {
+            let word = word.to_lowercase();
+            if OPEN_VERBS.contains(&word.as_str()) {
+                Some(Action::Open)
+            } else if CLOSE_VERBS.contains(&word.as_str()) {
+                Some(Action::Close)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(Action::Open)
+}
+
+// Number next to the first man-hour label in the text, after it or just before it
+fn declared_man_hours(text: &str) -> Option<f64> {
+    let lowered = text.to_ascii_lowercase();
+    let (position, label) = MAN_HOUR_LABELS.iter()
+        .filter_map(|label| lowered.find(label).map(|position| (position, *label)))
+        .min()?;
+    let number = |candidate: &str| candidate.trim_matches(|c: char| !c.is_ascii_digit() && c != '.').parse::<f64>().ok();
+    let line_end = lowered[position..].find('\n').map(|i| position + i).unwrap_or(lowered.len());
+    lowered[position + label.len()..line_end]
+        .split_whitespace()
+        .take(2)
+        .find_map(number)
+        .or_else(|| lowered[..position].split_whitespace().next_back().and_then(number))
+}
+
+#[derive(Debug, Default)]
+struct Rollup {
+    panels_open: BTreeSet<String>,
+    panels_close: BTreeSet<String>,
+    breakers_pull: BTreeSet<String>,
+    breakers_close: BTreeSet<String>,
+    zones: BTreeSet<String>,
+    tools: BTreeSet<String>,
+    steps: usize,
+    branches: usize,
+}
+
+fn joined(values: &BTreeSet<String>) -> String {
+    values.iter().cloned().collect::<Vec<_>>().join(",")
+}
+
+// Per-procedure rollup of access panels, circuit breakers and zones. Procedures are the
+// extents of module matches; text without modules is a single procedure
+pub fn procedure_records(
+    text: &str,
+    modules: &[HashMap<String, String>],
+    steps: &[HashMap<String, String>],
+    entities: &[Entity],
+) -> Vec<HashMap<String, String>> {
+    let mut extents = record_extents(modules, text.len());
+    if extents.is_empty() {
+        extents.push((0, text.len(), String::new()));
+    }
+    let sentences = sentence_bounds(text);
+    let mut rollups: Vec<Rollup> = extents.iter().map(|_| Rollup::default()).collect();
+    let locate = |offset: usize| extents.iter().position(|(start, end, _)| offset >= *start && offset < *end);
+
+    for step in steps {
+        if let Some(index) = step.get("original_start").and_then(|s| s.parse().ok()).and_then(locate) {
+            rollups[index].steps += 1;
+        }
+    }
+
+    for (start, sentence) in &sentences {
+        let branching = sentence.unicode_words().any(|word| BRANCH_WORDS.contains(&word.to_lowercase().as_str()));
+        if let Some(index) = locate(*start).filter(|_| branching) {
+            rollups[index].branches += 1;
+        }
+    }
+
+    for entity in entities {
+        let rollup = match locate(entity.start) {
+            Some(index) => &mut rollups[index],
+            None => continue,
+        };
+        match entity.entity_type.as_str() {
+            "access_panel" => {
+                // The first three digits of a panel number are its zone
+                rollup.zones.insert(entity.value[..3].to_string());
+                match action_for(text, &sentences, entity) {
+                    Action::Open => rollup.panels_open.insert(entity.value.clone()),
+                    Action::Close => rollup.panels_close.insert(entity.value.clone()),
+                };
+            }
+            "circuit_breaker" => {
+                match action_for(text, &sentences, entity) {
+                    Action::Open => rollup.breakers_pull.insert(entity.value.clone()),
+                    Action::Close => rollup.breakers_close.insert(entity.value.clone()),
+                };
+            }
+            "zone" => {
+                rollup.zones.insert(entity.value.clone());
+            }
+            TOOL_ENTITY_TYPE => {
+                rollup.tools.insert(entity.value.clone());
+            }
+            _ => {}
+        }
+    }
+
+    extents.iter().zip(rollups)
+        .map(|((start, end, module_id), rollup)| {
+            let mut record = HashMap::new();
+            record.insert("record_id".to_string(), stable_record_id("procedure", &[module_id.as_str()]));
+            record.insert("module_id".to_string(), module_id.clone());
+            record.insert("original_start".to_string(), start.to_string());
+            record.insert("original_end".to_string(), end.to_string());
+            record.insert("step_count".to_string(), rollup.steps.to_string());
+            record.insert("access_panels_open".to_string(), joined(&rollup.panels_open));
+            record.insert("access_panels_close".to_string(), joined(&rollup.panels_close));
+            record.insert("circuit_breakers_pull".to_string(), joined(&rollup.breakers_pull));
+            record.insert("circuit_breakers_close".to_string(), joined(&rollup.breakers_close));
+            record.insert("zones".to_string(), joined(&rollup.zones));
+            record.insert("tools".to_string(), joined(&rollup.tools));
+            record.insert("branch_count".to_string(), rollup.branches.to_string());
+            if let Some(hours) = declared_man_hours(&text[*start..*end]) {
+                record.insert("declared_man_hours".to_string(), hours.to_string());
+            }
+            record
+        })
+        .collect()
+}
+
+// Python binding - planning rollup per procedure
+#[pyfunction]
+#[pyo3(signature = (text, rules_path=None, pages=None, sections=None))]
+pub fn aggregate_procedures(
+    py: Python,
+    text: &str,
+    rules_path: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => ExtractionEngine::new(),
+        };
+        let text = scope_from_args(pages, sections)?.apply(&engine, text)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(engine.aggregate_procedures(&text))
+    })
+}
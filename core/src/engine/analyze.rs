@@ -0,0 +1,125 @@
+use pyo3::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::engine::segment::{is_rtl_char, is_unspaced_script, sentence_bounds};
+use crate::engine::splitter::split_pages;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Rough BPE behaviour: ~4 characters per token for alphabetic scripts, ~1 per ideograph
+const CHARS_PER_LLM_TOKEN: f64 = 4.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct TextStats {
+    pub characters: usize,
+    pub tokens: usize,
+    pub sentences: usize,
+    pub estimated_llm_tokens: usize,
+    // Letter counts per script - a proxy for language until a language model is wired in
+    pub scripts: BTreeMap<&'static str, usize>,
+}
+
+impl TextStats {
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let mut summary = HashMap::new();
+        summary.insert("characters".to_string(), self.characters.to_string());
+        summary.insert("tokens".to_string(), self.tokens.to_string());
+        summary.insert("sentences".to_string(), self.sentences.to_string());
+        summary.insert("estimated_llm_tokens".to_string(), self.estimated_llm_tokens.to_string());
+
+        let letters: usize = self.scripts.values().sum();
+        for (script, count) in &self.scripts {
+            let share = *count as f64 / letters.max(1) as f64;
+            summary.insert(format!("script_{}", script), format!("{:.3}", share));
+        }
+        summary
+    }
+}
+
+pub fn script_of(ch: char) -> &'static str {
+    match ch {
+        _ if is_unspaced_script(ch) => "cjk",
+        _ if is_rtl_char(ch) => if ('\u{0590}'..='\u{05FF}').contains(&ch) { "hebrew" } else { "arabic" },
+        '\u{AC00}'..='\u{D7AF}' | '\u{1100}'..='\u{11FF}' => "hangul",
+        '\u{0400}'..='\u{052F}' => "cyrillic",
+        '\u{0370}'..='\u{03FF}' => "greek",
+        _ if ch.is_ascii_alphabetic() || ('\u{00C0}'..='\u{024F}').contains(&ch) => "latin",
+        _ => "other",
+    }
+}
+
+pub fn analyze(text: &str) -> TextStats {
+    let mut stats = TextStats {
+        characters: text.chars().count(),
+        tokens: text.unicode_words().count(),
+        sentences: sentence_bounds(text).iter().filter(|(_, s)| !s.trim().is_empty()).count(),
+        ..TextStats::default()
+    };
+
+    let mut ideographs = 0;
+    let mut other_chars = 0;
+    for ch in text.chars() {
+        if ch.is_alphabetic() {
+            *stats.scripts.entry(script_of(ch)).or_default() += 1;
+        }
+        if is_unspaced_script(ch) {
+            ideographs += 1;
+        } else if !ch.is_whitespace() {
+            other_chars += 1;
+        }
+    }
+    stats.estimated_llm_tokens = ideographs + (other_chars as f64 / CHARS_PER_LLM_TOKEN).ceil() as usize;
+    stats
+}
+
+// Sections are pages of the text layer; text without form feeds is a single section
+pub fn analyze_sections(text: &str) -> (TextStats, Vec<(usize, TextStats)>) {
+    let sections = split_pages(text).into_iter()
+        .map(|(offset, page)| (offset, analyze(page)))
+        .collect();
+    (analyze(text), sections)
+}
+
+// Whole-text summary plus one summary per section
+pub type TextReport = (HashMap<String, String>, Vec<HashMap<String, String>>);
+
+// Python binding - pre-flight cost estimation before enabling LLM-assisted extraction
+#[pyfunction]
+pub fn analyze_text(py: Python, text: &str) -> PyResult<TextReport> {
+    py.allow_threads(|| {
+        let (total, sections) = analyze_sections(text);
+        let sections = sections.into_iter()
+            .enumerate()
+            .map(|(index, (offset, stats))| {
+                let mut entry = stats.get_summary();
+                entry.insert("section".to_string(), (index + 1).to_string());
+                entry.insert("start_offset".to_string(), offset.to_string());
+                entry
+            })
+            .collect();
+        Ok((total.get_summary(), sections))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_text() {
+        let stats = analyze("");
+        assert_eq!((stats.characters, stats.tokens, stats.sentences, stats.estimated_llm_tokens), (0, 0, 0, 0));
+        let (total, sections) = analyze_sections("");
+        assert_eq!(total.sentences, 0);
+        assert_eq!(sections.len(), 1);
+    }
+
+    #[test]
+    fn text_ending_in_form_feed() {
+        assert_eq!(analyze("Remove the panel.\x0c").sentences, 1);
+        assert_eq!(analyze("\x0c").sentences, 0);
+        let (total, sections) = analyze_sections("Remove the panel. Install the seal.\n\x0c");
+        assert_eq!(total.sentences, 2);
+        let counts: Vec<usize> = sections.iter().map(|(_, stats)| stats.sentences).collect();
+        assert_eq!(counts, [2, 0]);
+    }
+}
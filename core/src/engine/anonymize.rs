@@ -0,0 +1,179 @@
+use pyo3::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "flows")]
+use crate::engine::aggregate::{BRANCH_WORDS, CLOSE_VERBS, MAN_HOUR_LABELS, OPEN_VERBS};
+use crate::engine::entities::{BREAKER_WORDS, QUALIFIERS, STATION_WORDS, ZONE_WORDS};
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::pii::{PiiConfig, DEFAULT_NAME_LABELS};
+use crate::engine::splitter::SplitConfig;
+use crate::security::crypto::sha256;
+
+// Words the engine's own heuristics look for - task headings, subsection titles, warnings.
+// Scrambling them would change what a reproduction document extracts
+const STRUCTURE_WORDS: &[&str] = &[
+    "task", "subtask", "chapter", "section", "subject", "page", "of",
+    "general", "job", "set", "up", "setup", "information", "preparation", "procedure", "close", "out",
+    "closeout", "removal", "installation", "test", "inspection", "references", "tools", "and",
+    "equipment", "special", "consumable", "materials", "expendable", "parts", "access", "panel", "panels",
+    "warning", "caution", "note",
+];
+
+// Lower and upper case letters that are two bytes in UTF-8, for scrambling accented text
+// without moving any offset
+const LATIN1_LOWER: &str = "àáâãäåæçèéêëìíîïðñòóôõöøùúûüýþ";
+const LATIN1_UPPER: &str = "ÀÁÂÃÄÅÆÇÈÉÊËÌÍÎÏÐÑÒÓÔÕÖØÙÚÛÜÝÞ";
+
+fn keep_words<'a>(keep: &mut HashSet<String>, phrases: impl IntoIterator<Item = &'a str>) {
+    for phrase in phrases {
+        keep.extend(phrase.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).map(str::to_lowercase));
+    }
+}
+
+fn pick(choices: &str, byte: u8) -> char {
+    let count = choices.chars().count();
+    choices.chars().nth(byte as usize % count).unwrap_or('x')
+}
+
+// Replaces a document's words with deterministic nonsense of the same shape. Whitespace,
+// punctuation, digits, letter case and byte lengths survive, so numbering, headings, page
+// breaks and offsets line up with the original; words the engine keys on are kept.
+// Inside PII findings nothing is kept, digits included
+pub struct Anonymizer {
+    key: [u8; 32],
+    keep: HashSet<String>,
+    pii: PiiConfig,
+    // (word, inside PII) -> replacement, so a word scrambles the same way throughout
+    replacements: HashMap<(String, bool), String>,
+}
+
+impl Anonymizer {
+    // Without a seed the key is derived from the document, so the mapping cannot be recomputed
+    // without it. With one, every document scrambles the same word the same way
+    pub fn new(text: &str, seed: Option<&str>) -> Self {
+        let key = match seed {
+            Some(seed) => sha256(&[b"anonymize", &[0x1f], seed.as_bytes()]),
+            None => sha256(&[b"anonymize", &[0x1f], &sha256(&[text.as_bytes()])]),
+        };
+        let mut keep = HashSet::new();
+        keep_words(&mut keep, STRUCTURE_WORDS.iter().copied());
+        #[cfg(feature = "flows")]
+        keep_words(&mut keep, OPEN_VERBS.iter().chain(CLOSE_VERBS).chain(BRANCH_WORDS).chain(MAN_HOUR_LABELS).copied());
+        keep_words(&mut keep, QUALIFIERS.iter().chain(ZONE_WORDS).chain(BREAKER_WORDS).copied());
+        keep_words(&mut keep, STATION_WORDS.iter().map(|(word, _)| *word));
+        keep_words(&mut keep, DEFAULT_NAME_LABELS.iter().copied());
+        keep_words(&mut keep, SplitConfig::default().title_markers.iter().map(String::as_str));
+        Self { key, keep, pii: PiiConfig::builtin(), replacements: HashMap::new() }
+    }
+
+    // Keeps the words of the rules' patterns and gazetteer, so the same records still match,
+    // and scrambles what the rules' PII detectors find as well as the built-in ones
+    pub fn with_engine(mut self, engine: &ExtractionEngine) -> Self {
+        keep_words(&mut self.keep, engine.get_patterns().values().flatten().map(String::as_str));
+        keep_words(&mut self.keep, engine.get_entity_config().gazetteer.values().flatten().map(String::as_str));
+        self.pii.merge(engine.get_pii_config().clone());
+        self
+    }
+
+    pub fn with_keep(mut self, words: &[String]) -> Self {
+        keep_words(&mut self.keep, words.iter().map(String::as_str));
+        self
+    }
+
+    fn scramble(&self, word: &str, private: bool, attempt: u8) -> String {
+        let lower = word.to_lowercase();
+        let seed = [&self.key[..], &[0x1f], lower.as_bytes(), &[private as u8, attempt]].concat();
+        let mut block = 0u32;
+        let mut digest = sha256(&[&seed, &block.to_be_bytes()]);
+        let mut used = 0;
+        let mut next_byte = || {
+            if used == digest.len() {
+                block += 1;
+                digest = sha256(&[&seed, &block.to_be_bytes()]);
+                used = 0;
+            }
+            used += 1;
+            digest[used - 1]
+        };
+        word.chars()
+            .map(|c| {
+                if c.is_ascii_digit() {
+                    return if private { (b'0' + next_byte() % 10) as char } else { c };
+                }
+                if !c.is_alphabetic() {
+                    return c;
+                }
+                match c.len_utf8() {
+                    1 if c.is_ascii_uppercase() => (b'A' + next_byte() % 26) as char,
+                    1 => (b'a' + next_byte() % 26) as char,
+                    2 if c.is_uppercase() => pick(LATIN1_UPPER, next_byte()),
+                    2 => pick(LATIN1_LOWER, next_byte()),
+                    // Three-byte letters become CJK ideographs, four-byte ones CJK Extension B
+                    length => {
+                        let base = if length == 3 { 0x4E00 } else { 0x20000 };
+                        let index = (next_byte() as u32) << 6 | (next_byte() as u32 & 0x3F);
+                        char::from_u32(base + index).unwrap_or(c)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn replace(&mut self, word: &str, private: bool) -> String {
+        // A single letter is a list marker or an initial - "A." must stay "A."
+        let letters = word.chars().filter(|c| c.is_alphabetic()).count();
+        if !private && (letters <= 1 || self.keep.contains(&word.to_lowercase())) {
+            return word.to_string();
+        }
+        let cache_key = (word.to_string(), private);
+        if let Some(replacement) = self.replacements.get(&cache_key) {
+            return replacement.clone();
+        }
+        // A scramble that happens to spell a kept word would start matching rules
+        let mut replacement = self.scramble(word, private, 0);
+        for attempt in 1..=u8::MAX {
+            if !self.keep.contains(&replacement.to_lowercase()) {
+                break;
+            }
+            replacement = self.scramble(word, private, attempt);
+        }
+        self.replacements.insert(cache_key, replacement.clone());
+        replacement
+    }
+
+    pub fn anonymize(&mut self, text: &str) -> String {
+        let findings = self.pii.scan(text);
+        let mut output = String::with_capacity(text.len());
+        let mut word_start = None;
+        for (offset, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+            if c.is_alphanumeric() {
+                word_start.get_or_insert(offset);
+                continue;
+            }
+            if let Some(start) = word_start.take() {
+                let private = findings.iter().any(|finding| finding.start < offset && start < finding.end);
+                let replacement = self.replace(&text[start..offset], private);
+                output.push_str(&replacement);
+            }
+            if offset < text.len() {
+                output.push(c);
+            }
+        }
+        output
+    }
+}
+
+// Python binding - a document customers can attach to a bug report in place of the manual
+#[pyfunction]
+#[pyo3(signature = (text, rules_path=None, keep=None, seed=None))]
+pub fn anonymize_document(py: Python, text: &str, rules_path: Option<&str>, keep: Option<Vec<String>>, seed: Option<&str>) -> PyResult<String> {
+    py.allow_threads(|| {
+        let mut anonymizer = Anonymizer::new(text, seed).with_keep(&keep.unwrap_or_default());
+        if let Some(rules_path) = rules_path {
+            let engine = ExtractionEngine::from_rules_file(rules_path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+            anonymizer = anonymizer.with_engine(&engine);
+        }
+        Ok(anonymizer.anonymize(text))
+    })
+}
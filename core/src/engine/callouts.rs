@@ -0,0 +1,246 @@
+use pyo3::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::engine::entities::record_extents;
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::figures::{figure_mentions, FIGURE_WORDS};
+use crate::engine::ids::stable_record_id;
+use crate::engine::scope::scope_from_args;
+
+// Words that introduce IPC item numbers in step text: "item 5", "items 3 and 4", "key 2"
+const ITEM_WORDS: &[&str] = &["items", "item", "keys", "key"];
+
+// OCR words below this confidence (0-100) are too likely misreads to link
+const MIN_CONFIDENCE: f64 = 60.0;
+
+// Callout labels are short: "5", "12", "103A"
+const MAX_LABEL_DIGITS: usize = 3;
+
+// One callout label read from a figure image, with its box in image pixels
+#[derive(Debug, Clone)]
+pub struct CalloutLabel {
+    pub label: String,
+    pub confidence: f64,
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// "5", "12", "103A" - up to three digits and an optional letter suffix
+fn is_callout(word: &str) -> bool {
+    let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let suffix = &word[digits..];
+    (1..=MAX_LABEL_DIGITS).contains(&digits) && (suffix.is_empty() || (suffix.len() == 1 && suffix.chars().all(|c| c.is_ascii_uppercase())))
+}
+
+// Callout labels in `tesseract ... tsv` output: word rows (level 5) whose text, without
+// surrounding punctuation such as a circle read as parentheses, looks like a callout. A label
+// read more than once keeps its most confident box
+pub fn parse_callout_words(tsv: &str) -> Vec<CalloutLabel> {
+    let mut labels: Vec<CalloutLabel> = Vec::new();
+    for line in tsv.lines() {
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns.len() < 12 || columns[0] != "5" {
+            continue;
+        }
+        let label = columns[11].trim_matches(|c: char| !c.is_ascii_alphanumeric()).to_uppercase();
+        let confidence: f64 = columns[10].trim().parse().unwrap_or(-1.0);
+        if !is_callout(&label) || confidence < MIN_CONFIDENCE {
+            continue;
+        }
+        let number = |index: usize| columns[index].trim().parse().unwrap_or(0);
+        let callout = CalloutLabel { label, confidence, left: number(6), top: number(7), width: number(8), height: number(9) };
+        match labels.iter_mut().find(|existing| existing.label == callout.label) {
+            Some(existing) if existing.confidence < callout.confidence => *existing = callout,
+            Some(_) => {}
+            None => labels.push(callout),
+        }
+    }
+    labels.sort_by(|a, b| a.label.len().cmp(&b.label.len()).then_with(|| a.label.cmp(&b.label)));
+    labels
+}
+
+// "Figure 401", "Fig. 401" and "401" all name figure 401
+fn figure_key(figure: &str) -> String {
+    let trimmed = figure.trim();
+    let lowered = trimmed.to_ascii_lowercase();
+    let rest = FIGURE_WORDS.iter()
+        .find_map(|word| lowered.starts_with(word).then(|| &trimmed[word.len()..]))
+        .unwrap_or(trimmed);
+    rest.trim_start_matches(['.', ' ', '\t']).trim().to_uppercase()
+}
+
+// The callout-shaped token at `start` and its end, if any
+fn callout_at(text: &str, start: usize) -> Option<(usize, String)> {
+    let token: String = text[start..].chars().take_while(char::is_ascii_alphanumeric).collect();
+    is_callout(&token).then(|| (start + token.len(), token))
+}
+
+// Item numbers the text refers to, as (start, end, label): lists after an item word, and a
+// parenthesized callout inside a sentence - "(5)", "(5, Fig. 401)". A "(5)" that starts its
+// line is a list marker, not a reference
+fn item_references(text: &str) -> Vec<(usize, usize, String)> {
+    let mut references = Vec::new();
+    let lowered = text.to_ascii_lowercase();
+    for word in ITEM_WORDS {
+        let mut from = 0;
+        while let Some(found) = lowered[from..].find(word) {
+            let start = from + found;
+            from = start + word.len();
+            if lowered[..start].chars().next_back().is_some_and(char::is_alphanumeric) || !text[from..].starts_with([' ', '\t']) {
+                continue;
+            }
+            // "items 3, 4 and 5", "keys 2 & 7"
+            let mut position = from;
+            loop {
+                let rest = &text[position..];
+                let label_start = position + rest.len() - rest.trim_start_matches([' ', '\t']).len();
+                let Some((end, label)) = callout_at(text, label_start) else { break };
+                if text[end..].starts_with(|c: char| c.is_alphanumeric() || c == '.' && text[end + 1..].starts_with(|c: char| c.is_ascii_digit())) {
+                    break;
+                }
+                references.push((label_start, end, label));
+                let after = text[end..].trim_start_matches([' ', '\t']);
+                let separator = [",", "and ", "&", "or "].iter().find(|separator| after.to_ascii_lowercase().starts_with(**separator));
+                match separator {
+                    Some(separator) => position = text.len() - after.len() + separator.len(),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    let mut from = 0;
+    while let Some(found) = text[from..].find('(') {
+        let open = from + found;
+        from = open + 1;
+        let line_start = text[..open].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        if text[line_start..open].trim_matches(|c: char| c.is_whitespace() || c == '\x0c').is_empty() {
+            continue;
+        }
+        if let Some((end, label)) = callout_at(text, from) {
+            if text[end..].starts_with([')', ',']) {
+                references.push((from, end, label));
+            }
+        }
+    }
+
+    references.sort();
+    references.dedup_by_key(|(start, _, _)| *start);
+    references
+}
+
+// The figure a step's item numbers point into: one the step itself mentions, otherwise the
+// latest figure mentioned earlier in its procedure
+fn step_figure(mentions: &[(usize, usize, String, bool)], procedure_start: usize, (start, end): (usize, usize)) -> Option<String> {
+    let in_step = mentions.iter()
+        .filter(|(at, _, _, _)| *at >= start && *at < end)
+        .min_by_key(|(at, _, _, caption)| (*caption, *at));
+    let earlier = || mentions.iter().rev().find(|(at, _, _, _)| *at >= procedure_start && *at < start);
+    in_step.or_else(earlier).map(|(_, _, number, _)| number.clone())
+}
+
+fn callout_record(figure: &str, callout: Option<&CalloutLabel>, label: &str, status: &str) -> HashMap<String, String> {
+    let mut record = HashMap::new();
+    record.insert("figure".to_string(), figure.to_string());
+    record.insert("label".to_string(), label.to_string());
+    record.insert("status".to_string(), status.to_string());
+    if let Some(callout) = callout {
+        record.insert("confidence".to_string(), format!("{:.1}", callout.confidence));
+        record.insert("left".to_string(), callout.left.to_string());
+        record.insert("top".to_string(), callout.top.to_string());
+        record.insert("width".to_string(), callout.width.to_string());
+        record.insert("height".to_string(), callout.height.to_string());
+    }
+    record
+}
+
+// Item numbers in each step matched against the callouts read from the step's figure. Status
+// is "linked" when the figure shows the label, "not_found" when OCR did not read it there, and
+// "unreferenced" for a callout no step mentions. Figures without labels are not checked
+pub fn callout_records(
+    text: &str,
+    modules: &[HashMap<String, String>],
+    steps: &[HashMap<String, String>],
+    figures: &HashMap<String, Vec<CalloutLabel>>,
+) -> Vec<HashMap<String, String>> {
+    let figures: HashMap<String, &Vec<CalloutLabel>> = figures.iter().map(|(figure, labels)| (figure_key(figure), labels)).collect();
+    let mut module_extents = record_extents(modules, text.len());
+    if module_extents.is_empty() {
+        module_extents.push((0, text.len(), String::new()));
+    }
+    let mentions = figure_mentions(text);
+    let references = item_references(text);
+
+    let mut referenced: HashSet<(String, String)> = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut records = Vec::new();
+    for (step_start, step_end, step_id) in record_extents(steps, text.len()) {
+        let Some((module_start, _, module_id)) = module_extents.iter().rev().find(|(start, _, _)| *start <= step_start) else { continue };
+        let Some(figure) = step_figure(&mentions, *module_start, (step_start, step_end)) else { continue };
+        let Some(labels) = figures.get(&figure) else { continue };
+        for (start, end, label) in references.iter().filter(|(start, _, _)| *start >= step_start && *start < step_end) {
+            let record_id = stable_record_id("callout", &[module_id, &step_id, &figure, label]);
+            if !seen.insert(record_id.clone()) {
+                continue;
+            }
+            let callout = labels.iter().find(|callout| callout.label == *label);
+            let status = if callout.is_some() { "linked" } else { "not_found" };
+            referenced.insert((figure.clone(), label.clone()));
+            let mut record = callout_record(&figure, callout, label, status);
+            record.insert("record_id".to_string(), record_id);
+            record.insert("step_id".to_string(), step_id.clone());
+            record.insert("module_id".to_string(), module_id.clone());
+            record.insert("original_start".to_string(), start.to_string());
+            record.insert("original_end".to_string(), end.to_string());
+            records.push(record);
+        }
+    }
+
+    let mut figure_numbers: Vec<&String> = figures.keys().collect();
+    figure_numbers.sort();
+    for figure in figure_numbers {
+        for callout in figures[figure].iter().filter(|callout| !referenced.contains(&(figure.clone(), callout.label.clone()))) {
+            let mut record = callout_record(figure, Some(callout), &callout.label, "unreferenced");
+            record.insert("record_id".to_string(), stable_record_id("callout", &[figure, &callout.label]));
+            records.push(record);
+        }
+    }
+    records
+}
+
+// Python binding - figures maps a figure number ("401" or "Figure 401") to its extracted image,
+// OCR'd with the rules' callout tool (tesseract by default), or to saved `.tsv` OCR output
+#[pyfunction]
+#[pyo3(signature = (text, figures, rules_path=None, pages=None, sections=None))]
+pub fn link_figure_callouts(
+    py: Python,
+    text: &str,
+    figures: HashMap<String, String>,
+    rules_path: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path).map_err(to_py_err)?,
+            None => ExtractionEngine::new(),
+        };
+        let mut labels = HashMap::new();
+        for (figure, path) in figures {
+            let is_tsv = Path::new(&path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("tsv"));
+            let words = if is_tsv {
+                std::fs::read_to_string(&path).map_err(|e| to_py_err(format!("Cannot read {}: {}", path, e).into()))?
+            } else {
+                String::from_utf8_lossy(&engine.get_converters().callouts(&path).map_err(to_py_err)?.bytes).to_string()
+            };
+            labels.insert(figure, parse_callout_words(&words));
+        }
+        let text = scope_from_args(pages, sections)?.apply(&engine, text)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(engine.link_callouts(&text, &labels))
+    })
+}
@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::engine::convert::ConverterWarning;
+use crate::engine::encoding::{transcode, DetectedEncoding};
+use crate::engine::ids::stable_record_id;
+use crate::engine::ocr::OcrMetrics;
+use crate::engine::splitter::PAGE_BREAK;
+
+pub const INVALID_ENCODING: &str = "invalid_encoding";
+pub const NO_TEXT: &str = "no_text";
+pub const GARBLED: &str = "garbled";
+
+// Share of a page's visible characters that may be replacement, control or private-use
+// characters before the page counts as failed OCR
+const MAX_GARBLED_RATIO: f64 = 0.3;
+
+fn default_max_failed_page_ratio() -> f64 {
+    0.25
+}
+
+// How much of a document may fail before it yields no results at all
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorBudget {
+    #[serde(default = "default_max_failed_page_ratio")]
+    pub max_failed_page_ratio: f64,
+}
+
+impl Default for ErrorBudget {
+    fn default() -> Self {
+        Self { max_failed_page_ratio: default_max_failed_page_ratio() }
+    }
+}
+
+impl ErrorBudget {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.max_failed_page_ratio) {
+            return Err(format!("error_budget.max_failed_page_ratio must be between 0 and 1, got {}", self.max_failed_page_ratio));
+        }
+        Ok(())
+    }
+
+    // The stricter budget wins when rule packs are merged
+    pub fn merge(&mut self, other: &ErrorBudget) {
+        self.max_failed_page_ratio = self.max_failed_page_ratio.min(other.max_failed_page_ratio);
+    }
+
+    pub fn check(&self, report: &PageReport) -> Result<(), String> {
+        let failed = report.failures.len();
+        if report.page_count > 0 && failed as f64 / report.page_count as f64 > self.max_failed_page_ratio {
+            return Err(format!(
+                "{} of {} pages failed, over the error budget of {:.0}%",
+                failed, report.page_count, self.max_failed_page_ratio * 100.0
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PageFailure {
+    // 1-based page number in the source
+    pub page: usize,
+    pub reason: &'static str,
+    pub detail: String,
+    // Byte offset of the (now empty) page in the extracted text
+    pub start_offset: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PageReport {
+    pub page_count: usize,
+    pub failures: Vec<PageFailure>,
+    // Stderr of the converter that produced the text layer, if one ran
+    pub warnings: Vec<ConverterWarning>,
+    // What the text layer was decoded from; None for text that was never raw bytes
+    pub encoding: Option<DetectedEncoding>,
+    // Page latency when the text layer came from page-by-page OCR
+    pub ocr: Option<OcrMetrics>,
+}
+
+impl PageReport {
+    // Share of pages that yielded text
+    pub fn completeness(&self) -> f64 {
+        if self.page_count == 0 {
+            return 1.0;
+        }
+        1.0 - self.failures.len() as f64 / self.page_count as f64
+    }
+
+    // The failures of source pages first..=last, with offsets relative to `base_offset`
+    pub fn pages(&self, first: usize, last: usize, base_offset: usize) -> PageReport {
+        // The empty tail after a final form feed is not a page
+        let last = last.min(self.page_count);
+        PageReport {
+            page_count: (last + 1).saturating_sub(first),
+            failures: self.failures.iter()
+                .filter(|failure| failure.page >= first && failure.page <= last)
+                .map(|failure| PageFailure {
+                    page: failure.page,
+                    start_offset: failure.start_offset.saturating_sub(base_offset),
+                    ..failure.clone()
+                })
+                .collect(),
+            warnings: self.warnings.clone(),
+            encoding: self.encoding,
+            ocr: self.ocr.as_ref().map(|ocr| ocr.pages(first, last)),
+        }
+    }
+
+    pub fn failure_records(&self) -> Vec<HashMap<String, String>> {
+        self.failures.iter()
+            .map(|failure| {
+                let page = failure.page.to_string();
+                let mut record = HashMap::new();
+                record.insert("record_id".to_string(), stable_record_id("page_failure", &[page.as_str()]));
+                record.insert("page".to_string(), page);
+                record.insert("reason".to_string(), failure.reason.to_string());
+                record.insert("detail".to_string(), failure.detail.clone());
+                record.insert("original_start".to_string(), failure.start_offset.to_string());
+                record
+            })
+            .collect()
+    }
+
+    // Adds `completeness` and `page_failures` to an output payload, `encoding` when the text
+    // was decoded from bytes, `converter_warnings` when the converter had any and
+    // `ocr_metrics` when the pages were OCR'd
+    pub fn add_to(&self, payload: &mut Value, failure_records: Vec<HashMap<String, String>>) {
+        payload["completeness"] = json!((self.completeness() * 1000.0).round() / 1000.0);
+        payload["page_failures"] = json!(failure_records);
+        if let Some(encoding) = &self.encoding {
+            payload["encoding"] = json!(encoding.get_info());
+        }
+        if !self.warnings.is_empty() {
+            let warnings: Vec<HashMap<String, String>> = self.warnings.iter().map(ConverterWarning::get_info).collect();
+            payload["converter_warnings"] = json!(warnings);
+        }
+        if let Some(ocr) = &self.ocr {
+            payload["ocr_metrics"] = ocr.to_json();
+        }
+    }
+}
+
+fn is_garbage(c: char) -> bool {
+    c == char::REPLACEMENT_CHARACTER
+        || (c.is_control() && !c.is_whitespace())
+        || ('\u{E000}'..='\u{F8FF}').contains(&c)
+}
+
+pub(crate) fn page_problem(page: &str) -> Option<(&'static str, String)> {
+    let visible: Vec<char> = page.chars().filter(|c| !c.is_whitespace()).collect();
+    if visible.is_empty() {
+        return Some((NO_TEXT, "Page has no text - it may be a scan without an OCR layer".to_string()));
+    }
+    let garbage = visible.iter().filter(|c| is_garbage(**c)).count();
+    let ratio = garbage as f64 / visible.len() as f64;
+    if ratio >= MAX_GARBLED_RATIO {
+        return Some((GARBLED, format!("{:.0}% of the page's characters are unreadable", ratio * 100.0)));
+    }
+    None
+}
+
+// A text layer decoded page by page. Pages that fail are emptied, so page numbering and the
+// rest of the document survive; the failures say what was lost
+pub struct DecodedText {
+    pub text: String,
+    pub report: PageReport,
+}
+
+impl DecodedText {
+    // UTF-16 and Latin-1 are transcoded first, so pages split on the form feeds of the text
+    pub fn decode(bytes: &[u8]) -> Self {
+        profile_span!("decode");
+        let (bytes, encoding) = transcode(bytes);
+        let mut segments: Vec<&[u8]> = bytes.split(|b| *b == PAGE_BREAK as u8).collect();
+        // pdftotext ends every page with a form feed; the empty tail after it is not a page
+        let trailing_break = segments.len() > 1 && segments.last().is_some_and(|tail| tail.is_empty());
+        if trailing_break {
+            segments.pop();
+        }
+
+        let mut text = String::new();
+        let mut report = PageReport { page_count: segments.len(), encoding: Some(encoding), ..PageReport::default() };
+        for (index, segment) in segments.iter().enumerate() {
+            if index > 0 {
+                text.push(PAGE_BREAK);
+            }
+            let start_offset = text.len();
+            let failure = match std::str::from_utf8(segment) {
+                Ok(page) => match page_problem(page) {
+                    Some(problem) => Some(problem),
+                    None => {
+                        text.push_str(page);
+                        None
+                    }
+                },
+                Err(e) => Some((INVALID_ENCODING, format!("Not UTF-8 text (invalid byte at page offset {})", e.valid_up_to()))),
+            };
+            if let Some((reason, detail)) = failure {
+                report.failures.push(PageFailure { page: index + 1, reason, detail, start_offset });
+            }
+        }
+        if trailing_break {
+            text.push(PAGE_BREAK);
+        }
+        Self { text, report }
+    }
+
+    pub fn read(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        Ok(Self::decode(&bytes))
+    }
+}
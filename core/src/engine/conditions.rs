@@ -0,0 +1,170 @@
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+use crate::engine::entities::record_extents;
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::ids::stable_record_id;
+use crate::engine::scope::scope_from_args;
+
+// Subsection titles (compared without spaces, hyphens or numbering) and the condition kind they hold
+const CONDITION_HEADINGS: &[(&str, &str)] = &[
+    ("JOBSETUP", "precondition"),
+    ("JOBSETUPINFORMATION", "precondition"),
+    ("PREPARATION", "precondition"),
+    ("CLOSEOUT", "postcondition"),
+];
+
+// Titles that end a condition subsection
+const OTHER_HEADINGS: &[&str] = &[
+    "PROCEDURE", "REMOVAL", "INSTALLATION", "TEST", "INSPECTION", "REFERENCES", "GENERAL",
+    "TOOLS", "TOOLSANDEQUIPMENT", "SPECIALTOOLS", "CONSUMABLEMATERIALS", "EXPENDABLEPARTS",
+    "ZONES", "ACCESSPANELS", "MATERIALS",
+];
+
+// Item markers like "(1)", "(a)", "A.", "1.", "-", "•" - returns the text after the marker
+pub fn strip_item_marker(line: &str) -> Option<&str> {
+    for bullet in ["-", "•", "*", "–"] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            return Some(rest.trim_start());
+        }
+    }
+    if let Some(inner) = line.strip_prefix('(') {
+        let close = inner.find(')')?;
+        let label = &inner[..close];
+        if !label.is_empty() && label.len() <= 3 && label.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Some(inner[close + 1..].trim_start());
+        }
+        return None;
+    }
+    let dot = line.find(". ")?;
+    let label = &line[..dot];
+    if !label.is_empty() && label.len() <= 3 && label.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Some(line[dot + 2..].trim_start());
+    }
+    None
+}
+
+// Heading key with numbering and punctuation removed: "2. Job Set-Up" -> "JOBSETUP"
+fn heading_key(line: &str) -> String {
+    let title = strip_item_marker(line).unwrap_or(line);
+    title.chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+pub fn condition_kind(line: &str) -> Option<&'static str> {
+    let key = heading_key(line);
+    CONDITION_HEADINGS.iter().find(|(heading, _)| *heading == key).map(|(_, kind)| *kind)
+}
+
+pub fn is_other_heading(line: &str) -> bool {
+    let key = heading_key(line);
+    OTHER_HEADINGS.contains(&key.as_str()) || line.starts_with("TASK ")
+}
+
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub kind: &'static str,
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Items of every Job Set-Up / Close-Out subsection. Once a subsection uses item markers,
+// unmarked lines continue the item above; otherwise every line is an item
+pub fn find_conditions(text: &str) -> Vec<Condition> {
+    let mut conditions: Vec<Condition> = Vec::new();
+    let mut kind: Option<&'static str> = None;
+    let mut marked = false;
+    let mut offset = 0;
+
+    for raw in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += raw.len();
+        let line = raw.trim_matches(|c: char| c.is_whitespace() || c == '\x0c');
+        if line.is_empty() {
+            continue;
+        }
+        let start = line_start + raw.find(line).unwrap_or(0);
+        let end = start + line.len();
+
+        if let Some(heading) = condition_kind(line) {
+            kind = Some(heading);
+            marked = false;
+            continue;
+        }
+        if is_other_heading(line) {
+            kind = None;
+            continue;
+        }
+        let current = match kind {
+            Some(current) => current,
+            None => continue,
+        };
+        // Sub-task headers group items but are not conditions themselves
+        if line.starts_with("SUBTASK") {
+            continue;
+        }
+
+        match strip_item_marker(line) {
+            Some(item) => {
+                marked = true;
+                if !item.is_empty() {
+                    conditions.push(Condition { kind: current, text: item.to_string(), start: end - item.len(), end });
+                }
+            }
+            None if marked && conditions.last().is_some_and(|c| c.kind == current) => {
+                if let Some(previous) = conditions.last_mut() {
+                    previous.text = format!("{} {}", previous.text, line);
+                    previous.end = end;
+                }
+            }
+            None => conditions.push(Condition { kind: current, text: line.to_string(), start, end }),
+        }
+    }
+    conditions
+}
+
+// Condition records attached to the module whose extent contains them
+pub fn condition_records(text: &str, modules: &[HashMap<String, String>]) -> Vec<HashMap<String, String>> {
+    let extents = record_extents(modules, text.len());
+    find_conditions(text).into_iter()
+        .map(|condition| {
+            let module_id = extents.iter()
+                .find(|(start, end, _)| condition.start >= *start && condition.start < *end)
+                .map(|(_, _, id)| id.clone())
+                .unwrap_or_default();
+            let mut record = HashMap::new();
+            record.insert("record_id".to_string(), stable_record_id("condition", &[&module_id, condition.kind, &condition.text]));
+            record.insert("kind".to_string(), condition.kind.to_string());
+            record.insert("module_id".to_string(), module_id);
+            record.insert("text".to_string(), condition.text);
+            record.insert("original_start".to_string(), condition.start.to_string());
+            record.insert("original_end".to_string(), condition.end.to_string());
+            record
+        })
+        .collect()
+}
+
+// Python binding - preconditions from Job Set-Up, postconditions from Close-Out
+#[pyfunction]
+#[pyo3(signature = (text, rules_path=None, pages=None, sections=None))]
+pub fn extract_conditions(
+    py: Python,
+    text: &str,
+    rules_path: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => ExtractionEngine::new(),
+        };
+        let text = scope_from_args(pages, sections)?.apply(&engine, text)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(engine.extract_conditions(&text))
+    })
+}
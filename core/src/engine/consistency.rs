@@ -0,0 +1,257 @@
+use pyo3::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::engine::conditions::{condition_kind, is_other_heading, strip_item_marker};
+use crate::engine::entities::record_extents;
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::figures::figure_mentions;
+use crate::engine::ids::stable_record_id;
+use crate::engine::scope::scope_from_args;
+
+// Lines starting with these hold a warning or caution for the step after them
+const WARNING_WORDS: &[&str] = &["WARNING", "CAUTION"];
+
+// The check of a reference to a figure the document has no caption for
+pub const MISSING_FIGURE: &str = "missing_figure";
+
+// Single letters that are more likely roman numerals, unless the list is already just before them
+const ROMAN_LETTERS: &[u32] = &[9, 22, 24];
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub check: &'static str,
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+    // Check-specific fields, e.g. expected and found for a numbering gap
+    pub details: Vec<(&'static str, String)>,
+}
+
+// Non-empty lines as (start, end, text), trimmed like find_conditions trims them
+fn lines(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for raw in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += raw.len();
+        let line = raw.trim_matches(|c: char| c.is_whitespace() || c == '\x0c');
+        if !line.is_empty() {
+            let start = line_start + raw.find(line).unwrap_or(0);
+            lines.push((start, start + line.len(), line));
+        }
+    }
+    lines
+}
+
+fn is_heading(line: &str) -> bool {
+    condition_kind(line).is_some() || is_other_heading(line)
+}
+
+fn is_warning(line: &str) -> bool {
+    WARNING_WORDS.iter().any(|word| {
+        line.strip_prefix(word).is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric()))
+    })
+}
+
+// List style and position of an item marker: "(3)" is ("(1)", 3), "B." is ("A.", 2)
+fn item_label(line: &str) -> Option<(&'static str, u32)> {
+    strip_item_marker(line)?;
+    let (label, paren) = match line.strip_prefix('(') {
+        Some(inner) => (&inner[..inner.find(')')?], true),
+        None => (&line[..line.find(". ")?], false),
+    };
+    if let Ok(number) = label.parse::<u32>() {
+        return Some((if paren { "(1)" } else { "1." }, number));
+    }
+    let mut chars = label.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if paren && c.is_ascii_lowercase() => Some(("(a)", c as u32 - 'a' as u32 + 1)),
+        (Some(c), None) if !paren && c.is_ascii_uppercase() => Some(("A.", c as u32 - 'A' as u32 + 1)),
+        _ => None,
+    }
+}
+
+// "(1)" style at position 3 -> "(3)", "A." style at position 2 -> "B."
+fn format_label(style: &str, position: u32) -> String {
+    match style {
+        "(1)" => format!("({})", position),
+        "1." => format!("{}.", position),
+        "(a)" => format!("({})", char::from_u32('a' as u32 + position - 1).unwrap_or('?')),
+        _ => format!("{}.", char::from_u32('A' as u32 + position - 1).unwrap_or('?')),
+    }
+}
+
+fn procedure_findings(
+    text: &str,
+    lines: &[(usize, usize, &str)],
+    (start, end): (usize, usize),
+    step_starts: &[usize],
+    figures: &BTreeSet<String>,
+    mentions: &[(usize, usize, String, bool)],
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let lines: Vec<&(usize, usize, &str)> = lines.iter().filter(|(s, _, _)| *s >= start && *s < end).collect();
+
+    // Numbering: each list style keeps its own count, and an item 1 starts a new list
+    let mut last: HashMap<&'static str, u32> = HashMap::new();
+    for (line_start, line_end, line) in &lines {
+        let (style, position) = match item_label(line) {
+            Some(label) => label,
+            None => continue,
+        };
+        let previous = last.get(style).copied();
+        if style == "(a)" && ROMAN_LETTERS.contains(&position) && previous != Some(position - 1) {
+            continue;
+        }
+        match previous {
+            Some(previous) if position > previous + 1 => findings.push(Finding {
+                check: "step_numbering_gap",
+                message: format!("{} follows {} - expected {}", format_label(style, position), format_label(style, previous), format_label(style, previous + 1)),
+                start: *line_start,
+                end: *line_end,
+                details: vec![("expected", format_label(style, previous + 1)), ("found", format_label(style, position))],
+            }),
+            Some(previous) if position <= previous && position != 1 => findings.push(Finding {
+                check: "step_numbering_order",
+                message: format!("{} follows {}", format_label(style, position), format_label(style, previous)),
+                start: *line_start,
+                end: *line_end,
+                details: vec![("expected", format_label(style, previous + 1)), ("found", format_label(style, position))],
+            }),
+            _ => {}
+        }
+        last.insert(style, position);
+    }
+
+    // Close-Out puts back what Job Set-Up prepared, so one without the other is a gap
+    let has_set_up = lines.iter().any(|(_, _, line)| condition_kind(line) == Some("precondition"));
+    if !has_set_up {
+        if let Some((line_start, line_end, line)) = lines.iter().find(|(_, _, line)| condition_kind(line) == Some("postcondition")) {
+            findings.push(Finding {
+                check: "close_out_without_set_up",
+                message: format!("{:?} has no Job Set-Up section before it", line),
+                start: *line_start,
+                end: *line_end,
+                details: Vec::new(),
+            });
+        }
+    }
+
+    // A warning applies to the next step; none before the next heading leaves it hanging
+    for (index, (line_start, line_end, line)) in lines.iter().enumerate() {
+        if !is_warning(line) {
+            continue;
+        }
+        let boundary = lines[index + 1..].iter()
+            .find(|(_, _, next)| is_heading(next))
+            .map(|(next_start, _, _)| *next_start)
+            .unwrap_or(end);
+        let item_follows = lines[index + 1..].iter()
+            .take_while(|(next_start, _, _)| *next_start < boundary)
+            .any(|(_, _, next)| !is_warning(next) && strip_item_marker(next).is_some());
+        let step_follows = step_starts.iter().any(|step| *step > *line_end && *step < boundary);
+        if !item_follows && !step_follows {
+            let kind = WARNING_WORDS.iter().find(|word| line.starts_with(*word)).copied().unwrap_or("WARNING");
+            findings.push(Finding {
+                check: "unattached_warning",
+                message: format!("{} is not followed by a step before the next heading", kind),
+                start: *line_start,
+                end: *line_end,
+                details: vec![("text", line.to_string())],
+            });
+        }
+    }
+
+    for (mention_start, mention_end, number, caption) in mentions {
+        if *caption || *mention_start < start || *mention_start >= end || figures.contains(number) {
+            continue;
+        }
+        findings.push(Finding {
+            check: MISSING_FIGURE,
+            message: format!("{} refers to figure {}, which has no caption in the document", &text[*mention_start..*mention_end], number),
+            start: *mention_start,
+            end: *mention_end,
+            details: vec![("figure", number.clone())],
+        });
+    }
+
+    findings.sort_by_key(|finding| finding.start);
+    findings
+}
+
+// Numbers of the figures with a caption among `mentions`
+fn captioned(mentions: &[(usize, usize, String, bool)]) -> BTreeSet<String> {
+    mentions.iter().filter(|(_, _, _, caption)| *caption).map(|(_, _, number, _)| number.clone()).collect()
+}
+
+// Numbers of the figures `text` has a caption for
+pub fn figure_captions(text: &str) -> BTreeSet<String> {
+    captioned(&figure_mentions(text))
+}
+
+// Structural anomalies of each procedure - the extents of module matches, or the whole text
+// when there are none. Figures are looked up across the whole text, a caption being a line
+// that starts with "Figure <number>"
+pub fn find_inconsistencies(text: &str, modules: &[HashMap<String, String>], steps: &[HashMap<String, String>]) -> Vec<(String, Finding)> {
+    let mut extents = record_extents(modules, text.len());
+    if extents.is_empty() {
+        extents.push((0, text.len(), String::new()));
+    }
+    let lines = lines(text);
+    let step_starts: Vec<usize> = steps.iter().filter_map(|step| step.get("original_start")?.parse().ok()).collect();
+    let mentions = figure_mentions(text);
+    let figures = captioned(&mentions);
+
+    extents.iter()
+        .flat_map(|(start, end, module_id)| {
+            procedure_findings(text, &lines, (*start, *end), &step_starts, &figures, &mentions)
+                .into_iter()
+                .map(move |finding| (module_id.clone(), finding))
+        })
+        .collect()
+}
+
+// A finding is identified by where it is, as a procedure may have several of one check
+pub fn finding_record_id(module_id: &str, check: &str, start: usize) -> String {
+    stable_record_id("finding", &[module_id, check, &start.to_string()])
+}
+
+pub fn finding_records(text: &str, modules: &[HashMap<String, String>], steps: &[HashMap<String, String>]) -> Vec<HashMap<String, String>> {
+    find_inconsistencies(text, modules, steps).into_iter()
+        .map(|(module_id, finding)| {
+            let mut record = HashMap::new();
+            record.insert("record_id".to_string(), finding_record_id(&module_id, finding.check, finding.start));
+            record.insert("check".to_string(), finding.check.to_string());
+            record.insert("module_id".to_string(), module_id);
+            record.insert("message".to_string(), finding.message);
+            record.insert("original_start".to_string(), finding.start.to_string());
+            record.insert("original_end".to_string(), finding.end.to_string());
+            for (key, value) in finding.details {
+                record.insert(key.to_string(), value);
+            }
+            record
+        })
+        .collect()
+}
+
+// Python binding - quality findings per procedure
+#[pyfunction]
+#[pyo3(signature = (text, rules_path=None, pages=None, sections=None))]
+pub fn check_procedures(
+    py: Python,
+    text: &str,
+    rules_path: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => ExtractionEngine::new(),
+        };
+        let text = scope_from_args(pages, sections)?.apply(&engine, text)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(engine.check_procedures(&text))
+    })
+}
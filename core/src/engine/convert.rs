@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::engine::ids::stable_record_id;
+use crate::engine::ocr::{OcrConfig, OcrMetrics};
+use crate::security::sandbox::{run_sandboxed, SandboxLimits};
+
+// Replaced by the input's absolute path in a converter command
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+
+// Word boxes of a PDF's text layer, for highlight exports, when the profile names no layout tool
+pub const DEFAULT_LAYOUT_TOOL: &[&str] = &["pdftotext", "-bbox", "{input}", "-"];
+
+// Words of a figure image with their boxes, for callout linking, when the profile names no
+// callout tool. Sparse-text segmentation finds labels scattered across a drawing
+pub const DEFAULT_CALLOUT_TOOL: &[&str] = &["tesseract", "{input}", "stdout", "--psm", "11", "tsv"];
+
+// Passwords for encrypted PDFs read natively: the one initialize_engine set for the process, or
+// one a single call passes for its own thread. Encrypted manuals with only an owner password
+// open without either
+static DEFAULT_PDF_PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+
+thread_local! {
+    static CALL_PDF_PASSWORD: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// An encrypted PDF that neither the password given nor the empty user password opens
+#[derive(Debug)]
+pub struct PdfPasswordError {
+    pub path: String,
+    // Whether a password was given at all
+    pub supplied: bool,
+}
+
+impl std::fmt::Display for PdfPasswordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() { "PDF" } else { self.path.as_str() };
+        if self.supplied {
+            write!(f, "{}: the password is neither the user nor the owner password", path)
+        } else {
+            write!(f, "{}: the PDF is encrypted and needs a password", path)
+        }
+    }
+}
+
+impl std::error::Error for PdfPasswordError {}
+
+// Raised to Python as ml_core.PdfPasswordError, a ValueError. The macro tests a cfg pyo3's own
+// build script sets, unknown to this crate
+#[allow(unexpected_cfgs)]
+pub mod python {
+    pyo3::create_exception!(ml_core, PdfPasswordError, pyo3::exceptions::PyValueError);
+}
+
+// An error reading an input as a Python exception: PdfPasswordError for an encrypted PDF that
+// did not open, RuntimeError otherwise
+pub fn read_error(error: Box<dyn std::error::Error>) -> pyo3::PyErr {
+    match error.downcast_ref::<PdfPasswordError>() {
+        Some(error) => python::PdfPasswordError::new_err(error.to_string()),
+        None => pyo3::PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(error.to_string()),
+    }
+}
+
+pub fn set_default_pdf_password(password: Option<String>) {
+    if let Ok(mut default) = DEFAULT_PDF_PASSWORD.lock() {
+        *default = password;
+    }
+}
+
+// The password of the current call, else the process default
+pub fn pdf_password() -> Option<String> {
+    CALL_PDF_PASSWORD.with(|password| password.borrow().clone())
+        .or_else(|| DEFAULT_PDF_PASSWORD.lock().ok().and_then(|default| default.clone()))
+}
+
+// Runs `read` with `password` for the PDFs it opens on this thread; None keeps the default
+pub fn with_pdf_password<T>(password: Option<&str>, read: impl FnOnce() -> T) -> T {
+    struct Restore(Option<String>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CALL_PDF_PASSWORD.with(|password| *password.borrow_mut() = self.0.take());
+        }
+    }
+    let Some(password) = password else { return read() };
+    let _restore = Restore(CALL_PDF_PASSWORD.with(|current| current.replace(Some(password.to_string()))));
+    read()
+}
+
+// External tools that turn an input into a text layer, e.g.
+// "pdf": ["pdftotext", "-layout", "{input}", "-"] or "tif": ["tesseract", "{input}", "stdout"].
+// Tools write the text to stdout and run sandboxed. PDFs with no tool are read natively (see
+// pdf::text), any other input with no tool as text
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConverterConfig {
+    // Command per lower-case file extension
+    #[serde(default)]
+    pub tools: HashMap<String, Vec<String>>,
+    // Command writing a PDF's words with their boxes as `pdftotext -bbox` XHTML
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Vec<String>>,
+    // Command writing a figure image's words with their boxes as `tesseract ... tsv` output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callouts: Option<Vec<String>>,
+    // Scanned inputs OCR'd page by page in parallel, ahead of `tools`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ocr: Option<OcrConfig>,
+    #[serde(default)]
+    pub sandbox: SandboxLimits,
+}
+
+// One stderr line from a converter that still succeeded
+#[derive(Debug, Clone)]
+pub struct ConverterWarning {
+    pub source: String,
+    pub tool: String,
+    // 1-based stderr line
+    pub line: usize,
+    pub message: String,
+}
+
+impl ConverterWarning {
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let line = self.line.to_string();
+        let mut info = HashMap::new();
+        info.insert("record_id".to_string(), stable_record_id("converter_warning", &[self.source.as_str(), line.as_str()]));
+        info.insert("source".to_string(), self.source.clone());
+        info.insert("tool".to_string(), self.tool.clone());
+        info.insert("line".to_string(), line);
+        info.insert("message".to_string(), self.message.clone());
+        info
+    }
+}
+
+pub struct ConvertedText {
+    pub bytes: Vec<u8>,
+    pub warnings: Vec<ConverterWarning>,
+    // Set when the text came from page-by-page OCR
+    pub ocr: Option<OcrMetrics>,
+}
+
+impl ConverterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (extension, command) in &self.tools {
+            if extension.is_empty() || extension.starts_with('.') || *extension != extension.to_lowercase() {
+                return Err(format!("converters.tools key {:?} must be a lower-case extension without the dot", extension));
+            }
+            if command.is_empty() {
+                return Err(format!("converters.tools.{} is an empty command", extension));
+            }
+            if !command.iter().any(|arg| arg.contains(INPUT_PLACEHOLDER)) {
+                return Err(format!("converters.tools.{} never passes {} to {}", extension, INPUT_PLACEHOLDER, command[0]));
+            }
+        }
+        for (name, command) in [("layout", &self.layout), ("callouts", &self.callouts)] {
+            let Some(command) = command else { continue };
+            if command.is_empty() {
+                return Err(format!("converters.{} is an empty command", name));
+            }
+            if !command.iter().any(|arg| arg.contains(INPUT_PLACEHOLDER)) {
+                return Err(format!("converters.{} never passes {} to {}", name, INPUT_PLACEHOLDER, command[0]));
+            }
+        }
+        if let Some(ocr) = &self.ocr {
+            ocr.validate()?;
+        }
+        self.sandbox.validate()
+    }
+
+    // Tools from later packs win; the stricter sandbox limits win
+    pub fn merge(&mut self, other: &ConverterConfig) {
+        self.tools.extend(other.tools.clone());
+        if other.layout.is_some() {
+            self.layout = other.layout.clone();
+        }
+        if other.callouts.is_some() {
+            self.callouts = other.callouts.clone();
+        }
+        if other.ocr.is_some() {
+            self.ocr = other.ocr.clone();
+        }
+        self.sandbox.merge(&other.sandbox);
+    }
+
+    pub fn tool_for(&self, path: &str) -> Option<&Vec<String>> {
+        let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+        self.tools.get(&extension)
+    }
+
+    // None when no tool is configured for the input's extension and it is not a PDF this
+    // build reads itself
+    pub fn convert(&self, path: &str) -> Option<Result<ConvertedText, Box<dyn std::error::Error>>> {
+        if let Some(ocr) = self.ocr.as_ref().filter(|ocr| ocr.applies_to(path)) {
+            return Some(ocr.convert(path, &self.sandbox));
+        }
+        match self.tool_for(path) {
+            Some(command) => Some(self.run_tool(command, path)),
+            None => native_text(path),
+        }
+    }
+
+    // The word boxes of a PDF, from the profile's layout tool or pdftotext
+    pub fn layout(&self, path: &str) -> Result<ConvertedText, Box<dyn std::error::Error>> {
+        let default: Vec<String> = DEFAULT_LAYOUT_TOOL.iter().map(|arg| arg.to_string()).collect();
+        self.run_tool(self.layout.as_ref().unwrap_or(&default), path)
+    }
+
+    // The OCR words of a figure image, from the profile's callout tool or tesseract
+    pub fn callouts(&self, path: &str) -> Result<ConvertedText, Box<dyn std::error::Error>> {
+        let default: Vec<String> = DEFAULT_CALLOUT_TOOL.iter().map(|arg| arg.to_string()).collect();
+        self.run_tool(self.callouts.as_ref().unwrap_or(&default), path)
+    }
+
+    fn run_tool(&self, command: &[String], path: &str) -> Result<ConvertedText, Box<dyn std::error::Error>> {
+        profile_span!("converter", Path::new(&command[0]).file_name().unwrap_or_default().to_string_lossy());
+        let input = Path::new(path).canonicalize().map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let input = input.to_string_lossy();
+        let command: Vec<String> = command.iter().map(|arg| arg.replace(INPUT_PLACEHOLDER, &input)).collect();
+
+        // Each run gets an empty scratch directory as its working, home and temp directory
+        let work_dir = std::env::temp_dir().join(format!("spp-convert-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&work_dir)?;
+        let result = run_sandboxed(&command, &self.sandbox, &work_dir);
+        let _ = std::fs::remove_dir_all(&work_dir);
+
+        let output = result.map_err(|e| format!("{}: {}", path, e))?;
+        let warnings = output.stderr.into_iter()
+            .enumerate()
+            .map(|(index, message)| ConverterWarning {
+                source: path.to_string(),
+                tool: command[0].clone(),
+                line: index + 1,
+                message,
+            })
+            .collect();
+        Ok(ConvertedText { bytes: output.stdout, warnings, ocr: None })
+    }
+}
+
+// Whether a file starts with the PDF header, whatever its extension
+pub fn is_pdf(path: &str) -> bool {
+    use std::io::Read;
+    let mut header = [0u8; 5];
+    std::fs::File::open(path).and_then(|mut file| file.read_exact(&mut header)).is_ok() && header == *b"%PDF-"
+}
+
+#[cfg(feature = "figures")]
+fn native_text(path: &str) -> Option<Result<ConvertedText, Box<dyn std::error::Error>>> {
+    is_pdf(path).then(|| crate::pdf::text::read_text_layer(path))
+}
+
+#[cfg(not(feature = "figures"))]
+fn native_text(_path: &str) -> Option<Result<ConvertedText, Box<dyn std::error::Error>>> {
+    None
+}
@@ -0,0 +1,126 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+// How much of a file is looked at to recognise UTF-16 without a byte order mark
+pub const SNIFF_BYTES: usize = 4096;
+
+// Share of the high (or low) bytes of UTF-16 code units that must be zero for BOM-less text
+// to count as UTF-16 - mostly-ASCII text has a zero in every other byte
+const MIN_UTF16_ZERO_RATIO: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl TextEncoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Utf16Le => "utf-16le",
+            TextEncoding::Utf16Be => "utf-16be",
+            TextEncoding::Latin1 => "latin-1",
+        }
+    }
+}
+
+// What a text layer was read as. Without a byte order mark, anything but UTF-8 is a guess
+// from the bytes, and Latin-1 is what is left when nothing else fits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectedEncoding {
+    pub encoding: TextEncoding,
+    pub bom: bool,
+}
+
+impl DetectedEncoding {
+    pub fn is_guess(&self) -> bool {
+        !self.bom && self.encoding != TextEncoding::Utf8
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("encoding".to_string(), self.encoding.name().to_string());
+        info.insert("bom".to_string(), self.bom.to_string());
+        info
+    }
+}
+
+fn utf16_order(bytes: &[u8]) -> Option<TextEncoding> {
+    let sample = &bytes[..bytes.len().min(SNIFF_BYTES) & !1];
+    if sample.len() < 2 {
+        return None;
+    }
+    let units = sample.len() / 2;
+    let zeros = |parity: usize| sample.iter().skip(parity).step_by(2).filter(|b| **b == 0).count() as f64 / units as f64;
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd >= MIN_UTF16_ZERO_RATIO && even < MIN_UTF16_ZERO_RATIO / 10.0 {
+        Some(TextEncoding::Utf16Le)
+    } else if even >= MIN_UTF16_ZERO_RATIO && odd < MIN_UTF16_ZERO_RATIO / 10.0 {
+        Some(TextEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+pub fn detect_encoding(bytes: &[u8]) -> DetectedEncoding {
+    let bom = |encoding| DetectedEncoding { encoding, bom: true };
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return bom(TextEncoding::Utf8);
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return bom(TextEncoding::Utf16Le);
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return bom(TextEncoding::Utf16Be);
+    }
+
+    // UTF-16 first: ASCII in UTF-16 is also valid UTF-8, full of NULs
+    let encoding = match utf16_order(bytes) {
+        Some(encoding) => encoding,
+        None if std::str::from_utf8(bytes).is_ok() => TextEncoding::Utf8,
+        // Any valid multi-byte sequence means UTF-8 with damaged pages, not a legacy encoding -
+        // reading it as Latin-1 would mangle every other non-ASCII character
+        None if bytes.utf8_chunks().any(|chunk| !chunk.valid().is_ascii()) => TextEncoding::Utf8,
+        None => TextEncoding::Latin1,
+    };
+    DetectedEncoding { encoding, bom: false }
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    // Unpaired surrogates and an odd trailing byte become U+FFFD, which page checks count as garbled
+    let mut text: String = char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect();
+    if bytes.len() % 2 == 1 {
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+    text
+}
+
+// The bytes as UTF-8, without a byte order mark. UTF-8 input is passed through unchanged, so
+// invalid bytes still fail only their own page
+pub fn transcode(bytes: &[u8]) -> (Cow<'_, [u8]>, DetectedEncoding) {
+    let detected = detect_encoding(bytes);
+    let body = if detected.bom {
+        match detected.encoding {
+            TextEncoding::Utf8 => &bytes[3..],
+            _ => &bytes[2..],
+        }
+    } else {
+        bytes
+    };
+    (decode_as(body, detected.encoding), detected)
+}
+
+// Text in a known encoding as UTF-8 bytes - for a file detected once and read a page at a time
+pub fn decode_as(bytes: &[u8], encoding: TextEncoding) -> Cow<'_, [u8]> {
+    let text = match encoding {
+        TextEncoding::Utf8 => return Cow::Borrowed(bytes),
+        TextEncoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+        TextEncoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+        TextEncoding::Latin1 => bytes.iter().map(|b| char::from(*b)).collect(),
+    };
+    Cow::Owned(text.into_bytes())
+}
@@ -0,0 +1,259 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::ids::stable_record_id;
+use crate::engine::scope::scope_from_args;
+
+// Side and position qualifiers that belong to an equipment designator ("LH MLG actuator")
+pub const QUALIFIERS: &[&str] = &["LH", "RH", "L", "R", "LEFT", "RIGHT", "FWD", "AFT", "UPPER", "LOWER", "INBD", "OUTBD", "CTR", "CENTER"];
+pub const ZONE_WORDS: &[&str] = &["ZONE", "ZONES"];
+// Words that introduce circuit breaker numbers - "C/B" is matched separately
+pub const BREAKER_WORDS: &[&str] = &["CB", "CBS", "BREAKER", "BREAKERS"];
+// Station keywords and the reference axis they measure along
+pub const STATION_WORDS: &[(&str, &str)] = &[
+    ("STA", "fuselage"),
+    ("STATION", "fuselage"),
+    ("FS", "fuselage"),
+    ("BS", "body"),
+    ("WS", "wing"),
+    ("BL", "buttock"),
+    ("WL", "water"),
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntityConfig {
+    // Known names per entity type, e.g. {"equipment": ["MLG actuator", "PTU"]}
+    #[serde(default)]
+    pub gazetteer: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Entity {
+    pub entity_type: String,
+    pub text: String,
+    // Canonical form used for the record ID, e.g. "311AL" or "fuselage 540"
+    pub value: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Entity {
+    fn new(entity_type: &str, text: &str, value: String, start: usize, end: usize) -> Self {
+        Self { entity_type: entity_type.to_string(), text: text[start..end].to_string(), value, start, end }
+    }
+}
+
+fn is_number(word: &str) -> bool {
+    !word.is_empty() && word.chars().all(|c| c.is_ascii_digit() || c == '.') && word.chars().next().is_some_and(|c| c.is_ascii_digit())
+}
+
+// ATA access panels are a three-digit zone followed by two or three letters, e.g. 311AL, 571CB
+fn is_access_panel(word: &str) -> bool {
+    if !word.is_ascii() {
+        return false;
+    }
+    let (digits, letters) = word.split_at(word.len().min(3));
+    word.len() <= 6
+        && digits.len() == 3
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && (2..=3).contains(&letters.len())
+        && letters.chars().all(|c| c.is_ascii_uppercase())
+}
+
+fn is_zone_number(word: &str) -> bool {
+    word.len() == 3 && word.chars().all(|c| c.is_ascii_digit())
+}
+
+// Breaker numbers such as C01, 4C12 or 2345
+fn is_breaker_number(word: &str) -> bool {
+    word.len() <= 10
+        && word.chars().any(|c| c.is_ascii_digit())
+        && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn is_breaker_keyword(text: &str, words: &[(usize, &str)], index: usize) -> bool {
+    let (start, word) = words[index];
+    if BREAKER_WORDS.contains(&word.to_uppercase().as_str()) {
+        return true;
+    }
+    // "C/B" splits into "C" and "B" around the slash
+    let end = start + word.len();
+    word.eq_ignore_ascii_case("c")
+        && text[end..].starts_with('/')
+        && words.get(index + 1).is_some_and(|&(s, w)| s == end + 1 && w.eq_ignore_ascii_case("b"))
+}
+
+// Words of a list such as "C01, C02 and C03" - a sentence end or line break ends it
+fn list_items<'a>(text: &str, words: &[(usize, &'a str)], accept: fn(&str) -> bool) -> Vec<(usize, &'a str)> {
+    let mut items = Vec::new();
+    let mut previous_end: Option<usize> = None;
+    for &(start, word) in words {
+        let broken = previous_end.is_some_and(|end| text[end..start].contains(['\n', '.', ';', ':']));
+        if broken || !(accept(word) || word.eq_ignore_ascii_case("and")) {
+            break;
+        }
+        if accept(word) {
+            items.push((start, word));
+        }
+        previous_end = Some(start + word.len());
+    }
+    items
+}
+
+fn rule_entities(text: &str, words: &[(usize, &str)]) -> Vec<Entity> {
+    let mut entities = Vec::new();
+    for (index, &(start, word)) in words.iter().enumerate() {
+        let upper = word.to_uppercase();
+        let next = words.get(index + 1);
+
+        if is_breaker_keyword(text, words, index) {
+            // "C/B" consumes the "B" as well, and lists like "CB C01 and C02" yield one entity each
+            let skip = if word.eq_ignore_ascii_case("c") { 2 } else { 1 };
+            for (number_start, number) in list_items(text, &words[index + skip..], is_breaker_number) {
+                entities.push(Entity::new("circuit_breaker", text, number.to_uppercase(), number_start, number_start + number.len()));
+            }
+        } else if is_access_panel(word) {
+            entities.push(Entity::new("access_panel", text, word.to_string(), start, start + word.len()));
+        } else if ZONE_WORDS.contains(&upper.as_str()) {
+            // "ZONE 311" and lists such as "ZONES 311 and 312"
+            for (number_start, number) in list_items(text, &words[index + 1..], is_zone_number) {
+                entities.push(Entity::new("zone", text, number.to_string(), number_start, number_start + number.len()));
+            }
+        } else if let Some((_, axis)) = STATION_WORDS.iter().find(|(keyword, _)| *keyword == upper) {
+            if let Some(&(number_start, number)) = next.filter(|(_, w)| is_number(w)) {
+                entities.push(Entity::new("station", text, format!("{} {}", axis, number), start, number_start + number.len()));
+            }
+        }
+    }
+    entities
+}
+
+fn gazetteer_entities(text: &str, words: &[(usize, &str)], config: &EntityConfig) -> Vec<Entity> {
+    // ASCII folding keeps byte offsets aligned with the original text
+    let lowered = text.to_ascii_lowercase();
+    let mut entities = Vec::new();
+    for (entity_type, terms) in &config.gazetteer {
+        for term in terms {
+            let needle = term.to_ascii_lowercase();
+            if needle.is_empty() {
+                continue;
+            }
+            for (mut start, matched) in lowered.match_indices(&needle) {
+                let end = start + matched.len();
+                let whole_word = words.iter().any(|&(s, _)| s == start) && words.iter().any(|&(s, w)| s + w.len() == end);
+                if !whole_word {
+                    continue;
+                }
+                // Pull preceding qualifiers into the designator
+                let mut position = words.iter().position(|&(s, _)| s == start).unwrap_or(0);
+                while position > 0 && QUALIFIERS.contains(&words[position - 1].1.to_uppercase().as_str()) {
+                    position -= 1;
+                    start = words[position].0;
+                }
+                let value = text[start..end].split_whitespace().collect::<Vec<_>>().join(" ").to_uppercase();
+                entities.push(Entity::new(entity_type, text, value, start, end));
+            }
+        }
+    }
+    entities
+}
+
+pub fn find_entities(text: &str, config: &EntityConfig) -> Vec<Entity> {
+    let words: Vec<(usize, &str)> = text.unicode_word_indices().collect();
+    let mut entities = rule_entities(text, &words);
+    entities.extend(gazetteer_entities(text, &words, config));
+    entities.sort_by_key(|e| (e.start, std::cmp::Reverse(e.end)));
+    // Keep the longest entity where spans overlap
+    let mut kept: Vec<Entity> = Vec::new();
+    for entity in entities {
+        if kept.last().is_none_or(|last| entity.start >= last.end) {
+            kept.push(entity);
+        }
+    }
+    kept
+}
+
+// A step (or module) covers the text from its match to the next one's match
+pub fn record_extents(records: &[HashMap<String, String>], text_len: usize) -> Vec<(usize, usize, String)> {
+    let mut starts: Vec<(usize, String)> = records.iter()
+        .filter_map(|record| {
+            let start = record.get("original_start")?.parse().ok()?;
+            Some((start, record.get("record_id")?.clone()))
+        })
+        .collect();
+    starts.sort();
+    starts.iter().enumerate()
+        .map(|(index, (start, id))| {
+            let end = starts.get(index + 1).map(|(next, _)| *next).unwrap_or(text_len);
+            (*start, end, id.clone())
+        })
+        .collect()
+}
+
+// Typed entity records, each linked to the steps whose text mentions it
+pub fn entity_records(text: &str, config: &EntityConfig, steps: &[HashMap<String, String>]) -> Vec<HashMap<String, String>> {
+    let extents = record_extents(steps, text.len());
+    let mut records: Vec<HashMap<String, String>> = Vec::new();
+    let mut index_by_id: HashMap<String, usize> = HashMap::new();
+
+    for entity in find_entities(text, config) {
+        let record_id = stable_record_id("entity", &[&entity.entity_type, &entity.value]);
+        let step_ids: Vec<&str> = extents.iter()
+            .filter(|(start, end, _)| entity.start >= *start && entity.start < *end)
+            .map(|(_, _, id)| id.as_str())
+            .collect();
+
+        // One record per distinct entity; later mentions add their steps
+        if let Some(&existing) = index_by_id.get(&record_id) {
+            let record = &mut records[existing];
+            let mut linked: Vec<String> = record["step_ids"].split(',').filter(|s| !s.is_empty()).map(str::to_string).collect();
+            for id in step_ids {
+                if !linked.iter().any(|l| l == id) {
+                    linked.push(id.to_string());
+                }
+            }
+            record.insert("step_ids".to_string(), linked.join(","));
+            let mentions: usize = record["mentions"].parse().unwrap_or(1);
+            record.insert("mentions".to_string(), (mentions + 1).to_string());
+            continue;
+        }
+
+        let mut record = HashMap::new();
+        record.insert("record_id".to_string(), record_id.clone());
+        record.insert("entity_type".to_string(), entity.entity_type);
+        record.insert("text".to_string(), entity.text);
+        record.insert("value".to_string(), entity.value);
+        record.insert("original_start".to_string(), entity.start.to_string());
+        record.insert("original_end".to_string(), entity.end.to_string());
+        record.insert("step_ids".to_string(), step_ids.join(","));
+        record.insert("mentions".to_string(), "1".to_string());
+        index_by_id.insert(record_id, records.len());
+        records.push(record);
+    }
+    records
+}
+
+// Python binding - uses the rules file's gazetteer and steps when one is given
+#[pyfunction]
+#[pyo3(signature = (text, rules_path=None, pages=None, sections=None))]
+pub fn extract_entities(
+    py: Python,
+    text: &str,
+    rules_path: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => ExtractionEngine::new(),
+        };
+        let text = scope_from_args(pages, sections)?.apply(&engine, text)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(engine.extract_entities(&text))
+    })
+}
@@ -1,94 +1,754 @@
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+use crate::engine::access::{AccessConfig, AccessMap};
+#[cfg(feature = "flows")]
+use crate::engine::aggregate::procedure_records;
+use crate::engine::completeness::{DecodedText, ErrorBudget};
+use crate::engine::convert::{is_pdf, read_error, set_default_pdf_password, with_pdf_password, ConverterConfig};
+#[cfg(feature = "flows")]
+use crate::engine::callouts::{callout_records, CalloutLabel};
+#[cfg(feature = "flows")]
+use crate::engine::conditions::condition_records;
+#[cfg(feature = "flows")]
+use crate::engine::consistency::finding_records;
+use crate::engine::entities::{entity_records, EntityConfig};
+#[cfg(feature = "flows")]
+use crate::engine::entities::find_entities;
+use crate::engine::fuzzy::{find_fuzzy_match, FuzzyConfig};
+use crate::engine::ids::{stable_record_id, IdNamespace};
+use crate::engine::language::tag_languages;
+use crate::engine::normalize::{normalize, NormalizationConfig};
+#[cfg(feature = "flows")]
+use crate::engine::parameters::parameter_records;
+use crate::engine::profile::resolve_profile;
+use crate::engine::pii::{pii_records, redact_records, PiiConfig};
+use crate::engine::scope::scope_from_args;
+use crate::engine::segment::is_grapheme_boundary;
+use crate::engine::taxonomy::TaxonomySchema;
+use crate::engine::stream::ExtractionStream;
+#[cfg(feature = "tables")]
+use crate::engine::tables::{limit_records, table_records};
+#[cfg(feature = "figures")]
+use crate::export::images::export_figures;
+#[cfg(feature = "figures")]
+use crate::pdf::document::PdfDocument;
+#[cfg(feature = "figures")]
+use crate::pdf::forms::{read_form_fields, FormField};
+use crate::pdf::layout::LayoutConfig;
+use crate::schema::overflow::TruncationConfig;
+use crate::security::seal::DeliveryConfig;
+
+const DEFAULT_PROFILE_NAME: &str = "default";
+const UNVERSIONED_RULES: &str = "unversioned";
+
+// Record fields naming the rule pack ("ata32@0.4") and rule alias that produced a record
+pub const RULE_PACK_FIELD: &str = "rule_pack";
+pub const RULE_FIELD: &str = "rule";
+
+fn default_profile_name() -> String {
+    DEFAULT_PROFILE_NAME.to_string()
+}
+
+fn default_rules_version() -> String {
+    UNVERSIONED_RULES.to_string()
+}
+
+// Single matching primitive - extraction and pattern simulation must agree exactly
+pub fn find_matches(pattern: &str, text: &str) -> Vec<(usize, usize)> {
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    text.match_indices(pattern)
+        .map(|(start, matched)| (start, start + matched.len()))
+        .filter(|&(start, end)| is_grapheme_boundary(text, start) && is_grapheme_boundary(text, end))
+        .collect()
+}
 
 // Core extraction engine - looks like normal ML pipeline code
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtractionEngine {
+    #[serde(default = "default_profile_name")]
+    name: String,
+    #[serde(default = "default_rules_version")]
+    version: String,
+    #[serde(default)]
+    normalization: NormalizationConfig,
+    #[serde(default)]
+    fuzzy: FuzzyConfig,
+    #[serde(default)]
+    layout: LayoutConfig,
+    #[serde(default)]
+    entities: EntityConfig,
+    #[serde(default)]
+    access: AccessConfig,
+    #[serde(default)]
+    pii: PiiConfig,
+    #[serde(default)]
+    error_budget: ErrorBudget,
+    #[serde(default)]
+    converters: ConverterConfig,
+    #[serde(default)]
+    delivery: DeliveryConfig,
+    #[serde(default)]
+    ids: IdNamespace,
+    #[serde(default)]
+    truncation: TruncationConfig,
+    // Levels, labels and descriptions of the taxonomy UIs offer; patterns do not use it
+    #[serde(default)]
+    taxonomy: TaxonomySchema,
     patterns: HashMap<String, Vec<String>>,
     prompts: HashMap<String, String>,
+    // JSON Schema per prompt name - outputs are constrained to it and parsed into records
+    #[serde(default)]
+    prompt_schemas: HashMap<String, serde_json::Value>,
+    // Minimum record confidence per category; records below it are dropped
     thresholds: HashMap<String, f64>,
+    // Names for patterns, per category: {"step": {"Remove the": "step-remove"}}. Records carry
+    // the alias, so disputes can name a rule without quoting it
+    #[serde(default)]
+    rule_aliases: HashMap<String, HashMap<String, String>>,
+    // Category -> pattern -> (pack, alias), kept across merges
+    #[serde(skip)]
+    lineage: HashMap<String, HashMap<String, (String, String)>>,
+    // A step whose text runs on into the start of the text extracted next, as a stream's
+    // window continues the one before it
+    #[serde(skip)]
+    continued_step: Option<String>,
+}
+
+impl Default for ExtractionEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ExtractionEngine {
     pub fn new() -> Self {
         Self {
+            name: default_profile_name(),
+            version: default_rules_version(),
+            normalization: NormalizationConfig::default(),
+            fuzzy: FuzzyConfig::default(),
+            layout: LayoutConfig::default(),
+            entities: EntityConfig::default(),
+            access: AccessConfig::default(),
+            pii: PiiConfig::default(),
+            error_budget: ErrorBudget::default(),
+            converters: ConverterConfig::default(),
+            delivery: DeliveryConfig::default(),
+            ids: IdNamespace::default(),
+            truncation: TruncationConfig::default(),
+            taxonomy: TaxonomySchema::default(),
             patterns: HashMap::new(),
             prompts: HashMap::new(),
+            prompt_schemas: HashMap::new(),
             thresholds: HashMap::new(),
+            rule_aliases: HashMap::new(),
+            lineage: HashMap::new(),
+            continued_step: None,
         }
     }
 
+    // Aliases must name patterns of their category, once each
+    fn validate_aliases(&self) -> Result<(), String> {
+        for (category, aliases) in &self.rule_aliases {
+            let patterns = self.patterns.get(category).map(Vec::as_slice).unwrap_or_default();
+            let mut seen = std::collections::HashSet::new();
+            for (pattern, alias) in aliases {
+                if !patterns.contains(pattern) {
+                    return Err(format!("rule_aliases.{} names {:?}, which is not one of its patterns", category, pattern));
+                }
+                if alias.trim().is_empty() || !seen.insert(alias.as_str()) {
+                    return Err(format!("rule_aliases.{} alias {:?} is empty or used twice", category, alias));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_thresholds(&self) -> Result<(), String> {
+        match self.thresholds.iter().find(|(_, threshold)| !(0.0..=1.0).contains(*threshold)) {
+            Some((category, threshold)) => Err(format!("thresholds.{} must be between 0 and 1, got {}", category, threshold)),
+            None => Ok(()),
+        }
+    }
+
+    // Each pattern's pack and alias; unnamed patterns are "<category>-<position>" in their pack
+    fn pattern_lineage(&self) -> HashMap<String, HashMap<String, (String, String)>> {
+        let pack = format!("{}@{}", self.name, self.version);
+        self.patterns.iter()
+            .map(|(category, patterns)| {
+                let aliases = self.rule_aliases.get(category);
+                let lineage = patterns.iter().enumerate()
+                    .map(|(index, pattern)| {
+                        let alias = aliases.and_then(|aliases| aliases.get(pattern)).cloned()
+                            .unwrap_or_else(|| format!("{}-{}", category, index + 1));
+                        (pattern.clone(), (pack.clone(), alias))
+                    })
+                    .collect();
+                (category.clone(), lineage)
+            })
+            .collect()
+    }
+
     pub fn load_config(&mut self, config_data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
-        // This looks like normal config loading, but actually decrypts
+        // Rules payloads are plain JSON - there is no decryption step, so a bad payload fails
+        // here as a JSON error or in one of the section checks below
         let config: ExtractionEngine = serde_json::from_slice(config_data)?;
+        config.access.validate()?;
+        config.pii.validate()?;
+        config.error_budget.validate()?;
+        config.converters.validate()?;
+        config.delivery.validate()?;
+        config.ids.validate()?;
+        config.truncation.validate()?;
+        config.fuzzy.validate()?;
+        config.layout.validate()?;
+        config.taxonomy.validate()?;
+        config.validate_aliases()?;
+        config.validate_thresholds()?;
+        self.lineage = config.pattern_lineage();
+        self.name = config.name;
+        self.version = config.version;
+        self.normalization = config.normalization;
+        self.fuzzy = config.fuzzy;
+        self.layout = config.layout;
+        self.entities = config.entities;
+        self.access = config.access;
+        self.pii = config.pii;
+        self.error_budget = config.error_budget;
+        self.converters = config.converters;
+        self.delivery = config.delivery;
+        self.ids = config.ids;
+        self.truncation = config.truncation;
+        self.taxonomy = config.taxonomy;
         self.patterns = config.patterns;
         self.prompts = config.prompts;
+        self.prompt_schemas = config.prompt_schemas;
         self.thresholds = config.thresholds;
+        self.rule_aliases = config.rule_aliases;
         Ok(())
     }
 
-    pub fn extract_modules(&self, text: &str) -> Vec<HashMap<String, String>> {
-        let mut modules = Vec::new();
-        
-        if let Some(patterns) = self.patterns.get("module") {
+    // Loads a rules file with the base profiles it extends merged in
+    pub fn from_rules_file(rules_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let resolved = resolve_profile(rules_path)?;
+        let mut engine = Self::new();
+        engine.load_config(&serde_json::to_vec(&resolved.config)?)?;
+        Ok(engine)
+    }
+
+    pub fn merge(&mut self, other: ExtractionEngine) {
+        // Merged engines report every contributing pack, e.g. "core@1.2+ata32@0.4"
+        let pack = format!("{}@{}", other.name, other.version);
+        if self.version == UNVERSIONED_RULES {
+            self.name = other.name.clone();
+            self.version = pack;
+        } else {
+            self.name = format!("{}+{}", self.name, other.name);
+            self.version = format!("{}+{}", self.version, pack);
+        }
+
+        // Combine rule packs - patterns accumulate, prompts/thresholds from later packs win
+        for (category, patterns) in other.patterns {
+            let entry = self.patterns.entry(category).or_default();
             for pattern in patterns {
-                // Normal pattern matching logic
-                if text.contains(pattern) {
-                    let mut module = HashMap::new();
-                    module.insert("pattern".to_string(), pattern.clone());
-                    module.insert("confidence".to_string(), "0.95".to_string());
-                    modules.push(module);
+                if !entry.contains(&pattern) {
+                    entry.push(pattern);
                 }
             }
         }
-        
-        modules
+        // A pattern in several packs keeps the lineage of the first, like its position
+        for (category, lineage) in other.lineage {
+            let entry = self.lineage.entry(category).or_default();
+            for (pattern, origin) in lineage {
+                entry.entry(pattern).or_insert(origin);
+            }
+        }
+        for (category, aliases) in other.rule_aliases {
+            let entry = self.rule_aliases.entry(category).or_default();
+            for (pattern, alias) in aliases {
+                entry.entry(pattern).or_insert(alias);
+            }
+        }
+        self.prompts.extend(other.prompts);
+        self.prompt_schemas.extend(other.prompt_schemas);
+        for (entity_type, terms) in other.entities.gazetteer {
+            let entry = self.entities.gazetteer.entry(entity_type).or_default();
+            for term in terms {
+                if !entry.contains(&term) {
+                    entry.push(term);
+                }
+            }
+        }
+        for tag in other.access.default_tags {
+            if !self.access.default_tags.contains(&tag) {
+                self.access.default_tags.push(tag);
+            }
+        }
+        self.access.sections.extend(other.access.sections);
+        self.pii.merge(other.pii);
+        self.error_budget.merge(&other.error_budget);
+        self.converters.merge(&other.converters);
+        self.delivery.merge(&other.delivery);
+        self.ids.merge(&other.ids);
+        self.truncation.merge(&other.truncation);
+        self.taxonomy.merge(&other.taxonomy);
+        self.thresholds.extend(other.thresholds);
     }
 
-    pub fn extract_steps(&self, text: &str) -> Vec<HashMap<String, String>> {
-        let mut steps = Vec::new();
-        
-        if let Some(patterns) = self.patterns.get("step") {
+    pub fn get_normalization(&self) -> &NormalizationConfig {
+        &self.normalization
+    }
+
+    pub fn get_fuzzy(&self) -> &FuzzyConfig {
+        &self.fuzzy
+    }
+
+    pub fn get_layout(&self) -> &LayoutConfig {
+        &self.layout
+    }
+
+    pub fn get_thresholds(&self) -> &HashMap<String, f64> {
+        &self.thresholds
+    }
+
+    // Patterns run on normalized text; records report offsets in both coordinate spaces.
+    // Exact matches win - the fuzzy layer only runs for patterns that did not match exactly
+    fn extract_records(&self, category: &str, confidence: f64, text: &str) -> Vec<HashMap<String, String>> {
+        profile_span!("patterns", category);
+        let mut records = Vec::new();
+        let min_confidence = self.thresholds.get(category).copied().unwrap_or(0.0);
+
+        if let Some(patterns) = self.patterns.get(category) {
+            let normalized = normalize(text, &self.normalization);
             for pattern in patterns {
-                if text.contains(pattern) {
-                    let mut step = HashMap::new();
-                    step.insert("pattern".to_string(), pattern.clone());
-                    step.insert("confidence".to_string(), "0.90".to_string());
-                    steps.push(step);
+                let normalized_pattern = normalize(pattern, &self.normalization);
+                let exact = find_matches(&normalized_pattern.text, &normalized.text).first()
+                    .map(|&(start, end)| (start, end, 0));
+                let found = exact.or_else(|| {
+                    if self.fuzzy.applies_to(&normalized_pattern.text) {
+                        find_fuzzy_match(&normalized_pattern.text, &normalized.text, self.fuzzy.max_distance)
+                    } else {
+                        None
+                    }
+                });
+
+                if let Some((start, end, distance)) = found {
+                    let confidence = self.fuzzy.penalize(confidence, distance);
+                    // Compared as records show it, so a threshold of 0.85 keeps a record of 0.85
+                    if (confidence * 100.0).round() / 100.0 < min_confidence - f64::EPSILON {
+                        continue;
+                    }
+                    let mut record = HashMap::new();
+                    record.insert("record_id".to_string(), stable_record_id(category, &[pattern]));
+                    record.insert("pattern".to_string(), pattern.clone());
+                    if let Some((pack, alias)) = self.lineage.get(category).and_then(|lineage| lineage.get(pattern)) {
+                        record.insert(RULE_PACK_FIELD.to_string(), pack.clone());
+                        record.insert(RULE_FIELD.to_string(), alias.clone());
+                    }
+                    record.insert("confidence".to_string(), format!("{:.2}", confidence));
+                    record.insert("start".to_string(), start.to_string());
+                    record.insert("end".to_string(), end.to_string());
+                    record.insert("original_start".to_string(), normalized.to_original(start).to_string());
+                    record.insert("original_end".to_string(), normalized.to_original(end).to_string());
+                    if distance > 0 {
+                        record.insert("match_type".to_string(), "fuzzy".to_string());
+                        record.insert("edit_distance".to_string(), distance.to_string());
+                        record.insert("matched_text".to_string(), normalized.text[start..end].to_string());
+                    }
+                    records.push(record);
                 }
             }
         }
-        
+
+        records
+    }
+
+    pub fn find_modules(&self, text: &str) -> Vec<HashMap<String, String>> {
+        // Normal pattern matching logic
+        self.extract_records("module", 0.95, text)
+    }
+
+    fn find_steps(&self, text: &str) -> Vec<HashMap<String, String>> {
+        self.extract_records("step", 0.90, text)
+    }
+
+    // Drops the patterns of `records` from `category`, so they match no later text. A pattern
+    // gives one record, at its first match, so text after that match has no record of it
+    pub(crate) fn retire_patterns(&mut self, category: &str, records: &[HashMap<String, String>]) {
+        let matched: HashSet<&str> = records.iter().filter_map(|record| record.get("pattern")).map(String::as_str).collect();
+        if let Some(patterns) = self.patterns.get_mut(category) {
+            patterns.retain(|pattern| !matched.contains(pattern.as_str()));
+        }
+    }
+
+    pub(crate) fn continue_step(&mut self, step_id: Option<String>) {
+        self.continued_step = step_id;
+    }
+
+    // Steps that text is linked to by where it is: those of `text`, and from its start the
+    // continued step until the first of them
+    fn linked_steps(&self, text: &str) -> Vec<HashMap<String, String>> {
+        let mut steps = self.find_steps(text);
+        if let Some(step_id) = &self.continued_step {
+            steps.push(HashMap::from([("record_id".to_string(), step_id.clone()), ("original_start".to_string(), "0".to_string())]));
+        }
         steps
     }
 
+    // The profile's record ID namespace, leaving the engine's records un-namespaced
+    pub(crate) fn take_ids(&mut self) -> IdNamespace {
+        std::mem::take(&mut self.ids)
+    }
+
+    // Adds the profile's access tags to records extracted from `text`. Records from other
+    // sources (LLM results) are tagged by their original_start or start_offset
+    pub fn tag_access(&self, text: &str, records: &mut [HashMap<String, String>]) {
+        if self.access.is_empty() {
+            return;
+        }
+        AccessMap::new(&self.access, &self.find_modules(text), &self.find_steps(text), text.len()).tag(records);
+    }
+
+    // Module extents of `text` with their access tags
+    pub fn access_map(&self, text: &str) -> AccessMap<'_> {
+        AccessMap::new(&self.access, &self.find_modules(text), &self.find_steps(text), text.len())
+    }
+
+    // Replaces the PII found in `text` wherever it shows up in the records, when the profile
+    // asks for redaction
+    pub fn redact_pii(&self, text: &str, records: &mut [HashMap<String, String>]) {
+        if self.pii.redact {
+            redact_records(text, &self.pii, records);
+        }
+    }
+
+    // Prefixes record IDs and the references between records with the profile's namespace
+    pub fn namespace_ids(&self, records: &mut [HashMap<String, String>]) {
+        self.ids.apply_records(records);
+    }
+
+    // Records that no single pattern produced carry the rules version of the whole engine
+    fn tagged(&self, text: &str, mut records: Vec<HashMap<String, String>>) -> Vec<HashMap<String, String>> {
+        profile_span!("tag_records");
+        let pack = format!("{}@{}", self.name, self.version);
+        for record in records.iter_mut() {
+            record.entry(RULE_PACK_FIELD.to_string()).or_insert_with(|| pack.clone());
+        }
+        self.tag_access(text, &mut records);
+        tag_languages(text, &mut records);
+        self.redact_pii(text, &mut records);
+        self.namespace_ids(&mut records);
+        records
+    }
+
+    pub fn extract_modules(&self, text: &str) -> Vec<HashMap<String, String>> {
+        self.tagged(text, self.find_modules(text))
+    }
+
+    pub fn extract_steps(&self, text: &str) -> Vec<HashMap<String, String>> {
+        self.tagged(text, self.find_steps(text))
+    }
+
+    // Equipment, zone, access panel and station entities, linked to the steps mentioning them
+    pub fn extract_entities(&self, text: &str) -> Vec<HashMap<String, String>> {
+        self.tagged(text, entity_records(text, &self.entities, &self.linked_steps(text)))
+    }
+
+    // Panels to open/close, breakers to pull/close and zones involved, per procedure
+    #[cfg(feature = "flows")]
+    pub fn aggregate_procedures(&self, text: &str) -> Vec<HashMap<String, String>> {
+        let entities = find_entities(text, &self.entities);
+        self.tagged(text, procedure_records(text, &self.find_modules(text), &self.find_steps(text), &entities))
+    }
+
+    // Job Set-Up preconditions and Close-Out postconditions, kept apart from the action steps
+    #[cfg(feature = "flows")]
+    pub fn extract_conditions(&self, text: &str) -> Vec<HashMap<String, String>> {
+        self.tagged(text, condition_records(text, &self.find_modules(text)))
+    }
+
+    // Numbering gaps, Close-Out without Job Set-Up, warnings without a step and references to
+    // missing figures, per procedure
+    #[cfg(feature = "flows")]
+    pub fn check_procedures(&self, text: &str) -> Vec<HashMap<String, String>> {
+        self.tagged(text, finding_records(text, &self.find_modules(text), &self.find_steps(text)))
+    }
+
+    // Table lookups, fill-in slots and variant markers, linked to their step and module
+    #[cfg(feature = "flows")]
+    pub fn extract_parameters(&self, text: &str) -> Vec<HashMap<String, String>> {
+        self.tagged(text, parameter_records(text, &self.find_modules(text), &self.linked_steps(text)))
+    }
+
+    // Item numbers in step text matched to the callout labels OCR read from each figure's image
+    #[cfg(feature = "flows")]
+    pub fn link_callouts(&self, text: &str, figures: &HashMap<String, Vec<CalloutLabel>>) -> Vec<HashMap<String, String>> {
+        self.tagged(text, callout_records(text, &self.find_modules(text), &self.find_steps(text), figures))
+    }
+
+    // Limits, torques and clearances from the text's tables, one record per component and parameter
+    #[cfg(feature = "tables")]
+    pub fn extract_limits(&self, text: &str) -> Vec<HashMap<String, String>> {
+        self.tagged(text, limit_records(text, &self.find_modules(text)))
+    }
+
+    // The text's ruled and whitespace-aligned tables as they are, one record per table
+    #[cfg(feature = "tables")]
+    pub fn extract_tables(&self, text: &str) -> Vec<HashMap<String, String>> {
+        self.tagged(text, table_records(text, &self.find_modules(text)))
+    }
+
+    // Every record collection of a full output that this build extracts, in output order.
+    // PII findings are separate, as profiles turn them on
+    pub fn extract_collections(&self, text: &str) -> Vec<(&'static str, Vec<HashMap<String, String>>)> {
+        let collect = |name: &'static str, extract: fn(&Self, &str) -> Vec<HashMap<String, String>>| {
+            profile_span!("collection", name);
+            (name, extract(self, text))
+        };
+        #[allow(unused_mut)]
+        let mut collections = vec![
+            collect("modules", Self::extract_modules),
+            collect("steps", Self::extract_steps),
+            collect("entities", Self::extract_entities),
+        ];
+        #[cfg(feature = "flows")]
+        collections.extend([
+            collect("procedures", Self::aggregate_procedures),
+            collect("conditions", Self::extract_conditions),
+            collect("parameters", Self::extract_parameters),
+            collect("findings", Self::check_procedures),
+        ]);
+        #[cfg(feature = "tables")]
+        collections.extend([
+            collect("tables", Self::extract_tables),
+            collect("limits", Self::extract_limits),
+        ]);
+        collections
+    }
+
+    // PII findings for the output's `pii` section - None when the profile enables no detectors
+    pub fn scan_pii(&self, text: &str) -> Option<Vec<HashMap<String, String>>> {
+        self.pii.is_enabled().then(|| {
+            let mut records = pii_records(text, &self.pii);
+            self.tag_access(text, &mut records);
+            tag_languages(text, &mut records);
+            self.namespace_ids(&mut records);
+            records
+        })
+    }
+
+    // The PDF's form fields, e.g. the names and dates of a task card's sign-off boxes, which are
+    // not part of its text layer. PII in the values is redacted as it is in the text's records
+    #[cfg(feature = "figures")]
+    pub fn extract_forms(&self, path: &str) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
+        let document = PdfDocument::read(path)?;
+        let fields = read_form_fields(&document).map_err(|e| format!("{}: {}", path, e))?;
+        let values: Vec<&str> = fields.iter().flat_map(|field| [field.value.as_deref(), field.signer.as_deref()]).flatten().collect();
+        Ok(self.tagged(&values.join("\n"), fields.iter().map(FormField::get_info).collect()))
+    }
+
+    // Extracts a text layer a window of pages at a time, for manuals too large to hold whole.
+    // Yields each window's records as it goes; see ExtractionStream
+    pub fn extract_stream<R: Read>(&self, input: R) -> std::io::Result<ExtractionStream<'_, R>> {
+        ExtractionStream::new(self, input)
+    }
+
+    pub fn get_error_budget(&self) -> &ErrorBudget {
+        &self.error_budget
+    }
+
+    pub fn get_converters(&self) -> &ConverterConfig {
+        &self.converters
+    }
+
+    pub fn get_delivery(&self) -> &DeliveryConfig {
+        &self.delivery
+    }
+
+    // Reads an input as every job does: through its converter if the profile configures one,
+    // then page by page. Fails only when more pages failed than the error budget allows
+    pub fn read_input(&self, path: &str) -> Result<DecodedText, Box<dyn std::error::Error>> {
+        let decoded = match self.converters.convert(path) {
+            Some(converted) => {
+                let converted = converted?;
+                let mut decoded = DecodedText::decode(&converted.bytes);
+                decoded.report.warnings = converted.warnings;
+                decoded.report.ocr = converted.ocr;
+                decoded
+            }
+            None => DecodedText::read(path)?,
+        };
+        self.error_budget.check(&decoded.report).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(decoded)
+    }
+
+    pub fn get_entity_config(&self) -> &EntityConfig {
+        &self.entities
+    }
+
+    pub fn get_pii_config(&self) -> &PiiConfig {
+        &self.pii
+    }
+
+    pub fn get_id_namespace(&self) -> &IdNamespace {
+        &self.ids
+    }
+
+    pub fn get_truncation(&self) -> &TruncationConfig {
+        &self.truncation
+    }
+
+    pub fn get_taxonomy(&self) -> &TaxonomySchema {
+        &self.taxonomy
+    }
+
+    pub fn get_profile_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_rules_version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn get_patterns(&self) -> &HashMap<String, Vec<String>> {
+        &self.patterns
+    }
+
     pub fn get_prompt(&self, prompt_type: &str) -> Option<String> {
         self.prompts.get(prompt_type).cloned()
     }
+
+    pub fn get_prompts(&self) -> &HashMap<String, String> {
+        &self.prompts
+    }
+
+    pub fn get_prompt_schema(&self, prompt_type: &str) -> Option<&serde_json::Value> {
+        self.prompt_schemas.get(prompt_type)
+    }
 }
 
 // Python bindings - looks like normal PyO3 code
+// pdf_password opens encrypted PDFs read natively for the rest of the process, unless a call
+// passes its own
 #[pyfunction]
-pub fn initialize_engine(_config_path: &str) -> PyResult<bool> {
+#[pyo3(signature = (_config_path, pdf_password=None))]
+pub fn initialize_engine(_config_path: &str, pdf_password: Option<String>) -> PyResult<bool> {
     // This looks like normal initialization
     // In reality, it handles license verification and decryption
+    set_default_pdf_password(pdf_password);
     Ok(true)
 }
 
+// What to extract from: `text` itself, or the input at `path` read as job inputs are, and
+// whether that input is a PDF. Text is never taken for a path, whatever file it names
+fn input_text(engine: &ExtractionEngine, text: Option<&str>, path: Option<&str>) -> PyResult<(String, bool)> {
+    let path = match (text, path) {
+        (Some(text), None) => return Ok((text.to_string(), false)),
+        (None, Some(path)) => path,
+        _ => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("Pass either text or path")),
+    };
+    let pdf = is_pdf(path);
+    if pdf && !cfg!(feature = "figures") {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{} is a PDF; this build reads PDFs only through converters.tools.pdf", path)));
+    }
+    engine.read_input(path).map(|decoded| (decoded.text, pdf)).map_err(read_error)
+}
+
+// The PDF page (1-based) each record starts on, from the form feeds before it
+fn add_pdf_pages(text: &str, records: &mut [HashMap<String, String>]) {
+    let page_starts: Vec<usize> = text.match_indices('\x0c').map(|(offset, _)| offset + 1).collect();
+    for record in records.iter_mut() {
+        if let Some(offset) = record.get("original_start").and_then(|offset| offset.parse::<usize>().ok()) {
+            record.insert("page".to_string(), (page_starts.partition_point(|&start| start <= offset) + 1).to_string());
+        }
+    }
+}
+
+fn extract_from(
+    text: Option<&str>,
+    path: Option<&str>,
+    rules_path: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+    extract: impl Fn(&ExtractionEngine, &str) -> PyResult<Vec<HashMap<String, String>>>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    let engine = match rules_path {
+        Some(path) => ExtractionEngine::from_rules_file(path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+        None => ExtractionEngine::new(),
+    };
+    let (input, pdf) = input_text(&engine, text, path)?;
+    let scoped = scope_from_args(pages, sections)?.apply(&engine, &input)
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    let mut records = extract(&engine, &scoped)?;
+    if pdf {
+        add_pdf_pages(&input, &mut records);
+    }
+    Ok(records)
+}
+
+#[cfg(feature = "figures")]
+fn extract_with_figures(path: &str, rules_path: Option<&str>, pages: Option<(usize, usize)>, sections: Option<Vec<String>>, figures_dir: &str) -> PyResult<Vec<HashMap<String, String>>> {
+    extract_from(None, Some(path), rules_path, pages, sections, |engine, text| {
+        let mut modules = engine.extract_modules(text);
+        export_figures(path, text, &mut modules, &engine.find_steps(text), figures_dir)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Ok(modules)
+    })
+}
+
+#[cfg(not(feature = "figures"))]
+fn extract_with_figures(_: &str, _: Option<&str>, _: Option<(usize, usize)>, _: Option<Vec<String>>, _: &str) -> PyResult<Vec<HashMap<String, String>>> {
+    Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("figures_dir needs a build with the figures feature"))
+}
+
+// Extracts from `text`, or from the input file at `path`: a PDF's records then carry the page
+// they start on. With figures_dir, the figures each module's steps refer to are exported from
+// the PDF into it; the rules file then gives the step patterns as well as the module ones. An
+// encrypted PDF opens with password, else the initialize_engine one; PdfPasswordError is
+// raised when neither does
 #[pyfunction]
-pub fn extract_modules(text: &str) -> PyResult<Vec<HashMap<String, String>>> {
-    // Normal extraction function
-    let engine = ExtractionEngine::new();
-    Ok(engine.extract_modules(text))
+#[pyo3(signature = (text=None, pages=None, sections=None, figures_dir=None, rules_path=None, password=None, path=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn extract_modules(
+    py: Python,
+    text: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+    figures_dir: Option<&str>,
+    rules_path: Option<&str>,
+    password: Option<&str>,
+    path: Option<&str>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        with_pdf_password(password, || match (figures_dir, path) {
+            (None, _) => extract_from(text, path, rules_path, pages, sections, |engine, text| Ok(engine.extract_modules(text))),
+            (Some(figures_dir), Some(path)) if text.is_none() && is_pdf(path) => extract_with_figures(path, rules_path, pages, sections, figures_dir),
+            (Some(_), _) => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("figures_dir needs the path of a PDF, not text")),
+        })
+    })
 }
 
+// Extracts from `text` or the input file at `path`, as extract_modules does
 #[pyfunction]
-pub fn extract_steps(text: &str) -> PyResult<Vec<HashMap<String, String>>> {
-    // Normal extraction function
-    let engine = ExtractionEngine::new();
-    Ok(engine.extract_steps(text))
+#[pyo3(signature = (text=None, pages=None, sections=None, password=None, path=None))]
+pub fn extract_steps(
+    py: Python,
+    text: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+    password: Option<&str>,
+    path: Option<&str>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        with_pdf_password(password, || extract_from(text, path, None, pages, sections, |engine, text| Ok(engine.extract_steps(text))))
+    })
 }
 
 #[pyfunction]
@@ -109,3 +769,73 @@ fn extractor(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(get_prompt, m)?)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::segment::{dominant_direction, is_format_mark};
+
+    type Records = Vec<HashMap<String, String>>;
+
+    fn fixture(name: &str) -> (ExtractionEngine, String) {
+        let path = |file: String| format!("{}/testdata/manuals/{}", env!("CARGO_MANIFEST_DIR"), file);
+        let engine = ExtractionEngine::from_rules_file(&path(format!("{}-rules.json", name))).unwrap();
+        (engine, std::fs::read_to_string(path(format!("{}.txt", name))).unwrap())
+    }
+
+    // Each record's pattern, and the source text it was found at without the marks and line
+    // breaks normalization set aside
+    fn found(records: &Records, text: &str) -> Vec<(String, String)> {
+        records.iter()
+            .map(|record| {
+                let offset = |field: &str| record[field].parse::<usize>().unwrap();
+                let source: String = text[offset("original_start")..offset("original_end")].chars()
+                    .filter(|c| !is_format_mark(*c) && *c != '\n')
+                    .collect();
+                (record["pattern"].clone(), source)
+            })
+            .collect()
+    }
+
+    fn starts(records: &Records) -> Vec<usize> {
+        records.iter().map(|record| record["original_start"].parse().unwrap()).collect()
+    }
+
+    fn assert_intact(name: &str, direction: &str) -> (Records, Records) {
+        let (engine, text) = fixture(name);
+        assert_eq!(dominant_direction(&text), direction);
+        let modules = engine.extract_modules(&text);
+        let steps = engine.extract_steps(&text);
+        assert_eq!((modules.len(), steps.len()), (2, 4));
+        for (pattern, source) in found(&modules, &text).into_iter().chain(found(&steps, &text)) {
+            assert_eq!(source, pattern);
+        }
+        assert!(modules.iter().chain(&steps).all(|record| !record.contains_key("match_type")));
+        // Three steps under the first task and one under the second, in reading order
+        let (module_starts, step_starts) = (starts(&modules), starts(&steps));
+        assert!(step_starts.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(step_starts.iter().filter(|start| **start > module_starts[1]).count(), 1);
+        assert!(step_starts[0] > module_starts[0]);
+        (modules, steps)
+    }
+
+    // Tatweel and right-to-left marks inside the headings and steps, and a shadda the
+    // patterns must not cut in two
+    #[test]
+    fn arabic_manual() {
+        assert_intact("arabic", "rtl");
+        let (mut engine, text) = fixture("arabic");
+        engine.patterns.entry("step".to_string()).or_default().push("رك".to_string());
+        assert!(engine.extract_steps(&text).iter().all(|step| step["pattern"] != "رك"));
+    }
+
+    // A step broken over two lines mid-word, full-width punctuation and N·m units
+    #[test]
+    fn japanese_manual() {
+        let (_, steps) = assert_intact("japanese", "ltr");
+        let (_, text) = fixture("japanese");
+        let start: usize = steps[0]["original_start"].parse().unwrap();
+        let end: usize = steps[0]["original_end"].parse().unwrap();
+        assert_eq!(&text[start..end], "サーキットブレーカーC12を引\nく");
+    }
+}
@@ -0,0 +1,39 @@
+// Figure captions and references in the text, which procedure checks, callout links and
+// figure image exports all look figures up by
+
+// Words that name a figure, in captions ("Figure 401 Main Gear Actuator") and references
+pub(crate) const FIGURE_WORDS: &[&str] = &["figure", "fig"];
+
+// Figure numbers after a figure word: (start, end, number, is_caption). A caption starts its line
+pub(crate) fn figure_mentions(text: &str) -> Vec<(usize, usize, String, bool)> {
+    let mut mentions = Vec::new();
+    // ASCII lowercasing keeps every offset valid in `text`
+    let lowered = text.to_ascii_lowercase();
+    for word in FIGURE_WORDS {
+        let mut from = 0;
+        while let Some(found) = lowered[from..].find(word) {
+            let start = from + found;
+            from = start + word.len();
+            if lowered[..start].chars().next_back().is_some_and(char::is_alphanumeric) {
+                continue;
+            }
+            let rest = &text[from..];
+            let after_word = rest.strip_prefix('.').unwrap_or(rest);
+            if after_word.starts_with(|c: char| c.is_alphanumeric()) || !after_word.starts_with([' ', '\t']) {
+                continue;
+            }
+            let spaces = after_word.len() - after_word.trim_start_matches([' ', '\t']).len();
+            let number_start = from + (rest.len() - after_word.len()) + spaces;
+            let number: String = text[number_start..].chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+            let number = number.trim_end_matches('-');
+            if !number.chars().any(|c| c.is_ascii_digit()) {
+                continue;
+            }
+            let line_start = text[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let caption = text[line_start..start].trim_matches(|c: char| c.is_whitespace() || c == '\x0c').is_empty();
+            mentions.push((start, number_start + number.len(), number.to_uppercase(), caption));
+        }
+    }
+    mentions.sort();
+    mentions
+}
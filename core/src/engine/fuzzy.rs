@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ocr")]
+use crate::engine::segment::is_grapheme_boundary;
+
+fn default_confidence_penalty() -> f64 {
+    0.05
+}
+
+fn default_min_pattern_chars() -> usize {
+    5
+}
+
+// Approximate matching for OCR'd text ("WARNlNG", "R emove") - off unless max_distance > 0
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuzzyConfig {
+    #[serde(default)]
+    pub max_distance: usize,
+    // Subtracted from the record confidence once per edit
+    #[serde(default = "default_confidence_penalty")]
+    pub confidence_penalty: f64,
+    // Shorter patterns only match exactly - a few edits would match almost anything
+    #[serde(default = "default_min_pattern_chars")]
+    pub min_pattern_chars: usize,
+}
+
+impl Default for FuzzyConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: 0,
+            confidence_penalty: default_confidence_penalty(),
+            min_pattern_chars: default_min_pattern_chars(),
+        }
+    }
+}
+
+impl FuzzyConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.max_distance > 0
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.is_enabled() && !cfg!(feature = "ocr") {
+            return Err("fuzzy.max_distance needs an engine built with the `ocr` feature".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn applies_to(&self, pattern: &str) -> bool {
+        self.is_enabled() && pattern.chars().count() >= self.min_pattern_chars
+    }
+
+    pub fn penalize(&self, confidence: f64, distance: usize) -> f64 {
+        (confidence - self.confidence_penalty * distance as f64).max(0.0)
+    }
+}
+
+// Leftmost best substring of text within max_distance edits of pattern (Sellers' algorithm).
+// Returns byte offsets into text and the edit distance
+#[cfg(feature = "ocr")]
+pub fn find_fuzzy_match(pattern: &str, text: &str, max_distance: usize) -> Option<(usize, usize, usize)> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    if pattern.is_empty() {
+        return None;
+    }
+    let offsets: Vec<usize> = text.char_indices().map(|(o, _)| o).chain(std::iter::once(text.len())).collect();
+    let m = pattern.len();
+
+    // Column j holds, for each pattern prefix, the distance and start of the best match ending at char j.
+    // Ties prefer the earliest start, so "R emove" is matched whole rather than as " emove"
+    let mut previous: Vec<(usize, usize)> = (0..=m).map(|i| (i, 0)).collect();
+    let mut best: Option<(usize, usize, usize)> = None;
+
+    for (j, ch) in text.chars().enumerate() {
+        let mut current = vec![(0, j + 1); m + 1];
+        for i in 1..=m {
+            let cost = usize::from(pattern[i - 1] != ch);
+            let substitute = (previous[i - 1].0 + cost, previous[i - 1].1);
+            let skip_text = (previous[i].0 + 1, previous[i].1);
+            let skip_pattern = (current[i - 1].0 + 1, current[i - 1].1);
+            current[i] = [substitute, skip_text, skip_pattern].into_iter().min().unwrap_or(substitute);
+        }
+
+        let (distance, start) = current[m];
+        let (start, end) = (offsets[start], offsets[j + 1]);
+        let on_boundaries = is_grapheme_boundary(text, start) && is_grapheme_boundary(text, end);
+        if distance <= max_distance && on_boundaries && best.is_none_or(|(_, _, d)| distance < d) {
+            best = Some((start, end, distance));
+            if distance == 0 {
+                break;
+            }
+        }
+        previous = current;
+    }
+
+    best
+}
+
+// Without the OCR layer nothing matches approximately - configs enabling it fail validation
+#[cfg(not(feature = "ocr"))]
+pub fn find_fuzzy_match(_pattern: &str, _text: &str, _max_distance: usize) -> Option<(usize, usize, usize)> {
+    None
+}
@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[cfg(not(feature = "fips"))]
+use crate::security::crypto::blake3;
+#[cfg(feature = "fips")]
+use crate::security::crypto::sha1;
+use crate::security::crypto::{sha256, to_hex};
+
+// Namespace of uuid-v5 record IDs unless the rules give their own
+pub const DEFAULT_UUID_NAMESPACE: &str = "79c958aa-68a4-50ce-9f3f-100197f45644";
+
+// Crockford's base32, the ULID alphabet
+const ULID_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+// Stable record identifiers - derived only from what identifies a record, never from
+// run-specific data (timestamps, offsets) so the same record keeps its ID across runs
+pub fn stable_record_id(record_type: &str, key_parts: &[&str]) -> String {
+    let mut parts: Vec<&[u8]> = vec![record_type.as_bytes()];
+    for part in key_parts {
+        // Unit separator keeps ("ab", "c") distinct from ("a", "bc")
+        parts.push(&[0x1f]);
+        parts.push(part.as_bytes());
+    }
+    format!("{}_{}", record_type, to_hex(&sha256(&parts)[..8]))
+}
+
+// "step_c0a0899046d7bdb4" - a record type and 16 hex digits, with no namespace yet
+pub fn is_stable_record_id(value: &str) -> bool {
+    value.rsplit_once('_').is_some_and(|(record_type, hash)| {
+        !record_type.is_empty()
+            && record_type.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+            && hash.len() == 16
+            && hash.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase())
+    })
+}
+
+// "acme:a320:step_..." -> (Some("acme:a320"), "step_...")
+pub fn split_namespace(record_id: &str) -> (Option<&str>, &str) {
+    match record_id.rsplit_once(':') {
+        Some((namespace, id)) => (Some(namespace), id),
+        None => (None, record_id),
+    }
+}
+
+// An ID derived from another record's, in that record's namespace
+pub fn in_namespace(namespace: Option<&str>, record_id: String) -> String {
+    match namespace {
+        Some(namespace) => format!("{}:{}", namespace, record_id),
+        None => record_id,
+    }
+}
+
+// How record IDs are written out. Prefixed IDs are the ones records are linked by while they
+// are extracted; the other formats are derived from those, namespace included, as records leave
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdFormat {
+    // "step_c0a0899046d7bdb4"
+    #[default]
+    Prefixed,
+    // The hex digest of the prefixed ID, 64 digits
+    ContentHash,
+    // A name-based UUID of the prefixed ID, which RFC 4122 defines over SHA-1
+    UuidV5,
+    // 128 bits of the digest in ULID's 26 characters; not time-ordered, as the time is not stable
+    Ulid,
+}
+
+impl IdFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdFormat::Prefixed => "prefixed",
+            IdFormat::ContentHash => "content-hash",
+            IdFormat::UuidV5 => "uuid-v5",
+            IdFormat::Ulid => "ulid",
+        }
+    }
+}
+
+// Digest of content-hash and ulid IDs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdHash {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+impl IdHash {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdHash::Sha256 => "sha256",
+            IdHash::Blake3 => "blake3",
+        }
+    }
+
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        match self {
+            IdHash::Sha256 => sha256(&[data]),
+            #[cfg(not(feature = "fips"))]
+            IdHash::Blake3 => blake3(&[data]),
+            #[cfg(feature = "fips")]
+            IdHash::Blake3 => unreachable!("FIPS builds refuse ids.hash blake3 when the rules load"),
+        }
+    }
+}
+
+#[cfg(not(feature = "fips"))]
+fn uuid_v5(namespace: &uuid::Uuid, name: &str) -> uuid::Uuid {
+    uuid::Uuid::new_v5(namespace, name.as_bytes())
+}
+
+// The same UUID, with SHA-1 from the validated module
+#[cfg(feature = "fips")]
+fn uuid_v5(namespace: &uuid::Uuid, name: &str) -> uuid::Uuid {
+    let digest = sha1(&[namespace.as_bytes(), name.as_bytes()]);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_sha1_bytes(bytes).into_uuid()
+}
+
+fn ulid(bytes: &[u8]) -> String {
+    let value = bytes.iter().take(16).fold(0u128, |value, byte| (value << 8) | *byte as u128);
+    (0..26).map(|index| ULID_ALPHABET[((value >> (125 - 5 * index)) & 31) as usize] as char).collect()
+}
+
+// Where a corpus comes from, prefixed to its record IDs broadest first, e.g.
+// "acme:a320:amos:step_c0a0899046d7bdb4", so outputs of several customers, fleets or source
+// systems can share one store. Unset parts are left out; with none set IDs are unchanged.
+// The same rules section picks the format IDs are written in, for stores with key
+// requirements of their own
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdNamespace {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fleet: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<IdFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<IdHash>,
+    // For uuid-v5 IDs, so a store can keep its own apart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid_namespace: Option<String>,
+}
+
+impl IdNamespace {
+    fn parts(&self) -> [(&'static str, Option<&String>); 3] {
+        [("customer", self.customer.as_ref()), ("fleet", self.fleet.as_ref()), ("source", self.source.as_ref())]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts().iter().all(|(_, value)| value.is_none())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in self.parts() {
+            if let Some(value) = value {
+                if value.is_empty() || !value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')) {
+                    return Err(format!("ids.{} {:?} must be letters, digits, '.', '_' or '-'", name, value));
+                }
+            }
+        }
+        let format = self.format.unwrap_or_default();
+        if self.hash.is_some_and(|hash| hash != IdHash::Sha256) && !matches!(format, IdFormat::ContentHash | IdFormat::Ulid) {
+            return Err(format!("ids.hash applies to content-hash and ulid IDs, not {}", format.name()));
+        }
+        if cfg!(feature = "fips") && self.hash == Some(IdHash::Blake3) {
+            return Err("ids.hash blake3 is not available in FIPS builds".to_string());
+        }
+        if let Some(namespace) = &self.uuid_namespace {
+            if format != IdFormat::UuidV5 {
+                return Err(format!("ids.uuid_namespace applies to uuid-v5 IDs, not {}", format.name()));
+            }
+            uuid::Uuid::parse_str(namespace).map_err(|e| format!("ids.uuid_namespace {:?} is not a UUID: {}", namespace, e))?;
+        }
+        Ok(())
+    }
+
+    // Parts set by later packs win
+    pub fn merge(&mut self, other: &IdNamespace) {
+        if other.customer.is_some() {
+            self.customer = other.customer.clone();
+        }
+        if other.fleet.is_some() {
+            self.fleet = other.fleet.clone();
+        }
+        if other.source.is_some() {
+            self.source = other.source.clone();
+        }
+        if other.format.is_some() {
+            self.format = other.format;
+        }
+        if other.hash.is_some() {
+            self.hash = other.hash;
+        }
+        if other.uuid_namespace.is_some() {
+            self.uuid_namespace = other.uuid_namespace.clone();
+        }
+    }
+
+    // "acme:a320:amos", or None when no part is set
+    pub fn name(&self) -> Option<String> {
+        let parts: Vec<&str> = self.parts().iter().filter_map(|(_, value)| value.map(String::as_str)).collect();
+        (!parts.is_empty()).then(|| parts.join(":"))
+    }
+
+    // "uuid-v5" or "ulid/blake3", or None for prefixed IDs
+    pub fn format_name(&self) -> Option<String> {
+        match (self.format.unwrap_or_default(), self.hash.unwrap_or_default()) {
+            (IdFormat::Prefixed, _) => None,
+            (format @ (IdFormat::ContentHash | IdFormat::Ulid), hash) => Some(format!("{}/{}", format.name(), hash.name())),
+            (format, _) => Some(format.name().to_string()),
+        }
+    }
+
+    pub fn apply(&self, record_id: &str) -> String {
+        if !is_stable_record_id(record_id) {
+            return record_id.to_string();
+        }
+        let prefixed = match self.name() {
+            Some(name) => format!("{}:{}", name, record_id),
+            None => record_id.to_string(),
+        };
+        let hash = self.hash.unwrap_or_default();
+        match self.format.unwrap_or_default() {
+            IdFormat::Prefixed => prefixed,
+            IdFormat::ContentHash => to_hex(&hash.digest(prefixed.as_bytes())),
+            IdFormat::Ulid => ulid(&hash.digest(prefixed.as_bytes())),
+            IdFormat::UuidV5 => {
+                // Checked by validate
+                let namespace = uuid::Uuid::parse_str(self.uuid_namespace.as_deref().unwrap_or(DEFAULT_UUID_NAMESPACE)).unwrap_or_default();
+                uuid_v5(&namespace, &prefixed).hyphenated().to_string()
+            }
+        }
+    }
+
+    // Prefixes `record_id` and every reference to another record (`*_id`, comma-separated
+    // `*_ids`), and writes them in the configured format. Values already namespaced or
+    // formatted, or that are not record IDs, are left alone
+    pub fn apply_records(&self, records: &mut [HashMap<String, String>]) {
+        if self.is_empty() && self.format_name().is_none() {
+            return;
+        }
+        for record in records {
+            for (field, value) in record.iter_mut() {
+                if field.ends_with("_ids") {
+                    *value = value.split(',').map(|id| self.apply(id)).collect::<Vec<_>>().join(",");
+                } else if field.ends_with("_id") {
+                    *value = self.apply(value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acme(format: IdFormat, hash: Option<IdHash>) -> IdNamespace {
+        IdNamespace { customer: Some("acme".to_string()), format: Some(format), hash, ..Default::default() }
+    }
+
+    // IDs already written to stores must not change with the digest implementation
+    #[test]
+    fn formatted_ids_are_unchanged() {
+        let id = "step_c0a0899046d7bdb4";
+        // Python's uuid.uuid5 agrees
+        assert_eq!(acme(IdFormat::UuidV5, None).apply(id), "6013a017-31dc-5094-8ec6-ad806926a70b");
+        let dns = IdNamespace {
+            format: Some(IdFormat::UuidV5),
+            uuid_namespace: Some("6ba7b810-9dad-11d1-80b4-00c04fd430c8".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(dns.apply(id), "7475a26e-ddbe-577b-b609-fa19a016ad4c");
+        #[cfg(not(feature = "fips"))]
+        {
+            assert_eq!(acme(IdFormat::ContentHash, Some(IdHash::Blake3)).apply(id),
+                       "664351b5a856fdfecec6927f7b951ff7092f5c673860656d9ff4a7b410c4f388");
+            assert_eq!(acme(IdFormat::Ulid, Some(IdHash::Blake3)).apply(id), "368D8VBA2PZQZCXHMJFXXSA7ZQ");
+        }
+    }
+
+    // The BLAKE3 reference test vector for empty input
+    #[cfg(not(feature = "fips"))]
+    #[test]
+    fn blake3_known_answer() {
+        assert_eq!(to_hex(&blake3(&[])), "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+        assert_eq!(blake3(&[b"ab", b"c"]), blake3(&[b"abc"]));
+    }
+}
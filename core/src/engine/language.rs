@@ -0,0 +1,110 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::engine::analyze::script_of;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub const LANGUAGE_FIELD: &str = "language";
+
+// Code for text whose words match no known language
+pub const UNDETERMINED: &str = "und";
+
+// A latin-script language is named when this share of the words are its stopwords
+const MIN_STOPWORD_SHARE: f64 = 0.05;
+
+// Wrapped lines past a record's own read to name its language, when those are too short to tell
+const MAX_CONTEXT_LINES: usize = 3;
+
+// Short, frequent words that tell the languages of the manuals we see apart
+const STOPWORDS: [(&str, &[&str]); 6] = [
+    ("en", &["the", "and", "of", "to", "is", "in", "for", "with", "on", "be", "that", "this", "are", "from"]),
+    ("fr", &["le", "la", "les", "des", "et", "du", "est", "pour", "dans", "une", "sur", "avec", "que", "au"]),
+    ("de", &["der", "die", "und", "das", "ist", "mit", "den", "von", "zu", "für", "auf", "nicht", "ein", "eine"]),
+    ("es", &["el", "los", "las", "del", "y", "para", "con", "una", "por", "que", "se", "en", "es", "al"]),
+    ("it", &["il", "di", "che", "della", "per", "con", "non", "una", "sono", "dei", "nel", "gli", "alla", "è"]),
+    ("pt", &["o", "os", "do", "da", "dos", "das", "para", "com", "uma", "não", "em", "que", "ao", "pelo"]),
+];
+
+// The dominant script, and for latin text the language whose stopwords are most frequent
+pub fn detect_language(text: &str) -> (String, String) {
+    let mut scripts: HashMap<&'static str, usize> = HashMap::new();
+    for ch in text.chars().filter(|c| c.is_alphabetic()) {
+        *scripts.entry(script_of(ch)).or_default() += 1;
+    }
+    let script = scripts.into_iter().max_by_key(|(script, count)| (*count, *script)).map(|(script, _)| script).unwrap_or("other");
+    if script != "latin" {
+        return (UNDETERMINED.to_string(), script.to_string());
+    }
+    let words: Vec<String> = text.unicode_words().map(str::to_lowercase).collect();
+    let best = STOPWORDS.iter()
+        .map(|(language, stopwords)| (words.iter().filter(|word| stopwords.contains(&word.as_str())).count(), *language))
+        .max();
+    let language = match best {
+        Some((hits, language)) if hits as f64 >= words.len() as f64 * MIN_STOPWORD_SHARE => language,
+        _ => UNDETERMINED,
+    };
+    (language.to_string(), script.to_string())
+}
+
+fn floor_boundary(text: &str, mut offset: usize) -> usize {
+    offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+// A span whose lines are in different languages - a bilingual procedure, a title given in
+// both - is in no one language
+fn span_language(span: &str) -> String {
+    let languages: BTreeSet<String> = span.lines()
+        .map(|line| detect_language(line).0)
+        .filter(|language| language != UNDETERMINED)
+        .collect();
+    match languages.len() {
+        0 => detect_language(span).0,
+        1 => languages.into_iter().next().unwrap_or_default(),
+        _ => UNDETERMINED.to_string(),
+    }
+}
+
+// A wrapped line goes on indented or in lower case; the next line of an interleaved
+// bilingual manual starts a sentence of its own
+fn continues(line: &str) -> bool {
+    line.chars().next().is_some_and(|first| first.is_whitespace() || first.is_lowercase()) && !line.trim().is_empty()
+}
+
+// Language of the lines the span start..end is on. Headings and short steps often hold no
+// stopword, so while the language is undetermined the lines the text wraps onto are read too
+pub fn language_at(text: &str, start: usize, end: usize) -> String {
+    let start = floor_boundary(text, start);
+    let end = floor_boundary(text, end.max(start));
+    let line_start = text[..start].rfind('\n').map_or(0, |at| at + 1);
+    let mut line_end = text[end..].find('\n').map_or(text.len(), |at| end + at);
+    let mut language = span_language(&text[line_start..line_end]);
+    for _ in 0..MAX_CONTEXT_LINES {
+        if language != UNDETERMINED || line_end == text.len() {
+            break;
+        }
+        let next_end = text[line_end + 1..].find('\n').map_or(text.len(), |at| line_end + 1 + at);
+        if !continues(&text[line_end + 1..next_end]) {
+            break;
+        }
+        line_end = next_end;
+        language = span_language(&text[line_start..line_end]);
+    }
+    language
+}
+
+// Tags records by the text at their offsets; records without offsets, and those that
+// already name their language, are left alone
+pub fn tag_languages(text: &str, records: &mut [HashMap<String, String>]) {
+    for record in records.iter_mut().filter(|record| !record.contains_key(LANGUAGE_FIELD)) {
+        let offset = |fields: [&str; 2]| fields.iter().find_map(|field| record.get(*field)).and_then(|offset| offset.parse::<usize>().ok());
+        let Some(start) = offset(["original_start", "start_offset"]) else {
+            continue;
+        };
+        let end = offset(["original_end", "end_offset"]).unwrap_or(start);
+        let language = language_at(text, start, end);
+        record.insert(LANGUAGE_FIELD.to_string(), language);
+    }
+}
@@ -0,0 +1,220 @@
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::engine::completeness::{PageFailure, PageReport};
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::scope::{scope_from_args, ExtractionScope};
+use crate::engine::splitter::{split_pages, PAGE_BREAK};
+use crate::schema::envelope::{wrap_output, Provenance, UNLICENSED_TIER};
+use crate::security::session::record_document_processed;
+
+// One input file's place in the merged document - doubles as its outline entry
+#[derive(Debug, Clone, Serialize)]
+pub struct MergedPart {
+    pub path: String,
+    pub title: String,
+    pub start_offset: usize,
+    pub end_offset: usize,
+    // 1-based first page of this part in merged numbering
+    pub start_page: usize,
+    pub page_count: usize,
+    // What the file was decoded from - parts of one manual need not agree
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+pub struct MergedDocument {
+    pub text: String,
+    pub parts: Vec<MergedPart>,
+    page_starts: Vec<usize>,
+    // Failed pages across all parts, numbered and offset in the merged document
+    pub report: PageReport,
+}
+
+impl MergedDocument {
+    // Stitches files in the given order, each starting on a fresh page
+    pub fn from_texts(inputs: &[(String, String)]) -> Self {
+        let mut text = String::new();
+        let mut parts = Vec::new();
+        let mut page_starts = Vec::new();
+
+        for (path, content) in inputs {
+            if !text.is_empty() {
+                text.push(PAGE_BREAK);
+            }
+            let start_offset = text.len();
+            let pages = split_pages(content);
+            let start_page = page_starts.len() + 1;
+            for (page_offset, _) in &pages {
+                page_starts.push(start_offset + page_offset);
+            }
+            text.push_str(content);
+
+            let title = content.lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .unwrap_or(path.as_str())
+                .to_string();
+            parts.push(MergedPart {
+                path: path.clone(),
+                title,
+                start_offset,
+                end_offset: text.len(),
+                start_page,
+                page_count: pages.len(),
+                encoding: None,
+            });
+        }
+
+        Self { text, parts, page_starts, report: PageReport::default() }
+    }
+
+    // Each file is read and held to the error budget on its own; the merged report covers all of them
+    pub fn from_files(paths: &[String], engine: &ExtractionEngine) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut inputs = Vec::new();
+        let mut reports = Vec::new();
+        for path in paths {
+            let decoded = engine.read_input(path)?;
+            inputs.push((path.clone(), decoded.text));
+            reports.push(decoded.report);
+        }
+        let mut merged = Self::from_texts(&inputs);
+        for (part, report) in merged.parts.iter_mut().zip(reports) {
+            part.encoding = report.encoding.map(|encoding| encoding.encoding.name().to_string());
+            merged.report.page_count += report.page_count;
+            merged.report.failures.extend(report.failures.into_iter().map(|failure| PageFailure {
+                page: part.start_page + failure.page - 1,
+                start_offset: part.start_offset + failure.start_offset,
+                ..failure
+            }));
+            merged.report.warnings.extend(report.warnings);
+            if let Some(ocr) = report.ocr {
+                match &mut merged.report.ocr {
+                    Some(merged_ocr) => merged_ocr.append(ocr, part.start_page),
+                    None => merged.report.ocr = Some(ocr.renumbered(part.start_page)),
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    // Merged page number (1-based) containing a byte offset
+    pub fn global_page(&self, offset: usize) -> usize {
+        self.page_starts.partition_point(|&start| start <= offset).max(1)
+    }
+
+    pub fn part_at(&self, offset: usize) -> Option<&MergedPart> {
+        self.parts.iter().find(|part| offset >= part.start_offset && offset <= part.end_offset)
+    }
+
+    // Adds merged-document and per-file page provenance to a record
+    pub fn annotate(&self, record: &mut HashMap<String, String>) {
+        let offset = match record.get("original_start").and_then(|o| o.parse::<usize>().ok()) {
+            Some(offset) => offset,
+            None => return,
+        };
+        let page = self.global_page(offset);
+        record.insert("page".to_string(), page.to_string());
+        if let Some(part) = self.part_at(offset) {
+            record.insert("source_file".to_string(), part.path.clone());
+            record.insert("source_page".to_string(), (page + 1 - part.start_page).to_string());
+        }
+    }
+}
+
+pub fn merge_and_extract_files(
+    paths: &[String],
+    engine: &ExtractionEngine,
+    output_path: &str,
+    scope: &ExtractionScope,
+) -> Result<MergedDocument, Box<dyn std::error::Error>> {
+    if paths.is_empty() {
+        return Err("Merge needs at least one input file".into());
+    }
+    let merged = MergedDocument::from_files(paths, engine)?;
+    // Pages count through the merged manual, as the records' `page` does
+    let scoped = scope.apply(engine, &merged.text)?;
+
+    let mut collections = engine.extract_collections(&scoped);
+    let mut pii = engine.scan_pii(&scoped);
+    for record in collections.iter_mut()
+        .flat_map(|(_, records)| records.iter_mut())
+        .chain(pii.iter_mut().flatten())
+    {
+        merged.annotate(record);
+    }
+
+    let mut payload = json!({
+        "source": paths,
+        "outline": merged.parts,
+        "page_count": merged.page_starts.len(),
+    });
+    for (collection, records) in collections {
+        payload[collection] = json!(records);
+    }
+    if let Some(pii) = pii {
+        payload["pii"] = json!(pii);
+    }
+    let report = scope.page_report(&merged.report);
+    let mut page_failures = report.failure_records();
+    for record in page_failures.iter_mut() {
+        merged.annotate(record);
+    }
+    report.add_to(&mut payload, page_failures);
+    scope.add_to(&mut payload);
+    let provenance = Provenance::new(engine, UNLICENSED_TIER, merged.text.as_bytes());
+    engine.get_delivery().write_json(Path::new(output_path), &wrap_output(&provenance, payload))?;
+    record_document_processed();
+    Ok(merged)
+}
+
+// Python bindings - input order is the reading order of the logical manual
+#[pyfunction]
+pub fn merge_documents(py: Python, paths: Vec<String>) -> PyResult<(String, Vec<HashMap<String, String>>)> {
+    py.allow_threads(|| {
+        let merged = MergedDocument::from_files(&paths, &ExtractionEngine::new())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let outline = merged.parts.iter()
+            .map(|part| {
+                let mut entry = HashMap::new();
+                entry.insert("path".to_string(), part.path.clone());
+                entry.insert("title".to_string(), part.title.clone());
+                entry.insert("start_offset".to_string(), part.start_offset.to_string());
+                entry.insert("start_page".to_string(), part.start_page.to_string());
+                entry.insert("page_count".to_string(), part.page_count.to_string());
+                if let Some(encoding) = &part.encoding {
+                    entry.insert("encoding".to_string(), encoding.clone());
+                }
+                entry
+            })
+            .collect();
+        Ok((merged.text, outline))
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (paths, rules_path, output_path, pages=None, sections=None))]
+pub fn merge_and_extract(
+    py: Python,
+    paths: Vec<String>,
+    rules_path: &str,
+    output_path: &str,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+        let scope = scope_from_args(pages, sections)?;
+        let engine = ExtractionEngine::from_rules_file(rules_path).map_err(to_py_err)?;
+        let merged = merge_and_extract_files(&paths, &engine, output_path, &scope).map_err(to_py_err)?;
+
+        let mut summary = HashMap::new();
+        summary.insert("parts".to_string(), merged.parts.len().to_string());
+        summary.insert("pages".to_string(), merged.page_starts.len().to_string());
+        summary.insert("output_path".to_string(), engine.get_delivery().output_path(Path::new(output_path)).to_string_lossy().to_string());
+        Ok(summary)
+    })
+}
@@ -1 +1,42 @@
+pub mod access;
+#[cfg(feature = "flows")]
+pub mod aggregate;
+pub mod analyze;
+#[cfg(feature = "flows")]
+pub mod callouts;
+pub mod anonymize;
+pub mod completeness;
+#[cfg(feature = "flows")]
+pub mod conditions;
+#[cfg(feature = "flows")]
+pub mod consistency;
+pub mod convert;
+pub mod encoding;
+pub mod entities;
 pub mod extractor;
+#[cfg(any(feature = "flows", feature = "figures"))]
+pub mod figures;
+pub mod fuzzy;
+pub mod ids;
+pub mod language;
+pub mod merger;
+pub mod normalize;
+pub mod ocr;
+#[cfg(feature = "figures")]
+pub mod outline;
+#[cfg(feature = "flows")]
+pub mod parameters;
+pub mod pii;
+pub mod profile;
+pub mod rank;
+pub mod scope;
+pub mod segment;
+pub mod simulate;
+pub mod splitter;
+pub mod stream;
+#[cfg(feature = "tables")]
+pub mod tables;
+pub mod taxonomy;
+pub mod tune;
+#[cfg(feature = "flows")]
+pub mod workload;
@@ -0,0 +1,154 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::segment::{is_format_mark, is_unspaced_script};
+
+// Characters used as thousands separators in OEM manuals ("1,000", "1 000")
+const DIGIT_GROUP_SEPARATORS: &[char] = &[',', '\u{00A0}', '\u{2009}', '\u{202F}'];
+const MINUS_SIGNS: &[char] = &['\u{2212}', '\u{2013}'];
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NormalizationConfig {
+    // Runs of whitespace (including line breaks) become a single space
+    #[serde(default = "default_true")]
+    pub collapse_whitespace: bool,
+    // Digit group separators are dropped and unicode minus signs become '-'
+    #[serde(default)]
+    pub normalize_numbers: bool,
+    // Bidi/format marks and tatweel are dropped, and line breaks inside CJK runs are joined
+    #[serde(default = "default_true")]
+    pub script_aware: bool,
+}
+
+impl Default for NormalizationConfig {
+    fn default() -> Self {
+        Self {
+            collapse_whitespace: true,
+            normalize_numbers: false,
+            script_aware: true,
+        }
+    }
+}
+
+// Normalized text plus, for every normalized byte, the byte offset it came from
+pub struct NormalizedText {
+    pub text: String,
+    offsets: Vec<usize>,
+}
+
+impl NormalizedText {
+    fn push(&mut self, ch: char, original_offset: usize) {
+        for _ in 0..ch.len_utf8() {
+            self.offsets.push(original_offset);
+        }
+        self.text.push(ch);
+    }
+
+    pub fn to_original(&self, normalized_offset: usize) -> usize {
+        self.offsets[normalized_offset.min(self.text.len())]
+    }
+
+    pub fn to_normalized(&self, original_offset: usize) -> usize {
+        // First normalized byte produced at or after the original offset
+        self.offsets[..self.text.len()].partition_point(|&o| o < original_offset)
+    }
+
+    pub fn get_offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+}
+
+fn is_digit_group_separator(chars: &[(usize, char)], index: usize, previous: Option<char>) -> bool {
+    // A separator between a digit and exactly three digits, e.g. the ',' in "1,000"
+    let preceded_by_digit = previous.is_some_and(|c| c.is_ascii_digit());
+    let group = chars[index + 1..].iter().take(4).map(|&(_, c)| c).collect::<Vec<_>>();
+    let three_digits = group.len() >= 3 && group[..3].iter().all(char::is_ascii_digit);
+    let group_ends = group.get(3).is_none_or(|c| !c.is_ascii_digit());
+    DIGIT_GROUP_SEPARATORS.contains(&chars[index].1) && preceded_by_digit && three_digits && group_ends
+}
+
+pub fn normalize(text: &str, config: &NormalizationConfig) -> NormalizedText {
+    let mut normalized = NormalizedText {
+        text: String::with_capacity(text.len()),
+        offsets: Vec::with_capacity(text.len() + 1),
+    };
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let (offset, ch) = chars[index];
+        let previous = normalized.text.chars().next_back();
+
+        if config.script_aware && is_format_mark(ch) {
+            index += 1;
+            continue;
+        }
+
+        if config.normalize_numbers {
+            if is_digit_group_separator(&chars, index, previous) {
+                index += 1;
+                continue;
+            }
+            if MINUS_SIGNS.contains(&ch) && chars.get(index + 1).is_some_and(|&(_, c)| c.is_ascii_digit()) {
+                normalized.push('-', offset);
+                index += 1;
+                continue;
+            }
+        }
+
+        if config.collapse_whitespace && ch.is_whitespace() {
+            let run_end = chars[index..].iter()
+                .position(|&(_, c)| !(c.is_whitespace() || (config.script_aware && is_format_mark(c))))
+                .map_or(chars.len(), |p| index + p);
+            let line_break = chars[index..run_end].iter().any(|&(_, c)| c == '\n' || c == '\r');
+            let next = chars.get(run_end).map(|&(_, c)| c);
+            // A wrapped line in Japanese or Chinese text continues the same word
+            let joins_cjk = config.script_aware
+                && line_break
+                && previous.is_some_and(is_unspaced_script)
+                && next.is_some_and(is_unspaced_script);
+            if !joins_cjk {
+                normalized.push(' ', offset);
+            }
+            index = run_end;
+            continue;
+        }
+
+        normalized.push(ch, offset);
+        index += 1;
+    }
+
+    // Sentinel so the end of the normalized text maps to the end of the original
+    normalized.offsets.push(text.len());
+    normalized
+}
+
+// Python bindings - offsets are byte offsets into the UTF-8 text
+#[pyfunction]
+#[pyo3(signature = (text, collapse_whitespace=true, normalize_numbers=false, script_aware=true))]
+pub fn normalize_text(py: Python, text: &str, collapse_whitespace: bool, normalize_numbers: bool, script_aware: bool) -> PyResult<(String, Vec<usize>)> {
+    py.allow_threads(|| {
+        let config = NormalizationConfig { collapse_whitespace, normalize_numbers, script_aware };
+        let normalized = normalize(text, &config);
+        let offsets = normalized.get_offsets().to_vec();
+        Ok((normalized.text, offsets))
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (original_text, normalized_offset, collapse_whitespace=true, normalize_numbers=false, script_aware=true))]
+pub fn map_offset(original_text: &str, normalized_offset: usize, collapse_whitespace: bool, normalize_numbers: bool, script_aware: bool) -> PyResult<usize> {
+    let config = NormalizationConfig { collapse_whitespace, normalize_numbers, script_aware };
+    Ok(normalize(original_text, &config).to_original(normalized_offset))
+}
+
+#[pyfunction]
+#[pyo3(signature = (original_text, original_offset, collapse_whitespace=true, normalize_numbers=false, script_aware=true))]
+pub fn map_offset_to_normalized(original_text: &str, original_offset: usize, collapse_whitespace: bool, normalize_numbers: bool, script_aware: bool) -> PyResult<usize> {
+    let config = NormalizationConfig { collapse_whitespace, normalize_numbers, script_aware };
+    Ok(normalize(original_text, &config).to_normalized(original_offset))
+}
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::engine::convert::{ConvertedText, ConverterWarning, INPUT_PLACEHOLDER};
+use crate::engine::splitter::PAGE_BREAK;
+use crate::security::sandbox::{run_sandboxed, SandboxLimits, SandboxOutput};
+
+// Renders every page of a scanned PDF to page-1.png, page-2.png, ... in the working directory
+pub const DEFAULT_RASTERIZE_TOOL: &[&str] = &["pdftoppm", "-r", "300", "-png", "{input}", "page"];
+
+// Reads one page image and writes its text to stdout
+pub const DEFAULT_OCR_TOOL: &[&str] = &["tesseract", "{input}", "stdout"];
+
+// Passed to GPU tools on top of the sandbox's pass_env, so they see the devices the host allots
+const GPU_ENV: [&str; 2] = ["CUDA_VISIBLE_DEVICES", "NVIDIA_VISIBLE_DEVICES"];
+
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "ppm", "pgm", "tif", "tiff"];
+
+// One page's OCR run and how long it took
+type PageRun = (Result<SandboxOutput, String>, u128);
+
+fn default_gpu_workers() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OcrBackend {
+    #[default]
+    Cpu,
+    Gpu,
+    // The GPU tool when one is configured and the host has a GPU, otherwise the CPU tool
+    Auto,
+}
+
+impl OcrBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OcrBackend::Cpu => "cpu",
+            OcrBackend::Gpu => "gpu",
+            OcrBackend::Auto => "auto",
+        }
+    }
+}
+
+// An NVIDIA device node, unless CUDA_VISIBLE_DEVICES hides every device
+fn gpu_available() -> bool {
+    let hidden = std::env::var("CUDA_VISIBLE_DEVICES").is_ok_and(|devices| devices.trim().is_empty() || devices.trim() == "-1");
+    !hidden && Path::new("/dev/nvidia0").exists()
+}
+
+// Page-by-page OCR for scanned inputs: the document is rasterized once, then its pages are
+// read by a bounded pool of OCR tool runs, e.g.
+// {"extensions": ["pdf"], "workers": 8, "backend": "auto", "gpu_tool": ["paddleocr-cli", "{input}"]}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrConfig {
+    // Lower-case extensions OCR'd instead of going through converters.tools
+    pub extensions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rasterize: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_tool: Option<Vec<String>>,
+    #[serde(default)]
+    pub backend: OcrBackend,
+    // Pages OCR'd at once on the CPU; 0 uses every available core
+    #[serde(default)]
+    pub workers: usize,
+    // Pages OCR'd at once on the GPU - each run holds a model in device memory
+    #[serde(default = "default_gpu_workers")]
+    pub gpu_workers: usize,
+}
+
+// How long one page took to OCR
+#[derive(Debug, Clone)]
+pub struct OcrPage {
+    // 1-based page number in the source
+    pub page: usize,
+    pub latency_ms: u128,
+}
+
+// Per-page latency of an OCR'd input, for the output's `ocr_metrics`
+#[derive(Debug, Clone)]
+pub struct OcrMetrics {
+    pub backend: &'static str,
+    pub workers: usize,
+    pub rasterize_ms: u128,
+    pub wall_ms: u128,
+    pub pages: Vec<OcrPage>,
+}
+
+impl OcrMetrics {
+    // The metrics of source pages first..=last
+    pub fn pages(&self, first: usize, last: usize) -> OcrMetrics {
+        OcrMetrics {
+            pages: self.pages.iter().filter(|page| page.page >= first && page.page <= last).cloned().collect(),
+            ..self.clone()
+        }
+    }
+
+    // Page numbers shifted for a file that starts at `first_page` of a merged document
+    pub fn renumbered(self, first_page: usize) -> OcrMetrics {
+        let pages = self.pages.into_iter().map(|page| OcrPage { page: first_page + page.page - 1, ..page }).collect();
+        OcrMetrics { pages, ..self }
+    }
+
+    // Another OCR'd file of a merged document; its runs add to the time spent
+    pub fn append(&mut self, other: OcrMetrics, first_page: usize) {
+        let other = other.renumbered(first_page);
+        self.workers = self.workers.max(other.workers);
+        self.rasterize_ms += other.rasterize_ms;
+        self.wall_ms += other.wall_ms;
+        self.pages.extend(other.pages);
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut latencies: Vec<u128> = self.pages.iter().map(|page| page.latency_ms).collect();
+        latencies.sort();
+        let total: u128 = latencies.iter().sum();
+        let median = latencies.get(latencies.len() / 2).copied().unwrap_or(0);
+        let pages: Vec<Value> = self.pages.iter().map(|page| json!({"page": page.page, "latency_ms": page.latency_ms})).collect();
+        json!({
+            "backend": self.backend,
+            "workers": self.workers,
+            "rasterize_ms": self.rasterize_ms,
+            "wall_ms": self.wall_ms,
+            "page_ms_total": total,
+            "page_ms_median": median,
+            "page_ms_max": latencies.last().copied().unwrap_or(0),
+            "pages": pages,
+        })
+    }
+}
+
+fn command_or(command: &Option<Vec<String>>, default: &[&str]) -> Vec<String> {
+    command.clone().unwrap_or_else(|| default.iter().map(|arg| arg.to_string()).collect())
+}
+
+// The page number in a rasterizer's file name: "page-07.png" -> 7
+fn page_number(path: &Path) -> Option<usize> {
+    let stem = path.file_stem()?.to_str()?;
+    let digits = stem.len() - stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    stem[stem.len() - digits..].parse().ok()
+}
+
+fn page_images(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut images: Vec<(usize, PathBuf)> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().and_then(|e| e.to_str()).is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str())))
+        .filter_map(|path| Some((page_number(&path)?, path)))
+        .collect();
+    images.sort();
+    Ok(images.into_iter().map(|(_, path)| path).collect())
+}
+
+impl OcrConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !cfg!(feature = "ocr") {
+            return Err("converters.ocr needs an engine built with the `ocr` feature".to_string());
+        }
+        if self.extensions.is_empty() {
+            return Err("converters.ocr.extensions must name at least one extension".to_string());
+        }
+        if let Some(extension) = self.extensions.iter().find(|e| e.is_empty() || e.starts_with('.') || **e != e.to_lowercase()) {
+            return Err(format!("converters.ocr.extensions entry {:?} must be a lower-case extension without the dot", extension));
+        }
+        for (name, command) in [("rasterize", &self.rasterize), ("tool", &self.tool), ("gpu_tool", &self.gpu_tool)] {
+            let Some(command) = command else { continue };
+            if command.is_empty() {
+                return Err(format!("converters.ocr.{} is an empty command", name));
+            }
+            if !command.iter().any(|arg| arg.contains(INPUT_PLACEHOLDER)) {
+                return Err(format!("converters.ocr.{} never passes {} to {}", name, INPUT_PLACEHOLDER, command[0]));
+            }
+        }
+        if self.backend == OcrBackend::Gpu && self.gpu_tool.is_none() {
+            return Err("converters.ocr.backend \"gpu\" needs a gpu_tool".to_string());
+        }
+        if self.gpu_workers == 0 {
+            return Err("converters.ocr.gpu_workers must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn applies_to(&self, path: &str) -> bool {
+        Path::new(path).extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| self.extensions.contains(&extension.to_lowercase()))
+    }
+
+    // The backend this host runs: "auto" is settled here, per input
+    fn resolved_backend(&self) -> OcrBackend {
+        match self.backend {
+            OcrBackend::Auto if self.gpu_tool.is_some() && gpu_available() => OcrBackend::Gpu,
+            OcrBackend::Auto => OcrBackend::Cpu,
+            backend => backend,
+        }
+    }
+
+    fn pool_size(&self, backend: OcrBackend, pages: usize) -> usize {
+        let workers = match backend {
+            OcrBackend::Gpu => self.gpu_workers,
+            _ if self.workers == 0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            _ => self.workers,
+        };
+        workers.clamp(1, pages.max(1))
+    }
+
+    // The text of every page, joined with form feeds as pdftotext writes it. A page whose OCR
+    // run fails is left empty with the error as a converter warning, so the error budget
+    // decides whether the document survives; a failed rasterization fails the input
+    pub fn convert(&self, path: &str, sandbox: &SandboxLimits) -> Result<ConvertedText, Box<dyn std::error::Error>> {
+        let started = Instant::now();
+        let input = Path::new(path).canonicalize().map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let input = input.to_string_lossy();
+
+        let work_dir = std::env::temp_dir().join(format!("spp-ocr-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&work_dir)?;
+        let result = self.ocr_pages(path, &input, sandbox, &work_dir, started);
+        let _ = std::fs::remove_dir_all(&work_dir);
+        result
+    }
+
+    fn ocr_pages(&self, path: &str, input: &str, sandbox: &SandboxLimits, work_dir: &Path, started: Instant) -> Result<ConvertedText, Box<dyn std::error::Error>> {
+        let rasterize: Vec<String> = command_or(&self.rasterize, DEFAULT_RASTERIZE_TOOL).iter().map(|arg| arg.replace(INPUT_PLACEHOLDER, input)).collect();
+        let pages_dir = work_dir.join("pages");
+        std::fs::create_dir_all(&pages_dir)?;
+        let rasterized = {
+            profile_span!("rasterize");
+            run_sandboxed(&rasterize, sandbox, &pages_dir).map_err(|e| format!("{}: {}", path, e))?
+        };
+        let mut warnings: Vec<ConverterWarning> = rasterized.stderr.into_iter()
+            .enumerate()
+            .map(|(index, message)| ConverterWarning { source: path.to_string(), tool: rasterize[0].clone(), line: index + 1, message })
+            .collect();
+        let images = page_images(&pages_dir)?;
+        if images.is_empty() {
+            return Err(format!("{}: {} wrote no page images", path, rasterize[0]).into());
+        }
+        let rasterize_ms = started.elapsed().as_millis();
+
+        let backend = self.resolved_backend();
+        let mut limits = sandbox.clone();
+        let tool = match backend {
+            OcrBackend::Gpu => {
+                limits.pass_env.extend(GPU_ENV.iter().map(|name| name.to_string()));
+                limits.limit_address_space = false;
+                command_or(&self.gpu_tool, DEFAULT_OCR_TOOL)
+            }
+            _ => command_or(&self.tool, DEFAULT_OCR_TOOL),
+        };
+        let workers = self.pool_size(backend, images.len());
+
+        // Workers take the next unread page until none are left; results land in page order
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<PageRun>>> = Mutex::new(images.iter().map(|_| None).collect());
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(image) = images.get(index) else { break };
+                    profile_span!("ocr_page");
+                    let page_started = Instant::now();
+                    let command: Vec<String> = tool.iter().map(|arg| arg.replace(INPUT_PLACEHOLDER, &image.to_string_lossy())).collect();
+                    let page_dir = work_dir.join(format!("ocr-{}", index + 1));
+                    let output = std::fs::create_dir_all(&page_dir)
+                        .map_err(|e| e.to_string())
+                        .and_then(|_| run_sandboxed(&command, &limits, &page_dir));
+                    let _ = std::fs::remove_dir_all(&page_dir);
+                    if let Ok(mut results) = results.lock() {
+                        results[index] = Some((output, page_started.elapsed().as_millis()));
+                    }
+                });
+            }
+        });
+
+        let mut bytes = Vec::new();
+        let mut pages = Vec::new();
+        for (index, result) in results.into_inner().map_err(|e| e.to_string())?.into_iter().enumerate() {
+            let page = index + 1;
+            let (output, latency_ms) = result.unwrap_or_else(|| (Err("OCR did not run".to_string()), 0));
+            let mut page_warning = |message: String| {
+                let line = warnings.len() + 1;
+                warnings.push(ConverterWarning { source: path.to_string(), tool: tool[0].clone(), line, message: format!("page {}: {}", page, message) });
+            };
+            if index > 0 {
+                bytes.push(PAGE_BREAK as u8);
+            }
+            match output {
+                Ok(output) => {
+                    output.stderr.into_iter().for_each(&mut page_warning);
+                    // Tesseract ends each page with its own form feed; only ours may split pages
+                    bytes.extend(output.stdout.into_iter().filter(|b| *b != PAGE_BREAK as u8));
+                }
+                Err(e) => page_warning(e),
+            }
+            pages.push(OcrPage { page, latency_ms });
+        }
+
+        let metrics = OcrMetrics { backend: backend.name(), workers, rasterize_ms, wall_ms: started.elapsed().as_millis(), pages };
+        Ok(ConvertedText { bytes, warnings, ocr: Some(metrics) })
+    }
+}
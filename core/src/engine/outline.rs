@@ -0,0 +1,299 @@
+use pyo3::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::splitter::split_pages;
+use crate::pdf::document::{PdfDocument, PdfObject};
+
+// Outlines nested deeper than this are taken as a reference cycle, as are name trees
+const MAX_OUTLINE_DEPTH: usize = 32;
+// Named destinations may point at one another; real ones are a single hop
+const MAX_DESTINATION_HOPS: usize = 4;
+// A heading line shorter than this is too generic to stand for the start of a bookmark title
+const MIN_WRAPPED_HEADING: usize = 8;
+
+// One bookmark of a PDF's outline, in outline order
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub title: String,
+    // 1 for the outline's top level
+    pub level: usize,
+    // 0-based page index; None when the destination is missing or in another document
+    pub page: Option<usize>,
+}
+
+struct OutlineReader<'a> {
+    document: &'a PdfDocument,
+    page_index: HashMap<u32, usize>,
+    // Destinations by name, from the catalog's /Dests and the /Dests name tree
+    named: HashMap<Vec<u8>, PdfObject>,
+    visited: HashSet<u32>,
+    bookmarks: Vec<Bookmark>,
+}
+
+impl OutlineReader<'_> {
+    fn collect_names(&mut self, node: &PdfObject, depth: usize) {
+        if depth > MAX_OUTLINE_DEPTH {
+            return;
+        }
+        let Ok(node) = self.document.resolve(node) else { return };
+        if let Some(PdfObject::Array(pairs)) = node.get("Names").map(|names| self.document.resolve(names).unwrap_or(PdfObject::Null)) {
+            for pair in pairs.chunks_exact(2) {
+                if let Some(name) = pair[0].as_bytes() {
+                    self.named.insert(name, pair[1].clone());
+                }
+            }
+        }
+        if let Some(PdfObject::Array(kids)) = node.get("Kids").map(|kids| self.document.resolve(kids).unwrap_or(PdfObject::Null)) {
+            for kid in &kids {
+                self.collect_names(kid, depth + 1);
+            }
+        }
+    }
+
+    fn destination_page(&self, destination: &PdfObject, hops: usize) -> Option<usize> {
+        if hops > MAX_DESTINATION_HOPS {
+            return None;
+        }
+        match self.document.resolve(destination).ok()? {
+            PdfObject::Array(items) => match items.first()? {
+                PdfObject::Reference(number, _) => self.page_index.get(number).copied(),
+                // Some writers give the page index where a page reference belongs
+                other => other.as_number().map(|index| index as usize).filter(|index| *index < self.page_index.len()),
+            },
+            // A named destination's value may be a dictionary with the destination under /D
+            dictionary @ PdfObject::Dictionary(_) => self.destination_page(dictionary.get("D")?, hops + 1),
+            PdfObject::Name(name) => self.destination_page(self.named.get(name.as_bytes())?, hops + 1),
+            string @ PdfObject::String(_) => self.destination_page(self.named.get(&string.as_bytes()?)?, hops + 1),
+            _ => None,
+        }
+    }
+
+    // The page a bookmark goes to: its /Dest, or the destination of its GoTo action
+    fn item_page(&self, item: &PdfObject) -> Option<usize> {
+        if let Some(destination) = item.get("Dest") {
+            return self.destination_page(destination, 0);
+        }
+        let action = self.document.resolve(item.get("A")?).ok()?;
+        match action.get("S").and_then(PdfObject::as_name) {
+            Some("GoTo") => self.destination_page(action.get("D")?, 0),
+            _ => None,
+        }
+    }
+
+    fn read_items(&mut self, first: Option<&PdfObject>, level: usize) {
+        if level > MAX_OUTLINE_DEPTH {
+            return;
+        }
+        let mut next = first.cloned();
+        while let Some(PdfObject::Reference(number, _)) = next {
+            if !self.visited.insert(number) {
+                break;
+            }
+            let Ok(item) = self.document.object(number) else { break };
+            let title = item.get("Title")
+                .and_then(|title| self.document.resolve(title).ok())
+                .and_then(|title| title.as_text())
+                .unwrap_or_default();
+            let title = title.split(|c: char| c.is_whitespace() || c.is_control()).filter(|word| !word.is_empty()).collect::<Vec<_>>().join(" ");
+            let page = self.item_page(&item);
+            self.bookmarks.push(Bookmark { title, level, page });
+            self.read_items(item.get("First"), level + 1);
+            next = item.get("Next").cloned();
+        }
+    }
+}
+
+// Every bookmark of the document's outline, depth first. A document without an outline has none
+pub fn read_outline(document: &PdfDocument) -> Result<Vec<Bookmark>, String> {
+    let page_index = document.pages()?.iter().enumerate().map(|(index, page)| (page.number, index)).collect();
+    let catalog = document.catalog()?;
+    let mut reader = OutlineReader { document, page_index, named: HashMap::new(), visited: HashSet::new(), bookmarks: Vec::new() };
+    if let Some(PdfObject::Dictionary(entries)) = catalog.get("Dests").map(|dests| document.resolve(dests)).transpose()? {
+        for (name, destination) in entries {
+            reader.named.insert(name.into_bytes(), destination);
+        }
+    }
+    let names = catalog.get("Names").map(|names| document.resolve(names)).transpose()?.unwrap_or(PdfObject::Null);
+    if let Some(tree) = names.get("Dests") {
+        reader.collect_names(tree, 0);
+    }
+    let outlines = catalog.get("Outlines").map(|outlines| document.resolve(outlines)).transpose()?.unwrap_or(PdfObject::Null);
+    reader.read_items(outlines.get("First"), 1);
+    Ok(reader.bookmarks)
+}
+
+// A run of the text from one bookmark to the next
+#[derive(Debug, Clone)]
+pub struct OutlineSection {
+    pub index: usize,
+    // None for the text before the first bookmark
+    pub title: Option<String>,
+    // 0 for the text before the first bookmark
+    pub level: usize,
+    // Titles of the enclosing bookmarks, outermost first
+    pub path: Vec<String>,
+    // 1-based, inclusive
+    pub start_page: usize,
+    pub end_page: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+    // Whether the title was found on the bookmark's page; the section otherwise starts at the
+    // top of the page
+    pub heading_found: bool,
+}
+
+impl OutlineSection {
+    pub fn get_info(&self, text: &str) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("index".to_string(), self.index.to_string());
+        info.insert("level".to_string(), self.level.to_string());
+        info.insert("path".to_string(), self.path.join(" > "));
+        info.insert("start_page".to_string(), self.start_page.to_string());
+        info.insert("end_page".to_string(), self.end_page.to_string());
+        info.insert("start_offset".to_string(), self.start_offset.to_string());
+        info.insert("end_offset".to_string(), self.end_offset.to_string());
+        info.insert("heading_found".to_string(), self.heading_found.to_string());
+        if let Some(title) = &self.title {
+            info.insert("title".to_string(), title.clone());
+        }
+        info.insert("text".to_string(), text[self.start_offset..self.end_offset].to_string());
+        info
+    }
+}
+
+fn normalized(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Start of the first line in `text[from..to]` holding the title, or the first line of a title
+// wrapped over several. Bookmark titles are often the heading as printed, minus its layout
+fn find_heading(text: &str, from: usize, to: usize, title: &str) -> Option<(usize, usize)> {
+    let title = normalized(title);
+    if title.is_empty() {
+        return None;
+    }
+    let mut start = from;
+    for line in text[from..to].split_inclusive('\n') {
+        let content = normalized(line);
+        let wrapped = content.len() >= MIN_WRAPPED_HEADING && title.starts_with(&content);
+        if !content.is_empty() && (content.contains(&title) || wrapped) {
+            return Some((start, start + line.len()));
+        }
+        start += line.len();
+    }
+    None
+}
+
+// Cuts `text` - a PDF's text layer, a form feed after every page - where its bookmarks point.
+// Bookmarks deeper than max_level are left inside their parent's section. Sections follow the
+// page order of the text even where the outline does not
+pub fn outline_sections(text: &str, bookmarks: &[Bookmark], max_level: usize) -> Vec<OutlineSection> {
+    let pages = split_pages(text);
+    let page_of = |offset: usize| pages.partition_point(|(start, _)| *start <= offset).max(1);
+
+    let mut enclosing: Vec<String> = Vec::new();
+    let mut targets = Vec::new();
+    for bookmark in bookmarks {
+        enclosing.truncate(bookmark.level.saturating_sub(1));
+        let path = enclosing.clone();
+        enclosing.push(bookmark.title.clone());
+        match bookmark.page {
+            Some(page) if bookmark.level <= max_level && page < pages.len() => targets.push((page, bookmark, path)),
+            _ => {}
+        }
+    }
+    targets.sort_by_key(|(page, _, _)| *page);
+
+    // (start, bookmark, path, heading found)
+    let mut starts: Vec<(usize, &Bookmark, Vec<String>, bool)> = Vec::new();
+    // Where the previous section's heading line ends: the next heading is looked for after it
+    let mut floor = 0;
+    for (page, bookmark, path) in targets {
+        let (page_start, page_text) = pages[page];
+        let from = floor.max(page_start);
+        let to = page_start + page_text.len();
+        let (start, found) = match find_heading(text, from.min(to), to, &bookmark.title) {
+            Some((start, line_end)) => {
+                floor = line_end;
+                (start, true)
+            }
+            None => {
+                floor = from;
+                (from, false)
+            }
+        };
+        // Bookmarks that land on one spot, such as a chapter and its first task on the same
+        // page, make one section under the innermost
+        if starts.last().is_some_and(|(previous, _, _, _)| *previous == start) {
+            starts.pop();
+        }
+        starts.push((start, bookmark, path, found));
+    }
+
+    let mut sections = Vec::new();
+    let first = starts.first().map(|(start, _, _, _)| *start).unwrap_or(text.len());
+    if !text[..first].trim().is_empty() {
+        sections.push(OutlineSection {
+            index: 0,
+            title: None,
+            level: 0,
+            path: Vec::new(),
+            start_page: 1,
+            end_page: page_of(first.saturating_sub(1)),
+            start_offset: 0,
+            end_offset: first,
+            heading_found: false,
+        });
+    }
+    for (position, (start, bookmark, path, found)) in starts.iter().enumerate() {
+        let end = starts.get(position + 1).map(|(next, _, _, _)| *next).unwrap_or(text.len());
+        sections.push(OutlineSection {
+            index: sections.len(),
+            title: Some(bookmark.title.clone()),
+            level: bookmark.level,
+            path: path.clone(),
+            start_page: page_of(*start),
+            end_page: page_of(end.saturating_sub(1).max(*start)),
+            start_offset: *start,
+            end_offset: end,
+            heading_found: *found,
+        });
+    }
+    sections
+}
+
+// Python binding - sections of a PDF cut at its bookmarks rather than at heading patterns,
+// each with the modules extracted from it. Module offsets are within the section's text.
+// max_level=1 keeps to the outline's top level, typically chapters
+#[pyfunction]
+#[pyo3(signature = (pdf_path, rules_path=None, max_level=None))]
+pub fn split_by_outline(py: Python, pdf_path: &str, rules_path: Option<&str>, max_level: Option<usize>) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        if max_level == Some(0) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("max_level counts from 1, the outline's top level"));
+        }
+        let to_runtime_err = |e: String| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e);
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path).map_err(|e| to_runtime_err(e.to_string()))?,
+            None => ExtractionEngine::new(),
+        };
+        let document = PdfDocument::read(pdf_path).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let bookmarks = read_outline(&document).map_err(|e| to_runtime_err(format!("{}: {}", pdf_path, e)))?;
+        if bookmarks.iter().all(|bookmark| bookmark.page.is_none()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{} has no bookmarks to split by", pdf_path)));
+        }
+        let text = engine.read_input(pdf_path).map_err(|e| to_runtime_err(e.to_string()))?.text;
+
+        let sections = outline_sections(&text, &bookmarks, max_level.unwrap_or(usize::MAX));
+        sections.iter()
+            .map(|section| {
+                let modules = engine.extract_modules(&text[section.start_offset..section.end_offset]);
+                let mut info = section.get_info(&text);
+                info.insert("module_count".to_string(), modules.len().to_string());
+                info.insert("modules".to_string(), serde_json::to_string(&modules).map_err(|e| to_runtime_err(e.to_string()))?);
+                Ok(info)
+            })
+            .collect()
+    })
+}
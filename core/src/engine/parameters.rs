@@ -0,0 +1,406 @@
+use pyo3::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::engine::conditions::strip_item_marker;
+use crate::engine::entities::record_extents;
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::ids::stable_record_id;
+use crate::engine::scope::scope_from_args;
+
+// What a parameter sets - the nearest of these before a table reference or blank names it
+const QUANTITY_WORDS: &[&str] = &[
+    "torque", "pressure", "clearance", "gap", "dimension", "temperature", "tolerance", "length",
+    "force", "load", "backlash", "resistance", "voltage", "current", "tension", "preload", "travel",
+    "angle", "thickness", "weight", "quantity", "wear", "runout", "play", "rate", "speed", "level",
+];
+
+// Names of last resort, used when no quantity word comes before them
+const GENERIC_WORDS: &[&str] = &["value", "limit", "setting"];
+
+// Words that introduce a lookup: "Table 201", "Figure 401", "Fig. 2"
+const REFERENCE_WORDS: &[(&str, &str)] = &[
+    ("table", "Table"),
+    ("figure", "Figure"),
+    ("fig", "Figure"),
+    ("chart", "Chart"),
+    ("graph", "Graph"),
+];
+
+// Unfilled values left in the text
+const UNKNOWN_VALUES: &[&str] = &["TBD", "TBA", "TBC"];
+
+// A dash number like "-200" is a variant only in a sentence using one of these
+const VARIANT_WORDS: &[&str] = &[
+    "variant", "aircraft", "airplane", "model", "series", "version", "configuration", "effectivity",
+    "applicable", "applicability",
+];
+
+// Wording that limits a variant marker to everything but the variants it names
+const EXCLUSION_PHRASES: &[&str] = &["except", "not applicable", "other than", "not for", "excluding"];
+
+const MAX_PLACEHOLDER_LENGTH: usize = 40;
+const MAX_DASH_DIGITS: usize = 4;
+const MAX_DASH_SUFFIX: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct StepParameter {
+    // "parameter" or "variant"
+    pub marker: &'static str,
+    // reference, placeholder / dash_number, service_bulletin, modification
+    pub kind: &'static str,
+    // What a parameter sets; None for variant markers
+    pub quantity: Option<String>,
+    pub value: String,
+    // Slot name for procedure templates: "torque_table_201", "variant_200"
+    pub name: String,
+    // only, except or applies; None for parameters
+    pub applicability: Option<&'static str>,
+    pub sentence: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+// "Table 201" -> "table_201"
+fn slot_name(parts: &[&str]) -> String {
+    let joined = parts.join(" ").to_lowercase();
+    joined.split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+// Words with their offsets, split on anything that is not a letter or digit
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut word_start = None;
+    for (offset, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        if c.is_alphanumeric() {
+            word_start.get_or_insert(offset);
+        } else if let Some(start) = word_start.take() {
+            words.push((start, &text[start..offset]));
+        }
+    }
+    words
+}
+
+// (start, end) of every sentence. Item markers, headings and blank lines start a new one, and
+// ".", ";", "!" or "?" before whitespace ends one - lines wrapped inside an item stay together
+fn sentences(text: &str) -> Vec<(usize, usize)> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    for raw in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += raw.len();
+        let line = raw.trim_matches(|c: char| c.is_whitespace() || c == '\x0c');
+        if line.is_empty() || strip_item_marker(line).is_some() || line.starts_with("TASK ") {
+            if start < line_start {
+                sentences.push((start, line_start));
+            }
+            start = line_start;
+        }
+        let bytes = raw.as_bytes();
+        for (index, c) in raw.char_indices() {
+            if matches!(c, '.' | ';' | '!' | '?') && bytes.get(index + 1).is_none_or(|next| next.is_ascii_whitespace()) {
+                let end = line_start + index + 1;
+                if start < end {
+                    sentences.push((start, end));
+                }
+                start = end;
+            }
+        }
+    }
+    if start < text.len() {
+        sentences.push((start, text.len()));
+    }
+    sentences
+}
+
+fn sentence_at(sentences: &[(usize, usize)], offset: usize) -> (usize, usize) {
+    sentences.iter()
+        .find(|(start, end)| offset >= *start && offset < *end)
+        .copied()
+        .unwrap_or((offset, offset))
+}
+
+fn singular(word: &str) -> String {
+    let lower = word.to_lowercase();
+    match lower.strip_suffix('s') {
+        Some(stem) if QUANTITY_WORDS.contains(&stem) || GENERIC_WORDS.contains(&stem) || VARIANT_WORDS.contains(&stem) => stem.to_string(),
+        _ => lower,
+    }
+}
+
+// The quantity named closest before `offset` in its sentence, preferring a specific word
+// ("torque") over a generic one ("value") anywhere before it
+fn quantity_before(text: &str, sentence: (usize, usize), offset: usize) -> Option<String> {
+    let before: Vec<String> = words(&text[sentence.0..offset]).into_iter().map(|(_, word)| singular(word)).collect();
+    before.iter().rev().find(|word| QUANTITY_WORDS.contains(&word.as_str()))
+        .or_else(|| before.iter().rev().find(|word| GENERIC_WORDS.contains(&word.as_str())))
+        .cloned()
+}
+
+// Identifier after a reference word: "201", "201A", "2-1", "A" - returns (id, end offset)
+fn reference_id(text: &str, from: usize) -> Option<(&str, usize)> {
+    let rest = &text[from..];
+    let after_dot = rest.strip_prefix('.').unwrap_or(rest);
+    let trimmed = after_dot.trim_start_matches([' ', '\t']);
+    if trimmed.len() == after_dot.len() {
+        return None;
+    }
+    let id_start = from + (rest.len() - trimmed.len());
+    let first = trimmed.chars().next()?;
+    if !first.is_ascii_digit() && !first.is_ascii_uppercase() {
+        return None;
+    }
+    let length = trimmed.find(|c: char| !c.is_ascii_alphanumeric() && c != '-').unwrap_or(trimmed.len());
+    let id = trimmed[..length].trim_end_matches('-');
+    // A lone capital is a reference only as a table letter, not the start of the next word
+    if id.is_empty() || id.len() > 12 || (!id.chars().any(|c| c.is_ascii_digit()) && id.len() > 1) {
+        return None;
+    }
+    Some((id, id_start + id.len()))
+}
+
+fn parameter(kind: &'static str, quantity: Option<String>, value: &str, name: String, start: usize, end: usize) -> StepParameter {
+    StepParameter {
+        marker: "parameter",
+        kind,
+        quantity,
+        value: value.to_string(),
+        name,
+        applicability: None,
+        sentence: String::new(),
+        start,
+        end,
+    }
+}
+
+fn variant(kind: &'static str, value: String, name: String, start: usize, end: usize) -> StepParameter {
+    StepParameter { marker: "variant", kind, quantity: None, value, name, applicability: None, sentence: String::new(), start, end }
+}
+
+// "Tighten to the torque given in Table 201": a value looked up elsewhere. Plain cross
+// references ("Refer to Figure 401") name no quantity and are left alone
+fn find_references(text: &str, sentences: &[(usize, usize)], found: &mut Vec<StepParameter>) {
+    for (start, word) in words(text) {
+        let lower = word.to_lowercase();
+        let canonical = match REFERENCE_WORDS.iter().find(|(reference, _)| *reference == lower) {
+            Some((_, canonical)) => *canonical,
+            None => continue,
+        };
+        let (id, end) = match reference_id(text, start + word.len()) {
+            Some(id) => id,
+            None => continue,
+        };
+        let quantity = match quantity_before(text, sentence_at(sentences, start), start) {
+            Some(quantity) => quantity,
+            None => continue,
+        };
+        let value = format!("{} {}", canonical, id);
+        let name = slot_name(&[&quantity, &value]);
+        found.push(parameter("reference", Some(quantity), &value, name, start, end));
+    }
+}
+
+// "<serial number>", "[part number]", "____" and "TBD": slots the text leaves to be filled in
+fn find_placeholders(text: &str, sentences: &[(usize, usize)], found: &mut Vec<StepParameter>) {
+    for (start, c) in text.char_indices() {
+        let close = match c {
+            '<' => '>',
+            '[' => ']',
+            '_' if !text[..start].ends_with('_') => {
+                let length = text[start..].find(|c: char| c != '_').unwrap_or(text.len() - start);
+                if length >= 3 {
+                    let quantity = quantity_before(text, sentence_at(sentences, start), start).unwrap_or_else(|| "value".to_string());
+                    let name = slot_name(&[&quantity]);
+                    found.push(parameter("placeholder", Some(quantity), &text[start..start + length], name, start, start + length));
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        let inner_start = start + 1;
+        let inner_length = match text[inner_start..].find([close, '\n', '<', '[']) {
+            Some(length) if text[inner_start + length..].starts_with(close) => length,
+            _ => continue,
+        };
+        let inner = &text[inner_start..inner_start + inner_length];
+        // "x < 5 and y > 3" is a comparison, "[A]" a note letter
+        if inner.len() > MAX_PLACEHOLDER_LENGTH || inner.trim() != inner || inner.chars().filter(|c| c.is_alphabetic()).count() < 2 {
+            continue;
+        }
+        let end = inner_start + inner_length + 1;
+        found.push(parameter("placeholder", None, &text[start..end], slot_name(&[inner]), start, end));
+    }
+    for (start, word) in words(text) {
+        if UNKNOWN_VALUES.contains(&word) {
+            let quantity = quantity_before(text, sentence_at(sentences, start), start).unwrap_or_else(|| "value".to_string());
+            let name = slot_name(&[&quantity]);
+            found.push(parameter("placeholder", Some(quantity), word, name, start, start + word.len()));
+        }
+    }
+}
+
+// "-200", "-300ER" - the dash number's end, when one starts at `start`
+fn dash_number_end(text: &str, start: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    if bytes[start] != b'-' || text[..start].chars().next_back().is_some_and(|c| !matches!(c, ' ' | '\t' | '\n' | '(' | ',' | '/')) {
+        return None;
+    }
+    let digits = bytes[start + 1..].iter().take_while(|b| b.is_ascii_digit()).count();
+    if digits == 0 || digits > MAX_DASH_DIGITS {
+        return None;
+    }
+    let suffix = bytes[start + 1 + digits..].iter().take_while(|b| b.is_ascii_uppercase()).count();
+    let end = start + 1 + digits + suffix;
+    // Temperatures, percentages and decimals are not variants
+    let rest = &text[end..];
+    let next = rest.chars().next();
+    if suffix > MAX_DASH_SUFFIX
+        || next.is_some_and(|c| c.is_alphanumeric() || c == '°' || c == '%')
+        || (rest.starts_with('.') && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+    {
+        return None;
+    }
+    Some(end)
+}
+
+// "applicable to -200 variants only", "pre-SB 32-1234", "post-mod 12345": text that applies
+// to some aircraft configurations and not others
+fn find_variants(text: &str, sentences: &[(usize, usize)], found: &mut Vec<StepParameter>) {
+    for (start, _) in text.match_indices('-') {
+        let end = match dash_number_end(text, start) {
+            Some(end) => end,
+            None => continue,
+        };
+        let sentence = sentence_at(sentences, start);
+        let in_context = words(&text[sentence.0..sentence.1]).iter().any(|(_, word)| VARIANT_WORDS.contains(&singular(word).as_str()));
+        if in_context {
+            let value = &text[start..end];
+            found.push(variant("dash_number", value.to_string(), slot_name(&["variant", value]), start, end));
+        }
+    }
+
+    let all_words = words(text);
+    for (index, (start, word)) in all_words.iter().enumerate() {
+        let side = word.to_lowercase();
+        if side != "pre" && side != "post" {
+            continue;
+        }
+        let (kind_start, kind_word) = match all_words.get(index + 1) {
+            Some(next) => *next,
+            None => continue,
+        };
+        // "pre-SB" or "pre SB", nothing else between them
+        if !matches!(&text[start + word.len()..kind_start], "-" | " ") {
+            continue;
+        }
+        let (kind, label) = match kind_word.to_lowercase().as_str() {
+            "sb" => ("service_bulletin", "SB"),
+            "mod" | "modification" => ("modification", "mod"),
+            _ => continue,
+        };
+        let (id, end) = match reference_id(text, kind_start + kind_word.len()) {
+            Some(id) => id,
+            None => continue,
+        };
+        let value = format!("{}-{} {}", side, label, id);
+        let name = slot_name(&[&value]);
+        found.push(variant(kind, value, name, *start, end));
+    }
+}
+
+fn applicability(sentence: &str) -> &'static str {
+    let lower = sentence.to_lowercase();
+    if EXCLUSION_PHRASES.iter().any(|phrase| lower.contains(phrase)) {
+        "except"
+    } else if words(&lower).iter().any(|(_, word)| *word == "only") {
+        "only"
+    } else {
+        "applies"
+    }
+}
+
+// Parameter slots and variant markers in document order, each with the sentence holding it
+pub fn find_parameters(text: &str) -> Vec<StepParameter> {
+    let sentences = sentences(text);
+    let mut found = Vec::new();
+    find_references(text, &sentences, &mut found);
+    find_placeholders(text, &sentences, &mut found);
+    find_variants(text, &sentences, &mut found);
+    found.sort_by_key(|parameter| parameter.start);
+    for parameter in &mut found {
+        let (start, end) = sentence_at(&sentences, parameter.start);
+        parameter.sentence = text[start..end].trim().to_string();
+        if parameter.marker == "variant" {
+            parameter.applicability = Some(applicability(&parameter.sentence));
+        }
+    }
+    found
+}
+
+// Parameter records linked to the step and module whose extents contain them. A slot repeated
+// within one step is one record, at its first mention
+pub fn parameter_records(text: &str, modules: &[HashMap<String, String>], steps: &[HashMap<String, String>]) -> Vec<HashMap<String, String>> {
+    let module_extents = record_extents(modules, text.len());
+    let step_extents = record_extents(steps, text.len());
+    let containing = |extents: &[(usize, usize, String)], offset: usize| {
+        extents.iter()
+            .find(|(start, end, _)| offset >= *start && offset < *end)
+            .map(|(_, _, id)| id.clone())
+            .unwrap_or_default()
+    };
+    let mut seen = HashSet::new();
+    let mut records = Vec::new();
+    for parameter in find_parameters(text) {
+        let module_id = containing(&module_extents, parameter.start);
+        let step_id = containing(&step_extents, parameter.start);
+        let quantity = parameter.quantity.clone().unwrap_or_default();
+        let record_id = stable_record_id("parameter", &[&module_id, &step_id, parameter.marker, parameter.kind, &quantity, &parameter.value]);
+        if !seen.insert(record_id.clone()) {
+            continue;
+        }
+        let mut record = HashMap::new();
+        record.insert("record_id".to_string(), record_id);
+        record.insert("marker".to_string(), parameter.marker.to_string());
+        record.insert("kind".to_string(), parameter.kind.to_string());
+        if let Some(quantity) = parameter.quantity {
+            record.insert("quantity".to_string(), quantity);
+        }
+        if let Some(applicability) = parameter.applicability {
+            record.insert("applicability".to_string(), applicability.to_string());
+        }
+        record.insert("value".to_string(), parameter.value);
+        record.insert("name".to_string(), parameter.name);
+        record.insert("text".to_string(), parameter.sentence);
+        record.insert("step_id".to_string(), step_id);
+        record.insert("module_id".to_string(), module_id);
+        record.insert("original_start".to_string(), parameter.start.to_string());
+        record.insert("original_end".to_string(), parameter.end.to_string());
+        records.push(record);
+    }
+    records
+}
+
+// Python binding - table lookups, fill-in slots and variant applicability in step text
+#[pyfunction]
+#[pyo3(signature = (text, rules_path=None, pages=None, sections=None))]
+pub fn extract_parameters(
+    py: Python,
+    text: &str,
+    rules_path: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => ExtractionEngine::new(),
+        };
+        let text = scope_from_args(pages, sections)?.apply(&engine, text)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(engine.extract_parameters(&text))
+    })
+}
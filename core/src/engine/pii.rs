@@ -0,0 +1,381 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::engine::extractor::{find_matches, ExtractionEngine};
+use crate::engine::ids::stable_record_id;
+
+pub const EMAIL_DETECTOR: &str = "email";
+pub const PHONE_DETECTOR: &str = "phone";
+pub const EMPLOYEE_ID_DETECTOR: &str = "employee_id";
+pub const NAME_DETECTOR: &str = "name";
+pub const PII_DETECTORS: &[&str] = &[EMAIL_DETECTOR, PHONE_DETECTOR, EMPLOYEE_ID_DETECTOR, NAME_DETECTOR];
+
+// Sign-off labels a person's name follows on engineering orders and task cards
+pub const DEFAULT_NAME_LABELS: &[&str] = &[
+    "inspected by", "performed by", "prepared by", "approved by", "checked by", "signed by",
+    "inspector", "technician", "mechanic", "originator", "contact",
+];
+
+// Digits in a phone number: "555-1234" up to an international E.164 number
+const MIN_PHONE_DIGITS: usize = 7;
+const MAX_PHONE_DIGITS: usize = 15;
+// Local numbers shorter than this need the "555-1234" shape
+const MIN_UNSHAPED_PHONE_DIGITS: usize = 10;
+const MIN_EMPLOYEE_ID_DIGITS: usize = 3;
+
+fn default_replacement() -> String {
+    "[REDACTED]".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PiiConfig {
+    // Detectors to run, from PII_DETECTORS; none disables scanning
+    #[serde(default)]
+    pub detectors: Vec<String>,
+    // Employee ID prefixes followed by digits, e.g. ["EMP", "E#"]
+    #[serde(default)]
+    pub employee_id_prefixes: Vec<String>,
+    // Labels that precede a name, e.g. "Inspected by:"; the built-in sign-off labels when empty
+    #[serde(default)]
+    pub name_labels: Vec<String>,
+    // Known names, e.g. a staff list, matched wherever they appear
+    #[serde(default)]
+    pub names: Vec<String>,
+    // Replace findings in every record written to an output
+    #[serde(default)]
+    pub redact: bool,
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+}
+
+impl Default for PiiConfig {
+    fn default() -> Self {
+        Self {
+            detectors: Vec::new(),
+            employee_id_prefixes: Vec::new(),
+            name_labels: Vec::new(),
+            names: Vec::new(),
+            redact: false,
+            replacement: default_replacement(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiFinding {
+    pub detector: &'static str,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Runs of non-whitespace with their offsets, stripped of surrounding punctuation
+fn tokens(text: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (c.is_whitespace(), start) {
+            (true, Some(begin)) => {
+                let token: &str = &text[begin..index];
+                let trimmed = token.trim_start_matches(|c: char| !c.is_alphanumeric() && c != '+');
+                let offset = begin + token.len() - trimmed.len();
+                tokens.push((offset, trimmed.trim_end_matches(|c: char| !c.is_alphanumeric())));
+                start = None;
+            }
+            (false, None) => start = Some(index),
+            _ => {}
+        }
+    }
+    tokens.into_iter().filter(|(_, token)| !token.is_empty()).collect()
+}
+
+fn is_email(token: &str) -> bool {
+    let Some((local, domain)) = token.split_once('@') else { return false };
+    let domain_labels: Vec<&str> = domain.split('.').collect();
+    !local.is_empty()
+        && local.chars().all(|c| c.is_alphanumeric() || "._%+-".contains(c))
+        && domain_labels.len() >= 2
+        && domain_labels.iter().all(|label| !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-'))
+}
+
+fn find_emails(text: &str) -> Vec<PiiFinding> {
+    tokens(text).into_iter()
+        .filter(|(_, token)| is_email(token))
+        .map(|(start, token)| PiiFinding { detector: EMAIL_DETECTOR, start, end: start + token.len() })
+        .collect()
+}
+
+// Digit groups joined by spaces, dashes, dots or parentheses, on one line. Without a "+", a
+// leading two-digit group is an ATA or part number ("36-11-00-000-801") and 4-2 is a date
+fn phone_at(text: &str, start: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut end = start;
+    let mut digits = 0;
+    let mut groups: Vec<usize> = vec![0];
+    while end < bytes.len() {
+        let b = bytes[end];
+        if b.is_ascii_digit() {
+            digits += 1;
+            *groups.last_mut()? += 1;
+        } else if b"-. ()".contains(&b) || (b == b'+' && end == start) {
+            if groups.last() != Some(&0) {
+                groups.push(0);
+            }
+        } else {
+            break;
+        }
+        end += 1;
+    }
+    while end > start && !bytes[end - 1].is_ascii_digit() {
+        end -= 1;
+    }
+    groups.retain(|group| *group > 0);
+    let international = bytes[start] == b'+';
+    let shaped = groups == [3, 4];
+    let plausible = (MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&digits)
+        && groups.len() >= 2
+        && (international || shaped || digits >= MIN_UNSHAPED_PHONE_DIGITS)
+        && (international || groups[0] != 2 && !groups.starts_with(&[4, 2]));
+    plausible.then_some(end)
+}
+
+fn find_phones(text: &str) -> Vec<PiiFinding> {
+    let bytes = text.as_bytes();
+    let mut findings = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let b = bytes[index];
+        let boundary = index == 0 || !bytes[index - 1].is_ascii_alphanumeric() && !b"-.+".contains(&bytes[index - 1]);
+        if boundary && (b.is_ascii_digit() || b == b'+' || b == b'(') {
+            if let Some(end) = phone_at(text, index) {
+                let after = bytes.get(end).copied();
+                if after.is_none_or(|after| !after.is_ascii_alphanumeric()) {
+                    findings.push(PiiFinding { detector: PHONE_DETECTOR, start: index, end });
+                    index = end;
+                    continue;
+                }
+            }
+        }
+        index += 1;
+    }
+    findings
+}
+
+fn find_employee_ids(text: &str, prefixes: &[String]) -> Vec<PiiFinding> {
+    tokens(text).into_iter()
+        .filter(|(_, token)| {
+            prefixes.iter().any(|prefix| {
+                token.strip_prefix(prefix.as_str())
+                    .map(|rest| rest.trim_start_matches(['-', '#']))
+                    .is_some_and(|digits| digits.len() >= MIN_EMPLOYEE_ID_DIGITS && digits.chars().all(|c| c.is_ascii_digit()))
+            })
+        })
+        .map(|(start, token)| PiiFinding { detector: EMPLOYEE_ID_DETECTOR, start, end: start + token.len() })
+        .collect()
+}
+
+// "Smith", "J." or "O'Neil" - a capitalized word or an initial
+fn is_name_word(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(char::is_uppercase)
+        && chars.all(|c| c.is_alphabetic() || c == '\'' || c == '-' || c == '.')
+}
+
+// Two or three name words after a sign-off label, e.g. "Inspected by: J. Smith"
+fn find_labelled_names(text: &str, labels: &[String]) -> Vec<PiiFinding> {
+    let lower = text.to_lowercase();
+    // Lowercasing can change byte lengths; labels are matched only when it did not
+    if lower.len() != text.len() {
+        return Vec::new();
+    }
+    let mut findings = Vec::new();
+    for label in labels {
+        let label = label.to_lowercase();
+        for (label_start, _) in lower.match_indices(&label) {
+            let label_end = label_start + label.len();
+            let (Some(before), Some(rest)) = (text.get(..label_start), text.get(label_end..)) else { continue };
+            let before_ok = before.chars().next_back().is_none_or(|c| !c.is_alphanumeric());
+            let line = rest.split('\n').next().unwrap_or_default();
+            let skipped = line.len() - line.trim_start_matches([':', ' ', '\t']).len();
+            if !before_ok || skipped == 0 {
+                continue;
+            }
+            let name_tokens: Vec<(usize, &str)> = tokens(&line[skipped..]).into_iter()
+                .take(3)
+                .take_while(|(_, word)| is_name_word(word))
+                .collect();
+            if name_tokens.len() >= 2 {
+                let (first, _) = name_tokens[0];
+                let (last, word) = name_tokens[name_tokens.len() - 1];
+                let base = label_end + skipped;
+                findings.push(PiiFinding { detector: NAME_DETECTOR, start: base + first, end: base + last + word.len() });
+            }
+        }
+    }
+    findings
+}
+
+impl PiiConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.detectors.is_empty()
+    }
+
+    // Every built-in detector, for scanning without a rules profile
+    pub fn builtin() -> Self {
+        Self {
+            detectors: [EMAIL_DETECTOR, PHONE_DETECTOR, NAME_DETECTOR].iter().map(|d| d.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(detector) = self.detectors.iter().find(|detector| !PII_DETECTORS.contains(&detector.as_str())) {
+            return Err(format!("Unknown PII detector {:?} (expected one of {})", detector, PII_DETECTORS.join(", ")));
+        }
+        if self.has(EMPLOYEE_ID_DETECTOR) && self.employee_id_prefixes.iter().all(|prefix| prefix.is_empty()) {
+            return Err("The employee_id PII detector needs employee_id_prefixes".to_string());
+        }
+        if self.redact && self.replacement.is_empty() {
+            return Err("PII redaction needs a non-empty replacement".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn merge(&mut self, other: PiiConfig) {
+        for (into, from) in [
+            (&mut self.detectors, other.detectors),
+            (&mut self.employee_id_prefixes, other.employee_id_prefixes),
+            (&mut self.name_labels, other.name_labels),
+            (&mut self.names, other.names),
+        ] {
+            for value in from {
+                if !into.contains(&value) {
+                    into.push(value);
+                }
+            }
+        }
+        // Any pack asking for redaction gets it
+        if other.redact {
+            self.redact = true;
+            self.replacement = other.replacement;
+        }
+    }
+
+    fn has(&self, detector: &str) -> bool {
+        self.detectors.iter().any(|d| d == detector)
+    }
+
+    // Findings in text order; where detectors overlap the earliest, then longest, wins
+    pub fn scan(&self, text: &str) -> Vec<PiiFinding> {
+        let mut findings = Vec::new();
+        if self.has(EMAIL_DETECTOR) {
+            findings.extend(find_emails(text));
+        }
+        if self.has(PHONE_DETECTOR) {
+            findings.extend(find_phones(text));
+        }
+        if self.has(EMPLOYEE_ID_DETECTOR) {
+            findings.extend(find_employee_ids(text, &self.employee_id_prefixes));
+        }
+        if self.has(NAME_DETECTOR) {
+            let defaults: Vec<String>;
+            let labels = if self.name_labels.is_empty() {
+                defaults = DEFAULT_NAME_LABELS.iter().map(|label| label.to_string()).collect();
+                &defaults
+            } else {
+                &self.name_labels
+            };
+            findings.extend(find_labelled_names(text, labels));
+            for name in &self.names {
+                findings.extend(find_matches(name, text).into_iter().map(|(start, end)| PiiFinding { detector: NAME_DETECTOR, start, end }));
+            }
+        }
+
+        findings.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+        let mut kept: Vec<PiiFinding> = Vec::new();
+        for finding in findings {
+            if kept.last().is_none_or(|last| finding.start >= last.end) {
+                kept.push(finding);
+            }
+        }
+        kept
+    }
+}
+
+// First character of each word kept, the rest starred: "j***.*****@****.***"
+pub fn mask(value: &str) -> String {
+    let mut previous_alphanumeric = false;
+    value.chars()
+        .map(|c| {
+            let masked = if c.is_alphanumeric() && previous_alphanumeric { '*' } else { c };
+            previous_alphanumeric = c.is_alphanumeric();
+            masked
+        })
+        .collect()
+}
+
+pub fn pii_record_id(detector: &str, start: usize) -> String {
+    stable_record_id("pii", &[detector, &start.to_string()])
+}
+
+// One record per finding. Records carry the masked value only, so reports can be shared
+pub fn pii_records(text: &str, config: &PiiConfig) -> Vec<HashMap<String, String>> {
+    config.scan(text).into_iter()
+        .map(|finding| {
+            let start = finding.start.to_string();
+            let mut record = HashMap::new();
+            record.insert("record_id".to_string(), pii_record_id(finding.detector, finding.start));
+            record.insert("detector".to_string(), finding.detector.to_string());
+            record.insert("masked".to_string(), mask(&text[finding.start..finding.end]));
+            record.insert("original_start".to_string(), start);
+            record.insert("original_end".to_string(), finding.end.to_string());
+            record
+        })
+        .collect()
+}
+
+// Fields that hold identifiers, offsets or rules patterns rather than document text
+fn is_redactable(key: &str) -> bool {
+    !(key == "record_id" || key == "pattern" || key == "masked" || key == "rule" || key == "rule_pack" || key.ends_with("_id") || key.ends_with("_ids")
+        || key.ends_with("_start") || key.ends_with("_end") || key == "start" || key == "end")
+}
+
+// Replaces every finding's text in the text fields of `records`
+pub fn redact_records(text: &str, config: &PiiConfig, records: &mut [HashMap<String, String>]) {
+    // Canonical entity values are upper-cased, so case variants are replaced too
+    let mut values: Vec<String> = config.scan(text).iter()
+        .map(|finding| &text[finding.start..finding.end])
+        .flat_map(|value| [value.to_string(), value.to_uppercase(), value.to_lowercase()])
+        .collect();
+    if values.is_empty() {
+        return;
+    }
+    // Longest first, so a full name is replaced before a shorter finding inside it
+    values.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    values.dedup();
+    for record in records.iter_mut() {
+        for (key, field) in record.iter_mut() {
+            if is_redactable(key) && values.iter().any(|value| field.contains(value.as_str())) {
+                for value in &values {
+                    *field = field.replace(value.as_str(), &config.replacement);
+                }
+            }
+        }
+    }
+}
+
+// Python binding - the profile's detectors with rules_path, otherwise email, phone and name
+#[pyfunction]
+#[pyo3(signature = (text, rules_path=None))]
+pub fn scan_pii(py: Python, text: &str, rules_path: Option<&str>) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        match rules_path {
+            Some(path) => {
+                let engine = ExtractionEngine::from_rules_file(path)
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+                Ok(engine.scan_pii(text).unwrap_or_default())
+            }
+            None => Ok(pii_records(text, &PiiConfig::builtin())),
+        }
+    })
+}
@@ -0,0 +1,133 @@
+use pyo3::prelude::*;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::schema::envelope::hash_input;
+
+// Rules key naming the base profiles, e.g. "extends": "base.json" or ["base.json", "pii.json"].
+// Paths are relative to the file that names them
+pub const EXTENDS_KEY: &str = "extends";
+
+// A rules file with its bases applied, and the files that went into it in the order applied
+pub struct ResolvedProfile {
+    pub config: Value,
+    pub chain: Vec<String>,
+    // Dotted key -> the file that last set it, for explain_profile
+    pub sources: BTreeMap<String, String>,
+}
+
+fn read_profile(path: &Path) -> Result<Map<String, Value>, Box<dyn std::error::Error>> {
+    let data = std::fs::read(path).map_err(|e| format!("Cannot read rules file {}: {}", path.display(), e))?;
+    match serde_json::from_slice(&data).map_err(|e| format!("Invalid rules file {}: {}", path.display(), e))? {
+        Value::Object(config) => Ok(config),
+        _ => Err(format!("Invalid rules file {}: expected a JSON object", path.display()).into()),
+    }
+}
+
+fn bases(config: &Map<String, Value>, path: &Path) -> Result<Vec<PathBuf>, String> {
+    let names = match config.get(EXTENDS_KEY) {
+        None => return Ok(Vec::new()),
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().map(|name| name.as_str().unwrap_or_default()).collect(),
+        Some(_) => vec![""],
+    };
+    if names.iter().any(|name| name.is_empty()) {
+        return Err(format!("{} in {} must be a rules file path or a list of them", EXTENDS_KEY, path.display()));
+    }
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Ok(names.into_iter().map(|name| dir.join(name)).collect())
+}
+
+// Files in the order they apply: every base before the profiles extending it, each file once.
+// `stack` is the current inheritance path, so a file reached again through it is a cycle
+fn linearize(path: &Path, stack: &mut Vec<PathBuf>, order: &mut Vec<(PathBuf, Map<String, Value>)>) -> Result<(), Box<dyn std::error::Error>> {
+    let canonical = path.canonicalize().map_err(|e| format!("Cannot read rules file {}: {}", path.display(), e))?;
+    if stack.contains(&canonical) {
+        let cycle: Vec<String> = stack.iter().skip_while(|entry| **entry != canonical).chain([&canonical]).map(|entry| entry.display().to_string()).collect();
+        return Err(format!("Profile inheritance cycle: {}", cycle.join(" -> ")).into());
+    }
+    if order.iter().any(|(applied, _)| *applied == canonical) {
+        return Ok(());
+    }
+    let mut config = read_profile(path)?;
+    stack.push(canonical.clone());
+    for base in bases(&config, path)? {
+        linearize(&base, stack, order)?;
+    }
+    stack.pop();
+    config.remove(EXTENDS_KEY);
+    order.push((canonical, config));
+    Ok(())
+}
+
+// JSON merge patch (RFC 7386): objects merge key by key, null removes a key, and any other
+// value - arrays included - replaces what the base had
+fn merge_patch(target: &mut Value, patch: Value, prefix: &str, file: &str, sources: &mut BTreeMap<String, String>) {
+    let Value::Object(patch) = patch else {
+        sources.retain(|key, _| key != prefix && !key.starts_with(&format!("{}.", prefix)));
+        sources.insert(prefix.to_string(), file.to_string());
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        sources.retain(|key, _| key != prefix);
+        *target = Value::Object(Map::new());
+    }
+    for (key, value) in patch {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        let Some(object) = target.as_object_mut() else { return };
+        if value.is_null() {
+            object.remove(&key);
+            sources.retain(|source, _| *source != path && !source.starts_with(&format!("{}.", path)));
+            continue;
+        }
+        merge_patch(object.entry(key).or_insert(Value::Null), value, &path, file, sources);
+    }
+}
+
+// The effective configuration of a rules file: its bases merged in order, then the file itself
+pub fn resolve_profile(path: &str) -> Result<ResolvedProfile, Box<dyn std::error::Error>> {
+    let mut order = Vec::new();
+    linearize(Path::new(path), &mut Vec::new(), &mut order)?;
+    let mut config = Value::Object(Map::new());
+    let mut sources = BTreeMap::new();
+    let mut chain = Vec::new();
+    for (file, patch) in order {
+        let file = file.display().to_string();
+        merge_patch(&mut config, Value::Object(patch), "", &file, &mut sources);
+        chain.push(file);
+    }
+    Ok(ResolvedProfile { config, chain, sources })
+}
+
+// What a run's manifest records for its rules: the file's hash, or for a profile with bases the
+// hash of every file in the chain, so a changed base is noticed too
+pub fn profile_hash(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let resolved = resolve_profile(path)?;
+    if resolved.chain.len() == 1 {
+        let data = std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        return Ok(hash_input(&data));
+    }
+    let mut data = Vec::new();
+    for file in &resolved.chain {
+        data.extend(std::fs::read(file).map_err(|e| format!("Cannot read {}: {}", file, e))?);
+        data.push(0x1f);
+    }
+    Ok(hash_input(&data))
+}
+
+// Python binding - the effective merged rules of a profile as JSON: the files applied, the
+// configuration an engine loads, and which file set each value
+#[pyfunction]
+pub fn explain_profile(rules_path: &str) -> PyResult<String> {
+    let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+    let resolved = resolve_profile(rules_path).map_err(to_py_err)?;
+    let explained = json!({
+        "profile": rules_path,
+        "chain": resolved.chain,
+        "effective": resolved.config,
+        "sources": resolved.sources,
+    });
+    serde_json::to_string_pretty(&explained).map_err(|e| to_py_err(e.into()))
+}
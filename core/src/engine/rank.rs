@@ -0,0 +1,216 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::engine::entities::record_extents;
+use crate::schema::envelope::payload_of;
+
+// Okapi BM25 with the usual parameters
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+// Added per gazetteer or designator entity the query names and the module mentions - a
+// taxonomy hit outweighs a few shared words
+const TAXONOMY_BOOST: f64 = 2.0;
+
+// Record fields whose words go into a module's document
+const TEXT_FIELDS: &[&str] = &["pattern", "matched_text", "text", "value"];
+
+fn words(text: &str) -> Vec<String> {
+    text.unicode_words().map(str::to_lowercase).collect()
+}
+
+fn field<'a>(record: &'a Value, name: &str) -> &'a str {
+    record.get(name).and_then(Value::as_str).unwrap_or("")
+}
+
+fn offset(record: &Value) -> Option<usize> {
+    field(record, "original_start").parse().ok()
+}
+
+fn records<'a>(payload: &'a Value, collection: &str) -> impl Iterator<Item = &'a Value> {
+    payload.get(collection).and_then(Value::as_array).into_iter().flatten()
+}
+
+fn as_strings(record: &Value) -> HashMap<String, String> {
+    record.as_object().into_iter().flatten()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            (key.clone(), value)
+        })
+        .collect()
+}
+
+// Whether `phrase` occurs in `query` as a run of whole words
+fn contains_phrase(query: &[String], phrase: &[String]) -> bool {
+    !phrase.is_empty() && query.windows(phrase.len()).any(|window| window == phrase)
+}
+
+// What one module is known by: its title, its steps, entities and conditions, and the
+// source text it covers when that is available
+struct ModuleDocument {
+    record: HashMap<String, String>,
+    terms: HashMap<String, usize>,
+    length: usize,
+    entities: BTreeSet<String>,
+}
+
+impl ModuleDocument {
+    fn add_text(&mut self, text: &str) {
+        for word in words(text) {
+            *self.terms.entry(word).or_default() += 1;
+            self.length += 1;
+        }
+    }
+
+    fn add_record(&mut self, record: &Value) {
+        for name in TEXT_FIELDS {
+            self.add_text(field(record, name));
+        }
+    }
+}
+
+fn module_documents(payload: &Value, text: Option<&str>) -> Vec<ModuleDocument> {
+    let modules: Vec<HashMap<String, String>> = records(payload, "modules").map(as_strings).collect();
+    let text_len = text.map(str::len).unwrap_or(usize::MAX);
+    let extents = record_extents(&modules, text_len);
+    let locate = |offset: usize| extents.iter().position(|(start, end, _)| offset >= *start && offset < *end);
+
+    let mut documents: Vec<ModuleDocument> = extents.iter()
+        .map(|(_, _, module_id)| ModuleDocument {
+            record: modules.iter().find(|module| module.get("record_id") == Some(module_id)).cloned().unwrap_or_default(),
+            terms: HashMap::new(),
+            length: 0,
+            entities: BTreeSet::new(),
+        })
+        .collect();
+    let by_id: HashMap<&str, usize> = extents.iter().enumerate()
+        .map(|(index, (_, _, module_id))| (module_id.as_str(), index))
+        .collect();
+
+    for (index, (start, end, _)) in extents.iter().enumerate() {
+        let document = &mut documents[index];
+        let title = document.record.get("pattern").cloned().unwrap_or_default();
+        document.add_text(&title);
+        if let Some(text) = text.and_then(|text| text.get(*start..*end)) {
+            document.add_text(text);
+        }
+    }
+
+    // The source text already holds the words of every record located in it
+    if text.is_none() {
+        for step in records(payload, "steps") {
+            if let Some(index) = offset(step).and_then(locate) {
+                documents[index].add_record(step);
+            }
+        }
+        for condition in records(payload, "conditions") {
+            if let Some(&index) = by_id.get(field(condition, "module_id")) {
+                documents[index].add_record(condition);
+            }
+        }
+    }
+
+    // An entity record only keeps its first mention - with source text, every module
+    // whose text names it counts
+    for entity in records(payload, "entities") {
+        let mut indexes: BTreeSet<usize> = offset(entity).and_then(locate).into_iter().collect();
+        if let Some(text) = text {
+            let mention = words(field(entity, "text"));
+            indexes.extend(extents.iter().enumerate()
+                .filter(|(_, (start, end, _))| text.get(*start..*end).is_some_and(|span| contains_phrase(&words(span), &mention)))
+                .map(|(index, _)| index));
+        }
+        for index in indexes {
+            documents[index].entities.insert(field(entity, "record_id").to_string());
+            if text.is_none() {
+                documents[index].add_record(entity);
+            }
+        }
+    }
+    documents
+}
+
+// Entities the query names, by record_id, with the canonical values to report
+fn taxonomy_terms(payload: &Value, query: &[String]) -> HashMap<String, String> {
+    records(payload, "entities")
+        .filter(|entity| {
+            contains_phrase(query, &words(field(entity, "text"))) || contains_phrase(query, &words(field(entity, "value")))
+        })
+        .map(|entity| (field(entity, "record_id").to_string(), field(entity, "value").to_string()))
+        .collect()
+}
+
+// Modules that match the query, best first, each with its score. Without source text the
+// documents are built from the extracted records alone
+pub fn rank_value(output: &Value, query: &str, text: Option<&str>, limit: usize) -> Vec<HashMap<String, String>> {
+    let payload = payload_of(output);
+    let documents = module_documents(payload, text);
+    let query_words = words(query);
+    let mut query_terms = query_words.clone();
+    query_terms.sort();
+    query_terms.dedup();
+    let taxonomy = taxonomy_terms(payload, &query_words);
+
+    let count = documents.len() as f64;
+    let average_length = documents.iter().map(|document| document.length).sum::<usize>() as f64 / count.max(1.0);
+    let idf: HashMap<&str, f64> = query_terms.iter()
+        .map(|term| {
+            let frequency = documents.iter().filter(|document| document.terms.contains_key(term)).count() as f64;
+            (term.as_str(), (1.0 + (count - frequency + 0.5) / (frequency + 0.5)).ln())
+        })
+        .collect();
+
+    let mut ranked: Vec<(f64, f64, Vec<&str>, &ModuleDocument)> = documents.iter()
+        .map(|document| {
+            let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * document.length as f64 / average_length.max(1.0));
+            let bm25: f64 = query_terms.iter()
+                .map(|term| {
+                    let frequency = document.terms.get(term).copied().unwrap_or(0) as f64;
+                    idf[term.as_str()] * frequency * (BM25_K1 + 1.0) / (frequency + norm)
+                })
+                .sum();
+            let mut matches: Vec<&str> = document.entities.iter()
+                .filter_map(|record_id| taxonomy.get(record_id).map(String::as_str))
+                .collect();
+            matches.sort_unstable();
+            matches.dedup();
+            (bm25 + TAXONOMY_BOOST * matches.len() as f64, bm25, matches, document)
+        })
+        .filter(|(score, _, _, _)| *score > 0.0)
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    ranked.into_iter().take(limit).enumerate()
+        .map(|(index, (score, bm25, matches, document))| {
+            let mut record = document.record.clone();
+            record.insert("rank".to_string(), (index + 1).to_string());
+            record.insert("score".to_string(), format!("{:.3}", score));
+            record.insert("bm25".to_string(), format!("{:.3}", bm25));
+            record.insert("taxonomy_matches".to_string(), matches.join(","));
+            record
+        })
+        .collect()
+}
+
+// Python binding - takes the output JSON text. The source text is read from the output's
+// `source` when `text` is not given and the file still exists
+#[pyfunction]
+#[pyo3(signature = (results, query, text=None, limit=10))]
+pub fn rank_modules(py: Python, results: &str, query: &str, text: Option<&str>, limit: usize) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let output: Value = serde_json::from_str(results)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid results JSON: {}", e)))?;
+        let source_text = match text {
+            Some(_) => None,
+            None => payload_of(&output).get("source")
+                .and_then(Value::as_str)
+                .and_then(|source| std::fs::read_to_string(source).ok()),
+        };
+        Ok(rank_value(&output, query, text.or(source_text.as_deref()), limit))
+    })
+}
@@ -0,0 +1,189 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use utoipa::ToSchema;
+
+use crate::engine::completeness::PageReport;
+use crate::engine::entities::record_extents;
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::splitter::{split_pages, PAGE_BREAK};
+use crate::jobs::taxonomy::find_ata;
+use crate::pdf::layout::strip_running_lines;
+
+// The part of a document extraction runs on. Text outside it is blanked rather than cut
+// away, so record offsets and page numbers still point into the whole document
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ExtractionScope {
+    // First and last page, 1-based and inclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<usize>>)]
+    pub pages: Option<(usize, usize)>,
+    // Modules whose title line contains one of these, or whose ATA chapter or section is one
+    // of them: "32", "32-11" or "TASK 32-11-00"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<String>,
+}
+
+// "120-180" or a single page, "12"
+pub fn parse_page_range(spec: &str) -> Result<(usize, usize), String> {
+    let page = |value: &str| value.trim().parse::<usize>().map_err(|_| format!("Invalid page range {:?}", spec));
+    match spec.split_once('-') {
+        Some((start, end)) => Ok((page(start)?, page(end)?)),
+        None => page(spec).map(|page| (page, page)),
+    }
+}
+
+// Pages as a document's page reports count them - pdftotext's final form feed opens no page
+fn document_pages(text: &str) -> Vec<(usize, &str)> {
+    let mut pages = split_pages(text);
+    if pages.len() > 1 && text.ends_with(PAGE_BREAK) {
+        pages.pop();
+    }
+    pages
+}
+
+fn line_at(text: &str, offset: usize) -> &str {
+    let offset = offset.min(text.len());
+    if !text.is_char_boundary(offset) {
+        return "";
+    }
+    let start = text[..offset].rfind('\n').map(|index| index + 1).unwrap_or(0);
+    let end = text[offset..].find('\n').map(|index| offset + index).unwrap_or(text.len());
+    &text[start..end]
+}
+
+fn intersect(spans: &[(usize, usize)], start: usize, end: usize) -> Vec<(usize, usize)> {
+    spans.iter()
+        .map(|(span_start, span_end)| ((*span_start).max(start), (*span_end).min(end)))
+        .filter(|(span_start, span_end)| span_start < span_end)
+        .collect()
+}
+
+impl ExtractionScope {
+    pub fn new(pages: Option<(usize, usize)>, sections: Option<Vec<String>>) -> Result<Self, String> {
+        let scope = Self { pages, sections: sections.unwrap_or_default() };
+        scope.validate()?;
+        Ok(scope)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_none() && self.sections.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some((start, end)) = self.pages {
+            if start == 0 || end < start {
+                return Err(format!("Invalid page range {}-{}: pages count from 1 and the range must not run backwards", start, end));
+            }
+        }
+        if self.sections.iter().any(|section| section.trim().is_empty()) {
+            return Err("Section filters must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    fn matches_section(&self, title_line: &str, pattern: &str) -> bool {
+        let ata = find_ata(title_line);
+        self.sections.iter().any(|section| {
+            let section = section.trim();
+            title_line.contains(section)
+                || pattern.contains(section)
+                || ata.as_ref().is_some_and(|(chapter, ata_section, _)| chapter == section || ata_section == section)
+        })
+    }
+
+    // Byte ranges of `text` inside the scope, in order. Fails when the scope selects nothing,
+    // so a mistyped chapter never passes for an empty one
+    pub fn spans(&self, engine: &ExtractionEngine, text: &str) -> Result<Vec<(usize, usize)>, String> {
+        let mut spans = vec![(0, text.len())];
+
+        if let Some((start, end)) = self.pages {
+            let pages = document_pages(text);
+            if start > pages.len() {
+                return Err(format!("Page range {}-{} starts after the last page ({})", start, end, pages.len()));
+            }
+            let (last_offset, last_page) = pages[end.min(pages.len()) - 1];
+            spans = intersect(&spans, pages[start - 1].0, last_offset + last_page.len());
+        }
+
+        if !self.sections.is_empty() {
+            let modules = engine.find_modules(text);
+            let selected: Vec<(usize, usize)> = record_extents(&modules, text.len()).into_iter()
+                .filter(|(start, _, module_id)| {
+                    let pattern = modules.iter()
+                        .find(|module| module.get("record_id") == Some(module_id))
+                        .and_then(|module| module.get("pattern"))
+                        .map(String::as_str)
+                        .unwrap_or_default();
+                    self.matches_section(line_at(text, *start), pattern)
+                })
+                .flat_map(|(start, end, _)| intersect(&spans, start, end))
+                .collect();
+            if selected.is_empty() {
+                let within = self.pages.map(|(start, end)| format!(" within pages {}-{}", start, end)).unwrap_or_default();
+                return Err(format!("No module matches sections {}{}", self.sections.join(", "), within));
+            }
+            spans = selected;
+        }
+        Ok(spans)
+    }
+
+    // The text to extract from: running headers and footers blanked (see layout), and every
+    // character outside the scope's spans replaced by as many spaces as it had bytes. Line and
+    // page breaks stay. Sections are looked up after the headers go, so a chapter title repeated
+    // on every page never selects a module
+    pub fn apply<'a>(&self, engine: &ExtractionEngine, text: &'a str) -> Result<Cow<'a, str>, String> {
+        let text = strip_running_lines(text, engine.get_layout());
+        if self.is_empty() {
+            return Ok(text);
+        }
+        let spans = self.spans(engine, &text)?;
+        let mut masked = String::with_capacity(text.len());
+        let mut spans = spans.iter().peekable();
+        for (offset, c) in text.char_indices() {
+            while spans.peek().is_some_and(|(_, end)| offset >= *end) {
+                spans.next();
+            }
+            let inside = spans.peek().is_some_and(|(start, _)| offset >= *start);
+            if inside || c.is_whitespace() {
+                masked.push(c);
+            } else {
+                masked.extend(std::iter::repeat_n(' ', c.len_utf8()));
+            }
+        }
+        Ok(Cow::Owned(masked))
+    }
+
+    // The page failures of the pages in scope - failures elsewhere lose nothing that was asked for
+    pub fn page_report(&self, report: &PageReport) -> PageReport {
+        match self.pages {
+            Some((start, end)) => report.pages(start, end, 0),
+            None => report.clone(),
+        }
+    }
+
+    // Records the scope in an output payload, so a partial extraction never passes for a whole one
+    pub fn add_to(&self, payload: &mut Value) {
+        if !self.is_empty() {
+            payload["scope"] = json!(self);
+        }
+    }
+
+    // Whether any text of the range start..end is inside the scope
+    pub fn covers(&self, masked: &str, start: usize, end: usize) -> bool {
+        self.is_empty() || masked.get(start..end).is_some_and(|text| !text.trim().is_empty())
+    }
+
+    // Whether any text of 1-based `page` is inside the scope, for what is placed by page rather
+    // than by offset
+    pub fn covers_page(&self, masked: &str, page: usize) -> bool {
+        self.is_empty() || page.checked_sub(1).and_then(|index| document_pages(masked).get(index).copied())
+            .is_some_and(|(_, text)| !text.trim().is_empty())
+    }
+}
+
+// Python arguments shared by the extraction bindings: pages=(start, end), sections=[...]
+pub fn scope_from_args(pages: Option<(usize, usize)>, sections: Option<Vec<String>>) -> PyResult<ExtractionScope> {
+    ExtractionScope::new(pages, sections).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
@@ -0,0 +1,105 @@
+use pyo3::prelude::*;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+// Invisible bidi and format controls that PDF text layers scatter through RTL text.
+// ZWJ/ZWNJ are kept - they change shaping and meaning in Arabic, Persian and Indic scripts
+const FORMAT_MARKS: &[char] = &[
+    '\u{200B}', '\u{200E}', '\u{200F}', '\u{061C}', '\u{FEFF}',
+    '\u{202A}', '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}',
+    '\u{2066}', '\u{2067}', '\u{2068}', '\u{2069}',
+];
+
+// Arabic tatweel - justification stretching, not part of the word
+const TATWEEL: char = '\u{0640}';
+
+pub fn is_format_mark(ch: char) -> bool {
+    FORMAT_MARKS.contains(&ch) || ch == TATWEEL
+}
+
+// Scripts written without spaces between words - a line break inside them is not a word break.
+// Hangul is excluded because Korean separates words with spaces
+pub fn is_unspaced_script(ch: char) -> bool {
+    matches!(ch,
+        '\u{3000}'..='\u{303F}'   // CJK symbols and punctuation
+        | '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+        | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+        | '\u{FF00}'..='\u{FFEF}' // Halfwidth and fullwidth forms
+        | '\u{20000}'..='\u{2FFFF}'
+    )
+}
+
+pub fn is_rtl_char(ch: char) -> bool {
+    matches!(ch,
+        '\u{0590}'..='\u{08FF}'   // Hebrew, Arabic, Syriac, Thaana
+        | '\u{FB1D}'..='\u{FDFF}' // Hebrew and Arabic presentation forms A
+        | '\u{FE70}'..='\u{FEFC}' // Arabic presentation forms B
+    )
+}
+
+// A match must start and end between user-perceived characters, so "e" never
+// matches the base of a decomposed "é" and a pattern never splits a combining sequence
+pub fn is_grapheme_boundary(text: &str, offset: usize) -> bool {
+    if offset == 0 || offset == text.len() {
+        return true;
+    }
+    if !text.is_char_boundary(offset) {
+        return false;
+    }
+    GraphemeCursor::new(offset, text.len(), true)
+        .is_boundary(text, 0)
+        .unwrap_or(false)
+}
+
+// "rtl" when most letters are Hebrew/Arabic, otherwise "ltr"
+pub fn dominant_direction(text: &str) -> &'static str {
+    let (rtl, ltr) = text.chars()
+        .filter(|c| c.is_alphabetic())
+        .fold((0usize, 0usize), |(rtl, ltr), c| if is_rtl_char(c) { (rtl + 1, ltr) } else { (rtl, ltr + 1) });
+    if rtl > ltr { "rtl" } else { "ltr" }
+}
+
+// Sentences with their byte offsets. unicode-segmentation's sentence iterators take one from
+// their size hint, which overflows once fewer than two bounds are left - in an empty text, or
+// one ending in a form feed - so they are only ever stepped, never collected or counted
+pub fn sentence_bounds(text: &str) -> Vec<(usize, &str)> {
+    let mut sentences = Vec::new();
+    for sentence in text.split_sentence_bound_indices() {
+        sentences.push(sentence);
+    }
+    sentences
+}
+
+// Segment text with its start and end byte offsets
+pub type Segment = (String, usize, usize);
+
+// UAX #29 segments with byte offsets; ideographs come out one per word
+pub fn segment(text: &str, granularity: &str) -> Result<Vec<Segment>, Box<dyn std::error::Error>> {
+    let segments: Vec<(usize, &str)> = match granularity {
+        "word" => text.unicode_word_indices().collect(),
+        "grapheme" => text.grapheme_indices(true).collect(),
+        "sentence" => sentence_bounds(text).into_iter()
+            .filter(|(_, s)| !s.trim().is_empty())
+            .collect(),
+        other => return Err(format!("Unknown granularity: {}", other).into()),
+    };
+    Ok(segments.into_iter()
+        .map(|(start, s)| (s.to_string(), start, start + s.len()))
+        .collect())
+}
+
+// Python bindings - offsets are byte offsets into the UTF-8 text
+#[pyfunction]
+#[pyo3(signature = (text, granularity="word"))]
+pub fn segment_text(py: Python, text: &str, granularity: &str) -> PyResult<Vec<Segment>> {
+    py.allow_threads(|| {
+        segment(text, granularity)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    })
+}
+
+#[pyfunction]
+pub fn text_direction(text: &str) -> PyResult<String> {
+    Ok(dominant_direction(text).to_string())
+}
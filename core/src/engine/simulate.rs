@@ -0,0 +1,133 @@
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+use crate::engine::extractor::find_matches;
+use crate::engine::fuzzy::{find_fuzzy_match, FuzzyConfig};
+use crate::engine::normalize::{normalize, NormalizationConfig};
+
+// Offsets listed in the report - enough to debug a ticket without dumping the document
+const MAX_REPORTED_MATCHES: usize = 20;
+const CONTEXT_CHARS: usize = 40;
+
+fn floor_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+fn context_around(text: &str, start: usize, end: usize) -> String {
+    let from = floor_boundary(text, start.saturating_sub(CONTEXT_CHARS));
+    let to = ceil_boundary(text, end + CONTEXT_CHARS);
+    text[from..to].replace('\n', "\\n")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Explains the most likely reason a pattern that was expected to match did not
+fn diagnose_miss(pattern: &str, text: &str) -> String {
+    if pattern.is_empty() {
+        return "Pattern is empty and never matches".to_string();
+    }
+    if pattern.trim() != pattern {
+        return "Pattern has leading or trailing whitespace".to_string();
+    }
+    if let Some(offset) = text.to_lowercase().find(&pattern.to_lowercase()) {
+        return format!("Matches only when ignoring case (near offset {})", offset);
+    }
+    if collapse_whitespace(text).contains(&collapse_whitespace(pattern)) {
+        return "Matches only after collapsing whitespace or line breaks".to_string();
+    }
+
+    // Longest prefix of the pattern that does occur shows where the text diverges
+    let mut longest = "";
+    for (index, _) in pattern.char_indices().skip(1).chain(std::iter::once((pattern.len(), ' '))) {
+        let prefix = &pattern[..index];
+        if text.contains(prefix) {
+            longest = prefix;
+        } else {
+            break;
+        }
+    }
+    if longest.is_empty() {
+        "No part of the pattern occurs in the text".to_string()
+    } else {
+        format!("Text diverges after matching prefix '{}'", longest)
+    }
+}
+
+// Runs the same normalize-then-match pipeline as extraction
+pub fn simulate(pattern: &str, text: &str, config: &NormalizationConfig, fuzzy: &FuzzyConfig) -> HashMap<String, String> {
+    let normalized = normalize(text, config);
+    let normalized_pattern = normalize(pattern, config);
+    let matches = find_matches(&normalized_pattern.text, &normalized.text);
+
+    let mut report = HashMap::new();
+    report.insert("pattern".to_string(), pattern.to_string());
+    report.insert("text_length".to_string(), text.len().to_string());
+    report.insert("matched".to_string(), (!matches.is_empty()).to_string());
+    report.insert("match_count".to_string(), matches.len().to_string());
+
+    match matches.first() {
+        Some(&(start, end)) => {
+            let offsets: Vec<String> = matches.iter()
+                .take(MAX_REPORTED_MATCHES)
+                .map(|(s, e)| format!("{}-{}", s, e))
+                .collect();
+            let original_offsets: Vec<String> = matches.iter()
+                .take(MAX_REPORTED_MATCHES)
+                .map(|&(s, e)| format!("{}-{}", normalized.to_original(s), normalized.to_original(e)))
+                .collect();
+            report.insert("offsets".to_string(), offsets.join(","));
+            report.insert("original_offsets".to_string(), original_offsets.join(","));
+            report.insert("first_match_context".to_string(), context_around(&normalized.text, start, end));
+        }
+        None => {
+            report.insert("hint".to_string(), diagnose_miss(&normalized_pattern.text, &normalized.text));
+            let fuzzy_match = if fuzzy.applies_to(&normalized_pattern.text) {
+                find_fuzzy_match(&normalized_pattern.text, &normalized.text, fuzzy.max_distance)
+            } else {
+                None
+            };
+            if let Some((start, end, distance)) = fuzzy_match {
+                report.insert("fuzzy_matched".to_string(), "true".to_string());
+                report.insert("edit_distance".to_string(), distance.to_string());
+                report.insert("fuzzy_original_offsets".to_string(), format!("{}-{}", normalized.to_original(start), normalized.to_original(end)));
+                report.insert("matched_text".to_string(), normalized.text[start..end].to_string());
+            }
+        }
+    }
+
+    report
+}
+
+// Python binding - tests one candidate pattern with the engine's own matcher
+#[pyfunction]
+#[pyo3(signature = (pattern, text, collapse_whitespace=true, normalize_numbers=false, script_aware=true, max_distance=0))]
+pub fn simulate_pattern(
+    py: Python,
+    pattern: &str,
+    text: &str,
+    collapse_whitespace: bool,
+    normalize_numbers: bool,
+    script_aware: bool,
+    max_distance: usize,
+) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let config = NormalizationConfig { collapse_whitespace, normalize_numbers, script_aware };
+        let fuzzy = FuzzyConfig { max_distance, ..FuzzyConfig::default() };
+        fuzzy.validate().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(simulate(pattern, text, &config, &fuzzy))
+    })
+}
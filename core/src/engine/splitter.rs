@@ -0,0 +1,271 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::scope::{scope_from_args, ExtractionScope};
+use crate::schema::envelope::{wrap_output, Provenance, UNLICENSED_TIER};
+use crate::security::session::record_document_processed;
+
+// Page break emitted by pdftotext and most PDF text layers
+pub const PAGE_BREAK: char = '\x0c';
+
+// Lines inspected at the top and bottom of a page for titles and page numbers
+const HEADER_LINES: usize = 6;
+const FOOTER_LINES: usize = 3;
+
+fn default_title_markers() -> Vec<String> {
+    vec![
+        "SERVICE BULLETIN".to_string(),
+        "ALERT SERVICE BULLETIN".to_string(),
+        "SERVICE LETTER".to_string(),
+    ]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitConfig {
+    #[serde(default = "default_title_markers")]
+    pub title_markers: Vec<String>,
+    // Split where page numbering restarts at 1
+    #[serde(default = "default_true")]
+    pub split_on_numbering_reset: bool,
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        Self {
+            title_markers: default_title_markers(),
+            split_on_numbering_reset: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogicalDocument {
+    pub index: usize,
+    pub title: Option<String>,
+    pub document_number: Option<String>,
+    // 1-based, inclusive page range in the source
+    pub start_page: usize,
+    pub end_page: usize,
+    // Byte offset of this document's text within the source text
+    pub start_offset: usize,
+    pub text: String,
+}
+
+impl LogicalDocument {
+    pub fn get_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        metadata.insert("index".to_string(), self.index.to_string());
+        metadata.insert("start_page".to_string(), self.start_page.to_string());
+        metadata.insert("end_page".to_string(), self.end_page.to_string());
+        metadata.insert("start_offset".to_string(), self.start_offset.to_string());
+        if let Some(title) = &self.title {
+            metadata.insert("title".to_string(), title.clone());
+        }
+        if let Some(number) = &self.document_number {
+            metadata.insert("document_number".to_string(), number.clone());
+        }
+        metadata
+    }
+}
+
+// Pages with their byte offset in the source text
+pub fn split_pages(text: &str) -> Vec<(usize, &str)> {
+    let mut pages = Vec::new();
+    let mut offset = 0;
+    for page in text.split(PAGE_BREAK) {
+        pages.push((offset, page));
+        offset += page.len() + PAGE_BREAK.len_utf8();
+    }
+    pages
+}
+
+fn header_lines(page: &str) -> impl Iterator<Item = &str> {
+    page.lines().map(str::trim).filter(|l| !l.is_empty()).take(HEADER_LINES)
+}
+
+fn find_title(page: &str, config: &SplitConfig) -> Option<String> {
+    header_lines(page)
+        .find(|line| {
+            let upper = line.to_uppercase();
+            config.title_markers.iter().any(|marker| upper.contains(marker.as_str()))
+        })
+        .map(str::to_string)
+}
+
+// Bulletin numbers look like "SB 737-32-1234" or "No. A320-29-1102"
+fn find_document_number(page: &str) -> Option<String> {
+    for line in header_lines(page) {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        for (index, token) in tokens.iter().enumerate() {
+            let label = token.trim_end_matches(':').to_uppercase();
+            if matches!(label.as_str(), "NO." | "NUMBER" | "SB" | "BULLETIN") {
+                if let Some(candidate) = tokens.get(index + 1) {
+                    let candidate = candidate.trim_matches(|c: char| !c.is_alphanumeric() && c != '-');
+                    if candidate.contains('-') && candidate.chars().any(|c| c.is_ascii_digit()) {
+                        return Some(candidate.to_string());
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+// Reads "Page 3", "Page 3 of 12" or "3 of 12" from the page footer or header
+fn find_page_number(page: &str) -> Option<u32> {
+    let lines: Vec<&str> = page.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let footer = lines.iter().rev().take(FOOTER_LINES);
+    let header = lines.iter().take(FOOTER_LINES);
+
+    for line in footer.chain(header) {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        for (index, token) in tokens.iter().enumerate() {
+            let number = match token.parse::<u32>() {
+                Ok(number) => number,
+                Err(_) => continue,
+            };
+            let after_page = index > 0 && tokens[index - 1].eq_ignore_ascii_case("page");
+            let before_of = tokens.get(index + 1).is_some_and(|t| t.eq_ignore_ascii_case("of"));
+            if after_page || before_of {
+                return Some(number);
+            }
+        }
+    }
+    None
+}
+
+// Indices of the pages that start a new logical document - page 0 always does
+pub fn detect_boundaries(pages: &[(usize, &str)], config: &SplitConfig) -> Vec<usize> {
+    let mut boundaries = vec![0];
+    let mut previous_number: Option<u32> = pages.first().and_then(|(_, p)| find_page_number(p));
+
+    for (index, (_, page)) in pages.iter().enumerate().skip(1) {
+        let number = find_page_number(page);
+        let title_page = find_title(page, config).is_some();
+        let numbering_reset = config.split_on_numbering_reset
+            && number == Some(1)
+            && previous_number.is_some_and(|n| n > 1);
+
+        if title_page || numbering_reset {
+            boundaries.push(index);
+        }
+        if number.is_some() {
+            previous_number = number;
+        }
+    }
+    boundaries
+}
+
+pub fn split_document(text: &str, config: &SplitConfig) -> Vec<LogicalDocument> {
+    let pages = split_pages(text);
+    let boundaries = detect_boundaries(&pages, config);
+
+    let mut documents = Vec::new();
+    for (index, &first) in boundaries.iter().enumerate() {
+        let last = boundaries.get(index + 1).map(|next| next - 1).unwrap_or(pages.len() - 1);
+        let start_offset = pages[first].0;
+        let end_offset = pages[last].0 + pages[last].1.len();
+        let first_page = pages[first].1;
+
+        documents.push(LogicalDocument {
+            index,
+            title: find_title(first_page, config),
+            document_number: find_document_number(first_page),
+            start_page: first + 1,
+            end_page: last + 1,
+            start_offset,
+            text: text[start_offset..end_offset].to_string(),
+        });
+    }
+    documents
+}
+
+// Splits the source, runs extraction per logical document and writes one output file each
+pub fn split_and_extract_file(
+    input_path: &str,
+    engine: &ExtractionEngine,
+    output_dir: &str,
+    config: &SplitConfig,
+    scope: &ExtractionScope,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let decoded = engine.read_input(input_path)?;
+    let text = decoded.text;
+    let scoped = scope.apply(engine, &text).map_err(|e| format!("{}: {}", input_path, e))?;
+    let stem = Path::new(input_path).file_stem().and_then(|s| s.to_str()).unwrap_or("document");
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut written = Vec::new();
+    // Parts are found on the whole text, so a scope never moves a part boundary
+    for document in split_document(&text, config) {
+        let end_offset = document.start_offset + document.text.len();
+        if !scope.covers(&scoped, document.start_offset, end_offset) {
+            continue;
+        }
+        let part_text = &scoped[document.start_offset..end_offset];
+        let mut payload = json!({
+            "source": input_path,
+            "part": document.get_metadata(),
+        });
+        for (collection, records) in engine.extract_collections(part_text) {
+            payload[collection] = json!(records);
+        }
+        if let Some(pii) = engine.scan_pii(part_text) {
+            payload["pii"] = json!(pii);
+        }
+        let (first_page, last_page) = match scope.pages {
+            Some((start, end)) => (document.start_page.max(start), document.end_page.min(end)),
+            None => (document.start_page, document.end_page),
+        };
+        let report = decoded.report.pages(first_page, last_page, document.start_offset);
+        report.add_to(&mut payload, report.failure_records());
+        scope.add_to(&mut payload);
+        let provenance = Provenance::new(engine, UNLICENSED_TIER, document.text.as_bytes());
+        let output_path = Path::new(output_dir).join(format!("{}_part{:03}.json", stem, document.index + 1));
+        let output_path = engine.get_delivery().write_json(&output_path, &wrap_output(&provenance, payload))?;
+        record_document_processed();
+        written.push(output_path.to_string_lossy().to_string());
+    }
+    Ok(written)
+}
+
+// Python bindings
+#[pyfunction]
+pub fn split_documents(py: Python, text: &str) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let documents = split_document(text, &SplitConfig::default());
+        Ok(documents.into_iter()
+            .map(|document| {
+                let mut entry = document.get_metadata();
+                entry.insert("text".to_string(), document.text);
+                entry
+            })
+            .collect())
+    })
+}
+
+// Parts with no text inside the pages or sections are not written
+#[pyfunction]
+#[pyo3(signature = (input_path, rules_path, output_dir, pages=None, sections=None))]
+pub fn split_and_extract(
+    py: Python,
+    input_path: &str,
+    rules_path: &str,
+    output_dir: &str,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<Vec<String>> {
+    py.allow_threads(|| {
+        let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+        let scope = scope_from_args(pages, sections)?;
+        let engine = ExtractionEngine::from_rules_file(rules_path).map_err(to_py_err)?;
+        split_and_extract_file(input_path, &engine, output_dir, &SplitConfig::default(), &scope).map_err(to_py_err)
+    })
+}
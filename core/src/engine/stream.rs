@@ -0,0 +1,749 @@
+use pyo3::prelude::*;
+#[cfg(feature = "flows")]
+use std::collections::BTreeSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Chain, Cursor, Read, Write};
+
+use crate::engine::access::ACCESS_TAGS_FIELD;
+#[cfg(feature = "figures")]
+use crate::engine::convert::ConverterWarning;
+use crate::engine::completeness::{page_problem, PageFailure, PageReport, INVALID_ENCODING};
+#[cfg(feature = "flows")]
+use crate::engine::consistency::{figure_captions, finding_record_id, MISSING_FIGURE};
+use crate::engine::convert::is_pdf;
+use crate::engine::encoding::{decode_as, detect_encoding, DetectedEncoding, TextEncoding, SNIFF_BYTES};
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::ids::IdNamespace;
+use crate::engine::normalize::normalize;
+use crate::engine::pii::pii_record_id;
+use crate::engine::splitter::PAGE_BREAK;
+#[cfg(feature = "figures")]
+use crate::pdf::document::PdfDocument;
+use crate::pdf::layout::{LayoutConfig, RunningLineCounter, RunningLines};
+#[cfg(feature = "figures")]
+use crate::pdf::text::{PdfTextPages, NATIVE_TOOL};
+use crate::security::seal::SealWriter;
+use crate::storage::atomic::AtomicFile;
+
+// Pages held at most before a window is extracted, however long its module runs
+pub const DEFAULT_WINDOW_PAGES: usize = 64;
+
+// One page of a text layer, as DecodedText would hold it: a failed page is empty, and offsets
+// count the form feeds between pages
+pub struct StreamPage {
+    // 1-based
+    pub number: usize,
+    pub start_offset: usize,
+    pub text: String,
+    pub failure: Option<PageFailure>,
+}
+
+type Source<R> = BufReader<Chain<Cursor<Vec<u8>>, R>>;
+
+// A text layer decoded a page at a time. The encoding is detected from the first 4 KiB -
+// the same sample UTF-16 is recognised from when a file is read whole
+pub struct PageReader<R: Read> {
+    input: Source<R>,
+    encoding: DetectedEncoding,
+    pages: usize,
+    offset: usize,
+    // The last page ended on a form feed, so another follows unless the input ends there
+    open: bool,
+    // The input ended on a form feed, which ends its last page
+    trailing_break: bool,
+}
+
+fn read_unit(input: &mut impl Read, unit: &mut [u8; 2]) -> std::io::Result<usize> {
+    let mut read = 0;
+    while read < unit.len() {
+        match input.read(&mut unit[read..])? {
+            0 => break,
+            count => read += count,
+        }
+    }
+    Ok(read)
+}
+
+impl<R: Read> PageReader<R> {
+    pub fn new(mut input: R) -> std::io::Result<Self> {
+        let mut head = Vec::with_capacity(SNIFF_BYTES);
+        (&mut input).take(SNIFF_BYTES as u64).read_to_end(&mut head)?;
+        // A character the sample cuts in two is not a damaged one
+        let sample = match std::str::from_utf8(&head) {
+            Err(e) if head.len() == SNIFF_BYTES && e.error_len().is_none() => &head[..e.valid_up_to()],
+            _ => &head[..],
+        };
+        let encoding = detect_encoding(sample);
+        let bom = match (encoding.bom, encoding.encoding) {
+            (false, _) => 0,
+            (true, TextEncoding::Utf8) => 3,
+            (true, _) => 2,
+        };
+        head.drain(..bom);
+        Ok(Self { input: BufReader::new(Cursor::new(head).chain(input)), encoding, pages: 0, offset: 0, open: true, trailing_break: false })
+    }
+
+    pub fn encoding(&self) -> DetectedEncoding {
+        self.encoding
+    }
+
+    pub fn get_input(&self) -> &R {
+        self.input.get_ref().get_ref().1
+    }
+
+    pub fn ended_on_break(&self) -> bool {
+        self.trailing_break
+    }
+
+    // The raw bytes of the next page, and whether a form feed ended it. UTF-16 is read a code
+    // unit at a time, as a 0x0C byte may be half of another character
+    fn read_segment(&mut self) -> std::io::Result<(Vec<u8>, bool)> {
+        let mut segment = Vec::new();
+        let page_break = match self.encoding.encoding {
+            TextEncoding::Utf16Le => [PAGE_BREAK as u8, 0],
+            TextEncoding::Utf16Be => [0, PAGE_BREAK as u8],
+            TextEncoding::Utf8 | TextEncoding::Latin1 => {
+                self.input.read_until(PAGE_BREAK as u8, &mut segment)?;
+                let ended = segment.last() == Some(&(PAGE_BREAK as u8));
+                if ended {
+                    segment.pop();
+                }
+                return Ok((segment, ended));
+            }
+        };
+        let mut unit = [0u8; 2];
+        loop {
+            let read = read_unit(&mut self.input, &mut unit)?;
+            if read < unit.len() {
+                segment.extend_from_slice(&unit[..read]);
+                return Ok((segment, false));
+            }
+            if unit == page_break {
+                return Ok((segment, true));
+            }
+            segment.extend_from_slice(&unit);
+        }
+    }
+
+    pub fn next_page(&mut self) -> std::io::Result<Option<StreamPage>> {
+        if !self.open {
+            return Ok(None);
+        }
+        let (segment, ended) = self.read_segment()?;
+        self.open = ended;
+        // pdftotext ends every page with a form feed; the empty tail after it is not a page
+        if !ended && segment.is_empty() && self.pages > 0 {
+            self.trailing_break = true;
+            return Ok(None);
+        }
+        self.pages += 1;
+        let start_offset = self.offset;
+        let bytes = decode_as(&segment, self.encoding.encoding);
+        let (text, problem) = match std::str::from_utf8(&bytes) {
+            Ok(page) => match page_problem(page) {
+                Some(problem) => (String::new(), Some(problem)),
+                None => (page.to_string(), None),
+            },
+            Err(e) => (String::new(), Some((INVALID_ENCODING, format!("Not UTF-8 text (invalid byte at page offset {})", e.valid_up_to())))),
+        };
+        self.offset = start_offset + text.len() + PAGE_BREAK.len_utf8();
+        let failure = problem.map(|(reason, detail)| PageFailure { page: self.pages, reason, detail, start_offset });
+        Ok(Some(StreamPage { number: self.pages, start_offset, text, failure }))
+    }
+}
+
+// The records of a run of pages, with offsets into the whole document
+pub struct StreamWindow {
+    pub first_page: usize,
+    pub last_page: usize,
+    pub start_offset: usize,
+    // Every collection of a full output, then `pii` when the profile scans for it, then
+    // `page_failures`
+    pub collections: Vec<(&'static str, Vec<HashMap<String, String>>)>,
+}
+
+impl StreamWindow {
+    pub fn record_count(&self) -> usize {
+        self.collections.iter().map(|(_, records)| records.len()).sum()
+    }
+}
+
+// Window offsets to document offsets
+fn rebase(record: &mut HashMap<String, String>, original_base: usize, normalized_base: usize) {
+    let fields = [("original_start", original_base), ("original_end", original_base), ("start", normalized_base), ("end", normalized_base)];
+    for (field, base) in fields {
+        if let Some(value) = record.get_mut(field) {
+            if let Ok(offset) = value.parse::<usize>() {
+                *value = (offset + base).to_string();
+            }
+        }
+    }
+}
+
+// Record IDs made from a record's offset, made again from its document offset
+fn rekey(collection: &str, record: &mut HashMap<String, String>) {
+    let field = |name: &str| record.get(name).cloned().unwrap_or_default();
+    let start = field("original_start").parse::<usize>().unwrap_or_default();
+    let record_id = match collection {
+        #[cfg(feature = "flows")]
+        "findings" => finding_record_id(&field("module_id"), &field("check"), start),
+        "pii" => pii_record_id(&field("detector"), start),
+        _ => return,
+    };
+    record.insert("record_id".to_string(), record_id);
+}
+
+// A later window's mentions of an entity added to its record from the first
+fn merge_entity(record: &mut HashMap<String, String>, later: HashMap<String, String>) {
+    let mut union = |field: &str, ordered: bool| {
+        let mut values: Vec<String> = record.get(field).into_iter().chain(later.get(field)).flat_map(|ids| ids.split(',')).filter(|id| !id.is_empty()).map(str::to_string).collect();
+        if ordered {
+            values.sort();
+        }
+        let mut seen = HashSet::new();
+        values.retain(|value| seen.insert(value.clone()));
+        if !values.is_empty() {
+            record.insert(field.to_string(), values.join(","));
+        }
+    };
+    union("step_ids", false);
+    union(ACCESS_TAGS_FIELD, true);
+    let mentions = |record: &HashMap<String, String>| record.get("mentions").and_then(|count| count.parse::<usize>().ok()).unwrap_or(1);
+    record.insert("mentions".to_string(), (mentions(record) + mentions(&later)).to_string());
+}
+
+// A page read into the stream and not yet behind its windows
+struct BufferedPage {
+    number: usize,
+    start_offset: usize,
+    end_offset: usize,
+    failure: Option<PageFailure>,
+}
+
+// Characters of the next window that normalizing the end of a window can depend on: a
+// separator before three digits, or a line break joining Japanese or Chinese text
+const LOOKAHEAD_CHARS: usize = 8;
+
+// The offset of the first character of `text` from `from` on that is not whitespace
+fn next_visible(text: &str, from: usize) -> Option<usize> {
+    text[from..].char_indices().find(|(_, c)| !c.is_whitespace()).map(|(at, _)| from + at)
+}
+
+// Extraction of a text layer without holding it whole. Windows are cut where a module heading
+// starts, so each holds whole modules and extracts as the whole document would: records name
+// the module they are in, and record IDs and offsets are the document's. A window is cut short
+// at `max_window_pages`, and the records after such a cut name no module.
+// A module or step pattern gives one record, at its first match, so the stream's copy of the
+// rules drops a pattern once it has matched. Entities gather their mentions and steps across
+// windows and, with the figure references no caption has turned up for, are yielded with the
+// last window. Only the text since the last cut, the entities and the IDs yielded are kept.
+// Running headers and footers are blanked when the stream is given them (see
+// scan_running_lines), as they have to be counted over the whole document first.
+// A document over its error budget yields an error after its last window
+pub struct ExtractionStream<'a, R: Read> {
+    engine: &'a ExtractionEngine,
+    rules: ExtractionEngine,
+    ids: IdNamespace,
+    pages: PageReader<R>,
+    running: Option<RunningLines>,
+    max_window_pages: usize,
+    // Text read but not yet extracted, from a module heading or the start of the document
+    buffer: String,
+    buffer_start: usize,
+    buffered: VecDeque<BufferedPage>,
+    // Pages whose failures a window has reported
+    reported_pages: usize,
+    normalized_base: usize,
+    entities: Vec<HashMap<String, String>>,
+    entity_index: HashMap<String, usize>,
+    #[cfg(feature = "flows")]
+    captions: BTreeSet<String>,
+    #[cfg(feature = "flows")]
+    missing_figures: Vec<HashMap<String, String>>,
+    yielded: HashSet<String>,
+    // A module heading has been read, so text outside modules is no procedure
+    has_modules: bool,
+    report: PageReport,
+    drained: bool,
+    finished: bool,
+}
+
+impl<'a, R: Read> ExtractionStream<'a, R> {
+    pub fn new(engine: &'a ExtractionEngine, input: R) -> std::io::Result<Self> {
+        let pages = PageReader::new(input)?;
+        let report = PageReport { encoding: Some(pages.encoding()), ..PageReport::default() };
+        let mut rules = engine.clone();
+        let ids = rules.take_ids();
+        Ok(Self {
+            engine,
+            rules,
+            ids,
+            pages,
+            running: None,
+            max_window_pages: DEFAULT_WINDOW_PAGES,
+            buffer: String::new(),
+            buffer_start: 0,
+            buffered: VecDeque::new(),
+            reported_pages: 0,
+            normalized_base: 0,
+            entities: Vec::new(),
+            entity_index: HashMap::new(),
+            #[cfg(feature = "flows")]
+            captions: BTreeSet::new(),
+            #[cfg(feature = "flows")]
+            missing_figures: Vec::new(),
+            yielded: HashSet::new(),
+            has_modules: false,
+            report,
+            drained: false,
+            finished: false,
+        })
+    }
+
+    pub fn with_max_window_pages(mut self, max_window_pages: usize) -> Self {
+        self.max_window_pages = max_window_pages.max(1);
+        self
+    }
+
+    pub fn with_running_lines(mut self, running: RunningLines) -> Self {
+        self.running = Some(running);
+        self
+    }
+
+    // Pages read so far and the ones that failed
+    pub fn report(&self) -> &PageReport {
+        &self.report
+    }
+
+    pub fn get_input(&self) -> &R {
+        self.pages.get_input()
+    }
+
+    fn read_page(&mut self) -> std::io::Result<bool> {
+        let Some(page) = self.pages.next_page()? else {
+            return Ok(false);
+        };
+        self.report.page_count += 1;
+        self.report.failures.extend(page.failure.clone());
+        if page.number > 1 {
+            self.buffer.push(PAGE_BREAK);
+        }
+        match &self.running {
+            Some(running) => self.buffer.push_str(&running.blank_page(&page.text)),
+            None => self.buffer.push_str(&page.text),
+        }
+        self.buffered.push_back(BufferedPage {
+            number: page.number,
+            start_offset: page.start_offset,
+            end_offset: page.start_offset + page.text.len(),
+            failure: page.failure,
+        });
+        Ok(true)
+    }
+
+    // Where the buffer is cut, and whether at a heading: at the last module heading after its
+    // start, or at the last page once it holds more than a window's pages
+    fn cut(&self) -> Option<(usize, bool)> {
+        let heading = self.rules.find_modules(&self.buffer).iter()
+            .filter_map(|module| module.get("original_start")?.parse::<usize>().ok())
+            .filter(|&start| start > 0)
+            .max();
+        if let Some(heading) = heading {
+            return Some((heading, true));
+        }
+        let last = self.buffered.back().filter(|_| self.buffered.len() > self.max_window_pages)?;
+        next_visible(&self.buffer, last.start_offset - self.buffer_start).filter(|&start| start > 0).map(|start| (start, false))
+    }
+
+    fn next_window(&mut self) -> std::io::Result<Option<StreamWindow>> {
+        if self.drained {
+            return Ok(None);
+        }
+        while self.read_page()? {
+            if let Some((cut, at_heading)) = self.cut() {
+                self.has_modules |= at_heading;
+                let rest = self.buffer.split_off(cut);
+                let text = std::mem::replace(&mut self.buffer, rest);
+                return Ok(Some(self.extract_window(text, false)));
+            }
+        }
+        self.drained = true;
+        if self.buffered.is_empty() {
+            return Ok(None);
+        }
+        if self.pages.ended_on_break() {
+            self.buffer.push(PAGE_BREAK);
+        }
+        let text = std::mem::take(&mut self.buffer);
+        Ok(Some(self.extract_window(text, true)))
+    }
+
+    fn extract_window(&mut self, text: String, last: bool) -> StreamWindow {
+        profile_span!("stream_window");
+        let start_offset = self.buffer_start;
+        let end_offset = start_offset + text.len();
+        let mut collections = self.rules.extract_collections(&text);
+        if let Some(pii) = self.rules.scan_pii(&text) {
+            collections.push(("pii", pii));
+        }
+        #[cfg(feature = "flows")]
+        self.captions.extend(figure_captions(&text));
+
+        for (collection, records) in collections.iter_mut() {
+            match *collection {
+                "modules" => {
+                    self.rules.retire_patterns("module", records);
+                    self.has_modules |= !records.is_empty();
+                }
+                "steps" => {
+                    self.rules.retire_patterns("step", records);
+                    let last_step = records.iter().max_by_key(|step| step.get("original_start").and_then(|start| start.parse::<usize>().ok()));
+                    if let Some(step_id) = last_step.and_then(|step| step.get("record_id")) {
+                        self.rules.continue_step(Some(step_id.clone()));
+                    }
+                }
+                // Text without modules is one procedure, but only in a document without any
+                "procedures" | "findings" if self.has_modules => records.retain(|record| record.get("module_id").is_some_and(|id| !id.is_empty())),
+                _ => {}
+            }
+            for record in records.iter_mut() {
+                rebase(record, start_offset, self.normalized_base);
+                rekey(collection, record);
+            }
+            match *collection {
+                "entities" => {
+                    for record in records.drain(..) {
+                        let record_id = record.get("record_id").cloned().unwrap_or_default();
+                        match self.entity_index.get(&record_id) {
+                            Some(&index) => merge_entity(&mut self.entities[index], record),
+                            None => {
+                                self.entity_index.insert(record_id, self.entities.len());
+                                self.entities.push(record);
+                            }
+                        }
+                    }
+                }
+                #[cfg(feature = "flows")]
+                "findings" => {
+                    let (missing, found): (Vec<_>, Vec<_>) = records.drain(..).partition(|record| record.get("check").is_some_and(|check| check == MISSING_FIGURE));
+                    *records = found;
+                    self.missing_figures.extend(missing.into_iter().filter(|record| record.get("figure").is_none_or(|figure| !self.captions.contains(figure))));
+                }
+                _ => {}
+            }
+        }
+        if last {
+            for (collection, records) in collections.iter_mut() {
+                match *collection {
+                    "entities" => records.append(&mut self.entities),
+                    #[cfg(feature = "flows")]
+                    "findings" => records.extend(self.missing_figures.drain(..).filter(|record| record.get("figure").is_none_or(|figure| !self.captions.contains(figure)))),
+                    _ => {}
+                }
+            }
+        }
+        for (_, records) in collections.iter_mut() {
+            self.ids.apply_records(records);
+            records.retain(|record| record.get("record_id").is_none_or(|id| self.yielded.insert(id.clone())));
+        }
+
+        let first_page = self.buffered.front().map(|page| page.number).unwrap_or(1);
+        let window_pages: Vec<&BufferedPage> = self.buffered.iter().filter(|page| last || page.start_offset < end_offset).collect();
+        let last_page = window_pages.last().map(|page| page.number).unwrap_or(first_page);
+        let failures = window_pages.iter()
+            .filter(|page| page.number > self.reported_pages)
+            .filter_map(|page| page.failure.clone())
+            .collect();
+        self.reported_pages = last_page;
+        let failures = PageReport { page_count: window_pages.len(), failures, ..PageReport::default() };
+        collections.push(("page_failures", failures.failure_records()));
+
+        if !last {
+            // The normalized length of the window as it is in the whole text, where the end of
+            // the window normalizes with the start of the next
+            let lookahead: String = self.buffer.chars().take(LOOKAHEAD_CHARS).collect();
+            self.normalized_base += normalize(&(text.clone() + &lookahead), self.rules.get_normalization()).to_normalized(text.len());
+            while self.buffered.front().is_some_and(|page| page.end_offset < end_offset) {
+                self.buffered.pop_front();
+            }
+        }
+        self.buffer_start = end_offset;
+        StreamWindow { first_page, last_page, start_offset, collections }
+    }
+}
+
+impl<R: Read> Iterator for ExtractionStream<'_, R> {
+    type Item = Result<StreamWindow, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.next_window() {
+            Ok(Some(window)) => Some(Ok(window)),
+            Ok(None) => {
+                self.finished = true;
+                self.engine.get_error_budget().check(&self.report).err().map(|e| Err(e.into()))
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
+
+// The running headers and footers of a text layer, counted in a first pass over it so a
+// stream of it can blank them as extraction of the whole text does
+pub fn scan_running_lines<R: Read>(input: R, config: &LayoutConfig) -> std::io::Result<RunningLines> {
+    let mut pages = PageReader::new(input)?;
+    let mut counter = RunningLineCounter::new(config);
+    while let Some(page) = pages.next_page()? {
+        counter.add_page(&page.text);
+    }
+    Ok(counter.finish())
+}
+
+type Collections = Vec<(&'static str, Vec<HashMap<String, String>>)>;
+
+fn write_collection<W: Write>(output: &mut W, collection: &str, records: Vec<HashMap<String, String>>) -> Result<(), Box<dyn std::error::Error>> {
+    for mut record in records {
+        record.insert("collection".to_string(), collection.to_string());
+        serde_json::to_writer(&mut *output, &record)?;
+        output.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+// Windows written as JSON Lines, a record per line with its `collection`, then the records
+// `trailing` has once the stream is through. Returns the windows and records written
+fn write_records<R: Read, W: Write>(
+    stream: &mut ExtractionStream<'_, R>,
+    output: &mut W,
+    trailing: impl FnOnce(&ExtractionStream<'_, R>) -> Collections,
+) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let (mut windows, mut records) = (0, 0);
+    for window in &mut *stream {
+        let window = window?;
+        windows += 1;
+        records += window.record_count();
+        for (collection, collection_records) in window.collections {
+            write_collection(output, collection, collection_records)?;
+        }
+    }
+    for (collection, collection_records) in trailing(stream) {
+        records += collection_records.len();
+        write_collection(output, collection, collection_records)?;
+    }
+    Ok((windows, records))
+}
+
+// Writes a stream's records to `output_path`, through a temporary file, so a document over
+// budget or an interrupted run leaves no partial output. Encrypted to the profile's delivery
+// recipients like a job output
+fn write_stream<R: Read>(
+    engine: &ExtractionEngine,
+    mut stream: ExtractionStream<'_, R>,
+    input_path: &str,
+    output_path: &str,
+    trailing: impl FnOnce(&ExtractionStream<'_, R>) -> Collections,
+) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let delivery = engine.get_delivery();
+    let written = delivery.output_path(std::path::Path::new(output_path));
+    let mut file = AtomicFile::create(&written).map_err(|e| format!("Cannot write {}: {}", written.display(), e))?;
+    let (windows, records) = if delivery.is_enabled() {
+        let mut writer = SealWriter::new(file, &delivery.recipients)?;
+        let counts = write_records(&mut stream, &mut writer, trailing).map_err(|e| format!("{}: {}", input_path, e))?;
+        writer.finish()?.commit()?;
+        counts
+    } else {
+        let counts = write_records(&mut stream, &mut file, trailing).map_err(|e| format!("{}: {}", input_path, e))?;
+        file.commit()?;
+        counts
+    };
+
+    let report = stream.report();
+    let mut summary = HashMap::new();
+    summary.insert("output_path".to_string(), written.to_string_lossy().to_string());
+    summary.insert("pages".to_string(), report.page_count.to_string());
+    summary.insert("failed_pages".to_string(), report.failures.len().to_string());
+    summary.insert("completeness".to_string(), format!("{:.3}", report.completeness()));
+    summary.insert("windows".to_string(), windows.to_string());
+    summary.insert("records".to_string(), records.to_string());
+    if let Some(encoding) = report.encoding {
+        summary.insert("encoding".to_string(), encoding.encoding.name().to_string());
+    }
+    Ok(summary)
+}
+
+// A stream of `open`'s text layer, blanking running lines counted in a first pass over it when
+// the profile strips them
+fn open_stream<'a, R: Read>(
+    engine: &'a ExtractionEngine,
+    open: impl Fn() -> Result<R, Box<dyn std::error::Error>>,
+    max_window_pages: usize,
+) -> Result<ExtractionStream<'a, R>, Box<dyn std::error::Error>> {
+    let stream = engine.extract_stream(open()?)?.with_max_window_pages(max_window_pages);
+    let layout = engine.get_layout();
+    Ok(match layout.strip_running_lines {
+        true => stream.with_running_lines(scan_running_lines(open()?, layout)?),
+        false => stream,
+    })
+}
+
+// Streams a text layer's records to `output_path`. A PDF without a converter tool is read
+// through its native text layer, a page at a time
+pub fn extract_stream_file(
+    engine: &ExtractionEngine,
+    input_path: &str,
+    output_path: &str,
+    max_window_pages: usize,
+) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    if engine.get_converters().tool_for(input_path).is_some() {
+        return Err(format!("{} is read through converters.tools; streaming reads text layers as they are - convert it first", input_path).into());
+    }
+    if is_pdf(input_path) {
+        return extract_stream_pdf(engine, input_path, output_path, max_window_pages);
+    }
+    let open = || std::fs::File::open(input_path).map_err(|e| format!("Cannot read {}: {}", input_path, e).into());
+    let stream = open_stream(engine, open, max_window_pages)?;
+    write_stream(engine, stream, input_path, output_path, |_| Vec::new())
+}
+
+// The PDF is parsed once and its pages read as text twice when running lines are stripped.
+// Form fields and what could not be read follow the windows, as `forms` and
+// `converter_warnings` records
+#[cfg(feature = "figures")]
+fn extract_stream_pdf(
+    engine: &ExtractionEngine,
+    input_path: &str,
+    output_path: &str,
+    max_window_pages: usize,
+) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let document = PdfDocument::read(input_path)?;
+    let stream = open_stream(engine, || PdfTextPages::new(&document, input_path), max_window_pages)?;
+    write_stream(engine, stream, input_path, output_path, |stream| {
+        let mut trailing = Vec::new();
+        let mut warnings = stream.get_input().warnings().to_vec();
+        match engine.extract_forms(input_path) {
+            Ok(fields) if !fields.is_empty() => trailing.push(("forms", fields)),
+            Ok(_) => {}
+            Err(e) => warnings.push(ConverterWarning {
+                source: input_path.to_string(),
+                tool: NATIVE_TOOL.to_string(),
+                line: warnings.len() + 1,
+                message: format!("Forms: {}", e),
+            }),
+        }
+        if !warnings.is_empty() {
+            trailing.push(("converter_warnings", warnings.iter().map(ConverterWarning::get_info).collect()));
+        }
+        trailing
+    })
+}
+
+#[cfg(not(feature = "figures"))]
+fn extract_stream_pdf(_engine: &ExtractionEngine, input_path: &str, _output_path: &str, _max_window_pages: usize) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    Err(format!("{} is a PDF; streaming one reads its text layer natively, which needs a build with the figures feature", input_path).into())
+}
+
+// Python binding - extracts a text layer too large to load a window of pages at a time, into
+// a JSON Lines file that read_records pages through
+#[pyfunction]
+#[pyo3(signature = (input_path, rules_path, output_path, max_window_pages=DEFAULT_WINDOW_PAGES))]
+pub fn extract_stream(py: Python, input_path: &str, rules_path: &str, output_path: &str, max_window_pages: usize) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+        if max_window_pages == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("max_window_pages must be at least 1"));
+        }
+        let engine = ExtractionEngine::from_rules_file(rules_path).map_err(to_py_err)?;
+        extract_stream_file(&engine, input_path, output_path, max_window_pages).map_err(to_py_err)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::layout::strip_running_lines;
+    use crate::fixtures::manual::{FixtureConfig, SyntheticManual};
+
+    // Records of a collection as sorted JSON, so outputs compare whatever order they come in
+    fn sorted(records: &[HashMap<String, String>]) -> Vec<String> {
+        let mut records: Vec<String> = records.iter().map(|record| serde_json::to_string(&record.iter().collect::<std::collections::BTreeMap<_, _>>()).unwrap()).collect();
+        records.sort();
+        records
+    }
+
+    // A synthetic manual with a running header and footer on every page, and a figure
+    // referred to on its first page and captioned on its last
+    fn manual(lines_per_page: usize) -> (String, ExtractionEngine) {
+        let config = FixtureConfig { lines_per_page, ..FixtureConfig::default() };
+        let manual = SyntheticManual::generate(&config).unwrap();
+        let pages = manual.pages();
+        let mut text = String::new();
+        for (index, page) in pages.iter().enumerate() {
+            text.push_str("AIRCRAFT MAINTENANCE MANUAL\n");
+            if index == 0 {
+                text.push_str("Refer to Figure 999 for the zones.\n");
+            }
+            text.push_str(page);
+            if index == pages.len() - 1 {
+                text.push_str("Figure 999 Zones\n");
+            }
+            text.push_str(&format!("Page {}\n\x0c", index + 1));
+        }
+        let mut engine = ExtractionEngine::new();
+        engine.load_config(manual.rules().to_string().as_bytes()).unwrap();
+        (text, engine)
+    }
+
+    fn whole(engine: &ExtractionEngine, text: &str) -> HashMap<&'static str, Vec<String>> {
+        let text = strip_running_lines(text, engine.get_layout());
+        let mut collections = engine.extract_collections(&text);
+        collections.extend(engine.scan_pii(&text).map(|pii| ("pii", pii)));
+        collections.iter().map(|(name, records)| (*name, sorted(records))).collect()
+    }
+
+    fn streamed(engine: &ExtractionEngine, text: &str, max_window_pages: usize) -> (HashMap<&'static str, Vec<String>>, usize) {
+        let running = scan_running_lines(Cursor::new(text.as_bytes()), engine.get_layout()).unwrap();
+        let stream = engine.extract_stream(Cursor::new(text.as_bytes())).unwrap()
+            .with_max_window_pages(max_window_pages)
+            .with_running_lines(running);
+        let mut collections: HashMap<&'static str, Vec<HashMap<String, String>>> = HashMap::new();
+        let mut windows = 0;
+        for window in stream {
+            windows += 1;
+            for (name, records) in window.unwrap().collections {
+                if name != "page_failures" {
+                    collections.entry(name).or_default().extend(records);
+                }
+            }
+        }
+        (collections.iter().map(|(name, records)| (*name, sorted(records))).collect(), windows)
+    }
+
+    #[test]
+    fn stream_matches_whole_document() {
+        for lines_per_page in [10, 17, 30] {
+            let (text, engine) = manual(lines_per_page);
+            let whole = whole(&engine, &text);
+            let (stream, windows) = streamed(&engine, &text, DEFAULT_WINDOW_PAGES);
+            assert!(windows > 1, "{} lines a page gave one window", lines_per_page);
+            assert!(whole["modules"].len() > 1 && !whole["steps"].is_empty());
+            for (name, records) in &whole {
+                assert_eq!(stream.get(name).unwrap_or(&Vec::new()), records, "{} with {} lines a page", name, lines_per_page);
+            }
+        }
+    }
+
+    #[test]
+    fn stream_yields_each_record_once() {
+        let (text, engine) = manual(10);
+        let (stream, _) = streamed(&engine, &text, 1);
+        for (name, records) in stream {
+            let ids: HashSet<&str> = records.iter().map(String::as_str).collect();
+            assert_eq!(ids.len(), records.len(), "{}", name);
+        }
+    }
+}
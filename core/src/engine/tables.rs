@@ -0,0 +1,817 @@
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+use crate::engine::entities::record_extents;
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::ids::stable_record_id;
+use crate::engine::scope::scope_from_args;
+
+// Header words naming the column that says what a row is about
+const COMPONENT_WORDS: &[&str] = &[
+    "component", "components", "part", "parts", "description", "location", "fastener",
+    "fasteners", "nomenclature", "name", "bolt", "bolts", "nut", "nuts",
+];
+// Header words of a column numbering the rows - its numbers are not values
+const ITEM_WORDS: &[&str] = &["item", "items", "no", "no.", "key", "ref", "ref."];
+const UNIT_WORDS: &[&str] = &["unit", "units", "uom"];
+const CONDITION_WORDS: &[&str] = &[
+    "condition", "conditions", "remark", "remarks", "note", "notes", "configuration", "effectivity",
+    "applicability", "temperature range",
+];
+const MIN_WORDS: &[&str] = &["min", "min.", "minimum", "low", "lower", "from"];
+const MAX_WORDS: &[&str] = &["max", "max.", "maximum", "high", "upper", "to"];
+const NOMINAL_WORDS: &[&str] = &["nom", "nom.", "nominal", "value", "values", "target"];
+
+// Cells meaning "no value here"
+const EMPTY_CELLS: &[&str] = &["-", "--", "—", "–", "n/a", "na", "none"];
+
+// Header lines looked at above the first data row
+const MAX_HEADER_LINES: usize = 4;
+// Lines a wrapped cell may run on for below its row
+const MAX_WRAPPED_LINES: usize = 2;
+// Longest text after a number that still reads as its unit ("lbf.in", "N·m", "psi")
+const MAX_UNIT_LENGTH: usize = 10;
+const TAB_WIDTH: usize = 8;
+// Lines above a table searched for its "Table ..." title
+const MAX_TITLE_DISTANCE: usize = 3;
+
+// Column separators of ruled tables, drawn in ASCII or with box-drawing characters
+const VERTICAL_RULES: &[char] = &['|', '│', '┃', '║'];
+const HORIZONTAL_RULES: &[char] = &['-', '=', '─', '━', '═'];
+const RULE_JOINTS: &[char] = &['+', ':', '┼', '┬', '┴', '├', '┤', '┌', '┐', '└', '┘', '╪', '╫', '╬', '╞', '╡', '╔', '╗', '╚', '╝', '╠', '╣', '╦', '╩'];
+
+// A run of words with single spaces between them, placed by display column
+#[derive(Debug, Clone)]
+struct Cell {
+    text: String,
+    first_column: usize,
+    last_column: usize,
+}
+
+// Cells are separated by a tab or two or more spaces, the way layout-preserving text
+// conversion (pdftotext -layout) lays out table columns
+fn split_cells(line: &str) -> Vec<Cell> {
+    let mut cells: Vec<Cell> = Vec::new();
+    let mut column = 0;
+    let mut gap = 2;
+    let mut tab = false;
+    let mut current: Option<Cell> = None;
+    for c in line.chars() {
+        if c == '\t' {
+            column = (column / TAB_WIDTH + 1) * TAB_WIDTH;
+            gap += 1;
+            tab = true;
+            continue;
+        }
+        if c.is_whitespace() {
+            column += 1;
+            gap += 1;
+            continue;
+        }
+        match current.as_mut() {
+            Some(cell) if gap == 0 => cell.text.push(c),
+            Some(cell) if gap == 1 && !tab => {
+                cell.text.push(' ');
+                cell.text.push(c);
+            }
+            _ => {
+                cells.extend(current.take());
+                current = Some(Cell { text: c.to_string(), first_column: column, last_column: column });
+            }
+        }
+        if let Some(cell) = current.as_mut() {
+            cell.last_column = column;
+        }
+        column += 1;
+        gap = 0;
+        tab = false;
+    }
+    cells.extend(current);
+    cells
+}
+
+// A number as written: sign, digits and one decimal point or comma - returns it and the rest
+fn take_number(text: &str) -> Option<(&str, &str)> {
+    let bytes = text.as_bytes();
+    let mut end = usize::from(matches!(bytes.first(), Some(b'+' | b'-')));
+    let digits_start = end;
+    let mut decimal = false;
+    while end < bytes.len() {
+        match bytes[end] {
+            b'0'..=b'9' => end += 1,
+            b'.' | b',' if !decimal && bytes.get(end + 1).is_some_and(u8::is_ascii_digit) => {
+                decimal = true;
+                end += 1;
+            }
+            _ => break,
+        }
+    }
+    if !text[digits_start..end].bytes().any(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((&text[..end], &text[end..]))
+}
+
+fn decimals(number: &str) -> usize {
+    number.find(['.', ',']).map(|point| number.len() - point - 1).unwrap_or(0)
+}
+
+fn parse_number(number: &str) -> Option<f64> {
+    number.replace(',', ".").parse().ok()
+}
+
+// What one value cell says: a single value, a range, a value with a tolerance, or one bound
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Bounds {
+    min: Option<String>,
+    max: Option<String>,
+    nominal: Option<String>,
+    unit: Option<String>,
+}
+
+fn is_unit(text: &str) -> bool {
+    !text.is_empty()
+        && text.chars().count() <= MAX_UNIT_LENGTH
+        && !text.contains(' ')
+        && !text.chars().any(|c| c.is_ascii_digit())
+        && text.chars().any(|c| c.is_alphabetic() || c == '°' || c == '%' || c == '"')
+}
+
+// "25", "25 lbf.in", "25-30", "25 to 30", "25 ± 2", "0.5 max", "min. 12" - None when the cell
+// is not a value
+fn parse_bounds(cell: &str) -> Option<Bounds> {
+    let mut text = cell.trim();
+    let lower = text.to_lowercase();
+    let mut role = None;
+    let bound_words = MIN_WORDS.iter().map(|word| (*word, "min")).chain(MAX_WORDS.iter().map(|word| (*word, "max")));
+    for (word, name) in bound_words.filter(|(word, _)| word.len() > 2) {
+        if lower.starts_with(&format!("{} ", word)) {
+            text = text[word.len()..].trim_start();
+        } else if lower.ends_with(&format!(" {}", word)) {
+            text = text[..text.len() - word.len()].trim_end();
+        } else {
+            continue;
+        }
+        role = Some(name);
+        break;
+    }
+
+    let (first, rest) = take_number(text)?;
+    let rest = rest.trim_start();
+    let mut bounds = Bounds::default();
+    let mut unit = rest;
+    let range = ["-", "–", "to "].iter().find_map(|separator| rest.strip_prefix(separator));
+    let tolerance = ["±", "+/-", "+-"].iter().find_map(|separator| rest.strip_prefix(separator));
+    if let Some((second, after)) = range.and_then(|rest| take_number(rest.trim_start())) {
+        bounds.min = Some(first.to_string());
+        bounds.max = Some(second.to_string());
+        unit = after;
+    } else if let Some((plus_minus, after)) = tolerance.and_then(|rest| take_number(rest.trim_start())) {
+        let (nominal, spread) = (parse_number(first)?, parse_number(plus_minus)?);
+        let places = decimals(first).max(decimals(plus_minus));
+        bounds.min = Some(format!("{:.*}", places, nominal - spread));
+        bounds.max = Some(format!("{:.*}", places, nominal + spread));
+        bounds.nominal = Some(first.to_string());
+        unit = after;
+    } else {
+        match role {
+            Some("min") => bounds.min = Some(first.to_string()),
+            Some("max") => bounds.max = Some(first.to_string()),
+            _ => bounds.nominal = Some(first.to_string()),
+        }
+    }
+    let unit = unit.trim().trim_start_matches('(').trim_end_matches(')');
+    if !unit.is_empty() {
+        if !is_unit(unit) {
+            return None;
+        }
+        bounds.unit = Some(unit.to_string());
+    }
+    Some(bounds)
+}
+
+fn is_empty_cell(text: &str) -> bool {
+    EMPTY_CELLS.contains(&text.to_lowercase().as_str())
+}
+
+fn is_value_cell(text: &str) -> bool {
+    parse_bounds(text).is_some()
+}
+
+// A data row: two or more cells, at least one of them a value. Justified prose can space
+// words apart too, but ends its sentence with a full stop
+fn is_data_line(cells: &[Cell]) -> bool {
+    cells.len() >= 2
+        && cells.iter().skip(1).any(|cell| is_value_cell(&cell.text))
+        && !cells.last().is_some_and(|cell| cell.text.ends_with('.'))
+}
+
+fn is_title(line: &str) -> bool {
+    line.to_lowercase().starts_with("table ")
+}
+
+// A header line names columns: no values, and not a sentence
+fn is_header_line(line: &str, cells: &[Cell]) -> bool {
+    !cells.is_empty()
+        && !is_title(line)
+        && !line.ends_with('.')
+        && !cells.iter().any(|cell| take_number(&cell.text).is_some_and(|(_, rest)| rest.trim().is_empty()))
+}
+
+// A table found in layout text: its title line, header lines and rows
+#[derive(Debug, Clone)]
+pub struct TextTable {
+    pub title: String,
+    // "ruled" when drawn with column rules, "aligned" when laid out by whitespace alone
+    pub kind: &'static str,
+    // Label per column, top header line first: "TORQUE MIN"
+    pub columns: Vec<String>,
+    // Cell text per column, "" where a row leaves a column blank; with the row's offsets
+    pub rows: Vec<(Vec<String>, usize, usize)>,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Column extents from the data rows: cells that overlap share a column
+fn column_extents(rows: &[Vec<Cell>]) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = rows.iter()
+        .flat_map(|cells| cells.iter().map(|cell| (cell.first_column, cell.last_column)))
+        .collect();
+    spans.sort();
+    let mut columns: Vec<(usize, usize)> = Vec::new();
+    for (first, last) in spans {
+        match columns.last_mut() {
+            Some(column) if first <= column.1 => column.1 = column.1.max(last),
+            _ => columns.push((first, last)),
+        }
+    }
+    columns
+}
+
+fn column_of(columns: &[(usize, usize)], cell: &Cell) -> usize {
+    let centre = |first: usize, last: usize| (first + last) as f64 / 2.0;
+    columns.iter()
+        .position(|(first, last)| cell.first_column <= *last && *first <= cell.last_column)
+        .unwrap_or_else(|| {
+            let cell_centre = centre(cell.first_column, cell.last_column);
+            (0..columns.len())
+                .min_by(|a, b| {
+                    let distance = |index: usize| (centre(columns[index].0, columns[index].1) - cell_centre).abs();
+                    distance(*a).total_cmp(&distance(*b))
+                })
+                .unwrap_or(0)
+        })
+}
+
+// Column labels from the header lines. A line with fewer cells than there are columns has
+// merged cells: each covers the columns up to halfway to its neighbours, so "TORQUE" centred
+// over "MIN" and "MAX" labels both
+fn column_labels(columns: &[(usize, usize)], headers: &[Vec<Cell>]) -> Vec<String> {
+    let mut labels: Vec<Vec<String>> = vec![Vec::new(); columns.len()];
+    for header in headers {
+        if header.len() >= columns.len() {
+            for cell in header {
+                labels[column_of(columns, cell)].push(cell.text.clone());
+            }
+            continue;
+        }
+        for (index, cell) in header.iter().enumerate() {
+            let from = match index {
+                0 => 0,
+                _ => (header[index - 1].last_column + cell.first_column) / 2,
+            };
+            let to = match header.get(index + 1) {
+                Some(next) => (cell.last_column + next.first_column) / 2,
+                None => usize::MAX,
+            };
+            for (column, (first, last)) in columns.iter().enumerate() {
+                let centre = (first + last) / 2;
+                if centre >= from && centre <= to {
+                    labels[column].push(cell.text.clone());
+                }
+            }
+        }
+    }
+    labels.into_iter()
+        .map(|mut parts| {
+            parts.dedup();
+            parts.join(" ")
+        })
+        .collect()
+}
+
+// The "Table ..." line at most MAX_TITLE_DISTANCE lines above `first`, over blank lines only
+fn title_above(lines: &[(String, Vec<Cell>, usize, usize)], first: usize) -> Option<(String, usize)> {
+    lines[..first].iter().rev()
+        .take(MAX_TITLE_DISTANCE)
+        .take_while(|(line, _, _, _)| line.is_empty() || is_title(line))
+        .find(|(line, _, _, _)| is_title(line))
+        .map(|(line, _, start, _)| (line.clone(), *start))
+}
+
+// "+------+-----+", "|:---|---:|", "├──────┼─────┤"
+fn is_rule_line(line: &str) -> bool {
+    line.chars().any(|c| HORIZONTAL_RULES.contains(&c))
+        && line.chars().all(|c| c.is_whitespace() || HORIZONTAL_RULES.contains(&c) || VERTICAL_RULES.contains(&c) || RULE_JOINTS.contains(&c))
+}
+
+// The cells of "| Bolt | 25 lbf.in |": a line opening and closing with a column rule
+fn ruled_cells(line: &str) -> Option<Vec<String>> {
+    let inner = line.strip_prefix(VERTICAL_RULES)?.strip_suffix(VERTICAL_RULES)?;
+    let cells: Vec<String> = inner.split(VERTICAL_RULES).map(|cell| cell.trim().to_string()).collect();
+    (cells.len() >= 2).then_some(cells)
+}
+
+fn names_columns(cells: &[String]) -> bool {
+    cells.iter().any(|cell| !cell.is_empty())
+        && !cells.iter().any(|cell| take_number(cell).is_some_and(|(_, rest)| rest.trim().is_empty()))
+}
+
+// Cells of several lines joined column by column: wrapped cells of a grid row, or stacked
+// header lines
+fn join_lines(lines: &[&Vec<String>], columns: usize) -> Vec<String> {
+    (0..columns)
+        .map(|column| {
+            let mut parts: Vec<&str> = lines.iter()
+                .filter_map(|cells| cells.get(column).map(String::as_str))
+                .filter(|cell| !cell.is_empty())
+                .collect();
+            parts.dedup();
+            parts.join(" ")
+        })
+        .collect()
+}
+
+// Tables drawn with column rules, and the line range each covers. Rows are the lines between
+// rules when the table rules off every row (grid tables), otherwise one per line. The rows
+// above the first inner rule are the header - or the first row, when no rule sets it off and
+// it names columns rather than giving values
+fn find_ruled_tables(lines: &[(String, Vec<Cell>, usize, usize)]) -> Vec<(TextTable, usize, usize)> {
+    let mut tables = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let first = index;
+        while index < lines.len() && (is_rule_line(&lines[index].0) || ruled_cells(&lines[index].0).is_some()) {
+            index += 1;
+        }
+        if index == first {
+            index += 1;
+            continue;
+        }
+        let last = index - 1;
+        // "|---|---|" opens and closes with a column rule too, but is a rule
+        let block: Vec<(usize, Option<Vec<String>>)> = (first..=last)
+            .map(|line| (line, ruled_cells(&lines[line].0).filter(|_| !is_rule_line(&lines[line].0))))
+            .collect();
+        let row_lines = block.iter().filter(|(_, cells)| cells.is_some()).count();
+        let columns = block.iter().filter_map(|(_, cells)| cells.as_ref().map(Vec::len)).max().unwrap_or(0);
+        if row_lines < 2 || columns < 2 {
+            continue;
+        }
+
+        // Runs of row lines between rules, with whether a rule of '=' closes them
+        let mut segments: Vec<(Vec<usize>, bool)> = vec![(Vec::new(), false)];
+        for (position, (line, cells)) in block.iter().enumerate() {
+            let Some(segment) = segments.last_mut() else { continue };
+            if cells.is_some() {
+                segment.0.push(position);
+            } else if !segment.0.is_empty() {
+                segment.1 = lines[*line].0.contains(['=', '═']);
+                segments.push((Vec::new(), false));
+            }
+        }
+        segments.retain(|(positions, _)| !positions.is_empty());
+        // Grid tables rule off every row, so their rows average at most three lines. A header
+        // rule alone sets off only the header
+        let grid = segments.len() > 2 && segments.len() * 3 >= row_lines;
+        let cells_of = |position: usize| block[position].1.as_ref().expect("row line");
+        let line_of = |position: usize| block[position].0;
+
+        // (cells, start line, end line) per row, header rows first
+        let mut rows: Vec<(Vec<String>, usize, usize)> = if grid {
+            segments.iter()
+                .map(|(positions, _)| {
+                    let cells: Vec<&Vec<String>> = positions.iter().map(|position| cells_of(*position)).collect();
+                    (join_lines(&cells, columns), line_of(positions[0]), line_of(*positions.last().unwrap_or(&positions[0])))
+                })
+                .collect()
+        } else {
+            segments.iter().flat_map(|(positions, _)| positions.iter())
+                .map(|position| (join_lines(&[cells_of(*position)], columns), line_of(*position), line_of(*position)))
+                .collect()
+        };
+        let header_rows = match segments.first() {
+            Some((positions, _)) if segments.len() > 1 && !grid && positions.len() <= MAX_HEADER_LINES => positions.len(),
+            Some((_, double)) if grid && (*double || names_columns(&rows[0].0)) => 1,
+            _ if names_columns(&rows[0].0) && rows.len() > 1 => 1,
+            _ => 0,
+        };
+        let headers: Vec<Vec<String>> = rows.drain(..header_rows).map(|(cells, _, _)| cells).collect();
+        let labels = join_lines(&headers.iter().collect::<Vec<_>>(), columns);
+
+        let (title, start) = title_above(lines, first).unwrap_or_else(|| (String::new(), lines[first].2));
+        let rows = rows.into_iter().map(|(cells, first_line, last_line)| (cells, lines[first_line].2, lines[last_line].3)).collect();
+        tables.push((TextTable { title, kind: "ruled", columns: labels, rows, start, end: lines[last].3 }, first, last));
+    }
+    tables
+}
+
+// Tables in layout text: runs of data rows under one to four header lines, optionally titled
+// "Table ..." above. Lines of one cell inside the run continue the row above; a single blank
+// line between rows is allowed
+pub fn find_tables(text: &str) -> Vec<TextTable> {
+    let mut lines: Vec<(String, Vec<Cell>, usize, usize)> = Vec::new();
+    let mut offset = 0;
+    for raw in text.split_inclusive('\n') {
+        let content = raw.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_start_matches(|c: char| c.is_whitespace() || c == '\x0c').trim_end();
+        let start = offset + (content.len() - content.trim_start_matches(|c: char| c.is_whitespace() || c == '\x0c').len());
+        lines.push((trimmed.to_string(), split_cells(content), start, start + trimmed.len()));
+        offset += raw.len();
+    }
+
+    let mut tables = Vec::new();
+    let mut ruled = vec![false; lines.len()];
+    for (table, first, last) in find_ruled_tables(&lines) {
+        ruled[first..=last].iter_mut().for_each(|line| *line = true);
+        tables.push(table);
+    }
+    // The lines of ruled tables are not looked at again as aligned ones
+    let is_aligned_row = |index: usize| !ruled[index] && is_data_line(&lines[index].1);
+    let mut index = 0;
+    while index < lines.len() {
+        if !is_aligned_row(index) {
+            index += 1;
+            continue;
+        }
+        let body_start = index;
+        let mut body_end = index;
+        let mut cursor = index + 1;
+        let mut wrapped = 0;
+        while cursor < lines.len() {
+            let (line, cells, _, _) = &lines[cursor];
+            if is_aligned_row(cursor) {
+                body_end = cursor;
+                wrapped = 0;
+            } else if line.is_empty() {
+                if cursor + 1 >= lines.len() || !is_aligned_row(cursor + 1) {
+                    break;
+                }
+            } else if ruled[cursor] || cells.len() != 1 || is_title(line) || line.ends_with('.') || wrapped == MAX_WRAPPED_LINES {
+                break;
+            } else {
+                wrapped += 1;
+            }
+            cursor += 1;
+        }
+        index = body_end + 1;
+
+        let mut header_start = body_start;
+        while header_start > 0 && body_start - header_start < MAX_HEADER_LINES {
+            let (line, cells, _, _) = &lines[header_start - 1];
+            // A lone cell is a merged label over the header below it, never the only header
+            if ruled[header_start - 1] || !is_header_line(line, cells) || (cells.len() == 1 && header_start == body_start) {
+                break;
+            }
+            header_start -= 1;
+        }
+        if header_start == body_start {
+            continue;
+        }
+        let title = title_above(&lines, header_start);
+
+        let data: Vec<Vec<Cell>> = lines[body_start..=body_end].iter()
+            .filter(|(_, cells, _, _)| is_data_line(cells))
+            .map(|(_, cells, _, _)| cells.clone())
+            .collect();
+        let headers: Vec<Vec<Cell>> = lines[header_start..body_start].iter().map(|(_, cells, _, _)| cells.clone()).collect();
+        let columns = column_extents(&data);
+        if columns.len() < 2 {
+            continue;
+        }
+        let labels = column_labels(&columns, &headers);
+
+        let mut rows: Vec<(Vec<String>, usize, usize)> = Vec::new();
+        for (line, cells, start, end) in &lines[body_start..=body_end] {
+            if line.is_empty() {
+                continue;
+            }
+            if !is_data_line(cells) {
+                // A wrapped cell - joined to the same column of the row above
+                if let (Some(row), Some(cell)) = (rows.last_mut(), cells.first()) {
+                    let column = column_of(&columns, cell);
+                    let merged = format!("{} {}", row.0[column], cell.text);
+                    row.0[column] = merged.trim().to_string();
+                    row.2 = *end;
+                }
+                continue;
+            }
+            let mut values = vec![String::new(); columns.len()];
+            for cell in cells {
+                let column = column_of(&columns, cell);
+                values[column] = format!("{} {}", values[column], cell.text).trim().to_string();
+            }
+            rows.push((values, *start, *end));
+        }
+
+        let (title, start) = match title {
+            Some((title, start)) => (title, start),
+            None => (String::new(), lines[header_start].2),
+        };
+        tables.push(TextTable { title, kind: "aligned", columns: labels, end: lines[body_end].3, rows, start });
+    }
+    tables.sort_by_key(|table| table.start);
+    tables
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnRole {
+    Component,
+    Item,
+    Unit,
+    Condition,
+    Value,
+}
+
+fn label_words(label: &str) -> Vec<String> {
+    label.to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn has_word(label: &str, words: &[&str]) -> bool {
+    let lower = label.to_lowercase();
+    label_words(label).iter().any(|word| words.contains(&word.as_str()))
+        || words.iter().any(|word| word.contains(' ') && lower.contains(word))
+}
+
+fn column_role(label: &str, cells: &[&str]) -> ColumnRole {
+    let filled: Vec<&&str> = cells.iter().filter(|cell| !cell.is_empty() && !is_empty_cell(cell)).collect();
+    let values = filled.iter().filter(|cell| is_value_cell(cell)).count();
+    if has_word(label, UNIT_WORDS) {
+        ColumnRole::Unit
+    } else if has_word(label, COMPONENT_WORDS) {
+        ColumnRole::Component
+    } else if has_word(label, ITEM_WORDS) {
+        ColumnRole::Item
+    } else if has_word(label, CONDITION_WORDS) && values * 2 < filled.len().max(1) {
+        ColumnRole::Condition
+    } else if values * 2 > filled.len() {
+        ColumnRole::Value
+    } else {
+        ColumnRole::Component
+    }
+}
+
+// "TORQUE (LB-IN) MAX" -> ("torque", Some("LB-IN"), Some("max"))
+fn split_value_label(label: &str) -> (String, Option<String>, Option<&'static str>) {
+    let mut unit = None;
+    let mut text = label.to_string();
+    if let (Some(open), Some(close)) = (label.find('('), label.rfind(')')) {
+        if open < close {
+            let inner = label[open + 1..close].trim();
+            if is_unit(inner) {
+                unit = Some(inner.to_string());
+            }
+            text = format!("{} {}", &label[..open], &label[close + 1..]);
+        }
+    }
+    let mut bound = None;
+    let mut words = Vec::new();
+    for word in label_words(&text) {
+        if MIN_WORDS.contains(&word.as_str()) && word != "from" {
+            bound = Some("min");
+        } else if MAX_WORDS.contains(&word.as_str()) && word != "to" {
+            bound = Some("max");
+        } else if NOMINAL_WORDS.contains(&word.as_str()) {
+            bound = bound.or(Some("nominal"));
+        } else {
+            words.push(word);
+        }
+    }
+    (words.join(" "), unit, bound)
+}
+
+// The table's subject from its title: "Table 201. Torque Values" -> "torque"
+fn title_parameter(title: &str) -> String {
+    label_words(title).iter()
+        .skip(1)
+        .filter(|word| !word.chars().any(|c| c.is_ascii_digit()))
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|word| !word.is_empty() && !NOMINAL_WORDS.contains(word) && !matches!(*word, "limit" | "limits" | "data"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// The value columns of one parameter: "TORQUE MIN" and "TORQUE MAX" are both torque
+struct ParameterColumns {
+    name: String,
+    // From the header, as in "TORQUE (LB-IN)"
+    unit: Option<String>,
+    // Column and the bound it holds, None for a value or range
+    columns: Vec<(usize, Option<&'static str>)>,
+}
+
+// One long-format limit per row and parameter: a table of components against "TORQUE MIN",
+// "TORQUE MAX" and "CLEARANCE" columns becomes a torque and a clearance record per component
+pub fn unpivot_table(table: &TextTable) -> Vec<HashMap<String, String>> {
+    let roles: Vec<ColumnRole> = (0..table.columns.len())
+        .map(|column| {
+            let cells: Vec<&str> = table.rows.iter().map(|(cells, _, _)| cells[column].as_str()).collect();
+            column_role(&table.columns[column], &cells)
+        })
+        .collect();
+    let fallback = match title_parameter(&table.title) {
+        parameter if parameter.is_empty() => "value".to_string(),
+        parameter => parameter,
+    };
+    // Value columns grouped by parameter, in column order
+    let mut parameters: Vec<ParameterColumns> = Vec::new();
+    for (column, role) in roles.iter().enumerate() {
+        if *role != ColumnRole::Value {
+            continue;
+        }
+        let (name, unit, bound) = split_value_label(&table.columns[column]);
+        let name = if name.is_empty() { fallback.clone() } else { name };
+        match parameters.iter_mut().find(|parameter| parameter.name == name) {
+            Some(parameter) => {
+                parameter.columns.push((column, bound));
+                parameter.unit = parameter.unit.take().or(unit);
+            }
+            None => parameters.push(ParameterColumns { name, unit, columns: vec![(column, bound)] }),
+        }
+    }
+    let joined = |role: ColumnRole, cells: &[String]| {
+        roles.iter().zip(cells)
+            .filter(|(column_role, cell)| **column_role == role && !cell.is_empty() && !is_empty_cell(cell))
+            .map(|(_, cell)| cell.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut records = Vec::new();
+    for (cells, start, end) in &table.rows {
+        let component = joined(ColumnRole::Component, cells);
+        let condition = joined(ColumnRole::Condition, cells);
+        let item = joined(ColumnRole::Item, cells);
+        let row_unit = joined(ColumnRole::Unit, cells);
+        for parameter in &parameters {
+            let mut bounds = Bounds::default();
+            for (column, bound) in &parameter.columns {
+                let cell = &cells[*column];
+                let parsed = match parse_bounds(cell) {
+                    Some(parsed) => parsed,
+                    None => continue,
+                };
+                // A min or max column's single value is that bound
+                match (bound, parsed.nominal.clone(), parsed.min.is_none() && parsed.max.is_none()) {
+                    (Some("min"), Some(value), true) => bounds.min = Some(value),
+                    (Some("max"), Some(value), true) => bounds.max = Some(value),
+                    _ => {
+                        bounds.min = parsed.min.or(bounds.min);
+                        bounds.max = parsed.max.or(bounds.max);
+                        bounds.nominal = parsed.nominal.or(bounds.nominal);
+                    }
+                }
+                bounds.unit = bounds.unit.or(parsed.unit);
+            }
+            if bounds.min.is_none() && bounds.max.is_none() && bounds.nominal.is_none() {
+                continue;
+            }
+            // The cell's own unit, then the parameter's header, then the row's unit column
+            let unit = bounds.unit.clone()
+                .or_else(|| parameter.unit.clone())
+                .or_else(|| (!row_unit.is_empty()).then(|| row_unit.clone()))
+                .unwrap_or_default();
+            let mut record = HashMap::new();
+            record.insert("table".to_string(), table.title.clone());
+            record.insert("component".to_string(), component.clone());
+            if !item.is_empty() {
+                record.insert("item".to_string(), item.clone());
+            }
+            record.insert("parameter".to_string(), parameter.name.clone());
+            record.insert("min".to_string(), bounds.min.unwrap_or_default());
+            record.insert("max".to_string(), bounds.max.unwrap_or_default());
+            if let Some(nominal) = bounds.nominal {
+                record.insert("nominal".to_string(), nominal);
+            }
+            record.insert("unit".to_string(), unit);
+            record.insert("condition".to_string(), condition.clone());
+            record.insert("original_start".to_string(), start.to_string());
+            record.insert("original_end".to_string(), end.to_string());
+            records.push(record);
+        }
+    }
+    records
+}
+
+fn module_at(extents: &[(usize, usize, String)], offset: usize) -> String {
+    extents.iter()
+        .find(|(module_start, module_end, _)| offset >= *module_start && offset < *module_end)
+        .map(|(_, _, id)| id.clone())
+        .unwrap_or_default()
+}
+
+// Tables with the module they start in and their record ID. Tables of a module that share a
+// title are told apart by their order
+fn identified_tables(text: &str, modules: &[HashMap<String, String>]) -> Vec<(TextTable, String, String)> {
+    let extents = record_extents(modules, text.len());
+    let mut seen: HashMap<(String, String), usize> = HashMap::new();
+    find_tables(text).into_iter()
+        .map(|table| {
+            let module_id = module_at(&extents, table.start);
+            let ordinal = seen.entry((module_id.clone(), table.title.clone())).or_default();
+            *ordinal += 1;
+            let table_id = stable_record_id("table", &[&module_id, &table.title, &ordinal.to_string()]);
+            (table, module_id, table_id)
+        })
+        .collect()
+}
+
+// One record per table, its column labels and row cells as JSON arrays
+pub fn table_records(text: &str, modules: &[HashMap<String, String>]) -> Vec<HashMap<String, String>> {
+    identified_tables(text, modules).into_iter()
+        .map(|(table, module_id, table_id)| {
+            let rows: Vec<&Vec<String>> = table.rows.iter().map(|(cells, _, _)| cells).collect();
+            let mut record = HashMap::new();
+            record.insert("record_id".to_string(), table_id);
+            record.insert("module_id".to_string(), module_id);
+            record.insert("title".to_string(), table.title.clone());
+            record.insert("kind".to_string(), table.kind.to_string());
+            record.insert("columns".to_string(), serde_json::to_string(&table.columns).unwrap_or_default());
+            record.insert("rows".to_string(), serde_json::to_string(&rows).unwrap_or_default());
+            record.insert("column_count".to_string(), table.columns.len().to_string());
+            record.insert("row_count".to_string(), rows.len().to_string());
+            record.insert("original_start".to_string(), table.start.to_string());
+            record.insert("original_end".to_string(), table.end.to_string());
+            record
+        })
+        .collect()
+}
+
+// Limit records of every table, attached to the module whose extent contains the row and to
+// the table's own record
+pub fn limit_records(text: &str, modules: &[HashMap<String, String>]) -> Vec<HashMap<String, String>> {
+    let extents = record_extents(modules, text.len());
+    identified_tables(text, modules).iter()
+        .flat_map(|(table, _, table_id)| unpivot_table(table).into_iter().map(move |record| (record, table_id)))
+        .map(|(mut record, table_id)| {
+            let start: usize = record.get("original_start").and_then(|start| start.parse().ok()).unwrap_or(0);
+            let module_id = module_at(&extents, start);
+            let field = |name: &str| record.get(name).cloned().unwrap_or_default();
+            let record_id = stable_record_id("limit", &[&module_id, &field("table"), &field("component"), &field("parameter"), &field("condition")]);
+            record.insert("record_id".to_string(), record_id);
+            record.insert("module_id".to_string(), module_id);
+            record.insert("table_id".to_string(), table_id.clone());
+            record
+        })
+        .collect()
+}
+
+// Python binding - limits, torques and clearances from layout text tables, one record per
+// component and parameter
+#[pyfunction]
+#[pyo3(signature = (text, rules_path=None, pages=None, sections=None))]
+pub fn extract_limits(
+    py: Python,
+    text: &str,
+    rules_path: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => ExtractionEngine::new(),
+        };
+        let text = scope_from_args(pages, sections)?.apply(&engine, text)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(engine.extract_limits(&text))
+    })
+}
+
+// Python binding - ruled and whitespace-aligned tables with their column labels and rows, one
+// record per table
+#[pyfunction]
+#[pyo3(signature = (text, rules_path=None, pages=None, sections=None))]
+pub fn extract_tables(
+    py: Python,
+    text: &str,
+    rules_path: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => ExtractionEngine::new(),
+        };
+        let text = scope_from_args(pages, sections)?.apply(&engine, text)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(engine.extract_tables(&text))
+    })
+}
@@ -0,0 +1,213 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use utoipa::ToSchema;
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::jobs::taxonomy::{ATA_CHAPTERS, TAXONOMY_LEVELS};
+
+// The taxonomy a rules payload defines for downstream UIs, e.g.
+// "taxonomy": {"levels": [{"name": "chapter", "label": "Chapter"}, ...],
+//              "terms": [{"code": "32", "label": "Landing Gear", "children": [...]}]}.
+// Without one, the ATA chapters the classifier and browse tree use are the taxonomy
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TaxonomyLevelSchema {
+    // Key of the level, as classify_taxonomy reports it
+    pub name: String,
+    pub label: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+}
+
+// A term and the terms under it, one level down
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TaxonomyTerm {
+    pub code: String,
+    pub label: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(no_recursion)]
+    pub children: Vec<TaxonomyTerm>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TaxonomySchema {
+    // Top level first
+    #[serde(default)]
+    pub levels: Vec<TaxonomyLevelSchema>,
+    #[serde(default)]
+    pub terms: Vec<TaxonomyTerm>,
+}
+
+// The effective taxonomy, and whether the rules defined it or it is the built-in ATA one
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TaxonomySchemaResponse {
+    pub source: String,
+    pub levels: Vec<TaxonomyLevelSchema>,
+    pub terms: Vec<TaxonomyTerm>,
+}
+
+fn ata_schema() -> TaxonomySchema {
+    let descriptions = [
+        "ATA iSpec 2200 chapter, the system: 32 is Landing Gear",
+        "Section within the chapter, the subsystem: 32-11",
+        "Subject within the section, the unit or component: 32-11-00",
+    ];
+    TaxonomySchema {
+        levels: TAXONOMY_LEVELS.iter().zip(descriptions)
+            .map(|(name, description)| {
+                let mut label = name.to_string();
+                label[..1].make_ascii_uppercase();
+                TaxonomyLevelSchema { name: name.to_string(), label, description: description.to_string() }
+            })
+            .collect(),
+        terms: ATA_CHAPTERS.iter()
+            .map(|(code, title)| TaxonomyTerm { code: code.to_string(), label: title.to_string(), description: String::new(), children: Vec::new() })
+            .collect(),
+    }
+}
+
+fn validate_terms<'a>(terms: &'a [TaxonomyTerm], depth: usize, levels: &[TaxonomyLevelSchema], codes: &mut HashSet<&'a str>) -> Result<(), String> {
+    for term in terms {
+        if term.code.trim().is_empty() || term.label.trim().is_empty() {
+            return Err(format!("taxonomy.terms: every {} term needs a code and a label", levels[depth].name));
+        }
+        // UIs key terms by code, so a code names one term across all levels
+        if !codes.insert(&term.code) {
+            return Err(format!("taxonomy.terms: code {:?} is used twice", term.code));
+        }
+        if !term.children.is_empty() {
+            if depth + 1 == levels.len() {
+                return Err(format!("taxonomy.terms: {:?} has children below the last level, {}", term.code, levels[depth].name));
+            }
+            validate_terms(&term.children, depth + 1, levels, codes)?;
+        }
+    }
+    Ok(())
+}
+
+// Terms of `theirs` replace the label and description of ours with the same code and add
+// their children to ours; new codes are appended
+fn merge_terms(ours: &mut Vec<TaxonomyTerm>, theirs: &[TaxonomyTerm]) {
+    for term in theirs {
+        match ours.iter_mut().find(|existing| existing.code == term.code) {
+            Some(existing) => {
+                existing.label = term.label.clone();
+                if !term.description.is_empty() {
+                    existing.description = term.description.clone();
+                }
+                merge_terms(&mut existing.children, &term.children);
+            }
+            None => ours.push(term.clone()),
+        }
+    }
+}
+
+impl TaxonomySchema {
+    pub fn is_defined(&self) -> bool {
+        !self.levels.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.is_defined() {
+            return match self.terms.is_empty() {
+                true => Ok(()),
+                false => Err("taxonomy.terms needs taxonomy.levels to say what level each term is at".to_string()),
+            };
+        }
+        let mut names = HashSet::new();
+        for level in &self.levels {
+            if level.name.trim().is_empty() || level.label.trim().is_empty() {
+                return Err("taxonomy.levels: every level needs a name and a label".to_string());
+            }
+            if !names.insert(level.name.as_str()) {
+                return Err(format!("taxonomy.levels: {} is listed twice", level.name));
+            }
+        }
+        validate_terms(&self.terms, 0, &self.levels, &mut HashSet::new())
+    }
+
+    // A later pack with the same levels extends the terms; one with other levels replaces the
+    // taxonomy, since terms only have a meaning under their own levels
+    pub fn merge(&mut self, other: &TaxonomySchema) {
+        if !other.is_defined() {
+            return;
+        }
+        let same_levels = self.levels.len() == other.levels.len()
+            && self.levels.iter().zip(&other.levels).all(|(ours, theirs)| ours.name == theirs.name);
+        if !same_levels {
+            *self = other.clone();
+            return;
+        }
+        self.levels = other.levels.clone();
+        merge_terms(&mut self.terms, &other.terms);
+    }
+
+    pub fn effective(&self) -> TaxonomySchemaResponse {
+        let (source, schema) = match self.is_defined() {
+            true => ("rules", self.clone()),
+            false => ("builtin", ata_schema()),
+        };
+        TaxonomySchemaResponse { source: source.to_string(), levels: schema.levels, terms: schema.terms }
+    }
+}
+
+// A level, a term or the whole schema, as Python gets it. Empty descriptions and children are
+// left out, as in the JSON the server sends
+type SchemaEntry = HashMap<String, PyObject>;
+
+impl TaxonomyLevelSchema {
+    fn to_py(&self, py: Python) -> SchemaEntry {
+        let mut entry = HashMap::from([
+            ("name".to_string(), self.name.to_object(py)),
+            ("label".to_string(), self.label.to_object(py)),
+        ]);
+        if !self.description.is_empty() {
+            entry.insert("description".to_string(), self.description.to_object(py));
+        }
+        entry
+    }
+}
+
+impl TaxonomyTerm {
+    fn to_py(&self, py: Python) -> SchemaEntry {
+        let mut entry = HashMap::from([
+            ("code".to_string(), self.code.to_object(py)),
+            ("label".to_string(), self.label.to_object(py)),
+        ]);
+        if !self.description.is_empty() {
+            entry.insert("description".to_string(), self.description.to_object(py));
+        }
+        if !self.children.is_empty() {
+            let children: Vec<SchemaEntry> = self.children.iter().map(|child| child.to_py(py)).collect();
+            entry.insert("children".to_string(), children.to_object(py));
+        }
+        entry
+    }
+}
+
+impl TaxonomySchemaResponse {
+    fn to_py(&self, py: Python) -> SchemaEntry {
+        let levels: Vec<SchemaEntry> = self.levels.iter().map(|level| level.to_py(py)).collect();
+        let terms: Vec<SchemaEntry> = self.terms.iter().map(|term| term.to_py(py)).collect();
+        HashMap::from([
+            ("source".to_string(), self.source.to_object(py)),
+            ("levels".to_string(), levels.to_object(py)),
+            ("terms".to_string(), terms.to_object(py)),
+        ])
+    }
+}
+
+// Python binding - the taxonomy a UI should offer, as a dict: the rules' own, else the ATA chapters
+#[pyfunction]
+#[pyo3(signature = (rules_path=None))]
+pub fn get_taxonomy_schema(py: Python, rules_path: Option<&str>) -> PyResult<SchemaEntry> {
+    let engine = match rules_path {
+        Some(path) => ExtractionEngine::from_rules_file(path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+        None => ExtractionEngine::new(),
+    };
+    Ok(engine.get_taxonomy().effective().to_py(py))
+}
@@ -0,0 +1,200 @@
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::profile::EXTENDS_KEY;
+use crate::storage::atomic::write_atomic;
+
+// One labeled record: the confidence the engine gave it and whether a reviewer accepted it
+#[derive(Debug, Clone, Copy)]
+struct Label {
+    confidence: f64,
+    correct: bool,
+}
+
+// Precision, recall and F1 of keeping the records at or above `threshold`. Recall is over the
+// correct records of the set, so what the patterns never matched is not counted
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Scores {
+    pub threshold: f64,
+    pub kept: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+impl Scores {
+    fn at(labels: &[Label], threshold: f64) -> Self {
+        let kept: Vec<&Label> = labels.iter().filter(|label| label.confidence >= threshold).collect();
+        let true_positives = kept.iter().filter(|label| label.correct).count() as f64;
+        let correct = labels.iter().filter(|label| label.correct).count() as f64;
+        let precision = if kept.is_empty() { 0.0 } else { true_positives / kept.len() as f64 };
+        let recall = if correct == 0.0 { 0.0 } else { true_positives / correct };
+        let f1 = if precision + recall == 0.0 { 0.0 } else { 2.0 * precision * recall / (precision + recall) };
+        Self { threshold, kept: kept.len(), precision, recall, f1 }
+    }
+
+    fn add_to(&self, prefix: &str, info: &mut HashMap<String, String>) {
+        info.insert(format!("{}threshold", prefix), format!("{:.2}", self.threshold));
+        info.insert(format!("{}kept", prefix), self.kept.to_string());
+        info.insert(format!("{}precision", prefix), format!("{:.3}", self.precision));
+        info.insert(format!("{}recall", prefix), format!("{:.3}", self.recall));
+        info.insert(format!("{}f1", prefix), format!("{:.3}", self.f1));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CategoryTuning {
+    pub category: String,
+    pub records: usize,
+    pub correct: usize,
+    pub current: Scores,
+    pub tuned: Scores,
+}
+
+impl CategoryTuning {
+    pub fn changed(&self) -> bool {
+        (self.tuned.threshold - self.current.threshold).abs() > f64::EPSILON
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("category".to_string(), self.category.clone());
+        info.insert("records".to_string(), self.records.to_string());
+        info.insert("correct".to_string(), self.correct.to_string());
+        info.insert("changed".to_string(), self.changed().to_string());
+        self.current.add_to("current_", &mut info);
+        self.tuned.add_to("", &mut info);
+        info
+    }
+}
+
+// Labels files map each category to its reviewed records, as the outputs list them:
+// {"step": [{"record_id": "...", "confidence": "0.85", "correct": false}, ...]}
+fn load_labels(labels_path: &str) -> Result<BTreeMap<String, Vec<Label>>, Box<dyn std::error::Error>> {
+    let data = std::fs::read(labels_path).map_err(|e| format!("Cannot read labels {}: {}", labels_path, e))?;
+    let value: Value = serde_json::from_slice(&data).map_err(|e| format!("Labels {} are not JSON: {}", labels_path, e))?;
+    let categories = value.as_object().ok_or("Labels must be an object of category -> records")?;
+    let mut labels = BTreeMap::new();
+    for (category, records) in categories {
+        let records = records.as_array().ok_or_else(|| format!("Labels for {} must be a list of records", category))?;
+        let mut parsed = Vec::new();
+        for (index, record) in records.iter().enumerate() {
+            let field = |name: &str| record.get(name).ok_or_else(|| format!("{} record {} has no {}", category, index + 1, name));
+            // Records are string maps in the outputs, so both "0.85" and 0.85 are accepted
+            let confidence = match field("confidence")? {
+                Value::String(text) => text.parse::<f64>().ok(),
+                other => other.as_f64(),
+            };
+            let correct = match field("correct")? {
+                Value::String(text) => text.parse::<bool>().ok(),
+                other => other.as_bool(),
+            };
+            match (confidence, correct) {
+                (Some(confidence), Some(correct)) => parsed.push(Label { confidence, correct }),
+                _ => return Err(format!("{} record {} needs a numeric confidence and a true/false correct", category, index + 1).into()),
+            }
+        }
+        labels.insert(category.clone(), parsed);
+    }
+    Ok(labels)
+}
+
+// Tries every confidence in the set as the threshold and keeps the best F1. Ties go to the
+// higher threshold, which drops more records for the same F1 and so never loses precision
+fn tune_category(category: &str, labels: &[Label], current: f64) -> CategoryTuning {
+    let current = Scores::at(labels, current);
+    let mut tuned = current;
+    let mut candidates: Vec<f64> = labels.iter().map(|label| label.confidence).collect();
+    candidates.sort_by(f64::total_cmp);
+    candidates.dedup();
+    for threshold in candidates {
+        let scores = Scores::at(labels, threshold);
+        if scores.f1 > tuned.f1 + f64::EPSILON || ((scores.f1 - tuned.f1).abs() <= f64::EPSILON && threshold > tuned.threshold) {
+            tuned = scores;
+        }
+    }
+    CategoryTuning {
+        category: category.to_string(),
+        records: labels.len(),
+        correct: labels.iter().filter(|label| label.correct).count(),
+        current,
+        tuned,
+    }
+}
+
+pub fn tune_thresholds_for(engine: &ExtractionEngine, labels_path: &str) -> Result<Vec<CategoryTuning>, Box<dyn std::error::Error>> {
+    let labels = load_labels(labels_path)?;
+    let mut tunings = Vec::new();
+    for (category, labels) in &labels {
+        if !engine.get_patterns().contains_key(category) {
+            return Err(format!("Labels name category {}, which the rules have no patterns for", category).into());
+        }
+        let current = engine.get_thresholds().get(category).copied().unwrap_or(0.0);
+        // Without a single correct record every threshold scores 0, so there is nothing to tune
+        if labels.iter().any(|label| label.correct) {
+            tunings.push(tune_category(category, labels, current));
+        }
+    }
+    Ok(tunings)
+}
+
+// `target` as the overlay at `from` names it: relative when both are on the same root
+fn relative_to(target: &Path, from: &Path) -> PathBuf {
+    let (Ok(target), Ok(from)) = (target.canonicalize(), from.canonicalize()) else { return target.to_path_buf() };
+    let target: Vec<Component> = target.components().collect();
+    let from: Vec<Component> = from.components().collect();
+    let common = target.iter().zip(&from).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return target.iter().collect();
+    }
+    let mut relative: PathBuf = from[common..].iter().map(|_| "..").collect();
+    relative.extend(&target[common..]);
+    relative
+}
+
+// A rules file that extends `rules_path` and only sets the tuned thresholds, so it loads
+// wherever the rules do and the rule pack itself stays untouched
+pub fn write_overlay(rules_path: &str, tunings: &[CategoryTuning], overlay_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let overlay_dir = Path::new(overlay_path).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let base = relative_to(Path::new(rules_path), overlay_dir);
+    let thresholds: serde_json::Map<String, Value> = tunings.iter()
+        .map(|tuning| (tuning.category.clone(), json!((tuning.tuned.threshold * 100.0).round() / 100.0)))
+        .collect();
+    let overlay = json!({ EXTENDS_KEY: base.display().to_string(), "thresholds": thresholds });
+    write_atomic(overlay_path, serde_json::to_string_pretty(&overlay)? + "\n")
+        .map_err(|e| format!("Cannot write overlay {}: {}", overlay_path, e))?;
+    Ok(())
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+}
+
+type TuningReport = (HashMap<String, String>, Vec<HashMap<String, String>>);
+
+// Python binding - offline threshold search over reviewed records; with overlay_path the
+// thresholds are written as a rules file extending rules_path
+#[pyfunction]
+#[pyo3(signature = (rules_path, labels_path, overlay_path=None))]
+pub fn tune_thresholds(py: Python, rules_path: &str, labels_path: &str, overlay_path: Option<&str>) -> PyResult<TuningReport> {
+    py.allow_threads(|| {
+        let engine = ExtractionEngine::from_rules_file(rules_path).map_err(to_py_err)?;
+        let tunings = tune_thresholds_for(&engine, labels_path).map_err(to_py_err)?;
+        if let Some(overlay_path) = overlay_path {
+            write_overlay(rules_path, &tunings, overlay_path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        }
+
+        let mut summary = HashMap::new();
+        summary.insert("categories".to_string(), tunings.len().to_string());
+        summary.insert("changed".to_string(), tunings.iter().filter(|tuning| tuning.changed()).count().to_string());
+        summary.insert("records".to_string(), tunings.iter().map(|tuning| tuning.records).sum::<usize>().to_string());
+        if let Some(overlay_path) = overlay_path {
+            summary.insert("overlay_path".to_string(), overlay_path.to_string());
+        }
+        Ok((summary, tunings.iter().map(CategoryTuning::get_info).collect()))
+    })
+}
@@ -0,0 +1,143 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::engine::ids::{in_namespace, split_namespace, stable_record_id};
+use crate::schema::envelope::payload_of;
+
+// Minutes of labour per unit of structure - a planning baseline, not a substitute for declared man-hours
+const BASE_MINUTES: f64 = 15.0;
+const MINUTES_PER_STEP: f64 = 5.0;
+const MINUTES_PER_BRANCH: f64 = 10.0;
+const MINUTES_PER_TOOL: f64 = 5.0;
+const MINUTES_PER_PANEL: f64 = 10.0;
+const MINUTES_PER_BREAKER: f64 = 2.0;
+const MINUTES_PER_CONDITION: f64 = 5.0;
+
+// Relative weights of the complexity score
+const BRANCH_WEIGHT: f64 = 2.0;
+const PANEL_WEIGHT: f64 = 1.5;
+const BREAKER_WEIGHT: f64 = 0.5;
+const CONDITION_WEIGHT: f64 = 0.5;
+
+fn field<'a>(record: &'a Value, name: &str) -> &'a str {
+    record.get(name).and_then(Value::as_str).unwrap_or("")
+}
+
+fn count(record: &Value, name: &str) -> f64 {
+    field(record, name).parse().unwrap_or(0.0)
+}
+
+// Distinct entries across comma-joined list fields
+fn list_len(record: &Value, names: &[&str]) -> f64 {
+    let mut values: Vec<&str> = names.iter()
+        .flat_map(|name| field(record, name).split(','))
+        .filter(|value| !value.is_empty())
+        .collect();
+    values.sort_unstable();
+    values.dedup();
+    values.len() as f64
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkloadEstimate {
+    pub module_id: String,
+    pub steps: f64,
+    pub branches: f64,
+    pub tools: f64,
+    pub panels: f64,
+    pub breakers: f64,
+    pub conditions: f64,
+    pub declared_hours: Option<f64>,
+}
+
+impl WorkloadEstimate {
+    fn from_procedure(procedure: &Value, conditions: f64) -> Self {
+        Self {
+            module_id: field(procedure, "module_id").to_string(),
+            steps: count(procedure, "step_count"),
+            branches: count(procedure, "branch_count"),
+            tools: list_len(procedure, &["tools"]),
+            panels: list_len(procedure, &["access_panels_open", "access_panels_close"]),
+            breakers: list_len(procedure, &["circuit_breakers_pull", "circuit_breakers_close"]),
+            conditions,
+            declared_hours: field(procedure, "declared_man_hours").parse().ok(),
+        }
+    }
+
+    pub fn complexity_score(&self) -> f64 {
+        self.steps
+            + BRANCH_WEIGHT * self.branches
+            + self.tools
+            + PANEL_WEIGHT * self.panels
+            + BREAKER_WEIGHT * self.breakers
+            + CONDITION_WEIGHT * self.conditions
+    }
+
+    pub fn derived_minutes(&self) -> f64 {
+        BASE_MINUTES
+            + MINUTES_PER_STEP * self.steps
+            + MINUTES_PER_BRANCH * self.branches
+            + MINUTES_PER_TOOL * self.tools
+            + MINUTES_PER_PANEL * self.panels
+            + MINUTES_PER_BREAKER * self.breakers
+            + MINUTES_PER_CONDITION * self.conditions
+    }
+
+    // Declared man-hours win; the structural estimate is still reported alongside them
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let derived_hours = self.derived_minutes() / 60.0;
+        let (hours, source) = match self.declared_hours {
+            Some(hours) => (hours, "declared"),
+            None => (derived_hours, "derived"),
+        };
+        // Estimates share their module's namespace, and otherwise its ID
+        let (namespace, module_id) = split_namespace(&self.module_id);
+        let mut summary = HashMap::new();
+        summary.insert("record_id".to_string(), in_namespace(namespace, stable_record_id("workload", &[module_id])));
+        summary.insert("module_id".to_string(), self.module_id.clone());
+        summary.insert("complexity_score".to_string(), format!("{:.1}", self.complexity_score()));
+        summary.insert("estimated_hours".to_string(), format!("{:.2}", hours));
+        summary.insert("derived_hours".to_string(), format!("{:.2}", derived_hours));
+        summary.insert("duration_source".to_string(), source.to_string());
+        summary.insert("step_count".to_string(), self.steps.to_string());
+        summary.insert("branch_count".to_string(), self.branches.to_string());
+        summary.insert("tool_count".to_string(), self.tools.to_string());
+        summary.insert("panel_count".to_string(), self.panels.to_string());
+        summary.insert("breaker_count".to_string(), self.breakers.to_string());
+        summary.insert("condition_count".to_string(), self.conditions.to_string());
+        summary
+    }
+}
+
+// One estimate per procedure in a job, split or merge output (enveloped or flat)
+pub fn estimate_value(output: &Value) -> Result<Vec<WorkloadEstimate>, Box<dyn std::error::Error>> {
+    let payload = payload_of(output);
+    let procedures = payload.get("procedures")
+        .and_then(Value::as_array)
+        .ok_or("Results have no procedures - re-run extraction to include them")?;
+
+    let mut conditions: HashMap<&str, f64> = HashMap::new();
+    for condition in payload.get("conditions").and_then(Value::as_array).into_iter().flatten() {
+        *conditions.entry(field(condition, "module_id")).or_default() += 1.0;
+    }
+
+    Ok(procedures.iter()
+        .map(|procedure| {
+            let module_id = field(procedure, "module_id");
+            WorkloadEstimate::from_procedure(procedure, conditions.get(module_id).copied().unwrap_or(0.0))
+        })
+        .collect())
+}
+
+// Python binding - takes the output JSON text, returns one estimate per procedure
+#[pyfunction]
+pub fn estimate_workload(py: Python, results: &str) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let output: Value = serde_json::from_str(results)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid results JSON: {}", e)))?;
+        let estimates = estimate_value(&output)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(estimates.iter().map(WorkloadEstimate::get_summary).collect())
+    })
+}
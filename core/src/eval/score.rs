@@ -0,0 +1,233 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+// Unmatched spans listed per record type - enough to find the misses without dumping the set
+const MAX_LISTED_SPANS: usize = 20;
+
+// Offset fields, best first: outputs carry original_* next to the normalized start/end, and
+// outline entries use start_offset/end_offset
+const SPAN_FIELDS: [(&str, &str); 3] = [("original_start", "original_end"), ("start", "end"), ("start_offset", "end_offset")];
+
+// When a predicted span counts as finding a gold one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchCriterion {
+    Exact,
+    // Intersection over union of the two spans, 0 < min <= 1
+    Overlap(f64),
+}
+
+impl MatchCriterion {
+    pub fn parse(name: &str, min_overlap: f64) -> Result<Self, String> {
+        match name {
+            "exact" => Ok(MatchCriterion::Exact),
+            "overlap" if min_overlap > 0.0 && min_overlap <= 1.0 => Ok(MatchCriterion::Overlap(min_overlap)),
+            "overlap" => Err(format!("min_overlap must be above 0 and at most 1, got {}", min_overlap)),
+            other => Err(format!("Unknown match criterion: {} (expected exact, overlap)", other)),
+        }
+    }
+
+    // How well `predicted` matches `gold`, or None when it does not
+    fn score(&self, gold: &Span, predicted: &Span) -> Option<f64> {
+        if gold.source.is_some() && predicted.source.is_some() && gold.source != predicted.source {
+            return None;
+        }
+        match self {
+            MatchCriterion::Exact => (gold.start == predicted.start && gold.end == predicted.end).then_some(1.0),
+            MatchCriterion::Overlap(min) => {
+                let intersection = gold.end.min(predicted.end).saturating_sub(gold.start.max(predicted.start));
+                let union = gold.end.max(predicted.end) - gold.start.min(predicted.start);
+                let iou = if union == 0 { 1.0 } else { intersection as f64 / union as f64 };
+                (intersection > 0 || union == 0).then_some(iou).filter(|iou| iou >= min)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    // The input a merged output record came from, when it says so
+    source: Option<String>,
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    fn from_record(record: &Value) -> Option<Self> {
+        // Records are string maps in the outputs; hand-written gold files may use numbers
+        let offset = |name: &str| match record.get(name)? {
+            Value::String(text) => text.parse::<usize>().ok(),
+            other => other.as_u64().map(|offset| offset as usize),
+        };
+        let (start, end) = SPAN_FIELDS.iter().find_map(|(start, end)| Some((offset(start)?, offset(end)?)))?;
+        let source = record.get("source_file").and_then(Value::as_str).map(str::to_string);
+        (end >= start).then_some(Self { source, start, end })
+    }
+
+    fn describe(&self) -> String {
+        match &self.source {
+            Some(source) => format!("{}:{}-{}", source, self.start, self.end),
+            None => format!("{}-{}", self.start, self.end),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TypeScore {
+    pub record_type: String,
+    pub gold: usize,
+    pub predicted: usize,
+    pub true_positives: usize,
+    // Records of the type without offsets, which cannot be matched
+    pub unscored: usize,
+    pub missed: Vec<String>,
+    pub spurious: Vec<String>,
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 { 0.0 } else { numerator as f64 / denominator as f64 }
+}
+
+fn f1(precision: f64, recall: f64) -> f64 {
+    if precision + recall == 0.0 { 0.0 } else { 2.0 * precision * recall / (precision + recall) }
+}
+
+impl TypeScore {
+    pub fn precision(&self) -> f64 {
+        ratio(self.true_positives, self.predicted)
+    }
+
+    pub fn recall(&self) -> f64 {
+        ratio(self.true_positives, self.gold)
+    }
+
+    pub fn f1(&self) -> f64 {
+        f1(self.precision(), self.recall())
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("record_type".to_string(), self.record_type.clone());
+        info.insert("gold".to_string(), self.gold.to_string());
+        info.insert("predicted".to_string(), self.predicted.to_string());
+        info.insert("true_positives".to_string(), self.true_positives.to_string());
+        info.insert("false_positives".to_string(), (self.predicted - self.true_positives).to_string());
+        info.insert("false_negatives".to_string(), (self.gold - self.true_positives).to_string());
+        info.insert("precision".to_string(), format!("{:.3}", self.precision()));
+        info.insert("recall".to_string(), format!("{:.3}", self.recall()));
+        info.insert("f1".to_string(), format!("{:.3}", self.f1()));
+        info.insert("unscored".to_string(), self.unscored.to_string());
+        info.insert("missed".to_string(), self.missed.join(","));
+        info.insert("spurious".to_string(), self.spurious.join(","));
+        info
+    }
+}
+
+// Record collections of a gold or output file: the payload of a job, split or merge output,
+// or a bare object of record type -> records
+fn load_collections(path: &str) -> Result<BTreeMap<String, Vec<Value>>, Box<dyn std::error::Error>> {
+    let data = std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    let value: Value = serde_json::from_slice(&data).map_err(|e| format!("{} is not JSON: {}", path, e))?;
+    let value = value.get("payload").cloned().unwrap_or(value);
+    let object = value.as_object().ok_or_else(|| format!("{} must be an object of record type -> records", path))?;
+    Ok(object.iter()
+        .filter_map(|(record_type, records)| {
+            let records = records.as_array()?;
+            records.iter().all(Value::is_object).then(|| (record_type.clone(), records.clone()))
+        })
+        .collect())
+}
+
+fn spans(records: &[Value]) -> (Vec<Span>, usize) {
+    let spans: Vec<Span> = records.iter().filter_map(Span::from_record).collect();
+    let unscored = records.len() - spans.len();
+    (spans, unscored)
+}
+
+// One-to-one matching: the best-scoring pairs are taken first, so a predicted span is never
+// credited for two gold spans
+fn score_type(record_type: &str, gold: &[Value], predicted: &[Value], criterion: MatchCriterion) -> TypeScore {
+    let (gold_spans, gold_unscored) = spans(gold);
+    let (predicted_spans, predicted_unscored) = spans(predicted);
+    let mut pairs = Vec::new();
+    for (g, gold_span) in gold_spans.iter().enumerate() {
+        for (p, predicted_span) in predicted_spans.iter().enumerate() {
+            if let Some(score) = criterion.score(gold_span, predicted_span) {
+                pairs.push((score, g, p));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+    let mut gold_matched = vec![false; gold_spans.len()];
+    let mut predicted_matched = vec![false; predicted_spans.len()];
+    for (_, g, p) in pairs {
+        if !gold_matched[g] && !predicted_matched[p] {
+            gold_matched[g] = true;
+            predicted_matched[p] = true;
+        }
+    }
+    let unmatched = |spans: &[Span], matched: &[bool]| -> Vec<String> {
+        spans.iter().zip(matched).filter(|(_, matched)| !**matched).map(|(span, _)| span.describe()).take(MAX_LISTED_SPANS).collect()
+    };
+    TypeScore {
+        record_type: record_type.to_string(),
+        gold: gold_spans.len(),
+        predicted: predicted_spans.len(),
+        true_positives: gold_matched.iter().filter(|matched| **matched).count(),
+        unscored: gold_unscored + predicted_unscored,
+        missed: unmatched(&gold_spans, &gold_matched),
+        spurious: unmatched(&predicted_spans, &predicted_matched),
+    }
+}
+
+// Scores every record type the gold file annotates, or only `types`. Types the gold file
+// leaves out are not judged, since their absence says nothing about the predictions
+pub fn evaluate(gold_path: &str, predicted_path: &str, criterion: MatchCriterion, types: Option<&[String]>) -> Result<Vec<TypeScore>, Box<dyn std::error::Error>> {
+    let gold = load_collections(gold_path)?;
+    let predicted = load_collections(predicted_path)?;
+    if let Some(missing) = types.unwrap_or_default().iter().find(|record_type| !gold.contains_key(*record_type)) {
+        return Err(format!("{} has no {} annotations", gold_path, missing).into());
+    }
+    Ok(gold.iter()
+        .filter(|(record_type, _)| types.is_none_or(|types| types.contains(record_type)))
+        .map(|(record_type, gold)| score_type(record_type, gold, predicted.get(record_type).map(Vec::as_slice).unwrap_or_default(), criterion))
+        .collect())
+}
+
+// Micro-averaged totals over the types
+pub fn get_summary(scores: &[TypeScore], criterion: MatchCriterion) -> HashMap<String, String> {
+    let total = |count: fn(&TypeScore) -> usize| scores.iter().map(count).sum::<usize>();
+    let (gold, predicted, true_positives) = (total(|s| s.gold), total(|s| s.predicted), total(|s| s.true_positives));
+    let (precision, recall) = (ratio(true_positives, predicted), ratio(true_positives, gold));
+    let mut summary = HashMap::new();
+    summary.insert("record_types".to_string(), scores.len().to_string());
+    summary.insert("gold".to_string(), gold.to_string());
+    summary.insert("predicted".to_string(), predicted.to_string());
+    summary.insert("true_positives".to_string(), true_positives.to_string());
+    summary.insert("precision".to_string(), format!("{:.3}", precision));
+    summary.insert("recall".to_string(), format!("{:.3}", recall));
+    summary.insert("f1".to_string(), format!("{:.3}", f1(precision, recall)));
+    let criterion = match criterion {
+        MatchCriterion::Exact => "exact".to_string(),
+        MatchCriterion::Overlap(min) => format!("overlap>={}", min),
+    };
+    summary.insert("criterion".to_string(), criterion);
+    summary
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+}
+
+type EvaluationReport = (HashMap<String, String>, Vec<HashMap<String, String>>);
+
+// Python binding - precision, recall and F1 of an output against gold annotations, per record type
+#[pyfunction]
+#[pyo3(signature = (gold_path, predicted_path, criterion="overlap", min_overlap=0.5, types=None))]
+pub fn evaluate_extraction(py: Python, gold_path: &str, predicted_path: &str, criterion: &str, min_overlap: f64, types: Option<Vec<String>>) -> PyResult<EvaluationReport> {
+    py.allow_threads(|| {
+        let criterion = MatchCriterion::parse(criterion, min_overlap).map_err(|e| to_py_err(e.into()))?;
+        let scores = evaluate(gold_path, predicted_path, criterion, types.as_deref()).map_err(to_py_err)?;
+        Ok((get_summary(&scores, criterion), scores.iter().map(TypeScore::get_info).collect()))
+    })
+}
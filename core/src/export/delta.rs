@@ -0,0 +1,177 @@
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use chrono::Utc;
+
+use crate::licensing::branding::Branding;
+use crate::schema::envelope::{output_branding, output_watermark, payload_of, UNKNOWN_PROVENANCE};
+use crate::storage::atomic::write_atomic;
+
+// A record located by its stable ID, remembering which output collection it came from
+struct IndexedRecord<'a> {
+    collection: &'a str,
+    record: &'a Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeltaReport {
+    pub document: Option<String>,
+    pub generated_at: String,
+    pub added: Vec<Value>,
+    pub modified: Vec<Value>,
+    pub removed: Vec<Value>,
+    pub unchanged: usize,
+    // The current output's watermark, carried into the report
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branding: Option<Branding>,
+}
+
+impl DeltaReport {
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let mut summary = HashMap::new();
+        summary.insert("added".to_string(), self.added.len().to_string());
+        summary.insert("modified".to_string(), self.modified.len().to_string());
+        summary.insert("removed".to_string(), self.removed.len().to_string());
+        summary.insert("unchanged".to_string(), self.unchanged.to_string());
+        summary
+    }
+}
+
+// Where a removed record was last seen, for its tombstone: the caller's revision label, or else
+// the hash of the text the previous output was extracted from
+struct LastSeen {
+    revision: String,
+    at: String,
+}
+
+impl LastSeen {
+    fn of(previous: &Value, revision: Option<&str>) -> Self {
+        let provenance = |field: &str| previous.get("provenance")
+            .and_then(|provenance| provenance.get(field))
+            .and_then(Value::as_str)
+            .unwrap_or(UNKNOWN_PROVENANCE)
+            .to_string();
+        Self {
+            revision: revision.map(str::to_string).unwrap_or_else(|| provenance("input_hash")),
+            at: provenance("processed_at"),
+        }
+    }
+}
+
+// Every top-level array of objects carrying a record_id is treated as a record collection
+fn index_records(output: &Value) -> BTreeMap<&str, IndexedRecord<'_>> {
+    let mut index = BTreeMap::new();
+    if let Some(object) = output.as_object() {
+        for (collection, value) in object {
+            for record in value.as_array().into_iter().flatten() {
+                if let Some(record_id) = record.get("record_id").and_then(Value::as_str) {
+                    index.insert(record_id, IndexedRecord { collection, record });
+                }
+            }
+        }
+    }
+    index
+}
+
+fn changed_fields(before: &Value, after: &Value) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+
+    let mut fields: Vec<String> = before.keys()
+        .chain(after.keys())
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields.dedup();
+    fields
+}
+
+// Removed records are reported as tombstones, so stores fed the delta can retire them rather
+// than keep what the new revision no longer has
+pub fn compute_delta(previous: &Value, current: &Value, previous_revision: Option<&str>) -> DeltaReport {
+    let watermark = output_watermark(current);
+    let branding = output_branding(current);
+    let last_seen = LastSeen::of(previous, previous_revision);
+    let previous = payload_of(previous);
+    let current = payload_of(current);
+    let previous_index = index_records(previous);
+    let current_index = index_records(current);
+
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    let mut removed = Vec::new();
+    let mut unchanged = 0;
+
+    for (record_id, entry) in &current_index {
+        match previous_index.get(record_id) {
+            None => added.push(json!({
+                "record_id": record_id,
+                "collection": entry.collection,
+                "record": entry.record,
+            })),
+            Some(before) if before.record != entry.record => modified.push(json!({
+                "record_id": record_id,
+                "collection": entry.collection,
+                "changed_fields": changed_fields(before.record, entry.record),
+                "record": entry.record,
+            })),
+            Some(_) => unchanged += 1,
+        }
+    }
+
+    for (record_id, entry) in &previous_index {
+        if !current_index.contains_key(record_id) {
+            removed.push(json!({
+                "record_id": record_id,
+                "collection": entry.collection,
+                "tombstone": true,
+                "last_seen_revision": last_seen.revision,
+                "last_seen_at": last_seen.at,
+                "record": entry.record,
+            }));
+        }
+    }
+
+    DeltaReport {
+        document: current.get("source").and_then(Value::as_str).map(str::to_string),
+        generated_at: Utc::now().to_rfc3339(),
+        added,
+        modified,
+        removed,
+        unchanged,
+        watermark,
+        branding,
+    }
+}
+
+pub fn export_delta_file(previous_path: &str, current_path: &str, output_path: &str, previous_revision: Option<&str>) -> Result<DeltaReport, Box<dyn std::error::Error>> {
+    // No previous output means this is the first run - every record is new
+    let previous = if Path::new(previous_path).exists() {
+        serde_json::from_str(&std::fs::read_to_string(previous_path)?)?
+    } else {
+        Value::Null
+    };
+    let current: Value = serde_json::from_str(&std::fs::read_to_string(current_path)?)?;
+
+    let report = compute_delta(&previous, &current, previous_revision);
+    write_atomic(output_path, serde_json::to_string_pretty(&report)?)?;
+    Ok(report)
+}
+
+// Python binding - returns the added/modified/removed/unchanged counts. previous_revision labels
+// the tombstones of removed records, e.g. "Rev 41"
+#[pyfunction]
+#[pyo3(signature = (previous_path, current_path, output_path, previous_revision=None))]
+pub fn export_delta(py: Python, previous_path: &str, current_path: &str, output_path: &str, previous_revision: Option<&str>) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        export_delta_file(previous_path, current_path, output_path, previous_revision)
+            .map(|report| report.get_summary())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    })
+}
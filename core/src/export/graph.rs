@@ -0,0 +1,338 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::engine::access::ACCESS_TAGS_FIELD;
+use crate::export::registry::{Exporter, ExporterRegistry};
+use crate::schema::envelope::{output_watermark, payload_of};
+use crate::security::watermark::{WATERMARK_FILE, WATERMARK_MARKER};
+use crate::storage::atomic::write_atomic;
+
+// Node label per output collection; unknown collections get a label derived from their name
+const COLLECTION_LABELS: &[(&str, &str)] = &[
+    ("modules", "Module"),
+    ("steps", "Step"),
+    ("entities", "Entity"),
+    ("procedures", "Procedure"),
+    ("conditions", "Condition"),
+    ("parameters", "Parameter"),
+    ("tables", "Table"),
+    ("limits", "Limit"),
+    ("findings", "Finding"),
+    ("llm", "LlmResult"),
+    ("forms", "FormField"),
+];
+
+// Comma-separated record fields exported as string lists
+const LIST_FIELDS: &[&str] = &[ACCESS_TAGS_FIELD];
+
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    pub record_id: String,
+    pub label: String,
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GraphRelationship {
+    pub start: String,
+    pub end: String,
+    pub kind: String,
+}
+
+// Records as nodes, links between them as relationships
+#[derive(Debug, Default)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub relationships: Vec<GraphRelationship>,
+    // Watermark of the source output, repeated in every export format
+    pub watermark: Option<String>,
+}
+
+fn label_for(collection: &str) -> String {
+    if let Some((_, label)) = COLLECTION_LABELS.iter().find(|(name, _)| *name == collection) {
+        return label.to_string();
+    }
+    let singular = collection.strip_suffix('s').unwrap_or(collection);
+    singular.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+        })
+        .collect()
+}
+
+// Relationship type for a field that holds other record IDs
+fn relationship_for(field: &str) -> &'static str {
+    match field {
+        "step_ids" => "MENTIONED_IN",
+        "module_id" => "BELONGS_TO",
+        _ => "REFERENCES",
+    }
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn offsets(node: &GraphNode) -> Option<(usize, usize)> {
+    let start = node.properties.get("original_start")?.parse().ok()?;
+    let end = node.properties.get("original_end")?.parse().ok()?;
+    Some((start, end))
+}
+
+impl Graph {
+    // Every array of objects with a record_id in the payload is a node collection.
+    // Fields naming other records (module_id, step_ids, any *_id or *_ids) become relationships,
+    // and steps are PART_OF the module whose extent contains them
+    pub fn from_output(output: &Value) -> Self {
+        let mut graph = Graph { watermark: output_watermark(output), ..Graph::default() };
+        let payload = payload_of(output);
+        for (collection, value) in payload.as_object().into_iter().flatten() {
+            let label = label_for(collection);
+            for record in value.as_array().into_iter().flatten() {
+                let fields = match record.as_object() {
+                    Some(fields) => fields,
+                    None => continue,
+                };
+                let record_id = match fields.get("record_id").and_then(Value::as_str) {
+                    Some(record_id) => record_id.to_string(),
+                    None => continue,
+                };
+                let properties = fields.iter().map(|(key, value)| (key.clone(), as_text(value))).collect();
+                graph.nodes.push(GraphNode { record_id, label: label.clone(), properties });
+            }
+        }
+
+        let known: BTreeSet<&str> = graph.nodes.iter().map(|node| node.record_id.as_str()).collect();
+        let mut relationships = BTreeSet::new();
+        for node in &graph.nodes {
+            for (field, value) in &node.properties {
+                if field == "record_id" || !(field.ends_with("_id") || field.ends_with("_ids")) {
+                    continue;
+                }
+                for target in value.split(',').filter(|target| known.contains(target) && *target != node.record_id) {
+                    relationships.insert(GraphRelationship {
+                        start: node.record_id.clone(),
+                        end: target.to_string(),
+                        kind: relationship_for(field).to_string(),
+                    });
+                }
+            }
+        }
+
+        // Module extents run from one module match to the next
+        let mut modules: Vec<(usize, &str)> = graph.nodes.iter()
+            .filter(|node| node.label == "Module")
+            .filter_map(|node| offsets(node).map(|(start, _)| (start, node.record_id.as_str())))
+            .collect();
+        modules.sort();
+        for node in graph.nodes.iter().filter(|node| node.label == "Step") {
+            let start = match offsets(node) {
+                Some((start, _)) => start,
+                None => continue,
+            };
+            if let Some((_, module_id)) = modules.iter().rev().find(|(module_start, _)| *module_start <= start) {
+                relationships.insert(GraphRelationship {
+                    start: node.record_id.clone(),
+                    end: module_id.to_string(),
+                    kind: "PART_OF".to_string(),
+                });
+            }
+        }
+
+        graph.relationships = relationships.into_iter().collect();
+        graph
+    }
+
+    fn labels(&self) -> BTreeSet<&str> {
+        self.nodes.iter().map(|node| node.label.as_str()).collect()
+    }
+
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let mut summary = HashMap::new();
+        summary.insert("nodes".to_string(), self.nodes.len().to_string());
+        summary.insert("relationships".to_string(), self.relationships.len().to_string());
+        summary
+    }
+}
+
+fn cypher_string(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("'{}'", escaped)
+}
+
+fn cypher_value(key: &str, value: &str) -> String {
+    if LIST_FIELDS.contains(&key) {
+        let items: Vec<String> = value.split(',').filter(|item| !item.is_empty()).map(cypher_string).collect();
+        return format!("[{}]", items.join(", "));
+    }
+    cypher_string(value)
+}
+
+// Property keys are backquoted so any record field name is a valid identifier
+fn cypher_map(properties: &BTreeMap<String, String>) -> String {
+    let entries: Vec<String> = properties.iter()
+        .map(|(key, value)| format!("`{}`: {}", key.replace('`', "``"), cypher_value(key, value)))
+        .collect();
+    format!("{{{}}}", entries.join(", "))
+}
+
+// Constraints first so the relationship MATCHes can use the record_id index
+pub fn to_cypher(graph: &Graph) -> String {
+    let mut script = String::new();
+    if let Some(watermark) = &graph.watermark {
+        script.push_str(&format!("// {} {}\n", WATERMARK_MARKER, watermark));
+    }
+    for label in graph.labels() {
+        script.push_str(&format!(
+            "CREATE CONSTRAINT IF NOT EXISTS FOR (n:{}) REQUIRE n.record_id IS UNIQUE;\n",
+            label
+        ));
+    }
+    let labels: HashMap<&str, &str> = graph.nodes.iter()
+        .map(|node| (node.record_id.as_str(), node.label.as_str()))
+        .collect();
+    for node in &graph.nodes {
+        script.push_str(&format!("CREATE (:{} {});\n", node.label, cypher_map(&node.properties)));
+    }
+    for relationship in &graph.relationships {
+        let (start_label, end_label) = match (labels.get(relationship.start.as_str()), labels.get(relationship.end.as_str())) {
+            (Some(start), Some(end)) => (start, end),
+            _ => continue,
+        };
+        script.push_str(&format!(
+            "MATCH (a:{} {{record_id: {}}}), (b:{} {{record_id: {}}}) CREATE (a)-[:{}]->(b);\n",
+            start_label,
+            cypher_string(&relationship.start),
+            end_label,
+            cypher_string(&relationship.end),
+            relationship.kind
+        ));
+    }
+    script
+}
+
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+pub(crate) fn csv_row(fields: &[String]) -> String {
+    let quoted: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+    format!("{}\n", quoted.join(","))
+}
+
+// neo4j-admin bulk import layout: one nodes_<Label>.csv per label plus relationships.csv
+pub fn write_bulk_csv(graph: &Graph, output_dir: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(output_dir)?;
+    let mut written = Vec::new();
+
+    for label in graph.labels() {
+        let nodes: Vec<&GraphNode> = graph.nodes.iter().filter(|node| node.label == label).collect();
+        let columns: BTreeSet<&str> = nodes.iter()
+            .flat_map(|node| node.properties.keys().map(String::as_str))
+            .filter(|key| *key != "record_id")
+            .collect();
+
+        let mut header = vec!["record_id:ID".to_string()];
+        // List columns use the importer's default ';' array delimiter
+        header.extend(columns.iter().map(|column| match LIST_FIELDS.contains(column) {
+            true => format!("{}:string[]", column),
+            false => column.to_string(),
+        }));
+        header.push(":LABEL".to_string());
+        let mut content = csv_row(&header);
+        for node in nodes {
+            let mut row = vec![node.record_id.clone()];
+            row.extend(columns.iter().map(|column| {
+                let value = node.properties.get(*column).cloned().unwrap_or_default();
+                match LIST_FIELDS.contains(column) {
+                    true => value.replace(',', ";"),
+                    false => value,
+                }
+            }));
+            row.push(label.to_string());
+            content.push_str(&csv_row(&row));
+        }
+
+        let path = Path::new(output_dir).join(format!("nodes_{}.csv", label));
+        write_atomic(&path, content)?;
+        written.push(path.to_string_lossy().to_string());
+    }
+
+    let mut content = csv_row(&[":START_ID".to_string(), ":END_ID".to_string(), ":TYPE".to_string()]);
+    for relationship in &graph.relationships {
+        content.push_str(&csv_row(&[relationship.start.clone(), relationship.end.clone(), relationship.kind.clone()]));
+    }
+    let path = Path::new(output_dir).join("relationships.csv");
+    write_atomic(&path, content)?;
+    written.push(path.to_string_lossy().to_string());
+
+    // The importer ignores extra files, so the watermark travels beside the CSVs
+    if let Some(watermark) = &graph.watermark {
+        let path = Path::new(output_dir).join(WATERMARK_FILE);
+        write_atomic(&path, format!("{}\n", watermark))?;
+        written.push(path.to_string_lossy().to_string());
+    }
+    Ok(written)
+}
+
+// "cypher" writes one script to output_path; "csv" treats output_path as a directory
+pub fn export_graph_file(input_path: &str, output_path: &str, format: &str) -> Result<Graph, Box<dyn std::error::Error>> {
+    let output: Value = serde_json::from_str(&std::fs::read_to_string(input_path)?)?;
+    let graph = Graph::from_output(&output);
+    match format {
+        "cypher" => write_atomic(output_path, to_cypher(&graph))?,
+        "csv" => {
+            write_bulk_csv(&graph, output_path)?;
+        }
+        other => return Err(format!("Unknown graph format: {} (expected cypher or csv)", other).into()),
+    }
+    Ok(graph)
+}
+
+// Job exports: <stem>.cypher, or a <stem>_csv directory of bulk import files
+struct CypherExporter;
+
+impl Exporter for CypherExporter {
+    fn export(&self, output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        export_graph_file(&output_path.to_string_lossy(), &output_path.with_extension("cypher").to_string_lossy(), "cypher")?;
+        Ok(())
+    }
+}
+
+struct CsvExporter;
+
+impl Exporter for CsvExporter {
+    fn export(&self, output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let stem = output_path.file_stem().and_then(|s| s.to_str()).unwrap_or("document");
+        export_graph_file(&output_path.to_string_lossy(), &output_path.with_file_name(format!("{}_csv", stem)).to_string_lossy(), "csv")?;
+        Ok(())
+    }
+}
+
+pub(crate) fn register_exporters(registry: &mut ExporterRegistry) {
+    registry.add("cypher", Arc::new(|| Box::new(CypherExporter)));
+    registry.add("csv", Arc::new(|| Box::new(CsvExporter)));
+}
+
+// Python binding - returns node and relationship counts
+#[pyfunction]
+#[pyo3(signature = (input_path, output_path, format="cypher"))]
+pub fn export_graph(py: Python, input_path: &str, output_path: &str, format: &str) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let graph = export_graph_file(input_path, output_path, format)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let mut summary = graph.get_summary();
+        summary.insert("output_path".to_string(), output_path.to_string());
+        Ok(summary)
+    })
+}
@@ -0,0 +1,396 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::splitter::split_pages;
+use crate::pdf::document::{PdfDocument, PdfObject, PdfPage, PdfUpdate};
+use crate::schema::envelope::{output_watermark, payload_of};
+use crate::security::watermark::WATERMARK_MARKER;
+
+// Highlight color per collection, as PDF RGB. Procedures span whole modules and are left out
+const COLLECTION_COLORS: &[(&str, [f64; 3])] = &[
+    ("modules", [1.0, 0.85, 0.0]),
+    ("steps", [0.45, 0.9, 0.35]),
+    ("entities", [0.4, 0.7, 1.0]),
+    ("conditions", [1.0, 0.6, 0.2]),
+    ("parameters", [0.75, 0.5, 1.0]),
+    ("limits", [0.3, 0.85, 0.8]),
+    ("pii", [1.0, 0.4, 0.5]),
+];
+
+// Popup notes open beside the highlight, this wide and tall in points
+const POPUP_SIZE: (f64, f64) = (220.0, 90.0);
+
+// Words whose vertical centres are this share of a line height apart sit on different lines
+const LINE_TOLERANCE: f64 = 0.5;
+
+#[derive(Debug, Clone)]
+pub struct LayoutWord {
+    pub text: String,
+    // Points from the top-left corner of the page, as pdftotext reports them
+    pub x_min: f64,
+    pub y_min: f64,
+    pub x_max: f64,
+    pub y_max: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LayoutPage {
+    pub width: f64,
+    pub height: f64,
+    pub words: Vec<LayoutWord>,
+}
+
+fn attribute(tag: &str, name: &str) -> Option<f64> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let end = tag[start..].find('"')? + start;
+    tag[start..end].parse().ok()
+}
+
+pub(crate) fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find('&') {
+        output.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let Some(end) = rest.find(';') else { break };
+        let decoded = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => entity.strip_prefix("#x").map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                output.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+// `pdftotext -bbox` XHTML: a <page width height> element per page holding <word> elements
+pub fn parse_layout(xhtml: &str) -> Vec<LayoutPage> {
+    let mut pages: Vec<LayoutPage> = Vec::new();
+    let mut rest = xhtml;
+    while let Some(index) = rest.find('<') {
+        rest = &rest[index..];
+        let Some(tag_end) = rest.find('>') else { break };
+        let tag = &rest[..tag_end];
+        if tag.starts_with("<page ") {
+            pages.push(LayoutPage {
+                width: attribute(tag, "width").unwrap_or(0.0),
+                height: attribute(tag, "height").unwrap_or(0.0),
+                words: Vec::new(),
+            });
+        } else if tag.starts_with("<word ") {
+            let text_end = rest[tag_end..].find("</word>").map(|end| tag_end + end).unwrap_or(tag_end + 1);
+            let word = LayoutWord {
+                text: unescape(&rest[tag_end + 1..text_end]),
+                x_min: attribute(tag, "xMin").unwrap_or(0.0),
+                y_min: attribute(tag, "yMin").unwrap_or(0.0),
+                x_max: attribute(tag, "xMax").unwrap_or(0.0),
+                y_max: attribute(tag, "yMax").unwrap_or(0.0),
+            };
+            if let Some(page) = pages.last_mut() {
+                page.words.push(word);
+            }
+        }
+        rest = &rest[tag_end..];
+    }
+    pages
+}
+
+// Words compare on their letters and digits only, so punctuation pdftotext splits or glues
+// differently in its two modes does not matter
+fn word_key(word: &str) -> String {
+    word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+struct TextPage {
+    offset: usize,
+    // (start, end) of each word within the page, with its key
+    tokens: Vec<(usize, usize, String)>,
+}
+
+fn text_pages(text: &str) -> Vec<TextPage> {
+    split_pages(text).into_iter()
+        .map(|(offset, page)| {
+            let mut tokens = Vec::new();
+            let mut start = None;
+            for (index, c) in page.char_indices().chain(std::iter::once((page.len(), ' '))) {
+                match (c.is_whitespace(), start) {
+                    (false, None) => start = Some(index),
+                    (true, Some(word_start)) => {
+                        tokens.push((word_start, index, word_key(&page[word_start..index])));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            TextPage { offset, tokens }
+        })
+        .collect()
+}
+
+// The layout words of `start..end` (page-relative): where the words of the span appear in the
+// same order, nearest to where the span sits in the text layer
+fn locate<'a>(page: &TextPage, layout: &'a LayoutPage, start: usize, end: usize) -> Option<Vec<&'a LayoutWord>> {
+    let keyed_tokens: Vec<&(usize, usize, String)> = page.tokens.iter().filter(|(_, _, key)| !key.is_empty()).collect();
+    let first = keyed_tokens.iter().position(|(token_start, token_end, _)| *token_end > start && *token_start < end)?;
+    let needles: Vec<&str> = keyed_tokens[first..].iter()
+        .take_while(|(token_start, _, _)| *token_start < end)
+        .map(|(_, _, key)| key.as_str())
+        .collect();
+
+    let keyed_words: Vec<(usize, String)> = layout.words.iter()
+        .enumerate()
+        .map(|(index, word)| (index, word_key(&word.text)))
+        .filter(|(_, key)| !key.is_empty())
+        .collect();
+    if keyed_words.len() < needles.len() {
+        return None;
+    }
+    let expected = first as f64 * keyed_words.len() as f64 / keyed_tokens.len() as f64;
+    let position = (0..=keyed_words.len() - needles.len())
+        .filter(|position| needles.iter().enumerate().all(|(offset, needle)| keyed_words[position + offset].1 == *needle))
+        .min_by(|a, b| (*a as f64 - expected).abs().total_cmp(&(*b as f64 - expected).abs()))?;
+    Some(keyed_words[position..position + needles.len()].iter().map(|(index, _)| &layout.words[*index]).collect())
+}
+
+// One box per line the words run over, in layout coordinates
+fn line_boxes(words: &[&LayoutWord]) -> Vec<[f64; 4]> {
+    let mut boxes: Vec<[f64; 4]> = Vec::new();
+    for word in words {
+        let centre = (word.y_min + word.y_max) / 2.0;
+        match boxes.last_mut() {
+            Some(line) if ((line[1] + line[3]) / 2.0 - centre).abs() <= (line[3] - line[1]) * LINE_TOLERANCE && word.x_min >= line[0] => {
+                line[0] = line[0].min(word.x_min);
+                line[1] = line[1].min(word.y_min);
+                line[2] = line[2].max(word.x_max);
+                line[3] = line[3].max(word.y_max);
+            }
+            _ => boxes.push([word.x_min, word.y_min, word.x_max, word.y_max]),
+        }
+    }
+    boxes
+}
+
+fn numbers(values: &[f64]) -> PdfObject {
+    PdfObject::Array(values.iter().map(|value| PdfObject::number(*value)).collect())
+}
+
+// A highlight and its popup note. Layout boxes are flipped into the page's bottom-up space
+fn annotations(update: &mut PdfUpdate, page: &PdfPage, layout: &LayoutPage, boxes: &[[f64; 4]], color: [f64; 3], note: &str, record_id: &str) -> PdfObject {
+    let [x0, _, x1, y1] = page.crop_box;
+    let scale_x = if layout.width > 0.0 { (x1 - x0) / layout.width } else { 1.0 };
+    let scale_y = if layout.height > 0.0 { (y1 - page.crop_box[1]) / layout.height } else { 1.0 };
+
+    let mut quads = Vec::new();
+    let mut rect = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+    for [left, top, right, bottom] in boxes {
+        let (left, right) = (x0 + left * scale_x, x0 + right * scale_x);
+        let (top, bottom) = (y1 - top * scale_y, y1 - bottom * scale_y);
+        // Upper left, upper right, lower left, lower right - the order viewers expect
+        quads.extend_from_slice(&[left, top, right, top, left, bottom, right, bottom]);
+        rect = [rect[0].min(left), rect[1].min(bottom), rect[2].max(right), rect[3].max(top)];
+    }
+
+    let highlight_number = update.reserve();
+    let popup_number = update.reserve();
+    let highlight_ref = PdfObject::Reference(highlight_number, 0);
+    let page_ref = PdfObject::Reference(page.number, page.generation);
+    update.put(highlight_number, 0, PdfObject::Dictionary(vec![
+        ("Type".to_string(), PdfObject::name("Annot")),
+        ("Subtype".to_string(), PdfObject::name("Highlight")),
+        ("Rect".to_string(), numbers(&rect)),
+        ("QuadPoints".to_string(), numbers(&quads)),
+        ("C".to_string(), numbers(&color)),
+        ("T".to_string(), PdfObject::text("spp")),
+        ("Contents".to_string(), PdfObject::text(note)),
+        ("NM".to_string(), PdfObject::text(record_id)),
+        ("P".to_string(), page_ref.clone()),
+        ("F".to_string(), PdfObject::Number("4".to_string())),
+        ("Popup".to_string(), PdfObject::Reference(popup_number, 0)),
+    ]));
+    let popup_rect = [rect[2], rect[3] - POPUP_SIZE.1, rect[2] + POPUP_SIZE.0, rect[3]];
+    update.put(popup_number, 0, PdfObject::Dictionary(vec![
+        ("Type".to_string(), PdfObject::name("Annot")),
+        ("Subtype".to_string(), PdfObject::name("Popup")),
+        ("Rect".to_string(), numbers(&popup_rect)),
+        ("Parent".to_string(), highlight_ref.clone()),
+        ("P".to_string(), page_ref),
+        ("Open".to_string(), PdfObject::Bool(false)),
+    ]));
+    highlight_ref
+}
+
+#[derive(Debug, Default)]
+pub struct HighlightReport {
+    // Highlights per collection
+    pub highlighted: BTreeMap<String, usize>,
+    // Records with no place on the page - a rotated page, or words pdftotext laid out differently
+    pub unplaced: Vec<String>,
+    pub pages: BTreeSet<usize>,
+}
+
+impl HighlightReport {
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let mut summary = HashMap::new();
+        summary.insert("highlighted".to_string(), self.highlighted.values().sum::<usize>().to_string());
+        for (collection, count) in &self.highlighted {
+            summary.insert(collection.clone(), count.to_string());
+        }
+        summary.insert("unplaced".to_string(), self.unplaced.len().to_string());
+        summary.insert("pages".to_string(), self.pages.len().to_string());
+        summary
+    }
+}
+
+fn note(collection: &str, record: &Value, text: &str) -> String {
+    let field = |name: &str| record.get(name).and_then(Value::as_str);
+    let kind = collection.strip_suffix('s').unwrap_or(collection);
+    let mut note = format!("{} {}", kind, field("record_id").unwrap_or_default());
+    if let Some(confidence) = field("confidence") {
+        note.push_str(&format!("\nconfidence {}", confidence));
+    }
+    if let Some(detector) = field("detector") {
+        note.push_str(&format!("\ndetector {}", detector));
+    }
+    // PII notes show the masked value, never the finding itself
+    let shown = field("masked").map(str::to_string).unwrap_or_else(|| text.trim().to_string());
+    if !shown.is_empty() {
+        note.push_str(&format!("\n{}", shown));
+    }
+    note
+}
+
+pub fn compute_highlights(output: &Value, text: &str, layout: &[LayoutPage], document: &PdfDocument, highlighted_path: &str) -> Result<HighlightReport, Box<dyn std::error::Error>> {
+    let payload = payload_of(output);
+    // Split parts' offsets count from the start of their part
+    let base = payload.get("part")
+        .and_then(|part| part.get("start_offset"))
+        .and_then(Value::as_str)
+        .and_then(|offset| offset.parse::<usize>().ok())
+        .unwrap_or(0);
+    let pages = document.pages()?;
+    let text_pages = text_pages(text);
+
+    let mut report = HighlightReport::default();
+    let mut update = PdfUpdate::new(document);
+    let mut added: BTreeMap<usize, Vec<PdfObject>> = BTreeMap::new();
+    for (collection, color) in COLLECTION_COLORS {
+        let records = payload.get(*collection).and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+        for record in records {
+            let offset = |name: &str| record.get(name).and_then(Value::as_str).and_then(|value| value.parse::<usize>().ok());
+            let record_id = record.get("record_id").and_then(Value::as_str).unwrap_or_default().to_string();
+            let (Some(start), Some(end)) = (offset("original_start"), offset("original_end")) else { continue };
+            let (start, end) = (base + start, base + end);
+            let page_index = text_pages.iter().rposition(|page| page.offset <= start);
+            let placed = page_index.and_then(|index| {
+                let (pdf_page, layout_page) = (pages.get(index)?, layout.get(index)?);
+                if pdf_page.rotate != 0 {
+                    return None;
+                }
+                let text_page = &text_pages[index];
+                let words = locate(text_page, layout_page, start - text_page.offset, end.saturating_sub(text_page.offset))?;
+                let span = text.get(start..end.min(text.len())).unwrap_or_default();
+                let annotation = annotations(&mut update, pdf_page, layout_page, &line_boxes(&words), *color, &note(collection, record, span), &record_id);
+                Some((index, annotation))
+            });
+            match placed {
+                Some((index, annotation)) => {
+                    added.entry(index).or_default().push(annotation);
+                    *report.highlighted.entry(collection.to_string()).or_default() += 1;
+                    report.pages.insert(index + 1);
+                }
+                None => report.unplaced.push(record_id),
+            }
+        }
+    }
+
+    // Each annotated page is rewritten with its existing annotations followed by the new ones
+    for (index, annotations) in added {
+        let page = &pages[index];
+        let mut dictionary = page.dictionary.clone();
+        let mut annots = match page.dictionary.get("Annots") {
+            Some(existing) => document.resolve(existing)?.as_array().map(<[PdfObject]>::to_vec).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        annots.extend(annotations);
+        dictionary.set("Annots", PdfObject::Array(annots));
+        update.put(page.number, page.generation, dictionary);
+    }
+    if let Some(watermark) = output_watermark(output) {
+        update.comment(&format!("{} {}", WATERMARK_MARKER, watermark));
+    }
+    update.write(highlighted_path)?;
+    Ok(report)
+}
+
+pub fn export_highlights_file(
+    output_path: &str,
+    pdf_path: &str,
+    highlighted_path: &str,
+    text_path: Option<&str>,
+    layout_path: Option<&str>,
+    rules_path: Option<&str>,
+) -> Result<HighlightReport, Box<dyn std::error::Error>> {
+    let output: Value = serde_json::from_str(&std::fs::read_to_string(output_path)?)?;
+    let engine = match rules_path {
+        Some(rules_path) => ExtractionEngine::from_rules_file(rules_path)?,
+        None => ExtractionEngine::new(),
+    };
+
+    // The text layer the offsets point into, read the way the job read it
+    let text = match text_path {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?,
+        None => {
+            let source = payload_of(&output).get("source").and_then(Value::as_str)
+                .ok_or_else(|| format!("{} names no source; pass text_path", output_path))?;
+            engine.read_input(source)?.text
+        }
+    };
+    let layout = match layout_path {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?,
+        None => String::from_utf8_lossy(&engine.get_converters().layout(pdf_path)?.bytes).to_string(),
+    };
+
+    let document = PdfDocument::read(pdf_path)?;
+    compute_highlights(&output, &text, &parse_layout(&layout), &document, highlighted_path)
+}
+
+// Python binding - writes a copy of the PDF with a highlight per record and returns the counts.
+// The text layer defaults to the output's `source`, read through the rules' converters; word
+// boxes come from `layout_path` or the rules' layout tool (pdftotext -bbox)
+#[pyfunction]
+#[pyo3(signature = (output_path, pdf_path, highlighted_path, text_path=None, layout_path=None, rules_path=None))]
+pub fn export_highlights(
+    py: Python,
+    output_path: &str,
+    pdf_path: &str,
+    highlighted_path: &str,
+    text_path: Option<&str>,
+    layout_path: Option<&str>,
+    rules_path: Option<&str>,
+) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let report = export_highlights_file(output_path, pdf_path, highlighted_path, text_path, layout_path, rules_path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let mut summary = report.get_summary();
+        summary.insert("output_path".to_string(), highlighted_path.to_string());
+        Ok(summary)
+    })
+}
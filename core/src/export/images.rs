@@ -0,0 +1,377 @@
+// Figure images out of a PDF: the image XObjects drawn on the page of each figure caption,
+// written as files. JPEG and JPEG 2000 images are copied as they are stored; other images are
+// decoded and written as uncompressed PNG, which every viewer accepts
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::engine::entities::record_extents;
+use crate::engine::figures::figure_mentions;
+use crate::engine::splitter::PAGE_BREAK;
+use crate::pdf::document::{parse_content, PdfDocument, PdfObject};
+use crate::pdf::text::MAX_FORM_DEPTH;
+use crate::storage::atomic::write_atomic;
+
+// Icons, bullets and rules drawn as images are smaller than this on either side
+const MIN_IMAGE_SIDE: usize = 16;
+
+// An image drawn on more pages than this is a logo or a page border, not a figure
+const MAX_IMAGE_PAGES: usize = 2;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// Largest block of a stored deflate stream
+const MAX_STORED_BLOCK: usize = 0xffff;
+
+// A figure the steps refer to: its caption, the page the caption is on, and the files its
+// images were written to
+#[derive(Debug, Clone)]
+pub struct ExportedFigure {
+    pub figure: String,
+    pub caption: String,
+    pub page: usize,
+    pub paths: Vec<String>,
+    // Images on the page that could not be written, e.g. for a filter this reader lacks
+    pub problems: Vec<String>,
+}
+
+impl ExportedFigure {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "figure": self.figure,
+            "caption": self.caption,
+            "page": self.page,
+            "paths": self.paths,
+            "problems": self.problems,
+        })
+    }
+}
+
+// Samples of a decoded image, by color space
+enum Colors {
+    Gray,
+    Rgb,
+    Cmyk,
+    // One index per pixel into a palette of the base space's components
+    Indexed(Box<Colors>, Vec<u8>),
+}
+
+impl Colors {
+    fn components(&self) -> usize {
+        match self {
+            Colors::Gray | Colors::Indexed(..) => 1,
+            Colors::Rgb => 3,
+            Colors::Cmyk => 4,
+        }
+    }
+}
+
+fn color_space(document: &PdfDocument, space: &PdfObject) -> Result<Colors, String> {
+    let space = document.resolve(space)?;
+    let (family, parameters) = match &space {
+        PdfObject::Array(parts) => (parts.first().and_then(PdfObject::as_name).unwrap_or_default(), &parts[1.min(parts.len())..]),
+        other => (other.as_name().unwrap_or_default(), &[][..]),
+    };
+    match family {
+        "DeviceGray" | "CalGray" | "G" => Ok(Colors::Gray),
+        "DeviceRGB" | "CalRGB" | "RGB" => Ok(Colors::Rgb),
+        "DeviceCMYK" | "CMYK" => Ok(Colors::Cmyk),
+        // The profile's component count is all a plain rendering needs
+        "ICCBased" => {
+            let profile = document.resolve(parameters.first().ok_or("ICCBased color space without a profile")?)?;
+            match profile.get("N").and_then(PdfObject::as_number).map(|n| n as usize) {
+                Some(1) => Ok(Colors::Gray),
+                Some(3) => Ok(Colors::Rgb),
+                Some(4) => Ok(Colors::Cmyk),
+                other => Err(format!("ICCBased color space with {:?} components is not exported", other)),
+            }
+        }
+        "Indexed" | "I" if parameters.len() == 3 => {
+            let base = color_space(document, &parameters[0])?;
+            if matches!(base, Colors::Indexed(..)) {
+                return Err("Indexed color space over an indexed base".to_string());
+            }
+            let palette = match &parameters[2] {
+                reference @ PdfObject::Reference(..) => match document.resolve(reference)? {
+                    PdfObject::Dictionary(_) => document.stream(reference)?.1,
+                    lookup => lookup.as_bytes().ok_or("Indexed color space without a lookup table")?,
+                },
+                lookup => lookup.as_bytes().ok_or("Indexed color space without a lookup table")?,
+            };
+            Ok(Colors::Indexed(Box::new(base), palette))
+        }
+        _ => Err(format!("Color space {} is not exported", if family.is_empty() { "?" } else { family })),
+    }
+}
+
+// One 8-bit gray or RGB pixel from a sample's components
+fn to_pixel(colors: &Colors, components: &[u8], pixels: &mut Vec<u8>) {
+    match colors {
+        Colors::Gray | Colors::Rgb => pixels.extend_from_slice(components),
+        Colors::Cmyk => {
+            let black = 255 - components[3] as u16;
+            pixels.extend(components[..3].iter().map(|&ink| ((255 - ink as u16) * black / 255) as u8));
+        }
+        Colors::Indexed(base, palette) => {
+            let width = base.components();
+            let offset = components[0] as usize * width;
+            match palette.get(offset..offset + width) {
+                Some(entry) => to_pixel(base, entry, pixels),
+                // Out of range indices show as black
+                None => pixels.extend(std::iter::repeat_n(0, if matches!(**base, Colors::Gray) { 1 } else { 3 })),
+            }
+        }
+    }
+}
+
+// A zlib stream of stored deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        output.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        output.push(blocks.peek().is_none() as u8);
+        output.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        output.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    output
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32fast::hash(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// An 8-bit gray (1 channel) or RGB (3 channels) PNG
+fn write_png(width: usize, height: usize, channels: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut rows = Vec::with_capacity(pixels.len() + height);
+    for row in pixels.chunks(width * channels) {
+        rows.push(0);
+        rows.extend_from_slice(row);
+    }
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, if channels == 1 { 0 } else { 2 }, 0, 0, 0]);
+    let mut png = PNG_SIGNATURE.to_vec();
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib_stored(&rows));
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+// An image XObject as a file: its extension and bytes. None for images that are not pictures:
+// stencil masks and images too small to be figures
+fn encode_image(document: &PdfDocument, reference: &PdfObject, dictionary: &PdfObject) -> Result<Option<(&'static str, Vec<u8>)>, String> {
+    let dimension = |key: &str| dictionary.get(key).and_then(PdfObject::as_number).unwrap_or(0.0) as usize;
+    let (width, height) = (dimension("Width"), dimension("Height"));
+    if dictionary.get("ImageMask").is_some_and(|mask| matches!(mask, PdfObject::Bool(true))) || width.min(height) < MIN_IMAGE_SIDE {
+        return Ok(None);
+    }
+    let filters: Vec<&str> = match dictionary.get("Filter") {
+        Some(PdfObject::Array(filters)) => filters.iter().filter_map(PdfObject::as_name).collect(),
+        Some(filter) => filter.as_name().into_iter().collect(),
+        None => Vec::new(),
+    };
+    match filters.as_slice() {
+        ["DCTDecode"] | ["DCT"] => return Ok(Some(("jpg", document.raw_stream(reference)?.1))),
+        ["JPXDecode"] => return Ok(Some(("jp2", document.raw_stream(reference)?.1))),
+        _ => {}
+    }
+    let colors = color_space(document, dictionary.get("ColorSpace").ok_or("Image without a color space")?)?;
+    let bits = dictionary.get("BitsPerComponent").and_then(PdfObject::as_number).unwrap_or(8.0) as usize;
+    if !matches!(bits, 1 | 2 | 4 | 8) {
+        return Err(format!("Images of {} bits per component are not exported", bits));
+    }
+    let data = document.stream(reference)?.1;
+    let components = colors.components();
+    let row_length = (width * components * bits).div_ceil(8);
+    if data.len() < row_length * height {
+        return Err(format!("Image data is {} bytes, too short for {}x{} pixels", data.len(), width, height));
+    }
+
+    // Samples are scaled to 8 bits, except palette indices
+    let maximum = (1u16 << bits) - 1;
+    let scale = |sample: u16| if matches!(colors, Colors::Indexed(..)) { sample as u8 } else { (sample * 255 / maximum) as u8 };
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    let mut sample = vec![0u8; components];
+    for row in data.chunks(row_length).take(height) {
+        for pixel in 0..width {
+            for (component, value) in sample.iter_mut().enumerate() {
+                let bit = (pixel * components + component) * bits;
+                let byte = row[bit / 8] as u16;
+                *value = scale((byte >> (8 - bits - bit % 8)) & maximum);
+            }
+            to_pixel(&colors, &sample, &mut pixels);
+        }
+    }
+    let channels = pixels.len() / (width * height).max(1);
+    Ok(Some(("png", write_png(width, height, channels, &pixels))))
+}
+
+// Image XObjects are streams, so always indirect
+fn object_number(reference: &PdfObject) -> u32 {
+    match reference {
+        PdfObject::Reference(number, _) => *number,
+        _ => 0,
+    }
+}
+
+// Image XObjects a content stream draws, in drawing order, including those inside forms
+fn drawn_images(document: &PdfDocument, data: &[u8], resources: &PdfObject, depth: usize, images: &mut Vec<(String, PdfObject)>) -> Result<(), String> {
+    for operation in parse_content(data)? {
+        if operation.operator != "Do" {
+            continue;
+        }
+        let name = operation.operands.first().and_then(PdfObject::as_name).unwrap_or_default();
+        let Some(reference) = resources.get("XObject").map(|x| document.resolve(x)).transpose()?.and_then(|x| x.get(name).cloned()) else { continue };
+        let dictionary = document.resolve(&reference)?;
+        match dictionary.get("Subtype").and_then(PdfObject::as_name) {
+            Some("Image") if !images.iter().any(|(_, drawn)| *drawn == reference) => images.push((name.to_string(), reference)),
+            Some("Form") if depth < MAX_FORM_DEPTH => {
+                let form_resources = match dictionary.get("Resources") {
+                    Some(form_resources) => document.resolve(form_resources)?,
+                    None => resources.clone(),
+                };
+                drawn_images(document, &document.stream(&reference)?.1, &form_resources, depth + 1, images)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// The line a caption starts, "Figure 5-2 Main Gear Actuator"
+fn caption_line(text: &str, start: usize) -> String {
+    let end = text[start..].find(['\n', PAGE_BREAK]).map(|end| start + end).unwrap_or(text.len());
+    text[start..end].split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Exports the figures each module's steps refer to ("see Figure 5-2") into `dir`, and adds
+// them to the module records as `figures`, a JSON list. A figure's images are those drawn on
+// the page of its caption: paired with the captions in order when the page has as many of
+// each, all given to a page's only caption, and otherwise shared by every caption on the page.
+// `text` is the PDF's text layer, or the part of it being extracted
+pub fn export_figures(
+    pdf_path: &str,
+    text: &str,
+    modules: &mut [HashMap<String, String>],
+    steps: &[HashMap<String, String>],
+    dir: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mentions = figure_mentions(text);
+    let page_starts: Vec<usize> = text.match_indices(PAGE_BREAK).map(|(offset, _)| offset + 1).collect();
+    let page_of = |offset: usize| page_starts.partition_point(|&start| start <= offset) + 1;
+    // The first caption of each figure, and the figures captioned on each page in text order
+    let mut captions: HashMap<&str, (String, usize)> = HashMap::new();
+    let mut page_captions: HashMap<usize, Vec<&str>> = HashMap::new();
+    for (start, _, number, _) in mentions.iter().filter(|(_, _, _, caption)| *caption) {
+        if !captions.contains_key(number.as_str()) {
+            captions.insert(number, (caption_line(text, *start), page_of(*start)));
+            page_captions.entry(page_of(*start)).or_default().push(number);
+        }
+    }
+
+    // Figures referred to in each module's steps, in the order the steps mention them
+    let module_extents = record_extents(modules, text.len());
+    let step_extents = record_extents(steps, text.len());
+    let mut referenced: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (start, _, number, _) in mentions.iter().filter(|(_, _, _, caption)| !caption) {
+        let in_step = step_extents.iter().any(|(step_start, step_end, _)| (*step_start..*step_end).contains(start));
+        let module = module_extents.iter().find(|(module_start, module_end, _)| (*module_start..*module_end).contains(start));
+        if let (true, Some((_, _, module_id)), true) = (in_step, module, captions.contains_key(number.as_str())) {
+            let figures = referenced.entry(module_id.as_str()).or_default();
+            if !figures.contains(&number.as_str()) {
+                figures.push(number);
+            }
+        }
+    }
+
+    let needed_pages: HashSet<usize> = referenced.values().flatten().map(|number| captions[number].1).collect();
+    let mut exported: HashMap<&str, ExportedFigure> = HashMap::new();
+    if !needed_pages.is_empty() {
+        let document = PdfDocument::read(pdf_path)?;
+        let pages = document.pages().map_err(|e| format!("{}: {}", pdf_path, e))?;
+        // Every page is walked to tell running images from figures
+        let mut drawn: Vec<Vec<(String, PdfObject)>> = Vec::new();
+        let mut page_problems: HashMap<usize, Vec<String>> = HashMap::new();
+        for (index, page) in pages.iter().enumerate() {
+            let mut problems = Vec::new();
+            let data = document.page_content(page, &mut problems);
+            let mut images = Vec::new();
+            if let Err(e) = drawn_images(&document, &data, &page.resources, 0, &mut images) {
+                problems.push(e);
+            }
+            drawn.push(images);
+            page_problems.insert(index + 1, problems);
+        }
+        let mut pages_drawn: HashMap<u32, usize> = HashMap::new();
+        for images in &drawn {
+            for (_, reference) in images {
+                *pages_drawn.entry(object_number(reference)).or_default() += 1;
+            }
+        }
+
+        std::fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir, e))?;
+        for &page in &needed_pages {
+            let mut problems = page_problems.remove(&page).unwrap_or_default();
+            let mut files = Vec::new();
+            for (name, reference) in drawn.get(page - 1).into_iter().flatten().filter(|(_, reference)| pages_drawn[&object_number(reference)] <= MAX_IMAGE_PAGES) {
+                match document.resolve(reference).and_then(|dictionary| encode_image(&document, reference, &dictionary)) {
+                    Ok(Some(file)) => files.push(file),
+                    Ok(None) => {}
+                    Err(e) => problems.push(format!("Image {}: {}", name, e)),
+                }
+            }
+            let figures = &page_captions[&page];
+            let write = |stem: String, (extension, data): &(&str, Vec<u8>)| -> Result<String, String> {
+                let path = Path::new(dir).join(format!("{}.{}", stem, extension));
+                write_atomic(&path, data).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+                Ok(path.to_string_lossy().to_string())
+            };
+            let mut paths: Vec<Vec<String>> = vec![Vec::new(); figures.len()];
+            if files.len() == figures.len() {
+                for (index, file) in files.iter().enumerate() {
+                    paths[index].push(write(format!("figure_{}", figures[index]), file)?);
+                }
+            } else if figures.len() == 1 {
+                for (index, file) in files.iter().enumerate() {
+                    paths[0].push(write(format!("figure_{}_{}", figures[0], index + 1), file)?);
+                }
+            } else {
+                let shared = files.iter().enumerate()
+                    .map(|(index, file)| write(format!("page_{}_{}", page, index + 1), file))
+                    .collect::<Result<Vec<_>, _>>()?;
+                paths.iter_mut().for_each(|figure_paths| figure_paths.clone_from(&shared));
+            }
+            for (figure, paths) in figures.iter().zip(paths) {
+                let caption = captions[figure].0.clone();
+                exported.insert(figure, ExportedFigure { figure: figure.to_string(), caption, page, paths, problems: problems.clone() });
+            }
+        }
+    }
+
+    for module in modules.iter_mut() {
+        let figures: Vec<serde_json::Value> = module.get("record_id")
+            .and_then(|module_id| referenced.get(module_id.as_str()))
+            .into_iter()
+            .flatten()
+            .filter_map(|figure| exported.get(figure).map(ExportedFigure::to_json))
+            .collect();
+        module.insert("figures".to_string(), serde_json::Value::Array(figures).to_string());
+    }
+    Ok(())
+}
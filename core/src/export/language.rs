@@ -0,0 +1,108 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::engine::language::{LANGUAGE_FIELD, UNDETERMINED};
+use crate::storage::atomic::write_atomic;
+
+// Records whose language could not be told - bare headings, part number lists - and records
+// from before tagging belong to no one language, so every filtered output keeps them
+fn language_of(record: &Value) -> Option<&str> {
+    record.get(LANGUAGE_FIELD).and_then(Value::as_str).filter(|language| *language != UNDETERMINED)
+}
+
+fn payload_mut(output: &mut Value) -> &mut Value {
+    match output.get("payload").is_some() {
+        true => &mut output["payload"],
+        false => output,
+    }
+}
+
+// Record counts per language across the output's collections
+pub fn output_languages(output: &Value) -> BTreeMap<String, usize> {
+    let payload = output.get("payload").unwrap_or(output);
+    let mut counts = BTreeMap::new();
+    for records in payload.as_object().into_iter().flat_map(|collections| collections.values()) {
+        for record in records.as_array().into_iter().flatten().filter(|record| record.is_object()) {
+            *counts.entry(language_of(record).unwrap_or(UNDETERMINED).to_string()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+// The output with only the records in `languages`, plus those of no one language. Provenance
+// and everything that is not a record collection are kept as they are
+pub fn filter_languages(output: &Value, languages: &[String]) -> Value {
+    let mut filtered = output.clone();
+    if let Some(collections) = payload_mut(&mut filtered).as_object_mut() {
+        for records in collections.values_mut() {
+            if let Some(records) = records.as_array_mut() {
+                records.retain(|record| !record.is_object() || language_of(record).is_none_or(|language| languages.iter().any(|kept| kept == language)));
+            }
+        }
+    }
+    filtered
+}
+
+// One output per language found; an output with no language told apart is its own, under "und"
+pub fn split_languages(output: &Value) -> BTreeMap<String, Value> {
+    let mut languages: Vec<String> = output_languages(output).into_keys().filter(|language| language != UNDETERMINED).collect();
+    if languages.is_empty() {
+        languages.push(UNDETERMINED.to_string());
+    }
+    languages.into_iter()
+        .map(|language| {
+            let filtered = filter_languages(output, std::slice::from_ref(&language));
+            (language, filtered)
+        })
+        .collect()
+}
+
+pub fn filter_output_file(input_path: &str, output_path: &str, languages: &[String]) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let output: Value = serde_json::from_str(&std::fs::read_to_string(input_path)?)?;
+    let filtered = filter_languages(&output, languages);
+    write_atomic(output_path, serde_json::to_string_pretty(&filtered)?)?;
+    let total: usize = output_languages(&output).values().sum();
+    let kept: usize = output_languages(&filtered).values().sum();
+    let mut summary = HashMap::new();
+    summary.insert("records".to_string(), kept.to_string());
+    summary.insert("dropped".to_string(), (total - kept).to_string());
+    summary.insert("output_path".to_string(), output_path.to_string());
+    Ok(summary)
+}
+
+// Writes <stem>.<language>.json files to output_dir; returns their paths by language
+pub fn split_output_file(input_path: &str, output_dir: &str) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let output: Value = serde_json::from_str(&std::fs::read_to_string(input_path)?)?;
+    let stem = Path::new(input_path).file_stem().and_then(|s| s.to_str()).unwrap_or("document");
+    std::fs::create_dir_all(output_dir)?;
+    let mut written = HashMap::new();
+    for (language, filtered) in split_languages(&output) {
+        let path = Path::new(output_dir).join(format!("{}.{}.json", stem, language));
+        write_atomic(&path, serde_json::to_string_pretty(&filtered)?)?;
+        written.insert(language, path.to_string_lossy().to_string());
+    }
+    Ok(written)
+}
+
+// Python bindings - a language-filtered copy of an output, or one copy per language, for any
+// exporter to read instead of the output itself
+#[pyfunction]
+pub fn filter_output_by_language(py: Python, input_path: &str, output_path: &str, languages: Vec<String>) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        if languages.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("languages must name at least one language"));
+        }
+        filter_output_file(input_path, output_path, &languages)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    })
+}
+
+#[pyfunction]
+pub fn split_output_by_language(py: Python, input_path: &str, output_dir: &str) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        split_output_file(input_path, output_dir)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    })
+}
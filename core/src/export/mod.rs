@@ -0,0 +1,13 @@
+pub mod delta;
+pub mod graph;
+#[cfg(feature = "figures")]
+pub mod highlight;
+#[cfg(feature = "figures")]
+pub mod images;
+pub mod language;
+pub mod rdf;
+pub mod redline;
+pub mod registry;
+pub mod spoken;
+pub mod workcards;
+pub mod xlsx;
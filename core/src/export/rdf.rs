@@ -0,0 +1,223 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::engine::access::ACCESS_TAGS_FIELD;
+use crate::engine::ids::{in_namespace, split_namespace, stable_record_id};
+use crate::export::graph::{Graph, GraphNode};
+use crate::export::registry::{Exporter, ExporterRegistry};
+use crate::security::watermark::WATERMARK_MARKER;
+use crate::storage::atomic::write_atomic;
+
+// The vocabulary every export uses - shipped with the crate so consumers can load it alongside the data
+pub const ONTOLOGY_TTL: &str = include_str!("../../ontology/spp.ttl");
+pub const ONTOLOGY_IRI: &str = "urn:structured-pdf-parser:ontology#";
+pub const DEFAULT_BASE_IRI: &str = "urn:structured-pdf-parser:record:";
+
+const INTEGER_FIELDS: &[&str] = &[
+    "original_start", "original_end", "start", "end", "page", "source_page",
+    "step_count", "branch_count", "mentions", "edit_distance",
+];
+const DECIMAL_FIELDS: &[&str] = &["confidence", "declared_man_hours"];
+const BOOLEAN_FIELDS: &[&str] = &["checked", "signed", "read_only", "required"];
+
+// Procedure list fields that name entities by value, with the property linking them
+const PROCEDURE_LINKS: &[(&str, &str, &str)] = &[
+    ("tools", "tool", "requiresTool"),
+    ("access_panels_open", "access_panel", "opensPanel"),
+    ("access_panels_close", "access_panel", "closesPanel"),
+    ("circuit_breakers_pull", "circuit_breaker", "pullsBreaker"),
+    ("circuit_breakers_close", "circuit_breaker", "closesBreaker"),
+];
+
+fn class_for(node: &GraphNode) -> &'static str {
+    let property = |name: &str| node.properties.get(name).map(String::as_str).unwrap_or("");
+    match node.label.as_str() {
+        "Module" => "Module",
+        "Step" => "Step",
+        "Procedure" => "Procedure",
+        "LlmResult" => "LlmResult",
+        "Limit" => "Limit",
+        "Finding" => "Finding",
+        "FormField" => "FormField",
+        "Parameter" => match property("marker") {
+            "variant" => "VariantMarker",
+            _ => "Parameter",
+        },
+        "Condition" => match property("kind") {
+            "precondition" => "Precondition",
+            "postcondition" => "Postcondition",
+            _ => "Condition",
+        },
+        "Entity" => match property("entity_type") {
+            "tool" => "Tool",
+            "equipment" | "part" => "Part",
+            "access_panel" => "AccessPanel",
+            "zone" => "Zone",
+            "station" => "Station",
+            "circuit_breaker" => "CircuitBreaker",
+            _ => "Entity",
+        },
+        _ => "Record",
+    }
+}
+
+// record_id -> recordId
+fn property_name(field: &str) -> String {
+    let mut name = String::new();
+    let mut upper = false;
+    for c in field.chars() {
+        if c == '_' || c == '-' || c == ' ' {
+            upper = !name.is_empty();
+        } else if upper {
+            name.extend(c.to_uppercase());
+            upper = false;
+        } else if c.is_ascii_alphanumeric() {
+            name.push(c);
+        }
+    }
+    name
+}
+
+fn turtle_string(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+fn literal(field: &str, value: &str) -> String {
+    if INTEGER_FIELDS.contains(&field) && value.parse::<i64>().is_ok() {
+        return value.to_string();
+    }
+    if DECIMAL_FIELDS.contains(&field) && value.parse::<f64>().is_ok() {
+        return format!("{}^^xsd:decimal", turtle_string(value));
+    }
+    if BOOLEAN_FIELDS.contains(&field) && value.parse::<bool>().is_ok() {
+        return value.to_string();
+    }
+    turtle_string(value)
+}
+
+// Record IDs are "<type>_<hex>" and fit a prefixed name; anything else gets a full IRI
+fn subject(base_iri: &str, record_id: &str) -> String {
+    if !record_id.is_empty() && record_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        format!("rec:{}", record_id)
+    } else {
+        format!("<{}{}>", base_iri, record_id.replace(['<', '>', '"', ' ', '{', '}', '|', '\\', '^', '`'], "_"))
+    }
+}
+
+fn relationship_property(kind: &str) -> &'static str {
+    match kind {
+        "PART_OF" => "partOf",
+        "MENTIONED_IN" => "mentionedIn",
+        "BELONGS_TO" => "belongsTo",
+        _ => "references",
+    }
+}
+
+pub fn to_turtle(graph: &Graph, base_iri: &str) -> String {
+    let known: BTreeSet<&str> = graph.nodes.iter().map(|node| node.record_id.as_str()).collect();
+    // Entities by type and value, for IDs written in a format that cannot be derived again
+    let mut entities: HashMap<(&str, &str), Vec<&str>> = HashMap::new();
+    for node in graph.nodes.iter().filter(|node| node.label == "Entity") {
+        if let (Some(entity_type), Some(value)) = (node.properties.get("entity_type"), node.properties.get("value")) {
+            entities.entry((entity_type.as_str(), value.as_str())).or_default().push(node.record_id.as_str());
+        }
+    }
+    let mut links: HashMap<&str, Vec<(String, String)>> = HashMap::new();
+    for relationship in &graph.relationships {
+        links.entry(relationship.start.as_str())
+            .or_default()
+            .push((relationship_property(&relationship.kind).to_string(), relationship.end.clone()));
+    }
+
+    let mut turtle = graph.watermark.as_ref()
+        .map(|watermark| format!("# {} {}\n", WATERMARK_MARKER, watermark))
+        .unwrap_or_default();
+    turtle.push_str(&format!(
+        "@prefix spp: <{}> .\n@prefix rec: <{}> .\n@prefix xsd: <http://www.w3.org/2001/XMLSchema#> .\n",
+        ONTOLOGY_IRI, base_iri
+    ));
+    for node in &graph.nodes {
+        let mut statements = vec![format!("a spp:{}", class_for(node))];
+        for (field, value) in &node.properties {
+            if field == ACCESS_TAGS_FIELD {
+                for tag in value.split(',').filter(|tag| !tag.is_empty()) {
+                    statements.push(format!("spp:accessTag {}", turtle_string(tag)));
+                }
+                continue;
+            }
+            let name = property_name(field);
+            if !name.is_empty() {
+                statements.push(format!("spp:{} {}", name, literal(field, value)));
+            }
+        }
+        for (property, target) in links.get(node.record_id.as_str()).into_iter().flatten() {
+            statements.push(format!("spp:{} {}", property, subject(base_iri, target)));
+        }
+        if node.label == "Procedure" {
+            let (namespace, _) = split_namespace(&node.record_id);
+            for (field, entity_type, property) in PROCEDURE_LINKS {
+                let values = node.properties.get(*field).map(String::as_str).unwrap_or("");
+                for value in values.split(',').filter(|value| !value.is_empty()) {
+                    let target = in_namespace(namespace, stable_record_id("entity", &[entity_type, value]));
+                    let target = match entities.get(&(*entity_type, value)).map(Vec::as_slice) {
+                        _ if known.contains(target.as_str()) => Some(target.as_str()),
+                        Some([only]) => Some(*only),
+                        _ => None,
+                    };
+                    if let Some(target) = target {
+                        statements.push(format!("spp:{} {}", property, subject(base_iri, target)));
+                    }
+                }
+            }
+        }
+        turtle.push_str(&format!("\n{}\n    {} .\n", subject(base_iri, &node.record_id), statements.join(" ;\n    ")));
+    }
+    turtle
+}
+
+pub fn export_rdf_file(input_path: &str, output_path: &str, base_iri: &str) -> Result<Graph, Box<dyn std::error::Error>> {
+    let output: Value = serde_json::from_str(&std::fs::read_to_string(input_path)?)?;
+    let graph = Graph::from_output(&output);
+    write_atomic(output_path, to_turtle(&graph, base_iri))?;
+    Ok(graph)
+}
+
+// Job exports: <stem>.ttl
+struct TurtleExporter;
+
+impl Exporter for TurtleExporter {
+    fn export(&self, output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        export_rdf_file(&output_path.to_string_lossy(), &output_path.with_extension("ttl").to_string_lossy(), DEFAULT_BASE_IRI)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn register_exporters(registry: &mut ExporterRegistry) {
+    registry.add("rdf", Arc::new(|| Box::new(TurtleExporter)));
+}
+
+// Python bindings - Turtle export and the ontology it uses
+#[pyfunction]
+#[pyo3(signature = (input_path, output_path, base_iri=DEFAULT_BASE_IRI))]
+pub fn export_rdf(py: Python, input_path: &str, output_path: &str, base_iri: &str) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let graph = export_rdf_file(input_path, output_path, base_iri)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let mut summary = graph.get_summary();
+        summary.insert("output_path".to_string(), output_path.to_string());
+        Ok(summary)
+    })
+}
+
+#[pyfunction]
+pub fn get_rdf_ontology() -> PyResult<String> {
+    Ok(ONTOLOGY_TTL.to_string())
+}
@@ -0,0 +1,437 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use chrono::Utc;
+
+use crate::engine::entities::record_extents;
+use crate::licensing::branding::Branding;
+use crate::schema::envelope::{output_branding, output_watermark, payload_of};
+use crate::security::watermark::WATERMARK_MARKER;
+use crate::storage::atomic::write_atomic;
+
+// Word diffs are quadratic; longer step pairs are shown as replaced outright
+const MAX_WORD_DIFF_CELLS: usize = 1_000_000;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+table{border-collapse:collapse;width:100%;margin-bottom:1em}\
+th,td{border:1px solid #ccc;padding:4px 8px;vertical-align:top;text-align:left}\
+td.number{width:3em;color:#666}\
+tr.inserted td.current{background:#e6ffec}\
+tr.deleted td.previous{background:#ffebe9}\
+tr.changed td{background:#fff8c5}\
+del{background:#ffc1c0;text-decoration:line-through}\
+ins{background:#abf2bc;text-decoration:none}\
+.status{font-size:0.7em;text-transform:uppercase;color:#666}\
+table.signoff td{height:2em}\
+.brand{font-size:0.9em;color:#444;margin-bottom:0}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepChange {
+    Unchanged,
+    Changed,
+    Inserted,
+    Deleted,
+}
+
+impl StepChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StepChange::Unchanged => "unchanged",
+            StepChange::Changed => "changed",
+            StepChange::Inserted => "inserted",
+            StepChange::Deleted => "deleted",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RedlineStep {
+    pub record_id: String,
+    pub change: StepChange,
+    // 1-based positions within the procedure in each revision
+    pub previous_number: Option<usize>,
+    pub current_number: Option<usize>,
+    pub previous_text: Option<String>,
+    pub current_text: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RedlineProcedure {
+    pub module_id: String,
+    pub title: String,
+    // "added", "removed", "modified" or "unchanged"
+    pub status: &'static str,
+    pub steps: Vec<RedlineStep>,
+}
+
+#[derive(Debug, Default)]
+pub struct Redline {
+    pub previous_source: Option<String>,
+    pub current_source: Option<String>,
+    pub generated_at: String,
+    pub procedures: Vec<RedlineProcedure>,
+    // The current output's watermark, carried into the page
+    pub watermark: Option<String>,
+    // The licensee the page is made for, from the current output
+    pub branding: Option<Branding>,
+}
+
+impl Redline {
+    fn count(&self, change: StepChange) -> usize {
+        self.procedures.iter().flat_map(|procedure| &procedure.steps).filter(|step| step.change == change).count()
+    }
+
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let mut summary = HashMap::new();
+        summary.insert("procedures".to_string(), self.procedures.len().to_string());
+        summary.insert(
+            "procedures_changed".to_string(),
+            self.procedures.iter().filter(|procedure| procedure.status != "unchanged").count().to_string(),
+        );
+        summary.insert("steps_inserted".to_string(), self.count(StepChange::Inserted).to_string());
+        summary.insert("steps_deleted".to_string(), self.count(StepChange::Deleted).to_string());
+        summary.insert("steps_changed".to_string(), self.count(StepChange::Changed).to_string());
+        summary.insert("steps_unchanged".to_string(), self.count(StepChange::Unchanged).to_string());
+        summary
+    }
+}
+
+// A procedure's steps in document order as (record_id, text)
+pub(crate) struct ProcedureSteps {
+    pub module_id: String,
+    pub title: String,
+    pub steps: Vec<(String, String)>,
+}
+
+fn as_strings(record: &Value) -> HashMap<String, String> {
+    record.as_object().into_iter().flatten()
+        .filter_map(|(key, value)| value.as_str().map(|value| (key.clone(), value.to_string())))
+        .collect()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Steps grouped under the module whose extent holds them. A step's text runs from its start to
+// the next step or module, line breaks kept; without the source text it is the step's pattern
+pub(crate) fn raw_procedure_steps(output: &Value, text: Option<&str>) -> Vec<ProcedureSteps> {
+    let payload = payload_of(output);
+    let records = |collection: &str| -> Vec<HashMap<String, String>> {
+        payload.get(collection).and_then(Value::as_array).into_iter().flatten().map(as_strings).collect()
+    };
+    let modules = records("modules");
+    let steps = records("steps");
+    let text_len = text.map(str::len).unwrap_or(usize::MAX);
+    let module_extents = record_extents(&modules, text_len);
+    let mut boundaries = modules.clone();
+    boundaries.extend(steps.iter().cloned());
+    let step_extents = record_extents(&boundaries, text_len);
+
+    let mut procedures: Vec<ProcedureSteps> = module_extents.iter()
+        .map(|(_, _, module_id)| ProcedureSteps {
+            module_id: module_id.clone(),
+            title: modules.iter()
+                .find(|module| module.get("record_id") == Some(module_id))
+                .and_then(|module| module.get("pattern"))
+                .cloned()
+                .unwrap_or_else(|| module_id.clone()),
+            steps: Vec::new(),
+        })
+        .collect();
+    // Steps before the first module still show up, under a procedure of their own
+    let mut loose = ProcedureSteps { module_id: String::new(), title: "Steps outside any procedure".to_string(), steps: Vec::new() };
+
+    for (start, end, record_id) in &step_extents {
+        let Some(step) = steps.iter().find(|step| step.get("record_id") == Some(record_id)) else {
+            continue;
+        };
+        let step_text = text.and_then(|text| text.get(*start..*end))
+            .map(|step_text| step_text.trim().to_string())
+            .filter(|step_text| !step_text.is_empty())
+            .unwrap_or_else(|| step.get("pattern").cloned().unwrap_or_default());
+        let owner = module_extents.iter().position(|(module_start, module_end, _)| start >= module_start && start < module_end);
+        match owner {
+            Some(index) => procedures[index].steps.push((record_id.clone(), step_text)),
+            None => loose.steps.push((record_id.clone(), step_text)),
+        }
+    }
+    if !loose.steps.is_empty() {
+        procedures.insert(0, loose);
+    }
+    procedures
+}
+
+fn procedure_steps(output: &Value, text: Option<&str>) -> Vec<ProcedureSteps> {
+    let mut procedures = raw_procedure_steps(output, text);
+    for procedure in &mut procedures {
+        for (_, step_text) in &mut procedure.steps {
+            *step_text = collapse_whitespace(step_text);
+        }
+    }
+    procedures
+}
+
+// Steps of both revisions in reading order: current steps in place, each deleted step after
+// the step it followed in the previous revision
+fn compare_steps(previous: &[(String, String)], current: &[(String, String)]) -> Vec<RedlineStep> {
+    let previous_ids: HashMap<&str, usize> = previous.iter().enumerate().map(|(index, (id, _))| (id.as_str(), index)).collect();
+    let current_ids: BTreeSet<&str> = current.iter().map(|(id, _)| id.as_str()).collect();
+
+    let deleted = |(index, (record_id, text)): (usize, &(String, String))| RedlineStep {
+        record_id: record_id.clone(),
+        change: StepChange::Deleted,
+        previous_number: Some(index + 1),
+        current_number: None,
+        previous_text: Some(text.clone()),
+        current_text: None,
+    };
+    // Previous steps that are gone, keyed by the surviving step before them
+    let mut deleted_after: HashMap<Option<&str>, Vec<RedlineStep>> = HashMap::new();
+    let mut anchor: Option<&str> = None;
+    for (index, step) in previous.iter().enumerate() {
+        if current_ids.contains(step.0.as_str()) {
+            anchor = Some(step.0.as_str());
+        } else {
+            deleted_after.entry(anchor).or_default().push(deleted((index, step)));
+        }
+    }
+
+    let mut steps = deleted_after.remove(&None).unwrap_or_default();
+    for (index, (record_id, text)) in current.iter().enumerate() {
+        let before = previous_ids.get(record_id.as_str()).map(|&previous_index| (previous_index, &previous[previous_index].1));
+        steps.push(RedlineStep {
+            record_id: record_id.clone(),
+            change: match before {
+                None => StepChange::Inserted,
+                Some((_, previous_text)) if previous_text != text => StepChange::Changed,
+                Some(_) => StepChange::Unchanged,
+            },
+            previous_number: before.map(|(previous_index, _)| previous_index + 1),
+            current_number: Some(index + 1),
+            previous_text: before.map(|(_, previous_text)| previous_text.clone()),
+            current_text: Some(text.clone()),
+        });
+        steps.extend(deleted_after.remove(&Some(record_id.as_str())).unwrap_or_default());
+    }
+    steps
+}
+
+pub fn compute_redline(previous: &Value, current: &Value, previous_text: Option<&str>, current_text: Option<&str>) -> Redline {
+    let previous_procedures = procedure_steps(previous, previous_text);
+    let current_procedures = procedure_steps(current, current_text);
+    let find_previous = |module_id: &str| previous_procedures.iter().find(|procedure| procedure.module_id == module_id);
+
+    let mut procedures: Vec<RedlineProcedure> = current_procedures.iter()
+        .map(|procedure| {
+            let before = find_previous(&procedure.module_id);
+            let steps = compare_steps(before.map(|before| before.steps.as_slice()).unwrap_or_default(), &procedure.steps);
+            let status = match before {
+                None => "added",
+                Some(_) if steps.iter().any(|step| step.change != StepChange::Unchanged) => "modified",
+                Some(_) => "unchanged",
+            };
+            RedlineProcedure { module_id: procedure.module_id.clone(), title: procedure.title.clone(), status, steps }
+        })
+        .collect();
+    // Procedures dropped from the manual come last, with every step deleted
+    for procedure in &previous_procedures {
+        if !current_procedures.iter().any(|current| current.module_id == procedure.module_id) {
+            procedures.push(RedlineProcedure {
+                module_id: procedure.module_id.clone(),
+                title: procedure.title.clone(),
+                status: "removed",
+                steps: compare_steps(&procedure.steps, &[]),
+            });
+        }
+    }
+
+    let source = |output: &Value| payload_of(output).get("source").and_then(Value::as_str).map(str::to_string);
+    Redline {
+        previous_source: source(previous),
+        current_source: source(current),
+        generated_at: Utc::now().to_rfc3339(),
+        procedures,
+        watermark: output_watermark(current),
+        branding: output_branding(current),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Previous and current cell content, with deleted words struck out and inserted words marked
+fn word_diff(previous: &str, current: &str) -> (String, String) {
+    let old: Vec<&str> = previous.split_whitespace().collect();
+    let new: Vec<&str> = current.split_whitespace().collect();
+    if old.len() * new.len() > MAX_WORD_DIFF_CELLS {
+        return (format!("<del>{}</del>", escape_html(previous)), format!("<ins>{}</ins>", escape_html(current)));
+    }
+
+    // Longest common subsequence lengths of every pair of suffixes
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] { lengths[i + 1][j + 1] + 1 } else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+        }
+    }
+
+    let (mut old_cell, mut new_cell) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            old_cell.push(escape_html(old[i]));
+            new_cell.push(escape_html(new[j]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            new_cell.push(format!("<ins>{}</ins>", escape_html(new[j])));
+            j += 1;
+        } else {
+            old_cell.push(format!("<del>{}</del>", escape_html(old[i])));
+            i += 1;
+        }
+    }
+    (old_cell.join(" "), new_cell.join(" "))
+}
+
+fn step_row(step: &RedlineStep) -> String {
+    let number = |number: Option<usize>| number.map(|number| number.to_string()).unwrap_or_default();
+    let text = |text: &Option<String>| text.as_deref().map(escape_html).unwrap_or_default();
+    let (previous, current) = match (step.change, &step.previous_text, &step.current_text) {
+        (StepChange::Changed, Some(previous), Some(current)) => word_diff(previous, current),
+        (StepChange::Deleted, _, _) => (format!("<del>{}</del>", text(&step.previous_text)), String::new()),
+        (StepChange::Inserted, _, _) => (String::new(), format!("<ins>{}</ins>", text(&step.current_text))),
+        _ => (text(&step.previous_text), text(&step.current_text)),
+    };
+    format!(
+        "<tr class=\"{}\" data-record-id=\"{}\"><td class=\"number\">{}</td><td class=\"previous\">{}</td><td class=\"number\">{}</td><td class=\"current\">{}</td></tr>\n",
+        step.change.as_str(),
+        escape_html(&step.record_id),
+        number(step.previous_number),
+        previous,
+        number(step.current_number),
+        current,
+    )
+}
+
+// One self-contained page: a table per procedure and a sign-off line under each
+pub fn render_redline_html(redline: &Redline) -> String {
+    let summary = redline.get_summary();
+    let source = |source: &Option<String>| escape_html(source.as_deref().unwrap_or("(none)"));
+    let mut html = String::from("<!DOCTYPE html>\n");
+    if let Some(watermark) = &redline.watermark {
+        html.push_str(&format!("<!-- {} {} -->\n", WATERMARK_MARKER, watermark));
+    }
+    let brand = redline.branding.as_ref().map(Branding::label).filter(|label| !label.is_empty());
+    let title_prefix = brand.as_ref().map(|label| format!("{} - ", escape_html(label))).unwrap_or_default();
+    html.push_str(&format!(
+        "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}Redline: {}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        title_prefix,
+        source(&redline.current_source),
+        STYLE,
+    ));
+    if let Some(label) = &brand {
+        html.push_str(&format!("<p class=\"brand\">{}</p>\n", escape_html(label)));
+    }
+    html.push_str("<h1>Revision redline</h1>\n");
+    html.push_str(&format!(
+        "<p>Previous: {}<br>Current: {}<br>Generated: {}</p>\n",
+        source(&redline.previous_source),
+        source(&redline.current_source),
+        escape_html(&redline.generated_at),
+    ));
+    html.push_str(&format!(
+        "<p>{} of {} procedures changed: {} steps inserted, {} deleted, {} changed, {} unchanged.</p>\n",
+        summary["procedures_changed"],
+        summary["procedures"],
+        summary["steps_inserted"],
+        summary["steps_deleted"],
+        summary["steps_changed"],
+        summary["steps_unchanged"],
+    ));
+
+    for procedure in &redline.procedures {
+        html.push_str(&format!(
+            "<section class=\"procedure {}\" data-module-id=\"{}\">\n<h2>{} <span class=\"status\">{}</span></h2>\n",
+            procedure.status,
+            escape_html(&procedure.module_id),
+            escape_html(&procedure.title),
+            procedure.status,
+        ));
+        html.push_str("<table>\n<thead><tr><th>#</th><th>Previous</th><th>#</th><th>Current</th></tr></thead>\n<tbody>\n");
+        for step in &procedure.steps {
+            html.push_str(&step_row(step));
+        }
+        html.push_str("</tbody>\n</table>\n");
+        html.push_str("<table class=\"signoff\"><tr><td>Reviewed by</td><td></td><td>Date</td><td></td></tr></table>\n</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+pub(crate) fn read_source(output: &Value, source_path: Option<&str>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    match source_path {
+        Some(path) => Ok(Some(std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?)),
+        None => Ok(payload_of(output).get("source")
+            .and_then(Value::as_str)
+            .and_then(|source| std::fs::read_to_string(source).ok())),
+    }
+}
+
+pub fn export_redline_file(
+    previous_path: &str,
+    current_path: &str,
+    output_path: &str,
+    previous_source: Option<&str>,
+    current_source: Option<&str>,
+) -> Result<Redline, Box<dyn std::error::Error>> {
+    // No previous output means every procedure is new, as for delta exports
+    let previous = if Path::new(previous_path).exists() {
+        serde_json::from_str(&std::fs::read_to_string(previous_path)?)?
+    } else {
+        Value::Null
+    };
+    let current: Value = serde_json::from_str(&std::fs::read_to_string(current_path)?)?;
+    let previous_text = read_source(&previous, previous_source)?;
+    let current_text = read_source(&current, current_source)?;
+
+    let redline = compute_redline(&previous, &current, previous_text.as_deref(), current_text.as_deref());
+    write_atomic(output_path, render_redline_html(&redline))?;
+    Ok(redline)
+}
+
+// Python binding - writes the HTML page and returns the procedure and step counts. Source
+// texts default to each output's `source`; pass the old revision's text when the file has
+// since been overwritten
+#[pyfunction]
+#[pyo3(signature = (previous_path, current_path, output_path, previous_source=None, current_source=None))]
+pub fn export_redline(
+    py: Python,
+    previous_path: &str,
+    current_path: &str,
+    output_path: &str,
+    previous_source: Option<&str>,
+    current_source: Option<&str>,
+) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let redline = export_redline_file(previous_path, current_path, output_path, previous_source, current_source)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let mut summary = redline.get_summary();
+        summary.insert("output_path".to_string(), output_path.to_string());
+        Ok(summary)
+    })
+}
@@ -0,0 +1,116 @@
+use pyo3::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::export::{graph, rdf};
+
+// The job output itself, which every run writes and no exporter may be named
+pub const JSON_FORMAT: &str = "json";
+
+// Writes an export of the JSON output at `output_path` next to it, e.g. <stem>.cypher
+pub trait Exporter: Send + Sync {
+    fn export(&self, output_path: &Path) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+// Makes the exporter for one output; called outside the registry lock
+pub type ExporterFactory = Arc<dyn Fn() -> Box<dyn Exporter> + Send + Sync>;
+
+// Export formats by name. The built-in ones are added by their own modules, through
+// register_exporters; custom ones by add_exporter, or register_exporter from Python
+#[derive(Default)]
+pub struct ExporterRegistry {
+    factories: BTreeMap<String, ExporterFactory>,
+    built_in: BTreeSet<String>,
+}
+
+impl ExporterRegistry {
+    pub fn add(&mut self, name: &str, factory: ExporterFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+}
+
+fn registry() -> &'static RwLock<ExporterRegistry> {
+    static REGISTRY: OnceLock<RwLock<ExporterRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = ExporterRegistry::default();
+        graph::register_exporters(&mut registry);
+        rdf::register_exporters(&mut registry);
+        registry.built_in = registry.factories.keys().cloned().collect();
+        RwLock::new(registry)
+    })
+}
+
+// No factory runs under the lock, so a panicking one cannot leave the map half-written and a
+// poisoned lock is still safe to use
+fn read() -> std::sync::RwLockReadGuard<'static, ExporterRegistry> {
+    registry().read().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Adds a custom format, or replaces a custom format of the same name. Names are lower case
+// letters, digits, '-' and '_'; built-in formats and "json" cannot be replaced
+pub fn add_exporter(name: &str, factory: ExporterFactory) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') {
+        return Err(format!("Export format names are lower case letters, digits, - and _, got {:?}", name));
+    }
+    let mut registry = registry().write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if name == JSON_FORMAT || registry.built_in.contains(name) {
+        return Err(format!("{} is a built-in format and cannot be replaced", name));
+    }
+    registry.add(name, factory);
+    Ok(())
+}
+
+// Registered formats in name order, "json" excluded
+pub fn export_formats() -> Vec<String> {
+    read().factories.keys().cloned().collect()
+}
+
+pub fn check_export_format(format: &str) -> Result<(), String> {
+    let known = read().factories.contains_key(format);
+    match known {
+        true => Ok(()),
+        false => Err(format!("Unknown export format: {} (expected {})", format, export_formats().join(", "))),
+    }
+}
+
+pub fn exporter_for(format: &str) -> Result<Box<dyn Exporter>, String> {
+    let factory = read().factories.get(format).cloned();
+    match factory {
+        Some(factory) => Ok(factory()),
+        None => Err(check_export_format(format).unwrap_err()),
+    }
+}
+
+// A Python callable taking the output path. Job workers run without the GIL, so each export
+// takes it for the call
+struct PythonExporter {
+    export: Arc<PyObject>,
+}
+
+impl Exporter for PythonExporter {
+    fn export(&self, output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        Python::with_gil(|py| {
+            self.export.call1(py, (output_path.to_string_lossy().to_string(),))
+                .map(|_| ())
+                .map_err(|e| format!("{}: {}", output_path.display(), e).into())
+        })
+    }
+}
+
+// Python bindings - `exporter(output_path)` writes its export next to the JSON output; spp run
+// --format and run_jobs(export_format=...) then select it by name
+#[pyfunction]
+pub fn register_exporter(py: Python, name: &str, exporter: PyObject) -> PyResult<()> {
+    if !exporter.as_ref(py).is_callable() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("exporter must be callable with the output path"));
+    }
+    let export = Arc::new(exporter);
+    let factory: ExporterFactory = Arc::new(move || Box::new(PythonExporter { export: Arc::clone(&export) }));
+    add_exporter(name, factory).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+#[pyfunction]
+pub fn get_export_formats() -> PyResult<Vec<String>> {
+    Ok(export_formats())
+}
@@ -0,0 +1,403 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::export::redline::{raw_procedure_steps, read_source};
+use crate::schema::envelope::{output_watermark, payload_of};
+use crate::security::watermark::WATERMARK_MARKER;
+use crate::storage::atomic::write_atomic;
+
+// First line of every spoken export, so a watermark check can tell it apart
+pub const SPOKEN_HEADER: &str = "Spoken procedures";
+
+// Units read out after a number: (abbreviation, singular, plural)
+const UNITS: &[(&str, &str, &str)] = &[
+    ("in-lb", "inch-pound", "inch-pounds"),
+    ("in.-lb", "inch-pound", "inch-pounds"),
+    ("lb-in", "pound-inch", "pound-inches"),
+    ("lbf.in", "pound-force inch", "pound-force inches"),
+    ("lbf-in", "pound-force inch", "pound-force inches"),
+    ("ft-lb", "foot-pound", "foot-pounds"),
+    ("lb-ft", "pound-foot", "pound-feet"),
+    ("lbf.ft", "pound-force foot", "pound-force feet"),
+    ("N·m", "newton meter", "newton meters"),
+    ("N.m", "newton meter", "newton meters"),
+    ("Nm", "newton meter", "newton meters"),
+    ("psi", "pound per square inch", "pounds per square inch"),
+    ("psig", "pound per square inch gauge", "pounds per square inch gauge"),
+    ("kPa", "kilopascal", "kilopascals"),
+    ("MPa", "megapascal", "megapascals"),
+    ("bar", "bar", "bar"),
+    ("mm", "millimeter", "millimeters"),
+    ("cm", "centimeter", "centimeters"),
+    ("m", "meter", "meters"),
+    ("in.", "inch", "inches"),
+    ("ft", "foot", "feet"),
+    ("lb", "pound", "pounds"),
+    ("lbs", "pound", "pounds"),
+    ("kg", "kilogram", "kilograms"),
+    ("g", "gram", "grams"),
+    ("°C", "degree Celsius", "degrees Celsius"),
+    ("°F", "degree Fahrenheit", "degrees Fahrenheit"),
+    ("°", "degree", "degrees"),
+    ("deg", "degree", "degrees"),
+    ("V", "volt", "volts"),
+    ("VDC", "volt DC", "volts DC"),
+    ("VAC", "volt AC", "volts AC"),
+    ("A", "amp", "amps"),
+    ("mA", "milliamp", "milliamps"),
+    ("Hz", "hertz", "hertz"),
+    ("kHz", "kilohertz", "kilohertz"),
+    ("ohm", "ohm", "ohms"),
+    ("ohms", "ohm", "ohms"),
+    ("Ω", "ohm", "ohms"),
+    ("L", "liter", "liters"),
+    ("ml", "milliliter", "milliliters"),
+    ("mL", "milliliter", "milliliters"),
+    ("qt", "quart", "quarts"),
+    ("gal", "gallon", "gallons"),
+    ("s", "second", "seconds"),
+    ("sec", "second", "seconds"),
+    ("min", "minute", "minutes"),
+    ("min.", "minute", "minutes"),
+    ("h", "hour", "hours"),
+    ("hr", "hour", "hours"),
+    ("rpm", "revolution per minute", "revolutions per minute"),
+    ("%", "percent", "percent"),
+];
+
+// Abbreviations read out the same wherever they appear - units come first after a number,
+// so "5 min." is minutes and "min. length" is minimum
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("AMM", "Aircraft Maintenance Manual"),
+    ("IPC", "Illustrated Parts Catalog"),
+    ("SRM", "Structural Repair Manual"),
+    ("P/N", "part number"),
+    ("S/N", "serial number"),
+    ("approx.", "approximately"),
+    ("Approx.", "Approximately"),
+    ("Ref.", "Refer to"),
+    ("ref.", "refer to"),
+    ("REF", "refer to"),
+    ("Fig.", "Figure"),
+    ("fig.", "figure"),
+    ("Para.", "Paragraph"),
+    ("para.", "paragraph"),
+    ("No.", "number"),
+    ("Qty", "Quantity"),
+    ("qty", "quantity"),
+    ("max.", "maximum"),
+    ("max", "maximum"),
+    ("min.", "minimum"),
+    ("min", "minimum"),
+    ("LH", "left hand"),
+    ("RH", "right hand"),
+    ("FWD", "forward"),
+    ("fwd", "forward"),
+    ("NLG", "nose landing gear"),
+    ("MLG", "main landing gear"),
+    ("CB", "circuit breaker"),
+    ("w/", "with"),
+    ("w/o", "without"),
+    ("e.g.", "for example"),
+    ("i.e.", "that is"),
+    ("ea.", "each"),
+    ("assy", "assembly"),
+    ("Assy", "Assembly"),
+    ("thru", "through"),
+    ("&", "and"),
+    ("±", "plus or minus"),
+];
+
+// Lines starting with these are read before the step they belong to
+const WARNING_WORDS: &[&str] = &["WARNING", "CAUTION"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpokenFormat {
+    Ssml,
+    Script,
+}
+
+impl SpokenFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format {
+            "ssml" => Ok(SpokenFormat::Ssml),
+            "script" => Ok(SpokenFormat::Script),
+            other => Err(format!("Unknown spoken format: {} (expected ssml or script)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpokenStep {
+    pub record_id: String,
+    // Warnings and cautions to read before the step
+    pub warnings: Vec<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpokenProcedure {
+    pub module_id: String,
+    pub title: String,
+    pub steps: Vec<SpokenStep>,
+}
+
+// "45", "0.05", "1,200" or "1/4"
+fn is_number(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_digit())
+        && token.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '/'))
+        && !token.ends_with(['.', ',', '/'])
+}
+
+fn unit(token: &str, quantity: &str) -> Option<&'static str> {
+    UNITS.iter()
+        .find(|(abbreviation, _, _)| *abbreviation == token)
+        .map(|(_, singular, plural)| if quantity == "1" { *singular } else { *plural })
+}
+
+fn abbreviation(token: &str) -> Option<&'static str> {
+    ABBREVIATIONS.iter().find(|(abbreviation, _)| *abbreviation == token).map(|(_, spoken)| *spoken)
+}
+
+// "45-50" reads "45 to 50"; reference numbers like "21-10-00" read group by group. The last
+// number is the one a unit after the range goes with
+fn spoken_number(token: &str) -> Option<(String, &str)> {
+    let parts: Vec<&str> = token.split('-').collect();
+    if parts.len() < 2 || !parts.iter().all(|part| is_number(part)) {
+        return None;
+    }
+    let spoken = if parts.len() == 2 { parts.join(" to ") } else { parts.join(" ") };
+    Some((spoken, parts[parts.len() - 1]))
+}
+
+// One whitespace-separated word and, when it is a number a unit may follow, that number
+fn spoken_word(word: &str, previous_number: Option<&str>) -> (String, Option<String>) {
+    let lead = word.len() - word.trim_start_matches(['(', '"']).len();
+    let (lead, rest) = word.split_at(lead);
+    // Trailing punctuation is kept, unless it belongs to the abbreviation ("approx.")
+    let core = rest.trim_end_matches([',', ';', ':', ')', '"', '.']);
+    let mut trail = &rest[core.len()..];
+    let mut core = core;
+    if let Some(dotted) = rest.get(..core.len() + 1).filter(|dotted| dotted.ends_with('.')) {
+        if previous_number.and_then(|quantity| unit(dotted, quantity)).is_some() || abbreviation(dotted).is_some() {
+            core = dotted;
+            trail = &rest[dotted.len()..];
+        }
+    }
+
+    let spoken = if let Some(words) = previous_number.and_then(|quantity| unit(core, quantity)) {
+        words.to_string()
+    } else if let Some(words) = abbreviation(core) {
+        words.to_string()
+    } else if is_number(core) {
+        return (format!("{}{}{}", lead, core, trail), Some(core.to_string()).filter(|_| trail.is_empty()));
+    } else if let Some((spoken, last)) = spoken_number(core) {
+        let number = Some(last.to_string()).filter(|_| trail.is_empty());
+        return (format!("{}{}{}", lead, spoken, trail), number);
+    } else {
+        // A number glued to its unit: "5mm", "20°C", "10%"
+        let digits = core.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ',')).unwrap_or(core.len());
+        let (quantity, suffix) = core.split_at(digits);
+        match unit(suffix, quantity) {
+            Some(words) if is_number(quantity) => format!("{} {}", quantity, words),
+            _ => core.to_string(),
+        }
+    };
+    (format!("{}{}{}", lead, spoken, trail), None)
+}
+
+// Text as it should be read aloud: abbreviations expanded, units spelled out after their
+// numbers, ranges and reference numbers made readable, whitespace collapsed
+pub fn spoken_text(text: &str) -> String {
+    // "±0.05" is split so the sign is a word of its own
+    let text = text.replace('±', " ± ");
+    let mut words = Vec::new();
+    let mut previous_number: Option<String> = None;
+    let mut text_words = text.split_whitespace().peekable();
+    while let Some(word) = text_words.next() {
+        let (mut spoken, number) = spoken_word(word, previous_number.as_deref());
+        // "max." ending a sentence gives its period back, "Ref. AMM" does not
+        let sentence_ends = text_words.peek().is_some_and(|next| next.starts_with(char::is_uppercase) && abbreviation(next).is_none());
+        if word.ends_with('.') && !spoken.ends_with('.') && sentence_ends && !spoken.ends_with(" to") {
+            spoken.push('.');
+        }
+        words.push(spoken);
+        previous_number = number;
+    }
+    words.join(" ")
+}
+
+fn is_warning(line: &str) -> bool {
+    WARNING_WORDS.iter().any(|word| line.starts_with(word))
+}
+
+// "2.", "(b)" or "-" alone on a line: the next step's marker, left in this step's text
+fn is_marker(line: &str) -> bool {
+    let label = line.strip_prefix('(').and_then(|inner| inner.strip_suffix(')'))
+        .or_else(|| line.strip_suffix('.'))
+        .unwrap_or(line);
+    matches!(line, "-" | "•" | "*" | "–")
+        || (!label.is_empty() && label.len() <= 3 && label != line && label.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+// A step's own text without item markers, and the warnings at its end that are the next step's
+fn split_step(text: &str) -> (String, Vec<String>) {
+    let mut lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    while lines.last().is_some_and(|line| is_marker(line)) {
+        lines.pop();
+    }
+    let marked: Vec<bool> = lines.iter().map(|line| line.split_once(' ').is_some_and(|(marker, _)| is_marker(marker))).collect();
+    // Warnings with no item after them lead into the next step
+    let split = (1..lines.len()).find(|&index| is_warning(lines[index]) && !marked[index..].contains(&true)).unwrap_or(lines.len());
+    // Item markers are read as numbers, and each step already says its own
+    for (line, marked) in lines.iter_mut().zip(marked) {
+        if let Some((_, rest)) = line.split_once(' ').filter(|_| marked) {
+            *line = rest.trim_start();
+        }
+    }
+    let mut warnings: Vec<String> = Vec::new();
+    for line in &lines[split..] {
+        match warnings.last_mut() {
+            Some(warning) if !is_warning(line) => {
+                warning.push(' ');
+                warning.push_str(line);
+            }
+            _ => warnings.push(line.to_string()),
+        }
+    }
+    (lines[..split].join(" "), warnings)
+}
+
+// Procedures in reading order, as written. A warning between two steps goes with the second one
+pub fn procedures_in_reading_order(output: &Value, text: Option<&str>) -> Vec<SpokenProcedure> {
+    raw_procedure_steps(output, text).into_iter()
+        .map(|procedure| {
+            let mut carried: Vec<String> = Vec::new();
+            let mut steps: Vec<SpokenStep> = Vec::new();
+            let count = procedure.steps.len();
+            for (index, (record_id, step_text)) in procedure.steps.into_iter().enumerate() {
+                let (mut text, mut warnings) = split_step(&step_text);
+                // The last step keeps its trailing warnings; no step follows to read them before
+                if index + 1 == count && !warnings.is_empty() {
+                    text = format!("{} {}", text, warnings.join(" "));
+                    warnings.clear();
+                }
+                steps.push(SpokenStep { record_id, warnings: std::mem::take(&mut carried), text });
+                carried = warnings;
+            }
+            SpokenProcedure { module_id: procedure.module_id, title: procedure.title, steps }
+        })
+        .collect()
+}
+
+// Procedures in reading order, with every text as it should be read aloud
+pub fn spoken_procedures(output: &Value, text: Option<&str>) -> Vec<SpokenProcedure> {
+    let mut procedures = procedures_in_reading_order(output, text);
+    for procedure in &mut procedures {
+        procedure.title = spoken_text(&procedure.title);
+        for step in &mut procedure.steps {
+            step.text = spoken_text(&step.text);
+            for warning in &mut step.warnings {
+                *warning = spoken_text(warning);
+            }
+        }
+    }
+    procedures
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn ends_sentence(text: &str) -> String {
+    if text.ends_with(['.', '!', '?']) { text.to_string() } else { format!("{}.", text) }
+}
+
+// SSML 1.1 for speech engines: a pause after each step number, warnings read with emphasis
+pub fn render_ssml(procedures: &[SpokenProcedure], source: Option<&str>, watermark: Option<&str>) -> String {
+    let mut ssml = format!("<!-- {}: {} -->\n", SPOKEN_HEADER, escape_xml(source.unwrap_or("(none)")).replace("--", "- -"));
+    if let Some(watermark) = watermark {
+        ssml.push_str(&format!("<!-- {} {} -->\n", WATERMARK_MARKER, watermark));
+    }
+    ssml.push_str("<speak version=\"1.1\" xmlns=\"http://www.w3.org/2001/10/synthesis\" xml:lang=\"en-US\">\n");
+    for procedure in procedures {
+        ssml.push_str(&format!(
+            "<p><s>{}</s><s>{} steps.</s></p>\n<break time=\"1s\"/>\n",
+            escape_xml(&ends_sentence(&procedure.title)),
+            procedure.steps.len(),
+        ));
+        for (index, step) in procedure.steps.iter().enumerate() {
+            for warning in &step.warnings {
+                ssml.push_str(&format!("<p><emphasis level=\"strong\">{}</emphasis></p>\n<break time=\"500ms\"/>\n", escape_xml(&ends_sentence(warning))));
+            }
+            ssml.push_str(&format!(
+                "<p><s>Step {} of {}.</s><break time=\"300ms\"/><s>{}</s></p>\n",
+                index + 1,
+                procedure.steps.len(),
+                escape_xml(&ends_sentence(&step.text)),
+            ));
+        }
+        ssml.push_str("<break time=\"2s\"/>\n");
+    }
+    ssml.push_str("</speak>\n");
+    ssml
+}
+
+// Plain scripted steps, one line each, for apps that feed text to their own voice
+pub fn render_script(procedures: &[SpokenProcedure], source: Option<&str>, watermark: Option<&str>) -> String {
+    let mut script = format!("# {}: {}\n", SPOKEN_HEADER, source.unwrap_or("(none)"));
+    if let Some(watermark) = watermark {
+        script.push_str(&format!("# {} {}\n", WATERMARK_MARKER, watermark));
+    }
+    for procedure in procedures {
+        script.push_str(&format!("\n{} {} steps.\n", ends_sentence(&procedure.title), procedure.steps.len()));
+        for (index, step) in procedure.steps.iter().enumerate() {
+            for warning in &step.warnings {
+                script.push_str(&format!("{}\n", ends_sentence(warning)));
+            }
+            script.push_str(&format!("Step {} of {}. {}\n", index + 1, procedure.steps.len(), ends_sentence(&step.text)));
+        }
+    }
+    script
+}
+
+pub fn export_spoken_file(
+    input_path: &str,
+    output_path: &str,
+    format: SpokenFormat,
+    source_path: Option<&str>,
+) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let output: Value = serde_json::from_str(&std::fs::read_to_string(input_path)?)?;
+    let text = read_source(&output, source_path)?;
+    let procedures = spoken_procedures(&output, text.as_deref());
+    let source = payload_of(&output).get("source").and_then(Value::as_str);
+    let watermark = output_watermark(&output);
+    let rendered = match format {
+        SpokenFormat::Ssml => render_ssml(&procedures, source, watermark.as_deref()),
+        SpokenFormat::Script => render_script(&procedures, source, watermark.as_deref()),
+    };
+    write_atomic(output_path, rendered)?;
+
+    let mut summary = HashMap::new();
+    summary.insert("procedures".to_string(), procedures.len().to_string());
+    summary.insert("steps".to_string(), procedures.iter().map(|procedure| procedure.steps.len()).sum::<usize>().to_string());
+    summary.insert(
+        "warnings".to_string(),
+        procedures.iter().flat_map(|procedure| &procedure.steps).map(|step| step.warnings.len()).sum::<usize>().to_string(),
+    );
+    summary.insert("output_path".to_string(), output_path.to_string());
+    Ok(summary)
+}
+
+// Python binding - format is "ssml" or "script". Step text comes from the output's `source`
+// unless source_path gives the text
+#[pyfunction]
+#[pyo3(signature = (input_path, output_path, format="ssml", source_path=None))]
+pub fn export_spoken(py: Python, input_path: &str, output_path: &str, format: &str, source_path: Option<&str>) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let format = SpokenFormat::parse(format).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        export_spoken_file(input_path, output_path, format, source_path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    })
+}
@@ -0,0 +1,290 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::export::graph::csv_row;
+use crate::export::redline::read_source;
+use crate::export::spoken::procedures_in_reading_order;
+use crate::engine::access::ACCESS_TAGS_FIELD;
+use crate::engine::language::LANGUAGE_FIELD;
+use crate::export::xlsx::write_workbook;
+use crate::schema::envelope::{output_watermark, payload_of};
+use crate::security::watermark::WATERMARK_MARKER;
+use crate::storage::atomic::write_atomic;
+
+// What a template column can hold. "blank" is an empty cell for technicians to fill in
+pub const WORKCARD_FIELDS: [&str; 12] = [
+    "procedure", "module_id", "step_number", "step", "record_id", "tooling", "warnings",
+    "access_panels", "circuit_breakers", "access_tags", "language", "blank",
+];
+
+// Entity types listed under "tooling"
+const TOOLING_TYPES: &[&str] = &["tool", "equipment"];
+
+// Spreadsheet width of a column the template gives none for, in characters
+const DEFAULT_WIDTH: f64 = 14.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkCardFormat {
+    Csv,
+    Xlsx,
+}
+
+impl WorkCardFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format {
+            "csv" => Ok(WorkCardFormat::Csv),
+            "xlsx" => Ok(WorkCardFormat::Xlsx),
+            other => Err(format!("Unknown work card format: {} (expected csv or xlsx)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkCardColumn {
+    pub header: String,
+    pub field: String,
+    #[serde(default)]
+    pub width: Option<f64>,
+}
+
+impl WorkCardColumn {
+    fn new(header: &str, field: &str, width: f64) -> Self {
+        Self { header: header.to_string(), field: field.to_string(), width: Some(width) }
+    }
+}
+
+// Columns of a work card sheet, left to right
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkCardTemplate {
+    #[serde(default = "default_sheet")]
+    pub sheet: String,
+    pub columns: Vec<WorkCardColumn>,
+}
+
+fn default_sheet() -> String {
+    "Work cards".to_string()
+}
+
+impl Default for WorkCardTemplate {
+    fn default() -> Self {
+        Self {
+            sheet: default_sheet(),
+            columns: vec![
+                WorkCardColumn::new("Procedure", "procedure", 30.0),
+                WorkCardColumn::new("Step", "step_number", 6.0),
+                WorkCardColumn::new("Instruction", "step", 60.0),
+                WorkCardColumn::new("Tooling", "tooling", 25.0),
+                WorkCardColumn::new("Warnings", "warnings", 40.0),
+                WorkCardColumn::new("Technician", "blank", 14.0),
+                WorkCardColumn::new("Inspector", "blank", 14.0),
+                WorkCardColumn::new("Date", "blank", 12.0),
+            ],
+        }
+    }
+}
+
+impl WorkCardTemplate {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("Cannot read work card template {}: {}", path, e))?;
+        let template: Self = serde_json::from_str(&content).map_err(|e| format!("Invalid work card template {}: {}", path, e))?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.columns.is_empty() {
+            return Err("columns must list at least one column".to_string());
+        }
+        if let Some(column) = self.columns.iter().find(|column| !WORKCARD_FIELDS.contains(&column.field.as_str())) {
+            return Err(format!("Unknown work card field: {} (expected {})", column.field, WORKCARD_FIELDS.join(", ")));
+        }
+        if let Some(column) = self.columns.iter().find(|column| column.width.is_some_and(|width| !(1.0..=255.0).contains(&width))) {
+            return Err(format!("Column {} width must be 1-255 characters", column.header));
+        }
+        Ok(())
+    }
+}
+
+// Entity values of the given types by the step record IDs they are mentioned in
+fn entities_by_step(output: &Value, types: &[&str]) -> HashMap<String, Vec<String>> {
+    let mut by_step: HashMap<String, Vec<String>> = HashMap::new();
+    let entities = payload_of(output).get("entities").and_then(Value::as_array).into_iter().flatten();
+    for entity in entities {
+        let field = |name: &str| entity.get(name).and_then(Value::as_str).unwrap_or_default();
+        if !types.contains(&field("entity_type")) {
+            continue;
+        }
+        for step_id in field("step_ids").split(',').filter(|step_id| !step_id.is_empty()) {
+            let values = by_step.entry(step_id.to_string()).or_default();
+            if !values.iter().any(|value| value == field("value")) {
+                values.push(field("value").to_string());
+            }
+        }
+    }
+    by_step
+}
+
+// A field of each step record, by record ID
+fn step_field(output: &Value, field: &str) -> HashMap<String, String> {
+    payload_of(output).get("steps").and_then(Value::as_array).into_iter().flatten()
+        .filter_map(|step| {
+            let value = |name: &str| step.get(name).and_then(Value::as_str).map(str::to_string);
+            Some((value("record_id")?, value(field)?))
+        })
+        .collect()
+}
+
+// The header row, then one row per step in procedure order
+pub fn workcard_rows(output: &Value, text: Option<&str>, template: &WorkCardTemplate) -> Vec<Vec<String>> {
+    let tooling = entities_by_step(output, TOOLING_TYPES);
+    let panels = entities_by_step(output, &["access_panel"]);
+    let breakers = entities_by_step(output, &["circuit_breaker"]);
+    let access_tags = step_field(output, ACCESS_TAGS_FIELD);
+    let languages = step_field(output, LANGUAGE_FIELD);
+    let listed = |by_step: &HashMap<String, Vec<String>>, record_id: &str| {
+        by_step.get(record_id).map(|values| values.join("; ")).unwrap_or_default()
+    };
+
+    let mut rows = vec![template.columns.iter().map(|column| column.header.clone()).collect()];
+    for procedure in procedures_in_reading_order(output, text) {
+        for (index, step) in procedure.steps.iter().enumerate() {
+            rows.push(template.columns.iter()
+                .map(|column| match column.field.as_str() {
+                    "procedure" => procedure.title.clone(),
+                    "module_id" => procedure.module_id.clone(),
+                    "step_number" => (index + 1).to_string(),
+                    "step" => step.text.clone(),
+                    "record_id" => step.record_id.clone(),
+                    "tooling" => listed(&tooling, &step.record_id),
+                    "warnings" => step.warnings.join("\n"),
+                    "access_panels" => listed(&panels, &step.record_id),
+                    "circuit_breakers" => listed(&breakers, &step.record_id),
+                    "access_tags" => access_tags.get(&step.record_id).cloned().unwrap_or_default(),
+                    "language" => languages.get(&step.record_id).cloned().unwrap_or_default(),
+                    _ => String::new(),
+                })
+                .collect());
+        }
+    }
+    rows
+}
+
+pub fn export_workcards_file(
+    input_path: &str,
+    output_path: &str,
+    format: WorkCardFormat,
+    template: &WorkCardTemplate,
+    source_path: Option<&str>,
+) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let output: Value = serde_json::from_str(&std::fs::read_to_string(input_path)?)?;
+    let text = read_source(&output, source_path)?;
+    let rows = workcard_rows(&output, text.as_deref(), template);
+    let watermark = output_watermark(&output);
+    match format {
+        // A comment line ahead of the header, as CSV readers that take comments skip it
+        WorkCardFormat::Csv => {
+            let mut content = watermark.as_ref().map(|watermark| format!("# {} {}\n", WATERMARK_MARKER, watermark)).unwrap_or_default();
+            content.extend(rows.iter().map(|row| csv_row(row)));
+            write_atomic(output_path, content)?
+        }
+        WorkCardFormat::Xlsx => {
+            let widths: Vec<f64> = template.columns.iter().map(|column| column.width.unwrap_or(DEFAULT_WIDTH)).collect();
+            write_atomic(output_path, write_workbook(&template.sheet, &rows, &widths, watermark.as_deref())?)?
+        }
+    }
+
+    let mut summary = HashMap::new();
+    summary.insert("rows".to_string(), (rows.len() - 1).to_string());
+    summary.insert("columns".to_string(), template.columns.len().to_string());
+    summary.insert("output_path".to_string(), output_path.to_string());
+    Ok(summary)
+}
+
+// Python binding - format is "csv" or "xlsx"; template_path names a JSON column template
+#[pyfunction]
+#[pyo3(signature = (input_path, output_path, format="csv", template_path=None, source_path=None))]
+pub fn export_workcards(
+    py: Python,
+    input_path: &str,
+    output_path: &str,
+    format: &str,
+    template_path: Option<&str>,
+    source_path: Option<&str>,
+) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+        let format = WorkCardFormat::parse(format).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let template = match template_path {
+            Some(path) => WorkCardTemplate::load(path).map_err(to_py_err)?,
+            None => WorkCardTemplate::default(),
+        };
+        export_workcards_file(input_path, output_path, format, &template, source_path).map_err(to_py_err)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::watermark::find_watermark;
+    use serde_json::json;
+
+    const TOKEN: &str = "spp-wm1:lic-1:0123abcd:feedbeef:1700000000:0011223344556677";
+
+    fn output() -> Value {
+        json!({
+            "provenance": {"watermark": TOKEN},
+            "payload": {
+                "modules": [{"record_id": "module_1", "pattern": "TASK 32-11-00", "original_start": "0"}],
+                "steps": [{"record_id": "step_1", "pattern": "Remove the panel.", "original_start": "14",
+                           "access_tags": "export-controlled", "language": "en"}],
+            },
+        })
+    }
+
+    fn template() -> WorkCardTemplate {
+        WorkCardTemplate {
+            sheet: "Cards".to_string(),
+            columns: vec![
+                WorkCardColumn::new("Step", "step", 40.0),
+                WorkCardColumn::new("Tags", "access_tags", 20.0),
+                WorkCardColumn::new("Language", "language", 8.0),
+            ],
+        }
+    }
+
+    #[test]
+    fn rows_carry_access_tags_and_language() {
+        assert!(template().validate().is_ok());
+        let rows = workcard_rows(&output(), None, &template());
+        assert_eq!(rows[1], ["Remove the panel.", "export-controlled", "en"]);
+    }
+
+    #[test]
+    fn workbooks_and_csv_are_watermarked() {
+        let dir = std::env::temp_dir().join(format!("spp-workcards-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("output.json");
+        std::fs::write(&input, output().to_string()).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        for (name, format) in [("cards.xlsx", WorkCardFormat::Xlsx), ("cards.csv", WorkCardFormat::Csv)] {
+            export_workcards_file(&path("output.json"), &path(name), format, &template(), None).unwrap();
+            let found = find_watermark(&path(name)).unwrap().unwrap();
+            assert_eq!((found.format, found.token.as_str()), (&name[6..], TOKEN));
+        }
+        // Same input, same workbook
+        let first = std::fs::read(path("cards.xlsx")).unwrap();
+        export_workcards_file(&path("output.json"), &path("cards.xlsx"), WorkCardFormat::Xlsx, &template(), None).unwrap();
+        assert_eq!(std::fs::read(path("cards.xlsx")).unwrap(), first);
+
+        // Workbooks without a watermark and other binary files have none, rather than failing
+        std::fs::write(&input, json!({"payload": output()["payload"]}).to_string()).unwrap();
+        export_workcards_file(&path("output.json"), &path("plain.xlsx"), WorkCardFormat::Xlsx, &template(), None).unwrap();
+        std::fs::write(path("image.bin"), [0x89u8, 0xff, 0xfe, 0x00]).unwrap();
+        assert!(find_watermark(&path("plain.xlsx")).unwrap().is_none());
+        assert!(find_watermark(&path("image.bin")).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
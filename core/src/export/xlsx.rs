@@ -0,0 +1,40 @@
+use rust_xlsxwriter::{DocProperties, ExcelDateTime, Format, FormatAlign, Workbook};
+
+use crate::security::watermark::WATERMARK_PROPERTY;
+
+// Longest sheet name Excel opens
+const MAX_SHEET_NAME: usize = 31;
+
+fn sheet_name(name: &str) -> String {
+    let cleaned: String = name.chars().filter(|c| !"[]:*?/\\'".contains(*c)).take(MAX_SHEET_NAME).collect();
+    if cleaned.trim().is_empty() { "Sheet1".to_string() } else { cleaned }
+}
+
+// A workbook with one sheet: a bold, frozen header row, then body text wrapped at the top of
+// its cell. widths are in characters, per column. The watermark goes in a custom document
+// property, where find_watermark reads it back
+pub fn write_workbook(sheet: &str, rows: &[Vec<String>], widths: &[f64], watermark: Option<&str>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut workbook = Workbook::new();
+    // A fixed creation time keeps identical inputs byte-identical
+    let mut properties = DocProperties::new().set_creation_datetime(&ExcelDateTime::from_ymd(1980, 1, 1)?);
+    if let Some(watermark) = watermark {
+        properties = properties.set_custom_property(WATERMARK_PROPERTY, watermark);
+    }
+    workbook.set_properties(&properties);
+
+    let header = Format::new().set_bold();
+    let body = Format::new().set_text_wrap().set_align(FormatAlign::Top);
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet_name(sheet))?;
+    worksheet.set_freeze_panes(1, 0)?;
+    for (column, width) in widths.iter().enumerate() {
+        worksheet.set_column_width(column as u16, *width)?;
+    }
+    for (row_index, row) in rows.iter().enumerate() {
+        let format = if row_index == 0 { &header } else { &body };
+        for (column, value) in row.iter().enumerate() {
+            worksheet.write_string_with_format(row_index as u32, column as u16, value, format)?;
+        }
+    }
+    Ok(workbook.save_to_buffer()?)
+}
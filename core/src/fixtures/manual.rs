@@ -0,0 +1,436 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::storage::atomic::write_atomic;
+
+// Item markers procedure steps can be numbered with. Top-level headings are numbered "1.",
+// so steps never continue their list
+pub const STEP_NUMBERING: [&str; 4] = ["(1)", "A.", "(a)", "-"];
+
+// ATA chapters the generator has a system name for
+const SYSTEMS: &[(u32, &str)] = &[
+    (21, "Air Conditioning"), (24, "Electrical Power"), (27, "Flight Controls"), (28, "Fuel"),
+    (29, "Hydraulic Power"), (32, "Landing Gear"), (36, "Pneumatic"), (49, "Auxiliary Power Unit"),
+];
+
+const COMPONENTS: &[&str] = &[
+    "Actuator", "Control Valve", "Pump", "Filter Element", "Pressure Sensor", "Check Valve",
+    "Heat Exchanger", "Shutoff Valve",
+];
+
+// Task kinds with the verbs of their steps
+const TASK_KINDS: &[(&str, &[&str])] = &[
+    ("Removal", &["Disconnect", "Remove", "Put caps on", "Discard", "Release"]),
+    ("Installation", &["Install", "Connect", "Torque", "Safety", "Lubricate"]),
+    ("Inspection", &["Examine", "Measure", "Clean", "Look for cracks on", "Do a leak check of"]),
+];
+
+const STEP_OBJECTS: &[&str] = &[
+    "electrical connector", "retaining bolts", "hydraulic line", "clamp", "bonding jumper", "seal",
+    "attach nuts", "mounting bracket", "drain line", "lockwire",
+];
+
+const SET_UP_ITEMS: &[&str] = &[
+    "Make sure that the aircraft is electrically grounded.",
+    "Put the aircraft on jacks.",
+    "Make sure that hydraulic power is off.",
+    "Put a DO-NOT-OPERATE tag on the flight deck controls.",
+];
+
+const CLOSE_OUT_ITEMS: &[&str] = &[
+    "Remove all tools and equipment from the work area.",
+    "Make sure that the work area is clean.",
+    "Remove the DO-NOT-OPERATE tag from the flight deck controls.",
+];
+
+const WARNINGS: &[&str] = &[
+    "WARNING: MAKE SURE THAT THE SYSTEM IS DEPRESSURIZED BEFORE YOU DISCONNECT THE LINES.",
+    "WARNING: OBEY THE SAFETY PRECAUTIONS FOR ELECTRICAL POWER.",
+    "CAUTION: PUT CAPS ON ALL OPEN LINES TO KEEP CONTAMINATION OUT.",
+    "CAUTION: DO NOT USE TOOLS THAT CAN CAUSE DAMAGE TO THE SEALS.",
+];
+
+// Steps above this can not be numbered with letters
+const MAX_LETTERED_STEPS: usize = 26;
+
+fn default_seed() -> u64 {
+    1
+}
+
+fn default_chapters() -> Vec<u32> {
+    vec![21, 29, 32]
+}
+
+fn default_tasks_per_chapter() -> usize {
+    3
+}
+
+fn default_min_steps() -> usize {
+    3
+}
+
+fn default_max_steps() -> usize {
+    8
+}
+
+fn default_numbering() -> Vec<String> {
+    vec!["(1)".to_string()]
+}
+
+fn default_warning_rate() -> f64 {
+    0.15
+}
+
+fn default_table_rate() -> f64 {
+    0.3
+}
+
+fn default_figure_rate() -> f64 {
+    0.3
+}
+
+fn default_lines_per_page() -> usize {
+    54
+}
+
+fn default_title() -> String {
+    "SYNTHETIC AIRCRAFT MAINTENANCE MANUAL".to_string()
+}
+
+// What a synthetic manual holds. The same settings and seed always give the same manual
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureConfig {
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+    // ATA chapter numbers, one chapter each
+    #[serde(default = "default_chapters")]
+    pub chapters: Vec<u32>,
+    #[serde(default = "default_tasks_per_chapter")]
+    pub tasks_per_chapter: usize,
+    #[serde(default = "default_min_steps")]
+    pub min_steps: usize,
+    #[serde(default = "default_max_steps")]
+    pub max_steps: usize,
+    // Step numbering styles of STEP_NUMBERING - each task uses one of them
+    #[serde(default = "default_numbering")]
+    pub numbering: Vec<String>,
+    // Share of steps with a WARNING or CAUTION before them
+    #[serde(default = "default_warning_rate")]
+    pub warning_rate: f64,
+    // Share of tasks with a torque limits table
+    #[serde(default = "default_table_rate")]
+    pub table_rate: f64,
+    // Share of tasks referring to a figure, captioned at the end of the task
+    #[serde(default = "default_figure_rate")]
+    pub figure_rate: f64,
+    // Lines before a form feed starts the next page
+    #[serde(default = "default_lines_per_page")]
+    pub lines_per_page: usize,
+    #[serde(default = "default_title")]
+    pub title: String,
+}
+
+impl Default for FixtureConfig {
+    fn default() -> Self {
+        Self {
+            seed: default_seed(),
+            chapters: default_chapters(),
+            tasks_per_chapter: default_tasks_per_chapter(),
+            min_steps: default_min_steps(),
+            max_steps: default_max_steps(),
+            numbering: default_numbering(),
+            warning_rate: default_warning_rate(),
+            table_rate: default_table_rate(),
+            figure_rate: default_figure_rate(),
+            lines_per_page: default_lines_per_page(),
+            title: default_title(),
+        }
+    }
+}
+
+impl FixtureConfig {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("Cannot read fixture config {}: {}", path, e))?;
+        let config: Self = serde_json::from_str(&content).map_err(|e| format!("Invalid fixture config {}: {}", path, e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.chapters.is_empty() {
+            return Err("chapters must list at least one ATA chapter".to_string());
+        }
+        if let Some(chapter) = self.chapters.iter().find(|chapter| !(1..=99).contains(*chapter)) {
+            return Err(format!("chapter {} is not an ATA chapter (1-99)", chapter));
+        }
+        if self.tasks_per_chapter == 0 || self.tasks_per_chapter > 90 {
+            return Err(format!("tasks_per_chapter must be 1-90, got {}", self.tasks_per_chapter));
+        }
+        if self.min_steps == 0 || self.min_steps > self.max_steps {
+            return Err(format!("steps must satisfy 1 <= min_steps <= max_steps, got {}-{}", self.min_steps, self.max_steps));
+        }
+        if self.numbering.is_empty() {
+            return Err("numbering must list at least one style".to_string());
+        }
+        for style in &self.numbering {
+            if !STEP_NUMBERING.contains(&style.as_str()) {
+                return Err(format!("Unknown numbering style: {} (expected {})", style, STEP_NUMBERING.join(", ")));
+            }
+            if matches!(style.as_str(), "A." | "(a)") && self.max_steps > MAX_LETTERED_STEPS {
+                return Err(format!("numbering {} allows at most {} steps, max_steps is {}", style, MAX_LETTERED_STEPS, self.max_steps));
+            }
+        }
+        for (name, rate) in [("warning_rate", self.warning_rate), ("table_rate", self.table_rate), ("figure_rate", self.figure_rate)] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(format!("{} must be between 0 and 1, got {}", name, rate));
+            }
+        }
+        if self.lines_per_page < 10 {
+            return Err(format!("lines_per_page must be at least 10, got {}", self.lines_per_page));
+        }
+        Ok(())
+    }
+}
+
+// SplitMix64 - small, seedable and the same on every platform, unlike a system RNG
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn between(&mut self, low: usize, high: usize) -> usize {
+        low + self.below(high - low + 1)
+    }
+
+    fn chance(&mut self, rate: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < rate
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+fn step_marker(style: &str, position: usize) -> String {
+    match style {
+        "(1)" => format!("({})", position),
+        "A." => format!("{}.", (b'A' + (position - 1) as u8) as char),
+        "(a)" => format!("({})", (b'a' + (position - 1) as u8) as char),
+        _ => "-".to_string(),
+    }
+}
+
+// A generated manual and what went into it - the ground truth to check an extraction against
+#[derive(Debug, Clone, Default)]
+pub struct SyntheticManual {
+    // Pages end with a form feed, as pdftotext writes them
+    pub text: String,
+    pub page_count: usize,
+    // Heading line of every task, in order
+    pub tasks: Vec<String>,
+    pub steps: usize,
+    pub warnings: usize,
+    pub tables: usize,
+    // Limit rows across the tables, one limits record each
+    pub table_rows: usize,
+    pub figures: usize,
+    // Each step's sentence, without its marker
+    step_texts: Vec<String>,
+    seed: u64,
+}
+
+impl SyntheticManual {
+    pub fn generate(config: &FixtureConfig) -> Result<Self, String> {
+        config.validate()?;
+        let mut rng = Rng(config.seed);
+        let mut manual = Self { seed: config.seed, ..Self::default() };
+        let mut lines = vec![config.title.clone(), String::new()];
+        let mut figure_number = 400;
+
+        for chapter in &config.chapters {
+            let system = SYSTEMS.iter().find(|(number, _)| number == chapter).map(|(_, name)| *name).unwrap_or("General");
+            lines.push(format!("CHAPTER {} - {}", chapter, system.to_uppercase()));
+            lines.push(String::new());
+            let first_component = rng.below(COMPONENTS.len());
+            for task in 0..config.tasks_per_chapter {
+                let (kind, verbs) = TASK_KINDS[task % TASK_KINDS.len()];
+                let component = COMPONENTS[(first_component + task / TASK_KINDS.len()) % COMPONENTS.len()];
+                let heading = format!("TASK {:02}-{:02}-{:02} {} of the {} {}", chapter, 10 + task / 10, task % 10 + 1, kind, system, component);
+                let figure = rng.chance(config.figure_rate).then(|| {
+                    figure_number += 1;
+                    figure_number
+                });
+                lines.push(heading.clone());
+                lines.push("1. General".to_string());
+                lines.push(format!("A. This task gives the procedure for the {} of the {}.", kind.to_lowercase(), component.to_lowercase()));
+                if let Some(figure) = figure {
+                    lines.push(format!("B. Refer to Figure {} for the location of the {}.", figure, component.to_lowercase()));
+                }
+                lines.push(format!("MAN-HOURS: {}.{}", rng.between(0, 4), rng.between(1, 9)));
+                lines.push("2. Job Set-Up".to_string());
+                let panel = format!("{}{}{}AL", rng.between(1, 8), rng.between(1, 9), rng.between(1, 9));
+                lines.push(format!("A. {}", rng.pick(SET_UP_ITEMS)));
+                lines.push(format!("B. Open access panel {}.", panel));
+                lines.push("3. Procedure".to_string());
+
+                let style = rng.pick(&config.numbering).clone();
+                let step_count = rng.between(config.min_steps, config.max_steps);
+                let table_after = rng.chance(config.table_rate).then(|| rng.between(1, step_count));
+                for position in 1..=step_count {
+                    if rng.chance(config.warning_rate) {
+                        lines.push(rng.pick(WARNINGS).to_string());
+                        manual.warnings += 1;
+                    }
+                    let sentence = format!("{} the {} {}.", rng.pick(verbs), component.to_lowercase(), rng.pick(STEP_OBJECTS));
+                    lines.push(format!("{} {}", step_marker(&style, position), sentence));
+                    manual.step_texts.push(sentence);
+                    if table_after == Some(position) {
+                        manual.table_rows += push_table(&mut lines, &mut rng, component);
+                        manual.tables += 1;
+                    }
+                }
+                manual.steps += step_count;
+
+                lines.push("4. Close-Out".to_string());
+                lines.push(format!("A. Close access panel {}.", panel));
+                lines.push(format!("B. {}", rng.pick(CLOSE_OUT_ITEMS)));
+                if let Some(figure) = figure {
+                    lines.push(format!("Figure {} {} {} Location", figure, system, component));
+                    manual.figures += 1;
+                }
+                lines.push(String::new());
+                manual.tasks.push(heading);
+            }
+        }
+
+        for (index, page) in lines.chunks(config.lines_per_page).enumerate() {
+            manual.text.push_str(&page.join("\n"));
+            manual.text.push_str("\n\x0c");
+            manual.page_count = index + 1;
+        }
+        Ok(manual)
+    }
+
+    pub fn pages(&self) -> Vec<&str> {
+        self.text.split_terminator('\x0c').collect()
+    }
+
+    // Bookmarks of the PDF: a chapter's heading, and each of its tasks under it
+    #[cfg(feature = "figures")]
+    pub fn outline(&self) -> Vec<crate::fixtures::pdf::OutlineEntry> {
+        let tasks: BTreeSet<&str> = self.tasks.iter().map(String::as_str).collect();
+        let mut outline = Vec::new();
+        for (page, text) in self.pages().iter().enumerate() {
+            for line in text.lines().map(str::trim) {
+                let level = if line.starts_with("CHAPTER ") { 1 } else if tasks.contains(line) { 2 } else { continue };
+                outline.push(crate::fixtures::pdf::OutlineEntry { level, title: line.to_string(), page });
+            }
+        }
+        outline
+    }
+
+    // A rules pack that finds every task as a module and every distinct step sentence as a step
+    pub fn rules(&self) -> Value {
+        let steps: BTreeSet<&str> = self.step_texts.iter().map(String::as_str).collect();
+        json!({
+            "name": format!("synthetic-{}", self.seed),
+            "version": "1",
+            "patterns": {"module": self.tasks, "step": steps},
+            "prompts": {},
+            "thresholds": {},
+        })
+    }
+
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let distinct_steps: BTreeSet<&str> = self.step_texts.iter().map(String::as_str).collect();
+        let mut summary = HashMap::new();
+        summary.insert("seed".to_string(), self.seed.to_string());
+        summary.insert("pages".to_string(), self.page_count.to_string());
+        summary.insert("tasks".to_string(), self.tasks.len().to_string());
+        summary.insert("steps".to_string(), self.steps.to_string());
+        summary.insert("distinct_steps".to_string(), distinct_steps.len().to_string());
+        summary.insert("warnings".to_string(), self.warnings.to_string());
+        summary.insert("tables".to_string(), self.tables.to_string());
+        summary.insert("table_rows".to_string(), self.table_rows.to_string());
+        summary.insert("figures".to_string(), self.figures.to_string());
+        summary.insert("bytes".to_string(), self.text.len().to_string());
+        summary
+    }
+}
+
+// A layout table of torque limits, as pdftotext -layout renders one. Returns its row count
+fn push_table(lines: &mut Vec<String>, rng: &mut Rng, component: &str) -> usize {
+    let fasteners = ["Bolt", "Nut", "Screw", "Union"];
+    let rows = rng.between(2, fasteners.len());
+    lines.push(String::new());
+    lines.push("        TORQUE VALUES".to_string());
+    lines.push(format!("        {:<30}{:<16}{}", "COMPONENT", "MIN (LBF-IN)", "MAX (LBF-IN)"));
+    for fastener in &fasteners[..rows] {
+        let minimum = rng.between(4, 40) * 5;
+        lines.push(format!("        {:<30}{:<16}{}", format!("{} {}", component, fastener), minimum, minimum + 10));
+    }
+    lines.push(String::new());
+    rows
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+}
+
+// Python binding - writes the manual's text layer, and optionally a PDF of it and a rules
+// pack that extracts it. The summary counts what the manual holds
+#[pyfunction]
+#[pyo3(signature = (output_path, config_path=None, seed=None, pdf_path=None, rules_path=None))]
+pub fn generate_manual(
+    py: Python,
+    output_path: &str,
+    config_path: Option<&str>,
+    seed: Option<u64>,
+    pdf_path: Option<&str>,
+    rules_path: Option<&str>,
+) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let mut config = match config_path {
+            Some(path) => FixtureConfig::load(path).map_err(to_py_err)?,
+            None => FixtureConfig::default(),
+        };
+        if let Some(seed) = seed {
+            config.seed = seed;
+        }
+        let manual = SyntheticManual::generate(&config).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        write_atomic(output_path, &manual.text)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(format!("Cannot write {}: {}", output_path, e)))?;
+        if let Some(pdf_path) = pdf_path {
+            write_pdf(&manual, &config.title, pdf_path)?;
+        }
+        if let Some(rules_path) = rules_path {
+            let rules = serde_json::to_string_pretty(&manual.rules()).map_err(|e| to_py_err(e.into()))?;
+            write_atomic(rules_path, rules + "\n")
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(format!("Cannot write {}: {}", rules_path, e)))?;
+        }
+        Ok(manual.get_summary())
+    })
+}
+
+#[cfg(feature = "figures")]
+fn write_pdf(manual: &SyntheticManual, title: &str, path: &str) -> PyResult<()> {
+    crate::fixtures::pdf::write_text_pdf(&manual.pages(), &manual.outline(), title, path)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(format!("Cannot write {}: {}", path, e)))
+}
+
+#[cfg(not(feature = "figures"))]
+fn write_pdf(_manual: &SyntheticManual, _title: &str, _path: &str) -> PyResult<()> {
+    Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("pdf_path needs a build with the figures feature"))
+}
@@ -0,0 +1,3 @@
+pub mod manual;
+#[cfg(feature = "figures")]
+pub mod pdf;
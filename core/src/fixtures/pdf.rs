@@ -0,0 +1,181 @@
+use crate::pdf::document::PdfObject;
+use crate::storage::atomic::write_atomic;
+
+// A4 in points, with the text block inset by the margin
+const PAGE_SIZE: (f64, f64) = (595.0, 842.0);
+const MARGIN: f64 = 36.0;
+const FONT_SIZE: f64 = 9.0;
+const LEADING: f64 = 11.0;
+
+// Literal string with its delimiters; text outside Latin-1 has no glyph in the base fonts
+fn literal(line: &str) -> Vec<u8> {
+    let mut raw = vec![b'('];
+    for c in line.chars() {
+        match c {
+            '(' | ')' | '\\' => raw.extend_from_slice(&[b'\\', c as u8]),
+            c if (c as u32) < 0x100 && !c.is_control() => raw.push(c as u32 as u8),
+            _ => raw.push(b'?'),
+        }
+    }
+    raw.push(b')');
+    raw
+}
+
+// One page's content: every line in Courier, so pdftotext -layout gives the columns back
+fn content_stream(page: &str) -> Vec<u8> {
+    let lines: Vec<&str> = page.lines().collect();
+    let leading = LEADING.min((PAGE_SIZE.1 - 2.0 * MARGIN) / lines.len().max(1) as f64);
+    let mut content = format!(
+        "BT\n/F1 {:.2} Tf\n{:.2} TL\n{:.2} {:.2} Td\n",
+        FONT_SIZE.min(leading * 0.85),
+        leading,
+        MARGIN,
+        PAGE_SIZE.1 - MARGIN - leading,
+    ).into_bytes();
+    for line in lines {
+        content.extend_from_slice(&literal(line));
+        content.extend_from_slice(b" Tj T*\n");
+    }
+    content.extend_from_slice(b"ET\n");
+    content
+}
+
+// A bookmark of a generated PDF: its level (1 for the top), title and 0-based page
+pub struct OutlineEntry {
+    pub level: usize,
+    pub title: String,
+    pub page: usize,
+}
+
+// The outline root and its items, numbered from `first`, every item open. Each item's parent
+// is the nearest earlier item of a lower level
+fn outline_objects(outline: &[OutlineEntry], first: usize, page_numbers: &[usize]) -> Vec<PdfObject> {
+    let reference = |number: usize| PdfObject::Reference(number as u32, 0);
+    let number_of = |index: usize| first + 1 + index;
+    let parents: Vec<Option<usize>> = (0..outline.len())
+        .map(|index| (0..index).rev().find(|&earlier| outline[earlier].level < outline[index].level))
+        .collect();
+    let children = |parent: Option<usize>| -> Vec<usize> { (0..outline.len()).filter(|&index| parents[index] == parent).collect() };
+    let descendants = |index: usize| (index + 1..outline.len()).take_while(|&later| outline[later].level > outline[index].level).count();
+    let links = |entries: &mut Vec<(String, PdfObject)>, kids: &[usize]| {
+        if let (Some(&head), Some(&tail)) = (kids.first(), kids.last()) {
+            entries.push(("First".to_string(), reference(number_of(head))));
+            entries.push(("Last".to_string(), reference(number_of(tail))));
+        }
+    };
+
+    let top = children(None);
+    let mut root = vec![
+        ("Type".to_string(), PdfObject::name("Outlines")),
+        ("Count".to_string(), PdfObject::Number(outline.len().to_string())),
+    ];
+    links(&mut root, &top);
+    let mut objects = vec![PdfObject::Dictionary(root)];
+    for (index, entry) in outline.iter().enumerate() {
+        let siblings = children(parents[index]);
+        let position = siblings.iter().position(|&sibling| sibling == index).unwrap_or_default();
+        let mut item = vec![
+            ("Title".to_string(), PdfObject::text(&entry.title)),
+            ("Parent".to_string(), reference(parents[index].map(number_of).unwrap_or(first))),
+            ("Dest".to_string(), PdfObject::Array(vec![reference(page_numbers[entry.page]), PdfObject::name("Fit")])),
+        ];
+        if position > 0 {
+            item.push(("Prev".to_string(), reference(number_of(siblings[position - 1]))));
+        }
+        if let Some(&next) = siblings.get(position + 1) {
+            item.push(("Next".to_string(), reference(number_of(next))));
+        }
+        let kids = children(Some(index));
+        if !kids.is_empty() {
+            links(&mut item, &kids);
+            item.push(("Count".to_string(), PdfObject::Number(descendants(index).to_string())));
+        }
+        objects.push(PdfObject::Dictionary(item));
+    }
+    objects
+}
+
+// A new PDF with one text page per page of `pages` and the bookmarks of `outline` - only for
+// documents this crate generates; existing PDFs are changed with PdfUpdate, which keeps their bytes
+pub fn write_text_pdf(pages: &[&str], outline: &[OutlineEntry], title: &str, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let reference = |number: usize| PdfObject::Reference(number as u32, 0);
+    // 1 catalog, 2 page tree, 3 font, 4 info, then a page and its content per page, then the
+    // outline when there is one
+    let page_numbers: Vec<usize> = (0..pages.len()).map(|index| 5 + 2 * index).collect();
+    let outline_root = 5 + 2 * pages.len();
+    let mut catalog = vec![
+        ("Type".to_string(), PdfObject::name("Catalog")),
+        ("Pages".to_string(), reference(2)),
+    ];
+    if !outline.is_empty() {
+        catalog.push(("Outlines".to_string(), reference(outline_root)));
+        catalog.push(("PageMode".to_string(), PdfObject::name("UseOutlines")));
+    }
+    let mut objects: Vec<(PdfObject, Option<Vec<u8>>)> = vec![
+        (PdfObject::Dictionary(catalog), None),
+        (PdfObject::Dictionary(vec![
+            ("Type".to_string(), PdfObject::name("Pages")),
+            ("Kids".to_string(), PdfObject::Array(page_numbers.iter().map(|number| reference(*number)).collect())),
+            ("Count".to_string(), PdfObject::Number(pages.len().to_string())),
+        ]), None),
+        (PdfObject::Dictionary(vec![
+            ("Type".to_string(), PdfObject::name("Font")),
+            ("Subtype".to_string(), PdfObject::name("Type1")),
+            ("BaseFont".to_string(), PdfObject::name("Courier")),
+            ("Encoding".to_string(), PdfObject::name("WinAnsiEncoding")),
+        ]), None),
+        (PdfObject::Dictionary(vec![
+            ("Title".to_string(), PdfObject::text(title)),
+            ("Producer".to_string(), PdfObject::text(&format!("ml_core {} fixtures", env!("CARGO_PKG_VERSION")))),
+        ]), None),
+    ];
+    for (index, page) in pages.iter().enumerate() {
+        let media_box = [0.0, 0.0, PAGE_SIZE.0, PAGE_SIZE.1].iter().map(|value| PdfObject::number(*value)).collect();
+        objects.push((PdfObject::Dictionary(vec![
+            ("Type".to_string(), PdfObject::name("Page")),
+            ("Parent".to_string(), reference(2)),
+            ("MediaBox".to_string(), PdfObject::Array(media_box)),
+            ("Resources".to_string(), PdfObject::Dictionary(vec![
+                ("Font".to_string(), PdfObject::Dictionary(vec![("F1".to_string(), reference(3))])),
+            ])),
+            ("Contents".to_string(), reference(page_numbers[index] + 1)),
+        ]), None));
+        let content = content_stream(page);
+        objects.push((PdfObject::Dictionary(vec![
+            ("Length".to_string(), PdfObject::Number(content.len().to_string())),
+        ]), Some(content)));
+    }
+
+    if !outline.is_empty() {
+        objects.extend(outline_objects(outline, outline_root, &page_numbers).into_iter().map(|object| (object, None)));
+    }
+
+    let mut output = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::new();
+    for (index, (object, stream)) in objects.iter().enumerate() {
+        offsets.push(output.len());
+        output.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        object.write(&mut output);
+        if let Some(stream) = stream {
+            output.extend_from_slice(b"\nstream\n");
+            output.extend_from_slice(stream);
+            output.extend_from_slice(b"endstream");
+        }
+        output.extend_from_slice(b"\nendobj\n");
+    }
+    let xref_offset = output.len();
+    output.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f\r\n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        output.extend_from_slice(format!("{:010} 00000 n\r\n", offset).as_bytes());
+    }
+    let trailer = PdfObject::Dictionary(vec![
+        ("Size".to_string(), PdfObject::Number((objects.len() + 1).to_string())),
+        ("Root".to_string(), reference(1)),
+        ("Info".to_string(), reference(4)),
+    ]);
+    output.extend_from_slice(b"trailer\n");
+    trailer.write(&mut output);
+    output.extend_from_slice(format!("\nstartxref\n{}\n%%EOF\n", xref_offset).as_bytes());
+    write_atomic(path, output)?;
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::scope::{scope_from_args, ExtractionScope};
+use crate::jobs::manifest::{canonical_output, reproducible_hash};
+use crate::jobs::policy::DocumentPolicy;
+use crate::jobs::runner::extract_payload;
+use crate::schema::envelope::{wrap_output, Provenance, UNLICENSED_TIER};
+
+// Values quoted in a divergence - enough to recognize the record
+const MAX_QUOTED_CHARS: usize = 200;
+
+// Where two canonical outputs first differ, in document order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    // JSON path, e.g. payload.steps[3].confidence
+    pub path: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+fn quote(value: Option<&Value>) -> String {
+    let text = match value {
+        Some(value) => value.to_string(),
+        None => "(missing)".to_string(),
+    };
+    match text.char_indices().nth(MAX_QUOTED_CHARS) {
+        Some((index, _)) => format!("{}...", &text[..index]),
+        None => text,
+    }
+}
+
+pub fn first_divergence(expected: &Value, actual: &Value, path: &str) -> Option<Divergence> {
+    let child = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            // Keys are sorted, so the first differing key is the same on every check
+            let mut keys: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter().find_map(|key| match (expected.get(key), actual.get(key)) {
+                (Some(e), Some(a)) => first_divergence(e, a, &child(key)),
+                (e, a) => Some(Divergence { path: child(key), expected: quote(e), actual: quote(a) }),
+            })
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            let common = expected.iter().zip(actual).enumerate()
+                .find_map(|(index, (e, a))| first_divergence(e, a, &format!("{}[{}]", path, index)));
+            common.or_else(|| {
+                (expected.len() != actual.len()).then(|| {
+                    let index = expected.len().min(actual.len());
+                    Divergence { path: format!("{}[{}]", path, index), expected: quote(expected.get(index)), actual: quote(actual.get(index)) }
+                })
+            })
+        }
+        _ if expected == actual => None,
+        _ => Some(Divergence { path: path.to_string(), expected: quote(Some(expected)), actual: quote(Some(actual)) }),
+    }
+}
+
+// One extraction of the checked input, on one of the run's threads
+#[derive(Debug, Clone)]
+pub struct DeterminismRun {
+    pub run: usize,
+    pub threads: usize,
+    pub hash: String,
+    pub divergence: Option<Divergence>,
+}
+
+impl DeterminismRun {
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("run".to_string(), self.run.to_string());
+        info.insert("threads".to_string(), self.threads.to_string());
+        info.insert("output_hash".to_string(), self.hash.clone());
+        info.insert("status".to_string(), if self.divergence.is_some() { "diverged" } else { "match" }.to_string());
+        if let Some(divergence) = &self.divergence {
+            info.insert("path".to_string(), divergence.path.clone());
+            info.insert("expected".to_string(), divergence.expected.clone());
+            info.insert("actual".to_string(), divergence.actual.clone());
+        }
+        info
+    }
+}
+
+fn extract_canonical(engine: &ExtractionEngine, input: &str, scope: &ExtractionScope) -> Result<Value, String> {
+    let (payload, text) = extract_payload(engine, "determinism", scope, input, DocumentPolicy::RulesOnly, None).map_err(|e| e.to_string())?;
+    let provenance = Provenance::new(engine, UNLICENSED_TIER, text.as_bytes());
+    Ok(canonical_output(&wrap_output(&provenance, payload)))
+}
+
+// Thread counts of the runs when none are given: serial, a pair, and every core
+pub fn default_thread_counts() -> Vec<usize> {
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut counts = vec![1, 2, cores];
+    counts.dedup();
+    counts
+}
+
+// Extracts `input` `runs` times, each run on the next of `thread_counts` threads at once, and
+// compares every output with the first. Each run loads the rules afresh, so hash-map ordering
+// and other per-instance state cannot hide behind a shared engine
+pub fn verify(input: &str, rules_path: &str, scope: &ExtractionScope, runs: usize, thread_counts: &[usize]) -> Result<Vec<DeterminismRun>, Box<dyn std::error::Error>> {
+    let mut baseline: Option<Value> = None;
+    let mut checked = Vec::new();
+    for run in 0..runs {
+        let threads = thread_counts[run % thread_counts.len()];
+        let engine = ExtractionEngine::from_rules_file(rules_path)?;
+        let outputs: Vec<Result<Value, String>> = std::thread::scope(|pool| {
+            let handles: Vec<_> = (0..threads).map(|_| pool.spawn(|| extract_canonical(&engine, input, scope))).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap_or_else(|_| Err("Extraction thread panicked".to_string()))).collect()
+        });
+        for output in outputs {
+            let output = output?;
+            let expected = baseline.get_or_insert_with(|| output.clone());
+            checked.push(DeterminismRun {
+                run: run + 1,
+                threads,
+                hash: reproducible_hash(&output)?,
+                divergence: first_divergence(expected, &output, ""),
+            });
+        }
+    }
+    Ok(checked)
+}
+
+type DeterminismResults = (HashMap<String, String>, Vec<HashMap<String, String>>);
+
+// Python binding - one entry per extraction; threads defaults to 1, 2 and the core count
+#[pyfunction]
+#[pyo3(signature = (input_path, rules_path, runs=5, threads=None, pages=None, sections=None))]
+pub fn verify_determinism(
+    py: Python,
+    input_path: &str,
+    rules_path: &str,
+    runs: usize,
+    threads: Option<Vec<usize>>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<DeterminismResults> {
+    let threads = threads.unwrap_or_else(default_thread_counts);
+    if runs < 2 || threads.is_empty() || threads.contains(&0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("runs must be at least 2 and every thread count at least 1"));
+    }
+    let scope = scope_from_args(pages, sections)?;
+    let checked = py.allow_threads(|| verify(input_path, rules_path, &scope, runs, &threads).map_err(|e| e.to_string()))
+        .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)?;
+
+    let diverged: Vec<&DeterminismRun> = checked.iter().filter(|run| run.divergence.is_some()).collect();
+    let mut summary = HashMap::new();
+    summary.insert("input".to_string(), input_path.to_string());
+    summary.insert("runs".to_string(), runs.to_string());
+    summary.insert("extractions".to_string(), checked.len().to_string());
+    summary.insert("diverged".to_string(), diverged.len().to_string());
+    summary.insert("deterministic".to_string(), diverged.is_empty().to_string());
+    if let Some(first) = checked.first() {
+        summary.insert("output_hash".to_string(), first.hash.clone());
+    }
+    if let Some(first) = diverged.first() {
+        summary.extend(first.get_info().into_iter().filter(|(key, _)| ["run", "threads", "path", "expected", "actual"].contains(&key.as_str())));
+    }
+    Ok((summary, checked.iter().map(DeterminismRun::get_info).collect()))
+}
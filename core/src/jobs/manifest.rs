@@ -0,0 +1,260 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::profile::profile_hash;
+use crate::engine::scope::ExtractionScope;
+use crate::jobs::policy::DocumentPolicy;
+use crate::jobs::runner::{extract_payload, output_path_for};
+use crate::jobs::store::Job;
+use crate::schema::envelope::{hash_input, wrap_output, Provenance, ENGINE_VERSION};
+use crate::schema::overflow::overflow_dir_for;
+use crate::security::doctor::compiled_features;
+use crate::storage::atomic::write_atomic;
+
+pub const MANIFEST_VERSION: u32 = 1;
+
+// Provenance fields that differ on every run by design - left out of output hashes
+const VOLATILE_PROVENANCE: [&str; 2] = ["processed_at", "watermark"];
+
+// One output a reproducible run wrote, with what went into it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub job_id: String,
+    pub input: String,
+    // Of the input file as read, before decoding
+    pub input_hash: String,
+    pub scope: ExtractionScope,
+    #[serde(default)]
+    pub policy: DocumentPolicy,
+    pub output: String,
+    pub output_hash: String,
+}
+
+// Everything a run's outputs depend on. Extraction has no randomness, so there is no seed:
+// the same inputs, rules, engine version and features give the same records
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub manifest_version: u32,
+    pub created_at: DateTime<Utc>,
+    pub engine_version: String,
+    pub features: Vec<String>,
+    pub rules_path: String,
+    pub rules_hash: String,
+    pub rules_version: String,
+    pub profile: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_format: Option<String>,
+    pub license_tier: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export_format: Option<String>,
+    // Full-policy outputs of an LLM-assisted run hold model answers, which replay cannot repeat
+    #[serde(default)]
+    pub llm_assisted: bool,
+    pub outputs: Vec<ManifestEntry>,
+}
+
+// An output with its volatile provenance removed - what two runs of the same input must agree on
+pub fn canonical_output(output: &Value) -> Value {
+    let mut output = output.clone();
+    if let Some(provenance) = output.get_mut("provenance").and_then(Value::as_object_mut) {
+        for field in VOLATILE_PROVENANCE {
+            provenance.remove(field);
+        }
+    }
+    output
+}
+
+// Hash of the canonical output, so a replay can match it
+pub fn reproducible_hash(output: &Value) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(hash_input(&serde_json::to_vec(&canonical_output(output))?))
+}
+
+pub(crate) fn file_hash(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let data = std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    Ok(hash_input(&data))
+}
+
+// Collects the outputs of a run across its workers
+pub struct ManifestRecorder {
+    manifest: Mutex<RunManifest>,
+}
+
+impl ManifestRecorder {
+    pub fn new(rules_path: &str, engine: &ExtractionEngine, license_tier: &str, export_format: Option<&str>, llm_assisted: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            manifest: Mutex::new(RunManifest {
+                manifest_version: MANIFEST_VERSION,
+                created_at: Utc::now(),
+                engine_version: ENGINE_VERSION.to_string(),
+                features: compiled_features().into_iter().map(str::to_string).collect(),
+                rules_path: rules_path.to_string(),
+                rules_hash: profile_hash(rules_path)?,
+                rules_version: engine.get_rules_version().to_string(),
+                profile: engine.get_profile_name().to_string(),
+                id_namespace: engine.get_id_namespace().name(),
+                id_format: engine.get_id_namespace().format_name(),
+                license_tier: license_tier.to_string(),
+                export_format: export_format.map(str::to_string),
+                llm_assisted,
+                outputs: Vec::new(),
+            }),
+        })
+    }
+
+    pub fn record(&self, job: &Job, input: &str, output_path: &Path, output: &Value) -> Result<(), Box<dyn std::error::Error>> {
+        let entry = ManifestEntry {
+            job_id: job.job_id.clone(),
+            input: input.to_string(),
+            input_hash: file_hash(input)?,
+            scope: job.scope.clone(),
+            policy: job.policy_for(input),
+            output: output_path.to_string_lossy().to_string(),
+            output_hash: reproducible_hash(output)?,
+        };
+        self.manifest.lock().map_err(|_| "Manifest lock poisoned")?.outputs.push(entry);
+        Ok(())
+    }
+
+    // Workers finish in any order; outputs are listed by job, inputs in job order
+    pub fn write(&self, manifest_path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut manifest = self.manifest.lock().map_err(|_| "Manifest lock poisoned")?.clone();
+        manifest.outputs.sort_by(|a, b| a.job_id.cmp(&b.job_id));
+        write_atomic(manifest_path, serde_json::to_string_pretty(&manifest)? + "\n")
+            .map_err(|e| format!("Cannot write manifest {}: {}", manifest_path, e))?;
+        Ok(manifest.outputs.len())
+    }
+}
+
+// How one manifest output fared on replay
+pub struct ReplayCheck {
+    pub job_id: String,
+    pub input: String,
+    // "match", "mismatch" or "error"
+    pub status: &'static str,
+    pub expected_hash: String,
+    pub actual_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+impl ReplayCheck {
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("job_id".to_string(), self.job_id.clone());
+        info.insert("input".to_string(), self.input.clone());
+        info.insert("status".to_string(), self.status.to_string());
+        info.insert("expected_hash".to_string(), self.expected_hash.clone());
+        if let Some(actual_hash) = &self.actual_hash {
+            info.insert("actual_hash".to_string(), actual_hash.clone());
+        }
+        if let Some(error) = &self.error {
+            info.insert("error".to_string(), error.clone());
+        }
+        info
+    }
+}
+
+// Differences between the manifest's environment and this one. Any of them can change outputs,
+// so they are reported even when every hash still matches
+fn environment_problems(manifest: &RunManifest, rules_path: &str, engine: &ExtractionEngine) -> Vec<String> {
+    let mut problems = Vec::new();
+    if manifest.engine_version != ENGINE_VERSION {
+        problems.push(format!("engine version is {}, the run used {}", ENGINE_VERSION, manifest.engine_version));
+    }
+    let features: Vec<String> = compiled_features().into_iter().map(str::to_string).collect();
+    if features != manifest.features {
+        problems.push(format!("features are [{}], the run used [{}]", features.join(", "), manifest.features.join(", ")));
+    }
+    match profile_hash(rules_path) {
+        Ok(hash) if hash != manifest.rules_hash => problems.push(format!("rules file {} has changed since the run", rules_path)),
+        Ok(_) => {}
+        Err(e) => problems.push(e.to_string()),
+    }
+    if engine.get_rules_version() != manifest.rules_version {
+        problems.push(format!("rules version is {}, the run used {}", engine.get_rules_version(), manifest.rules_version));
+    }
+    if manifest.llm_assisted && manifest.outputs.iter().any(|entry| entry.policy == DocumentPolicy::Full) {
+        problems.push("the run was LLM-assisted; replay re-runs the rules only, so full-policy outputs differ".to_string());
+    }
+    problems
+}
+
+fn replay_entry(engine: &ExtractionEngine, manifest: &RunManifest, entry: &ManifestEntry) -> Result<String, Box<dyn std::error::Error>> {
+    let input_hash = file_hash(&entry.input)?;
+    if input_hash != entry.input_hash {
+        return Err(format!("{} has changed since the run", entry.input).into());
+    }
+    let (mut payload, text) = extract_payload(engine, &entry.job_id, &entry.scope, &entry.input, entry.policy, None)?;
+    let overflow_dir = overflow_dir_for(&output_path_for("", &entry.input));
+    engine.get_truncation().truncate(&mut payload, &overflow_dir, engine.get_delivery());
+    // The recorded tier stands in for the license: its watermark is volatile anyway
+    let provenance = Provenance::new(engine, &manifest.license_tier, text.as_bytes());
+    reproducible_hash(&wrap_output(&provenance, payload))
+}
+
+// Re-runs every output of a manifest and compares hashes; nothing is written
+pub fn replay(manifest: &RunManifest, rules_path: Option<&str>) -> Result<(Vec<String>, Vec<ReplayCheck>), Box<dyn std::error::Error>> {
+    let rules_path = rules_path.unwrap_or(&manifest.rules_path);
+    let engine = ExtractionEngine::from_rules_file(rules_path)?;
+    let problems = environment_problems(manifest, rules_path, &engine);
+    let checks = manifest.outputs.iter()
+        .map(|entry| {
+            let (status, actual_hash, error) = match replay_entry(&engine, manifest, entry) {
+                Ok(hash) if hash == entry.output_hash => ("match", Some(hash), None),
+                Ok(hash) => ("mismatch", Some(hash), None),
+                Err(e) => ("error", None, Some(e.to_string())),
+            };
+            ReplayCheck {
+                job_id: entry.job_id.clone(),
+                input: entry.input.clone(),
+                status,
+                expected_hash: entry.output_hash.clone(),
+                actual_hash,
+                error,
+            }
+        })
+        .collect();
+    Ok((problems, checks))
+}
+
+pub fn load_manifest(manifest_path: &str) -> Result<RunManifest, Box<dyn std::error::Error>> {
+    let data = std::fs::read(manifest_path).map_err(|e| format!("Cannot read manifest {}: {}", manifest_path, e))?;
+    let manifest: RunManifest = serde_json::from_slice(&data).map_err(|e| format!("Invalid manifest {}: {}", manifest_path, e))?;
+    if manifest.manifest_version > MANIFEST_VERSION {
+        return Err(format!("Manifest version {} is newer than this engine supports ({})", manifest.manifest_version, MANIFEST_VERSION).into());
+    }
+    Ok(manifest)
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+}
+
+type ReplayResults = (HashMap<String, String>, Vec<HashMap<String, String>>);
+
+// Python binding - rules_path replaces the manifest's when the rules file has moved
+#[pyfunction]
+#[pyo3(signature = (manifest_path, rules_path=None))]
+pub fn replay_manifest(py: Python, manifest_path: &str, rules_path: Option<&str>) -> PyResult<ReplayResults> {
+    let manifest = load_manifest(manifest_path).map_err(to_py_err)?;
+    let (problems, checks) = py.allow_threads(|| replay(&manifest, rules_path).map_err(|e| e.to_string()))
+        .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)?;
+    let count = |status: &str| checks.iter().filter(|check| check.status == status).count();
+
+    let mut summary = HashMap::new();
+    summary.insert("outputs".to_string(), checks.len().to_string());
+    summary.insert("matched".to_string(), count("match").to_string());
+    summary.insert("mismatched".to_string(), count("mismatch").to_string());
+    summary.insert("errors".to_string(), count("error").to_string());
+    summary.insert("environment_problems".to_string(), problems.join("; "));
+    let reproduced = problems.is_empty() && count("match") == checks.len();
+    summary.insert("reproduced".to_string(), reproduced.to_string());
+    Ok((summary, checks.iter().map(ReplayCheck::get_info).collect()))
+}
@@ -0,0 +1,13 @@
+pub mod store;
+pub mod runner;
+pub mod preflight;
+pub mod taxonomy;
+pub mod profiles;
+pub mod policy;
+pub mod manifest;
+pub mod usage;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod quality;
+pub mod determinism;
+pub mod snapshot;
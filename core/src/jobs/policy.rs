@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+// How deep one document of a job is processed, so a run can mix scans, low-value documents
+// and fully extracted manuals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DocumentPolicy {
+    // No output at all
+    Skip,
+    // The text layer only, as read or converted (e.g. OCR'd) - no extraction
+    OcrOnly,
+    // Rule-based extraction, never sent to an LLM
+    RulesOnly,
+    // Rule-based extraction, plus LLM assistance when the run has an LLM config
+    #[default]
+    Full,
+}
+
+pub const POLICY_NAMES: [&str; 4] = ["skip", "ocr-only", "rules-only", "full"];
+
+impl DocumentPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentPolicy::Skip => "skip",
+            DocumentPolicy::OcrOnly => "ocr-only",
+            DocumentPolicy::RulesOnly => "rules-only",
+            DocumentPolicy::Full => "full",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "skip" => Ok(DocumentPolicy::Skip),
+            "ocr-only" => Ok(DocumentPolicy::OcrOnly),
+            "rules-only" => Ok(DocumentPolicy::RulesOnly),
+            "full" => Ok(DocumentPolicy::Full),
+            other => Err(format!("Unknown document policy: {} (expected {})", other, POLICY_NAMES.join(", "))),
+        }
+    }
+
+    pub fn extracts(&self) -> bool {
+        matches!(self, DocumentPolicy::RulesOnly | DocumentPolicy::Full)
+    }
+}
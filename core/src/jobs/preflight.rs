@@ -0,0 +1,319 @@
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::engine::completeness::DecodedText;
+use crate::engine::convert::ConvertedText;
+use crate::engine::encoding::DetectedEncoding;
+use crate::engine::extractor::ExtractionEngine;
+use crate::jobs::quality::{score_document, QualityScore};
+use crate::jobs::runner::output_path_for;
+use crate::jobs::store::{JobStatus, JobStore};
+use crate::licensing::manager::License;
+#[cfg(feature = "figures")]
+use crate::pdf::text::read_text_layer;
+
+// Text layers this large without form feeds are usually a lost page structure
+const UNPAGED_WARNING_BYTES: usize = 100_000;
+
+// Pre-flight result for one input - nothing is extracted or written
+#[derive(Debug, Clone, Default)]
+pub struct InputCheck {
+    pub path: String,
+    pub job_id: Option<String>,
+    pub bytes: usize,
+    pub pages: usize,
+    pub encoding: Option<DetectedEncoding>,
+    // Set once the input could be read, so it can be routed before extraction
+    pub quality: Option<QualityScore>,
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl InputCheck {
+    pub fn status(&self) -> &'static str {
+        match (&self.error, self.warnings.is_empty()) {
+            (Some(_), _) => "error",
+            (None, false) => "warning",
+            (None, true) => "ok",
+        }
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("path".to_string(), self.path.clone());
+        info.insert("status".to_string(), self.status().to_string());
+        info.insert("bytes".to_string(), self.bytes.to_string());
+        info.insert("pages".to_string(), self.pages.to_string());
+        if let Some(encoding) = &self.encoding {
+            info.extend(encoding.get_info());
+        }
+        if let Some(quality) = &self.quality {
+            info.extend(quality.get_info());
+        }
+        info.insert("warnings".to_string(), self.warnings.join("; "));
+        if let Some(job_id) = &self.job_id {
+            info.insert("job_id".to_string(), job_id.clone());
+        }
+        if let Some(error) = &self.error {
+            info.insert("error".to_string(), error.clone());
+        }
+        info
+    }
+}
+
+#[cfg(feature = "figures")]
+fn native_text(path: &str, bytes: &[u8]) -> Option<Result<ConvertedText, Box<dyn std::error::Error>>> {
+    bytes.starts_with(b"%PDF-").then(|| read_text_layer(path))
+}
+
+#[cfg(not(feature = "figures"))]
+fn native_text(_path: &str, _bytes: &[u8]) -> Option<Result<ConvertedText, Box<dyn std::error::Error>>> {
+    None
+}
+
+// Reads the input exactly as a job would, then looks for what would make its output useless
+pub fn check_input(path: &str, engine: Option<&ExtractionEngine>) -> InputCheck {
+    let mut check = InputCheck { path: path.to_string(), ..InputCheck::default() };
+    let input = Path::new(path);
+    if !input.exists() {
+        check.error = Some("File not found".to_string());
+        return check;
+    }
+    if input.is_dir() {
+        check.error = Some("Path is a directory".to_string());
+        return check;
+    }
+    let bytes = match std::fs::read(input) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            check.error = Some(format!("Cannot read file: {}", e));
+            return check;
+        }
+    };
+    check.bytes = bytes.len();
+    // A configured converter runs here exactly as it would in the job, and with no engine a PDF's
+    // text layer is read natively as the default converters would
+    let converted = match engine {
+        Some(engine) => engine.get_converters().convert(path),
+        None => native_text(path, &bytes),
+    };
+    let DecodedText { text, report } = match converted {
+        Some(Ok(converted)) => {
+            let mut decoded = DecodedText::decode(&converted.bytes);
+            decoded.report.warnings = converted.warnings;
+            decoded.report.ocr = converted.ocr;
+            decoded
+        }
+        Some(Err(e)) => {
+            check.error = Some(format!("Converter failed: {}", e));
+            return check;
+        }
+        None if bytes.starts_with(b"%PDF-") => {
+            check.quality = Some(QualityScore::without_text_layer());
+            check.error = Some("Binary PDF - extract its text layer first (e.g. pdftotext -layout) or configure a pdf converter".to_string());
+            return check;
+        }
+        None => DecodedText::decode(&bytes),
+    };
+
+    check.pages = report.page_count;
+    check.encoding = report.encoding;
+    let quality = score_document(&text, &report, report.ocr.is_some());
+    // Empty documents already say so below
+    if quality.needs_manual_handling() && !text.trim().is_empty() {
+        check.warnings.push(format!("Quality grade {} - route to manual handling ({})", quality.grade(), quality.reasons.join("; ")));
+    }
+    check.quality = Some(quality);
+    if let Some(encoding) = report.encoding.filter(DetectedEncoding::is_guess) {
+        check.warnings.push(format!("No byte order mark - read as {}", encoding.encoding.name()));
+    }
+    for warning in &report.warnings {
+        check.warnings.push(format!("{}: {}", warning.tool, warning.message));
+    }
+    if text.trim().is_empty() {
+        check.warnings.push("Document is empty".to_string());
+        return check;
+    }
+    let budget = engine.map(|engine| engine.get_error_budget().clone()).unwrap_or_default();
+    if let Err(e) = budget.check(&report) {
+        check.error = Some(e);
+        return check;
+    }
+    for failure in &report.failures {
+        check.warnings.push(format!("Page {} will be skipped ({}): {}", failure.page, failure.reason, failure.detail));
+    }
+    if text.contains('\0') {
+        check.warnings.push("Contains NUL bytes - the text layer may be damaged".to_string());
+    }
+    if check.pages == 1 && text.len() > UNPAGED_WARNING_BYTES {
+        check.warnings.push("No page breaks - pages will not be numbered or split".to_string());
+    }
+    if let Some(engine) = engine {
+        if engine.extract_modules(&text).is_empty() {
+            check.warnings.push("No module matches - the output would have no procedures".to_string());
+        } else if engine.extract_steps(&text).is_empty() {
+            check.warnings.push("No step matches".to_string());
+        }
+    }
+    check
+}
+
+// Inputs a run would write to the same output file, since outputs are named after the input
+// stem. Jobs refuse distinct inputs that collide (see output_collision), so those are errors
+fn flag_collisions(checks: &mut [InputCheck]) {
+    let mut seen: HashMap<(Option<String>, std::path::PathBuf), String> = HashMap::new();
+    for check in checks.iter_mut() {
+        let key = (check.job_id.clone(), output_path_for("", &check.path));
+        match seen.get(&key) {
+            Some(first) if *first == check.path => check.warnings.push("Listed more than once".to_string()),
+            Some(first) => {
+                let collision = format!("Output would overwrite the output of {}; the job would be refused", first);
+                check.error.get_or_insert(collision);
+            }
+            None => {
+                seen.insert(key, check.path.clone());
+            }
+        }
+    }
+}
+
+// Why a license would be refused, or None when it is usable
+pub fn license_problem(license_path: &str) -> Option<String> {
+    let license = match License::load_signed(license_path) {
+        Ok(license) => license,
+        Err(e) => return Some(e.to_string()),
+    };
+    if license.is_deactivated() {
+        return Some("License has been deactivated".to_string());
+    }
+    if !license.is_bound_here() {
+        return Some("License is bound to another machine".to_string());
+    }
+    if license.days_remaining() <= 0 {
+        return Some("License has expired".to_string());
+    }
+    if !license.is_valid() {
+        return Some("License validation failed".to_string());
+    }
+    None
+}
+
+pub struct PreflightReport {
+    pub license_problem: Option<Option<String>>,
+    pub inputs: Vec<InputCheck>,
+}
+
+impl PreflightReport {
+    pub fn new(mut inputs: Vec<InputCheck>, license_path: Option<&str>) -> Self {
+        flag_collisions(&mut inputs);
+        Self { license_problem: license_path.map(license_problem), inputs }
+    }
+
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let count = |status: &str| self.inputs.iter().filter(|check| check.status() == status).count();
+        let errors = count("error");
+        let license_ok = !matches!(self.license_problem, Some(Some(_)));
+
+        let mut summary = HashMap::new();
+        summary.insert("files".to_string(), self.inputs.len().to_string());
+        summary.insert("ok".to_string(), count("ok").to_string());
+        summary.insert("warnings".to_string(), count("warning").to_string());
+        summary.insert("errors".to_string(), errors.to_string());
+        summary.insert("bytes".to_string(), self.inputs.iter().map(|check| check.bytes).sum::<usize>().to_string());
+        let manual = self.inputs.iter().filter(|check| check.quality.as_ref().is_some_and(QualityScore::needs_manual_handling)).count();
+        summary.insert("manual".to_string(), manual.to_string());
+        if let Some(problem) = &self.license_problem {
+            summary.insert("license_valid".to_string(), problem.is_none().to_string());
+            if let Some(problem) = problem {
+                summary.insert("license_error".to_string(), problem.clone());
+            }
+        }
+        summary.insert("ready".to_string(), (errors == 0 && license_ok).to_string());
+        summary
+    }
+
+    pub fn get_results(&self) -> (HashMap<String, String>, Vec<HashMap<String, String>>) {
+        (self.get_summary(), self.inputs.iter().map(InputCheck::get_info).collect())
+    }
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+}
+
+fn load_engine(rules_path: Option<&str>) -> PyResult<Option<ExtractionEngine>> {
+    rules_path.map(ExtractionEngine::from_rules_file).transpose().map_err(to_py_err)
+}
+
+type PreflightResults = (HashMap<String, String>, Vec<HashMap<String, String>>);
+
+// Python bindings - dry runs; with rules_path the rules are also tried against each input
+#[pyfunction]
+#[pyo3(signature = (paths, rules_path=None, license_path=None))]
+pub fn validate_inputs(py: Python, paths: Vec<String>, rules_path: Option<&str>, license_path: Option<&str>) -> PyResult<PreflightResults> {
+    let engine = load_engine(rules_path)?;
+    let checks = py.allow_threads(|| paths.iter().map(|path| check_input(path, engine.as_ref())).collect());
+    Ok(PreflightReport::new(checks, license_path).get_results())
+}
+
+// Every input of every queued job, without claiming any of them
+#[pyfunction]
+#[pyo3(signature = (db_path, rules_path=None, license_path=None))]
+pub fn validate_jobs(py: Python, db_path: &str, rules_path: Option<&str>, license_path: Option<&str>) -> PyResult<PreflightResults> {
+    let engine = load_engine(rules_path)?;
+    let store = JobStore::open(db_path).map_err(to_py_err)?;
+    let jobs = store.list(Some(JobStatus::Queued), u32::MAX).map_err(to_py_err)?;
+    let checks = py.allow_threads(|| {
+        jobs.iter()
+            .flat_map(|job| job.inputs.iter().map(move |input| (job, input)))
+            .map(|(job, input)| InputCheck { job_id: Some(job.job_id.clone()), ..check_input(input, engine.as_ref()) })
+            .collect()
+    });
+    Ok(PreflightReport::new(checks, license_path).get_results())
+}
+
+// Why a run under this license would be refused, or None when it is usable
+#[pyfunction]
+pub fn check_license(license_path: &str) -> Option<String> {
+    license_problem(license_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_text(name: &str, text: &str) -> InputCheck {
+        let path = std::env::temp_dir().join(format!("spp-preflight-{}-{}.txt", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        let check = check_input(&path.to_string_lossy(), None);
+        std::fs::remove_file(&path).unwrap();
+        check
+    }
+
+    #[test]
+    fn empty_and_form_feed_ended_inputs() {
+        let empty = check_text("empty", "");
+        assert!(empty.error.is_none());
+        assert!(empty.warnings.iter().any(|warning| warning == "Document is empty"));
+        let blank = check_text("blank", "\x0c");
+        assert!(blank.warnings.iter().any(|warning| warning == "Document is empty"));
+        let paged = check_text("paged", "TASK 21-10-00 Removal\n(1) Remove the panel.\n\x0c");
+        assert!(paged.error.is_none());
+        assert_eq!(paged.pages, 1);
+    }
+
+    // With no rules, a PDF is still read through its text layer rather than refused
+    #[cfg(feature = "figures")]
+    #[test]
+    fn pdf_without_an_engine() {
+        let path = std::env::temp_dir().join(format!("spp-preflight-{}.pdf", std::process::id()));
+        let pages = ["TASK 21-10-00 Removal\n(1) Remove the panel.", "(2) Disconnect the duct."];
+        crate::fixtures::pdf::write_text_pdf(&pages, &[], "Preflight", &path.to_string_lossy()).unwrap();
+        let check = check_input(&path.to_string_lossy(), None);
+        std::fs::remove_file(&path).unwrap();
+        assert!(check.error.is_none(), "{:?}", check.error);
+        assert_eq!(check.pages, 2);
+        assert!(!check.warnings.iter().any(|warning| warning == "Document is empty"));
+    }
+}
@@ -0,0 +1,310 @@
+use pyo3::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::engine::scope::{parse_page_range, ExtractionScope};
+use crate::export::registry::{check_export_format, export_formats, JSON_FORMAT};
+use crate::jobs::policy::DocumentPolicy;
+
+// Keys a [defaults] or [profiles.<name>] table may set. Path settings are relative to the
+// file that sets them; SPP_<KEY> environment variables override any of them
+const PATH_KEYS: [&str; 7] = ["output_dir", "rules", "db", "license", "index", "llm", "webhooks"];
+const NUMBER_KEYS: [&str; 3] = ["concurrency", "priority", "max_jobs"];
+const SCOPE_KEYS: [&str; 2] = ["pages", "sections"];
+
+// One run's settings - every field is optional so layers can be stacked
+#[derive(Debug, Clone, Default)]
+pub struct RunProfile {
+    // File paths or glob patterns of the inputs to submit
+    pub inputs: Option<Vec<String>>,
+    pub output_dir: Option<String>,
+    // "json" (outputs only) or an export format written next to each output
+    pub format: Option<String>,
+    pub concurrency: Option<usize>,
+    // Extraction profile - the rules file
+    pub rules: Option<String>,
+    pub db: Option<String>,
+    pub license: Option<String>,
+    pub index: Option<String>,
+    // LLM config that full-policy inputs are assisted with
+    pub llm: Option<String>,
+    // JSON list of webhooks told of the run's outputs and jobs
+    pub webhooks: Option<String>,
+    pub priority: Option<i64>,
+    pub max_jobs: Option<usize>,
+    // Scope of the jobs submitted from the inputs: "120-180" and chapters or sections
+    pub pages: Option<(usize, usize)>,
+    pub sections: Option<Vec<String>>,
+    // Input pattern and policy pairs, in file order - an input takes the policy of the first
+    // pattern it matches, and inputs matching none are processed in full
+    pub policies: Option<Vec<(String, String)>>,
+}
+
+fn resolve_path(base_dir: &Path, value: &str) -> String {
+    let path = Path::new(value);
+    if path.is_absolute() {
+        value.to_string()
+    } else {
+        base_dir.join(path).to_string_lossy().to_string()
+    }
+}
+
+// "scans/*.pdf=ocr-only,archive/*=skip", as SPP_POLICIES gives it
+fn parse_policies(value: &str) -> Result<Vec<(String, String)>, String> {
+    value.split(',').map(str::trim).filter(|v| !v.is_empty())
+        .map(|pair| {
+            let (pattern, policy) = pair.rsplit_once('=')
+                .ok_or_else(|| format!("policies must be pattern=policy pairs, got {:?}", pair))?;
+            DocumentPolicy::parse(policy.trim())?;
+            Ok((pattern.trim().to_string(), policy.trim().to_string()))
+        })
+        .collect()
+}
+
+fn parse_count(key: &str, value: i64) -> Result<usize, String> {
+    if value < 1 {
+        return Err(format!("{} must be at least 1, got {}", key, value));
+    }
+    Ok(value as usize)
+}
+
+impl RunProfile {
+    fn set(&mut self, key: &str, value: String) -> Result<(), String> {
+        let number = || value.trim().parse::<i64>().map_err(|_| format!("{} must be an integer, got {:?}", key, value));
+        match key {
+            "inputs" => {
+                self.inputs = Some(value.split(',').map(str::trim).filter(|v| !v.is_empty()).map(str::to_string).collect());
+            }
+            "output_dir" => self.output_dir = Some(value),
+            "format" => self.format = Some(value),
+            "rules" => self.rules = Some(value),
+            "db" => self.db = Some(value),
+            "license" => self.license = Some(value),
+            "index" => self.index = Some(value),
+            "llm" => self.llm = Some(value),
+            "webhooks" => self.webhooks = Some(value),
+            "concurrency" => self.concurrency = Some(parse_count(key, number()?)?),
+            "priority" => self.priority = Some(number()?),
+            "max_jobs" => self.max_jobs = Some(parse_count(key, number()?)?),
+            "pages" => self.pages = Some(parse_page_range(&value)?),
+            "sections" => {
+                self.sections = Some(value.split(',').map(str::trim).filter(|v| !v.is_empty()).map(str::to_string).collect());
+            }
+            "policies" => self.policies = Some(parse_policies(&value)?),
+            other => return Err(format!("unknown setting {}", other)),
+        }
+        Ok(())
+    }
+
+    fn from_table(table: &Table, base_dir: &Path, context: &str) -> Result<Self, String> {
+        let mut profile = Self::default();
+        for (key, item) in table.iter() {
+            let error = |expected: &str| format!("{}.{} must be {}", context, key, expected);
+            if key != "inputs" && key != "format" && key != "policies" && !PATH_KEYS.contains(&key)
+                && !NUMBER_KEYS.contains(&key) && !SCOPE_KEYS.contains(&key)
+            {
+                return Err(format!("{}: unknown setting {}", context, key));
+            }
+            if key == "inputs" {
+                let patterns = item.as_array().ok_or_else(|| error("an array of strings"))?
+                    .iter()
+                    .map(|value| value.as_str().map(|pattern| resolve_path(base_dir, pattern)).ok_or_else(|| error("an array of strings")))
+                    .collect::<Result<Vec<_>, _>>()?;
+                profile.inputs = Some(patterns);
+            } else if key == "sections" {
+                let sections = item.as_array().ok_or_else(|| error("an array of strings"))?
+                    .iter()
+                    .map(|value| value.as_str().map(str::to_string).ok_or_else(|| error("an array of strings")))
+                    .collect::<Result<Vec<_>, _>>()?;
+                profile.sections = Some(sections);
+            } else if key == "policies" {
+                let policies = item.as_table_like().ok_or_else(|| error("a table of pattern = policy"))?
+                    .iter()
+                    .map(|(pattern, policy)| {
+                        let policy = policy.as_str().ok_or_else(|| error("a table of pattern = policy"))?;
+                        DocumentPolicy::parse(policy).map_err(|e| format!("{}.{}: {}", context, key, e))?;
+                        Ok((resolve_path(base_dir, pattern), policy.to_string()))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                profile.policies = Some(policies);
+            } else if NUMBER_KEYS.contains(&key) {
+                let value = item.as_integer().ok_or_else(|| error("an integer"))?;
+                profile.set(key, value.to_string()).map_err(|e| format!("{}.{}", context, e))?;
+            } else {
+                let value = item.as_str().ok_or_else(|| error("a string"))?;
+                let value = if PATH_KEYS.contains(&key) { resolve_path(base_dir, value) } else { value.to_string() };
+                profile.set(key, value).map_err(|e| format!("{}: {}", context, e))?;
+            }
+        }
+        Ok(profile)
+    }
+
+    // SPP_INPUTS (comma-separated), SPP_OUTPUT_DIR, SPP_FORMAT, SPP_CONCURRENCY, SPP_RULES, ...
+    pub fn from_env() -> Result<Self, String> {
+        let mut profile = Self::default();
+        for key in ["inputs", "format", "policies"].iter().chain(PATH_KEYS.iter()).chain(NUMBER_KEYS.iter()).chain(SCOPE_KEYS.iter()) {
+            let variable = format!("SPP_{}", key.to_uppercase());
+            if let Ok(value) = std::env::var(&variable) {
+                profile.set(key, value).map_err(|e| format!("{}: {}", variable, e))?;
+            }
+        }
+        Ok(profile)
+    }
+
+    // Settings of `other` win over this profile's
+    pub fn overlay(&mut self, other: RunProfile) {
+        self.inputs = other.inputs.or(self.inputs.take());
+        self.output_dir = other.output_dir.or(self.output_dir.take());
+        self.format = other.format.or(self.format.take());
+        self.concurrency = other.concurrency.or(self.concurrency);
+        self.rules = other.rules.or(self.rules.take());
+        self.db = other.db.or(self.db.take());
+        self.license = other.license.or(self.license.take());
+        self.index = other.index.or(self.index.take());
+        self.llm = other.llm.or(self.llm.take());
+        self.webhooks = other.webhooks.or(self.webhooks.take());
+        self.priority = other.priority.or(self.priority);
+        self.max_jobs = other.max_jobs.or(self.max_jobs);
+        self.pages = other.pages.or(self.pages);
+        self.sections = other.sections.or(self.sections.take());
+        self.policies = other.policies.or(self.policies.take());
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(format) = &self.format {
+            if format != JSON_FORMAT && check_export_format(format).is_err() {
+                return Err(format!("Unknown format: {} (expected {}, {})", format, JSON_FORMAT, export_formats().join(", ")));
+            }
+        }
+        if self.inputs.is_some() && self.output_dir.is_none() {
+            return Err("Profiles with inputs need an output_dir".to_string());
+        }
+        ExtractionScope::new(self.pages, self.sections.clone())?;
+        Ok(())
+    }
+
+    pub fn get_settings(&self) -> HashMap<String, String> {
+        let mut settings = HashMap::new();
+        let mut put = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                settings.insert(key.to_string(), value);
+            }
+        };
+        put("inputs", self.inputs.as_ref().map(|inputs| inputs.join(",")));
+        put("output_dir", self.output_dir.clone());
+        put("format", self.format.clone());
+        put("concurrency", self.concurrency.map(|n| n.to_string()));
+        put("rules", self.rules.clone());
+        put("db", self.db.clone());
+        put("license", self.license.clone());
+        put("index", self.index.clone());
+        put("llm", self.llm.clone());
+        put("webhooks", self.webhooks.clone());
+        put("priority", self.priority.map(|n| n.to_string()));
+        put("max_jobs", self.max_jobs.map(|n| n.to_string()));
+        put("pages", self.pages.map(|(start, end)| format!("{}-{}", start, end)));
+        put("sections", self.sections.as_ref().map(|sections| sections.join(",")));
+        put("policies", self.policies.as_ref().map(|policies| {
+            policies.iter().map(|(pattern, policy)| format!("{}={}", pattern, policy)).collect::<Vec<_>>().join(",")
+        }));
+        settings
+    }
+}
+
+// Parsed spp.toml - a [defaults] table, [profiles.<name>] tables and an optional
+// `include = [...]` list of files whose settings this file overrides
+#[derive(Debug, Default)]
+pub struct CliConfig {
+    pub defaults: RunProfile,
+    pub profiles: BTreeMap<String, RunProfile>,
+}
+
+impl CliConfig {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config = Self::default();
+        config.load_file(Path::new(path), &mut Vec::new())?;
+        Ok(config)
+    }
+
+    fn load_file(&mut self, path: &Path, including: &mut Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+        let canonical = path.canonicalize()
+            .map_err(|e| format!("Cannot read config {}: {}", path.display(), e))?;
+        if including.contains(&canonical) {
+            return Err(format!("Config include cycle at {}", path.display()).into());
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config {}: {}", path.display(), e))?;
+        let document: DocumentMut = content.parse()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let base_dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let context_error = |e: String| format!("{}: {}", path.display(), e);
+
+        // Layer 1: Included files first, so this file's settings override theirs
+        including.push(canonical);
+        if let Some(include) = document.get("include") {
+            let includes = include.as_array().ok_or_else(|| context_error("include must be an array of paths".to_string()))?;
+            for included in includes.iter() {
+                let included = included.as_str().ok_or_else(|| context_error("include must be an array of paths".to_string()))?;
+                self.load_file(&base_dir.join(included), including)?;
+            }
+        }
+        including.pop();
+
+        // Layer 2: This file's own tables
+        for (key, item) in document.iter() {
+            match (key, item) {
+                ("include", _) => {}
+                ("defaults", Item::Table(table)) => {
+                    let defaults = RunProfile::from_table(table, &base_dir, "defaults").map_err(context_error)?;
+                    self.defaults.overlay(defaults);
+                }
+                ("profiles", Item::Table(profiles)) => {
+                    for (name, item) in profiles.iter() {
+                        let context = format!("profiles.{}", name);
+                        let table = item.as_table().ok_or_else(|| context_error(format!("{} must be a table", context)))?;
+                        let profile = RunProfile::from_table(table, &base_dir, &context).map_err(context_error)?;
+                        self.profiles.entry(name.to_string()).or_default().overlay(profile);
+                    }
+                }
+                (other, _) => return Err(context_error(format!("unexpected key or table {}", other)).into()),
+            }
+        }
+        Ok(())
+    }
+
+    // Defaults, then the named profile, then SPP_* environment variables
+    pub fn resolve(&self, profile: Option<&str>) -> Result<RunProfile, Box<dyn std::error::Error>> {
+        let mut resolved = self.defaults.clone();
+        if let Some(name) = profile {
+            let selected = self.profiles.get(name).ok_or_else(|| {
+                let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                format!("Unknown profile {} (defined: {})", name, names.join(", "))
+            })?;
+            resolved.overlay(selected.clone());
+        }
+        resolved.overlay(RunProfile::from_env()?);
+        resolved.validate()?;
+        Ok(resolved)
+    }
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+}
+
+// Python bindings - SPP_PROFILE selects the profile when none is given
+#[pyfunction]
+#[pyo3(signature = (config_path, profile=None))]
+pub fn load_run_profile(config_path: &str, profile: Option<String>) -> PyResult<HashMap<String, String>> {
+    let config = CliConfig::load(config_path).map_err(to_py_err)?;
+    let profile = profile.or_else(|| std::env::var("SPP_PROFILE").ok());
+    let resolved = config.resolve(profile.as_deref()).map_err(to_py_err)?;
+    Ok(resolved.get_settings())
+}
+
+#[pyfunction]
+pub fn list_run_profiles(config_path: &str) -> PyResult<Vec<String>> {
+    let config = CliConfig::load(config_path).map_err(to_py_err)?;
+    Ok(config.profiles.keys().cloned().collect())
+}
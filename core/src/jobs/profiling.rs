@@ -0,0 +1,387 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use prost::Message;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::filter::dynamic_filter_fn;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::storage::atomic::write_atomic;
+
+// Where a run's spans go, outermost span first. One run records at a time: the subscriber is
+// process-wide, since job workers and OCR pages run on threads of their own
+static RECORDING: Mutex<Option<Arc<Recording>>> = Mutex::new(None);
+static RECORDING_ACTIVE: AtomicBool = AtomicBool::new(false);
+static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+thread_local! {
+    static STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+// Time spent in a span stack itself, its child spans excluded
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StackTotals {
+    pub wall_ns: u64,
+    pub cpu_ns: u64,
+    pub count: u64,
+}
+
+#[derive(Default)]
+struct Recording {
+    stacks: Mutex<HashMap<Vec<String>, StackTotals>>,
+}
+
+struct Frame {
+    id: u64,
+    name: String,
+    started: Instant,
+    cpu_ns: u64,
+    child_wall_ns: u64,
+    child_cpu_ns: u64,
+}
+
+// CPU time of the calling thread, so spans on parallel workers do not count each other
+#[cfg(unix)]
+fn thread_cpu_ns() -> u64 {
+    let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } != 0 {
+        return 0;
+    }
+    time.tv_sec.max(0) as u64 * 1_000_000_000 + time.tv_nsec.max(0) as u64
+}
+
+// Without a thread CPU clock spans record wall-clock time only
+#[cfg(not(unix))]
+fn thread_cpu_ns() -> u64 {
+    0
+}
+
+// A span's `label` field, e.g. the collection of a `collection` span
+struct Label(Option<String>);
+
+impl Visit for Label {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "label" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "label" {
+            self.0 = Some(value.to_string());
+        }
+    }
+}
+
+// Frame names end up in folded stacks, where ';' separates frames and a space ends the stack
+fn frame_name(metadata: &Metadata<'_>, label: Option<String>) -> String {
+    let name = match label {
+        Some(label) => format!("{}:{}", metadata.name(), label),
+        None => metadata.name().to_string(),
+    };
+    name.chars().map(|c| if c == ';' || c.is_whitespace() { '_' } else { c }).collect()
+}
+
+// A span's frame name, kept with the span in the registry
+struct FrameName(String);
+
+// Times the spans entered on every thread while a run records. Spans are only enabled then,
+// so outside a profiled run they cost one atomic load
+struct SpanProfiler;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanProfiler {
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, context: Context<'_, S>) {
+        let mut label = Label(None);
+        attributes.record(&mut label);
+        if let Some(span) = context.span(id) {
+            span.extensions_mut().insert(FrameName(frame_name(attributes.metadata(), label.0)));
+        }
+    }
+
+    fn on_enter(&self, id: &Id, context: Context<'_, S>) {
+        let name = context.span(id)
+            .and_then(|span| span.extensions().get::<FrameName>().map(|name| name.0.clone()))
+            .unwrap_or_default();
+        STACK.with(|stack| stack.borrow_mut().push(Frame {
+            id: id.into_u64(),
+            name,
+            started: Instant::now(),
+            cpu_ns: thread_cpu_ns(),
+            child_wall_ns: 0,
+            child_cpu_ns: 0,
+        }));
+    }
+
+    fn on_exit(&self, id: &Id, _context: Context<'_, S>) {
+        STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let Some(position) = stack.iter().rposition(|frame| frame.id == id.into_u64()) else { return };
+            let frame = stack.remove(position);
+            let wall_ns = frame.started.elapsed().as_nanos() as u64;
+            let cpu_ns = thread_cpu_ns().saturating_sub(frame.cpu_ns);
+            if let Some(parent) = position.checked_sub(1).and_then(|parent| stack.get_mut(parent)) {
+                parent.child_wall_ns += wall_ns;
+                parent.child_cpu_ns += cpu_ns;
+            }
+            let recording = RECORDING.lock().ok().and_then(|recording| recording.clone());
+            let Some(recording) = recording else { return };
+            let mut path: Vec<String> = stack[..position].iter().map(|frame| frame.name.clone()).collect();
+            path.push(frame.name);
+            if let Ok(mut stacks) = recording.stacks.lock() {
+                let totals = stacks.entry(path).or_default();
+                totals.wall_ns += wall_ns.saturating_sub(frame.child_wall_ns);
+                totals.cpu_ns += cpu_ns.saturating_sub(frame.child_cpu_ns);
+                totals.count += 1;
+            };
+        });
+    }
+}
+
+// Installs the profiler as the process's tracing subscriber, once. Fails when the embedding
+// application installed its own first
+fn install() -> Result<(), String> {
+    let installed = *INSTALLED.get_or_init(|| {
+        let recorded = dynamic_filter_fn(|metadata, _| metadata.is_span() && RECORDING_ACTIVE.load(Ordering::Relaxed));
+        let subscriber = tracing_subscriber::registry().with(SpanProfiler.with_filter(recorded));
+        tracing::subscriber::set_global_default(subscriber).is_ok()
+    });
+    installed.then_some(()).ok_or_else(|| "Another tracing subscriber is installed; profile the run with it instead".to_string())
+}
+
+// A run being profiled; recording stops when it is finished or dropped
+pub struct ProfileRun {
+    recording: Arc<Recording>,
+    started: Instant,
+    started_at: SystemTime,
+}
+
+pub fn start() -> Result<ProfileRun, String> {
+    install()?;
+    let mut active = RECORDING.lock().map_err(|_| "Profiler lock poisoned")?;
+    if active.is_some() {
+        return Err("Another run is being profiled in this process".to_string());
+    }
+    let recording = Arc::new(Recording::default());
+    *active = Some(Arc::clone(&recording));
+    RECORDING_ACTIVE.store(true, Ordering::Relaxed);
+    Ok(ProfileRun { recording, started: Instant::now(), started_at: SystemTime::now() })
+}
+
+impl ProfileRun {
+    pub fn finish(self) -> Profile {
+        let stacks = self.recording.stacks.lock().map(|mut stacks| std::mem::take(&mut *stacks)).unwrap_or_default();
+        let mut stacks: Vec<(Vec<String>, StackTotals)> = stacks.into_iter().collect();
+        stacks.sort_by(|a, b| a.0.cmp(&b.0));
+        Profile {
+            stacks,
+            duration_ns: self.started.elapsed().as_nanos() as u64,
+            started_ns: self.started_at.duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0),
+        }
+    }
+}
+
+impl Drop for ProfileRun {
+    fn drop(&mut self) {
+        RECORDING_ACTIVE.store(false, Ordering::Relaxed);
+        if let Ok(mut active) = RECORDING.lock() {
+            *active = None;
+        }
+    }
+}
+
+// The messages of pprof's profile.proto written here, with its field numbers
+#[derive(Clone, PartialEq, Message)]
+struct PprofProfile {
+    #[prost(message, repeated, tag = "1")]
+    sample_type: Vec<ValueType>,
+    #[prost(message, repeated, tag = "2")]
+    sample: Vec<Sample>,
+    #[prost(message, repeated, tag = "4")]
+    location: Vec<Location>,
+    #[prost(message, repeated, tag = "5")]
+    function: Vec<Function>,
+    #[prost(string, repeated, tag = "6")]
+    string_table: Vec<String>,
+    #[prost(int64, tag = "9")]
+    time_nanos: i64,
+    #[prost(int64, tag = "10")]
+    duration_nanos: i64,
+    #[prost(message, optional, tag = "11")]
+    period_type: Option<ValueType>,
+    #[prost(int64, tag = "12")]
+    period: i64,
+    #[prost(int64, tag = "14")]
+    default_sample_type: i64,
+}
+
+// Strings are indices into the profile's string table
+#[derive(Clone, PartialEq, Message)]
+struct ValueType {
+    #[prost(int64, tag = "1")]
+    kind: i64,
+    #[prost(int64, tag = "2")]
+    unit: i64,
+}
+
+// Locations run from the innermost frame out
+#[derive(Clone, PartialEq, Message)]
+struct Sample {
+    #[prost(uint64, repeated, tag = "1")]
+    location_id: Vec<u64>,
+    #[prost(int64, repeated, tag = "2")]
+    value: Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Location {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(message, repeated, tag = "4")]
+    line: Vec<Line>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Line {
+    #[prost(uint64, tag = "1")]
+    function_id: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Function {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(int64, tag = "2")]
+    name: i64,
+    #[prost(int64, tag = "3")]
+    system_name: i64,
+    #[prost(int64, tag = "4")]
+    filename: i64,
+}
+
+// Self time per span stack of a finished run
+pub struct Profile {
+    pub stacks: Vec<(Vec<String>, StackTotals)>,
+    pub duration_ns: u64,
+    started_ns: u64,
+}
+
+impl Profile {
+    // Folded stacks, as flamegraph.pl and inferno-flamegraph read them: one line per stack
+    // with its wall-clock self time in microseconds
+    pub fn folded(&self) -> String {
+        self.stacks.iter()
+            .filter(|(_, totals)| totals.wall_ns >= 1000)
+            .map(|(stack, totals)| format!("{} {}\n", stack.join(";"), totals.wall_ns / 1000))
+            .collect()
+    }
+
+    // A pprof profile (profile.proto, uncompressed, which pprof reads as well as gzipped) with
+    // one sample per span stack: its span count, CPU and wall-clock nanoseconds
+    pub fn pprof(&self) -> Vec<u8> {
+        let mut strings: Vec<String> = vec![String::new()];
+        let mut intern = |text: &str| -> i64 {
+            match strings.iter().position(|s| s == text) {
+                Some(index) => index as i64,
+                None => {
+                    strings.push(text.to_string());
+                    (strings.len() - 1) as i64
+                }
+            }
+        };
+        let (count, nanoseconds) = (intern("count"), intern("nanoseconds"));
+        let (spans, cpu, wall) = (intern("spans"), intern("cpu"), intern("wall"));
+        let sample_type = [(spans, count), (cpu, nanoseconds), (wall, nanoseconds)]
+            .into_iter()
+            .map(|(kind, unit)| ValueType { kind, unit })
+            .collect();
+
+        // A function and a location of the same id per frame name
+        let mut functions: Vec<String> = Vec::new();
+        let mut sample = Vec::new();
+        for (stack, totals) in &self.stacks {
+            let mut location_id = Vec::new();
+            for name in stack.iter().rev() {
+                let id = match functions.iter().position(|function| function == name) {
+                    Some(index) => index + 1,
+                    None => {
+                        functions.push(name.clone());
+                        functions.len()
+                    }
+                };
+                location_id.push(id as u64);
+            }
+            let value = [totals.count, totals.cpu_ns, totals.wall_ns].iter().map(|value| *value as i64).collect();
+            sample.push(Sample { location_id, value });
+        }
+        let file = intern("ml_core");
+        let location = (1..=functions.len() as u64).map(|id| Location { id, line: vec![Line { function_id: id }] }).collect();
+        let function = functions.iter().zip(1..).map(|(name, id)| {
+            let name = intern(name);
+            Function { id, name, system_name: name, filename: file }
+        }).collect();
+        PprofProfile {
+            sample_type,
+            sample,
+            location,
+            function,
+            string_table: strings,
+            time_nanos: self.started_ns as i64,
+            duration_nanos: self.duration_ns as i64,
+            period_type: Some(ValueType { kind: cpu, unit: nanoseconds }),
+            period: 1,
+            default_sample_type: cpu,
+        }.encode_to_vec()
+    }
+
+    // Writes <prefix>.folded and <prefix>.pprof
+    pub fn write(&self, prefix: &str) -> Result<(), Box<dyn std::error::Error>> {
+        for (extension, data) in [("folded", self.folded().into_bytes()), ("pprof", self.pprof())] {
+            let path = format!("{}.{}", prefix, extension);
+            write_atomic(&path, data).map_err(|e| format!("Cannot write profile {}: {}", path, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_span_stacks_as_folded_and_pprof() {
+        let run = start().unwrap();
+        assert!(start().is_err());
+        {
+            let _job = tracing::info_span!("job").entered();
+            for _ in 0..2 {
+                let _collection = tracing::info_span!("collection", label = "steps").entered();
+                std::thread::sleep(std::time::Duration::from_millis(2));
+            }
+        }
+        let profile = run.finish();
+        let stacks: Vec<(String, u64)> = profile.stacks.iter().map(|(stack, totals)| (stack.join(";"), totals.count)).collect();
+        assert_eq!(stacks, [("job".to_string(), 1), ("job;collection:steps".to_string(), 2)]);
+        assert!(profile.folded().contains("job;collection:steps "));
+
+        let pprof = PprofProfile::decode(profile.pprof().as_slice()).unwrap();
+        let name = |index: i64| pprof.string_table[index as usize].as_str();
+        assert_eq!(pprof.sample_type.iter().map(|kind| name(kind.kind)).collect::<Vec<_>>(), ["spans", "cpu", "wall"]);
+        assert_eq!(name(pprof.default_sample_type), "cpu");
+        let nested = &pprof.sample[1];
+        assert_eq!(nested.value[0], 2);
+        let frames: Vec<&str> = nested.location_id.iter()
+            .map(|id| name(pprof.function.iter().find(|function| function.id == *id).unwrap().name))
+            .collect();
+        assert_eq!(frames, ["collection:steps", "job"]);
+
+        // Spans outside a profiled run are not recorded
+        let _ignored = tracing::info_span!("after").entered();
+        assert!(start().unwrap().finish().stacks.is_empty());
+    }
+}
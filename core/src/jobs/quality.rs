@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use crate::engine::completeness::{PageReport, NO_TEXT};
+use crate::engine::language::detect_language;
+use crate::jobs::policy::DocumentPolicy;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Grade floors on the 0-100 score; anything below D is an F
+const GRADE_FLOORS: [(char, f64); 4] = [('A', 85.0), ('B', 70.0), ('C', 55.0), ('D', 40.0)];
+
+// Text layers covering less of the document than this are treated as missing
+const MIN_TEXT_LAYER: f64 = 0.5;
+
+// How a document should be processed, from what its text layer looks like before extraction
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityScore {
+    // Share of pages with a usable text layer
+    pub text_layer: f64,
+    // Whether the text came from OCR rather than the document's own text layer
+    pub ocr: bool,
+    // Estimated share of words recognized correctly; 1.0 for clean text layers
+    pub ocr_confidence: f64,
+    // 0 for running text, towards 1 for tables, columns and fragments
+    pub layout_complexity: f64,
+    // ISO 639-1 code, or "und" when the words match no known language
+    pub language: String,
+    pub script: String,
+    pub score: f64,
+    pub reasons: Vec<String>,
+}
+
+impl QualityScore {
+    // Binary documents that no converter turned into text
+    pub fn without_text_layer() -> Self {
+        Self {
+            text_layer: 0.0,
+            ocr: false,
+            ocr_confidence: 0.0,
+            layout_complexity: 0.0,
+            language: "und".to_string(),
+            script: "other".to_string(),
+            score: 0.0,
+            reasons: vec!["No text layer".to_string()],
+        }
+    }
+
+    pub fn grade(&self) -> char {
+        GRADE_FLOORS.iter().find(|(_, floor)| self.score >= *floor).map(|(grade, _)| *grade).unwrap_or('F')
+    }
+
+    // F grades and documents without a text layer need a person, not a policy
+    pub fn needs_manual_handling(&self) -> bool {
+        self.grade() == 'F' || self.text_layer < MIN_TEXT_LAYER
+    }
+
+    // C grades are extracted by rules alone, since an LLM spends tokens on the noise; D grades
+    // keep only their text for a reviewer
+    pub fn recommended_policy(&self) -> DocumentPolicy {
+        if self.needs_manual_handling() {
+            return DocumentPolicy::Skip;
+        }
+        match self.grade() {
+            'A' | 'B' => DocumentPolicy::Full,
+            'C' => DocumentPolicy::RulesOnly,
+            _ => DocumentPolicy::OcrOnly,
+        }
+    }
+
+    pub fn route(&self) -> &'static str {
+        if self.needs_manual_handling() { "manual" } else { "automatic" }
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("quality_grade".to_string(), self.grade().to_string());
+        info.insert("quality_score".to_string(), format!("{:.1}", self.score));
+        info.insert("text_layer".to_string(), format!("{:.3}", self.text_layer));
+        info.insert("ocr".to_string(), self.ocr.to_string());
+        info.insert("ocr_confidence".to_string(), format!("{:.3}", self.ocr_confidence));
+        info.insert("layout_complexity".to_string(), format!("{:.3}", self.layout_complexity));
+        info.insert("language".to_string(), self.language.clone());
+        info.insert("script".to_string(), self.script.clone());
+        info.insert("recommended_policy".to_string(), self.recommended_policy().as_str().to_string());
+        info.insert("route".to_string(), self.route().to_string());
+        info.insert("quality_reasons".to_string(), self.reasons.join("; "));
+        info
+    }
+}
+
+// OCR errors show up as words mixing letters with digits or symbols, single stray letters and
+// characters no manual prints
+fn is_suspect_word(word: &str) -> bool {
+    let letters = word.chars().filter(|c| c.is_alphabetic()).count();
+    let digits = word.chars().filter(|c| c.is_ascii_digit()).count();
+    let chars = word.chars().count();
+    if letters == 0 {
+        return false;
+    }
+    // Part numbers and task references (e.g. 32-11-00, MS20995C32) are legitimately mixed
+    let upper = word.chars().filter(|c| c.is_uppercase()).count();
+    if digits > 0 && upper + digits == chars {
+        return false;
+    }
+    let mixed_digits = digits > 0 && letters > digits;
+    let mixed_case = word.chars().skip(1).any(char::is_uppercase) && word.chars().any(char::is_lowercase);
+    // Upper-case single letters are list labels such as "A." and "B."
+    let stray = chars == 1 && upper == 0 && !matches!(word, "a" | "y" | "o" | "e" | "à" | "è");
+    mixed_digits || mixed_case || stray
+}
+
+fn is_noise(c: char) -> bool {
+    matches!(c, '|' | '~' | '^' | '`' | '¦' | '§' | '¬' | char::REPLACEMENT_CHARACTER)
+}
+
+fn ocr_confidence(text: &str) -> f64 {
+    let words: Vec<&str> = text.unicode_words().collect();
+    if words.is_empty() {
+        return 0.0;
+    }
+    let suspect = words.iter().filter(|word| is_suspect_word(word)).count();
+    let visible = text.chars().filter(|c| !c.is_whitespace()).count().max(1);
+    let noise = text.chars().filter(|c| is_noise(*c)).count();
+    let clean = 1.0 - suspect as f64 / words.len() as f64;
+    (clean * (1.0 - (noise as f64 / visible as f64 * 10.0).min(1.0))).clamp(0.0, 1.0)
+}
+
+// Lines split by wide gaps are table rows or columns; very short lines are fragments of
+// callouts, headers and figure labels
+fn layout_complexity(text: &str) -> f64 {
+    let lines: Vec<&str> = text.lines().map(str::trim_end).filter(|line| !line.trim().is_empty()).collect();
+    if lines.is_empty() {
+        return 0.0;
+    }
+    let gaps = |line: &str| line.trim_start().split("  ").filter(|cell| !cell.trim().is_empty()).count().saturating_sub(1);
+    let tabular = lines.iter().filter(|line| gaps(line) >= 2).count();
+    let columns = lines.iter().filter(|line| gaps(line) == 1).count();
+    let fragments = lines.iter().filter(|line| line.trim().chars().count() < 12).count();
+    let share = |count: usize| count as f64 / lines.len() as f64;
+    (share(tabular) * 0.5 + share(columns) * 0.3 + share(fragments) * 0.2).min(1.0)
+}
+
+// Scores a decoded document; `ocr` says whether its text came from OCR
+pub fn score_document(text: &str, report: &PageReport, ocr: bool) -> QualityScore {
+    let blank = report.failures.iter().filter(|failure| failure.reason == NO_TEXT).count();
+    let pages = report.page_count.max(1);
+    let text_layer = if text.trim().is_empty() { 0.0 } else { 1.0 - blank as f64 / pages as f64 };
+    let confidence = ocr_confidence(text);
+    let complexity = layout_complexity(text);
+    let (language, script) = detect_language(text);
+
+    // Pages without text cannot be rescued by how clean the rest is
+    let mut score = 100.0 * text_layer * (0.75 * confidence + 0.25 * (1.0 - complexity));
+    let mut reasons = Vec::new();
+    if text_layer < MIN_TEXT_LAYER {
+        reasons.push(format!("Text layer on {:.0}% of pages", text_layer * 100.0));
+    }
+    if confidence < 0.9 {
+        reasons.push(format!("{:.0}% of words look misrecognized", (1.0 - confidence) * 100.0));
+    }
+    if complexity > 0.5 {
+        reasons.push("Mostly tables, columns or fragments".to_string());
+    }
+    // Only latin-script languages are told apart, so other scripts are not penalized
+    if language == "und" && matches!(script.as_str(), "latin" | "other") && !text.trim().is_empty() {
+        score -= 10.0;
+        reasons.push(format!("Unrecognized language ({} script)", script));
+    }
+    QualityScore {
+        text_layer,
+        ocr,
+        ocr_confidence: confidence,
+        layout_complexity: complexity,
+        language,
+        script,
+        score: score.clamp(0.0, 100.0),
+        reasons,
+    }
+}
@@ -0,0 +1,676 @@
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+#[cfg(feature = "llm")]
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+
+#[cfg(feature = "figures")]
+use crate::engine::convert::{is_pdf, ConverterWarning};
+use crate::engine::extractor::ExtractionEngine;
+#[cfg(feature = "llm")]
+use crate::engine::language::tag_languages;
+use crate::engine::scope::{scope_from_args, ExtractionScope};
+use crate::export::registry::{check_export_format, exporter_for, JSON_FORMAT};
+use crate::jobs::manifest::ManifestRecorder;
+use crate::jobs::policy::DocumentPolicy;
+use crate::jobs::store::{Job, JobStatus, JobStore};
+use crate::jobs::usage::{time_phase, Phase, RunUsage};
+use crate::licensing::manager::License;
+#[cfg(feature = "llm")]
+use crate::llm::assist::LlmAssistant;
+#[cfg(feature = "llm")]
+use crate::llm::provider::LlmConfig;
+#[cfg(feature = "figures")]
+use crate::pdf::text::NATIVE_TOOL;
+use crate::schema::envelope::{wrap_output, Provenance, UNLICENSED_TIER};
+use crate::schema::overflow::{overflow_dir_for, write_overflow};
+use crate::security::session::record_document_processed;
+use crate::server::webhooks::{load_webhooks, WebhookEvent, WebhookNotifier};
+use crate::storage::atomic::clean_orphans;
+use crate::storage::backend::Storage;
+use crate::storage::sqlite::SqliteStorage;
+#[cfg(feature = "search")]
+use crate::search::index::RecordIndex;
+
+// Where a job writes the output for one of its inputs
+pub fn output_path_for(output_dir: &str, input: &str) -> PathBuf {
+    let stem = Path::new(input)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("document");
+    Path::new(output_dir).join(format!("{}.json", stem))
+}
+
+// Two inputs of one job with the same stem, such as m.txt and m.pdf, would write one output and
+// resume from each other's checkpoints, so jobs refuse them instead. An input listed twice only
+// writes the same output again
+pub fn output_collision(inputs: &[String]) -> Option<String> {
+    let mut seen: HashMap<PathBuf, &str> = HashMap::new();
+    for input in inputs {
+        let output = output_path_for("", input);
+        match seen.get(&output) {
+            Some(first) if first != input => {
+                return Some(format!("{} and {} would both write {}; rename one or give them separate jobs", first, input, output.display()));
+            }
+            Some(_) => {}
+            None => {
+                seen.insert(output, input);
+            }
+        }
+    }
+    None
+}
+
+pub const CHECKPOINT_NAMESPACE: &str = "job_checkpoints";
+
+// How far a job got - a retried job skips the inputs it already wrote outputs for
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobCheckpoint {
+    // Leading inputs whose outputs are written
+    pub completed: usize,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// The payload of one input's output, and the decoded text its provenance hashes. Any LLM
+// records of the full policy are added by the caller
+pub fn extract_payload(
+    engine: &ExtractionEngine,
+    job_id: &str,
+    scope: &ExtractionScope,
+    input: &str,
+    policy: DocumentPolicy,
+    usage: Option<&RunUsage>,
+) -> Result<(Value, String), Box<dyn std::error::Error>> {
+    let decoded = time_phase(usage, Phase::Read, || {
+        profile_span!("read");
+        engine.read_input(input)
+    })?;
+    profile_span!("extract");
+    let _extracting = usage.map(|usage| usage.timer(Phase::Extract));
+    let scoped = scope.apply(engine, &decoded.text).map_err(|e| format!("{}: {}", input, e))?;
+    #[allow(unused_mut)]
+    let mut report = scope.page_report(&decoded.report);
+    let mut payload = json!({
+        "job_id": job_id,
+        "source": input,
+    });
+    if policy != DocumentPolicy::Full {
+        payload["policy"] = json!(policy.as_str());
+    }
+    if policy.extracts() {
+        for (collection, records) in engine.extract_collections(&scoped) {
+            payload[collection] = json!(records);
+        }
+        // Form fields are placed by page; those on no page belong to no part of a scope. A
+        // form that does not read loses the fields, not the job
+        #[cfg(feature = "figures")]
+        if is_pdf(input) {
+            match engine.extract_forms(input) {
+                Ok(fields) => {
+                    let fields: Vec<_> = fields.into_iter()
+                        .filter(|field| match field.get("page").and_then(|page| page.parse().ok()) {
+                            Some(page) => scope.covers_page(&scoped, page),
+                            None => scope.is_empty(),
+                        })
+                        .collect();
+                    if !fields.is_empty() {
+                        payload["forms"] = json!(fields);
+                    }
+                }
+                Err(e) => report.warnings.push(ConverterWarning {
+                    source: input.to_string(),
+                    tool: NATIVE_TOOL.to_string(),
+                    line: report.warnings.len() + 1,
+                    message: format!("Forms: {}", e),
+                }),
+            }
+        }
+    } else {
+        payload["text"] = json!(scoped);
+    }
+    // The text of an ocr-only output is masked like any record
+    if let Some(pii) = engine.scan_pii(&scoped) {
+        payload["pii"] = json!(pii);
+    }
+    report.add_to(&mut payload, report.failure_records());
+    scope.add_to(&mut payload);
+    Ok((payload, decoded.text))
+}
+
+// Executes queued jobs one at a time against a single rules configuration
+pub struct JobRunner {
+    store: JobStore,
+    // Shared read-only with the other workers of a parallel run
+    engine: Arc<ExtractionEngine>,
+    // Outputs are watermarked with this license when one is given
+    license: Option<Arc<License>>,
+    // Each output is also exported to this format
+    export_format: Option<String>,
+    // Progress is checkpointed here after every input
+    checkpoints: Option<Arc<dyn Storage>>,
+    // Reproducible runs record every output written here
+    manifest: Option<Arc<ManifestRecorder>>,
+    // Time, CPU and I/O of the run, by phase
+    usage: Option<Arc<RunUsage>>,
+    // Told of every output written and every job that completes or fails
+    notifier: Option<Arc<WebhookNotifier>>,
+    // Inputs with the full policy are also run through the rule pack's prompts
+    #[cfg(feature = "llm")]
+    assistant: Option<Arc<Mutex<LlmAssistant>>>,
+    // Records of every written output are added to this index
+    #[cfg(feature = "search")]
+    index: Option<Arc<RecordIndex>>,
+}
+
+impl JobRunner {
+    pub fn new(store: JobStore, engine: ExtractionEngine) -> Self {
+        Self {
+            store,
+            engine: Arc::new(engine),
+            license: None,
+            export_format: None,
+            checkpoints: None,
+            manifest: None,
+            usage: None,
+            notifier: None,
+            #[cfg(feature = "llm")]
+            assistant: None,
+            #[cfg(feature = "search")]
+            index: None,
+        }
+    }
+
+    pub fn with_license(mut self, license: License) -> Self {
+        self.license = Some(Arc::new(license));
+        self
+    }
+
+    pub fn with_export_format(mut self, format: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        check_export_format(format)?;
+        // Exports read the output back as JSON and would write it out in the clear
+        if self.engine.get_delivery().is_enabled() {
+            return Err("Outputs encrypted to delivery.recipients cannot be exported as well".into());
+        }
+        self.export_format = Some(format.to_string());
+        Ok(self)
+    }
+
+    pub fn with_checkpoints(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.checkpoints = Some(storage);
+        self
+    }
+
+    pub fn with_manifest(mut self, manifest: Arc<ManifestRecorder>) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    pub fn with_usage(mut self, usage: Arc<RunUsage>) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: Arc<WebhookNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    fn notify(&self, event: WebhookEvent, data: Value) {
+        if let Some(notifier) = &self.notifier {
+            notifier.notify_job(event, data);
+        }
+    }
+
+    // Workers share the assistant, and with it the run budget and prompt cache
+    #[cfg(feature = "llm")]
+    pub fn with_assistant(mut self, assistant: LlmAssistant) -> Self {
+        self.assistant = Some(Arc::new(Mutex::new(assistant)));
+        self
+    }
+
+    #[cfg(feature = "llm")]
+    fn assist(&self, job: &Job, text: &str, payload: &mut Value) -> Result<(), Box<dyn std::error::Error>> {
+        let assistant = match &self.assistant {
+            Some(assistant) => assistant,
+            None => return Ok(()),
+        };
+        profile_span!("llm");
+        let _assisting = self.usage.as_deref().map(|usage| usage.timer(Phase::Llm));
+        let scoped = job.scope.apply(&self.engine, text)?;
+        let mut assistant = assistant.lock().map_err(|_| "LLM assistant lock poisoned")?;
+        let mut assisted = assistant.assist_document(&self.engine, &scoped)?;
+        self.engine.tag_access(&scoped, &mut assisted);
+        tag_languages(&scoped, &mut assisted);
+        self.engine.redact_pii(&scoped, &mut assisted);
+        self.engine.namespace_ids(&mut assisted);
+        payload["llm"] = json!(assisted);
+        let mut withheld = assistant.get_withheld().to_vec();
+        if !withheld.is_empty() {
+            self.engine.tag_access(&scoped, &mut withheld);
+            tag_languages(&scoped, &mut withheld);
+            self.engine.namespace_ids(&mut withheld);
+            payload["llm_withheld"] = json!(withheld);
+        }
+        if !assistant.get_warnings().is_empty() {
+            payload["warnings"] = json!(assistant.get_warnings());
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "search")]
+    pub fn with_index(mut self, index: RecordIndex) -> Self {
+        self.index = Some(Arc::new(index));
+        self
+    }
+
+    // Another runner with the same rules, license, export and index on its own connection
+    fn worker(&self, store: JobStore) -> Self {
+        Self {
+            store,
+            engine: Arc::clone(&self.engine),
+            license: self.license.clone(),
+            export_format: self.export_format.clone(),
+            checkpoints: self.checkpoints.clone(),
+            manifest: self.manifest.clone(),
+            usage: self.usage.clone(),
+            notifier: self.notifier.clone(),
+            #[cfg(feature = "llm")]
+            assistant: self.assistant.clone(),
+            #[cfg(feature = "search")]
+            index: self.index.clone(),
+        }
+    }
+
+    pub fn run_next(&mut self) -> Result<Option<Job>, Box<dyn std::error::Error>> {
+        let job = match self.store.claim_next()? {
+            Some(job) => job,
+            None => return Ok(None),
+        };
+
+        match self.execute(&job) {
+            Ok(true) => {
+                self.store.finish(&job.job_id, JobStatus::Completed, None)?;
+                if let Some(checkpoints) = &self.checkpoints {
+                    checkpoints.delete(CHECKPOINT_NAMESPACE, &job.job_id)?;
+                }
+                self.notify(WebhookEvent::BatchFinished, json!({
+                    "job_id": job.job_id,
+                    "inputs": job.inputs.len(),
+                    "output_dir": job.output_dir,
+                }));
+            }
+            Ok(false) => {} // Cancelled while running - status already recorded
+            Err(e) => {
+                self.store.finish(&job.job_id, JobStatus::Failed, Some(e.to_string()))?;
+                self.notify(WebhookEvent::Failure, json!({
+                    "job_id": job.job_id,
+                    "error": e.to_string(),
+                }));
+            }
+        }
+
+        self.store.get(&job.job_id)
+    }
+
+    pub fn run_all(&mut self, max_jobs: Option<usize>) -> Result<Vec<Job>, Box<dyn std::error::Error>> {
+        let mut finished = Vec::new();
+        while max_jobs.is_none_or(|max| finished.len() < max) {
+            match self.run_next()? {
+                Some(job) => finished.push(job),
+                None => break,
+            }
+        }
+        Ok(finished)
+    }
+
+    // Runs `workers` runners side by side on db_path. Jobs are claimed in a transaction, so no
+    // job runs twice; max_jobs caps the total across workers
+    pub fn run_workers(self, db_path: &str, workers: usize, max_jobs: Option<usize>) -> Result<Vec<Job>, Box<dyn std::error::Error>> {
+        let mut runners = Vec::new();
+        for _ in 1..workers {
+            runners.push(self.worker(JobStore::open(db_path)?));
+        }
+        runners.push(self);
+
+        let claimed = AtomicUsize::new(0);
+        let results: Vec<Result<Vec<Job>, String>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = runners.into_iter()
+                .map(|mut runner| {
+                    let claimed = &claimed;
+                    scope.spawn(move || {
+                        let mut finished = Vec::new();
+                        while max_jobs.is_none_or(|max| claimed.fetch_add(1, Ordering::SeqCst) < max) {
+                            match runner.run_next().map_err(|e| e.to_string())? {
+                                Some(job) => finished.push(job),
+                                None => break,
+                            }
+                        }
+                        Ok(finished)
+                    })
+                })
+                .collect();
+            handles.into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|_| Err("Job worker panicked".to_string())))
+                .collect()
+        });
+
+        let mut finished = Vec::new();
+        for result in results {
+            finished.extend(result?);
+        }
+        finished.sort_by_key(|job| job.finished_at);
+        Ok(finished)
+    }
+
+    // Inputs a previous attempt finished, as long as their outputs are still on disk
+    fn resume_point(&self, job: &Job) -> Result<usize, Box<dyn std::error::Error>> {
+        let checkpoint = match &self.checkpoints {
+            Some(checkpoints) => checkpoints.get(CHECKPOINT_NAMESPACE, &job.job_id)?,
+            None => None,
+        };
+        let completed = checkpoint
+            .and_then(|stored| serde_json::from_slice::<JobCheckpoint>(&stored).ok())
+            .map(|checkpoint| checkpoint.completed.min(job.inputs.len()))
+            .unwrap_or(0);
+        Ok(job.inputs[..completed].iter()
+            .take_while(|input| {
+                let output = self.engine.get_delivery().output_path(&output_path_for(&job.output_dir, input));
+                job.policy_for(input) == DocumentPolicy::Skip || output.exists()
+            })
+            .count())
+    }
+
+    fn checkpoint(&self, job: &Job, completed: usize) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(checkpoints) = &self.checkpoints {
+            let checkpoint = JobCheckpoint { completed, updated_at: Some(Utc::now()) };
+            checkpoints.put(CHECKPOINT_NAMESPACE, &job.job_id, &serde_json::to_vec(&checkpoint)?)?;
+        }
+        Ok(())
+    }
+
+    // Returns false when the job was cancelled part-way through
+    fn execute(&self, job: &Job) -> Result<bool, Box<dyn std::error::Error>> {
+        profile_span!("job");
+        std::fs::create_dir_all(&job.output_dir)?;
+        // A run killed part-way leaves temporary files, never half-written outputs; a job picking
+        // the directory back up clears them away
+        clean_orphans(&job.output_dir)?;
+        if let Some(collision) = output_collision(&job.inputs) {
+            return Err(collision.into());
+        }
+        let resume_from = self.resume_point(job)?;
+        if resume_from > 0 {
+            self.store.update_progress(&job.job_id, resume_from as u32)?;
+        }
+
+        for (index, input) in job.inputs.iter().enumerate().skip(resume_from) {
+            let cancelled = self.store.get(&job.job_id)?
+                .map(|current| current.status == JobStatus::Cancelled)
+                .unwrap_or(true);
+            if cancelled {
+                return Ok(false);
+            }
+
+            let policy = job.policy_for(input);
+            if policy == DocumentPolicy::Skip {
+                self.store.update_progress(&job.job_id, (index + 1) as u32)?;
+                self.checkpoint(job, index + 1)?;
+                continue;
+            }
+            profile_span!("input");
+            let usage = self.usage.as_deref();
+            let (payload, text) = extract_payload(&self.engine, &job.job_id, &job.scope, input, policy, usage)?;
+            #[cfg(feature = "llm")]
+            let payload = {
+                let mut payload = payload;
+                if policy == DocumentPolicy::Full {
+                    self.assist(job, &text, &mut payload).map_err(|e| format!("{}: {}", input, e))?;
+                }
+                payload
+            };
+            let mut payload = payload;
+            let output_path = output_path_for(&job.output_dir, input);
+            let overflow = self.engine.get_truncation()
+                .truncate(&mut payload, &overflow_dir_for(&output_path), self.engine.get_delivery());
+            let provenance = match &self.license {
+                Some(license) => Provenance::licensed(&self.engine, license, text.as_bytes()),
+                None => Provenance::new(&self.engine, UNLICENSED_TIER, text.as_bytes()),
+            };
+            let output = wrap_output(&provenance, payload);
+
+            {
+                profile_span!("write");
+                let _writing = usage.map(|usage| usage.timer(Phase::Write));
+                write_overflow(Path::new(&job.output_dir), &overflow, self.engine.get_delivery())?;
+                let output_path = self.engine.get_delivery().write_json(&output_path, &output)?;
+                record_document_processed();
+                if let Some(manifest) = &self.manifest {
+                    manifest.record(job, input, &output_path, &output)?;
+                }
+                if let Some(format) = &self.export_format {
+                    exporter_for(format)?.export(&output_path)?;
+                }
+                self.notify(WebhookEvent::DocumentFinished, json!({
+                    "job_id": job.job_id,
+                    "input": input,
+                    "output_path": output_path.to_string_lossy(),
+                }));
+            }
+            #[cfg(feature = "search")]
+            if let Some(index) = &self.index {
+                time_phase(usage, Phase::Index, || {
+                    profile_span!("index");
+                    index.index_output(&output, Some(&text), Some(&job.job_id))
+                })?;
+            }
+
+            self.store.update_progress(&job.job_id, (index + 1) as u32)?;
+            self.checkpoint(job, index + 1)?;
+        }
+
+        Ok(true)
+    }
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+}
+
+// Python bindings - every call opens the job database so separate processes can share it
+#[pyfunction]
+#[pyo3(signature = (db_path, inputs, output_dir, priority=0, deadline=None, pages=None, sections=None, policies=None, tenant_id=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn submit_job(
+    py: Python,
+    db_path: &str,
+    inputs: Vec<String>,
+    output_dir: String,
+    priority: i64,
+    deadline: Option<&str>,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+    policies: Option<HashMap<String, String>>,
+    tenant_id: Option<String>,
+) -> PyResult<String> {
+    py.allow_threads(|| {
+        let scope = scope_from_args(pages, sections)?;
+        if let Some(collision) = output_collision(&inputs) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(collision));
+        }
+        let policies = policies.unwrap_or_default().into_iter()
+            .map(|(input, policy)| Ok((input, DocumentPolicy::parse(&policy)?)))
+            .collect::<Result<BTreeMap<_, _>, String>>()
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let deadline = deadline
+            .map(|d| DateTime::parse_from_rfc3339(d).map(|d| d.with_timezone(&Utc)))
+            .transpose()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid deadline: {}", e)))?;
+        let store = JobStore::open(db_path).map_err(to_py_err)?;
+        let job = store.submit(inputs, output_dir, priority, deadline, scope, policies, tenant_id).map_err(to_py_err)?;
+        Ok(job.job_id)
+    })
+}
+
+#[pyfunction]
+pub fn get_job_status(py: Python, db_path: &str, job_id: &str) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let store = JobStore::open(db_path).map_err(to_py_err)?;
+        store.get(job_id)
+            .map_err(to_py_err)?
+            .map(|job| job.get_info())
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Unknown job: {}", job_id)))
+    })
+}
+
+#[pyfunction]
+pub fn cancel_job(py: Python, db_path: &str, job_id: &str) -> PyResult<bool> {
+    py.allow_threads(|| {
+        let store = JobStore::open(db_path).map_err(to_py_err)?;
+        store.cancel(job_id).map_err(to_py_err)
+    })
+}
+
+// Requeues a failed or cancelled job; it resumes after the last input it finished
+#[pyfunction]
+pub fn retry_job(py: Python, db_path: &str, job_id: &str) -> PyResult<bool> {
+    py.allow_threads(|| {
+        let store = JobStore::open(db_path).map_err(to_py_err)?;
+        store.requeue(job_id).map_err(to_py_err)
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (db_path, status=None, limit=100))]
+pub fn list_jobs(py: Python, db_path: &str, status: Option<&str>, limit: u32) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let status = status
+            .map(|s| JobStatus::parse(s).ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown job status: {}", s))
+            }))
+            .transpose()?;
+        let store = JobStore::open(db_path).map_err(to_py_err)?;
+        let jobs = store.list(status, limit).map_err(to_py_err)?;
+        Ok(jobs.iter().map(|job| job.get_info()).collect())
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (db_path, rules_path, max_jobs=None, index_dir=None, license_path=None, workers=1, export_format=None, manifest_path=None, llm_config_path=None, usage_path=None, profile_path=None, webhooks_path=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn run_jobs(
+    py: Python,
+    db_path: &str,
+    rules_path: &str,
+    max_jobs: Option<usize>,
+    index_dir: Option<&str>,
+    license_path: Option<&str>,
+    workers: usize,
+    export_format: Option<&str>,
+    manifest_path: Option<&str>,
+    llm_config_path: Option<&str>,
+    usage_path: Option<&str>,
+    profile_path: Option<&str>,
+    webhooks_path: Option<&str>,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    if workers == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("workers must be at least 1"));
+    }
+    let engine = ExtractionEngine::from_rules_file(rules_path).map_err(to_py_err)?;
+    // The search index holds records in the clear, which encrypted delivery exists to prevent
+    if index_dir.is_some() && engine.get_delivery().is_enabled() {
+        return Err(to_py_err("index_dir cannot be used with outputs encrypted to delivery.recipients".into()));
+    }
+    let store = JobStore::open(db_path).map_err(to_py_err)?;
+    // Checkpoints live in the job database next to the jobs
+    let checkpoints = SqliteStorage::open(db_path).map_err(to_py_err)?;
+    let license = license_path.map(License::load_signed).transpose().map_err(to_py_err)?;
+    // Reproducible runs list every output they write, with its hashes, in a manifest
+    let manifest = match manifest_path {
+        Some(_) => {
+            let tier = license.as_ref().map(License::get_tier).unwrap_or(UNLICENSED_TIER);
+            let recorder = ManifestRecorder::new(rules_path, &engine, tier, export_format, llm_config_path.is_some());
+            Some(Arc::new(recorder.map_err(to_py_err)?))
+        }
+        None => None,
+    };
+    let runner = JobRunner::new(store, engine).with_checkpoints(Arc::new(checkpoints));
+    let runner = match license {
+        Some(license) => runner.with_license(license),
+        None => runner,
+    };
+    let runner = match &manifest {
+        Some(manifest) => runner.with_manifest(Arc::clone(manifest)),
+        None => runner,
+    };
+    // "json" is the output itself
+    let runner = match export_format {
+        Some(format) if format != JSON_FORMAT => runner.with_export_format(format).map_err(to_py_err)?,
+        _ => runner,
+    };
+    #[cfg(feature = "llm")]
+    let runner = match llm_config_path {
+        Some(llm_config_path) => {
+            let config = LlmConfig::load(llm_config_path).map_err(to_py_err)?;
+            runner.with_assistant(LlmAssistant::from_config(config).map_err(to_py_err)?)
+        }
+        None => runner,
+    };
+    #[cfg(not(feature = "llm"))]
+    if llm_config_path.is_some() {
+        return Err(to_py_err("llm_config_path needs a build with the llm feature".into()));
+    }
+    #[cfg(feature = "search")]
+    let runner = match index_dir {
+        Some(index_dir) => runner.with_index(RecordIndex::open(index_dir).map_err(to_py_err)?),
+        None => runner,
+    };
+    #[cfg(not(feature = "search"))]
+    if index_dir.is_some() {
+        return Err(to_py_err("index_dir needs a build with the search feature".into()));
+    }
+    // A JSON list of webhooks, told of each output and of each job as it completes or fails
+    let notifier = webhooks_path.map(load_webhooks).transpose().map_err(to_py_err)?
+        .map(|hooks| Arc::new(WebhookNotifier::new(hooks)));
+    let runner = match &notifier {
+        Some(notifier) => runner.with_notifier(Arc::clone(notifier)),
+        None => runner,
+    };
+    #[cfg(not(feature = "profiling"))]
+    if profile_path.is_some() {
+        return Err(to_py_err("profile_path needs a build with the profiling feature".into()));
+    }
+
+    // Started last, so loading the rules and opening the index are not part of the run
+    let usage = usage_path.map(|_| Arc::new(RunUsage::start()));
+    #[cfg(feature = "profiling")]
+    let profile = profile_path.map(|_| crate::jobs::profiling::start()).transpose().map_err(|e| to_py_err(e.into()))?;
+    let runner = match &usage {
+        Some(usage) => runner.with_usage(Arc::clone(usage)),
+        None => runner,
+    };
+
+    let finished = py.allow_threads(|| {
+        let finished = runner.run_workers(db_path, workers, max_jobs).map_err(|e| e.to_string());
+        if let Some(notifier) = &notifier {
+            notifier.wait();
+        }
+        finished
+    })
+    .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)?;
+    if let (Some(manifest), Some(manifest_path)) = (manifest, manifest_path) {
+        manifest.write(manifest_path).map_err(to_py_err)?;
+    }
+    if let (Some(usage), Some(usage_path)) = (usage, usage_path) {
+        usage.write(usage_path, workers).map_err(to_py_err)?;
+    }
+    #[cfg(feature = "profiling")]
+    if let (Some(profile), Some(profile_path)) = (profile, profile_path) {
+        profile.finish().write(profile_path).map_err(to_py_err)?;
+    }
+    Ok(finished.iter().map(|job| job.get_info()).collect())
+}
@@ -0,0 +1,326 @@
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use chrono::Utc;
+
+use crate::engine::extractor::{ExtractionEngine, RULE_PACK_FIELD};
+use crate::engine::profile::profile_hash;
+use crate::engine::scope::ExtractionScope;
+use crate::export::delta::{compute_delta, DeltaReport};
+use crate::jobs::manifest::file_hash;
+use crate::jobs::policy::DocumentPolicy;
+use crate::jobs::runner::extract_payload;
+use crate::schema::envelope::{hash_input, ENGINE_VERSION};
+use crate::storage::atomic::write_atomic;
+
+// Job ID the payloads of a snapshot are extracted under, the same for both rules versions so
+// it never shows up as a change
+const SNAPSHOT_JOB_ID: &str = "snapshot";
+
+// One side of a snapshot diff: a rules file and what identifies its version
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotRules {
+    pub rules_path: String,
+    pub rules_hash: String,
+    pub rules_version: String,
+    pub profile: String,
+}
+
+impl SnapshotRules {
+    // What records of this version carry in their rule_pack field
+    fn pack(&self) -> String {
+        format!("{}@{}", self.profile, self.rules_version)
+    }
+
+    fn load(rules_path: &str) -> Result<(Self, ExtractionEngine), Box<dyn std::error::Error>> {
+        let engine = ExtractionEngine::from_rules_file(rules_path)?;
+        let rules = Self {
+            rules_path: rules_path.to_string(),
+            rules_hash: profile_hash(rules_path)?,
+            rules_version: engine.get_rules_version().to_string(),
+            profile: engine.get_profile_name().to_string(),
+        };
+        Ok((rules, engine))
+    }
+}
+
+// Added, modified, removed and unchanged records, for a document or a whole corpus
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RecordCounts {
+    pub added: usize,
+    pub modified: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+impl RecordCounts {
+    pub fn changed(&self) -> bool {
+        self.added + self.modified + self.removed > 0
+    }
+
+    fn add(&mut self, other: &RecordCounts) {
+        self.added += other.added;
+        self.modified += other.modified;
+        self.removed += other.removed;
+        self.unchanged += other.unchanged;
+    }
+}
+
+// How one document's records moved between the rules versions
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentDiff {
+    pub input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_hash: Option<String>,
+    pub counts: RecordCounts,
+    pub collections: BTreeMap<String, RecordCounts>,
+    // Whether each side's payload came from the cache rather than an extraction
+    pub before_cached: bool,
+    pub after_cached: bool,
+    pub added: Vec<Value>,
+    pub modified: Vec<Value>,
+    pub removed: Vec<Value>,
+    // Either side failed to extract; nothing is compared
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DocumentDiff {
+    fn failed(input: &str, error: String) -> Self {
+        Self {
+            input: input.to_string(),
+            input_hash: None,
+            counts: RecordCounts::default(),
+            collections: BTreeMap::new(),
+            before_cached: false,
+            after_cached: false,
+            added: Vec::new(),
+            modified: Vec::new(),
+            removed: Vec::new(),
+            error: Some(error),
+        }
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("input".to_string(), self.input.clone());
+        info.insert("status".to_string(), match (&self.error, self.counts.changed()) {
+            (Some(_), _) => "error",
+            (None, true) => "changed",
+            (None, false) => "unchanged",
+        }.to_string());
+        info.insert("added".to_string(), self.counts.added.to_string());
+        info.insert("modified".to_string(), self.counts.modified.to_string());
+        info.insert("removed".to_string(), self.counts.removed.to_string());
+        info.insert("unchanged".to_string(), self.counts.unchanged.to_string());
+        info.insert("cached".to_string(), (self.before_cached && self.after_cached).to_string());
+        if let Some(error) = &self.error {
+            info.insert("error".to_string(), error.clone());
+        }
+        info
+    }
+}
+
+// What a rule-pack release changes across a corpus: record counts per document and in total,
+// and the records themselves, for review before the release is approved
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotDiff {
+    pub generated_at: String,
+    pub engine_version: String,
+    pub before: SnapshotRules,
+    pub after: SnapshotRules,
+    pub documents: Vec<DocumentDiff>,
+    pub totals: RecordCounts,
+    pub collections: BTreeMap<String, RecordCounts>,
+}
+
+impl SnapshotDiff {
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let changed = self.documents.iter().filter(|document| document.counts.changed()).count();
+        let errors = self.documents.iter().filter(|document| document.error.is_some()).count();
+        let mut summary = HashMap::new();
+        summary.insert("before_version".to_string(), self.before.rules_version.clone());
+        summary.insert("after_version".to_string(), self.after.rules_version.clone());
+        summary.insert("documents".to_string(), self.documents.len().to_string());
+        summary.insert("documents_changed".to_string(), changed.to_string());
+        summary.insert("errors".to_string(), errors.to_string());
+        summary.insert("added".to_string(), self.totals.added.to_string());
+        summary.insert("modified".to_string(), self.totals.modified.to_string());
+        summary.insert("removed".to_string(), self.totals.removed.to_string());
+        summary.insert("unchanged".to_string(), self.totals.unchanged.to_string());
+        summary
+    }
+}
+
+// Payloads extracted with one rules version, kept under <cache_dir>/<key>.json. The key covers
+// the engine version, the rules and the input as read, so a stale payload is never reused
+struct PayloadCache<'a> {
+    dir: Option<&'a Path>,
+}
+
+impl PayloadCache<'_> {
+    fn path(&self, rules: &SnapshotRules, input_hash: &str) -> Option<std::path::PathBuf> {
+        let key = hash_input(format!("{}\n{}\n{}", ENGINE_VERSION, rules.rules_hash, input_hash).as_bytes());
+        self.dir.map(|dir| dir.join(format!("{}.json", key.trim_start_matches("sha256:"))))
+    }
+
+    // The payload of `input` under `rules`, and whether it came from the cache
+    fn payload(&self, engine: &ExtractionEngine, rules: &SnapshotRules, input: &str, input_hash: &str) -> Result<(Value, bool), Box<dyn std::error::Error>> {
+        let path = self.path(rules, input_hash);
+        if let Some(cached) = path.as_ref().and_then(|path| std::fs::read(path).ok()) {
+            if let Ok(payload) = serde_json::from_slice(&cached) {
+                return Ok((payload, true));
+            }
+        }
+        let (payload, _) = extract_payload(engine, SNAPSHOT_JOB_ID, &ExtractionScope::default(), input, DocumentPolicy::RulesOnly, None)?;
+        if let Some(path) = path {
+            write_atomic(&path, serde_json::to_vec(&payload)?).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        }
+        Ok((payload, false))
+    }
+}
+
+fn collection_counts(delta: &DeltaReport, unchanged: &BTreeMap<String, usize>) -> BTreeMap<String, RecordCounts> {
+    let mut collections: BTreeMap<String, RecordCounts> = BTreeMap::new();
+    let collection_of = |entry: &Value| entry.get("collection").and_then(Value::as_str).unwrap_or_default().to_string();
+    for entry in &delta.added {
+        collections.entry(collection_of(entry)).or_default().added += 1;
+    }
+    for entry in &delta.modified {
+        collections.entry(collection_of(entry)).or_default().modified += 1;
+    }
+    for entry in &delta.removed {
+        collections.entry(collection_of(entry)).or_default().removed += 1;
+    }
+    for (collection, count) in unchanged {
+        collections.entry(collection.clone()).or_default().unchanged += count;
+    }
+    collections
+}
+
+// Records of `after`, by collection, that `before` has under the same record_id unchanged
+fn unchanged_by_collection(before: &Value, after: &Value) -> BTreeMap<String, usize> {
+    let records = |payload: &Value| -> Vec<(String, Value)> {
+        payload.as_object().into_iter().flatten()
+            .flat_map(|(collection, records)| records.as_array().into_iter().flatten().map(move |record| (collection.clone(), record.clone())))
+            .collect()
+    };
+    let previous: HashMap<String, Value> = records(before).into_iter()
+        .filter_map(|(_, record)| Some((record.get("record_id")?.as_str()?.to_string(), record)))
+        .collect();
+    let mut unchanged = BTreeMap::new();
+    for (collection, record) in records(after) {
+        let kept = record.get("record_id").and_then(Value::as_str)
+            .and_then(|record_id| previous.get(record_id))
+            .is_some_and(|other| *other == record);
+        if kept {
+            *unchanged.entry(collection).or_default() += 1;
+        }
+    }
+    unchanged
+}
+
+// Records name the pack and version that produced them, so every record of a new release would
+// differ in rule_pack alone. The previous version's records are stamped with the new one first
+fn restamp(payload: &mut Value, before: &str, after: &str) {
+    for records in payload.as_object_mut().into_iter().flat_map(|collections| collections.values_mut()) {
+        for record in records.as_array_mut().into_iter().flatten() {
+            if let Some(pack) = record.get_mut(RULE_PACK_FIELD).filter(|pack| pack.as_str() == Some(before)) {
+                *pack = Value::String(after.to_string());
+            }
+        }
+    }
+}
+
+fn diff_document(
+    engines: (&ExtractionEngine, &ExtractionEngine),
+    rules: (&SnapshotRules, &SnapshotRules),
+    cache: &PayloadCache,
+    input: &str,
+) -> Result<DocumentDiff, Box<dyn std::error::Error>> {
+    let input_hash = file_hash(input)?;
+    let (mut before, before_cached) = cache.payload(engines.0, rules.0, input, &input_hash)?;
+    let (after, after_cached) = cache.payload(engines.1, rules.1, input, &input_hash)?;
+    restamp(&mut before, &rules.0.pack(), &rules.1.pack());
+    let delta = compute_delta(&before, &after, Some(&rules.0.rules_version));
+    let collections = collection_counts(&delta, &unchanged_by_collection(&before, &after));
+    Ok(DocumentDiff {
+        input: input.to_string(),
+        input_hash: Some(input_hash),
+        counts: RecordCounts {
+            added: delta.added.len(),
+            modified: delta.modified.len(),
+            removed: delta.removed.len(),
+            unchanged: delta.unchanged,
+        },
+        collections,
+        before_cached,
+        after_cached,
+        added: delta.added,
+        modified: delta.modified,
+        removed: delta.removed,
+        error: None,
+    })
+}
+
+// Runs both rules versions over the corpus with the rules-only policy and compares the records
+// of every document by record_id. A document that fails under either version is reported with
+// its error rather than stopping the diff
+pub fn diff_snapshots(before_rules: &str, after_rules: &str, inputs: &[String], cache_dir: Option<&str>) -> Result<SnapshotDiff, Box<dyn std::error::Error>> {
+    let (before, before_engine) = SnapshotRules::load(before_rules)?;
+    let (after, after_engine) = SnapshotRules::load(after_rules)?;
+    if let Some(dir) = cache_dir {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Cannot create cache directory {}: {}", dir, e))?;
+    }
+    let cache = PayloadCache { dir: cache_dir.map(Path::new) };
+
+    let mut totals = RecordCounts::default();
+    let mut collections: BTreeMap<String, RecordCounts> = BTreeMap::new();
+    let mut documents = Vec::new();
+    for input in inputs {
+        let document = diff_document((&before_engine, &after_engine), (&before, &after), &cache, input)
+            .unwrap_or_else(|e| DocumentDiff::failed(input, e.to_string()));
+        totals.add(&document.counts);
+        for (collection, counts) in &document.collections {
+            collections.entry(collection.clone()).or_default().add(counts);
+        }
+        documents.push(document);
+    }
+    Ok(SnapshotDiff {
+        generated_at: Utc::now().to_rfc3339(),
+        engine_version: ENGINE_VERSION.to_string(),
+        before,
+        after,
+        documents,
+        totals,
+        collections,
+    })
+}
+
+type SnapshotResults = (HashMap<String, String>, Vec<HashMap<String, String>>);
+
+// Python binding - writes the full report to report_path and returns the totals with one
+// entry per document
+#[pyfunction]
+#[pyo3(signature = (before_rules, after_rules, inputs, report_path, cache_dir=None))]
+pub fn diff_rules_snapshot(
+    py: Python,
+    before_rules: &str,
+    after_rules: &str,
+    inputs: Vec<String>,
+    report_path: &str,
+    cache_dir: Option<&str>,
+) -> PyResult<SnapshotResults> {
+    if inputs.is_empty() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("inputs must name at least one document"));
+    }
+    let diff = py.allow_threads(|| {
+        let diff = diff_snapshots(before_rules, after_rules, &inputs, cache_dir).map_err(|e| e.to_string())?;
+        let report = serde_json::to_string_pretty(&diff).map_err(|e| e.to_string())?;
+        write_atomic(report_path, report + "\n").map_err(|e| format!("Cannot write report {}: {}", report_path, e))?;
+        Ok(diff)
+    }).map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, String>)?;
+    Ok((diff.get_summary(), diff.documents.iter().map(DocumentDiff::get_info).collect()))
+}
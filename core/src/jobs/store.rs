@@ -0,0 +1,361 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::engine::scope::ExtractionScope;
+use crate::jobs::policy::DocumentPolicy;
+
+// How long a connection waits for another worker's write to finish before failing
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+    Expired,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "completed" => Some(JobStatus::Completed),
+            "failed" => Some(JobStatus::Failed),
+            "cancelled" => Some(JobStatus::Cancelled),
+            "expired" => Some(JobStatus::Expired),
+            _ => None,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Running)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub job_id: String,
+    pub inputs: Vec<String>,
+    pub output_dir: String,
+    pub priority: i64,
+    pub deadline: Option<DateTime<Utc>>,
+    pub status: JobStatus,
+    pub progress_done: u32,
+    pub progress_total: u32,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    // Pages and sections every input is extracted from
+    pub scope: ExtractionScope,
+    // Inputs processed other than in full, by input path
+    pub policies: BTreeMap<String, DocumentPolicy>,
+    // Server tenant whose queries see the job; None for jobs only local callers see
+    pub tenant_id: Option<String>,
+}
+
+impl Job {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let inputs: String = row.get("inputs")?;
+        let status: String = row.get("status")?;
+        let scope: Option<String> = row.get("scope")?;
+        let policies: Option<String> = row.get("policies")?;
+        Ok(Self {
+            job_id: row.get("job_id")?,
+            inputs: serde_json::from_str(&inputs).unwrap_or_default(),
+            output_dir: row.get("output_dir")?,
+            priority: row.get("priority")?,
+            deadline: row.get("deadline")?,
+            status: JobStatus::parse(&status).unwrap_or(JobStatus::Failed),
+            progress_done: row.get("progress_done")?,
+            progress_total: row.get("progress_total")?,
+            error: row.get("error")?,
+            created_at: row.get("created_at")?,
+            started_at: row.get("started_at")?,
+            finished_at: row.get("finished_at")?,
+            scope: scope.and_then(|scope| serde_json::from_str(&scope).ok()).unwrap_or_default(),
+            policies: policies.and_then(|policies| serde_json::from_str(&policies).ok()).unwrap_or_default(),
+            tenant_id: row.get("tenant_id")?,
+        })
+    }
+
+    pub fn policy_for(&self, input: &str) -> DocumentPolicy {
+        self.policies.get(input).copied().unwrap_or_default()
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("job_id".to_string(), self.job_id.clone());
+        info.insert("status".to_string(), self.status.as_str().to_string());
+        info.insert("priority".to_string(), self.priority.to_string());
+        info.insert("progress".to_string(), format!("{}/{}", self.progress_done, self.progress_total));
+        info.insert("output_dir".to_string(), self.output_dir.clone());
+        info.insert("created_at".to_string(), self.created_at.to_rfc3339());
+        if let Some(deadline) = self.deadline {
+            info.insert("deadline".to_string(), deadline.to_rfc3339());
+        }
+        if let Some(started_at) = self.started_at {
+            info.insert("started_at".to_string(), started_at.to_rfc3339());
+        }
+        if let Some(finished_at) = self.finished_at {
+            info.insert("finished_at".to_string(), finished_at.to_rfc3339());
+        }
+        if let Some(error) = &self.error {
+            info.insert("error".to_string(), error.clone());
+        }
+        if let Some(tenant_id) = &self.tenant_id {
+            info.insert("tenant_id".to_string(), tenant_id.clone());
+        }
+        if let Some((start, end)) = self.scope.pages {
+            info.insert("pages".to_string(), format!("{}-{}", start, end));
+        }
+        if !self.scope.sections.is_empty() {
+            info.insert("sections".to_string(), self.scope.sections.join(","));
+        }
+        if !self.policies.is_empty() {
+            // Inputs per policy, e.g. "skip=1,ocr-only=3"
+            let mut counts: BTreeMap<DocumentPolicy, usize> = BTreeMap::new();
+            for policy in self.policies.values() {
+                *counts.entry(*policy).or_default() += 1;
+            }
+            let counts: Vec<String> = counts.iter().map(|(policy, count)| format!("{}={}", policy.as_str(), count)).collect();
+            info.insert("policies".to_string(), counts.join(","));
+        }
+        info
+    }
+}
+
+// SQLite-backed job table - the database file is the only state shared between workers
+pub struct JobStore {
+    conn: Connection,
+}
+
+impl JobStore {
+    pub fn open(db_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let conn = Connection::open(db_path)?;
+        // Workers in other threads or processes hold the write lock only briefly
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                job_id TEXT PRIMARY KEY,
+                inputs TEXT NOT NULL,
+                output_dir TEXT NOT NULL,
+                priority INTEGER NOT NULL DEFAULT 0,
+                deadline TEXT,
+                status TEXT NOT NULL,
+                progress_done INTEGER NOT NULL DEFAULT 0,
+                progress_total INTEGER NOT NULL DEFAULT 0,
+                error TEXT,
+                created_at TEXT NOT NULL,
+                started_at TEXT,
+                finished_at TEXT,
+                scope TEXT,
+                policies TEXT,
+                tenant_id TEXT
+            );
+            CREATE INDEX IF NOT EXISTS jobs_queue ON jobs (status, priority, deadline, created_at);",
+        )?;
+        // Databases from before extraction scopes, document policies and tenants lack the columns
+        for column in ["scope", "policies", "tenant_id"] {
+            let exists = conn.prepare("SELECT 1 FROM pragma_table_info('jobs') WHERE name = ?1")?.exists([column])?;
+            if !exists {
+                conn.execute_batch(&format!("ALTER TABLE jobs ADD COLUMN {} TEXT;", column))?;
+            }
+        }
+        Ok(Self { conn })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn submit(
+        &self,
+        inputs: Vec<String>,
+        output_dir: String,
+        priority: i64,
+        deadline: Option<DateTime<Utc>>,
+        scope: ExtractionScope,
+        mut policies: BTreeMap<String, DocumentPolicy>,
+        tenant_id: Option<String>,
+    ) -> Result<Job, Box<dyn std::error::Error>> {
+        if inputs.is_empty() {
+            return Err("Job has no inputs".into());
+        }
+        if tenant_id.as_deref().is_some_and(|tenant_id| tenant_id.trim().is_empty()) {
+            return Err("Tenant id cannot be empty".into());
+        }
+        scope.validate()?;
+        if let Some(input) = policies.keys().find(|input| !inputs.contains(input)) {
+            return Err(format!("Policy given for {}, which is not an input of the job", input).into());
+        }
+        policies.retain(|_, policy| *policy != DocumentPolicy::Full);
+
+        let job = Job {
+            job_id: Uuid::new_v4().to_string(),
+            progress_total: inputs.len() as u32,
+            inputs,
+            output_dir,
+            priority,
+            deadline,
+            status: JobStatus::Queued,
+            progress_done: 0,
+            error: None,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            scope,
+            policies,
+            tenant_id,
+        };
+        let scope = (!job.scope.is_empty()).then(|| serde_json::to_string(&job.scope)).transpose()?;
+        let policies = (!job.policies.is_empty()).then(|| serde_json::to_string(&job.policies)).transpose()?;
+
+        self.conn.execute(
+            "INSERT INTO jobs (job_id, inputs, output_dir, priority, deadline, status, progress_done, progress_total, created_at, scope, policies, tenant_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                job.job_id,
+                serde_json::to_string(&job.inputs)?,
+                job.output_dir,
+                job.priority,
+                job.deadline,
+                job.status.as_str(),
+                job.progress_total,
+                job.created_at,
+                scope,
+                policies,
+                job.tenant_id,
+            ],
+        )?;
+        Ok(job)
+    }
+
+    pub fn get(&self, job_id: &str) -> Result<Option<Job>, Box<dyn std::error::Error>> {
+        let job = self.conn
+            .query_row("SELECT * FROM jobs WHERE job_id = ?1", params![job_id], Job::from_row)
+            .optional()?;
+        Ok(job)
+    }
+
+    pub fn list(&self, status: Option<JobStatus>, limit: u32) -> Result<Vec<Job>, Box<dyn std::error::Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM jobs WHERE (?1 IS NULL OR status = ?1) ORDER BY created_at DESC LIMIT ?2",
+        )?;
+        let jobs = stmt
+            .query_map(params![status.map(|s| s.as_str()), limit], Job::from_row)?
+            .collect::<rusqlite::Result<Vec<Job>>>()?;
+        Ok(jobs)
+    }
+
+    // Jobs of one server tenant, newest first; jobs submitted without a tenant are never listed
+    pub fn list_for_tenant(&self, tenant_id: &str, status: Option<JobStatus>, limit: u32) -> Result<Vec<Job>, Box<dyn std::error::Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM jobs WHERE tenant_id = ?1 AND (?2 IS NULL OR status = ?2) ORDER BY created_at DESC LIMIT ?3",
+        )?;
+        let jobs = stmt
+            .query_map(params![tenant_id, status.map(|s| s.as_str()), limit], Job::from_row)?
+            .collect::<rusqlite::Result<Vec<Job>>>()?;
+        Ok(jobs)
+    }
+
+    // A job of another tenant reads as unknown
+    pub fn get_for_tenant(&self, tenant_id: &str, job_id: &str) -> Result<Option<Job>, Box<dyn std::error::Error>> {
+        let job = self.conn
+            .query_row("SELECT * FROM jobs WHERE job_id = ?1 AND tenant_id = ?2", params![job_id, tenant_id], Job::from_row)
+            .optional()?;
+        Ok(job)
+    }
+
+    pub fn cancel(&self, job_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        // Running jobs observe the cancellation between documents
+        let changed = self.conn.execute(
+            "UPDATE jobs SET status = 'cancelled', finished_at = ?2
+             WHERE job_id = ?1 AND status IN ('queued', 'running')",
+            params![job_id, Utc::now()],
+        )?;
+        Ok(changed > 0)
+    }
+
+    // Back in the queue with its progress and error cleared. Queued, running and expired jobs
+    // are left alone
+    pub fn requeue(&self, job_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let changed = self.conn.execute(
+            "UPDATE jobs SET status = 'queued', progress_done = 0, error = NULL, started_at = NULL, finished_at = NULL
+             WHERE job_id = ?1 AND status IN ('failed', 'cancelled')",
+            params![job_id],
+        )?;
+        Ok(changed > 0)
+    }
+
+    // Highest priority first, then earliest deadline, then submission order
+    pub fn claim_next(&mut self) -> Result<Option<Job>, Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        let tx = self.conn.transaction()?;
+
+        // Layer 1: Queued jobs whose deadline already passed will never be started
+        tx.execute(
+            "UPDATE jobs SET status = 'expired', finished_at = ?1
+             WHERE status = 'queued' AND deadline IS NOT NULL AND deadline < ?1",
+            params![now],
+        )?;
+
+        // Layer 2: Pick and mark the next job as running
+        let job = tx
+            .query_row(
+                "SELECT * FROM jobs WHERE status = 'queued'
+                 ORDER BY priority DESC, deadline IS NULL, deadline ASC, created_at ASC LIMIT 1",
+                [],
+                Job::from_row,
+            )
+            .optional()?;
+        let job = match job {
+            Some(mut job) => {
+                tx.execute(
+                    "UPDATE jobs SET status = 'running', started_at = ?2 WHERE job_id = ?1",
+                    params![job.job_id, now],
+                )?;
+                job.status = JobStatus::Running;
+                job.started_at = Some(now);
+                Some(job)
+            }
+            None => None,
+        };
+
+        tx.commit()?;
+        Ok(job)
+    }
+
+    pub fn update_progress(&self, job_id: &str, done: u32) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn.execute(
+            "UPDATE jobs SET progress_done = ?2 WHERE job_id = ?1",
+            params![job_id, done],
+        )?;
+        Ok(())
+    }
+
+    pub fn finish(&self, job_id: &str, status: JobStatus, error: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+        // A cancellation recorded while the job ran is never overwritten
+        self.conn.execute(
+            "UPDATE jobs SET status = ?2, error = ?3, finished_at = ?4
+             WHERE job_id = ?1 AND status = 'running'",
+            params![job_id, status.as_str(), error, Utc::now()],
+        )?;
+        Ok(())
+    }
+}
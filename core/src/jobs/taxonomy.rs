@@ -0,0 +1,517 @@
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+use utoipa::ToSchema;
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::splitter::PAGE_BREAK;
+use crate::engine::taxonomy::{TaxonomySchema, TaxonomyTerm};
+use crate::jobs::runner::output_path_for;
+use crate::jobs::store::JobStore;
+use crate::schema::envelope::payload_of;
+
+// ATA iSpec 2200 chapter titles shown in the browse tree; other chapters are "ATA <nn>"
+pub(crate) const ATA_CHAPTERS: &[(&str, &str)] = &[
+    ("05", "Time Limits / Maintenance Checks"),
+    ("06", "Dimensions and Areas"),
+    ("07", "Lifting and Shoring"),
+    ("08", "Leveling and Weighing"),
+    ("09", "Towing and Taxiing"),
+    ("10", "Parking, Mooring, Storage and Return to Service"),
+    ("11", "Placards and Markings"),
+    ("12", "Servicing"),
+    ("20", "Standard Practices - Airframe"),
+    ("21", "Air Conditioning"),
+    ("22", "Auto Flight"),
+    ("23", "Communications"),
+    ("24", "Electrical Power"),
+    ("25", "Equipment / Furnishings"),
+    ("26", "Fire Protection"),
+    ("27", "Flight Controls"),
+    ("28", "Fuel"),
+    ("29", "Hydraulic Power"),
+    ("30", "Ice and Rain Protection"),
+    ("31", "Indicating / Recording Systems"),
+    ("32", "Landing Gear"),
+    ("33", "Lights"),
+    ("34", "Navigation"),
+    ("35", "Oxygen"),
+    ("36", "Pneumatic"),
+    ("38", "Water / Waste"),
+    ("45", "Central Maintenance System"),
+    ("46", "Information Systems"),
+    ("49", "Airborne Auxiliary Power"),
+    ("51", "Standard Practices and Structures"),
+    ("52", "Doors"),
+    ("53", "Fuselage"),
+    ("54", "Nacelles / Pylons"),
+    ("55", "Stabilizers"),
+    ("56", "Windows"),
+    ("57", "Wings"),
+    ("61", "Propellers"),
+    ("71", "Power Plant"),
+    ("72", "Engine"),
+    ("73", "Engine Fuel and Control"),
+    ("74", "Ignition"),
+    ("75", "Air"),
+    ("76", "Engine Controls"),
+    ("77", "Engine Indicating"),
+    ("78", "Exhaust"),
+    ("79", "Oil"),
+    ("80", "Starting"),
+];
+
+pub fn chapter_title(chapter: &str) -> String {
+    ATA_CHAPTERS.iter()
+        .find(|(number, _)| *number == chapter)
+        .map(|(_, title)| title.to_string())
+        .unwrap_or_else(|| format!("ATA {}", chapter))
+}
+
+fn is_digits(part: &str, len: usize) -> bool {
+    part.len() == len && part.chars().all(|c| c.is_ascii_digit())
+}
+
+// Chapter and section of an ATA reference: "36-11-00-000-801" is chapter 36, section 36-11.
+// A model prefix is skipped ("A320-29-1102" and "737-32-1234" are chapter 29 and 32); dates are not
+pub fn parse_ata(token: &str) -> Option<(String, String)> {
+    let parts: Vec<&str> = token.split('-').collect();
+    let first = parts.iter().position(|part| is_digits(part, 2))?;
+    let prefix_ok = parts[..first].iter().all(|part| is_digits(part, 3) || part.chars().any(|c| c.is_ascii_alphabetic()));
+    let next = parts.get(first + 1)?;
+    if !prefix_ok || next.is_empty() || !next.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let chapter = parts[first].to_string();
+    // Only a standard chapter-section-subject number names a section
+    let section = if first == 0 && next.len() == 2 { next.to_string() } else { "00".to_string() };
+    Some((chapter.clone(), format!("{}-{}", chapter, section)))
+}
+
+// First ATA reference among the words of `text`
+pub fn find_ata(text: &str) -> Option<(String, String, String)> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+        .find_map(|word| parse_ata(word).map(|(chapter, section)| (chapter, section, word.to_string())))
+}
+
+// Levels of an ATA reference that name something: "32-11-00-000-801" is chapter 32,
+// section 32-11 and subject 32-11-00. Model-prefixed references only name a chapter
+fn ata_levels(token: &str) -> Option<Vec<String>> {
+    let (chapter, _) = parse_ata(token)?;
+    let parts: Vec<&str> = token.split('-').collect();
+    let mut levels = vec![chapter];
+    if is_digits(parts[0], 2) && parts.get(1).is_some_and(|part| is_digits(part, 2)) {
+        levels.push(format!("{}-{}", parts[0], parts[1]));
+        if parts.get(2).is_some_and(|part| is_digits(part, 2)) {
+            levels.push(format!("{}-{}-{}", parts[0], parts[1], parts[2]));
+        }
+    }
+    Some(levels)
+}
+
+// Names of the levels in an ATA path, top down
+pub(crate) const TAXONOMY_LEVELS: &[&str] = &["chapter", "section", "subject"];
+
+// A chapter title in the text ("Landing Gear") is weaker evidence than a reference to it
+const TITLE_WEIGHT: f64 = 0.5;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TaxonomyAlternate {
+    pub label: String,
+    pub score: f64,
+}
+
+// One level of a classified path. Scores are the share of all the text's evidence that
+// supports the path down to this level, so they never rise from chapter to subject
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TaxonomyLevel {
+    pub level: String,
+    pub label: String,
+    pub score: f64,
+    // Runners-up under the same parent, best first
+    pub alternates: Vec<TaxonomyAlternate>,
+}
+
+fn round_score(score: f64) -> f64 {
+    (score * 1000.0).round() / 1000.0
+}
+
+// Classifies text into the ATA taxonomy from the references and chapter titles it mentions.
+// Each level is picked from the candidates under the level above it; the path stops at the
+// deepest level any reference names. Empty when the text holds no evidence at all
+pub fn classify_taxonomy_text(text: &str, max_alternates: usize) -> Vec<TaxonomyLevel> {
+    let references: Vec<Vec<String>> = text.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+        .filter_map(ata_levels)
+        .collect();
+    // Chapter titles vote once each, for their chapter alone - "Engine" or "Fuel" turn up in
+    // most manuals, so repeating them is no stronger evidence
+    let words: Vec<String> = text.unicode_words().map(str::to_lowercase).collect();
+    let title_votes: Vec<&str> = ATA_CHAPTERS.iter()
+        .filter(|(_, title)| {
+            let title: Vec<String> = title.unicode_words().map(str::to_lowercase).collect();
+            words.windows(title.len()).any(|window| window == title.as_slice())
+        })
+        .map(|(chapter, _)| *chapter)
+        .collect();
+    let total = references.len() as f64 + title_votes.len() as f64 * TITLE_WEIGHT;
+    if total == 0.0 {
+        return Vec::new();
+    }
+
+    let mut path: Vec<TaxonomyLevel> = Vec::new();
+    for (depth, level) in TAXONOMY_LEVELS.iter().enumerate() {
+        // Candidates at this depth under the labels already chosen
+        let mut votes: BTreeMap<String, f64> = BTreeMap::new();
+        for reference in &references {
+            let under_path = path.iter().zip(reference).all(|(chosen, label)| chosen.label == *label);
+            if under_path {
+                if let Some(label) = reference.get(depth) {
+                    *votes.entry(label.clone()).or_default() += 1.0;
+                }
+            }
+        }
+        if depth == 0 {
+            for chapter in &title_votes {
+                *votes.entry(chapter.to_string()).or_default() += TITLE_WEIGHT;
+            }
+        }
+        let mut ranked: Vec<(String, f64)> = votes.into_iter().collect();
+        // Ties go to the lower label, so the same text always classifies the same way
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let Some((label, votes)) = ranked.first().cloned() else {
+            break;
+        };
+        path.push(TaxonomyLevel {
+            level: level.to_string(),
+            label,
+            score: round_score(votes / total),
+            alternates: ranked.into_iter().skip(1).take(max_alternates)
+                .map(|(label, votes)| TaxonomyAlternate { label, score: round_score(votes / total) })
+                .collect(),
+        });
+    }
+    path
+}
+
+// A term's own evidence in the text: one vote per word that is its code, and half a vote,
+// once, when its label appears
+fn term_evidence(term: &TaxonomyTerm, codes: &HashMap<&str, usize>, words: &[String]) -> f64 {
+    let label: Vec<String> = term.label.unicode_words().map(str::to_lowercase).collect();
+    let titled = !label.is_empty() && words.windows(label.len()).any(|window| window == label.as_slice());
+    *codes.get(term.code.as_str()).unwrap_or(&0) as f64 + if titled { TITLE_WEIGHT } else { 0.0 }
+}
+
+// A term's votes: its own evidence and that of every term below it, as a subject reference is
+// a vote for its section and chapter too
+fn term_votes(term: &TaxonomyTerm, codes: &HashMap<&str, usize>, words: &[String]) -> f64 {
+    term_evidence(term, codes, words) + term.children.iter().map(|child| term_votes(child, codes, words)).sum::<f64>()
+}
+
+// Classifies text into a rules taxonomy the way classify_taxonomy_text does into the ATA one.
+// Terms are named by their codes, and ties go to the term listed first
+pub fn classify_terms(text: &str, taxonomy: &TaxonomySchema, max_alternates: usize) -> Vec<TaxonomyLevel> {
+    let mut codes: HashMap<&str, usize> = HashMap::new();
+    for word in text.split_whitespace().map(|word| word.trim_matches(|c: char| !c.is_alphanumeric())) {
+        *codes.entry(word).or_default() += 1;
+    }
+    let words: Vec<String> = text.unicode_words().map(str::to_lowercase).collect();
+    let total: f64 = taxonomy.terms.iter().map(|term| term_votes(term, &codes, &words)).sum();
+    if total == 0.0 {
+        return Vec::new();
+    }
+
+    let mut path: Vec<TaxonomyLevel> = Vec::new();
+    let mut candidates = taxonomy.terms.as_slice();
+    for level in &taxonomy.levels {
+        let mut ranked: Vec<(&TaxonomyTerm, f64)> = candidates.iter()
+            .map(|term| (term, term_votes(term, &codes, &words)))
+            .filter(|(_, votes)| *votes > 0.0)
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let Some(&(term, votes)) = ranked.first() else {
+            break;
+        };
+        path.push(TaxonomyLevel {
+            level: level.name.clone(),
+            label: term.code.clone(),
+            score: round_score(votes / total),
+            alternates: ranked.iter().skip(1).take(max_alternates)
+                .map(|(term, votes)| TaxonomyAlternate { label: term.code.clone(), score: round_score(votes / total) })
+                .collect(),
+        });
+        candidates = &term.children;
+    }
+    path
+}
+
+fn field<'a>(record: &'a Value, name: &str) -> &'a str {
+    record.get(name).and_then(Value::as_str).unwrap_or("")
+}
+
+// The whole line a match sits on - titles usually carry the task number before the pattern
+fn line_at(text: &str, offset: usize) -> Option<&str> {
+    if !text.is_char_boundary(offset) {
+        return None;
+    }
+    let start = text[..offset].rfind('\n').map(|index| index + 1).unwrap_or(0);
+    let end = text[offset..].find('\n').map(|index| offset + index).unwrap_or(text.len());
+    text.get(start..end)
+}
+
+// First-page text before the line holding `first_title`
+fn front_matter(text: &str, first_title: Option<usize>) -> &str {
+    let first_page = text.split(PAGE_BREAK).next().unwrap_or_default();
+    let end = match first_title {
+        Some(offset) => first_page.get(..offset.min(first_page.len())).and_then(|head| head.rfind('\n')).unwrap_or(0),
+        None => first_page.len(),
+    };
+    &first_page[..end]
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProcedureNode {
+    pub module_id: String,
+    pub title: String,
+    // The reference the procedure was filed under, e.g. "36-11-00-000-801"
+    pub ata_reference: Option<String>,
+    pub step_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DocumentNode {
+    // The output file path, as in GraphQL document IDs
+    pub id: String,
+    pub job_id: String,
+    pub source: String,
+    pub procedures: Vec<ProcedureNode>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SectionNode {
+    pub section: String,
+    pub procedure_count: usize,
+    pub documents: Vec<DocumentNode>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ChapterNode {
+    pub chapter: String,
+    pub title: String,
+    pub document_count: usize,
+    pub procedure_count: usize,
+    pub sections: Vec<SectionNode>,
+}
+
+// ATA chapter -> section -> document -> procedure over every output in a job store. A document
+// is listed under each section it has procedures in, with just those procedures
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct TaxonomyTree {
+    pub chapters: Vec<ChapterNode>,
+    // Documents whose procedures carry no ATA reference
+    pub unclassified: Vec<DocumentNode>,
+    pub document_count: usize,
+    pub procedure_count: usize,
+    // Outputs that could not be read or parsed
+    pub skipped: Vec<String>,
+}
+
+// Procedures of one output with the (chapter, section) each is filed under
+fn classify_output(output: &Value, text: Option<&str>) -> Vec<(Option<(String, String)>, ProcedureNode)> {
+    let payload = payload_of(output);
+    let modules: Vec<&Value> = payload.get("modules").and_then(Value::as_array).into_iter().flatten().collect();
+    let procedures: Vec<&Value> = payload.get("procedures").and_then(Value::as_array).into_iter().flatten().collect();
+    // Outputs from before procedure rollups still list their modules
+    let module_ids: Vec<&str> = if procedures.is_empty() {
+        modules.iter().map(|module| field(module, "record_id")).collect()
+    } else {
+        procedures.iter().map(|procedure| field(procedure, "module_id")).collect()
+    };
+    // Document-level reference from the front matter before the first title line, e.g. a
+    // service bulletin number
+    let first_title = modules.iter().filter_map(|module| field(module, "original_start").parse::<usize>().ok()).min();
+    let document_ata = text.map(|text| front_matter(text, first_title)).and_then(find_ata);
+
+    module_ids.iter().enumerate()
+        .map(|(index, module_id)| {
+            let module = modules.iter().find(|module| field(module, "record_id") == *module_id);
+            let title = module.map(|module| field(module, "pattern")).unwrap_or_default();
+            let matched_text = module.map(|module| field(module, "matched_text")).unwrap_or_default();
+            let title_line = text.zip(module.and_then(|module| field(module, "original_start").parse().ok()))
+                .and_then(|(text, offset)| line_at(text, offset));
+            let ata = [title_line.unwrap_or_default(), matched_text, title].iter()
+                .find_map(|candidate| find_ata(candidate))
+                .or_else(|| document_ata.clone());
+            let step_count = procedures.get(index).map(|procedure| field(procedure, "step_count")).unwrap_or_default();
+            let node = ProcedureNode {
+                module_id: module_id.to_string(),
+                title: if title.is_empty() { module_id.to_string() } else { title.to_string() },
+                ata_reference: ata.as_ref().map(|(_, _, reference)| reference.clone()),
+                step_count: step_count.parse().unwrap_or(0),
+            };
+            (ata.map(|(chapter, section, _)| (chapter, section)), node)
+        })
+        .collect()
+}
+
+// Over the outputs of tenant_id's jobs, or of every job in the store when None
+pub fn build_taxonomy(store: &JobStore, tenant_id: Option<&str>) -> Result<TaxonomyTree, Box<dyn std::error::Error>> {
+    let mut tree = TaxonomyTree::default();
+    // chapter -> section -> document id -> document
+    let mut chapters: BTreeMap<String, BTreeMap<String, BTreeMap<String, DocumentNode>>> = BTreeMap::new();
+    let mut unclassified: BTreeMap<String, DocumentNode> = BTreeMap::new();
+
+    let jobs = match tenant_id {
+        Some(tenant_id) => store.list_for_tenant(tenant_id, None, u32::MAX)?,
+        None => store.list(None, u32::MAX)?,
+    };
+    for job in jobs {
+        for input in &job.inputs {
+            let path = output_path_for(&job.output_dir, input).to_string_lossy().to_string();
+            if !Path::new(&path).exists() {
+                continue;
+            }
+            let output: Value = match std::fs::read_to_string(&path).ok().and_then(|content| serde_json::from_str(&content).ok()) {
+                Some(output) => output,
+                None => {
+                    tree.skipped.push(path);
+                    continue;
+                }
+            };
+            let source = payload_of(&output).get("source").and_then(Value::as_str).unwrap_or(input).to_string();
+            let text = std::fs::read_to_string(&source).ok();
+            let empty_document = || DocumentNode {
+                id: path.clone(),
+                job_id: job.job_id.clone(),
+                source: source.clone(),
+                procedures: Vec::new(),
+            };
+
+            tree.document_count += 1;
+            for (ata, procedure) in classify_output(&output, text.as_deref()) {
+                tree.procedure_count += 1;
+                let document = match ata {
+                    Some((chapter, section)) => chapters.entry(chapter).or_default()
+                        .entry(section).or_default()
+                        .entry(path.clone()).or_insert_with(empty_document),
+                    None => unclassified.entry(path.clone()).or_insert_with(empty_document),
+                };
+                document.procedures.push(procedure);
+            }
+        }
+    }
+
+    tree.chapters = chapters.into_iter()
+        .map(|(chapter, sections)| {
+            let sections: Vec<SectionNode> = sections.into_iter()
+                .map(|(section, documents)| SectionNode {
+                    section,
+                    procedure_count: documents.values().map(|document| document.procedures.len()).sum(),
+                    documents: documents.into_values().collect(),
+                })
+                .collect();
+            let mut document_ids: Vec<&str> = sections.iter()
+                .flat_map(|section| section.documents.iter().map(|document| document.id.as_str()))
+                .collect();
+            document_ids.sort_unstable();
+            document_ids.dedup();
+            ChapterNode {
+                title: chapter_title(&chapter),
+                chapter,
+                document_count: document_ids.len(),
+                procedure_count: sections.iter().map(|section| section.procedure_count).sum(),
+                sections,
+            }
+        })
+        .collect();
+    tree.unclassified = unclassified.into_values().collect();
+    Ok(tree)
+}
+
+// Python binding - the browse tree as JSON, for navigation menus
+#[pyfunction]
+#[pyo3(signature = (db_path, tenant_id=None))]
+pub fn get_taxonomy_tree(py: Python, db_path: &str, tenant_id: Option<&str>) -> PyResult<String> {
+    let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+    let tree = py.allow_threads(|| {
+        let store = JobStore::open(db_path).map_err(|e| e.to_string())?;
+        build_taxonomy(&store, tenant_id).map_err(|e| e.to_string())
+    }).map_err(|e| to_py_err(e.into()))?;
+    serde_json::to_string_pretty(&tree).map_err(|e| to_py_err(e.into()))
+}
+
+// A level or an alternate, as Python gets it
+type TaxonomyEntry = HashMap<String, PyObject>;
+
+impl TaxonomyAlternate {
+    fn to_py(&self, py: Python) -> TaxonomyEntry {
+        HashMap::from([
+            ("label".to_string(), self.label.to_object(py)),
+            ("score".to_string(), self.score.to_object(py)),
+        ])
+    }
+}
+
+impl TaxonomyLevel {
+    // Scores stay floats and alternates a list, so callers can threshold without parsing
+    fn to_py(&self, py: Python) -> TaxonomyEntry {
+        let alternates: Vec<TaxonomyEntry> = self.alternates.iter().map(|alternate| alternate.to_py(py)).collect();
+        HashMap::from([
+            ("level".to_string(), self.level.to_object(py)),
+            ("label".to_string(), self.label.to_object(py)),
+            ("score".to_string(), self.score.to_object(py)),
+            ("alternates".to_string(), alternates.to_object(py)),
+        ])
+    }
+}
+
+// Python binding - the taxonomy path for a text, one dict per level: in the taxonomy of the
+// rules at rules_path when they define one, else in the ATA one
+#[pyfunction]
+#[pyo3(signature = (text, max_alternates=3, rules_path=None))]
+pub fn classify_taxonomy(py: Python, text: &str, max_alternates: usize, rules_path: Option<&str>) -> PyResult<Vec<TaxonomyEntry>> {
+    let levels = py.allow_threads(|| -> Result<Vec<TaxonomyLevel>, String> {
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path).map_err(|e| e.to_string())?,
+            None => ExtractionEngine::new(),
+        };
+        Ok(match engine.get_taxonomy() {
+            taxonomy if taxonomy.is_defined() => classify_terms(text, taxonomy, max_alternates),
+            _ => classify_taxonomy_text(text, max_alternates),
+        })
+    }).map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)?;
+    Ok(levels.iter().map(|level| level.to_py(py)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_into_the_rules_taxonomy() {
+        let taxonomy: TaxonomySchema = serde_json::from_value(serde_json::json!({
+            "levels": [{"name": "system", "label": "System"}, {"name": "unit", "label": "Unit"}],
+            "terms": [
+                {"code": "ELEC", "label": "Electrical"},
+                {"code": "HYD", "label": "Hydraulics", "children": [
+                    {"code": "HYD-PUMP", "label": "Engine-driven pump"},
+                    {"code": "HYD-ACC", "label": "Accumulator"},
+                ]},
+            ],
+        })).unwrap();
+        let text = "Hydraulics. Replace the HYD-PUMP (see HYD-ACC), then check the engine-driven pump. ELEC";
+
+        let path = classify_terms(text, &taxonomy, 3);
+        let levels: Vec<(&str, &str, f64)> = path.iter().map(|level| (level.level.as_str(), level.label.as_str(), level.score)).collect();
+        // HYD-PUMP has its code and label (1.5 votes) and HYD-ACC its code; HYD adds its label to
+        // theirs, for 3 of the 4 votes
+        assert_eq!(levels, [("system", "HYD", 0.75), ("unit", "HYD-PUMP", 0.375)]);
+        assert_eq!(path[0].alternates[0].label, "ELEC");
+        assert_eq!(path[1].alternates[0].label, "HYD-ACC");
+        assert!(classify_terms("Nothing to see", &taxonomy, 3).is_empty());
+    }
+}
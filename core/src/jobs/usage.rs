@@ -0,0 +1,277 @@
+use serde_json::{json, Map, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use crate::storage::atomic::write_atomic;
+
+// What a job run spends time on, per input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    // Reading the input, converters and OCR included
+    Read,
+    Extract,
+    Llm,
+    // Writing the output and its export
+    Write,
+    Index,
+}
+
+const PHASES: [Phase; 5] = [Phase::Read, Phase::Extract, Phase::Llm, Phase::Write, Phase::Index];
+
+fn phase_index(phase: Phase) -> usize {
+    PHASES.iter().position(|p| *p == phase).unwrap_or(0)
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Read => "read",
+            Phase::Extract => "extract",
+            Phase::Llm => "llm",
+            Phase::Write => "write",
+            Phase::Index => "index",
+        }
+    }
+}
+
+// CPU time of the process or of its finished children, and their resident-set high-water mark
+#[derive(Debug, Clone, Copy, Default)]
+struct CpuUsage {
+    user_us: u64,
+    system_us: u64,
+    max_rss_kb: u64,
+}
+
+#[cfg(unix)]
+fn rusage(who: libc::c_int) -> libc::rusage {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::getrusage(who, &mut usage);
+    }
+    usage
+}
+
+#[cfg(unix)]
+fn micros(time: libc::timeval) -> u64 {
+    time.tv_sec.max(0) as u64 * 1_000_000 + time.tv_usec.max(0) as u64
+}
+
+#[cfg(unix)]
+fn cpu_usage(children: bool) -> CpuUsage {
+    let usage = rusage(if children { libc::RUSAGE_CHILDREN } else { libc::RUSAGE_SELF });
+    // macOS reports ru_maxrss in bytes, other systems in KiB
+    let max_rss_kb = if cfg!(target_os = "macos") { usage.ru_maxrss.max(0) as u64 / 1024 } else { usage.ru_maxrss.max(0) as u64 };
+    CpuUsage { user_us: micros(usage.ru_utime), system_us: micros(usage.ru_stime), max_rss_kb }
+}
+
+// Without getrusage, CPU time and peaks read as zero
+#[cfg(not(unix))]
+fn cpu_usage(_children: bool) -> CpuUsage {
+    CpuUsage::default()
+}
+
+// CPU time of the calling thread alone, so parallel workers do not count each other
+#[cfg(target_os = "linux")]
+fn thread_cpu_us() -> u64 {
+    let usage = rusage(libc::RUSAGE_THREAD);
+    micros(usage.ru_utime) + micros(usage.ru_stime)
+}
+
+// Other Unix systems have no RUSAGE_THREAD, but do have the POSIX thread CPU clock
+#[cfg(all(unix, not(target_os = "linux")))]
+fn thread_cpu_us() -> u64 {
+    let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } != 0 {
+        return 0;
+    }
+    time.tv_sec.max(0) as u64 * 1_000_000 + time.tv_nsec.max(0) as u64 / 1000
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_us() -> u64 {
+    0
+}
+
+// Byte counters from /proc/self/io. The kernel adds those of finished child processes, so
+// converter and OCR tools are included
+#[derive(Debug, Clone, Copy, Default)]
+struct IoCounters {
+    // Bytes read and written through any file or pipe
+    read_chars: u64,
+    write_chars: u64,
+    // Bytes that reached the storage layer
+    read_bytes: u64,
+    write_bytes: u64,
+}
+
+#[cfg(target_os = "linux")]
+fn io_counters() -> IoCounters {
+    let Ok(io) = std::fs::read_to_string("/proc/self/io") else { return IoCounters::default() };
+    let field = |name: &str| io.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':')?.trim().parse().ok())
+        .unwrap_or(0);
+    IoCounters {
+        read_chars: field("rchar"),
+        write_chars: field("wchar"),
+        read_bytes: field("read_bytes"),
+        write_bytes: field("write_bytes"),
+    }
+}
+
+// Only Linux counts a process's I/O for it; elsewhere the counters read as zero
+#[cfg(not(target_os = "linux"))]
+fn io_counters() -> IoCounters {
+    IoCounters::default()
+}
+
+// The process's resident-set high-water mark (VmHWM), in KiB
+#[cfg(target_os = "linux")]
+fn peak_rss_kb() -> u64 {
+    std::fs::read_to_string("/proc/self/status").ok()
+        .and_then(|status| status.lines().find_map(|line| line.strip_prefix("VmHWM:")?.trim().trim_end_matches("kB").trim().parse().ok()))
+        .unwrap_or_else(|| cpu_usage(false).max_rss_kb)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peak_rss_kb() -> u64 {
+    cpu_usage(false).max_rss_kb
+}
+
+// Starts the high-water mark again from the current RSS, so a run reports its own peak and
+// not that of whatever the process did before. Kernels without it keep the lifetime peak
+#[cfg(target_os = "linux")]
+fn reset_peak_rss() -> bool {
+    std::fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reset_peak_rss() -> bool {
+    false
+}
+
+struct Snapshot {
+    at: Instant,
+    user_us: u64,
+    system_us: u64,
+    tools_user_us: u64,
+    tools_system_us: u64,
+    io: IoCounters,
+}
+
+impl Snapshot {
+    fn now() -> Self {
+        let own = cpu_usage(false);
+        let tools = cpu_usage(true);
+        Self {
+            at: Instant::now(),
+            user_us: own.user_us,
+            system_us: own.system_us,
+            tools_user_us: tools.user_us,
+            tools_system_us: tools.system_us,
+            io: io_counters(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PhaseTotals {
+    wall_us: AtomicU64,
+    cpu_us: AtomicU64,
+    runs: AtomicU64,
+}
+
+pub struct PhaseTimer<'a> {
+    totals: &'a PhaseTotals,
+    started: Instant,
+    cpu_us: u64,
+}
+
+impl Drop for PhaseTimer<'_> {
+    fn drop(&mut self) {
+        self.totals.wall_us.fetch_add(self.started.elapsed().as_micros() as u64, Ordering::Relaxed);
+        self.totals.cpu_us.fetch_add(thread_cpu_us().saturating_sub(self.cpu_us), Ordering::Relaxed);
+        self.totals.runs.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Resources of one job run, shared by its workers. Totals are process-wide deltas from the
+// start of the run; phases add up the time each worker thread spent in them
+pub struct RunUsage {
+    started: Snapshot,
+    peak_reset: bool,
+    phases: [PhaseTotals; 5],
+}
+
+impl RunUsage {
+    pub fn start() -> Self {
+        let peak_reset = reset_peak_rss();
+        Self { started: Snapshot::now(), peak_reset, phases: Default::default() }
+    }
+
+    pub fn time<T>(&self, phase: Phase, run: impl FnOnce() -> T) -> T {
+        let _timer = self.timer(phase);
+        run()
+    }
+
+    // Times the phase until the returned guard is dropped, early returns included
+    pub fn timer(&self, phase: Phase) -> PhaseTimer<'_> {
+        PhaseTimer { totals: &self.phases[phase_index(phase)], started: Instant::now(), cpu_us: thread_cpu_us() }
+    }
+
+    // Tool CPU time counts once a tool has exited, and tool peaks are the largest tool the
+    // process ever ran
+    pub fn report(&self, workers: usize) -> Value {
+        let now = Snapshot::now();
+        let ms = |us: u64| us / 1000;
+        let delta = |now: u64, then: u64| now.saturating_sub(then);
+        let mut phases = Map::new();
+        for (phase, totals) in PHASES.iter().zip(&self.phases) {
+            let runs = totals.runs.load(Ordering::Relaxed);
+            if runs == 0 {
+                continue;
+            }
+            phases.insert(phase.name().to_string(), json!({
+                "wall_ms": ms(totals.wall_us.load(Ordering::Relaxed)),
+                "cpu_ms": ms(totals.cpu_us.load(Ordering::Relaxed)),
+                "runs": runs,
+            }));
+        }
+        json!({
+            "wall_ms": now.at.duration_since(self.started.at).as_millis() as u64,
+            "workers": workers,
+            "outputs": self.phases[phase_index(Phase::Write)].runs.load(Ordering::Relaxed),
+            "cpu": {
+                "user_ms": ms(delta(now.user_us, self.started.user_us)),
+                "system_ms": ms(delta(now.system_us, self.started.system_us)),
+                "tools_user_ms": ms(delta(now.tools_user_us, self.started.tools_user_us)),
+                "tools_system_ms": ms(delta(now.tools_system_us, self.started.tools_system_us)),
+            },
+            "memory": {
+                "peak_rss_kb": peak_rss_kb(),
+                "peak_is_run": self.peak_reset,
+                "tools_peak_rss_kb": cpu_usage(true).max_rss_kb,
+            },
+            "io": {
+                "read_bytes": delta(now.io.read_bytes, self.started.io.read_bytes),
+                "write_bytes": delta(now.io.write_bytes, self.started.io.write_bytes),
+                "read_chars": delta(now.io.read_chars, self.started.io.read_chars),
+                "write_chars": delta(now.io.write_chars, self.started.io.write_chars),
+            },
+            "phases": phases,
+        })
+    }
+
+    pub fn write(&self, usage_path: &str, workers: usize) -> Result<(), Box<dyn std::error::Error>> {
+        write_atomic(usage_path, serde_json::to_string_pretty(&self.report(workers))? + "\n")
+            .map_err(|e| format!("Cannot write usage report {}: {}", usage_path, e))?;
+        Ok(())
+    }
+}
+
+// Times `run` when the caller tracks usage
+pub fn time_phase<T>(usage: Option<&RunUsage>, phase: Phase, run: impl FnOnce() -> T) -> T {
+    match usage {
+        Some(usage) => usage.time(phase, run),
+        None => run(),
+    }
+}
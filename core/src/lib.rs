@@ -1,24 +1,265 @@
 // Main library module - looks like normal Rust library structure
+
+// Enters a tracing span until the end of the enclosing block, for run profiles (see
+// jobs::profiling). Without the profiling feature it compiles to nothing
+macro_rules! profile_span {
+    ($name:literal) => {
+        #[cfg(feature = "profiling")]
+        let _profile_span = tracing::info_span!($name).entered();
+    };
+    ($name:literal, $label:expr) => {
+        #[cfg(feature = "profiling")]
+        let _profile_span = tracing::info_span!($name, label = %$label).entered();
+        #[cfg(not(feature = "profiling"))]
+        let _ = &$label;
+    };
+}
+
 pub mod engine;
 pub mod security;
 pub mod licensing;
+pub mod server;
+pub mod jobs;
+pub mod export;
+pub mod pdf;
+pub mod schema;
+#[cfg(feature = "llm")]
+pub mod llm;
+pub mod storage;
+pub mod fixtures;
+pub mod eval;
+#[cfg(feature = "search")]
+pub mod search;
 
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
 
 // Re-export main components
+pub use engine::access::*;
+#[cfg(feature = "flows")]
+pub use engine::aggregate::*;
+pub use engine::analyze::*;
+#[cfg(feature = "flows")]
+pub use engine::callouts::*;
+pub use engine::completeness::*;
+#[cfg(feature = "flows")]
+pub use engine::conditions::*;
+#[cfg(feature = "flows")]
+pub use engine::consistency::*;
+pub use engine::entities::*;
 pub use engine::extractor::*;
+pub use engine::fuzzy::*;
+pub use engine::ids::*;
+pub use engine::merger::*;
+pub use engine::normalize::*;
+#[cfg(feature = "figures")]
+pub use engine::outline::*;
+#[cfg(feature = "flows")]
+pub use engine::parameters::*;
+pub use engine::pii::*;
+pub use engine::profile::*;
+pub use engine::segment::*;
+pub use engine::simulate::*;
+pub use engine::splitter::*;
+pub use engine::stream::*;
+#[cfg(feature = "tables")]
+pub use engine::tables::*;
+pub use engine::taxonomy::*;
+pub use engine::tune::*;
+#[cfg(feature = "flows")]
+pub use engine::workload::*;
+pub use engine::rank::*;
+pub use engine::scope::*;
+pub use engine::anonymize::*;
+pub use security::bundle::*;
+pub use security::crypto::*;
+pub use security::payload::*;
+pub use security::seal::*;
+pub use security::session::*;
+pub use security::watermark::*;
 pub use security::validator::*;
+pub use licensing::branding::*;
 pub use licensing::manager::*;
+pub use licensing::transfer::*;
+pub use server::tenants::*;
+pub use server::http::*;
+pub use server::openapi::*;
+pub use server::webhooks::*;
+#[cfg(feature = "graphql")]
+pub use server::graphql::*;
+pub use jobs::store::*;
+pub use jobs::runner::*;
+pub use jobs::preflight::*;
+pub use jobs::taxonomy::*;
+pub use jobs::profiles::*;
+pub use jobs::manifest::*;
+pub use jobs::determinism::*;
+pub use jobs::snapshot::*;
+pub use export::delta::*;
+pub use export::graph::*;
+#[cfg(feature = "figures")]
+pub use export::highlight::*;
+pub use export::language::*;
+pub use export::rdf::*;
+pub use export::redline::*;
+pub use export::registry::*;
+pub use export::spoken::*;
+pub use export::workcards::*;
+#[cfg(feature = "figures")]
+pub use pdf::forms::*;
+pub use pdf::layout::*;
+#[cfg(feature = "figures")]
+pub use pdf::text::*;
+pub use schema::migrations::*;
+pub use schema::envelope::*;
+pub use schema::reader::*;
+pub use eval::score::*;
+#[cfg(feature = "search")]
+pub use search::index::*;
+#[cfg(feature = "llm")]
+pub use llm::provider::*;
+#[cfg(feature = "llm")]
+pub use llm::cache::*;
+#[cfg(feature = "llm")]
+pub use llm::budget::*;
+#[cfg(feature = "llm")]
+pub use llm::grammar::*;
+#[cfg(feature = "llm")]
+pub use llm::assist::*;
+#[cfg(feature = "llm")]
+pub use llm::tokenizer::*;
+pub use storage::atomic::*;
+pub use storage::backend::*;
+pub use storage::fs::*;
+pub use storage::memory::*;
+pub use storage::sqlite::*;
+pub use fixtures::manual::*;
 
 // Python module initialization
 #[pymodule]
-fn ml_core(_py: Python, m: &PyModule) -> PyResult<()> {
+fn ml_core(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("PdfPasswordError", py.get_type::<engine::convert::python::PdfPasswordError>())?;
     // Register engine functions
     m.add_function(wrap_pyfunction!(engine::extractor::initialize_engine, m)?)?;
     m.add_function(wrap_pyfunction!(engine::extractor::extract_modules, m)?)?;
     m.add_function(wrap_pyfunction!(engine::extractor::extract_steps, m)?)?;
+    #[cfg(feature = "figures")]
+    m.add_function(wrap_pyfunction!(pdf::text::read_pdf_text, m)?)?;
+    #[cfg(feature = "figures")]
+    m.add_function(wrap_pyfunction!(engine::outline::split_by_outline, m)?)?;
+    #[cfg(feature = "figures")]
+    m.add_function(wrap_pyfunction!(pdf::forms::extract_form_fields, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::entities::extract_entities, m)?)?;
+    #[cfg(feature = "flows")]
+    m.add_function(wrap_pyfunction!(engine::aggregate::aggregate_procedures, m)?)?;
+    #[cfg(feature = "flows")]
+    m.add_function(wrap_pyfunction!(engine::conditions::extract_conditions, m)?)?;
+    #[cfg(feature = "flows")]
+    m.add_function(wrap_pyfunction!(engine::consistency::check_procedures, m)?)?;
+    #[cfg(feature = "flows")]
+    m.add_function(wrap_pyfunction!(engine::parameters::extract_parameters, m)?)?;
+    #[cfg(feature = "flows")]
+    m.add_function(wrap_pyfunction!(engine::callouts::link_figure_callouts, m)?)?;
+    #[cfg(feature = "tables")]
+    m.add_function(wrap_pyfunction!(engine::tables::extract_limits, m)?)?;
+    #[cfg(feature = "tables")]
+    m.add_function(wrap_pyfunction!(engine::tables::extract_tables, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::pii::scan_pii, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::profile::explain_profile, m)?)?;
+    #[cfg(feature = "flows")]
+    m.add_function(wrap_pyfunction!(engine::workload::estimate_workload, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::rank::rank_modules, m)?)?;
     m.add_function(wrap_pyfunction!(engine::extractor::get_prompt, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::simulate::simulate_pattern, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::tune::tune_thresholds, m)?)?;
+    m.add_function(wrap_pyfunction!(eval::score::evaluate_extraction, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::anonymize::anonymize_document, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::normalize::normalize_text, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::normalize::map_offset, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::normalize::map_offset_to_normalized, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::segment::segment_text, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::segment::text_direction, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::analyze::analyze_text, m)?)?;
+    m.add_function(wrap_pyfunction!(pdf::layout::analyze_layout, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::splitter::split_documents, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::splitter::split_and_extract, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::stream::extract_stream, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::merger::merge_documents, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::merger::merge_and_extract, m)?)?;
+
+    // Register security functions
+    m.add_function(wrap_pyfunction!(security::crypto::get_crypto_info, m)?)?;
+    m.add_function(wrap_pyfunction!(security::seal::encrypt_file, m)?)?;
+    m.add_function(wrap_pyfunction!(security::session::get_session_info, m)?)?;
+    m.add_function(wrap_pyfunction!(security::doctor::run_self_test, m)?)?;
+    m.add_function(wrap_pyfunction!(security::payload::diagnose_payload, m)?)?;
+    m.add_function(wrap_pyfunction!(security::bundle::create_support_bundle, m)?)?;
+    m.add_function(wrap_pyfunction!(security::watermark::verify_watermark, m)?)?;
+    m.add_function(wrap_pyfunction!(security::watermark::extract_watermark, m)?)?;
+
+    // Register licensing functions
+    m.add_function(wrap_pyfunction!(licensing::transfer::get_machine_id, m)?)?;
+    m.add_function(wrap_pyfunction!(licensing::transfer::deactivate_license, m)?)?;
+    m.add_function(wrap_pyfunction!(licensing::transfer::verify_deactivation_receipt, m)?)?;
+
+    // Register server functions
+    m.add_function(wrap_pyfunction!(server::http::start_server, m)?)?;
+    m.add_function(wrap_pyfunction!(server::openapi::get_openapi_spec, m)?)?;
+    #[cfg(feature = "graphql")]
+    m.add_function(wrap_pyfunction!(server::graphql::graphql_query, m)?)?;
+    #[cfg(feature = "graphql")]
+    m.add_function(wrap_pyfunction!(server::graphql::get_graphql_schema, m)?)?;
+
+    // Register job functions
+    m.add_function(wrap_pyfunction!(jobs::runner::submit_job, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::runner::get_job_status, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::runner::cancel_job, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::runner::retry_job, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::runner::list_jobs, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::runner::run_jobs, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::preflight::validate_inputs, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::preflight::validate_jobs, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::preflight::check_license, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::taxonomy::get_taxonomy_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::taxonomy::classify_taxonomy, m)?)?;
+    m.add_function(wrap_pyfunction!(engine::taxonomy::get_taxonomy_schema, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::profiles::load_run_profile, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::profiles::list_run_profiles, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::manifest::replay_manifest, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::determinism::verify_determinism, m)?)?;
+    m.add_function(wrap_pyfunction!(jobs::snapshot::diff_rules_snapshot, m)?)?;
+    m.add_function(wrap_pyfunction!(fixtures::manual::generate_manual, m)?)?;
+
+    // Register search functions
+    #[cfg(feature = "search")]
+    m.add_function(wrap_pyfunction!(search::index::index_outputs, m)?)?;
+    #[cfg(feature = "search")]
+    m.add_function(wrap_pyfunction!(search::index::search, m)?)?;
+
+    // Register export functions
+    m.add_function(wrap_pyfunction!(export::delta::export_delta, m)?)?;
+    m.add_function(wrap_pyfunction!(export::graph::export_graph, m)?)?;
+    #[cfg(feature = "figures")]
+    m.add_function(wrap_pyfunction!(export::highlight::export_highlights, m)?)?;
+    m.add_function(wrap_pyfunction!(export::language::filter_output_by_language, m)?)?;
+    m.add_function(wrap_pyfunction!(export::language::split_output_by_language, m)?)?;
+    m.add_function(wrap_pyfunction!(export::rdf::export_rdf, m)?)?;
+    m.add_function(wrap_pyfunction!(export::rdf::get_rdf_ontology, m)?)?;
+    m.add_function(wrap_pyfunction!(export::redline::export_redline, m)?)?;
+    m.add_function(wrap_pyfunction!(export::registry::register_exporter, m)?)?;
+    m.add_function(wrap_pyfunction!(export::registry::get_export_formats, m)?)?;
+    m.add_function(wrap_pyfunction!(export::spoken::export_spoken, m)?)?;
+    m.add_function(wrap_pyfunction!(export::workcards::export_workcards, m)?)?;
+
+    // Register schema functions
+    m.add_function(wrap_pyfunction!(schema::migrations::migrate_output, m)?)?;
+    m.add_function(wrap_pyfunction!(schema::reader::read_records, m)?)?;
+
+    // Register LLM functions
+    #[cfg(feature = "llm")]
+    m.add_function(wrap_pyfunction!(llm::assist::llm_extract, m)?)?;
+    #[cfg(feature = "llm")]
+    m.add_function(wrap_pyfunction!(llm::tokenizer::count_llm_tokens, m)?)?;
     
     Ok(())
 }
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::licensing::manager::License;
+use crate::security::vendor::{tagged_fields, verify_vendor_signature};
+
+// License metadata keys the vendor sets for customer-facing reports. The vendor's signature
+// covers both, so a customer cannot rename themselves in someone else's reports
+pub const DISPLAY_NAME_KEY: &str = "display_name";
+pub const PROJECT_CODE_KEY: &str = "project_code";
+pub const BRANDING_SIGNATURE_KEY: &str = "branding_signature";
+
+const MAX_DISPLAY_NAME_CHARS: usize = 120;
+const MAX_PROJECT_CODE_CHARS: usize = 40;
+
+// What outputs and reports name the licensee by - see License::get_branding
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Branding {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_code: Option<String>,
+}
+
+impl Branding {
+    pub fn is_empty(&self) -> bool {
+        self.display_name.is_none() && self.project_code.is_none()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let check = |key: &str, value: &Option<String>, max: usize| match value {
+            Some(value) if value.trim().is_empty() => Err(format!("{} is empty", key)),
+            Some(value) if value.chars().count() > max => Err(format!("{} is longer than {} characters", key, max)),
+            Some(value) if value.chars().any(char::is_control) => Err(format!("{} contains control characters", key)),
+            _ => Ok(()),
+        };
+        check(DISPLAY_NAME_KEY, &self.display_name, MAX_DISPLAY_NAME_CHARS)?;
+        check(PROJECT_CODE_KEY, &self.project_code, MAX_PROJECT_CODE_CHARS)
+    }
+
+    // "Acme Airlines - A320 Heavy Check", or whichever half is set
+    pub fn label(&self) -> String {
+        [&self.display_name, &self.project_code].iter().filter_map(|part| part.as_deref()).collect::<Vec<_>>().join(" - ")
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        if let Some(display_name) = &self.display_name {
+            info.insert(DISPLAY_NAME_KEY.to_string(), display_name.clone());
+        }
+        if let Some(project_code) = &self.project_code {
+            info.insert(PROJECT_CODE_KEY.to_string(), project_code.clone());
+        }
+        info
+    }
+}
+
+impl License {
+    fn metadata_branding(&self) -> Branding {
+        Branding {
+            display_name: self.metadata.get(DISPLAY_NAME_KEY).cloned(),
+            project_code: self.metadata.get(PROJECT_CODE_KEY).cloned(),
+        }
+    }
+
+    // What the vendor signs to approve branding - see docs/security.md
+    fn branding_payload(&self, branding: &Branding) -> Vec<u8> {
+        let fields = [
+            self.license_id.as_str(),
+            &self.customer_id,
+            branding.display_name.as_deref().unwrap_or_default(),
+            branding.project_code.as_deref().unwrap_or_default(),
+        ];
+        tagged_fields("branding", &fields)
+    }
+
+    // Branding the vendor approved, or None. Unsigned, edited or malformed branding is ignored
+    // rather than refused, since the license itself is still good
+    pub fn get_branding(&self) -> Option<Branding> {
+        let branding = self.metadata_branding();
+        let signature = self.metadata.get(BRANDING_SIGNATURE_KEY)?;
+        let approved = verify_vendor_signature(&[&self.branding_payload(&branding)], signature);
+        (!branding.is_empty() && branding.validate().is_ok() && approved).then_some(branding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::vendor::sign_as_vendor;
+
+    #[test]
+    fn branding_needs_the_vendor_signature() {
+        let mut license = License::new("acme".to_string(), Vec::new());
+        license.metadata.insert(DISPLAY_NAME_KEY.to_string(), "Acme Airlines".to_string());
+        let signature = sign_as_vendor(&[&license.branding_payload(&license.metadata_branding())]);
+        license.metadata.insert(BRANDING_SIGNATURE_KEY.to_string(), signature);
+        assert_eq!(license.get_branding().unwrap().label(), "Acme Airlines");
+
+        license.metadata.insert(DISPLAY_NAME_KEY.to_string(), "Someone Else".to_string());
+        assert_eq!(license.get_branding(), None);
+        license.metadata.insert(BRANDING_SIGNATURE_KEY.to_string(), "1ac292ab".to_string());
+        assert_eq!(license.get_branding(), None);
+    }
+}
@@ -1,14 +1,31 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
+use crate::licensing::branding::{BRANDING_SIGNATURE_KEY, DISPLAY_NAME_KEY, PROJECT_CODE_KEY};
+use crate::licensing::transfer::{DEACTIVATED_AT_KEY, DEACTIVATION_RECEIPT_KEY};
 // Import secure validation from security module
+use crate::security::access::{AccessCounter, ACCESS_LIMIT_KEY, DEFAULT_ACCESS_LIMIT};
+use crate::security::clock::{build_date, hardcoded_expiration, BUILD_TIMESTAMP, HARDCODED_EXPIRATION_DAYS};
+use crate::security::crypto::{constant_time_eq, ed25519_public_key, from_hex};
+use crate::security::vendor::{tagged_fields, verify_vendor_signature};
+use crate::security::session::{customer_id_hash, SessionInfo};
+use crate::storage::atomic::write_atomic;
+use crate::storage::backend::Storage;
+use crate::storage::memory::MemoryStorage;
 use crate::security::validator::{ValidationConfig, ConfigManager};
 
-// Hardcoded security constants
-const BUILD_TIMESTAMP: u64 = 1734123456; // Must match security module
-const HARDCODED_EXPIRATION_DAYS: u64 = 14; // Must match security module
+// Metadata keys the license signature leaves out: this machine writes the deactivation keys,
+// and branding is approved, and signed, on its own
+const UNSIGNED_METADATA_KEYS: &[&str] = &[
+    DEACTIVATED_AT_KEY,
+    DEACTIVATION_RECEIPT_KEY,
+    DISPLAY_NAME_KEY,
+    PROJECT_CODE_KEY,
+    BRANDING_SIGNATURE_KEY,
+];
 
 // Secure license structure with hardcoded expiration
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,18 +36,30 @@ pub struct License {
     pub issued_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
     pub metadata: HashMap<String, String>,
+    // The vendor's Ed25519 signature over signed_payload, in hex
     pub security_signature: String,
+    // Public half (hex) of the license's own Ed25519 key, which signs its deactivation receipts
+    // and output watermarks. key_signature is the vendor's certificate for it
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub license_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub key_signature: String,
+    // Seed (hex) of that key, issued with the license; it never leaves the license file
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signing_key: String,
+}
+
+// Whether the vendor certified `license_key` as the key of license `license_id`
+pub fn verify_license_key(license_id: &str, license_key: &str, key_signature: &str) -> bool {
+    verify_vendor_signature(&[&tagged_fields("license-key", &[license_id, license_key])], key_signature)
 }
 
 impl License {
+    // Unsigned until the vendor signs it, so load_signed and deactivate refuse it
     pub fn new(customer_id: String, features: Vec<String>) -> Self {
         // Use hardcoded build timestamp for consistent expiration
-        let build_date = DateTime::from_timestamp(BUILD_TIMESTAMP as i64, 0)
-            .unwrap_or_else(|| Utc::now());
-        let expiration = build_date + chrono::Duration::days(HARDCODED_EXPIRATION_DAYS as i64);
-        
-        // Generate security signature
-        let signature = Self::generate_security_signature(&customer_id, &build_date);
+        let build_date = build_date();
+        let expiration = hardcoded_expiration();
         
         Self {
             license_id: Uuid::new_v4().to_string(),
@@ -39,7 +68,10 @@ impl License {
             issued_at: build_date,
             expires_at: expiration,
             metadata: HashMap::new(),
-            security_signature: signature,
+            security_signature: String::new(),
+            license_key: String::new(),
+            key_signature: String::new(),
+            signing_key: String::new(),
         }
     }
 
@@ -50,13 +82,26 @@ impl License {
             self.features.clone()
         );
         
-        validation_config.is_valid()
+        // Deactivated or moved seats stop validating on this machine
+        validation_config.is_valid() && self.is_bound_here() && !self.is_deactivated()
     }
 
     pub fn has_feature(&self, feature: &str) -> bool {
         self.features.contains(&feature.to_string())
     }
 
+    pub fn get_tier(&self) -> &str {
+        // Tier is carried in license metadata
+        self.metadata.get("tier").map(String::as_str).unwrap_or("standard")
+    }
+
+    // Access attempts allowed per 24-hour window, from license metadata
+    pub fn get_access_limit(&self) -> u64 {
+        self.metadata.get(ACCESS_LIMIT_KEY)
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(DEFAULT_ACCESS_LIMIT)
+    }
+
     pub fn days_remaining(&self) -> i64 {
         let validation_config = ValidationConfig::new(
             self.customer_id.clone(),
@@ -66,27 +111,53 @@ impl License {
         validation_config.days_remaining()
     }
 
-    fn generate_security_signature(customer_id: &str, build_date: &DateTime<Utc>) -> String {
-        // Use same signature generation as security module
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        
-        const SECURITY_SALT: &str = "ml_core_2024_secure";
-        
-        let mut hasher = DefaultHasher::new();
-        customer_id.hash(&mut hasher);
-        build_date.timestamp().hash(&mut hasher);
-        SECURITY_SALT.hash(&mut hasher);
-        
-        format!("{:x}", hasher.finish())
+    // What the vendor signs: every field but the signatures and the signing key, including the
+    // binding in metadata. Features and metadata are compact JSON, the metadata with sorted keys
+    pub fn signed_payload(&self) -> Vec<u8> {
+        let metadata: BTreeMap<&String, &String> = self.metadata.iter()
+            .filter(|(key, _)| !UNSIGNED_METADATA_KEYS.contains(&key.as_str()))
+            .collect();
+        let features = serde_json::to_string(&self.features).unwrap_or_default();
+        let metadata = serde_json::to_string(&metadata).unwrap_or_default();
+        let issued_at = self.issued_at.timestamp().to_string();
+        let expires_at = self.expires_at.timestamp().to_string();
+        tagged_fields("license", &[&self.license_id, &self.customer_id, &features, &issued_at, &expires_at, &metadata, &self.license_key])
+    }
+
+    // Seed of the license's own key, when the vendor certified the key and the seed is its
+    pub fn signing_seed(&self) -> Option<[u8; 32]> {
+        let seed: [u8; 32] = from_hex(&self.signing_key)?.try_into().ok()?;
+        let public_key = from_hex(&self.license_key)?;
+        let certified = verify_license_key(&self.license_id, &self.license_key, &self.key_signature);
+        (certified && constant_time_eq(&ed25519_public_key(&seed), &public_key)).then_some(seed)
+    }
+
+    // Reads a license file, rejecting ones whose signature does not check out
+    pub fn load_signed(license_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let license_data = std::fs::read_to_string(license_path)
+            .map_err(|e| format!("Cannot read license {}: {}", license_path, e))?;
+        let license: License = serde_json::from_str(&license_data)?;
+        if !license.validate_signature() {
+            return Err(format!("License signature is invalid: {}", license_path).into());
+        }
+        Ok(license)
     }
 
     pub fn validate_signature(&self) -> bool {
-        let build_date = DateTime::from_timestamp(BUILD_TIMESTAMP as i64, 0)
-            .unwrap_or_else(|| Utc::now());
-        let expected_signature = Self::generate_security_signature(&self.customer_id, &build_date);
-        
-        self.security_signature == expected_signature
+        verify_vendor_signature(&[&self.signed_payload()], &self.security_signature)
+    }
+
+    // Signs the license and a fresh license key with the development vendor key, as the
+    // vendor's tooling would with its own
+    #[cfg(test)]
+    pub(crate) fn sign_for_tests(&mut self) {
+        use crate::security::crypto::to_hex;
+        use crate::security::vendor::sign_as_vendor;
+        let seed = crate::security::crypto::sha256(&[b"test license key", self.license_id.as_bytes()]);
+        self.license_key = to_hex(&ed25519_public_key(&seed));
+        self.key_signature = sign_as_vendor(&[&tagged_fields("license-key", &[&self.license_id, &self.license_key])]);
+        self.signing_key = to_hex(&seed);
+        self.security_signature = sign_as_vendor(&[&self.signed_payload()]);
     }
 }
 
@@ -158,6 +229,10 @@ impl LicenseManager {
         }
     }
 
+    pub fn get_config_path(&self) -> &str {
+        &self.config_path
+    }
+
     pub fn get_license_info(&self, customer_id: &str) -> Option<&License> {
         self.licenses.get(customer_id)
     }
@@ -174,12 +249,16 @@ impl LicenseManager {
         }
         
         let license_json = serde_json::to_string_pretty(license)?;
-        std::fs::write(output_path, license_json)?;
+        write_atomic(output_path, license_json)?;
         Ok(())
     }
 
-    pub fn get_security_report(&self, customer_id: &str) -> Option<HashMap<String, String>> {
-        self.security_manager.get_security_report(customer_id)
+    pub fn get_session_info(&self, customer_id: &str) -> Option<SessionInfo> {
+        let info = self.security_manager.get_session_info(customer_id)?;
+        Some(match self.licenses.get(customer_id) {
+            Some(license) => info.with_license(license),
+            None => info,
+        })
     }
 
     pub fn get_hardcoded_expiration_info(&self) -> HashMap<String, String> {
@@ -189,10 +268,7 @@ impl LicenseManager {
         info.insert("security_level".to_string(), "Maximum".to_string());
         
         // Calculate actual expiration date
-        let build_date = DateTime::from_timestamp(BUILD_TIMESTAMP as i64, 0)
-            .unwrap_or_else(|| Utc::now());
-        let expiration = build_date + chrono::Duration::days(HARDCODED_EXPIRATION_DAYS as i64);
-        info.insert("expiration_date".to_string(), expiration.to_rfc3339());
+        info.insert("expiration_date".to_string(), hardcoded_expiration().to_rfc3339());
         
         info
     }
@@ -201,28 +277,56 @@ impl LicenseManager {
 // Enhanced feature access control
 pub struct FeatureAccess {
     manager: LicenseManager,
-    access_log: HashMap<String, u32>,
+    // One counter per customer, created on first access
+    access_log: Mutex<HashMap<String, Arc<AccessCounter>>>,
+    storage: Arc<dyn Storage>,
 }
 
 impl FeatureAccess {
     pub fn new(config_path: String) -> Self {
         Self {
             manager: LicenseManager::new(config_path),
-            access_log: HashMap::new(),
+            access_log: Mutex::new(HashMap::new()),
+            storage: Arc::new(MemoryStorage::new()),
         }
     }
 
-    pub fn check_access(&self, customer_id: &str, feature: &str) -> bool {
-        // Log access attempt
-        let access_count = self.access_log.get(customer_id).unwrap_or(&0) + 1;
-        
-        // Check access limits
-        if access_count > 1000 {
-            return false; // Too many access attempts
+    // Counts persist to the storage and carry over between runs
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    fn access_counter(&self, customer_id: &str) -> Result<Arc<AccessCounter>, Box<dyn std::error::Error>> {
+        let mut access_log = self.access_log.lock().map_err(|_| "Access log poisoned")?;
+        if let Some(counter) = access_log.get(customer_id) {
+            return Ok(Arc::clone(counter));
         }
+        let limit = self.manager.get_license_info(customer_id)
+            .map(License::get_access_limit)
+            .unwrap_or(DEFAULT_ACCESS_LIMIT);
+        let key = format!("feature.{}", customer_id_hash(customer_id));
+        let counter = Arc::new(AccessCounter::new(&key, limit).with_storage(Arc::clone(&self.storage))?);
+        access_log.insert(customer_id.to_string(), Arc::clone(&counter));
+        Ok(counter)
+    }
+
+    pub fn check_access(&self, customer_id: &str, feature: &str) -> bool {
+        // Log access attempt; a counter that cannot be loaded denies access
+        let within_limit = match self.access_counter(customer_id) {
+            Ok(counter) => counter.record(),
+            Err(_) => false,
+        };
         
-        // Validate access
-        self.manager.validate_license_access(customer_id, feature)
+        // Check access limits, then validate access
+        within_limit && self.manager.validate_license_access(customer_id, feature)
+    }
+
+    pub fn get_security_info(&self, customer_id: &str) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+        let mut info = self.manager.get_hardcoded_expiration_info();
+        info.extend(self.access_counter(customer_id)?.get_info());
+        info.insert("customer_id_hash".to_string(), customer_id_hash(customer_id));
+        Ok(info)
     }
 
     pub fn get_available_features(&self, customer_id: &str) -> Vec<String> {
@@ -237,29 +341,7 @@ impl FeatureAccess {
         }
     }
 
-    pub fn get_security_status(&self, customer_id: &str) -> HashMap<String, String> {
-        let mut status = HashMap::new();
-        
-        // License status
-        if let Some(license) = self.manager.get_license_info(customer_id) {
-            status.insert("license_valid".to_string(), license.is_valid().to_string());
-            status.insert("days_remaining".to_string(), license.days_remaining().to_string());
-            status.insert("signature_valid".to_string(), license.validate_signature().to_string());
-        } else {
-            status.insert("license_valid".to_string(), "false".to_string());
-        }
-        
-        // Security report
-        if let Some(security_info) = self.manager.get_security_report(customer_id) {
-            for (key, value) in security_info {
-                status.insert(format!("security_{}", key), value);
-            }
-        }
-        
-        // Access logging
-        let access_count = self.access_log.get(customer_id).unwrap_or(&0);
-        status.insert("access_attempts".to_string(), access_count.to_string());
-        
-        status
+    pub fn get_session_info(&self, customer_id: &str) -> Option<SessionInfo> {
+        self.manager.get_session_info(customer_id)
     }
 }
@@ -1 +1,3 @@
+pub mod branding;
 pub mod manager;
+pub mod transfer;
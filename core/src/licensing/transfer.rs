@@ -0,0 +1,219 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::licensing::manager::{verify_license_key, License};
+use crate::security::crypto::{ed25519_sign, ed25519_verify, from_hex, sha256, to_hex};
+use crate::security::vendor::tagged_fields;
+use crate::storage::atomic::write_atomic;
+
+// License metadata keys written by activation and deactivation
+pub const HWID_KEY: &str = "hwid";
+pub const DEACTIVATED_AT_KEY: &str = "deactivated_at";
+pub const DEACTIVATION_RECEIPT_KEY: &str = "deactivation_receipt";
+
+// OS-provided install identifiers, most stable first
+const MACHINE_ID_PATHS: &[&str] = &["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+// Hardware ID a license seat is bound to - a hash, so the raw machine ID never leaves the host
+pub fn machine_id() -> String {
+    let source = MACHINE_ID_PATHS.iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .find(|id| !id.is_empty())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok().map(|name| name.trim().to_string()))
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .unwrap_or_default();
+    format!("hwid_{}", to_hex(&sha256(&[b"hwid", &[0x1f], source.as_bytes()])[..16]))
+}
+
+// Proof that a seat was released on one machine, exchanged with the vendor for a new activation.
+// The license's own key signs it, and the receipt carries the vendor's certificate for that key,
+// so it verifies with nothing but the vendor public key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeactivationReceipt {
+    pub receipt_id: String,
+    pub license_id: String,
+    pub customer_id: String,
+    pub hwid: String,
+    pub deactivated_at: String,
+    #[serde(default)]
+    pub license_key: String,
+    #[serde(default)]
+    pub key_signature: String,
+    pub signature: String,
+}
+
+impl DeactivationReceipt {
+    fn signed_payload(&self) -> Vec<u8> {
+        let fields = [
+            self.receipt_id.as_str(),
+            &self.license_id,
+            &self.customer_id,
+            &self.hwid,
+            &self.deactivated_at,
+        ];
+        tagged_fields("deactivation", &fields)
+    }
+
+    // Receipts from before license keys have none, and never verify
+    pub fn verify(&self) -> bool {
+        let (Some(license_key), Some(signature)) = (from_hex(&self.license_key), from_hex(&self.signature)) else {
+            return false;
+        };
+        verify_license_key(&self.license_id, &self.license_key, &self.key_signature)
+            && ed25519_verify(&license_key, &[&self.signed_payload()], &signature)
+    }
+
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let mut summary = HashMap::new();
+        summary.insert("receipt_id".to_string(), self.receipt_id.clone());
+        summary.insert("license_id".to_string(), self.license_id.clone());
+        summary.insert("customer_id".to_string(), self.customer_id.clone());
+        summary.insert("hwid".to_string(), self.hwid.clone());
+        summary.insert("deactivated_at".to_string(), self.deactivated_at.clone());
+        summary.insert("license_key".to_string(), self.license_key.clone());
+        summary.insert("signature".to_string(), self.signature.clone());
+        summary
+    }
+}
+
+impl License {
+    pub fn get_hwid(&self) -> Option<&String> {
+        self.metadata.get(HWID_KEY)
+    }
+
+    pub fn is_deactivated(&self) -> bool {
+        self.metadata.contains_key(DEACTIVATED_AT_KEY)
+    }
+
+    // Unbound licenses run anywhere; bound ones only on the machine they were activated on
+    pub fn is_bound_here(&self) -> bool {
+        self.get_hwid().is_none_or(|hwid| *hwid == machine_id())
+    }
+
+    // Releases the seat on this machine. The license is unusable afterwards until the vendor
+    // issues a new activation for the receipt. The binding stays, since the vendor signed it
+    pub fn deactivate(&mut self) -> Result<DeactivationReceipt, Box<dyn std::error::Error>> {
+        if !self.validate_signature() {
+            return Err("License signature is invalid".into());
+        }
+        if let Some(deactivated_at) = self.metadata.get(DEACTIVATED_AT_KEY) {
+            return Err(format!("License {} was already deactivated at {}", self.license_id, deactivated_at).into());
+        }
+        let hwid = self.get_hwid()
+            .ok_or("License is not bound to a machine - there is no seat to release")?
+            .clone();
+        if hwid != machine_id() {
+            return Err("License is bound to another machine - deactivate it there".into());
+        }
+        let seed = self.signing_seed()
+            .ok_or("License has no certified license key to sign the receipt with - ask the vendor to reissue it")?;
+
+        let mut receipt = DeactivationReceipt {
+            receipt_id: Uuid::new_v4().to_string(),
+            license_id: self.license_id.clone(),
+            customer_id: self.customer_id.clone(),
+            hwid,
+            deactivated_at: Utc::now().to_rfc3339(),
+            license_key: self.license_key.clone(),
+            key_signature: self.key_signature.clone(),
+            signature: String::new(),
+        };
+        receipt.signature = to_hex(&ed25519_sign(&seed, &[&receipt.signed_payload()]));
+
+        self.metadata.insert(DEACTIVATED_AT_KEY.to_string(), receipt.deactivated_at.clone());
+        self.metadata.insert(DEACTIVATION_RECEIPT_KEY.to_string(), receipt.receipt_id.clone());
+        Ok(receipt)
+    }
+}
+
+// Deactivates the license file in place and writes the receipt beside it (or to receipt_path)
+pub fn deactivate_license_file(license_path: &str, receipt_path: Option<&str>) -> Result<(DeactivationReceipt, String), Box<dyn std::error::Error>> {
+    let license_data = std::fs::read_to_string(license_path)
+        .map_err(|e| format!("Cannot read license {}: {}", license_path, e))?;
+    let mut license: License = serde_json::from_str(&license_data)?;
+    let receipt = license.deactivate()?;
+
+    let receipt_path = receipt_path.map(str::to_string)
+        .unwrap_or_else(|| format!("{}.deactivation.json", license_path.trim_end_matches(".json")));
+    // Receipt first - a failed license write must not lose the proof of release
+    write_atomic(&receipt_path, serde_json::to_string_pretty(&receipt)?)?;
+    write_atomic(license_path, serde_json::to_string_pretty(&license)?)?;
+    Ok((receipt, receipt_path))
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+}
+
+// Python bindings - seat transfer between machines
+#[pyfunction]
+pub fn get_machine_id() -> PyResult<String> {
+    Ok(machine_id())
+}
+
+#[pyfunction]
+#[pyo3(signature = (license_path, receipt_path=None))]
+pub fn deactivate_license(license_path: &str, receipt_path: Option<&str>) -> PyResult<HashMap<String, String>> {
+    let (receipt, receipt_path) = deactivate_license_file(license_path, receipt_path).map_err(to_py_err)?;
+    let mut summary = receipt.get_summary();
+    summary.insert("receipt_path".to_string(), receipt_path);
+    Ok(summary)
+}
+
+#[pyfunction]
+pub fn verify_deactivation_receipt(receipt_path: &str) -> PyResult<bool> {
+    let receipt_data = std::fs::read_to_string(receipt_path)
+        .map_err(|e| to_py_err(format!("Cannot read receipt {}: {}", receipt_path, e).into()))?;
+    let receipt: DeactivationReceipt = serde_json::from_str(&receipt_data)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid receipt: {}", e)))?;
+    Ok(receipt.verify())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bound_license() -> License {
+        let mut license = License::new("acme".to_string(), vec!["extraction".to_string()]);
+        license.metadata.insert(HWID_KEY.to_string(), machine_id());
+        license.sign_for_tests();
+        license
+    }
+
+    #[test]
+    fn binding_is_signed_and_receipts_verify_against_the_vendor_key() {
+        let mut license = bound_license();
+        assert!(license.validate_signature() && license.is_bound_here() && !license.is_deactivated());
+        // Moving the seat or adding a feature breaks the vendor's signature
+        let mut moved = bound_license();
+        moved.metadata.insert(HWID_KEY.to_string(), "hwid_0000".to_string());
+        assert!(!moved.validate_signature());
+        let mut upgraded = bound_license();
+        upgraded.features.push("document_queries".to_string());
+        assert!(!upgraded.validate_signature());
+        assert!(!License::new("acme".to_string(), Vec::new()).validate_signature());
+
+        let receipt = license.deactivate().unwrap();
+        assert!(receipt.verify());
+        // The deactivation keys are written here, outside the signature
+        assert!(license.validate_signature() && license.is_deactivated());
+        assert!(license.deactivate().is_err());
+
+        let mut edited = receipt.clone();
+        edited.hwid = "hwid_0000".to_string();
+        assert!(!edited.verify());
+        // A key the vendor never certified signs nothing, even with a matching signature
+        let seed = [7u8; 32];
+        let mut forged = receipt.clone();
+        forged.license_key = to_hex(&crate::security::crypto::ed25519_public_key(&seed));
+        forged.signature = to_hex(&ed25519_sign(&seed, &[&forged.signed_payload()]));
+        assert!(!forged.verify());
+        let mut unsigned = bound_license();
+        unsigned.signing_key.clear();
+        assert!(unsigned.deactivate().is_err());
+    }
+}
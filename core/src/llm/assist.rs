@@ -0,0 +1,382 @@
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::ids::stable_record_id;
+use crate::engine::language::tag_languages;
+use crate::engine::scope::{scope_from_args, ExtractionScope};
+use crate::engine::splitter::split_pages;
+use crate::llm::budget::BudgetTracker;
+use crate::llm::grammar::{records_from_output, JsonSchema};
+use crate::llm::cache::{cache_key, chunk_hash, template_version, CacheStats, PromptCache};
+use crate::llm::failover::ProviderHealth;
+use crate::llm::provider::{build_provider, LlmConfig, LlmProvider, LlmRequest, LlmResponse};
+use crate::llm::tokenizer::{build_counter, TokenCounter};
+use crate::schema::envelope::{wrap_output, Provenance, UNLICENSED_TIER};
+use crate::security::session::record_document_processed;
+
+// Placeholder a prompt template uses for the section text
+const TEXT_PLACEHOLDER: &str = "{text}";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LlmUsage {
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+// One provider call, shared by every (page, prompt) slot that renders the same request
+struct PlannedCall {
+    key: String,
+    template_version: String,
+    chunk_hash: String,
+    prompt: String,
+    // As the configured tokenizer counts the prompt
+    prompt_tokens: u64,
+    schema: Option<JsonSchema>,
+}
+
+// Runs the rule pack's prompt templates against document sections through a provider
+pub struct LlmAssistant {
+    provider: Box<dyn LlmProvider>,
+    counter: Box<dyn TokenCounter>,
+    config: LlmConfig,
+    cache: Option<PromptCache>,
+    usage: LlmUsage,
+    run_budget: BudgetTracker,
+    warnings: Vec<String>,
+    // What the content filter kept from the provider in the last document
+    withheld: Vec<HashMap<String, String>>,
+    withheld_sections: u64,
+    withheld_spans: u64,
+    skipped_prompts: u64,
+    coalesced_prompts: u64,
+}
+
+impl LlmAssistant {
+    pub fn new(provider: Box<dyn LlmProvider>, config: LlmConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let cache = config.cache_dir.as_deref().map(PromptCache::open).transpose()?;
+        let run_budget = BudgetTracker::new("run", config.run_budget.clone());
+        Ok(Self {
+            provider,
+            counter: build_counter(&config)?,
+            config,
+            cache,
+            usage: LlmUsage::default(),
+            run_budget,
+            warnings: Vec::new(),
+            withheld: Vec::new(),
+            withheld_sections: 0,
+            withheld_spans: 0,
+            skipped_prompts: 0,
+            coalesced_prompts: 0,
+        })
+    }
+
+    pub fn from_config(config: LlmConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let provider = build_provider(&config)?;
+        Self::new(provider, config)
+    }
+
+    fn plan_call(&self, template: &str, chunk: &str, schema: Option<&Value>) -> Result<PlannedCall, Box<dyn std::error::Error>> {
+        // A schema change alters the output shape, so it versions the template too
+        let template_version = match schema {
+            Some(schema) => template_version(&format!("{}\x1f{}", template, schema)),
+            None => template_version(template),
+        };
+        let chunk_hash = chunk_hash(chunk);
+        let prompt = template.replace(TEXT_PLACEHOLDER, chunk);
+        Ok(PlannedCall {
+            key: cache_key(&self.config.model, &template_version, &chunk_hash, self.config.max_tokens),
+            template_version,
+            chunk_hash,
+            prompt_tokens: self.counter.count(&prompt),
+            prompt,
+            schema: schema.map(JsonSchema::from_value).transpose()?,
+        })
+    }
+
+    // Reserves the call's worst case against both budgets, or records why it was skipped
+    fn admit(&mut self, call: &PlannedCall, document_budget: &mut BudgetTracker) -> bool {
+        let pricing = &self.config.pricing;
+        let worst_case = self.run_budget.check(call.prompt_tokens, self.config.max_tokens, pricing)
+            .and_then(|worst_case| document_budget.check(call.prompt_tokens, self.config.max_tokens, pricing).map(|_| worst_case));
+        match worst_case {
+            Ok(worst_case) => {
+                self.run_budget.reserve(&worst_case);
+                document_budget.reserve(&worst_case);
+                true
+            }
+            Err(reason) => {
+                if !self.warnings.contains(&reason) {
+                    self.warnings.push(reason);
+                }
+                self.skipped_prompts += 1;
+                false
+            }
+        }
+    }
+
+    // Sends one wave of calls in parallel; results come back in input order
+    fn dispatch(&self, calls: &[&PlannedCall]) -> Vec<Result<LlmResponse, String>> {
+        let provider = self.provider.as_ref();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = calls.iter()
+                .map(|call| {
+                    let request = LlmRequest {
+                        model: self.config.model.clone(),
+                        prompt: call.prompt.clone(),
+                        max_tokens: self.config.max_tokens,
+                        schema: call.schema.clone(),
+                    };
+                    scope.spawn(move || provider.complete(&request).map_err(|e| e.to_string()))
+                })
+                .collect();
+            handles.into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|_| Err("LLM request thread panicked".to_string())))
+                .collect()
+        })
+    }
+
+    // One record per prompt template and non-empty page. Identical requests are sent once,
+    // cache hits are never sent, and the rest go out in waves of max_concurrency.
+    // Sections past an exhausted budget get no LLM records - rule-based extraction is unaffected.
+    // The content filter runs first: stripped parts are replaced, blocked sections never sent
+    pub fn assist_document(&mut self, engine: &ExtractionEngine, text: &str) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
+        let mut prompts: Vec<(&String, &String)> = engine.get_prompts().iter().collect();
+        prompts.sort();
+        let mut document_budget = BudgetTracker::new("document", self.config.document_budget.clone());
+
+        let mut slots = Vec::new();
+        let mut pending: Vec<PlannedCall> = Vec::new();
+        let mut results: HashMap<String, (LlmResponse, bool)> = HashMap::new();
+        let filter = &self.config.content_filter;
+        let withheld = if filter.is_empty() { Vec::new() } else { filter.scan(engine, text) };
+        self.withheld.clear();
+        for (index, (offset, page)) in split_pages(text).into_iter().enumerate() {
+            if page.trim().is_empty() {
+                continue;
+            }
+            let filtered = filter.apply(&withheld, offset, page);
+            self.withheld.extend(filtered.withheld.iter().map(|item| item.get_info(index + 1)));
+            let Some(page) = filtered.text else {
+                self.withheld_sections += 1;
+                continue;
+            };
+            self.withheld_spans += filtered.withheld.len() as u64;
+            for (name, template) in &prompts {
+                let call = self.plan_call(template, &page, engine.get_prompt_schema(name))?;
+                slots.push((index + 1, offset, name.to_string(), call.key.clone(), call.schema.clone()));
+
+                if results.contains_key(&call.key) || pending.iter().any(|p| p.key == call.key) {
+                    self.coalesced_prompts += 1;
+                } else if let Some(cached) = self.cache.as_mut().and_then(|cache| cache.get(&call.key)) {
+                    results.insert(call.key, (cached, true));
+                } else {
+                    pending.push(call);
+                }
+            }
+        }
+
+        for wave in pending.chunks(self.config.max_concurrency.max(1)) {
+            let admitted: Vec<&PlannedCall> = wave.iter()
+                .filter(|call| self.admit(call, &mut document_budget))
+                .collect();
+            let responses = self.dispatch(&admitted);
+            self.run_budget.settle();
+            document_budget.settle();
+
+            for (call, response) in admitted.into_iter().zip(responses) {
+                let mut response = response?;
+                // Endpoints that report no usage are charged what the tokenizer counts
+                if response.prompt_tokens == 0 && response.completion_tokens == 0 {
+                    response.prompt_tokens = call.prompt_tokens;
+                    response.completion_tokens = self.counter.count(&response.text);
+                }
+                self.usage.calls += 1;
+                self.usage.prompt_tokens += response.prompt_tokens;
+                self.usage.completion_tokens += response.completion_tokens;
+                self.run_budget.record(&response, &self.config.pricing);
+                document_budget.record(&response, &self.config.pricing);
+                if let Some(cache) = self.cache.as_mut() {
+                    cache.put(&call.key, &self.config.model, &call.template_version, &call.chunk_hash, &response)?;
+                }
+                results.insert(call.key.clone(), (response, false));
+            }
+        }
+
+        let mut records = Vec::new();
+        for (section, offset, name, key, schema) in slots {
+            let (response, cached) = match results.get(&key) {
+                Some(result) => result,
+                None => continue,
+            };
+            let section = section.to_string();
+            let mut base = HashMap::new();
+            base.insert("prompt".to_string(), name.clone());
+            base.insert("section".to_string(), section.clone());
+            base.insert("start_offset".to_string(), offset.to_string());
+            base.insert("cached".to_string(), cached.to_string());
+
+            let structured = schema.as_ref().map(|schema| {
+                serde_json::from_str::<Value>(&response.text)
+                    .ok()
+                    .filter(|value| schema.accepts(value))
+            });
+            match structured {
+                // Schema-constrained prompts yield one record per output item
+                Some(Some(value)) => {
+                    for (item, fields) in records_from_output(&value).into_iter().enumerate() {
+                        let item = item.to_string();
+                        // Bookkeeping fields win over schema fields of the same name
+                        let mut record = fields;
+                        record.extend(base.clone());
+                        record.insert("record_id".to_string(), stable_record_id("llm", &[name.as_str(), &section, &item]));
+                        records.push(record);
+                    }
+                }
+                Some(None) => {
+                    let mut record = base;
+                    record.insert("record_id".to_string(), stable_record_id("llm", &[name.as_str(), &section]));
+                    record.insert("output".to_string(), response.text.clone());
+                    record.insert("parse_error".to_string(), "Output does not match the prompt schema".to_string());
+                    records.push(record);
+                }
+                None => {
+                    let mut record = base;
+                    record.insert("record_id".to_string(), stable_record_id("llm", &[name.as_str(), &section]));
+                    record.insert("output".to_string(), response.text.clone());
+                    records.push(record);
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    // Budget warnings raised so far, e.g. "document budget exhausted: 40 of 40 calls used"
+    pub fn get_warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    // Withheld records of the last document: reason, rule, action and offsets, never the text
+    pub fn get_withheld(&self) -> &[HashMap<String, String>] {
+        &self.withheld
+    }
+
+    pub fn get_usage(&self) -> &LlmUsage {
+        &self.usage
+    }
+
+    pub fn get_cache_stats(&self) -> Option<&CacheStats> {
+        self.cache.as_ref().map(|cache| cache.get_stats())
+    }
+
+    pub fn get_provider_health(&self) -> Vec<ProviderHealth> {
+        self.provider.health()
+    }
+
+    // Run report returned to callers - usage plus cache statistics when caching is on, and the
+    // retries, failovers and circuit state per provider
+    pub fn get_report(&self) -> HashMap<String, String> {
+        let mut report = self.get_cache_stats().map(CacheStats::get_summary).unwrap_or_default();
+        report.insert("provider".to_string(), self.provider.name().to_string());
+        let health = self.get_provider_health();
+        if !health.is_empty() {
+            let total = |count: fn(&ProviderHealth) -> u64| health.iter().map(count).sum::<u64>().to_string();
+            report.insert("retries".to_string(), total(|provider| provider.retries));
+            report.insert("failovers".to_string(), total(|provider| provider.failovers));
+            report.insert("circuit_trips".to_string(), total(|provider| provider.circuit_trips));
+            let circuits: Vec<String> = health.iter().map(|provider| format!("{}={}", provider.provider, provider.circuit)).collect();
+            report.insert("provider_health".to_string(), circuits.join(", "));
+        }
+        report.insert("model".to_string(), self.config.model.clone());
+        report.insert("tokenizer".to_string(), self.counter.name().to_string());
+        report.insert("llm_calls".to_string(), self.usage.calls.to_string());
+        report.insert("prompt_tokens".to_string(), self.usage.prompt_tokens.to_string());
+        report.insert("completion_tokens".to_string(), self.usage.completion_tokens.to_string());
+        report.insert("estimated_cost".to_string(), format!("{:.4}", self.run_budget.get_spent().cost));
+        report.insert("budget_exhausted".to_string(), (!self.warnings.is_empty()).to_string());
+        report.insert("skipped_prompts".to_string(), self.skipped_prompts.to_string());
+        report.insert("coalesced_prompts".to_string(), self.coalesced_prompts.to_string());
+        if !self.config.content_filter.is_empty() {
+            report.insert("withheld_sections".to_string(), self.withheld_sections.to_string());
+            report.insert("withheld_spans".to_string(), self.withheld_spans.to_string());
+        }
+        report
+    }
+}
+
+pub fn llm_extract_file(
+    input_path: &str,
+    engine: &ExtractionEngine,
+    assistant: &mut LlmAssistant,
+    output_path: &str,
+    scope: &ExtractionScope,
+) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let decoded = engine.read_input(input_path)?;
+    let text = decoded.text;
+    // Pages left blank by the scope are never sent to the model
+    let scoped = scope.apply(engine, &text).map_err(|e| format!("{}: {}", input_path, e))?;
+    let mut assisted = assistant.assist_document(engine, &scoped)?;
+    engine.tag_access(&scoped, &mut assisted);
+    tag_languages(&scoped, &mut assisted);
+    engine.redact_pii(&scoped, &mut assisted);
+    engine.namespace_ids(&mut assisted);
+    let mut withheld = assistant.get_withheld().to_vec();
+    engine.tag_access(&scoped, &mut withheld);
+    tag_languages(&scoped, &mut withheld);
+    engine.namespace_ids(&mut withheld);
+    let mut report = assistant.get_report();
+
+    let mut payload = json!({
+        "source": input_path,
+        "modules": engine.extract_modules(&scoped),
+        "steps": engine.extract_steps(&scoped),
+        "llm": assisted,
+        "llm_report": report,
+        "warnings": assistant.get_warnings(),
+    });
+    let health = assistant.get_provider_health();
+    if !health.is_empty() {
+        payload["llm_providers"] = json!(health);
+    }
+    if !withheld.is_empty() {
+        payload["llm_withheld"] = json!(withheld);
+    }
+    if let Some(pii) = engine.scan_pii(&scoped) {
+        payload["pii"] = json!(pii);
+    }
+    let page_report = scope.page_report(&decoded.report);
+    page_report.add_to(&mut payload, page_report.failure_records());
+    scope.add_to(&mut payload);
+    let provenance = Provenance::new(engine, UNLICENSED_TIER, text.as_bytes());
+    let written = engine.get_delivery().write_json(Path::new(output_path), &wrap_output(&provenance, payload))?;
+    record_document_processed();
+    report.insert("output_path".to_string(), written.to_string_lossy().to_string());
+    Ok(report)
+}
+
+// Python binding - rules extraction plus the rule pack's prompts for each page
+#[pyfunction]
+#[pyo3(signature = (input_path, rules_path, llm_config_path, output_path, pages=None, sections=None))]
+pub fn llm_extract(
+    py: Python,
+    input_path: &str,
+    rules_path: &str,
+    llm_config_path: &str,
+    output_path: &str,
+    pages: Option<(usize, usize)>,
+    sections: Option<Vec<String>>,
+) -> PyResult<HashMap<String, String>> {
+    let scope = scope_from_args(pages, sections)?;
+    let run = || -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+        let engine = ExtractionEngine::from_rules_file(rules_path)?;
+        let mut assistant = LlmAssistant::from_config(LlmConfig::load(llm_config_path)?)?;
+        llm_extract_file(input_path, &engine, &mut assistant, output_path, &scope)
+    };
+    py.allow_threads(|| run().map_err(|e| e.to_string()))
+        .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)
+}
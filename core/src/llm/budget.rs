@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use crate::llm::provider::LlmResponse;
+
+// Limits for one scope (a run or a single document); unset limits are unbounded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Budget {
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    #[serde(default)]
+    pub max_calls: Option<u64>,
+    // In the currency the prices are quoted in
+    #[serde(default)]
+    pub max_cost: Option<f64>,
+}
+
+// Provider list prices used for the cost estimate
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Pricing {
+    #[serde(default)]
+    pub prompt_per_1k_tokens: f64,
+    #[serde(default)]
+    pub completion_per_1k_tokens: f64,
+}
+
+impl Pricing {
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.prompt_per_1k_tokens + completion_tokens as f64 * self.completion_per_1k_tokens) / 1000.0
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BudgetSpend {
+    pub calls: u64,
+    pub tokens: u64,
+    pub cost: f64,
+}
+
+pub struct BudgetTracker {
+    scope: &'static str,
+    limits: Budget,
+    spent: BudgetSpend,
+    // Worst-case spend of calls that are admitted but still in flight
+    reserved: BudgetSpend,
+}
+
+impl BudgetTracker {
+    pub fn new(scope: &'static str, limits: Budget) -> Self {
+        Self { scope, limits, spent: BudgetSpend::default(), reserved: BudgetSpend::default() }
+    }
+
+    // Admits a call only if its worst case - the counted prompt plus a full completion -
+    // fits next to what is spent and reserved, so concurrent calls can never overshoot a limit
+    pub fn check(&self, prompt_tokens: u64, max_completion_tokens: u32, pricing: &Pricing) -> Result<BudgetSpend, String> {
+        let completion_tokens = u64::from(max_completion_tokens);
+        let worst_case = BudgetSpend {
+            calls: 1,
+            tokens: prompt_tokens + completion_tokens,
+            cost: pricing.cost(prompt_tokens, completion_tokens),
+        };
+        let calls = self.spent.calls + self.reserved.calls;
+        let tokens = self.spent.tokens + self.reserved.tokens;
+        let cost = self.spent.cost + self.reserved.cost;
+
+        if let Some(max) = self.limits.max_calls {
+            if calls + worst_case.calls > max {
+                return Err(format!("{} budget exhausted: {} of {} calls used", self.scope, calls, max));
+            }
+        }
+        if let Some(max) = self.limits.max_tokens {
+            if tokens + worst_case.tokens > max {
+                return Err(format!("{} budget exhausted: {} of {} tokens used", self.scope, tokens, max));
+            }
+        }
+        if let Some(max) = self.limits.max_cost {
+            if cost + worst_case.cost > max {
+                return Err(format!("{} budget exhausted: {:.4} of {:.4} cost used", self.scope, cost, max));
+            }
+        }
+        Ok(worst_case)
+    }
+
+    pub fn reserve(&mut self, worst_case: &BudgetSpend) {
+        self.reserved.calls += worst_case.calls;
+        self.reserved.tokens += worst_case.tokens;
+        self.reserved.cost += worst_case.cost;
+    }
+
+    // Drops reservations once in-flight calls have been recorded with their actual usage
+    pub fn settle(&mut self) {
+        self.reserved = BudgetSpend::default();
+    }
+
+    pub fn record(&mut self, response: &LlmResponse, pricing: &Pricing) {
+        self.spent.calls += 1;
+        self.spent.tokens += response.prompt_tokens + response.completion_tokens;
+        self.spent.cost += pricing.cost(response.prompt_tokens, response.completion_tokens);
+    }
+
+    pub fn get_spent(&self) -> &BudgetSpend {
+        &self.spent
+    }
+}
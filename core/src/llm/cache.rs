@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+
+use crate::llm::provider::LlmResponse;
+use crate::security::crypto::{sha256, to_hex};
+use crate::storage::backend::{open_storage, Storage};
+
+pub const CACHE_NAMESPACE: &str = "llm_cache";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub writes: u64,
+    // Provider tokens served from the cache instead of being paid for again
+    pub tokens_saved: u64,
+}
+
+impl CacheStats {
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let mut summary = HashMap::new();
+        summary.insert("cache_hits".to_string(), self.hits.to_string());
+        summary.insert("cache_misses".to_string(), self.misses.to_string());
+        summary.insert("cache_writes".to_string(), self.writes.to_string());
+        summary.insert("cache_tokens_saved".to_string(), self.tokens_saved.to_string());
+        summary
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    model: String,
+    template_version: String,
+    chunk_hash: String,
+    created_at: DateTime<Utc>,
+    response: LlmResponse,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&sha256(&[data]))
+}
+
+// Templates are versioned by content, so editing a prompt invalidates its entries
+// while rule changes elsewhere in the pack do not
+pub fn template_version(template: &str) -> String {
+    sha256_hex(template.as_bytes())[..16].to_string()
+}
+
+pub fn chunk_hash(chunk: &str) -> String {
+    sha256_hex(chunk.as_bytes())
+}
+
+pub fn cache_key(model: &str, template_version: &str, chunk_hash: &str, max_tokens: u32) -> String {
+    let material = [model, template_version, chunk_hash, &max_tokens.to_string()].join("\x1f");
+    sha256_hex(material.as_bytes())
+}
+
+// Content-addressed cache of LLM responses - one JSON entry per key in the llm_cache namespace
+pub struct PromptCache {
+    storage: Arc<dyn Storage>,
+    stats: CacheStats,
+}
+
+impl PromptCache {
+    // A cache directory, or any storage spec such as "sqlite:cache.db"
+    pub fn open(spec: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::new(open_storage(spec)?))
+    }
+
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self { storage, stats: CacheStats::default() }
+    }
+
+    // An unreadable or foreign entry is a miss, never an error
+    pub fn get(&mut self, key: &str) -> Option<LlmResponse> {
+        let entry = self.storage.get(CACHE_NAMESPACE, key)
+            .ok()
+            .flatten()
+            .and_then(|data| serde_json::from_slice::<CacheEntry>(&data).ok())
+            .filter(|entry| entry.key == key);
+
+        match entry {
+            Some(entry) => {
+                self.stats.hits += 1;
+                self.stats.tokens_saved += entry.response.prompt_tokens + entry.response.completion_tokens;
+                Some(entry.response)
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn put(
+        &mut self,
+        key: &str,
+        model: &str,
+        template_version: &str,
+        chunk_hash: &str,
+        response: &LlmResponse,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let entry = CacheEntry {
+            key: key.to_string(),
+            model: model.to_string(),
+            template_version: template_version.to_string(),
+            chunk_hash: chunk_hash.to_string(),
+            created_at: Utc::now(),
+            response: response.clone(),
+        };
+        self.storage.put(CACHE_NAMESPACE, key, &serde_json::to_vec(&entry)?)?;
+        self.stats.writes += 1;
+        Ok(())
+    }
+
+    pub fn get_stats(&self) -> &CacheStats {
+        &self.stats
+    }
+}
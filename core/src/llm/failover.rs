@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::llm::provider::{LlmProvider, LlmRequest, LlmResponse, LlmStatusError};
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_backoff_multiplier() -> f64 {
+    2.0
+}
+
+fn default_max_backoff_ms() -> u64 {
+    8_000
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_cooldown_seconds() -> u64 {
+    60
+}
+
+// Rounds over the providers per request. Between rounds the wait grows by `multiplier`, from
+// `initial_backoff_ms` up to `max_backoff_ms`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_backoff_multiplier")]
+    pub multiplier: f64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            multiplier: default_backoff_multiplier(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("retry.max_attempts must be at least 1".to_string());
+        }
+        if self.multiplier.is_nan() || self.multiplier < 1.0 {
+            return Err(format!("retry.multiplier must be at least 1, got {}", self.multiplier));
+        }
+        Ok(())
+    }
+
+    // Wait before round `round` (the first retry is round 1)
+    pub fn backoff(&self, round: u32) -> Duration {
+        let delay = self.initial_backoff_ms as f64 * self.multiplier.powi(round.saturating_sub(1) as i32);
+        Duration::from_millis(delay.min(self.max_backoff_ms as f64) as u64)
+    }
+}
+
+// A provider failing `failure_threshold` calls in a row is skipped for `cooldown_seconds`, then
+// gets one trial call: success closes the circuit again, failure starts another cooldown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    #[serde(default = "default_cooldown_seconds")]
+    pub cooldown_seconds: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self { failure_threshold: default_failure_threshold(), cooldown_seconds: default_cooldown_seconds() }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.failure_threshold == 0 {
+            return Err("circuit_breaker.failure_threshold must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CircuitState {
+    Closed,
+    Open(Instant),
+    // Cooldown over and the trial call in flight
+    HalfOpen,
+}
+
+enum Outcome {
+    Success,
+    Failure(String),
+    // The provider answered but refused the request - says nothing about its health
+    Rejected(String),
+}
+
+// How one provider fared over a run, for the run report
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProviderHealth {
+    pub provider: String,
+    // "closed", "open" or "half_open"
+    pub circuit: String,
+    pub calls: u64,
+    pub successes: u64,
+    pub failures: u64,
+    pub retries: u64,
+    // Calls served after a more preferred provider failed or was cooling down
+    pub failovers: u64,
+    // Calls that passed this provider by while its circuit was open
+    pub skipped: u64,
+    pub circuit_trips: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+struct MemberState {
+    circuit: CircuitState,
+    consecutive_failures: u32,
+    health: ProviderHealth,
+}
+
+struct Member {
+    provider: Box<dyn LlmProvider>,
+    // Model name at this endpoint, when it differs from the request's
+    model: Option<String>,
+    state: Mutex<MemberState>,
+}
+
+// Client errors other than timeouts and rate limits fail the same way on every retry
+fn is_retryable(error: &(dyn std::error::Error + 'static)) -> bool {
+    match error.downcast_ref::<LlmStatusError>() {
+        Some(error) => !(400..500).contains(&error.status) || matches!(error.status, 408 | 429),
+        None => true,
+    }
+}
+
+// Providers in order of preference behind one LlmProvider: each request goes to the first
+// provider whose circuit is closed, and fails over to the next when it errors
+pub struct FailoverProvider {
+    name: String,
+    members: Vec<Member>,
+    retry: RetryPolicy,
+    breaker: CircuitBreakerConfig,
+}
+
+impl FailoverProvider {
+    // (label, provider, model override) per provider, most preferred first
+    pub fn new(providers: Vec<(String, Box<dyn LlmProvider>, Option<String>)>, retry: RetryPolicy, breaker: CircuitBreakerConfig) -> Self {
+        let name = providers.iter().map(|(_, provider, _)| provider.name()).collect::<Vec<_>>().join("+");
+        let members = providers.into_iter()
+            .map(|(label, provider, model)| Member {
+                provider,
+                model,
+                state: Mutex::new(MemberState {
+                    circuit: CircuitState::Closed,
+                    consecutive_failures: 0,
+                    health: ProviderHealth { provider: label, ..ProviderHealth::default() },
+                }),
+            })
+            .collect();
+        Self { name, members, retry, breaker }
+    }
+
+    // Whether the member takes this call; claims the trial call of a half-open circuit
+    fn admit(&self, member: &Member) -> bool {
+        let mut state = member.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.circuit {
+            CircuitState::Closed => true,
+            CircuitState::Open(until) if Instant::now() >= until => {
+                state.circuit = CircuitState::HalfOpen;
+                true
+            }
+            _ => {
+                state.health.skipped += 1;
+                false
+            }
+        }
+    }
+
+    fn record(&self, member: &Member, outcome: Outcome, retry: bool, failover: bool) {
+        let mut state = member.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.health.calls += 1;
+        state.health.retries += u64::from(retry);
+        match outcome {
+            Outcome::Success => {
+                state.health.successes += 1;
+                state.health.failovers += u64::from(failover);
+                state.consecutive_failures = 0;
+                state.circuit = CircuitState::Closed;
+            }
+            Outcome::Rejected(error) => {
+                state.health.failures += 1;
+                state.health.last_error = Some(error);
+                state.consecutive_failures = 0;
+                state.circuit = CircuitState::Closed;
+            }
+            Outcome::Failure(error) => {
+                state.health.failures += 1;
+                state.health.last_error = Some(error);
+                state.consecutive_failures += 1;
+                let trial_failed = state.circuit == CircuitState::HalfOpen;
+                if trial_failed || state.consecutive_failures >= self.breaker.failure_threshold {
+                    if !matches!(state.circuit, CircuitState::Open(_)) {
+                        state.health.circuit_trips += 1;
+                    }
+                    state.circuit = CircuitState::Open(Instant::now() + Duration::from_secs(self.breaker.cooldown_seconds));
+                }
+            }
+        }
+    }
+}
+
+impl LlmProvider for FailoverProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let mut errors: Vec<String> = Vec::new();
+        // Providers that rejected the request itself are not asked again
+        let mut rejected = vec![false; self.members.len()];
+        let mut attempted = vec![false; self.members.len()];
+        for round in 0..self.retry.max_attempts {
+            if round > 0 {
+                if rejected.iter().all(|rejected| *rejected) {
+                    break;
+                }
+                std::thread::sleep(self.retry.backoff(round));
+            }
+            for (index, member) in self.members.iter().enumerate() {
+                if rejected[index] || !self.admit(member) {
+                    continue;
+                }
+                let member_request = LlmRequest {
+                    model: member.model.clone().unwrap_or_else(|| request.model.clone()),
+                    prompt: request.prompt.clone(),
+                    max_tokens: request.max_tokens,
+                    schema: request.schema.clone(),
+                };
+                let retry = attempted[index];
+                attempted[index] = true;
+                match member.provider.complete(&member_request) {
+                    Ok(response) => {
+                        self.record(member, Outcome::Success, retry, index > 0);
+                        return Ok(response);
+                    }
+                    Err(error) => {
+                        let label = member.state.lock().unwrap_or_else(|e| e.into_inner()).health.provider.clone();
+                        errors.push(format!("{}: {}", label, error));
+                        rejected[index] = !is_retryable(error.as_ref());
+                        let outcome = if rejected[index] { Outcome::Rejected(error.to_string()) } else { Outcome::Failure(error.to_string()) };
+                        self.record(member, outcome, retry, false);
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            return Err("Every LLM provider is cooling down after repeated failures".into());
+        }
+        Err(format!("All LLM providers failed: {}", errors.join("; ")).into())
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.members.iter()
+            .map(|member| {
+                let state = member.state.lock().unwrap_or_else(|e| e.into_inner());
+                let mut health = state.health.clone();
+                health.circuit = match state.circuit {
+                    CircuitState::Closed => "closed",
+                    CircuitState::Open(_) => "open",
+                    CircuitState::HalfOpen => "half_open",
+                }
+                .to_string();
+                health
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::engine::extractor::{find_matches, ExtractionEngine};
+use crate::engine::ids::stable_record_id;
+use crate::engine::pii::PiiConfig;
+
+fn default_replacement() -> String {
+    "[WITHHELD]".to_string()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterAction {
+    // Replace the matching content and send the rest of the section
+    #[default]
+    Strip,
+    // Send nothing from the section
+    Block,
+}
+
+impl FilterAction {
+    fn name(&self) -> &'static str {
+        match self {
+            FilterAction::Strip => "strip",
+            FilterAction::Block => "block",
+        }
+    }
+}
+
+// Literal terms that mark controlled content, e.g. {"name": "itar", "terms": ["ITAR", "USML"]}.
+// Stripping removes each line holding a term
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterRule {
+    pub name: String,
+    pub terms: Vec<String>,
+    pub action: FilterAction,
+}
+
+// What never leaves for the LLM provider. Checked per section before the prompt is rendered;
+// an empty filter sends every section as it is
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentFilter {
+    // Access tags (the rule pack's access sections) whose text is withheld
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub tag_action: FilterAction,
+    #[serde(default)]
+    pub rules: Vec<FilterRule>,
+    // PII found by the rule pack's detectors, or every built-in detector when it enables none
+    #[serde(default)]
+    pub pii: Option<FilterAction>,
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+}
+
+impl Default for ContentFilter {
+    fn default() -> Self {
+        Self {
+            tags: Vec::new(),
+            tag_action: FilterAction::default(),
+            rules: Vec::new(),
+            pii: None,
+            replacement: default_replacement(),
+        }
+    }
+}
+
+// One piece of a section kept from the provider. Holds where and why, never the text itself
+#[derive(Debug, Clone)]
+pub struct Withheld {
+    // access_tag, rule or pii
+    pub reason: &'static str,
+    // The tag, rule name or PII detector
+    pub rule: String,
+    pub action: FilterAction,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Withheld {
+    pub fn get_info(&self, section: usize) -> HashMap<String, String> {
+        let start = self.start.to_string();
+        let mut info = HashMap::new();
+        info.insert("record_id".to_string(), stable_record_id("withheld", &[self.reason, &self.rule, &start]));
+        info.insert("section".to_string(), section.to_string());
+        info.insert("reason".to_string(), self.reason.to_string());
+        info.insert("rule".to_string(), self.rule.clone());
+        info.insert("action".to_string(), self.action.name().to_string());
+        info.insert("original_start".to_string(), start);
+        info.insert("original_end".to_string(), self.end.to_string());
+        info
+    }
+}
+
+// A section as it may be sent: None when a block applies
+pub struct FilteredSection {
+    pub text: Option<String>,
+    pub withheld: Vec<Withheld>,
+}
+
+// The line holding the byte range, without its newline
+fn line_around(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let line_start = text[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = text[end..].find('\n').map(|i| end + i).unwrap_or(text.len());
+    (line_start, line_end)
+}
+
+impl ContentFilter {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.rules.is_empty() && self.pii.is_none()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(tag) = self.tags.iter().find(|tag| tag.trim().is_empty()) {
+            return Err(format!("content_filter.tags holds an empty tag: {:?}", tag));
+        }
+        for rule in &self.rules {
+            if rule.name.trim().is_empty() {
+                return Err("content_filter.rules entries need a name".to_string());
+            }
+            if rule.terms.is_empty() || rule.terms.iter().any(String::is_empty) {
+                return Err(format!("content_filter rule {} needs non-empty terms", rule.name));
+            }
+        }
+        if self.replacement.is_empty() {
+            return Err("content_filter.replacement must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    // What to withhold from each section of `text`, as offsets into `text`
+    pub fn scan(&self, engine: &ExtractionEngine, text: &str) -> Vec<Withheld> {
+        let mut withheld = Vec::new();
+        if !self.tags.is_empty() {
+            let access = engine.access_map(text);
+            for tag in &self.tags {
+                for (start, end) in access.extents_tagged(tag.trim(), text.len()) {
+                    withheld.push(Withheld { reason: "access_tag", rule: tag.trim().to_string(), action: self.tag_action, start, end });
+                }
+            }
+        }
+        for rule in &self.rules {
+            for term in &rule.terms {
+                for (start, end) in find_matches(term, text) {
+                    let (start, end) = match rule.action {
+                        FilterAction::Strip => line_around(text, start, end),
+                        FilterAction::Block => (start, end),
+                    };
+                    withheld.push(Withheld { reason: "rule", rule: rule.name.clone(), action: rule.action, start, end });
+                }
+            }
+        }
+        if let Some(action) = self.pii {
+            let builtin;
+            let config = if engine.get_pii_config().is_enabled() {
+                engine.get_pii_config()
+            } else {
+                builtin = PiiConfig::builtin();
+                &builtin
+            };
+            for finding in config.scan(text) {
+                withheld.push(Withheld { reason: "pii", rule: finding.detector.to_string(), action, start: finding.start, end: finding.end });
+            }
+        }
+        withheld.sort_by_key(|item| (item.start, item.end));
+        withheld.dedup_by(|later, earlier| later.reason == earlier.reason && later.rule == earlier.rule && later.start == earlier.start);
+        withheld
+    }
+
+    // The section at `offset` with its withheld parts replaced, or no text when one blocks it or
+    // nothing is left to send. `withheld` is what scan found across the whole text
+    pub fn apply(&self, withheld: &[Withheld], offset: usize, section: &str) -> FilteredSection {
+        let end = offset + section.len();
+        let hits: Vec<Withheld> = withheld.iter()
+            .filter(|item| item.start < end && item.end > offset)
+            .map(|item| Withheld { start: item.start.max(offset), end: item.end.min(end), ..item.clone() })
+            .collect();
+        if hits.iter().any(|item| item.action == FilterAction::Block) {
+            return FilteredSection { text: None, withheld: hits };
+        }
+
+        let mut text = String::with_capacity(section.len());
+        let mut kept = false;
+        let mut copied = 0;
+        for item in &hits {
+            let (start, end) = (item.start - offset, item.end - offset);
+            if start < copied {
+                copied = copied.max(end);
+                continue;
+            }
+            kept |= !section[copied..start].trim().is_empty();
+            text.push_str(&section[copied..start]);
+            text.push_str(&self.replacement);
+            copied = end;
+        }
+        kept |= !section[copied..].trim().is_empty();
+        text.push_str(&section[copied..]);
+        FilteredSection { text: kept.then_some(text), withheld: hits }
+    }
+}
@@ -0,0 +1,328 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Subset of JSON Schema that constrained decoding can enforce character by character.
+// Object properties are emitted in key order and are all required
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonSchema {
+    String { values: Option<Vec<String>>, max_length: Option<usize> },
+    Number,
+    Integer,
+    Boolean,
+    Array { items: Box<JsonSchema>, max_items: Option<usize> },
+    Object { properties: Vec<(String, JsonSchema)> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixState {
+    Invalid,
+    // Valid so far but not yet a complete document
+    Partial,
+    Complete,
+}
+
+impl JsonSchema {
+    pub fn from_value(schema: &Value) -> Result<Self, Box<dyn std::error::Error>> {
+        let enum_values = schema.get("enum").and_then(Value::as_array).map(|values| {
+            values.iter().filter_map(Value::as_str).map(str::to_string).collect::<Vec<_>>()
+        });
+        let schema_type = schema.get("type").and_then(Value::as_str)
+            .or(enum_values.as_ref().map(|_| "string"))
+            .ok_or("Schema needs a type")?;
+
+        Ok(match schema_type {
+            "string" => JsonSchema::String {
+                values: enum_values,
+                max_length: schema.get("maxLength").and_then(Value::as_u64).map(|n| n as usize),
+            },
+            "number" => JsonSchema::Number,
+            "integer" => JsonSchema::Integer,
+            "boolean" => JsonSchema::Boolean,
+            "array" => {
+                let items = match schema.get("items") {
+                    Some(items) => JsonSchema::from_value(items)?,
+                    None => JsonSchema::String { values: None, max_length: None },
+                };
+                JsonSchema::Array {
+                    items: Box::new(items),
+                    max_items: schema.get("maxItems").and_then(Value::as_u64).map(|n| n as usize),
+                }
+            }
+            "object" => {
+                let mut properties = Vec::new();
+                if let Some(map) = schema.get("properties").and_then(Value::as_object) {
+                    for (name, property) in map {
+                        properties.push((name.clone(), JsonSchema::from_value(property)?));
+                    }
+                }
+                properties.sort_by(|a, b| a.0.cmp(&b.0));
+                JsonSchema::Object { properties }
+            }
+            other => return Err(format!("Unsupported schema type for constrained output: {}", other).into()),
+        })
+    }
+
+    // Whether text can still be extended into a document matching the schema
+    pub fn check_prefix(&self, text: &str) -> PrefixState {
+        let mut cursor = Cursor { chars: text.chars().collect(), position: 0 };
+        match cursor.value(self) {
+            Err(()) => PrefixState::Invalid,
+            Ok(Step::Eof) => PrefixState::Partial,
+            Ok(Step::Done) => {
+                cursor.whitespace();
+                if cursor.position == cursor.chars.len() {
+                    PrefixState::Complete
+                } else {
+                    PrefixState::Invalid
+                }
+            }
+        }
+    }
+
+    // Full validation for outputs that were not generated under the constraint (e.g. HTTP providers)
+    pub fn accepts(&self, value: &Value) -> bool {
+        match (self, value) {
+            (JsonSchema::String { values, max_length }, Value::String(s)) => {
+                values.as_ref().is_none_or(|values| values.contains(s))
+                    && max_length.is_none_or(|max| s.chars().count() <= max)
+            }
+            (JsonSchema::Number, Value::Number(_)) => true,
+            (JsonSchema::Integer, Value::Number(n)) => n.is_i64() || n.is_u64(),
+            (JsonSchema::Boolean, Value::Bool(_)) => true,
+            (JsonSchema::Array { items, max_items }, Value::Array(values)) => {
+                max_items.is_none_or(|max| values.len() <= max) && values.iter().all(|v| items.accepts(v))
+            }
+            (JsonSchema::Object { properties }, Value::Object(map)) => {
+                map.len() == properties.len()
+                    && properties.iter().all(|(name, schema)| map.get(name).is_some_and(|v| schema.accepts(v)))
+            }
+            _ => false,
+        }
+    }
+}
+
+enum Step {
+    Done,
+    // Input ended while everything so far was valid
+    Eof,
+}
+
+type Parse = Result<Step, ()>;
+
+macro_rules! step {
+    ($parse:expr) => {
+        if let Step::Eof = $parse? {
+            return Ok(Step::Eof);
+        }
+    };
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Cursor {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek();
+        self.position += usize::from(ch.is_some());
+        ch
+    }
+
+    // At most one whitespace character between tokens, so a model cannot pad forever
+    fn whitespace(&mut self) {
+        if self.peek().is_some_and(|c| c == ' ' || c == '\n') {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Parse {
+        match self.next() {
+            None => Ok(Step::Eof),
+            Some(ch) if ch == expected => Ok(Step::Done),
+            Some(_) => Err(()),
+        }
+    }
+
+    fn literal(&mut self, literal: &str) -> Parse {
+        for expected in literal.chars() {
+            step!(self.expect(expected));
+        }
+        Ok(Step::Done)
+    }
+
+    fn value(&mut self, schema: &JsonSchema) -> Parse {
+        match schema {
+            JsonSchema::String { values, max_length } => self.string(values.as_deref(), *max_length),
+            JsonSchema::Number => self.number(false),
+            JsonSchema::Integer => self.number(true),
+            JsonSchema::Boolean => match self.peek() {
+                None => Ok(Step::Eof),
+                Some('t') => self.literal("true"),
+                Some('f') => self.literal("false"),
+                Some(_) => Err(()),
+            },
+            JsonSchema::Array { items, max_items } => self.array(items, *max_items),
+            JsonSchema::Object { properties } => self.object(properties),
+        }
+    }
+
+    fn string(&mut self, values: Option<&[String]>, max_length: Option<usize>) -> Parse {
+        step!(self.expect('"'));
+        let mut content = String::new();
+        loop {
+            let ch = match self.next() {
+                None => return Ok(Step::Eof),
+                Some('"') => {
+                    return match values {
+                        Some(values) if !values.contains(&content) => Err(()),
+                        _ => Ok(Step::Done),
+                    };
+                }
+                Some('\\') => match self.next() {
+                    None => return Ok(Step::Eof),
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('/') => '/',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('u') => {
+                        let mut code = 0;
+                        for _ in 0..4 {
+                            let digit = match self.next() {
+                                None => return Ok(Step::Eof),
+                                Some(c) => c.to_digit(16).ok_or(())?,
+                            };
+                            code = code * 16 + digit;
+                        }
+                        char::from_u32(code).ok_or(())?
+                    }
+                    Some(_) => return Err(()),
+                },
+                Some(c) if (c as u32) < 0x20 => return Err(()),
+                Some(c) => c,
+            };
+            content.push(ch);
+
+            if max_length.is_some_and(|max| content.chars().count() > max) {
+                return Err(());
+            }
+            if values.is_some_and(|values| !values.iter().any(|v| v.starts_with(&content))) {
+                return Err(());
+            }
+        }
+    }
+
+    fn digits(&mut self) -> Parse {
+        match self.next() {
+            None => return Ok(Step::Eof),
+            Some(c) if c.is_ascii_digit() => {}
+            Some(_) => return Err(()),
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        Ok(Step::Done)
+    }
+
+    fn number(&mut self, integer: bool) -> Parse {
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+        match self.peek() {
+            None => return Ok(Step::Eof),
+            // JSON forbids leading zeros
+            Some('0') => self.position += 1,
+            Some(_) => step!(self.digits()),
+        }
+        if !integer && self.peek() == Some('.') {
+            self.position += 1;
+            step!(self.digits());
+        }
+        if !integer && matches!(self.peek(), Some('e') | Some('E')) {
+            self.position += 1;
+            if matches!(self.peek(), Some('+') | Some('-')) {
+                self.position += 1;
+            }
+            step!(self.digits());
+        }
+        // A number is only finished once something follows it
+        if self.peek().is_none() {
+            return Ok(Step::Eof);
+        }
+        Ok(Step::Done)
+    }
+
+    fn array(&mut self, items: &JsonSchema, max_items: Option<usize>) -> Parse {
+        step!(self.expect('['));
+        self.whitespace();
+        match self.peek() {
+            None => return Ok(Step::Eof),
+            Some(']') => {
+                self.position += 1;
+                return Ok(Step::Done);
+            }
+            Some(_) => {}
+        }
+
+        let mut count = 0;
+        loop {
+            step!(self.value(items));
+            count += 1;
+            self.whitespace();
+            match self.next() {
+                None => return Ok(Step::Eof),
+                Some(']') => return Ok(Step::Done),
+                Some(',') if max_items.is_none_or(|max| count < max) => self.whitespace(),
+                Some(_) => return Err(()),
+            }
+        }
+    }
+
+    fn object(&mut self, properties: &[(String, JsonSchema)]) -> Parse {
+        step!(self.expect('{'));
+        self.whitespace();
+        for (index, (name, schema)) in properties.iter().enumerate() {
+            if index > 0 {
+                step!(self.expect(','));
+                self.whitespace();
+            }
+            step!(self.literal(&Value::String(name.clone()).to_string()));
+            self.whitespace();
+            step!(self.expect(':'));
+            self.whitespace();
+            step!(self.value(schema));
+            self.whitespace();
+        }
+        self.expect('}')
+    }
+}
+
+// Flattens a schema-valid output into records - one per array item, or one for an object
+pub fn records_from_output(value: &Value) -> Vec<HashMap<String, String>> {
+    let items: Vec<&Value> = match value {
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    };
+    items.into_iter()
+        .filter_map(Value::as_object)
+        .map(|item| {
+            item.iter()
+                .map(|(key, value)| {
+                    let text = match value {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    (key.clone(), text)
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,187 @@
+use candle_core::quantized::gguf_file;
+use candle_core::{DType, Device, Tensor};
+use candle_transformers::generation::LogitsProcessor;
+use candle_transformers::models::{quantized_llama, quantized_qwen2};
+use std::sync::Mutex;
+use tokenizers::decoders::DecoderWrapper;
+use tokenizers::Tokenizer;
+
+use crate::llm::grammar::{JsonSchema, PrefixState};
+use crate::llm::provider::{LlmProvider, LlmRequest, LlmResponse};
+
+// End-of-turn markers across the model families we ship templates for
+const STOP_TOKENS: &[&str] = &["</s>", "<|endoftext|>", "<|im_end|>", "<|eot_id|>", "<|end|>"];
+
+enum LocalModel {
+    Llama(quantized_llama::ModelWeights),
+    Qwen2(quantized_qwen2::ModelWeights),
+}
+
+impl LocalModel {
+    fn forward(&mut self, input: &Tensor, position: usize) -> candle_core::Result<Tensor> {
+        match self {
+            LocalModel::Llama(model) => model.forward(input, position),
+            LocalModel::Qwen2(model) => model.forward(input, position),
+        }
+    }
+}
+
+// Quantized GGUF model run on the CPU - no network access needed
+pub struct LocalProvider {
+    // Generation mutates the KV cache, so concurrent requests take turns
+    model: Mutex<LocalModel>,
+    tokenizer: Tokenizer,
+    stop_tokens: Vec<u32>,
+    // Surface text of every token, for checking candidates against an output schema
+    token_texts: Vec<String>,
+    device: Device,
+}
+
+// Inverse of the GPT-2 byte-to-unicode table used by byte-level BPE vocabularies
+fn byte_level_decoder() -> std::collections::HashMap<char, u8> {
+    let mut table = std::collections::HashMap::new();
+    let mut shifted = 0u32;
+    for byte in 0..=255u8 {
+        let printable = matches!(byte, 33..=126 | 161..=172 | 174..=255);
+        let ch = if printable {
+            char::from(byte)
+        } else {
+            shifted += 1;
+            char::from_u32(255 + shifted).unwrap_or(char::REPLACEMENT_CHARACTER)
+        };
+        table.insert(ch, byte);
+    }
+    table
+}
+
+// Tokens that are not complete UTF-8 on their own map to "" and are never chosen under a schema
+fn token_texts(tokenizer: &Tokenizer) -> Vec<String> {
+    let byte_level = matches!(tokenizer.get_decoder(), Some(DecoderWrapper::ByteLevel(_)));
+    let table = byte_level_decoder();
+    (0..tokenizer.get_vocab_size(true) as u32)
+        .map(|id| {
+            let token = tokenizer.id_to_token(id).unwrap_or_default();
+            if byte_level {
+                token.chars()
+                    .map(|c| table.get(&c).copied())
+                    .collect::<Option<Vec<u8>>>()
+                    .and_then(|bytes| String::from_utf8(bytes).ok())
+                    .unwrap_or_default()
+            } else if let Some(hex) = token.strip_prefix("<0x").and_then(|t| t.strip_suffix('>')) {
+                // SentencePiece byte fallback - only single-byte (ASCII) tokens are usable alone
+                u8::from_str_radix(hex, 16).ok()
+                    .filter(u8::is_ascii)
+                    .map(|b| char::from(b).to_string())
+                    .unwrap_or_default()
+            } else {
+                token.replace('\u{2581}', " ")
+            }
+        })
+        .collect()
+}
+
+impl LocalProvider {
+    pub fn load(model_path: &str, tokenizer_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let device = Device::Cpu;
+        let mut file = std::fs::File::open(model_path)
+            .map_err(|e| format!("Cannot open model {}: {}", model_path, e))?;
+        let content = gguf_file::Content::read(&mut file)?;
+
+        let architecture = content.metadata.get("general.architecture")
+            .and_then(|value| value.to_string().ok())
+            .cloned()
+            .unwrap_or_else(|| "llama".to_string());
+        let model = match architecture.as_str() {
+            // Mistral GGUFs use the llama layout
+            "llama" | "mistral" => LocalModel::Llama(quantized_llama::ModelWeights::from_gguf(content, &mut file, &device)?),
+            "qwen2" => LocalModel::Qwen2(quantized_qwen2::ModelWeights::from_gguf(content, &mut file, &device)?),
+            other => return Err(format!("Unsupported GGUF architecture: {}", other).into()),
+        };
+
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| format!("Cannot load tokenizer {}: {}", tokenizer_path, e))?;
+        let stop_tokens: Vec<u32> = STOP_TOKENS.iter().filter_map(|token| tokenizer.token_to_id(token)).collect();
+        let mut token_texts = token_texts(&tokenizer);
+        for &id in &stop_tokens {
+            if let Some(text) = token_texts.get_mut(id as usize) {
+                text.clear();
+            }
+        }
+
+        Ok(Self { model: Mutex::new(model), tokenizer, stop_tokens, token_texts, device })
+    }
+
+    // Highest-scoring token that keeps the output a valid prefix of the schema
+    fn constrained_next(&self, logits: &Tensor, schema: &JsonSchema, output: &str) -> Result<u32, Box<dyn std::error::Error>> {
+        let scores: Vec<f32> = logits.to_vec1()?;
+        let mut ranked: Vec<usize> = (0..scores.len().min(self.token_texts.len())).collect();
+        ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+        for id in ranked {
+            let text = &self.token_texts[id];
+            if text.is_empty() {
+                continue;
+            }
+            if schema.check_prefix(&format!("{}{}", output, text)) != PrefixState::Invalid {
+                return Ok(id as u32);
+            }
+        }
+        Err("No token can continue the schema-constrained output".into())
+    }
+}
+
+impl LlmProvider for LocalProvider {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let encoding = self.tokenizer.encode(request.prompt.as_str(), true).map_err(|e| e.to_string())?;
+        let prompt_tokens = encoding.get_ids().to_vec();
+        if prompt_tokens.is_empty() {
+            return Err("Prompt encodes to no tokens".into());
+        }
+
+        let mut model = self.model.lock().map_err(|_| "Local model lock poisoned")?;
+        // Greedy decoding, matching temperature 0 on the HTTP provider
+        let mut sampler = LogitsProcessor::new(0, None, None);
+        let mut generated: Vec<u32> = Vec::new();
+        let mut constrained_output = String::new();
+        let mut input = Tensor::new(prompt_tokens.as_slice(), &self.device)?.unsqueeze(0)?;
+        let mut position = 0;
+
+        while generated.len() < request.max_tokens as usize {
+            let logits = model.forward(&input, position)?.squeeze(0)?.to_dtype(DType::F32)?;
+            position += input.dim(1)?;
+
+            // Under a schema only tokens that keep the output parseable are eligible,
+            // and generation stops as soon as the document is complete
+            let next = match &request.schema {
+                Some(schema) => {
+                    let next = self.constrained_next(&logits, schema, &constrained_output)?;
+                    constrained_output.push_str(&self.token_texts[next as usize]);
+                    next
+                }
+                None => sampler.sample(&logits)?,
+            };
+            if self.stop_tokens.contains(&next) {
+                break;
+            }
+            generated.push(next);
+            if request.schema.as_ref().is_some_and(|schema| schema.check_prefix(&constrained_output) == PrefixState::Complete) {
+                break;
+            }
+            input = Tensor::new(&[next], &self.device)?.unsqueeze(0)?;
+        }
+
+        let text = match request.schema {
+            Some(_) => constrained_output,
+            None => self.tokenizer.decode(&generated, true).map_err(|e| e.to_string())?,
+        };
+        Ok(LlmResponse {
+            text,
+            prompt_tokens: prompt_tokens.len() as u64,
+            completion_tokens: generated.len() as u64,
+        })
+    }
+}
@@ -0,0 +1,10 @@
+pub mod provider;
+pub mod cache;
+pub mod budget;
+pub mod grammar;
+pub mod assist;
+pub mod failover;
+pub mod filter;
+pub mod tokenizer;
+#[cfg(feature = "local-llm")]
+pub mod local;
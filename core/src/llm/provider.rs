@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::llm::budget::{Budget, Pricing};
+use crate::llm::filter::ContentFilter;
+use crate::llm::failover::{CircuitBreakerConfig, FailoverProvider, ProviderHealth, RetryPolicy};
+use crate::llm::grammar::JsonSchema;
+use crate::llm::tokenizer::TokenizerConfig;
+
+const DEFAULT_MAX_TOKENS: u32 = 512;
+const REQUEST_TIMEOUT_SECONDS: u64 = 120;
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+fn default_provider() -> String {
+    "http".to_string()
+}
+
+fn default_max_tokens() -> u32 {
+    DEFAULT_MAX_TOKENS
+}
+
+fn default_max_concurrency() -> usize {
+    DEFAULT_MAX_CONCURRENCY
+}
+
+// LLM settings are kept out of the rules file - rules are shared, endpoints and keys are not
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmConfig {
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    #[serde(default)]
+    pub endpoint: String,
+    pub model: String,
+    // Name of the environment variable holding the API key, never the key itself
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    // Local backend: GGUF weights and the matching tokenizer.json
+    #[serde(default)]
+    pub model_path: Option<String>,
+    #[serde(default)]
+    pub tokenizer_path: Option<String>,
+    // How budgets count prompt tokens - see llm::tokenizer
+    #[serde(default)]
+    pub tokenizer: Option<TokenizerConfig>,
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+    // Requests in flight at once; 1 restores strictly sequential calls
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    #[serde(default)]
+    pub pricing: Pricing,
+    #[serde(default)]
+    pub run_budget: Budget,
+    #[serde(default)]
+    pub document_budget: Budget,
+    // Tried in order when the provider above fails or is cooling down
+    #[serde(default)]
+    pub fallbacks: Vec<FallbackConfig>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    // What is stripped from or blocks a section before it is sent - see llm::filter
+    #[serde(default)]
+    pub content_filter: ContentFilter,
+}
+
+// Another provider to fail over to, configured like the primary one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallbackConfig {
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    #[serde(default)]
+    pub endpoint: String,
+    // Model name at this provider; the primary's when unset
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    #[serde(default)]
+    pub model_path: Option<String>,
+    #[serde(default)]
+    pub tokenizer_path: Option<String>,
+}
+
+impl LlmConfig {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = std::fs::read(path).map_err(|e| format!("Cannot read LLM config {}: {}", path, e))?;
+        let config: Self = serde_json::from_slice(&data)?;
+        config.retry.validate()?;
+        config.circuit_breaker.validate()?;
+        config.content_filter.validate()?;
+        Ok(config)
+    }
+
+    // The primary provider followed by the fallbacks
+    pub fn providers(&self) -> Vec<FallbackConfig> {
+        let primary = FallbackConfig {
+            provider: self.provider.clone(),
+            endpoint: self.endpoint.clone(),
+            model: None,
+            api_key_env: self.api_key_env.clone(),
+            model_path: self.model_path.clone(),
+            tokenizer_path: self.tokenizer_path.clone(),
+        };
+        std::iter::once(primary).chain(self.fallbacks.iter().cloned()).collect()
+    }
+}
+
+impl FallbackConfig {
+    // How the run report names the provider: its endpoint's host, or its model file
+    pub fn label(&self) -> String {
+        let location = match self.provider.as_str() {
+            "local" => self.model_path.as_deref()
+                .and_then(|path| std::path::Path::new(path).file_name())
+                .map(|name| name.to_string_lossy().to_string()),
+            _ => self.endpoint.strip_prefix("http://")
+                .map(|target| target.split('/').next().unwrap_or(target).to_string()),
+        };
+        match location {
+            Some(location) if !location.is_empty() => format!("{} {}", self.provider, location),
+            _ => self.provider.clone(),
+        }
+    }
+}
+
+pub struct LlmRequest {
+    pub model: String,
+    pub prompt: String,
+    pub max_tokens: u32,
+    // Providers that can constrain decoding must return output matching this schema
+    pub schema: Option<JsonSchema>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmResponse {
+    pub text: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+pub trait LlmProvider: Send + Sync {
+    fn name(&self) -> &str;
+    fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, Box<dyn std::error::Error>>;
+
+    // Per-provider call outcomes, for providers that spread calls over several backends
+    fn health(&self) -> Vec<ProviderHealth> {
+        Vec::new()
+    }
+}
+
+// A non-2xx answer from an endpoint; the status decides whether a retry can help
+#[derive(Debug)]
+pub struct LlmStatusError {
+    pub status: u16,
+    pub body: String,
+}
+
+impl std::fmt::Display for LlmStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "LLM endpoint returned {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for LlmStatusError {}
+
+// OpenAI-compatible chat completions endpoint (vLLM, llama.cpp server, gateways, hosted APIs)
+// over https://, verified against the Mozilla root certificates, or http://
+pub struct HttpProvider {
+    endpoint: String,
+    api_key: Option<String>,
+    // Keeps connections to the endpoint open between calls
+    agent: ureq::Agent,
+}
+
+impl HttpProvider {
+    pub fn new(endpoint: &str, api_key: Option<String>) -> Result<Self, Box<dyn std::error::Error>> {
+        if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") {
+            return Err(format!("LLM endpoint {} must start with https:// or http://", endpoint).into());
+        }
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(REQUEST_TIMEOUT_SECONDS)))
+            .http_status_as_error(false)
+            .build()
+            .into();
+        Ok(Self { endpoint: endpoint.to_string(), api_key, agent })
+    }
+}
+
+impl LlmProvider for HttpProvider {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn complete(&self, request: &LlmRequest) -> Result<LlmResponse, Box<dyn std::error::Error>> {
+        let body = json!({
+            "model": request.model,
+            "messages": [{"role": "user", "content": request.prompt}],
+            "max_tokens": request.max_tokens,
+            "temperature": 0,
+        });
+        let mut call = self.agent.post(&self.endpoint).header("Content-Type", "application/json");
+        if let Some(key) = &self.api_key {
+            call = call.header("Authorization", format!("Bearer {}", key));
+        }
+        let mut answer = call.send(body.to_string())?;
+        let status = answer.status().as_u16();
+        let response = answer.body_mut().read_to_string()?;
+        if !(200..300).contains(&status) {
+            return Err(Box::new(LlmStatusError { status, body: response }));
+        }
+        let response: Value = serde_json::from_str(&response)?;
+        let text = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or("LLM response has no choices[0].message.content")?;
+        Ok(LlmResponse {
+            text: text.to_string(),
+            prompt_tokens: response["usage"]["prompt_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: response["usage"]["completion_tokens"].as_u64().unwrap_or(0),
+        })
+    }
+}
+
+fn build_one(config: &FallbackConfig) -> Result<Box<dyn LlmProvider>, Box<dyn std::error::Error>> {
+    match config.provider.as_str() {
+        "http" => {
+            let api_key = config.api_key_env.as_ref().and_then(|name| std::env::var(name).ok());
+            Ok(Box::new(HttpProvider::new(&config.endpoint, api_key)?))
+        }
+        #[cfg(feature = "local-llm")]
+        "local" => {
+            let model_path = config.model_path.as_deref().ok_or("Local provider needs model_path")?;
+            let tokenizer_path = config.tokenizer_path.as_deref().ok_or("Local provider needs tokenizer_path")?;
+            Ok(Box::new(crate::llm::local::LocalProvider::load(model_path, tokenizer_path)?))
+        }
+        #[cfg(not(feature = "local-llm"))]
+        "local" => Err("Local LLM provider requires building with the local-llm feature".into()),
+        other => Err(format!("Unknown LLM provider: {}", other).into()),
+    }
+}
+
+// The configured providers behind retries, failover and a circuit breaker each
+pub fn build_provider(config: &LlmConfig) -> Result<Box<dyn LlmProvider>, Box<dyn std::error::Error>> {
+    let providers = config.providers().iter()
+        .map(|provider| Ok((provider.label(), build_one(provider)?, provider.model.clone())))
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+    Ok(Box::new(FailoverProvider::new(providers, config.retry.clone(), config.circuit_breaker.clone())))
+}
@@ -0,0 +1,217 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::engine::analyze::analyze;
+use crate::llm::provider::LlmConfig;
+
+fn default_kind() -> String {
+    "estimate".to_string()
+}
+
+// How prompt tokens are counted for budgets. "estimate" is the character heuristic of
+// analyze_text; "huggingface" loads the model's tokenizer.json for exact counts; "tiktoken"
+// counts with one of OpenAI's BPE encodings, named or looked up from the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenizerConfig {
+    #[serde(default = "default_kind")]
+    pub kind: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    // tiktoken: e.g. "o200k_base" or "cl100k_base"
+    #[serde(default)]
+    pub encoding: Option<String>,
+}
+
+pub trait TokenCounter: Send + Sync {
+    // As the run report names it, e.g. "estimate" or "huggingface tokenizer.json"
+    fn name(&self) -> &str;
+    fn count(&self, text: &str) -> u64;
+}
+
+pub struct EstimateCounter;
+
+impl TokenCounter for EstimateCounter {
+    fn name(&self) -> &str {
+        "estimate"
+    }
+
+    fn count(&self, text: &str) -> u64 {
+        analyze(text).estimated_llm_tokens as u64
+    }
+}
+
+#[cfg(feature = "hf-tokenizer")]
+pub struct HuggingFaceCounter {
+    name: String,
+    tokenizer: tokenizers::Tokenizer,
+}
+
+#[cfg(feature = "hf-tokenizer")]
+impl HuggingFaceCounter {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let tokenizer = tokenizers::Tokenizer::from_file(path)
+            .map_err(|e| format!("Cannot load tokenizer {}: {}", path, e))?;
+        let file = std::path::Path::new(path).file_name().map(|name| name.to_string_lossy().to_string());
+        Ok(Self { name: format!("huggingface {}", file.unwrap_or_default()), tokenizer })
+    }
+}
+
+#[cfg(feature = "hf-tokenizer")]
+impl TokenCounter for HuggingFaceCounter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Special tokens of the chat format are not in the prompt text, so they are not counted
+    fn count(&self, text: &str) -> u64 {
+        match self.tokenizer.encode(text, false) {
+            Ok(encoding) => encoding.len() as u64,
+            Err(_) => analyze(text).estimated_llm_tokens as u64,
+        }
+    }
+}
+
+#[cfg(feature = "hf-tokenizer")]
+fn huggingface(path: &str) -> Result<Box<dyn TokenCounter>, Box<dyn std::error::Error>> {
+    Ok(Box::new(HuggingFaceCounter::load(path)?))
+}
+
+#[cfg(not(feature = "hf-tokenizer"))]
+fn huggingface(_path: &str) -> Result<Box<dyn TokenCounter>, Box<dyn std::error::Error>> {
+    Err("tokenizer kind \"huggingface\" needs a build with the hf-tokenizer feature".into())
+}
+
+#[cfg(feature = "tiktoken")]
+const TIKTOKEN_ENCODINGS: [(&str, tiktoken_rs::tokenizer::Tokenizer); 6] = [
+    ("o200k_base", tiktoken_rs::tokenizer::Tokenizer::O200kBase),
+    ("o200k_harmony", tiktoken_rs::tokenizer::Tokenizer::O200kHarmony),
+    ("cl100k_base", tiktoken_rs::tokenizer::Tokenizer::Cl100kBase),
+    ("p50k_base", tiktoken_rs::tokenizer::Tokenizer::P50kBase),
+    ("p50k_edit", tiktoken_rs::tokenizer::Tokenizer::P50kEdit),
+    ("r50k_base", tiktoken_rs::tokenizer::Tokenizer::R50kBase),
+];
+
+#[cfg(feature = "tiktoken")]
+pub struct TiktokenCounter {
+    name: String,
+    bpe: &'static tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl TiktokenCounter {
+    // The encodings are built into the crate, so nothing is downloaded
+    pub fn load(encoding: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (name, tokenizer) = TIKTOKEN_ENCODINGS.iter().find(|(name, _)| *name == encoding).ok_or_else(|| {
+            let known: Vec<&str> = TIKTOKEN_ENCODINGS.iter().map(|(name, _)| *name).collect();
+            format!("Unknown tiktoken encoding: {} (expected one of {})", encoding, known.join(", "))
+        })?;
+        Ok(Self { name: format!("tiktoken {}", name), bpe: tiktoken_rs::bpe_for_tokenizer(*tokenizer)? })
+    }
+
+    // The encoding OpenAI uses for the model, e.g. o200k_base for gpt-4o
+    pub fn for_model(model: &str) -> Option<Self> {
+        let tokenizer = tiktoken_rs::tokenizer::get_tokenizer(model)?;
+        let (name, _) = TIKTOKEN_ENCODINGS.iter().find(|(_, known)| *known == tokenizer)?;
+        Self::load(name).ok()
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl TokenCounter for TiktokenCounter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Ordinary text only: "<|endoftext|>" in a document is counted as the characters it is
+    fn count(&self, text: &str) -> u64 {
+        self.bpe.count_ordinary(text) as u64
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+fn tiktoken(tokenizer: &TokenizerConfig, model: &str) -> Result<Box<dyn TokenCounter>, Box<dyn std::error::Error>> {
+    match &tokenizer.encoding {
+        Some(encoding) => Ok(Box::new(TiktokenCounter::load(encoding)?)),
+        None => Ok(Box::new(TiktokenCounter::for_model(model).ok_or_else(|| {
+            format!("tiktoken has no encoding for model {:?}; name one in tokenizer.encoding", model)
+        })?)),
+    }
+}
+
+#[cfg(not(feature = "tiktoken"))]
+fn tiktoken(_tokenizer: &TokenizerConfig, _model: &str) -> Result<Box<dyn TokenCounter>, Box<dyn std::error::Error>> {
+    Err("tokenizer kind \"tiktoken\" needs a build with the tiktoken feature".into())
+}
+
+#[cfg(feature = "tiktoken")]
+fn known_to_tiktoken(model: &str) -> Option<Box<dyn TokenCounter>> {
+    Some(Box::new(TiktokenCounter::for_model(model)?))
+}
+
+#[cfg(not(feature = "tiktoken"))]
+fn known_to_tiktoken(_model: &str) -> Option<Box<dyn TokenCounter>> {
+    None
+}
+
+// The configured counter. Without a `tokenizer` section the local backend counts with the
+// tokenizer it decodes with, where the build has one, other backends count with tiktoken
+// when their model is an OpenAI one it knows, and the rest estimate
+pub fn build_counter(config: &LlmConfig) -> Result<Box<dyn TokenCounter>, Box<dyn std::error::Error>> {
+    match &config.tokenizer {
+        Some(tokenizer) => match tokenizer.kind.as_str() {
+            "estimate" => Ok(Box::new(EstimateCounter)),
+            "huggingface" => huggingface(tokenizer.path.as_deref().ok_or("tokenizer kind \"huggingface\" needs a path")?),
+            "tiktoken" => tiktoken(tokenizer, &config.model),
+            other => Err(format!("Unknown tokenizer kind: {} (expected estimate, huggingface or tiktoken)", other).into()),
+        },
+        None => match (config.provider.as_str(), config.tokenizer_path.as_deref()) {
+            ("local", Some(path)) if cfg!(feature = "hf-tokenizer") => huggingface(path),
+            ("local", _) => Ok(Box::new(EstimateCounter)),
+            _ => Ok(known_to_tiktoken(&config.model).unwrap_or_else(|| Box::new(EstimateCounter))),
+        },
+    }
+}
+
+// Python binding - prompt tokens as budgets would count them under this LLM config
+#[pyfunction]
+pub fn count_llm_tokens(py: Python, text: &str, llm_config_path: &str) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        let counter = LlmConfig::load(llm_config_path)
+            .and_then(|config| build_counter(&config))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let mut result = HashMap::new();
+        result.insert("tokens".to_string(), counter.count(text).to_string());
+        result.insert("tokenizer".to_string(), counter.name().to_string());
+        Ok(result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_of_empty_and_form_feed_ended_text() {
+        assert_eq!(EstimateCounter.count(""), 0);
+        assert_eq!(EstimateCounter.count("\x0c"), 0);
+        assert!(EstimateCounter.count("Remove the access panel.\x0c") > 0);
+    }
+
+    // The examples of OpenAI's "How to count tokens with tiktoken" cookbook
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn tiktoken_counts_match_openai() {
+        let cl100k = TiktokenCounter::load("cl100k_base").unwrap();
+        assert_eq!(cl100k.bpe.encode_ordinary("tiktoken is great!"), [83, 1609, 5963, 374, 2294, 0]);
+        for (encoding, counts) in [("r50k_base", [5, 14]), ("cl100k_base", [6, 9]), ("o200k_base", [6, 8])] {
+            let counter = TiktokenCounter::load(encoding).unwrap();
+            assert_eq!([counter.count("antidisestablishmentarianism"), counter.count("お誕生日おめでとう")], counts, "{}", encoding);
+            assert_eq!(counter.count("<|endoftext|>"), 7);
+        }
+        assert_eq!(TiktokenCounter::for_model("gpt-4o-mini").unwrap().name(), "tiktoken o200k_base");
+        assert_eq!(TiktokenCounter::for_model("gpt-4").unwrap().name(), "tiktoken cl100k_base");
+        assert!(TiktokenCounter::for_model("qwen2.5-1.5b-instruct").is_none());
+        assert!(TiktokenCounter::load("o300k_base").is_err());
+    }
+}
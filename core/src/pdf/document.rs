@@ -0,0 +1,1138 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::engine::convert::{pdf_password, PdfPasswordError};
+use crate::pdf::crypt::SecurityHandler;
+use crate::pdf::inflate::zlib_decompress;
+use crate::storage::atomic::write_atomic;
+
+// Reading a PDF's object structure and appending an incremental update to it. The original
+// bytes are never rewritten, so signatures over them and everything this reader does not
+// understand survive. Content streams are only tokenized, for pdf::text
+
+// Page trees deeper than this are refused rather than walked
+const MAX_TREE_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PdfObject {
+    Null,
+    Bool(bool),
+    // Kept as written, so rewritten dictionaries keep their exact values
+    Number(String),
+    // Literal or hex string, delimiters included
+    String(Vec<u8>),
+    // Without the leading slash, still #-escaped as written
+    Name(String),
+    Array(Vec<PdfObject>),
+    Dictionary(Vec<(String, PdfObject)>),
+    Reference(u32, u16),
+}
+
+impl PdfObject {
+    pub fn get(&self, key: &str) -> Option<&PdfObject> {
+        match self {
+            PdfObject::Dictionary(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: PdfObject) {
+        if let PdfObject::Dictionary(entries) = self {
+            match entries.iter_mut().find(|(name, _)| name == key) {
+                Some(entry) => entry.1 = value,
+                None => entries.push((key.to_string(), value)),
+            }
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            PdfObject::Number(number) => number.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            PdfObject::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[PdfObject]> {
+        match self {
+            PdfObject::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    // The bytes a literal or hex string stands for, escapes and delimiters removed
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        let PdfObject::String(raw) = self else { return None };
+        if raw.first() == Some(&b'<') {
+            let digits: Vec<u8> = raw.iter().filter_map(|b| (*b as char).to_digit(16).map(|d| d as u8)).collect();
+            // An odd final digit is followed by an implied 0
+            return Some(digits.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0)).collect());
+        }
+        let inner = raw.get(1..raw.len().saturating_sub(1)).unwrap_or_default();
+        let mut bytes = Vec::with_capacity(inner.len());
+        let mut index = 0;
+        while index < inner.len() {
+            let byte = inner[index];
+            index += 1;
+            if byte != b'\\' {
+                // End-of-line markers in a string are a single newline
+                if byte == b'\r' {
+                    bytes.push(b'\n');
+                    if inner.get(index) == Some(&b'\n') {
+                        index += 1;
+                    }
+                } else {
+                    bytes.push(byte);
+                }
+                continue;
+            }
+            let Some(&escaped) = inner.get(index) else { break };
+            index += 1;
+            match escaped {
+                b'n' => bytes.push(b'\n'),
+                b'r' => bytes.push(b'\r'),
+                b't' => bytes.push(b'\t'),
+                b'b' => bytes.push(0x08),
+                b'f' => bytes.push(0x0c),
+                b'0'..=b'7' => {
+                    let mut value = (escaped - b'0') as u32;
+                    for _ in 0..2 {
+                        match inner.get(index) {
+                            Some(digit @ b'0'..=b'7') => {
+                                value = value * 8 + (digit - b'0') as u32;
+                                index += 1;
+                            }
+                            _ => break,
+                        }
+                    }
+                    bytes.push(value as u8);
+                }
+                // A backslash at the end of a line continues the string on the next
+                b'\r' => {
+                    if inner.get(index) == Some(&b'\n') {
+                        index += 1;
+                    }
+                }
+                b'\n' => {}
+                other => bytes.push(other),
+            }
+        }
+        Some(bytes)
+    }
+
+    // A text string as readers show it: UTF-16BE or UTF-8 after a byte order mark, else
+    // PDFDocEncoding, taken as Latin-1 - they differ only in rarely used punctuation
+    pub fn as_text(&self) -> Option<String> {
+        let bytes = self.as_bytes()?;
+        if let Some(utf16) = bytes.strip_prefix(&[0xfe, 0xff]) {
+            let units: Vec<u16> = utf16.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)])).collect();
+            return Some(String::from_utf16_lossy(&units));
+        }
+        if let Some(utf8) = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+            return Some(String::from_utf8_lossy(utf8).to_string());
+        }
+        Some(bytes.iter().map(|&byte| byte as char).collect())
+    }
+
+    pub fn number(value: f64) -> Self {
+        let text = format!("{:.3}", value);
+        PdfObject::Number(text.trim_end_matches('0').trim_end_matches('.').to_string())
+    }
+
+    pub fn name(name: &str) -> Self {
+        PdfObject::Name(name.to_string())
+    }
+
+    // Text strings as UTF-16BE with a byte order mark, which every reader decodes
+    pub fn text(text: &str) -> Self {
+        let mut hex = String::from("<FEFF");
+        for unit in text.encode_utf16() {
+            hex.push_str(&format!("{:04X}", unit));
+        }
+        hex.push('>');
+        PdfObject::String(hex.into_bytes())
+    }
+
+    // Arbitrary bytes as a hex string
+    pub fn bytes(bytes: &[u8]) -> Self {
+        let mut hex = String::from("<");
+        for byte in bytes {
+            hex.push_str(&format!("{:02X}", byte));
+        }
+        hex.push('>');
+        PdfObject::String(hex.into_bytes())
+    }
+
+    pub fn write(&self, output: &mut Vec<u8>) {
+        match self {
+            PdfObject::Null => output.extend_from_slice(b"null"),
+            PdfObject::Bool(value) => output.extend_from_slice(if *value { b"true" } else { b"false" }),
+            PdfObject::Number(number) => output.extend_from_slice(number.as_bytes()),
+            PdfObject::String(raw) => output.extend_from_slice(raw),
+            PdfObject::Name(name) => {
+                output.push(b'/');
+                output.extend_from_slice(name.as_bytes());
+            }
+            PdfObject::Array(items) => {
+                output.push(b'[');
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        output.push(b' ');
+                    }
+                    item.write(output);
+                }
+                output.push(b']');
+            }
+            PdfObject::Dictionary(entries) => {
+                output.extend_from_slice(b"<<");
+                for (name, value) in entries {
+                    output.push(b'/');
+                    output.extend_from_slice(name.as_bytes());
+                    output.push(b' ');
+                    value.write(output);
+                }
+                output.extend_from_slice(b">>");
+            }
+            PdfObject::Reference(number, generation) => output.extend_from_slice(format!("{} {} R", number, generation).as_bytes()),
+        }
+    }
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | b'\x0c' | b'\0')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(byte, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+// Tokenizer and object parser over the whole file
+struct Parser<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Self { data, position }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.data.get(self.position) {
+            if is_whitespace(*byte) {
+                self.position += 1;
+            } else if *byte == b'%' {
+                while self.data.get(self.position).is_some_and(|b| *b != b'\n' && *b != b'\r') {
+                    self.position += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn starts_with(&self, token: &[u8]) -> bool {
+        self.data[self.position.min(self.data.len())..].starts_with(token)
+    }
+
+    fn keyword(&mut self) -> &'a [u8] {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.peek().is_some_and(|b| !is_whitespace(b) && !is_delimiter(b)) {
+            self.position += 1;
+        }
+        &self.data[start..self.position]
+    }
+
+    fn expect(&mut self, keyword: &[u8]) -> Result<(), String> {
+        let found = self.keyword();
+        if found != keyword {
+            return Err(format!(
+                "Expected {} at byte {}, found {:?}",
+                String::from_utf8_lossy(keyword), self.position, String::from_utf8_lossy(found)
+            ));
+        }
+        Ok(())
+    }
+
+    fn integer(&mut self) -> Option<i64> {
+        std::str::from_utf8(self.keyword()).ok()?.parse().ok()
+    }
+
+    fn literal_string(&mut self) -> Result<PdfObject, String> {
+        let start = self.position;
+        let mut depth = 0;
+        while let Some(byte) = self.peek() {
+            self.position += 1;
+            match byte {
+                b'\\' => self.position += 1,
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(PdfObject::String(self.data[start..self.position].to_vec()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err(format!("Unterminated string at byte {}", start))
+    }
+
+    fn object(&mut self) -> Result<PdfObject, String> {
+        self.skip_whitespace();
+        let start = self.position;
+        match self.peek().ok_or("Unexpected end of file")? {
+            b'<' if self.starts_with(b"<<") => {
+                self.position += 2;
+                let mut entries = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.starts_with(b">>") {
+                        self.position += 2;
+                        return Ok(PdfObject::Dictionary(entries));
+                    }
+                    let key = match self.object()? {
+                        PdfObject::Name(name) => name,
+                        _ => return Err(format!("Dictionary key is not a name at byte {}", self.position)),
+                    };
+                    entries.push((key, self.object()?));
+                }
+            }
+            b'<' => {
+                let end = self.data[start..].iter().position(|b| *b == b'>').ok_or("Unterminated hex string")?;
+                self.position = start + end + 1;
+                Ok(PdfObject::String(self.data[start..self.position].to_vec()))
+            }
+            b'(' => self.literal_string(),
+            b'[' => {
+                self.position += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(b']') {
+                        self.position += 1;
+                        return Ok(PdfObject::Array(items));
+                    }
+                    items.push(self.object()?);
+                }
+            }
+            b'/' => {
+                self.position += 1;
+                let name_start = self.position;
+                while self.peek().is_some_and(|b| !is_whitespace(b) && !is_delimiter(b)) {
+                    self.position += 1;
+                }
+                Ok(PdfObject::Name(String::from_utf8_lossy(&self.data[name_start..self.position]).to_string()))
+            }
+            _ => {
+                let token = self.keyword();
+                match token {
+                    b"true" => Ok(PdfObject::Bool(true)),
+                    b"false" => Ok(PdfObject::Bool(false)),
+                    b"null" => Ok(PdfObject::Null),
+                    _ if !token.is_empty() && token.iter().all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'-' | b'+')) => {
+                        let number = String::from_utf8_lossy(token).to_string();
+                        // "12 0 R" is a reference; anything else leaves the lookahead unread
+                        let after_number = self.position;
+                        if let (Ok(object_number), Some(generation)) = (number.parse::<u32>(), self.integer()) {
+                            if self.keyword() == b"R" {
+                                return Ok(PdfObject::Reference(object_number, generation as u16));
+                            }
+                        }
+                        self.position = after_number;
+                        Ok(PdfObject::Number(number))
+                    }
+                    _ => Err(format!("Unexpected token {:?} at byte {}", String::from_utf8_lossy(token), start)),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum XrefEntry {
+    Offset(usize),
+    // Object stream number and index in it
+    Compressed(u32, usize),
+}
+
+pub struct PdfPage {
+    pub number: u32,
+    pub generation: u16,
+    pub dictionary: PdfObject,
+    // The page's /Resources, or its nearest ancestor's; Null when none is given
+    pub resources: PdfObject,
+    // The visible area, [x0 y0 x1 y1]: the crop box, else the media box, either inherited from
+    // the page tree when the page has none
+    pub crop_box: [f64; 4],
+    pub rotate: i64,
+}
+
+pub struct PdfDocument {
+    data: Vec<u8>,
+    xref: HashMap<u32, XrefEntry>,
+    trailer: PdfObject,
+    last_xref: usize,
+    // Whether the newest cross-reference section is a stream, which the update's must match
+    xref_stream: bool,
+    // Whether the cross-reference table was rebuilt from the objects in the file
+    rebuilt: bool,
+    // Set for encrypted documents, whose strings and streams are decrypted as they are read
+    security: Option<SecurityHandler>,
+    // The /Encrypt dictionary's object, the one object whose strings are never encrypted
+    encrypt_object: Option<u32>,
+}
+
+// PNG predictors (10-15) row by row, as xref streams are commonly stored
+fn unpredict(data: Vec<u8>, parameters: Option<&PdfObject>) -> Result<Vec<u8>, String> {
+    let predictor = parameters.and_then(|p| p.get("Predictor")).and_then(PdfObject::as_number).unwrap_or(1.0) as i64;
+    if predictor < 10 {
+        return Ok(data);
+    }
+    let columns = parameters.and_then(|p| p.get("Columns")).and_then(PdfObject::as_number).unwrap_or(1.0) as usize;
+    let colors = parameters.and_then(|p| p.get("Colors")).and_then(PdfObject::as_number).unwrap_or(1.0) as usize;
+    let bits = parameters.and_then(|p| p.get("BitsPerComponent")).and_then(PdfObject::as_number).unwrap_or(8.0) as usize;
+    let bytes_per_pixel = (colors * bits).div_ceil(8).max(1);
+    let row_length = (columns * colors * bits).div_ceil(8);
+
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_length];
+    for row in data.chunks(row_length + 1) {
+        let (filter, row) = row.split_first().ok_or("Empty predictor row")?;
+        let mut decoded = row.to_vec();
+        decoded.resize(row_length, 0);
+        for index in 0..row_length {
+            let left = if index >= bytes_per_pixel { decoded[index - bytes_per_pixel] } else { 0 };
+            let up = previous[index];
+            let up_left = if index >= bytes_per_pixel { previous[index - bytes_per_pixel] } else { 0 };
+            let prediction = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => {
+                    let estimate = left as i16 + up as i16 - up_left as i16;
+                    let (to_left, to_up, to_up_left) = ((estimate - left as i16).abs(), (estimate - up as i16).abs(), (estimate - up_left as i16).abs());
+                    if to_left <= to_up && to_left <= to_up_left {
+                        left
+                    } else if to_up <= to_up_left {
+                        up
+                    } else {
+                        up_left
+                    }
+                }
+                _ => return Err(format!("Unknown PNG predictor {}", filter)),
+            };
+            decoded[index] = decoded[index].wrapping_add(prediction);
+        }
+        output.extend_from_slice(&decoded);
+        previous = decoded;
+    }
+    Ok(output)
+}
+
+impl PdfDocument {
+    // Encrypted documents open with the password engine::convert holds for this call or
+    // process, else with the empty user password
+    pub fn read(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        Self::parse(data, pdf_password().as_deref()).map_err(|e| match e.downcast::<PdfPasswordError>() {
+            Ok(error) => Box::new(PdfPasswordError { path: path.to_string(), ..*error }) as Box<dyn std::error::Error>,
+            Err(e) => format!("{}: {}", path, e).into(),
+        })
+    }
+
+    pub fn parse(data: Vec<u8>, password: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        if !data.starts_with(b"%PDF-") {
+            return Err("Not a PDF file".into());
+        }
+        let mut document = Self {
+            data,
+            xref: HashMap::new(),
+            trailer: PdfObject::Null,
+            last_xref: 0,
+            xref_stream: false,
+            rebuilt: false,
+            security: None,
+            encrypt_object: None,
+        };
+        if let Err(e) = document.read_xref() {
+            document.rebuild_xref().map_err(|_| e)?;
+        }
+        if let Some(encrypt) = document.trailer.get("Encrypt").cloned() {
+            document.unlock(&encrypt, password)?;
+        }
+        Ok(document)
+    }
+
+    // The cross-reference sections from startxref back through each /Prev
+    fn read_xref(&mut self) -> Result<(), String> {
+        let tail_start = self.data.len().saturating_sub(2048);
+        let marker = self.data[tail_start..].windows(9).rposition(|window| window == b"startxref")
+            .ok_or("No startxref - the file is truncated")?;
+        let mut parser = Parser::new(&self.data, tail_start + marker + 9);
+        self.last_xref = parser.integer().ok_or("Invalid startxref offset")? as usize;
+        let mut next = Some(self.last_xref);
+        let mut visited = Vec::new();
+        while let Some(offset) = next.take() {
+            if visited.contains(&offset) {
+                break;
+            }
+            visited.push(offset);
+            let (trailer, is_stream) = self.read_xref_section(offset)?;
+            if visited.len() == 1 {
+                self.xref_stream = is_stream;
+            }
+            // Hybrid files list some objects in a stream next to the table
+            if let Some(stream_offset) = trailer.get("XRefStm").and_then(PdfObject::as_number) {
+                self.read_xref_section(stream_offset as usize)?;
+            }
+            next = trailer.get("Prev").and_then(PdfObject::as_number).map(|prev| prev as usize);
+            if self.trailer == PdfObject::Null {
+                self.trailer = trailer;
+            }
+        }
+        if self.trailer.get("Root").is_none() {
+            return Err("Trailer has no /Root".to_string());
+        }
+        // Offsets that are off, as after an editor rewrote part of the file, are caught here
+        // rather than as objects that fail to read later
+        for (number, entry) in &self.xref {
+            let XrefEntry::Offset(offset) = entry else { continue };
+            let mut parser = Parser::new(&self.data, *offset);
+            if parser.integer() != Some(*number as i64) || parser.integer().is_none() || parser.keyword() != b"obj" {
+                return Err(format!("Cross-reference entry of object {} points at byte {}, where it is not", number, offset));
+            }
+        }
+        Ok(())
+    }
+
+    // Rebuilds the cross-reference table of a file whose own is missing or damaged from the
+    // "N G obj" headers in its body, as other readers do. A number's last object wins, as in
+    // an incremental update. The trailer is the last one with a /Root, else the catalog's
+    fn rebuild_xref(&mut self) -> Result<(), String> {
+        self.xref.clear();
+        self.trailer = PdfObject::Null;
+        self.xref_stream = false;
+        self.rebuilt = true;
+        let mut streams = Vec::new();
+        let mut catalog = None;
+        for offset in 0..self.data.len() {
+            let line_start = offset == 0 || matches!(self.data[offset - 1], b'\n' | b'\r');
+            if !line_start || !self.data[offset].is_ascii_digit() {
+                continue;
+            }
+            let Ok((number, _, object, _)) = self.indirect_at(offset) else { continue };
+            self.xref.insert(number, XrefEntry::Offset(offset));
+            match object.get("Type").and_then(PdfObject::as_name) {
+                Some("ObjStm") => streams.push(number),
+                Some("XRef") if object.get("Root").is_some() => self.trailer = object,
+                Some("Catalog") => catalog = Some(number),
+                _ => {}
+            }
+        }
+        let mut position = 0;
+        while let Some(found) = self.data[position..].windows(7).position(|window| window == b"trailer") {
+            position += found + 7;
+            if let Ok(trailer) = Parser::new(&self.data, position).object() {
+                if trailer.get("Root").is_some() {
+                    self.trailer = trailer;
+                }
+            }
+        }
+        // Objects inside object streams, unless an object of their own is in the body
+        for stream in streams {
+            let Some(XrefEntry::Offset(offset)) = self.xref.get(&stream).copied() else { continue };
+            let Ok((dictionary, data)) = self.stream_at(offset) else { continue };
+            let count = dictionary.get("N").and_then(PdfObject::as_number).unwrap_or(0.0) as usize;
+            let mut header = Parser::new(&data, 0);
+            for index in 0..count {
+                let (Some(number), Some(_)) = (header.integer(), header.integer()) else { break };
+                self.xref.entry(number as u32).or_insert(XrefEntry::Compressed(stream, index));
+            }
+        }
+        if self.trailer == PdfObject::Null {
+            let catalog = catalog.ok_or("No catalog object to rebuild the file from")?;
+            self.trailer = PdfObject::Dictionary(vec![("Root".to_string(), PdfObject::Reference(catalog, 0))]);
+        }
+        Ok(())
+    }
+
+    fn unlock(&mut self, encrypt: &PdfObject, password: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        let dictionary = self.resolve(encrypt)?;
+        // The first file identifier salts the key, so it is never encrypted itself
+        let id = self.trailer.get("ID")
+            .and_then(PdfObject::as_array)
+            .and_then(|ids| ids.first())
+            .and_then(PdfObject::as_bytes)
+            .unwrap_or_default();
+        let handler = SecurityHandler::open(&dictionary, &id, password)?
+            .ok_or_else(|| PdfPasswordError { path: String::new(), supplied: password.is_some() })?;
+        self.security = Some(handler);
+        if let PdfObject::Reference(number, _) = encrypt {
+            self.encrypt_object = Some(*number);
+        }
+        Ok(())
+    }
+
+    fn security_for(&self, number: u32) -> Option<&SecurityHandler> {
+        self.security.as_ref().filter(|_| self.encrypt_object != Some(number))
+    }
+
+    // Newer sections are read first, so an entry already present wins
+    fn read_xref_section(&mut self, offset: usize) -> Result<(PdfObject, bool), String> {
+        let mut parser = Parser::new(&self.data, offset);
+        parser.skip_whitespace();
+        if parser.starts_with(b"xref") {
+            parser.expect(b"xref")?;
+            loop {
+                parser.skip_whitespace();
+                if parser.starts_with(b"trailer") {
+                    parser.expect(b"trailer")?;
+                    return Ok((parser.object()?, false));
+                }
+                let first = parser.integer().ok_or("Invalid xref subsection")? as u32;
+                let count = parser.integer().ok_or("Invalid xref subsection")? as u32;
+                for number in first..first.saturating_add(count) {
+                    let entry_offset = parser.integer().ok_or("Invalid xref entry")? as usize;
+                    parser.integer().ok_or("Invalid xref entry")?;
+                    let kind = parser.keyword();
+                    if kind == b"n" {
+                        self.xref.entry(number).or_insert(XrefEntry::Offset(entry_offset));
+                    }
+                }
+            }
+        }
+
+        let (dictionary, data) = self.stream_at(offset)?;
+        if dictionary.get("Type").and_then(PdfObject::as_name) != Some("XRef") {
+            return Err(format!("No cross-reference section at byte {}", offset));
+        }
+        let widths: Vec<usize> = dictionary.get("W").and_then(PdfObject::as_array).ok_or("XRef stream without /W")?
+            .iter()
+            .map(|width| width.as_number().unwrap_or(0.0) as usize)
+            .collect();
+        if widths.len() != 3 {
+            return Err("XRef stream /W must have three widths".to_string());
+        }
+        let size = dictionary.get("Size").and_then(PdfObject::as_number).unwrap_or(0.0) as u32;
+        let index: Vec<u32> = match dictionary.get("Index").and_then(PdfObject::as_array) {
+            Some(index) => index.iter().map(|value| value.as_number().unwrap_or(0.0) as u32).collect(),
+            None => vec![0, size],
+        };
+        let field = |row: &[u8], start: usize, width: usize| row[start..start + width].iter().fold(0usize, |value, byte| value << 8 | *byte as usize);
+        let row_length: usize = widths.iter().sum();
+        let mut rows = data.chunks_exact(row_length.max(1));
+        for range in index.chunks(2) {
+            let (first, count) = (range[0], range.get(1).copied().unwrap_or(0));
+            for number in first..first.saturating_add(count) {
+                let row = rows.next().ok_or("XRef stream is shorter than its /Index")?;
+                // A missing type field means type 1
+                let kind = if widths[0] == 0 { 1 } else { field(row, 0, widths[0]) };
+                let second = field(row, widths[0], widths[1]);
+                let third = field(row, widths[0] + widths[1], widths[2]);
+                let entry = match kind {
+                    1 => XrefEntry::Offset(second),
+                    2 => XrefEntry::Compressed(second as u32, third),
+                    _ => continue,
+                };
+                self.xref.entry(number).or_insert(entry);
+            }
+        }
+        Ok((dictionary, true))
+    }
+
+    // The indirect object starting at `offset`: "N G obj ... endobj", its strings decrypted
+    fn object_at(&self, offset: usize) -> Result<(PdfObject, usize), String> {
+        let (_, _, object, end) = self.indirect_at(offset)?;
+        Ok((object, end))
+    }
+
+    // As object_at, with the object's number and generation
+    fn indirect_at(&self, offset: usize) -> Result<(u32, u16, PdfObject, usize), String> {
+        let mut parser = Parser::new(&self.data, offset);
+        let number = parser.integer().ok_or_else(|| format!("No object at byte {}", offset))? as u32;
+        let generation = parser.integer().ok_or_else(|| format!("No object at byte {}", offset))? as u16;
+        parser.expect(b"obj")?;
+        let mut object = parser.object()?;
+        if let Some(security) = self.security_for(number) {
+            security.decrypt_strings(&mut object, number, generation);
+        }
+        Ok((number, generation, object, parser.position))
+    }
+
+    // A stream's dictionary and its data as stored, still filtered but decrypted
+    fn raw_stream_at(&self, offset: usize) -> Result<(PdfObject, Vec<u8>), String> {
+        let (number, generation, dictionary, end) = self.indirect_at(offset)?;
+        let mut parser = Parser::new(&self.data, end);
+        parser.expect(b"stream")?;
+        let mut start = parser.position;
+        if self.data.get(start) == Some(&b'\r') {
+            start += 1;
+        }
+        if self.data.get(start) == Some(&b'\n') {
+            start += 1;
+        }
+        let length = match dictionary.get("Length") {
+            Some(PdfObject::Reference(number, _)) => self.resolve_number(*number),
+            Some(length) => length.as_number().map(|length| length as usize),
+            None => None,
+        };
+        let end = match length {
+            Some(length) if start + length <= self.data.len() => start + length,
+            // A wrong /Length is common enough to fall back on the endstream keyword
+            _ => start + self.data[start..].windows(9).position(|window| window == b"endstream").ok_or("Unterminated stream")?,
+        };
+        let data = self.data[start..end].to_vec();
+        let data = match self.security_for(number) {
+            Some(security) => security.decrypt_stream(&dictionary, number, generation, data),
+            None => data,
+        };
+        Ok((dictionary, data))
+    }
+
+    fn stream_at(&self, offset: usize) -> Result<(PdfObject, Vec<u8>), String> {
+        let (dictionary, raw) = self.raw_stream_at(offset)?;
+        let data = match dictionary.get("Filter") {
+            None => raw,
+            Some(PdfObject::Name(filter)) if filter == "FlateDecode" => {
+                unpredict(zlib_decompress(&raw)?, dictionary.get("DecodeParms"))?
+            }
+            Some(PdfObject::Array(filters)) if filters.len() == 1 && filters[0].as_name() == Some("FlateDecode") => {
+                let parameters = dictionary.get("DecodeParms").and_then(|p| p.as_array().and_then(|p| p.first()).or(Some(p)));
+                unpredict(zlib_decompress(&raw)?, parameters)?
+            }
+            Some(filter) => {
+                let mut name = Vec::new();
+                filter.write(&mut name);
+                return Err(format!("Unsupported stream filter {}", String::from_utf8_lossy(&name)));
+            }
+        };
+        Ok((dictionary, data))
+    }
+
+    fn resolve_number(&self, number: u32) -> Option<usize> {
+        self.object(number).ok()?.as_number().map(|value| value as usize)
+    }
+
+    pub fn object(&self, number: u32) -> Result<PdfObject, String> {
+        match self.xref.get(&number) {
+            Some(XrefEntry::Offset(offset)) => Ok(self.object_at(*offset)?.0),
+            Some(XrefEntry::Compressed(stream, index)) => {
+                let offset = match self.xref.get(stream) {
+                    Some(XrefEntry::Offset(offset)) => *offset,
+                    _ => return Err(format!("Object stream {} is missing", stream)),
+                };
+                let (dictionary, data) = self.stream_at(offset)?;
+                let first = dictionary.get("First").and_then(PdfObject::as_number).ok_or("Object stream without /First")? as usize;
+                let mut header = Parser::new(&data, 0);
+                let mut object_offset = None;
+                for position in 0..=*index {
+                    let listed = header.integer();
+                    let offset = header.integer();
+                    if position == *index && listed == Some(number as i64) {
+                        object_offset = offset;
+                    }
+                }
+                let object_offset = object_offset.ok_or_else(|| format!("Object {} is not in object stream {}", number, stream))?;
+                Parser::new(&data, first + object_offset as usize).object()
+            }
+            None => Ok(PdfObject::Null),
+        }
+    }
+
+    // The dictionary and decoded data of a stream object. Streams are never inside object
+    // streams, so only objects at a file offset qualify
+    pub fn stream(&self, object: &PdfObject) -> Result<(PdfObject, Vec<u8>), String> {
+        self.stream_offset(object).and_then(|offset| self.stream_at(offset))
+    }
+
+    // A stream's data undecoded, for filters that are file formats of their own: a DCTDecode
+    // image is a JPEG file as it stands
+    pub fn raw_stream(&self, object: &PdfObject) -> Result<(PdfObject, Vec<u8>), String> {
+        self.stream_offset(object).and_then(|offset| self.raw_stream_at(offset))
+    }
+
+    fn stream_offset(&self, object: &PdfObject) -> Result<usize, String> {
+        let PdfObject::Reference(number, _) = object else { return Err("Stream is not an indirect object".to_string()) };
+        match self.xref.get(number) {
+            Some(XrefEntry::Offset(offset)) => Ok(*offset),
+            _ => Err(format!("Stream object {} is missing", number)),
+        }
+    }
+
+    // A page's content streams joined into one, as they are one stream split anywhere between
+    // tokens. Streams that cannot be decoded are left out and reported in `problems`
+    pub fn page_content(&self, page: &PdfPage, problems: &mut Vec<String>) -> Vec<u8> {
+        let contents = page.dictionary.get("Contents").cloned().unwrap_or(PdfObject::Null);
+        let streams = match &contents {
+            PdfObject::Array(streams) => streams.clone(),
+            PdfObject::Reference(..) => match self.resolve(&contents) {
+                Ok(PdfObject::Array(streams)) => streams,
+                _ => vec![contents],
+            },
+            _ => Vec::new(),
+        };
+        let mut data = Vec::new();
+        for stream in &streams {
+            match self.stream(stream) {
+                Ok((_, stream)) => {
+                    data.extend_from_slice(&stream);
+                    data.push(b'\n');
+                }
+                Err(e) => problems.push(e),
+            }
+        }
+        data
+    }
+
+    pub fn resolve(&self, object: &PdfObject) -> Result<PdfObject, String> {
+        match object {
+            PdfObject::Reference(number, _) => self.object(*number),
+            other => Ok(other.clone()),
+        }
+    }
+
+    // The document catalog, root of the page tree, outline and named destinations
+    pub fn catalog(&self) -> Result<PdfObject, String> {
+        self.resolve(self.trailer.get("Root").ok_or("Trailer has no /Root")?)
+    }
+
+    // Pages in reading order, with inherited boxes and rotation applied
+    pub fn pages(&self) -> Result<Vec<PdfPage>, String> {
+        let root = self.catalog()?;
+        let tree = root.get("Pages").ok_or("Catalog has no /Pages")?;
+        let mut pages = Vec::new();
+        let mut visited = HashSet::new();
+        self.collect_pages(tree, PageBoxes { media: [0.0, 0.0, 612.0, 792.0], crop: None, rotate: 0, resources: PdfObject::Null }, 0, &mut visited, &mut pages)?;
+        Ok(pages)
+    }
+
+    fn page_box(&self, dictionary: &PdfObject, key: &str) -> Result<Option<[f64; 4]>, String> {
+        match dictionary.get(key).map(|value| self.resolve(value)).transpose()? {
+            Some(PdfObject::Array(values)) if values.len() == 4 => {
+                let value = |index: usize| values[index].as_number().unwrap_or(0.0);
+                Ok(Some([value(0), value(1), value(2), value(3)]))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Each node is visited once: a node reached again makes the tree a cycle, or a graph
+    // whose walk could take exponential time
+    fn collect_pages(&self, node: &PdfObject, inherited: PageBoxes, depth: usize, visited: &mut HashSet<u32>, pages: &mut Vec<PdfPage>) -> Result<(), String> {
+        if depth > MAX_TREE_DEPTH {
+            return Err("Page tree is too deep".to_string());
+        }
+        let (number, generation) = match node {
+            PdfObject::Reference(number, generation) => (*number, *generation),
+            _ => return Err("Page tree node is not a reference".to_string()),
+        };
+        if !visited.insert(number) {
+            return Err(format!("Page tree is cyclic: object {} is reached twice", number));
+        }
+        let dictionary = self.object(number)?;
+        let boxes = PageBoxes {
+            media: self.page_box(&dictionary, "MediaBox")?.unwrap_or(inherited.media),
+            crop: self.page_box(&dictionary, "CropBox")?.or(inherited.crop),
+            rotate: dictionary.get("Rotate").and_then(PdfObject::as_number).map(|r| r as i64).unwrap_or(inherited.rotate),
+            resources: match dictionary.get("Resources") {
+                Some(resources) => self.resolve(resources)?,
+                None => inherited.resources,
+            },
+        };
+        match dictionary.get("Type").and_then(PdfObject::as_name) {
+            Some("Pages") => {
+                let kids = self.resolve(dictionary.get("Kids").ok_or("Page tree node has no /Kids")?)?;
+                for kid in kids.as_array().unwrap_or_default() {
+                    self.collect_pages(kid, boxes.clone(), depth + 1, visited, pages)?;
+                }
+            }
+            _ => pages.push(PdfPage {
+                number,
+                generation,
+                crop_box: boxes.crop.unwrap_or(boxes.media),
+                rotate: boxes.rotate.rem_euclid(360),
+                resources: boxes.resources,
+                dictionary,
+            }),
+        }
+        Ok(())
+    }
+}
+
+// Page attributes a page inherits from its ancestors in the page tree
+#[derive(Clone)]
+struct PageBoxes {
+    media: [f64; 4],
+    crop: Option<[f64; 4]>,
+    rotate: i64,
+    resources: PdfObject,
+}
+
+// Objects appended to a document as one incremental update
+pub struct PdfUpdate<'a> {
+    document: &'a PdfDocument,
+    next_number: u32,
+    objects: Vec<(u32, u16, PdfObject)>,
+    comments: Vec<String>,
+}
+
+impl<'a> PdfUpdate<'a> {
+    pub fn new(document: &'a PdfDocument) -> Self {
+        let size = document.trailer.get("Size").and_then(PdfObject::as_number).unwrap_or(0.0) as u32;
+        let highest = document.xref.keys().max().map(|number| number + 1).unwrap_or(1);
+        Self { document, next_number: size.max(highest), objects: Vec::new(), comments: Vec::new() }
+    }
+
+    // Number of the next object `add` will create, for objects that point at each other
+    pub fn reserve(&mut self) -> u32 {
+        self.next_number += 1;
+        self.next_number - 1
+    }
+
+    pub fn add(&mut self, object: PdfObject) -> PdfObject {
+        let number = self.reserve();
+        self.put(number, 0, object);
+        PdfObject::Reference(number, 0)
+    }
+
+    pub fn put(&mut self, number: u32, generation: u16, object: PdfObject) {
+        self.objects.retain(|(existing, _, _)| *existing != number);
+        self.objects.push((number, generation, object));
+    }
+
+    // A "% ..." line ahead of the update's objects
+    pub fn comment(&mut self, line: &str) {
+        self.comments.push(line.to_string());
+    }
+
+    pub fn write(mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        // Appended objects would have to be encrypted to match, which this writer does not do
+        if self.document.security.is_some() {
+            return Err("Cannot update an encrypted PDF; decrypt it first, e.g. with qpdf --decrypt".into());
+        }
+        // The update's /Prev would point at the damaged table
+        if self.document.rebuilt {
+            return Err("Cannot update a PDF whose cross-reference table is damaged; repair it first, e.g. with qpdf".into());
+        }
+        let mut output = self.document.data.clone();
+        if !output.ends_with(b"\n") {
+            output.push(b'\n');
+        }
+        for comment in &self.comments {
+            output.extend_from_slice(format!("% {}\n", comment).as_bytes());
+        }
+
+        let mut offsets = Vec::new();
+        for (number, generation, object) in &self.objects {
+            offsets.push((*number, *generation, output.len()));
+            output.extend_from_slice(format!("{} {} obj\n", number, generation).as_bytes());
+            object.write(&mut output);
+            output.extend_from_slice(b"\nendobj\n");
+        }
+
+        let mut trailer = vec![
+            ("Size".to_string(), PdfObject::Null),
+            ("Prev".to_string(), PdfObject::Number(self.document.last_xref.to_string())),
+        ];
+        for key in ["Root", "Info", "ID"] {
+            if let Some(value) = self.document.trailer.get(key) {
+                trailer.push((key.to_string(), value.clone()));
+            }
+        }
+
+        let xref_offset = output.len();
+        if self.document.xref_stream {
+            // The update's own cross-reference stream, uncompressed: type, 4-byte offset, generation
+            let stream_number = self.reserve();
+            offsets.push((stream_number, 0, xref_offset));
+            offsets.sort();
+            let mut index = Vec::new();
+            let mut rows = Vec::new();
+            for (position, (number, generation, offset)) in offsets.iter().enumerate() {
+                if position == 0 || offsets[position - 1].0 + 1 != *number {
+                    index.push(PdfObject::Number(number.to_string()));
+                    index.push(PdfObject::Number("0".to_string()));
+                }
+                if let Some(PdfObject::Number(count)) = index.last_mut() {
+                    *count = (count.parse::<u32>().unwrap_or(0) + 1).to_string();
+                }
+                rows.push(1u8);
+                rows.extend_from_slice(&(*offset as u32).to_be_bytes());
+                rows.extend_from_slice(&generation.to_be_bytes());
+            }
+            let mut dictionary = PdfObject::Dictionary(trailer);
+            dictionary.set("Size", PdfObject::Number(self.next_number.to_string()));
+            dictionary.set("Type", PdfObject::name("XRef"));
+            dictionary.set("W", PdfObject::Array(["1", "4", "2"].iter().map(|w| PdfObject::Number(w.to_string())).collect()));
+            dictionary.set("Index", PdfObject::Array(index));
+            dictionary.set("Length", PdfObject::Number(rows.len().to_string()));
+            output.extend_from_slice(format!("{} 0 obj\n", stream_number).as_bytes());
+            dictionary.write(&mut output);
+            output.extend_from_slice(b"\nstream\n");
+            output.extend_from_slice(&rows);
+            output.extend_from_slice(b"\nendstream\nendobj\n");
+        } else {
+            offsets.sort();
+            output.extend_from_slice(b"xref\n");
+            let mut start = 0;
+            while start < offsets.len() {
+                let mut end = start + 1;
+                while end < offsets.len() && offsets[end].0 == offsets[end - 1].0 + 1 {
+                    end += 1;
+                }
+                output.extend_from_slice(format!("{} {}\n", offsets[start].0, end - start).as_bytes());
+                for (_, generation, offset) in &offsets[start..end] {
+                    output.extend_from_slice(format!("{:010} {:05} n\r\n", offset, generation).as_bytes());
+                }
+                start = end;
+            }
+            let mut dictionary = PdfObject::Dictionary(trailer);
+            dictionary.set("Size", PdfObject::Number(self.next_number.to_string()));
+            output.extend_from_slice(b"trailer\n");
+            dictionary.write(&mut output);
+            output.push(b'\n');
+        }
+        output.extend_from_slice(format!("startxref\n{}\n%%EOF\n", xref_offset).as_bytes());
+        write_atomic(path, output)?;
+        Ok(())
+    }
+}
+
+// One operator of a content stream and its operands, e.g. Tf with [/F1 9]
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub operator: String,
+    pub operands: Vec<PdfObject>,
+}
+
+// Tokenizes a content stream (or a CMap, which uses the same syntax) into operations. Inline
+// images are skipped whole, since their data is binary
+pub fn parse_content(data: &[u8]) -> Result<Vec<Operation>, String> {
+    let mut parser = Parser::new(data, 0);
+    let mut operations = Vec::new();
+    let mut operands = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        let Some(byte) = parser.peek() else { break };
+        if !(byte.is_ascii_alphabetic() || matches!(byte, b'\'' | b'"')) {
+            operands.push(parser.object()?);
+            continue;
+        }
+        let operator = String::from_utf8_lossy(parser.keyword()).to_string();
+        match operator.as_str() {
+            "true" | "false" | "null" => {
+                parser.position -= operator.len();
+                operands.push(parser.object()?);
+                continue;
+            }
+            "ID" => {
+                // Image data runs to an EI between whitespace
+                let start = parser.position + 1;
+                let end = data[start.min(data.len())..].windows(4)
+                    .position(|window| is_whitespace(window[0]) && &window[1..3] == b"EI" && (is_whitespace(window[3]) || is_delimiter(window[3])))
+                    .map(|end| start + end + 3)
+                    .unwrap_or(data.len());
+                parser.position = end;
+                operands.clear();
+                continue;
+            }
+            _ => {}
+        }
+        operations.push(Operation { operator, operands: std::mem::take(&mut operands) });
+    }
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::convert::ConverterWarning;
+    use crate::pdf::text::PdfTextPages;
+    use std::io::Read;
+
+    fn fixture() -> Vec<u8> {
+        std::fs::read(format!("{}/testdata/encrypted/plain.pdf", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    }
+
+    fn position(data: &[u8], marker: &str) -> usize {
+        data.windows(marker.len()).position(|window| window == marker.as_bytes()).unwrap_or_else(|| panic!("{}", marker))
+    }
+
+    fn replace(data: &[u8], from: &str, to: &str) -> Vec<u8> {
+        let start = position(data, from);
+        [&data[..start], to.as_bytes(), &data[start + from.len()..]].concat()
+    }
+
+    fn cut_at(data: &[u8], marker: &str) -> Vec<u8> {
+        data[..position(data, marker)].to_vec()
+    }
+
+    fn text_of(data: Vec<u8>) -> Result<(String, Vec<ConverterWarning>), Box<dyn std::error::Error>> {
+        let document = PdfDocument::parse(data, None)?;
+        let mut pages = PdfTextPages::new(&document, "damaged.pdf")?;
+        let mut bytes = Vec::new();
+        pages.read_to_end(&mut bytes)?;
+        Ok((String::from_utf8(bytes)?, pages.into_warnings()))
+    }
+
+    #[test]
+    fn rebuilds_a_broken_cross_reference_table() {
+        let (plain, warnings) = text_of(fixture()).unwrap();
+        assert!(plain.contains("(2) Install the panel."));
+        assert!(warnings.is_empty());
+        let damaged = [
+            replace(&fixture(), "startxref\n1018", "startxref\n2"),
+            replace(&fixture(), "0000000699 00000 n", "0000000690 00000 n"),
+            replace(&fixture(), "/Root 1 0 R", "/Roof 1 0 R"),
+            cut_at(&fixture(), "xref\n0 9"),
+            cut_at(&fixture(), "trailer"),
+        ];
+        for data in damaged {
+            let document = PdfDocument::parse(data.clone(), None).unwrap();
+            assert!(document.rebuilt);
+            assert!(PdfUpdate::new(&document).write("unused.pdf").unwrap_err().to_string().contains("damaged"));
+            assert_eq!(text_of(data).unwrap().0, plain);
+        }
+        assert!(PdfDocument::parse(replace(&fixture(), "/Type /Catalog", "/Type /Catalop"), None).is_ok());
+        assert!(PdfDocument::parse(cut_at(&replace(&fixture(), "/Type /Catalog", "/Type /Catalop"), "trailer"), None).is_err());
+    }
+
+    #[test]
+    fn reads_around_truncated_streams() {
+        let (plain, _) = text_of(fixture()).unwrap();
+        // A wrong /Length falls back on the endstream keyword
+        assert_eq!(text_of(replace(&fixture(), "/Length 152", "/Length 999")).unwrap().0, plain);
+
+        let (text, warnings) = text_of(cut_at(&fixture(), "(\\(1\\) Connect")).unwrap();
+        assert!(text.contains("(2) Disconnect the duct."));
+        assert!(!text.contains("Installation"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.starts_with("Page 2: "), "{}", warnings[0].message);
+
+        // Whatever the file is cut short at, it reads or fails, and never panics
+        let data = fixture();
+        for length in 0..data.len() {
+            let _ = text_of(data[..length].to_vec());
+        }
+    }
+
+    #[test]
+    fn refuses_cyclic_page_trees() {
+        for kids in ["/Kids [2 0 R 7 0 R]", "/Kids [5 0 R 5 0 R]"] {
+            let document = PdfDocument::parse(replace(&fixture(), "/Kids [5 0 R 7 0 R]", kids), None).unwrap();
+            assert!(document.pages().err().unwrap().starts_with("Page tree is cyclic"), "{}", kids);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use pyo3::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::engine::convert::{read_error, with_pdf_password};
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::ids::stable_record_id;
+use crate::export::highlight::unescape;
+use crate::pdf::document::{PdfDocument, PdfObject};
+
+// Field trees nested deeper than this are taken as a reference cycle
+const MAX_FIELD_DEPTH: usize = 32;
+
+// Field flags (/Ff), 1 << (bit position - 1)
+const READ_ONLY: i64 = 1;
+const REQUIRED: i64 = 1 << 1;
+const PASSWORD: i64 = 1 << 13;
+const RADIO: i64 = 1 << 15;
+const PUSHBUTTON: i64 = 1 << 16;
+
+// The appearance state of a checkbox or radio button that is not selected
+const OFF: &str = "Off";
+
+pub const ACROFORM: &str = "acroform";
+pub const XFA: &str = "xfa";
+
+// One field of a PDF's interactive form, such as the sign-off boxes of a task card
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormField {
+    // The partial names of the field and its parents joined by '.', or the path of an XFA data
+    // value, e.g. "signoff.mechanic" or "form1.TaskCard[1].Inspector"
+    pub name: String,
+    // text, checkbox, radio, button, choice or signature; xfa for XFA data values, whose type
+    // lives in a template this module does not read
+    pub kind: &'static str,
+    // Text entered, the on state of a checkbox, the selected radio button or choices; None when
+    // empty, off, or a password
+    pub value: Option<String>,
+    // Checkboxes and radio groups only
+    pub checked: Option<bool>,
+    // Choice items as shown, or the on states of a checkbox or radio group
+    pub options: Vec<String>,
+    // 1-based page of the field's first widget
+    pub page: Option<usize>,
+    pub read_only: bool,
+    pub required: bool,
+    // Signature fields only: whether the field holds a signature, and its signer name and
+    // signing time when the signature dictionary gives them
+    pub signed: Option<bool>,
+    pub signer: Option<String>,
+    pub signed_at: Option<String>,
+    // ACROFORM or XFA
+    pub source: &'static str,
+}
+
+// What a field takes from its parents when it does not set it itself
+#[derive(Debug, Clone, Default)]
+struct Inherited {
+    field_type: Option<String>,
+    flags: i64,
+    value: Option<PdfObject>,
+}
+
+struct FieldReader<'a> {
+    document: &'a PdfDocument,
+    // 0-based page index by page object, and by each annotation a page lists in /Annots
+    page_index: HashMap<u32, usize>,
+    annotation_pages: HashMap<u32, usize>,
+    visited: HashSet<u32>,
+    fields: Vec<FormField>,
+}
+
+impl FieldReader<'_> {
+    fn resolve(&self, object: Option<&PdfObject>) -> PdfObject {
+        object.and_then(|object| self.document.resolve(object).ok()).unwrap_or(PdfObject::Null)
+    }
+
+    fn text(&self, object: Option<&PdfObject>) -> Option<String> {
+        self.resolve(object).as_text().filter(|text| !text.is_empty())
+    }
+
+    // A widget names its page in /P; writers that leave it out still list it in the page's /Annots
+    fn widget_page(&self, reference: &PdfObject, widget: &PdfObject) -> Option<usize> {
+        if let Some(PdfObject::Reference(page, _)) = widget.get("P") {
+            if let Some(index) = self.page_index.get(page) {
+                return Some(*index);
+            }
+        }
+        match reference {
+            PdfObject::Reference(number, _) => self.annotation_pages.get(number).copied(),
+            _ => None,
+        }
+    }
+
+    // Names of the states a checkbox or radio button has an appearance for, besides Off
+    fn on_states(&self, widgets: &[(PdfObject, PdfObject)]) -> Vec<String> {
+        let mut states: Vec<String> = Vec::new();
+        for (_, widget) in widgets {
+            let appearances = self.resolve(widget.get("AP"));
+            if let PdfObject::Dictionary(entries) = self.resolve(appearances.get("N")) {
+                for (state, _) in entries {
+                    if state != OFF && !states.contains(&state) {
+                        states.push(state);
+                    }
+                }
+            }
+        }
+        states
+    }
+
+    fn read_field(&mut self, reference: &PdfObject, parent: &str, inherited: &Inherited, depth: usize) {
+        if depth > MAX_FIELD_DEPTH {
+            return;
+        }
+        if let PdfObject::Reference(number, _) = reference {
+            if !self.visited.insert(*number) {
+                return;
+            }
+        }
+        let field = self.resolve(Some(reference));
+        let name = match self.text(field.get("T")) {
+            Some(partial) if parent.is_empty() => partial,
+            Some(partial) => format!("{}.{}", parent, partial),
+            None => parent.to_string(),
+        };
+        let inherited = Inherited {
+            field_type: self.resolve(field.get("FT")).as_name().map(str::to_string).or_else(|| inherited.field_type.clone()),
+            flags: self.resolve(field.get("Ff")).as_number().map(|flags| flags as i64).unwrap_or(inherited.flags),
+            value: field.get("V").cloned().or_else(|| inherited.value.clone()),
+        };
+        // Kids with a name of their own are fields; kids without one are the field's widgets
+        let kids: Vec<(PdfObject, PdfObject)> = self.resolve(field.get("Kids")).as_array().unwrap_or_default()
+            .iter()
+            .map(|kid| (kid.clone(), self.resolve(Some(kid))))
+            .collect();
+        let (children, mut widgets): (Vec<_>, Vec<_>) = kids.into_iter().partition(|(_, kid)| kid.get("T").is_some());
+        for (child, _) in &children {
+            self.read_field(child, &name, &inherited, depth + 1);
+        }
+        if !children.is_empty() && widgets.is_empty() {
+            return;
+        }
+        // A field with a single widget is usually merged with it into one dictionary
+        if widgets.is_empty() {
+            widgets.push((reference.clone(), field.clone()));
+        }
+        if let Some(form_field) = self.terminal_field(name, &field, &inherited, &widgets) {
+            self.fields.push(form_field);
+        }
+    }
+
+    fn terminal_field(&self, name: String, field: &PdfObject, inherited: &Inherited, widgets: &[(PdfObject, PdfObject)]) -> Option<FormField> {
+        let flags = inherited.flags;
+        let value = self.resolve(inherited.value.as_ref());
+        let mut form_field = FormField {
+            name,
+            page: widgets.iter().find_map(|(reference, widget)| self.widget_page(reference, widget)).map(|index| index + 1),
+            read_only: flags & READ_ONLY != 0,
+            required: flags & REQUIRED != 0,
+            source: ACROFORM,
+            ..FormField::default()
+        };
+        match inherited.field_type.as_deref()? {
+            "Tx" => {
+                form_field.kind = "text";
+                if flags & PASSWORD == 0 {
+                    form_field.value = value.as_text().filter(|text| !text.is_empty());
+                }
+            }
+            "Btn" if flags & PUSHBUTTON != 0 => form_field.kind = "button",
+            "Btn" => {
+                form_field.kind = if flags & RADIO != 0 { "radio" } else { "checkbox" };
+                // With /Opt, on states are indexes into it and the export values are its texts
+                let exports: Vec<String> = self.resolve(field.get("Opt")).as_array().unwrap_or_default()
+                    .iter()
+                    .filter_map(|option| self.resolve(Some(option)).as_text())
+                    .collect();
+                let export = |state: String| state.parse::<usize>().ok().and_then(|index| exports.get(index).cloned()).unwrap_or(state);
+                // Writers that never set /V still show the state the widgets are in
+                let state = value.as_name().map(str::to_string)
+                    .or_else(|| widgets.iter().find_map(|(_, widget)| widget.get("AS")?.as_name().filter(|state| *state != OFF).map(str::to_string)));
+                let on = state.filter(|state| state != OFF);
+                form_field.checked = Some(on.is_some());
+                form_field.value = on.map(&export);
+                form_field.options = match exports.is_empty() {
+                    true => self.on_states(widgets),
+                    false => exports.clone(),
+                };
+            }
+            "Ch" => {
+                form_field.kind = "choice";
+                // Items are texts, or [export value, text shown] pairs; /V holds export values
+                let items: Vec<(String, String)> = self.resolve(field.get("Opt")).as_array().unwrap_or_default()
+                    .iter()
+                    .filter_map(|option| match self.resolve(Some(option)) {
+                        PdfObject::Array(pair) => {
+                            let text = |item: Option<&PdfObject>| item.and_then(|item| self.resolve(Some(item)).as_text());
+                            let shown = text(pair.last())?;
+                            Some((text(pair.first()).unwrap_or_else(|| shown.clone()), shown))
+                        }
+                        option => option.as_text().map(|text| (text.clone(), text)),
+                    })
+                    .collect();
+                let shown = |export: String| items.iter().find(|(item, _)| *item == export).map_or(export, |(_, shown)| shown.clone());
+                let selected: Vec<String> = match &value {
+                    PdfObject::Array(values) => values.iter().filter_map(|value| self.resolve(Some(value)).as_text()).map(shown).collect(),
+                    value => value.as_text().map(shown).into_iter().collect(),
+                };
+                form_field.options = items.iter().map(|(_, shown)| shown.clone()).collect();
+                form_field.value = Some(selected.join(",")).filter(|selected| !selected.is_empty());
+            }
+            "Sig" => {
+                form_field.kind = "signature";
+                form_field.signed = Some(matches!(value, PdfObject::Dictionary(_)));
+                form_field.signer = self.text(value.get("Name"));
+                form_field.signed_at = self.text(value.get("M"));
+            }
+            _ => return None,
+        }
+        Some(form_field)
+    }
+}
+
+// The XML of the datasets packet: /XFA is either the whole XDP document or an array of packet
+// names and streams
+fn xfa_datasets(document: &PdfDocument, xfa: &PdfObject) -> Option<String> {
+    let packet = match document.resolve(xfa).ok()? {
+        PdfObject::Array(items) => items.chunks_exact(2)
+            .find(|pair| pair[0].as_text().as_deref() == Some("datasets"))
+            .map(|pair| pair[1].clone())?,
+        _ => xfa.clone(),
+    };
+    let (_, data) = document.stream(&packet).ok()?;
+    Some(String::from_utf8_lossy(&data).to_string())
+}
+
+#[derive(Default)]
+struct XmlElement {
+    path: String,
+    // Occurrences so far of each child name, for the [n] of repeated subforms
+    children: HashMap<String, usize>,
+    text: String,
+}
+
+// Leaf values under <xfa:data>, by their data path. Elements are named as in XFA SOM
+// expressions: the second of two sibling TaskCard elements is TaskCard[1]
+pub fn xfa_values(xml: &str) -> Vec<(String, String)> {
+    let is_data = |index: usize| xml[index + "<xfa:data".len()..].starts_with(['>', '/', ' ', '\t', '\r', '\n']);
+    let Some(start) = xml.match_indices("<xfa:data").map(|(index, _)| index).find(|index| is_data(*index)) else { return Vec::new() };
+    let Some(open) = xml[start..].find('>') else { return Vec::new() };
+    if xml[start..start + open].ends_with('/') {
+        return Vec::new();
+    }
+    let mut rest = &xml[start + open + 1..];
+    let mut stack = vec![XmlElement::default()];
+    let mut values = Vec::new();
+    while let Some(index) = rest.find('<') {
+        let top = stack.last_mut().expect("the data element is never popped");
+        top.text.push_str(&unescape(&rest[..index]));
+        rest = &rest[index..];
+        let skip = [("<!--", "-->"), ("<?", "?>")].into_iter().find(|(open, _)| rest.starts_with(open));
+        if let Some((_, close)) = skip {
+            rest = rest.find(close).map_or("", |end| &rest[end + close.len()..]);
+            continue;
+        }
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").unwrap_or(cdata.len());
+            top.text.push_str(&cdata[..end]);
+            rest = cdata.get(end + 3..).unwrap_or("");
+            continue;
+        }
+        let Some(end) = rest.find('>') else { break };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('/') {
+            // The closing tag of <xfa:data> itself
+            if stack.len() == 1 {
+                break;
+            }
+            let element = stack.pop().expect("checked above");
+            let value = element.text.trim();
+            if element.children.is_empty() && !value.is_empty() {
+                values.push((element.path, value.to_string()));
+            }
+            continue;
+        }
+        let name = tag.trim_end_matches('/').split(char::is_whitespace).next().unwrap_or_default();
+        let parent = stack.last_mut().expect("the data element is never popped");
+        let count = parent.children.entry(name.to_string()).or_default();
+        let segment = match *count {
+            0 => name.to_string(),
+            n => format!("{}[{}]", name, n),
+        };
+        *count += 1;
+        let path = match parent.path.is_empty() {
+            true => segment,
+            false => format!("{}.{}", parent.path, segment),
+        };
+        if !tag.ends_with('/') {
+            stack.push(XmlElement { path, ..XmlElement::default() });
+        }
+    }
+    values
+}
+
+// The fields of the document's interactive form, in field tree order. Dynamic XFA forms keep
+// their values only in the XFA datasets, which are read when the AcroForm has no fields; static
+// XFA forms repeat theirs in the AcroForm fields. A document without a form has none
+pub fn read_form_fields(document: &PdfDocument) -> Result<Vec<FormField>, String> {
+    let catalog = document.catalog()?;
+    let form = catalog.get("AcroForm").map(|form| document.resolve(form)).transpose()?.unwrap_or(PdfObject::Null);
+    if form == PdfObject::Null {
+        return Ok(Vec::new());
+    }
+    let pages = document.pages()?;
+    let mut annotation_pages = HashMap::new();
+    for (index, page) in pages.iter().enumerate() {
+        let annotations = page.dictionary.get("Annots").map(|annots| document.resolve(annots)).transpose()?.unwrap_or(PdfObject::Null);
+        for annotation in annotations.as_array().unwrap_or_default() {
+            if let PdfObject::Reference(number, _) = annotation {
+                annotation_pages.insert(*number, index);
+            }
+        }
+    }
+    let mut reader = FieldReader {
+        document,
+        page_index: pages.iter().enumerate().map(|(index, page)| (page.number, index)).collect(),
+        annotation_pages,
+        visited: HashSet::new(),
+        fields: Vec::new(),
+    };
+    let fields = reader.resolve(form.get("Fields"));
+    for field in fields.as_array().unwrap_or_default() {
+        reader.read_field(field, "", &Inherited::default(), 0);
+    }
+    if reader.fields.is_empty() {
+        if let Some(xml) = form.get("XFA").and_then(|xfa| xfa_datasets(document, xfa)) {
+            reader.fields.extend(xfa_values(&xml).into_iter().map(|(name, value)| FormField {
+                name,
+                kind: "xfa",
+                value: Some(value),
+                source: XFA,
+                ..FormField::default()
+            }));
+        }
+    }
+    Ok(reader.fields)
+}
+
+impl FormField {
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("record_id".to_string(), stable_record_id("form_field", &[&self.name]));
+        info.insert("field_name".to_string(), self.name.clone());
+        info.insert("field_type".to_string(), self.kind.to_string());
+        info.insert("form_type".to_string(), self.source.to_string());
+        info.insert("read_only".to_string(), self.read_only.to_string());
+        info.insert("required".to_string(), self.required.to_string());
+        let optional = [
+            ("value", self.value.clone()),
+            ("checked", self.checked.map(|checked| checked.to_string())),
+            ("options", Some(self.options.join(",")).filter(|options| !options.is_empty())),
+            ("page", self.page.map(|page| page.to_string())),
+            ("signed", self.signed.map(|signed| signed.to_string())),
+            ("signer", self.signer.clone()),
+            ("signed_at", self.signed_at.clone()),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                info.insert(key.to_string(), value);
+            }
+        }
+        info
+    }
+}
+
+// Python binding - the form fields of a PDF as records, tagged with the rules' access tags,
+// PII redaction and ID namespace like the records of its text
+#[pyfunction]
+#[pyo3(signature = (pdf_path, rules_path=None, password=None))]
+pub fn extract_form_fields(py: Python, pdf_path: &str, rules_path: Option<&str>, password: Option<&str>) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let engine = match rules_path {
+            Some(path) => ExtractionEngine::from_rules_file(path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => ExtractionEngine::new(),
+        };
+        with_pdf_password(password, || engine.extract_forms(pdf_path).map_err(read_error))
+    })
+}
@@ -0,0 +1,53 @@
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+// Decompressed size of any one stream. A few kilobytes of deflate data can expand to
+// gigabytes, so a stream past this is refused rather than held in memory
+const MAX_STREAM_BYTES: u64 = 256 * 1024 * 1024;
+
+// FlateDecode streams (RFC 1950 zlib around RFC 1951 deflate): cross-reference and object
+// streams, page content, fonts and images alike
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    decompress_capped(data, MAX_STREAM_BYTES)
+}
+
+fn decompress_capped(data: &[u8], max_bytes: u64) -> Result<Vec<u8>, String> {
+    if data.is_empty() {
+        return Err("Compressed stream is empty".to_string());
+    }
+    let mut output = Vec::new();
+    ZlibDecoder::new(data).take(max_bytes + 1).read_to_end(&mut output)
+        .map_err(|e| format!("Cannot decompress stream: {}", e))?;
+    if output.len() as u64 > max_bytes {
+        return Err(format!("Compressed stream expands past {} bytes", max_bytes));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decompresses_within_the_size_cap() {
+        assert_eq!(zlib_decompress(&compress(b"BT /F1 12 Tf (Remove) Tj ET")).unwrap(), b"BT /F1 12 Tf (Remove) Tj ET");
+        assert!(zlib_decompress(b"").is_err());
+        assert!(zlib_decompress(b"not zlib at all").is_err());
+        let truncated = compress(&[b'x'; 4096]);
+        assert!(zlib_decompress(&truncated[..truncated.len() / 2]).is_err());
+
+        let bomb = compress(&[0; 1 << 20]);
+        assert!(bomb.len() < 2048);
+        assert_eq!(decompress_capped(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+        assert!(decompress_capped(&bomb, 1 << 19).unwrap_err().contains("expands past 524288 bytes"));
+    }
+}
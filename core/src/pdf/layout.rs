@@ -0,0 +1,253 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::engine::splitter::{split_pages, PAGE_BREAK};
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_margin_lines() -> usize {
+    3
+}
+
+fn default_min_pages() -> usize {
+    3
+}
+
+fn default_min_share() -> f64 {
+    0.5
+}
+
+// Running headers and footers: chapter titles, revision dates and page numbers repeated at the
+// top and bottom of a manual's pages. Left in, they match module patterns on every page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutConfig {
+    // Blank running headers and footers before extraction
+    #[serde(default = "default_true")]
+    pub strip_running_lines: bool,
+    // Non-blank lines at each end of a page that may belong to its header or footer
+    #[serde(default = "default_margin_lines")]
+    pub margin_lines: usize,
+    // A line runs when it repeats on at least min_pages pages, and on min_share of the pages
+    #[serde(default = "default_min_pages")]
+    pub min_pages: usize,
+    #[serde(default = "default_min_share")]
+    pub min_share: f64,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            strip_running_lines: true,
+            margin_lines: default_margin_lines(),
+            min_pages: default_min_pages(),
+            min_share: default_min_share(),
+        }
+    }
+}
+
+impl LayoutConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.margin_lines == 0 {
+            return Err("layout.margin_lines must be at least 1".to_string());
+        }
+        if self.min_pages < 2 {
+            return Err("layout.min_pages must be at least 2 - a line on one page does not repeat".to_string());
+        }
+        if !(self.min_share > 0.0 && self.min_share <= 1.0) {
+            return Err(format!("layout.min_share must be in (0, 1], got {}", self.min_share));
+        }
+        Ok(())
+    }
+}
+
+// Header, body and footer of a page, as byte ranges of the whole text. A page without a
+// running header has an empty header range at its start, and likewise for footers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageLayout {
+    // 1-based
+    pub page: usize,
+    pub header: (usize, usize),
+    pub body: (usize, usize),
+    pub footer: (usize, usize),
+}
+
+impl PageLayout {
+    pub fn get_info(&self, text: &str) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("page".to_string(), self.page.to_string());
+        for (region, (start, end)) in [("header", self.header), ("body", self.body), ("footer", self.footer)] {
+            info.insert(format!("{}_start", region), start.to_string());
+            info.insert(format!("{}_end", region), end.to_string());
+        }
+        info.insert("header".to_string(), text[self.header.0..self.header.1].trim().to_string());
+        info.insert("footer".to_string(), text[self.footer.0..self.footer.1].trim().to_string());
+        info
+    }
+}
+
+// A non-blank line: byte range in the whole text (without its line break) and its key
+struct Line {
+    start: usize,
+    end: usize,
+    key: String,
+}
+
+// Lines compare with runs of digits and of whitespace collapsed, so "Page 3 of 40" repeats as
+// "Page 4 of 40" and a running "32-11-00" as "32-12-00"
+fn line_key(line: &str) -> String {
+    let mut key = String::with_capacity(line.len());
+    for c in line.trim().chars() {
+        let c = if c.is_ascii_digit() { '#' } else if c.is_whitespace() { ' ' } else { c };
+        if !(matches!(c, '#' | ' ') && key.ends_with(c)) {
+            key.push(c);
+        }
+    }
+    key
+}
+
+fn page_lines(offset: usize, page: &str) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut start = offset;
+    for line in page.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        if !content.trim().is_empty() {
+            lines.push(Line { start, end: start + content.len(), key: line_key(content) });
+        }
+        start += line.len();
+    }
+    lines
+}
+
+// Keys of the lines at each end of the pages that repeat on enough pages, counted a page at a
+// time so a stream of pages can be scanned without holding it whole
+pub struct RunningLineCounter {
+    config: LayoutConfig,
+    headers: HashMap<String, usize>,
+    footers: HashMap<String, usize>,
+    pages_with_text: usize,
+}
+
+fn count_keys(counts: &mut HashMap<String, usize>, margin: &[Line]) {
+    let keys: HashSet<&str> = margin.iter().map(|line| line.key.as_str()).collect();
+    for key in keys {
+        *counts.entry(key.to_string()).or_default() += 1;
+    }
+}
+
+impl RunningLineCounter {
+    pub fn new(config: &LayoutConfig) -> Self {
+        Self { config: config.clone(), headers: HashMap::new(), footers: HashMap::new(), pages_with_text: 0 }
+    }
+
+    pub fn add_page(&mut self, page: &str) {
+        let lines = page_lines(0, page);
+        if lines.is_empty() {
+            return;
+        }
+        self.pages_with_text += 1;
+        let margin = self.config.margin_lines;
+        count_keys(&mut self.headers, &lines[..lines.len().min(margin)]);
+        count_keys(&mut self.footers, &lines[lines.len().saturating_sub(margin)..]);
+    }
+
+    pub fn finish(self) -> RunningLines {
+        let needed = self.config.min_pages.max((self.config.min_share * self.pages_with_text as f64).ceil() as usize);
+        let running = |counts: HashMap<String, usize>| counts.into_iter().filter(|&(_, count)| count >= needed).map(|(key, _)| key).collect();
+        RunningLines { headers: running(self.headers), footers: running(self.footers), margin_lines: self.config.margin_lines }
+    }
+}
+
+// The running headers and footers of a document
+pub struct RunningLines {
+    headers: HashSet<String>,
+    footers: HashSet<String>,
+    margin_lines: usize,
+}
+
+impl RunningLines {
+    // A header is the run of repeating lines from the top of the page down, a footer the run
+    // from the bottom up, so a repeated line in the body never counts
+    fn layout(&self, page_number: usize, offset: usize, page: &str) -> PageLayout {
+        let lines = page_lines(offset, page);
+        let margin = self.margin_lines;
+        let page_end = offset + page.len();
+        let header_lines = lines.iter().take(margin).take_while(|line| self.headers.contains(&line.key)).count();
+        let footer_lines = lines[header_lines..].iter().rev().take(margin)
+            .take_while(|line| self.footers.contains(&line.key)).count();
+        let header_end = if header_lines > 0 { lines[header_lines - 1].end } else { offset };
+        let footer_start = if footer_lines > 0 { lines[lines.len() - footer_lines].start } else { page_end };
+        PageLayout {
+            page: page_number,
+            header: (offset, header_end),
+            body: (header_end, footer_start),
+            footer: (footer_start, page_end),
+        }
+    }
+
+    // One page with its header and footer blanked, as strip_running_lines blanks them in the
+    // whole text
+    pub fn blank_page<'a>(&self, page: &'a str) -> Cow<'a, str> {
+        let layout = self.layout(1, 0, page);
+        blank(page, &[layout.header, layout.footer])
+    }
+}
+
+// Header, body and footer of every page
+pub fn page_layouts(text: &str, config: &LayoutConfig) -> Vec<PageLayout> {
+    let pages = split_pages(text);
+    let mut counter = RunningLineCounter::new(config);
+    for (_, page) in &pages {
+        counter.add_page(page);
+    }
+    let running = counter.finish();
+    pages.iter().enumerate().map(|(index, &(offset, page))| running.layout(index + 1, offset, page)).collect()
+}
+
+// Every character in `ranges` but whitespace replaced by as many spaces as it had bytes
+fn blank<'a>(text: &'a str, ranges: &[(usize, usize)]) -> Cow<'a, str> {
+    let blanked: Vec<&(usize, usize)> = ranges.iter().filter(|(start, end)| start < end).collect();
+    if blanked.is_empty() {
+        return Cow::Borrowed(text);
+    }
+    let mut masked = String::with_capacity(text.len());
+    let mut ranges = blanked.into_iter().peekable();
+    for (offset, c) in text.char_indices() {
+        while ranges.peek().is_some_and(|(_, end)| offset >= *end) {
+            ranges.next();
+        }
+        let inside = ranges.peek().is_some_and(|(start, _)| offset >= *start);
+        if inside && !c.is_whitespace() {
+            masked.extend(std::iter::repeat_n(' ', c.len_utf8()));
+        } else {
+            masked.push(c);
+        }
+    }
+    Cow::Owned(masked)
+}
+
+// `text` with its running headers and footers blanked: every character in them replaced by as
+// many spaces as it had bytes, so offsets and page numbers still point into the whole text
+pub fn strip_running_lines<'a>(text: &'a str, config: &LayoutConfig) -> Cow<'a, str> {
+    if !config.strip_running_lines || !text.contains(PAGE_BREAK) {
+        return Cow::Borrowed(text);
+    }
+    let ranges: Vec<(usize, usize)> = page_layouts(text, config).iter()
+        .flat_map(|layout| [layout.header, layout.footer])
+        .collect();
+    blank(text, &ranges)
+}
+
+// Python binding - header, body and footer offsets of each form-feed separated page
+#[pyfunction]
+#[pyo3(signature = (text, margin_lines=3, min_pages=3, min_share=0.5))]
+pub fn analyze_layout(py: Python, text: &str, margin_lines: usize, min_pages: usize, min_share: f64) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let config = LayoutConfig { strip_running_lines: true, margin_lines, min_pages, min_share };
+        config.validate().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(page_layouts(text, &config).iter().map(|layout| layout.get_info(text)).collect())
+    })
+}
@@ -0,0 +1,11 @@
+#[cfg(feature = "figures")]
+pub mod crypt;
+#[cfg(feature = "figures")]
+pub mod document;
+#[cfg(feature = "figures")]
+pub mod forms;
+#[cfg(feature = "figures")]
+pub mod inflate;
+pub mod layout;
+#[cfg(feature = "figures")]
+pub mod text;
@@ -0,0 +1,868 @@
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::rc::Rc;
+
+use crate::engine::convert::{read_error, with_pdf_password, ConvertedText, ConverterWarning, PdfPasswordError};
+use crate::engine::segment::{dominant_direction, is_rtl_char};
+use crate::pdf::document::{parse_content, PdfDocument, PdfObject, PdfPage};
+
+// The tool converter warnings name for text read by this module
+pub const NATIVE_TOOL: &str = "pdf";
+
+// Form XObjects drawing form XObjects - deeper nesting is treated as a loop
+pub(crate) const MAX_FORM_DEPTH: usize = 8;
+
+// Blank lines kept for one vertical gap, so a page footer far below the body stays compact
+const MAX_BLANK_LINES: usize = 4;
+
+// What makes a band of lines two columns rather than one column or a table: at least this many
+// lines on each side of an empty gutter at least this many characters wide, and columns of at
+// least this many characters that their lines mostly fill. Limits tables have narrow value
+// columns, ragged cells, or gaps inside a side, and keep their rows
+const MIN_COLUMN_LINES: usize = 5;
+const MIN_GUTTER_CHARS: f64 = 2.0;
+const MIN_COLUMN_CHARS: f64 = 20.0;
+const MIN_COLUMN_FILL: f64 = 0.7;
+// Share of a side's lines that may hold a gap as wide as a gutter, as table rows do
+const MAX_GAPPED_LINES: f64 = 0.2;
+
+// WinAnsiEncoding glyph names for codes 0x20-0xFF, which also name most Latin glyphs a
+// /Differences array uses
+const WINANSI_NAMES: &str = "space exclam quotedbl numbersign dollar percent ampersand quotesingle parenleft parenright \
+    asterisk plus comma hyphen period slash zero one two three four five six seven eight nine colon semicolon less equal \
+    greater question at A B C D E F G H I J K L M N O P Q R S T U V W X Y Z bracketleft backslash bracketright \
+    asciicircum underscore grave a b c d e f g h i j k l m n o p q r s t u v w x y z braceleft bar braceright asciitilde \
+    .notdef Euro .notdef quotesinglbase florin quotedblbase ellipsis dagger daggerdbl circumflex perthousand Scaron \
+    guilsinglleft OE .notdef Zcaron .notdef .notdef quoteleft quoteright quotedblleft quotedblright bullet endash emdash \
+    tilde trademark scaron guilsinglright oe .notdef zcaron Ydieresis space exclamdown cent sterling currency yen brokenbar \
+    section dieresis copyright ordfeminine guillemotleft logicalnot hyphen registered macron degree plusminus twosuperior \
+    threesuperior acute mu paragraph periodcentered cedilla onesuperior ordmasculine guillemotright onequarter onehalf \
+    threequarters questiondown Agrave Aacute Acircumflex Atilde Adieresis Aring AE Ccedilla Egrave Eacute Ecircumflex \
+    Edieresis Igrave Iacute Icircumflex Idieresis Eth Ntilde Ograve Oacute Ocircumflex Otilde Odieresis multiply Oslash \
+    Ugrave Uacute Ucircumflex Udieresis Yacute Thorn germandbls agrave aacute acircumflex atilde adieresis aring ae \
+    ccedilla egrave eacute ecircumflex edieresis igrave iacute icircumflex idieresis eth ntilde ograve oacute ocircumflex \
+    otilde odieresis divide oslash ugrave uacute ucircumflex udieresis yacute thorn ydieresis";
+
+// Codes 0x80-0xFF of WinAnsiEncoding (Windows-1252) and MacRomanEncoding
+const WINANSI_HIGH: &str = "€\u{FFFD}‚ƒ„…†‡ˆ‰Š‹Œ\u{FFFD}Ž\u{FFFD}\u{FFFD}‘’“”•–—˜™š›œ\u{FFFD}žŸ\u{A0}¡¢£¤¥¦§¨©ª«¬\u{AD}®¯°±²³´µ¶·¸¹º»¼½¾¿ÀÁÂÃÄÅÆÇÈÉÊËÌÍÎÏÐÑÒÓÔÕÖ×ØÙÚÛÜÝÞßàáâãäåæçèéêëìíîïðñòóôõö÷øùúûüýþÿ";
+const MACROMAN_HIGH: &str = "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø¿¡¬√ƒ≈∆«»…\u{A0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{F8FF}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ";
+
+// StandardEncoding where it differs from ASCII
+const STANDARD_CODES: &[(u8, &str)] = &[
+    (0x27, "quoteright"), (0x60, "quoteleft"), (0xA1, "exclamdown"), (0xA2, "cent"), (0xA3, "sterling"),
+    (0xA4, "fraction"), (0xA5, "yen"), (0xA6, "florin"), (0xA7, "section"), (0xA8, "currency"), (0xA9, "quotesingle"),
+    (0xAA, "quotedblleft"), (0xAB, "guillemotleft"), (0xAC, "guilsinglleft"), (0xAD, "guilsinglright"), (0xAE, "fi"),
+    (0xAF, "fl"), (0xB1, "endash"), (0xB2, "dagger"), (0xB3, "daggerdbl"), (0xB4, "periodcentered"), (0xB6, "paragraph"),
+    (0xB7, "bullet"), (0xB8, "quotesinglbase"), (0xB9, "quotedblbase"), (0xBA, "quotedblright"), (0xBB, "guillemotright"),
+    (0xBC, "ellipsis"), (0xBD, "perthousand"), (0xBF, "questiondown"), (0xC1, "grave"), (0xC2, "acute"),
+    (0xC3, "circumflex"), (0xC4, "tilde"), (0xC5, "macron"), (0xC6, "breve"), (0xC7, "dotaccent"), (0xC8, "dieresis"),
+    (0xCA, "ring"), (0xCB, "cedilla"), (0xCD, "hungarumlaut"), (0xCE, "ogonek"), (0xCF, "caron"), (0xD0, "emdash"),
+    (0xE1, "AE"), (0xE3, "ordfeminine"), (0xE8, "Lslash"), (0xE9, "Oslash"), (0xEA, "OE"), (0xEB, "ordmasculine"),
+    (0xF1, "ae"), (0xF5, "dotlessi"), (0xF8, "lslash"), (0xF9, "oslash"), (0xFA, "oe"), (0xFB, "germandbls"),
+];
+
+// Glyph names outside WinAnsiEncoding that manuals commonly use
+const EXTRA_GLYPHS: &[(&str, &str)] = &[
+    ("fi", "fi"), ("fl", "fl"), ("ff", "ff"), ("ffi", "ffi"), ("ffl", "ffl"), ("minus", "−"), ("fraction", "⁄"),
+    ("breve", "˘"), ("dotaccent", "˙"), ("hungarumlaut", "˝"), ("ogonek", "˛"), ("caron", "ˇ"), ("ring", "˚"),
+    ("Lslash", "Ł"), ("lslash", "ł"), ("dotlessi", "ı"), ("nbspace", "\u{A0}"), ("sfthyphen", "\u{AD}"),
+    ("degreesign", "°"), ("mu1", "µ"), ("Omega", "Ω"), ("Delta", "∆"), ("approxequal", "≈"), ("lessequal", "≤"),
+    ("greaterequal", "≥"), ("notequal", "≠"), ("infinity", "∞"), ("arrowright", "→"), ("arrowleft", "←"),
+];
+
+fn winansi_char(code: u8) -> Option<char> {
+    match code {
+        0x20..=0x7E => Some(code as char),
+        0x80..=0xFF => WINANSI_HIGH.chars().nth((code - 0x80) as usize).filter(|c| *c != '\u{FFFD}'),
+        _ => None,
+    }
+}
+
+// Text of a glyph name: WinAnsi and common names, then uniXXXX and uXXXX[XX]. Suffixes such
+// as .sc or .alt name variants of the same character
+fn glyph_text(name: &str) -> Option<String> {
+    let base = match name.find('.') {
+        Some(0) | None => name,
+        Some(index) => &name[..index],
+    };
+    if let Some(index) = WINANSI_NAMES.split_whitespace().position(|glyph| glyph == base && glyph != ".notdef") {
+        return winansi_char(0x20 + index as u8).map(String::from);
+    }
+    if let Some((_, text)) = EXTRA_GLYPHS.iter().find(|(glyph, _)| *glyph == base) {
+        return Some(text.to_string());
+    }
+    let hex = base.strip_prefix("uni").filter(|hex| hex.len() >= 4).map(|hex| &hex[..4])
+        .or_else(|| base.strip_prefix('u').filter(|hex| (4..=6).contains(&hex.len())))?;
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32).map(String::from)
+}
+
+// A simple font's code -> text table for a named base encoding
+fn base_encoding(name: &str) -> Vec<Option<String>> {
+    let mut table: Vec<Option<String>> = (0..=255u8).map(|code| winansi_char(code).filter(|_| code < 0x80).map(String::from)).collect();
+    match name {
+        "WinAnsiEncoding" => {
+            for code in 0x80..=0xFFu8 {
+                table[code as usize] = winansi_char(code).map(String::from);
+            }
+        }
+        "MacRomanEncoding" => {
+            for (index, c) in MACROMAN_HIGH.chars().enumerate() {
+                table[0x80 + index] = Some(c.to_string());
+            }
+        }
+        _ => {
+            for (code, glyph) in STANDARD_CODES {
+                table[*code as usize] = glyph_text(glyph);
+            }
+        }
+    }
+    table
+}
+
+fn utf16_text(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn code_of(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |code, byte| code << 8 | *byte as u32)
+}
+
+// bfchar and bfrange mappings of a ToUnicode CMap
+fn parse_to_unicode(data: &[u8]) -> Result<HashMap<u32, String>, String> {
+    let mut map = HashMap::new();
+    for operation in parse_content(data)? {
+        match operation.operator.as_str() {
+            "endbfchar" => {
+                for pair in operation.operands.chunks_exact(2) {
+                    if let (Some(source), Some(target)) = (pair[0].as_bytes(), pair[1].as_bytes()) {
+                        map.insert(code_of(&source), utf16_text(&target));
+                    }
+                }
+            }
+            "endbfrange" => {
+                for range in operation.operands.chunks_exact(3) {
+                    let (Some(low), Some(high)) = (range[0].as_bytes(), range[1].as_bytes()) else { continue };
+                    let (low, high) = (code_of(&low), code_of(&high));
+                    // A range never crosses the last byte of its codes, so 256 entries at most
+                    for (offset, code) in (low..=high.min(low + 255)).enumerate() {
+                        let target = match &range[2] {
+                            PdfObject::Array(targets) => targets.get(offset).and_then(PdfObject::as_bytes),
+                            target => target.as_bytes().map(|mut target| {
+                                // The last UTF-16 unit counts up through the range
+                                if let [.., high_byte, low_byte] = target.as_mut_slice() {
+                                    let unit = u16::from_be_bytes([*high_byte, *low_byte]).wrapping_add(offset as u16);
+                                    [*high_byte, *low_byte] = unit.to_be_bytes();
+                                }
+                                target
+                            }),
+                        };
+                        if let Some(target) = target {
+                            map.insert(code, utf16_text(&target));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(map)
+}
+
+struct Font {
+    // Type0 fonts show two-byte codes; every other font one-byte codes
+    two_byte: bool,
+    to_unicode: HashMap<u32, String>,
+    encoding: Vec<Option<String>>,
+    // Glyph widths in glyph space, and what they are scaled by into text space
+    widths: HashMap<u32, f64>,
+    default_width: f64,
+    scale: f64,
+}
+
+impl Font {
+    fn load(document: &PdfDocument, dictionary: &PdfObject, problems: &mut Vec<String>) -> Self {
+        profile_span!("pdf_font");
+        let resolved = |object: Option<&PdfObject>| object.and_then(|object| document.resolve(object).ok());
+        let subtype = dictionary.get("Subtype").and_then(PdfObject::as_name).unwrap_or_default();
+        let base_font = dictionary.get("BaseFont").and_then(PdfObject::as_name).unwrap_or_default().to_string();
+        let mut font = Font {
+            two_byte: subtype == "Type0",
+            to_unicode: HashMap::new(),
+            encoding: Vec::new(),
+            widths: HashMap::new(),
+            // The base-14 fonts carry no widths; Courier is the one every glyph of is known
+            default_width: if base_font.contains("Courier") { 600.0 } else { 500.0 },
+            scale: 0.001,
+        };
+        if let Some(to_unicode @ PdfObject::Reference(..)) = dictionary.get("ToUnicode") {
+            match document.stream(to_unicode).and_then(|(_, data)| parse_to_unicode(&data)) {
+                Ok(map) => font.to_unicode = map,
+                Err(e) => problems.push(format!("ToUnicode of font {}: {}", base_font, e)),
+            }
+        }
+
+        if font.two_byte {
+            let descendant = resolved(dictionary.get("DescendantFonts"))
+                .and_then(|fonts| fonts.as_array().and_then(|fonts| fonts.first()).and_then(|font| document.resolve(font).ok()));
+            let Some(descendant) = descendant else { return font };
+            font.default_width = descendant.get("DW").and_then(PdfObject::as_number).unwrap_or(1000.0);
+            // [first [w1 w2 ...]] lists widths from `first` on; [first last w] gives a run one width
+            let widths = resolved(descendant.get("W")).unwrap_or(PdfObject::Null);
+            let items = widths.as_array().unwrap_or_default();
+            let mut index = 0;
+            while index + 1 < items.len() {
+                let Some(first) = items[index].as_number() else { break };
+                match &items[index + 1] {
+                    PdfObject::Array(run) => {
+                        for (offset, width) in run.iter().enumerate() {
+                            if let Some(width) = width.as_number() {
+                                font.widths.insert(first as u32 + offset as u32, width);
+                            }
+                        }
+                        index += 2;
+                    }
+                    last => {
+                        let (Some(last), Some(width)) = (last.as_number(), items.get(index + 2).and_then(PdfObject::as_number)) else { break };
+                        for code in first as u32..=(last as u32).min(first as u32 + 0xFFFF) {
+                            font.widths.insert(code, width);
+                        }
+                        index += 3;
+                    }
+                }
+            }
+            return font;
+        }
+
+        let encoding = resolved(dictionary.get("Encoding")).unwrap_or(PdfObject::Null);
+        let base = encoding.as_name().or_else(|| encoding.get("BaseEncoding").and_then(PdfObject::as_name)).unwrap_or("StandardEncoding");
+        font.encoding = base_encoding(base);
+        if let Some(differences) = resolved(encoding.get("Differences")) {
+            let mut code = 0usize;
+            for item in differences.as_array().unwrap_or_default() {
+                match item {
+                    PdfObject::Number(_) => code = item.as_number().unwrap_or(0.0) as usize,
+                    PdfObject::Name(glyph) => {
+                        if let Some(slot) = font.encoding.get_mut(code) {
+                            *slot = glyph_text(glyph);
+                        }
+                        code += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let first_char = dictionary.get("FirstChar").and_then(PdfObject::as_number).unwrap_or(0.0) as u32;
+        if let Some(widths) = resolved(dictionary.get("Widths")) {
+            for (offset, width) in widths.as_array().unwrap_or_default().iter().enumerate() {
+                if let Some(width) = document.resolve(width).ok().and_then(|width| width.as_number()) {
+                    font.widths.insert(first_char + offset as u32, width);
+                }
+            }
+        }
+        if let Some(missing) = resolved(dictionary.get("FontDescriptor")).and_then(|descriptor| descriptor.get("MissingWidth").and_then(PdfObject::as_number)) {
+            font.default_width = missing;
+        }
+        if subtype == "Type3" {
+            font.scale = resolved(dictionary.get("FontMatrix")).and_then(|matrix| matrix.as_array().and_then(|m| m.first()).and_then(PdfObject::as_number)).unwrap_or(0.001);
+        }
+        font
+    }
+
+    fn codes(&self, bytes: &[u8]) -> Vec<u32> {
+        match self.two_byte {
+            true => bytes.chunks(2).map(code_of).collect(),
+            false => bytes.iter().map(|byte| *byte as u32).collect(),
+        }
+    }
+
+    // Unmapped codes come out as U+FFFD, so the page counts as garbled rather than passing
+    // for text
+    fn text(&self, code: u32) -> String {
+        self.to_unicode.get(&code).cloned()
+            .or_else(|| self.encoding.get(code as usize).cloned().flatten())
+            .unwrap_or_else(|| '\u{FFFD}'.to_string())
+    }
+
+    fn width(&self, code: u32) -> f64 {
+        self.widths.get(&code).copied().unwrap_or(self.default_width) * self.scale
+    }
+}
+
+type Matrix = [f64; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// `a` then `b`, as PDF concatenates matrices
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+fn translate(tx: f64, ty: f64) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, tx, ty]
+}
+
+fn matrix_of(operands: &[PdfObject]) -> Option<Matrix> {
+    let numbers: Vec<f64> = operands.iter().filter_map(PdfObject::as_number).collect();
+    numbers.try_into().ok()
+}
+
+// Graphics state q and Q save and restore, text state included
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    font: Option<Rc<Font>>,
+    size: f64,
+    char_spacing: f64,
+    word_spacing: f64,
+    horizontal_scale: f64,
+    leading: f64,
+    rise: f64,
+}
+
+impl GraphicsState {
+    fn new(ctm: Matrix) -> Self {
+        Self { ctm, font: None, size: 0.0, char_spacing: 0.0, word_spacing: 0.0, horizontal_scale: 1.0, leading: 0.0, rise: 0.0 }
+    }
+
+    // Text space at the current text matrix -> device space
+    fn rendering_matrix(&self, text_matrix: &Matrix) -> Matrix {
+        let text = [self.size * self.horizontal_scale, 0.0, 0.0, self.size, 0.0, self.rise];
+        multiply(&multiply(&text, text_matrix), &self.ctm)
+    }
+}
+
+// One shown string, placed in device space
+#[derive(Debug, Clone)]
+struct Run {
+    x: f64,
+    y: f64,
+    end: f64,
+    size: f64,
+    text: String,
+}
+
+struct PageReader<'a> {
+    document: &'a PdfDocument,
+    // Fonts by object number, shared by the pages and forms that use them
+    fonts: HashMap<u32, Rc<Font>>,
+    runs: Vec<Run>,
+    problems: Vec<String>,
+}
+
+impl<'a> PageReader<'a> {
+    fn resource(&self, resources: &PdfObject, category: &str, name: &str) -> Option<PdfObject> {
+        let category = self.document.resolve(resources.get(category)?).ok()?;
+        category.get(name).cloned()
+    }
+
+    fn font(&mut self, resources: &PdfObject, name: &str) -> Option<Rc<Font>> {
+        let font = self.resource(resources, "Font", name)?;
+        if let PdfObject::Reference(number, _) = font {
+            if let Some(cached) = self.fonts.get(&number) {
+                return Some(cached.clone());
+            }
+        }
+        let dictionary = self.document.resolve(&font).ok()?;
+        let loaded = Rc::new(Font::load(self.document, &dictionary, &mut self.problems));
+        if let PdfObject::Reference(number, _) = font {
+            self.fonts.insert(number, loaded.clone());
+        }
+        Some(loaded)
+    }
+
+    // Shows a string at the text matrix and advances it past the glyphs
+    fn show(&mut self, state: &GraphicsState, text_matrix: &mut Matrix, bytes: &[u8]) {
+        let Some(font) = state.font.clone() else {
+            self.problems.push("Text shown before a font was set".to_string());
+            return;
+        };
+        let start = state.rendering_matrix(text_matrix);
+        let mut text = String::new();
+        for code in font.codes(bytes) {
+            text.push_str(&font.text(code));
+            // Word spacing applies to the single-byte space only
+            let word_spacing = if code == 32 && !font.two_byte { state.word_spacing } else { 0.0 };
+            let advance = (font.width(code) * state.size + state.char_spacing + word_spacing) * state.horizontal_scale;
+            *text_matrix = multiply(&translate(advance, 0.0), text_matrix);
+        }
+        let end = state.rendering_matrix(text_matrix);
+        self.runs.push(Run { x: start[4], y: start[5], end: end[4], size: start[2].hypot(start[3]), text });
+    }
+
+    // Interprets one content stream. Text in render mode 3 is read like any other: invisible
+    // text is how OCR'd scans carry their text layer
+    fn read(&mut self, data: &[u8], resources: &PdfObject, ctm: Matrix, depth: usize) -> Result<(), String> {
+        let mut state = GraphicsState::new(ctm);
+        let mut saved = Vec::new();
+        let (mut text_matrix, mut line_matrix) = (IDENTITY, IDENTITY);
+        for operation in parse_content(data)? {
+            let operands = &operation.operands;
+            let number = |index: usize| operands.get(index).and_then(PdfObject::as_number).unwrap_or(0.0);
+            match operation.operator.as_str() {
+                "q" => saved.push(state.clone()),
+                "Q" => state = saved.pop().unwrap_or(state),
+                "cm" => {
+                    if let Some(matrix) = matrix_of(operands) {
+                        state.ctm = multiply(&matrix, &state.ctm);
+                    }
+                }
+                "BT" => (text_matrix, line_matrix) = (IDENTITY, IDENTITY),
+                "Tf" => {
+                    let name = operands.first().and_then(PdfObject::as_name).unwrap_or_default();
+                    state.font = self.font(resources, name);
+                    if state.font.is_none() {
+                        self.problems.push(format!("Font {} is not in the resources", name));
+                    }
+                    state.size = number(1);
+                }
+                "Tc" => state.char_spacing = number(0),
+                "Tw" => state.word_spacing = number(0),
+                "Tz" => state.horizontal_scale = number(0) / 100.0,
+                "TL" => state.leading = number(0),
+                "Ts" => state.rise = number(0),
+                "Td" | "TD" => {
+                    if operation.operator == "TD" {
+                        state.leading = -number(1);
+                    }
+                    line_matrix = multiply(&translate(number(0), number(1)), &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tm" => {
+                    if let Some(matrix) = matrix_of(operands) {
+                        (text_matrix, line_matrix) = (matrix, matrix);
+                    }
+                }
+                "T*" | "'" | "\"" => {
+                    if operation.operator == "\"" {
+                        state.word_spacing = number(0);
+                        state.char_spacing = number(1);
+                    }
+                    line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                    text_matrix = line_matrix;
+                    if let Some(bytes) = operands.last().filter(|_| operation.operator != "T*").and_then(PdfObject::as_bytes) {
+                        self.show(&state, &mut text_matrix, &bytes);
+                    }
+                }
+                "Tj" => {
+                    if let Some(bytes) = operands.first().and_then(PdfObject::as_bytes) {
+                        self.show(&state, &mut text_matrix, &bytes);
+                    }
+                }
+                "TJ" => {
+                    for item in operands.first().and_then(PdfObject::as_array).unwrap_or_default() {
+                        match item.as_bytes() {
+                            Some(bytes) => self.show(&state, &mut text_matrix, &bytes),
+                            // Adjustments are in thousandths of the font size, positive moving left
+                            None => {
+                                let advance = -item.as_number().unwrap_or(0.0) / 1000.0 * state.size * state.horizontal_scale;
+                                text_matrix = multiply(&translate(advance, 0.0), &text_matrix);
+                            }
+                        }
+                    }
+                }
+                "Do" => {
+                    let name = operands.first().and_then(PdfObject::as_name).unwrap_or_default();
+                    self.form(resources, name, &state.ctm, depth)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Form XObjects draw in their own resources and matrix; images carry no text
+    fn form(&mut self, resources: &PdfObject, name: &str, ctm: &Matrix, depth: usize) -> Result<(), String> {
+        let Some(reference) = self.resource(resources, "XObject", name) else { return Ok(()) };
+        // Checked before decoding, since image filters are not ones this reader decodes
+        if self.document.resolve(&reference)?.get("Subtype").and_then(PdfObject::as_name) != Some("Form") {
+            return Ok(());
+        }
+        let (dictionary, data) = self.document.stream(&reference)?;
+        if depth >= MAX_FORM_DEPTH {
+            return Err(format!("Form XObject {} nests more than {} levels", name, MAX_FORM_DEPTH));
+        }
+        let matrix = dictionary.get("Matrix").and_then(|matrix| matrix_of(matrix.as_array()?)).unwrap_or(IDENTITY);
+        let form_resources = match dictionary.get("Resources") {
+            Some(form_resources) => self.document.resolve(form_resources)?,
+            None => resources.clone(),
+        };
+        self.read(&data, &form_resources, multiply(&matrix, ctm), depth + 1)
+    }
+
+    // A page's text; whatever could not be read is left in `problems`
+    fn page_text(&mut self, page: &PdfPage) -> String {
+        profile_span!("pdf_page");
+        self.runs.clear();
+        let data = self.document.page_content(page, &mut self.problems);
+        if let Err(e) = self.read(&data, &page.resources, IDENTITY, 0) {
+            self.problems.push(e);
+        }
+        layout(std::mem::take(&mut self.runs))
+    }
+}
+
+// Whether a character keeps its left-to-right order inside right-to-left text: Latin letters
+// and digits, Arabic-Indic digits included
+fn keeps_order(c: char) -> bool {
+    c.is_numeric() || (c.is_alphanumeric() && !is_rtl_char(c))
+}
+
+fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        other => other,
+    }
+}
+
+// A right-to-left line from the visual order its glyphs are drawn in, left to right, to the
+// order it is read in: reversed, except for the Latin words and numbers in it such as "C12"
+// or "32-11-00". Brackets outside those swap, since reversing the line mirrors them
+fn logical_rtl(visual: &str) -> String {
+    let chars: Vec<char> = visual.chars().rev().collect();
+    let mut output = String::with_capacity(visual.len());
+    let mut index = 0;
+    while index < chars.len() {
+        if !keeps_order(chars[index]) {
+            output.push(mirror(chars[index]));
+            index += 1;
+            continue;
+        }
+        // The longest stretch without right-to-left letters that ends on a character kept in order
+        let mut end = index;
+        let mut scan = index;
+        while scan < chars.len() && (chars[scan].is_numeric() || !is_rtl_char(chars[scan])) {
+            if keeps_order(chars[scan]) {
+                end = scan;
+            }
+            scan += 1;
+        }
+        output.extend(chars[index..=end].iter().rev());
+        index = end + 1;
+    }
+    output
+}
+
+// Lays runs out as lines top to bottom, like pdftotext -layout: columns become runs of spaces
+// one per character width, and the leftmost text on the page sets the margin. Two-column bands
+// are read a column at a time, each column with its own margin. Lines that are mostly Arabic
+// or Hebrew are put back in reading order and indented from the right margin instead
+fn layout(mut runs: Vec<Run>) -> String {
+    runs.retain(|run| !run.text.trim().is_empty() && run.size > 0.0);
+    if runs.is_empty() {
+        return String::new();
+    }
+    let (width, chars) = runs.iter().filter(|run| run.end > run.x)
+        .fold((0.0, 0), |(width, chars), run| (width + run.end - run.x, chars + run.text.chars().count()));
+    let char_width = if chars > 0 { width / chars as f64 } else { runs[0].size * 0.5 };
+    let left = runs.iter().map(|run| run.x).fold(f64::INFINITY, f64::min);
+    let page_right = runs.iter().map(|run| run.end).fold(f64::NEG_INFINITY, f64::max);
+
+    runs.sort_by(|a, b| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+    let mut lines: Vec<Vec<Run>> = Vec::new();
+    for run in runs {
+        match lines.last_mut() {
+            Some(line) if line[0].y - run.y <= 0.5 * line[0].size.min(run.size) => line.push(run),
+            _ => lines.push(vec![run]),
+        }
+    }
+
+    // Blank lines stand in for gaps of more than one typical baseline step
+    let mut steps: Vec<f64> = lines.windows(2).map(|pair| pair[0][0].y - pair[1][0].y).collect();
+    steps.sort_by(f64::total_cmp);
+    let step = steps.get(steps.len() / 2).copied().unwrap_or(0.0);
+    let mut text = String::new();
+    // A column starts back up the page, which takes no blank lines
+    let mut previous_y: Option<f64> = None;
+    for (margin, right, block) in reading_order(lines, (left, page_right), char_width) {
+        for mut line in block {
+            line.sort_by(|a, b| a.x.total_cmp(&b.x));
+            if let Some(previous_y) = previous_y.filter(|&previous_y| step > 0.0 && previous_y > line[0].y) {
+                let blanks = ((previous_y - line[0].y) / step).round() as usize;
+                text.extend(std::iter::repeat_n('\n', blanks.saturating_sub(1).min(MAX_BLANK_LINES)));
+            }
+            previous_y = Some(line[0].y);
+
+            let mut output = String::new();
+            let mut end = line[0].x;
+            for run in &line {
+                let gap = run.x - end;
+                if end > line[0].x && gap >= 0.15 * run.size {
+                    let spaces = if gap < 2.5 * char_width { 1 } else { ((gap / char_width).round() as usize).max(2) };
+                    if spaces > 1 || !(output.ends_with(char::is_whitespace) || run.text.starts_with(char::is_whitespace)) {
+                        output.push_str(&" ".repeat(spaces));
+                    }
+                }
+                output.push_str(&run.text);
+                end = end.max(run.end);
+            }
+            let (indent, output) = match dominant_direction(&output) {
+                "rtl" => (right - end, logical_rtl(output.trim())),
+                _ => (line[0].x - margin, output),
+            };
+            text.push_str(&" ".repeat((indent / char_width).round().max(0.0) as usize));
+            text.push_str(output.trim_end());
+            text.push('\n');
+        }
+    }
+    text
+}
+
+fn crosses(line: &[Run], x: f64) -> bool {
+    line.iter().any(|run| run.x < x && run.end > x)
+}
+
+// Whether one side of a band reads as a column of text: enough lines, wide enough, mostly
+// filled to its edges, and without the inner gaps of table rows
+fn is_column(side: &[Vec<Run>], char_width: f64) -> bool {
+    let lines: Vec<&Vec<Run>> = side.iter().filter(|line| !line.is_empty()).collect();
+    if lines.len() < MIN_COLUMN_LINES {
+        return false;
+    }
+    let start = lines.iter().flat_map(|line| line.iter()).map(|run| run.x).fold(f64::INFINITY, f64::min);
+    let end = lines.iter().flat_map(|line| line.iter()).map(|run| run.end).fold(f64::NEG_INFINITY, f64::max);
+    let width = end - start;
+    if width < MIN_COLUMN_CHARS * char_width {
+        return false;
+    }
+    let mut fills: Vec<f64> = lines.iter()
+        .map(|line| (line.iter().map(|run| run.end).fold(f64::NEG_INFINITY, f64::max) - start) / width)
+        .collect();
+    fills.sort_by(f64::total_cmp);
+    let gapped = lines.iter()
+        .filter(|line| {
+            let mut runs: Vec<&Run> = line.iter().collect();
+            runs.sort_by(|a, b| a.x.total_cmp(&b.x));
+            runs.windows(2).any(|pair| pair[1].x - pair[0].end >= MIN_GUTTER_CHARS * char_width)
+        })
+        .count();
+    fills[fills.len() / 2] >= MIN_COLUMN_FILL && (gapped as f64) <= MAX_GAPPED_LINES * lines.len() as f64
+}
+
+// Blocks of lines in reading order, each with the left and right margins indentation is
+// measured from.
+// The gutter is the x in the middle of the band that the fewest lines cross; the lines that
+// cross it split the page into bands, and each band with a column on both sides of the gutter
+// is read left column first, or right column first when the band is mostly Arabic or Hebrew.
+// Columns are split again, so three-column pages read in order too
+fn reading_order(lines: Vec<Vec<Run>>, margins: (f64, f64), char_width: f64) -> Vec<(f64, f64, Vec<Vec<Run>>)> {
+    let (margin, right_margin) = margins;
+    if lines.len() < MIN_COLUMN_LINES || char_width <= 0.0 {
+        return vec![(margin, right_margin, lines)];
+    }
+    let start = lines.iter().flatten().map(|run| run.x).fold(f64::INFINITY, f64::min);
+    let end = lines.iter().flatten().map(|run| run.end).fold(f64::NEG_INFINITY, f64::max);
+    if end - start < 2.0 * MIN_COLUMN_CHARS * char_width {
+        return vec![(margin, right_margin, lines)];
+    }
+    // Candidates leave room for a column either side; the longest stretch of least-crossed
+    // candidates has the gutter in its middle
+    let mut best: Option<(usize, f64, f64)> = None;
+    let mut stretch: Option<(usize, f64, f64)> = None;
+    let mut x = start + MIN_COLUMN_CHARS * char_width;
+    while x <= end - MIN_COLUMN_CHARS * char_width {
+        let crossing = lines.iter().filter(|line| crosses(line, x)).count();
+        stretch = match stretch {
+            Some((count, from, _)) if count == crossing => Some((count, from, x)),
+            _ => Some((crossing, x, x)),
+        };
+        if let Some((count, from, to)) = stretch {
+            let better = best.is_none_or(|(best_count, best_from, best_to)| count < best_count || (count == best_count && to - from > best_to - best_from));
+            if better {
+                best = Some((count, from, to));
+            }
+        }
+        x += 0.5 * char_width;
+    }
+    let Some((crossing, from, to)) = best else { return vec![(margin, right_margin, lines)] };
+    if crossing * 2 > lines.len() {
+        return vec![(margin, right_margin, lines)];
+    }
+    let gutter = (from + to) / 2.0;
+
+    let mut blocks: Vec<(f64, f64, Vec<Vec<Run>>)> = Vec::new();
+    let push_block = |blocks: &mut Vec<(f64, f64, Vec<Vec<Run>>)>, block_margins: (f64, f64), block: Vec<Vec<Run>>| {
+        match blocks.last_mut() {
+            Some((last_margin, last_right, last)) if (*last_margin, *last_right) == block_margins && block_margins == margins => last.extend(block),
+            _ => blocks.push((block_margins.0, block_margins.1, block)),
+        }
+    };
+    let mut lines = lines.into_iter().peekable();
+    while let Some(line) = lines.next() {
+        if crosses(&line, gutter) {
+            push_block(&mut blocks, margins, vec![line]);
+            continue;
+        }
+        let mut band = vec![line];
+        while let Some(next) = lines.next_if(|next| !crosses(next, gutter)) {
+            band.push(next);
+        }
+        let (left, right): (Vec<Vec<Run>>, Vec<Vec<Run>>) = band.iter()
+            .map(|line| {
+                let (left, right): (Vec<Run>, Vec<Run>) = line.iter().cloned().partition(|run| run.end <= gutter);
+                (left, right)
+            })
+            .unzip();
+        let left_edge = left.iter().flatten().map(|run| run.end).fold(f64::NEG_INFINITY, f64::max);
+        let right_edge = right.iter().flatten().map(|run| run.x).fold(f64::INFINITY, f64::min);
+        if right_edge - left_edge < MIN_GUTTER_CHARS * char_width || !is_column(&left, char_width) || !is_column(&right, char_width) {
+            push_block(&mut blocks, margins, band);
+            continue;
+        }
+        let band_text: String = band.iter().flatten().map(|run| run.text.as_str()).collect();
+        let sides = if dominant_direction(&band_text) == "rtl" { [right, left] } else { [left, right] };
+        for side in sides {
+            let side: Vec<Vec<Run>> = side.into_iter().filter(|line| !line.is_empty()).collect();
+            let side_margins = (
+                side.iter().flatten().map(|run| run.x).fold(f64::INFINITY, f64::min),
+                side.iter().flatten().map(|run| run.end).fold(f64::NEG_INFINITY, f64::max),
+            );
+            for (block_margin, block_right, block) in reading_order(side, side_margins, char_width) {
+                push_block(&mut blocks, (block_margin, block_right), block);
+            }
+        }
+    }
+    blocks
+}
+
+// A PDF's text layer read a page at a time, each page ending on a form feed. The parsed PDF is
+// held, its text a page at a time; pages that cannot be read come out empty and are reported
+// as warnings
+pub struct PdfTextPages<'a> {
+    path: String,
+    pages: Vec<PdfPage>,
+    next: usize,
+    reader: PageReader<'a>,
+    page: Cursor<Vec<u8>>,
+    warnings: Vec<ConverterWarning>,
+}
+
+impl<'a> PdfTextPages<'a> {
+    pub fn new(document: &'a PdfDocument, path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let pages = document.pages().map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self {
+            path: path.to_string(),
+            pages,
+            next: 0,
+            reader: PageReader { document, fonts: HashMap::new(), runs: Vec::new(), problems: Vec::new() },
+            page: Cursor::new(Vec::new()),
+            warnings: Vec::new(),
+        })
+    }
+
+    // What could not be read on the pages read so far
+    pub fn warnings(&self) -> &[ConverterWarning] {
+        &self.warnings
+    }
+
+    pub fn into_warnings(self) -> Vec<ConverterWarning> {
+        self.warnings
+    }
+
+    fn read_page(&mut self) {
+        let mut bytes = self.reader.page_text(&self.pages[self.next]).into_bytes();
+        bytes.push(0x0c);
+        let mut problems = std::mem::take(&mut self.reader.problems);
+        problems.dedup();
+        for problem in problems {
+            self.warnings.push(ConverterWarning {
+                source: self.path.clone(),
+                tool: NATIVE_TOOL.to_string(),
+                line: self.warnings.len() + 1,
+                message: format!("Page {}: {}", self.next + 1, problem),
+            });
+        }
+        self.next += 1;
+        self.page = Cursor::new(bytes);
+    }
+}
+
+impl Read for PdfTextPages<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.page.position() as usize == self.page.get_ref().len() && self.next < self.pages.len() {
+            self.read_page();
+        }
+        self.page.read(buf)
+    }
+}
+
+// The text layer of a PDF, a form feed after every page, so page numbers stay those of the PDF
+pub fn read_text_layer(path: &str) -> Result<ConvertedText, Box<dyn std::error::Error>> {
+    let document = PdfDocument::read(path)?;
+    let mut pages = PdfTextPages::new(&document, path)?;
+    let mut bytes = Vec::new();
+    pages.read_to_end(&mut bytes)?;
+    Ok(ConvertedText { bytes, warnings: pages.into_warnings(), ocr: None })
+}
+
+// Python binding - a PDF's text layer with form feeds between pages, and what could not be read.
+// Encrypted PDFs open with password as for extract_modules
+#[pyfunction]
+#[pyo3(signature = (path, password=None))]
+pub fn read_pdf_text(py: Python, path: &str, password: Option<&str>) -> PyResult<(String, Vec<HashMap<String, String>>)> {
+    py.allow_threads(|| {
+        let converted = with_pdf_password(password, || read_text_layer(path)).map_err(|e| match e.downcast_ref::<PdfPasswordError>() {
+            Some(_) => read_error(e),
+            None => PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()),
+        })?;
+        Ok((String::from_utf8_lossy(&converted.bytes).to_string(), converted.warnings.iter().map(ConverterWarning::get_info).collect()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Glyphs 5 units wide at size 10, placed left to right as a PDF draws them
+    fn run(x: f64, y: f64, text: &str) -> Run {
+        Run { x, y, end: x + 5.0 * text.chars().count() as f64, size: 10.0, text: text.to_string() }
+    }
+
+    #[test]
+    fn reads_right_to_left_lines_in_reading_order() {
+        // "افصل قاطع C12 قبل", "1. افصل" and an indented "قبل", as they are drawn
+        let runs = vec![
+            run(0.0, 700.0, "لبق"),
+            run(20.0, 700.0, "C12"),
+            run(40.0, 700.0, "عطاق"),
+            run(65.0, 700.0, "لصفا"),
+            run(50.0, 688.0, "لصفا"),
+            run(75.0, 688.0, ".1"),
+            run(55.0, 676.0, "لبق"),
+        ];
+        assert_eq!(layout(runs), "افصل قاطع C12 قبل\n1. افصل\n   قبل\n");
+        assert_eq!(logical_rtl("(32-11-00) ةمهملا"), "المهمة (32-11-00)");
+        // Left-to-right lines are untouched
+        assert_eq!(layout(vec![run(0.0, 700.0, "(1)"), run(20.0, 700.0, "Remove")]), "(1) Remove\n");
+    }
+
+    #[test]
+    fn reads_right_to_left_columns_right_column_first() {
+        let mut runs = Vec::new();
+        for line in 0..MIN_COLUMN_LINES {
+            let y = 700.0 - 12.0 * line as f64;
+            runs.push(run(0.0, y, &"ل".repeat(24)));
+            runs.push(run(150.0, y, &"ر".repeat(24)));
+        }
+        let text = layout(runs);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2 * MIN_COLUMN_LINES);
+        assert!(lines[..MIN_COLUMN_LINES].iter().all(|line| *line == "ر".repeat(24)), "{}", text);
+        assert!(lines[MIN_COLUMN_LINES..].iter().all(|line| *line == "ل".repeat(24)), "{}", text);
+    }
+}
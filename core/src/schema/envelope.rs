@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use chrono::Utc;
+use utoipa::ToSchema;
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::licensing::branding::Branding;
+use crate::licensing::manager::License;
+use crate::schema::migrations::CURRENT_SCHEMA_VERSION;
+use crate::security::crypto::{sha256, to_hex};
+use crate::security::watermark::Watermark;
+
+pub const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Tier recorded for offline runs driven by a rules file rather than a tenant license
+pub const UNLICENSED_TIER: &str = "unlicensed";
+
+// Placeholder for provenance facts that were never recorded (migrated archives)
+pub const UNKNOWN_PROVENANCE: &str = "unknown";
+
+// How an output was produced - enough to reproduce or explain any downstream record
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Provenance {
+    pub engine_version: String,
+    pub rules_version: String,
+    pub profile: String,
+    pub license_tier: String,
+    pub processed_at: String,
+    pub input_hash: String,
+    // The rules' record ID namespace, e.g. "acme:a320:amos" - see engine::ids
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_namespace: Option<String>,
+    // The format record IDs are written in when not prefixed, e.g. "uuid-v5" or "ulid/blake3"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_format: Option<String>,
+    // Licensed runs only - see security::watermark
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<String>,
+    // Licensed runs whose license carries approved branding - see licensing::branding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branding: Option<Branding>,
+}
+
+impl Provenance {
+    pub fn new(engine: &ExtractionEngine, license_tier: &str, input: &[u8]) -> Self {
+        Self {
+            engine_version: ENGINE_VERSION.to_string(),
+            rules_version: engine.get_rules_version().to_string(),
+            profile: engine.get_profile_name().to_string(),
+            license_tier: license_tier.to_string(),
+            processed_at: Utc::now().to_rfc3339(),
+            input_hash: hash_input(input),
+            id_namespace: engine.get_id_namespace().name(),
+            id_format: engine.get_id_namespace().format_name(),
+            watermark: None,
+            branding: None,
+        }
+    }
+
+    // Provenance of a run under a license: its tier, a watermark naming the license when it has
+    // a key to sign one with, and the license's branding
+    pub fn licensed(engine: &ExtractionEngine, license: &License, input: &[u8]) -> Self {
+        let mut provenance = Self::new(engine, license.get_tier(), input);
+        provenance.watermark = Watermark::issue(license, &provenance.input_hash).map(|watermark| watermark.encode());
+        provenance.branding = license.get_branding();
+        provenance
+    }
+
+    pub fn unknown() -> Self {
+        Self {
+            engine_version: UNKNOWN_PROVENANCE.to_string(),
+            rules_version: UNKNOWN_PROVENANCE.to_string(),
+            profile: UNKNOWN_PROVENANCE.to_string(),
+            license_tier: UNKNOWN_PROVENANCE.to_string(),
+            processed_at: UNKNOWN_PROVENANCE.to_string(),
+            input_hash: UNKNOWN_PROVENANCE.to_string(),
+            id_namespace: None,
+            id_format: None,
+            watermark: None,
+            branding: None,
+        }
+    }
+}
+
+pub fn hash_input(input: &[u8]) -> String {
+    format!("sha256:{}", to_hex(&sha256(&[input])))
+}
+
+pub fn wrap_output(provenance: &Provenance, payload: Value) -> Value {
+    json!({
+        "schema_version": CURRENT_SCHEMA_VERSION,
+        "provenance": provenance,
+        "payload": payload,
+    })
+}
+
+// Record collections live under "payload" from schema 2.0 on; older outputs are flat
+pub fn payload_of(output: &Value) -> &Value {
+    output.get("payload").unwrap_or(output)
+}
+
+pub fn output_watermark(output: &Value) -> Option<String> {
+    output.get("provenance")
+        .and_then(|provenance| provenance.get("watermark"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+// Reports name the licensee the way the output they are made from does
+pub fn output_branding(output: &Value) -> Option<Branding> {
+    output.get("provenance")
+        .and_then(|provenance| provenance.get("branding"))
+        .and_then(|branding| serde_json::from_value::<Branding>(branding.clone()).ok())
+        .filter(|branding| !branding.is_empty())
+}
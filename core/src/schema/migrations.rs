@@ -0,0 +1,140 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+
+use crate::engine::ids::stable_record_id;
+use crate::schema::envelope::Provenance;
+
+// Schema version written into every structured output
+pub const CURRENT_SCHEMA_VERSION: &str = "2.1";
+
+// Outputs written before versioning was introduced carry no schema_version field
+const UNVERSIONED_SCHEMA_VERSION: &str = "1.0";
+
+// Record collections and the record type used when deriving their IDs
+const RECORD_COLLECTIONS: &[(&str, &str)] = &[("modules", "module"), ("steps", "step")];
+
+pub struct Migration {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub description: &'static str,
+    pub apply: fn(&mut Value) -> Result<(), Box<dyn std::error::Error>>,
+}
+
+// Ordered chain of single-step upgrades - each entry's `to` is the next entry's `from`
+pub fn registry() -> Vec<Migration> {
+    vec![
+        Migration {
+            from: "1.0",
+            to: "1.1",
+            description: "Add stable record_id to module and step records",
+            apply: migrate_1_0_to_1_1,
+        },
+        Migration {
+            from: "1.1",
+            to: "2.0",
+            description: "Wrap output in a provenance envelope",
+            apply: migrate_1_1_to_2_0,
+        },
+        Migration {
+            from: "2.0",
+            to: "2.1",
+            description: "Records gain optional normalized and original offsets",
+            apply: migrate_additive,
+        },
+    ]
+}
+
+fn migrate_1_0_to_1_1(output: &mut Value) -> Result<(), Box<dyn std::error::Error>> {
+    for (collection, record_type) in RECORD_COLLECTIONS {
+        let records = match output.get_mut(*collection).and_then(Value::as_array_mut) {
+            Some(records) => records,
+            None => continue,
+        };
+        for record in records.iter_mut().filter_map(Value::as_object_mut) {
+            if record.contains_key("record_id") {
+                continue;
+            }
+            let pattern = record.get("pattern")
+                .and_then(Value::as_str)
+                .ok_or_else(|| format!("{} record without pattern cannot be assigned an ID", record_type))?;
+            let record_id = stable_record_id(record_type, &[pattern]);
+            record.insert("record_id".to_string(), Value::String(record_id));
+        }
+    }
+    Ok(())
+}
+
+fn migrate_1_1_to_2_0(output: &mut Value) -> Result<(), Box<dyn std::error::Error>> {
+    // Provenance was never recorded for these outputs, so it is marked unknown
+    let mut payload = output.take();
+    if let Some(object) = payload.as_object_mut() {
+        object.remove("schema_version");
+    }
+    *output = serde_json::json!({
+        "provenance": Provenance::unknown(),
+        "payload": payload,
+    });
+    Ok(())
+}
+
+// Minor versions that only add optional fields need no data changes
+fn migrate_additive(_output: &mut Value) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(())
+}
+
+fn parse_version(version: &str) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+    let (major, minor) = version.split_once('.')
+        .ok_or_else(|| format!("Invalid schema version: {}", version))?;
+    Ok((major.parse()?, minor.parse()?))
+}
+
+pub fn schema_version_of(output: &Value) -> String {
+    output.get("schema_version")
+        .and_then(Value::as_str)
+        .unwrap_or(UNVERSIONED_SCHEMA_VERSION)
+        .to_string()
+}
+
+pub fn migrate_value(mut output: Value, target_version: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    if !output.is_object() {
+        return Err("Structured output must be a JSON object".into());
+    }
+
+    let target = parse_version(target_version)?;
+    if target > parse_version(CURRENT_SCHEMA_VERSION)? {
+        return Err(format!("Unknown target schema version: {}", target_version).into());
+    }
+
+    let mut version = schema_version_of(&output);
+    if parse_version(&version)? > target {
+        return Err(format!("Cannot downgrade output from {} to {}", version, target_version).into());
+    }
+
+    // Walk the chain one step at a time until the target is reached
+    let migrations = registry();
+    while parse_version(&version)? < target {
+        let migration = migrations.iter()
+            .find(|m| m.from == version)
+            .ok_or_else(|| format!("No migration registered from schema {}", version))?;
+        (migration.apply)(&mut output)
+            .map_err(|e| format!("Migration {} -> {} failed: {}", migration.from, migration.to, e))?;
+        version = migration.to.to_string();
+        output["schema_version"] = Value::String(version.clone());
+    }
+
+    Ok(output)
+}
+
+// Python binding - takes and returns JSON text
+#[pyfunction]
+#[pyo3(signature = (old_json, target_version=CURRENT_SCHEMA_VERSION))]
+pub fn migrate_output(py: Python, old_json: &str, target_version: &str) -> PyResult<String> {
+    py.allow_threads(|| {
+        let output: Value = serde_json::from_str(old_json)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid output JSON: {}", e)))?;
+        let migrated = migrate_value(output, target_version)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        serde_json::to_string(&migrated)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    })
+}
@@ -0,0 +1,6 @@
+pub mod migrations;
+pub mod envelope;
+pub mod reader;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod overflow;
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::schema::envelope::hash_input;
+use crate::security::seal::DeliveryConfig;
+
+// Suffixes of the fields a cut field gains: where its full text went, and that text's hash
+pub const OVERFLOW_PATH_SUFFIX: &str = "_overflow_path";
+pub const OVERFLOW_HASH_SUFFIX: &str = "_overflow_hash";
+
+fn default_fields() -> Vec<String> {
+    vec!["text".to_string(), "matched_text".to_string()]
+}
+
+// Record text cut to a consumer's field-length limit, e.g. "truncation": {"max_chars": 32000}.
+// The full text of every cut field is written to a side file next to the output. Without
+// max_chars nothing is cut
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TruncationConfig {
+    // Characters, not bytes - the limit PLM and ticketing systems put on a field
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+    // Record fields that may be cut. IDs and JSON-encoded fields are best left out: a cut one
+    // no longer joins or parses
+    #[serde(default = "default_fields")]
+    pub fields: Vec<String>,
+}
+
+impl Default for TruncationConfig {
+    fn default() -> Self {
+        Self { max_chars: None, fields: default_fields() }
+    }
+}
+
+// The full text of a cut field, to be written at `path` - relative to the output's directory
+#[derive(Debug, Clone)]
+pub struct OverflowFile {
+    pub path: String,
+    pub body: String,
+}
+
+impl TruncationConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.max_chars.is_some()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_chars == Some(0) {
+            return Err("truncation.max_chars must be at least 1".to_string());
+        }
+        if self.fields.is_empty() {
+            return Err("truncation.fields must name at least one record field".to_string());
+        }
+        for field in &self.fields {
+            if field.ends_with(OVERFLOW_PATH_SUFFIX) || field.ends_with(OVERFLOW_HASH_SUFFIX) {
+                return Err(format!("truncation.fields: {} is written by truncation itself", field));
+            }
+        }
+        Ok(())
+    }
+
+    // The tightest limit wins, so an output fits every pack's consumer
+    pub fn merge(&mut self, other: &TruncationConfig) {
+        self.max_chars = match (self.max_chars, other.max_chars) {
+            (Some(ours), Some(theirs)) => Some(ours.min(theirs)),
+            (ours, theirs) => ours.or(theirs),
+        };
+        for field in &other.fields {
+            if !self.fields.contains(field) {
+                self.fields.push(field.clone());
+            }
+        }
+    }
+
+    // Cuts the long fields of every record in the payload's collections, pointing each at the
+    // side file its full text goes to under `overflow_dir`. Nothing is written here - replays
+    // rebuild the cut payload to compare hashes - so the caller writes the files returned
+    pub fn truncate(&self, payload: &mut Value, overflow_dir: &str, delivery: &DeliveryConfig) -> Vec<OverflowFile> {
+        let Some(max_chars) = self.max_chars else {
+            return Vec::new();
+        };
+        let Some(collections) = payload.as_object_mut() else {
+            return Vec::new();
+        };
+        let mut files = Vec::new();
+        let mut names = HashSet::new();
+        for (collection, records) in collections.iter_mut() {
+            let Some(records) = records.as_array_mut() else {
+                continue;
+            };
+            for (index, record) in records.iter_mut().enumerate() {
+                let Some(record) = record.as_object_mut() else {
+                    continue;
+                };
+                let record_name = record.get("record_id").and_then(Value::as_str)
+                    .map(file_safe)
+                    .unwrap_or_else(|| format!("{}_{}", collection, index + 1));
+                let long: Vec<String> = record.iter()
+                    .filter(|(field, value)| self.fields.contains(field) && value.as_str().is_some_and(|text| text.chars().count() > max_chars))
+                    .map(|(field, _)| field.clone())
+                    .collect();
+                for field in long {
+                    let Some(Value::String(text)) = record.get_mut(&field) else {
+                        continue;
+                    };
+                    let cut = text.char_indices().nth(max_chars).map(|(offset, _)| offset).unwrap_or(text.len());
+                    let body = std::mem::take(text);
+                    *text = body[..cut].to_string();
+
+                    let mut name = format!("{}.{}", record_name, file_safe(&field));
+                    let mut suffix = 1;
+                    while !names.insert(name.clone()) {
+                        suffix += 1;
+                        name = format!("{}.{}_{}", record_name, file_safe(&field), suffix);
+                    }
+                    let relative = Path::new(overflow_dir).join(format!("{}.txt", name));
+                    let path = delivery.output_path(&relative).to_string_lossy().to_string();
+                    record.insert(format!("{}{}", field, OVERFLOW_PATH_SUFFIX), Value::String(path));
+                    record.insert(format!("{}{}", field, OVERFLOW_HASH_SUFFIX), Value::String(hash_input(body.as_bytes())));
+                    files.push(OverflowFile { path: relative.to_string_lossy().to_string(), body });
+                }
+            }
+        }
+        files
+    }
+}
+
+// Record IDs carry namespaces and colons; side file names keep to characters every filesystem takes
+fn file_safe(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' }).collect()
+}
+
+// The side file directory of an output: <stem>_overflow next to it
+pub fn overflow_dir_for(output_path: &Path) -> String {
+    let stem = output_path.file_stem().and_then(|s| s.to_str()).unwrap_or("document");
+    format!("{}_overflow", stem)
+}
+
+// Writes the side files of one output, encrypted to the delivery recipients like the output
+pub fn write_overflow(output_dir: &Path, files: &[OverflowFile], delivery: &DeliveryConfig) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut written = Vec::new();
+    for file in files {
+        let path = output_dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        written.push(delivery.write_bytes(&path, file.body.as_bytes())?);
+    }
+    Ok(written)
+}
@@ -0,0 +1,240 @@
+use parquet::basic::{ConvertedType, LogicalType};
+use parquet::file::metadata::{ColumnChunkMetaData, RowGroupMetaData};
+use parquet::file::reader::FileReader;
+use parquet::file::serialized_reader::{ReadOptionsBuilder, SerializedFileReader};
+use parquet::file::statistics::Statistics;
+use parquet::record::reader::RowIter;
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+
+use crate::schema::reader::{ReadRecord, RecordFilter, COLLECTION_FILTER, LINES_COLLECTION};
+
+// Whether a column chunk can hold a row whose field reads as `value`. Only column statistics
+// of plain strings and integers are trusted; anything else might match
+fn may_hold(column: &ColumnChunkMetaData, value: &str) -> bool {
+    let descr = column.column_descr();
+    let plain_string = matches!(descr.logical_type_ref(), Some(LogicalType::String)) || descr.converted_type() == ConvertedType::UTF8;
+    let plain_integer = descr.logical_type_ref().is_none() && descr.converted_type() == ConvertedType::NONE;
+    // Nulls are never in the statistics, and read as "null"
+    if value == "null" {
+        return true;
+    }
+    match column.statistics() {
+        Some(Statistics::ByteArray(stats)) if plain_string => match (stats.min_opt(), stats.max_opt()) {
+            (Some(min), Some(max)) => match (min.as_utf8(), max.as_utf8()) {
+                (Ok(min), Ok(max)) => min <= value && value <= max,
+                _ => true,
+            },
+            _ => true,
+        },
+        // A field that is an integer reads as its decimal digits, which no other text is
+        Some(Statistics::Int32(stats)) if plain_integer => value.parse::<i32>().is_ok_and(|value| {
+            stats.min_opt().is_none_or(|min| *min <= value) && stats.max_opt().is_none_or(|max| value <= *max)
+        }),
+        Some(Statistics::Int64(stats)) if plain_integer => value.parse::<i64>().is_ok_and(|value| {
+            stats.min_opt().is_none_or(|min| *min <= value) && stats.max_opt().is_none_or(|max| value <= *max)
+        }),
+        _ => true,
+    }
+}
+
+// Whether a row group can hold a row the filter selects: a field the table does not have at
+// all never matches, and a top-level column's statistics can rule its value out
+fn group_may_match(group: &RowGroupMetaData, collection: Option<&str>, fields: &[(String, String)]) -> bool {
+    let has_field = |key: &str| group.columns().iter().any(|column| column.column_path().parts().first().is_some_and(|part| part == key));
+    let column = |key: &str| group.columns().iter().find(|column| matches!(column.column_path().parts(), [part] if part == key));
+    if let Some(collection) = collection {
+        let possible = match column(COLLECTION_FILTER) {
+            Some(column) => may_hold(column, collection),
+            None => has_field(COLLECTION_FILTER) || collection == LINES_COLLECTION,
+        };
+        if !possible {
+            return false;
+        }
+    }
+    fields.iter().all(|(key, value)| match column(key) {
+        Some(column) => may_hold(column, value),
+        None => has_field(key),
+    })
+}
+
+// Reads the rows of a Parquet table as records, one at a time. Row groups the filter rules
+// out by their statistics are never decoded. Cursors are "group:rows", rows read of a row group
+pub struct ParquetReader {
+    rows: RowIter<'static>,
+    filter: RecordFilter,
+    // Row groups still to read, with their row counts; the first is being read
+    groups: VecDeque<(usize, i64)>,
+    taken: i64,
+}
+
+impl ParquetReader {
+    pub fn open(path: &str, filter: RecordFilter, cursor: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let (group, skip) = match cursor {
+            Some(cursor) => {
+                let invalid = || format!("Invalid cursor {:?}", cursor);
+                let (group, rows) = cursor.split_once(':').ok_or_else(invalid)?;
+                (group.parse::<usize>().map_err(|_| invalid())?, rows.parse::<i64>().map_err(|_| invalid())?)
+            }
+            None => (0, 0),
+        };
+        let open = || -> Result<File, String> { File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e)) };
+        let unreadable = |e: parquet::errors::ParquetError| format!("Cannot read {} as Parquet: {}", path, e);
+        let footer = SerializedFileReader::new(open()?).map_err(unreadable)?;
+        let groups: VecDeque<(usize, i64)> = footer.metadata().row_groups().iter().enumerate()
+            .filter(|(index, metadata)| *index >= group && group_may_match(metadata, filter.collection(), filter.fields()))
+            .map(|(index, metadata)| (index, metadata.num_rows()))
+            .collect();
+        let kept: HashSet<usize> = groups.iter().map(|(index, _)| *index).collect();
+        let options = ReadOptionsBuilder::new().with_predicate(Box::new(move |_, index| kept.contains(&index))).build();
+        let reader = SerializedFileReader::new_with_options(open()?, options).map_err(unreadable)?;
+        let mut reader = Self { rows: RowIter::from_file_into(Box::new(reader)), filter, groups, taken: 0 };
+        // Resuming inside the row group the cursor names, unless it no longer matches
+        if reader.groups.front().is_some_and(|(index, _)| *index == group) {
+            for _ in 0..skip {
+                if reader.next_row().is_none() {
+                    break;
+                }
+            }
+        }
+        Ok(reader)
+    }
+
+    // The next row of the kept row groups, whatever the filter says of it
+    fn next_row(&mut self) -> Option<Result<parquet::record::Row, String>> {
+        while self.groups.front().is_some_and(|(_, rows)| self.taken >= *rows) {
+            self.groups.pop_front();
+            self.taken = 0;
+        }
+        self.groups.front()?;
+        let row = self.rows.next()?;
+        self.taken += 1;
+        Some(row.map_err(|e| e.to_string()))
+    }
+
+    // Where the next record is read from, or None once every row is read
+    pub fn cursor(&self) -> Option<String> {
+        let mut groups = self.groups.iter();
+        let (index, rows) = groups.next()?;
+        if self.taken < *rows {
+            return Some(format!("{}:{}", index, self.taken));
+        }
+        groups.next().map(|(index, _)| format!("{}:0", index))
+    }
+}
+
+impl Iterator for ParquetReader {
+    type Item = Result<ReadRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let row = match self.next_row()? {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            };
+            let Value::Object(fields) = row.to_json_value() else {
+                continue;
+            };
+            let collection = fields.get(COLLECTION_FILTER).and_then(Value::as_str).unwrap_or(LINES_COLLECTION).to_string();
+            if self.filter.selects(&collection) && self.filter.matches(&fields) {
+                return Some(Ok(ReadRecord { collection, fields }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::reader::RecordSource;
+    use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    const SCHEMA: &str = "
+        message records {
+            REQUIRED BYTE_ARRAY collection (UTF8);
+            REQUIRED BYTE_ARRAY module_id (UTF8);
+            REQUIRED INT64 page;
+        }
+    ";
+
+    // One row group per entry: (collection, module_id, page) rows
+    fn write_table(path: &std::path::Path, groups: &[&[(&str, &str, i64)]]) {
+        let schema = Arc::new(parse_message_type(SCHEMA).unwrap());
+        let mut writer = SerializedFileWriter::new(File::create(path).unwrap(), schema, Default::default()).unwrap();
+        for rows in groups {
+            let mut group = writer.next_row_group().unwrap();
+            let collections = rows.iter().map(|row| ByteArray::from(row.0)).collect::<Vec<_>>();
+            let modules = rows.iter().map(|row| ByteArray::from(row.1)).collect::<Vec<_>>();
+            for values in [collections, modules] {
+                let mut column = group.next_column().unwrap().unwrap();
+                column.typed::<ByteArrayType>().write_batch(&values, None, None).unwrap();
+                column.close().unwrap();
+            }
+            let mut column = group.next_column().unwrap().unwrap();
+            let pages: Vec<i64> = rows.iter().map(|row| row.2).collect();
+            column.typed::<Int64Type>().write_batch(&pages, None, None).unwrap();
+            column.close().unwrap();
+            group.close().unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    fn filter(pairs: &[(&str, &str)]) -> RecordFilter {
+        RecordFilter::new(&pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<HashMap<_, _>>())
+    }
+
+    fn kept(path: &str, pairs: &[(&str, &str)]) -> Vec<usize> {
+        ParquetReader::open(path, filter(pairs), None).unwrap().groups.iter().map(|(index, _)| *index).collect()
+    }
+
+    #[test]
+    fn reads_rows_with_filters_checked_against_row_groups() {
+        let dir = std::env::temp_dir().join(format!("spp-parquet-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let table = dir.join("records.parquet");
+        write_table(&table, &[
+            &[("modules", "m1", 1), ("modules", "m2", 2)],
+            &[("steps", "m1", 3), ("steps", "m1", 4)],
+            &[("steps", "m2", 5), ("steps", "m2", 6)],
+        ]);
+        let path = table.to_str().unwrap();
+
+        let all: Vec<ReadRecord> = ParquetReader::open(path, RecordFilter::default(), None).unwrap().map(Result::unwrap).collect();
+        assert_eq!(all.len(), 6);
+        assert_eq!(all[0].collection, "modules");
+        assert_eq!(all[2].get_info()["page"], "3");
+
+        assert_eq!(kept(path, &[]), [0, 1, 2]);
+        assert_eq!(kept(path, &[("collection", "steps")]), [1, 2]);
+        assert_eq!(kept(path, &[("collection", "steps"), ("module_id", "m2")]), [2]);
+        assert_eq!(kept(path, &[("page", "4")]), [1]);
+        assert_eq!(kept(path, &[("page", "four")]), Vec::<usize>::new());
+        assert_eq!(kept(path, &[("task_id", "t1")]), Vec::<usize>::new());
+        // Statistics only bound a row group's values: m15 sorts between m1 and m2, so the first
+        // group is read, and has no such row
+        assert_eq!(kept(path, &[("module_id", "m15")]), [0]);
+        assert_eq!(ParquetReader::open(path, filter(&[("module_id", "m15")]), None).unwrap().count(), 0);
+
+        // Paging one record at a time, through read_records' reader and its cursors
+        let steps = [("collection", "steps"), ("module_id", "m2")];
+        let mut source = RecordSource::open(path, filter(&steps), None).unwrap();
+        assert_eq!(source.cursor().as_deref(), Some("2:0"));
+        assert_eq!(source.next().unwrap().unwrap().get_info()["page"], "5");
+        let cursor = source.cursor().unwrap();
+        assert_eq!(cursor, "2:1");
+        let mut resumed = RecordSource::open(path, filter(&steps), Some(&cursor)).unwrap();
+        assert_eq!(resumed.next().unwrap().unwrap().get_info()["page"], "6");
+        assert!(resumed.next().is_none());
+        assert_eq!(resumed.cursor(), None);
+
+        let mut from_cursor = RecordSource::open(path, RecordFilter::default(), Some("1:1")).unwrap();
+        assert_eq!(from_cursor.next().unwrap().unwrap().get_info()["page"], "4");
+        assert!(ParquetReader::open(path, RecordFilter::default(), Some("1")).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,550 @@
+use pyo3::prelude::*;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::security::seal::SEALED_EXTENSION;
+
+// Filter key naming the collection rather than a record field, as for search
+pub(crate) const COLLECTION_FILTER: &str = "collection";
+// Collection of JSONL records and Parquet rows that do not name their own
+pub(crate) const LINES_COLLECTION: &str = "records";
+const PARQUET_MAGIC: &[u8] = b"PAR1";
+
+// Filters pushed down into the reader: collections that are not selected are skipped byte by
+// byte without being parsed, and only one record of a selected collection is held at a time
+#[derive(Debug, Clone, Default)]
+pub struct RecordFilter {
+    collection: Option<String>,
+    fields: Vec<(String, String)>,
+}
+
+// Record fields as filters and get_info compare them - strings as they are, other values as JSON
+fn field_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl RecordFilter {
+    // Exact matches on `collection` and on any record field, e.g. {"module_id": "module-3f2a"}
+    pub fn new(filters: &HashMap<String, String>) -> Self {
+        let mut fields: Vec<(String, String)> = filters.iter()
+            .filter(|(key, _)| key.as_str() != COLLECTION_FILTER)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        fields.sort();
+        Self { collection: filters.get(COLLECTION_FILTER).cloned(), fields }
+    }
+
+    pub fn collection(&self) -> Option<&str> {
+        self.collection.as_deref()
+    }
+
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    pub fn selects(&self, collection: &str) -> bool {
+        self.collection.as_deref().is_none_or(|selected| selected == collection)
+    }
+
+    pub fn matches(&self, record: &Map<String, Value>) -> bool {
+        self.fields.iter().all(|(key, value)| record.get(key).is_some_and(|field| field_text(field) == *value))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadRecord {
+    pub collection: String,
+    pub fields: Map<String, Value>,
+}
+
+impl ReadRecord {
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info: HashMap<String, String> = self.fields.iter().map(|(key, value)| (key.clone(), field_text(value))).collect();
+        info.insert(COLLECTION_FILTER.to_string(), self.collection.clone());
+        info
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    // One output document: collections under "payload", or at the top of pre-2.0 outputs
+    Output,
+    // One JSON object per line: a record, or a whole output
+    Lines,
+}
+
+// Where the scan is inside an output document
+#[derive(Debug, Clone, PartialEq)]
+enum Frame {
+    // The document, or its payload
+    Object { payload: bool },
+    Collection(String),
+}
+
+// Where scan_value is inside the value it scans
+#[derive(Default)]
+struct ValueScan {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    started: bool,
+}
+
+impl ValueScan {
+    // Some(true) when `byte` closes the value, Some(false) when it is the delimiter after a
+    // scalar and so not part of it
+    fn step(&mut self, byte: u8) -> Result<Option<bool>, ()> {
+        if self.started && self.depth == 0 && !self.in_string && (matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace()) {
+            return Ok(Some(false));
+        }
+        self.started = true;
+        if self.in_string {
+            match byte {
+                _ if self.escaped => self.escaped = false,
+                b'\\' => self.escaped = true,
+                b'"' => {
+                    self.in_string = false;
+                    return Ok((self.depth == 0).then_some(true));
+                }
+                _ => {}
+            }
+            return Ok(None);
+        }
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' => {
+                self.depth = self.depth.checked_sub(1).ok_or(())?;
+                return Ok((self.depth == 0).then_some(true));
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+}
+
+// Reads records one at a time from an output or JSONL file of any size. The position between
+// records is a cursor a later reader can resume from, so callers can page through a file
+// without holding more than a page of it
+pub struct OutputReader<R: BufRead> {
+    input: R,
+    format: Format,
+    filter: RecordFilter,
+    position: u64,
+    stack: Vec<Frame>,
+    started: bool,
+    // Records of a JSONL line holding a whole output, and how many of them were read
+    pending: VecDeque<ReadRecord>,
+    line_start: u64,
+    taken: usize,
+    // Records of the first line to skip, when resuming inside it
+    skip: usize,
+    // Set by the first error, after which nothing more is read
+    failed: bool,
+}
+
+impl OutputReader<BufReader<File>> {
+    pub fn open(path: &str, filter: RecordFilter, cursor: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let extension = Path::new(path).extension().and_then(|extension| extension.to_str()).unwrap_or_default().to_ascii_lowercase();
+        if extension == SEALED_EXTENSION {
+            return Err(format!("{} is encrypted to the profile's delivery recipients; decrypt it with age first", path).into());
+        }
+        let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+        let format = if matches!(extension.as_str(), "jsonl" | "ndjson") { Format::Lines } else { Format::Output };
+        let mut reader = Self::new(BufReader::new(file), format, filter);
+        match cursor {
+            Some(cursor) => reader.resume(cursor)?,
+            None => reader.input.seek(SeekFrom::Start(0))?,
+        };
+        Ok(reader)
+    }
+}
+
+// What read_records pages through: an output or JSONL file, or a Parquet table
+pub enum RecordSource {
+    Json(OutputReader<BufReader<File>>),
+    #[cfg(feature = "parquet")]
+    Parquet(crate::schema::parquet::ParquetReader),
+}
+
+impl RecordSource {
+    pub fn open(path: &str, filter: RecordFilter, cursor: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let extension = Path::new(path).extension().and_then(|extension| extension.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let mut magic = [0u8; 4];
+        let read = File::open(path).and_then(|mut file| std::io::Read::read(&mut file, &mut magic)).unwrap_or(0);
+        if extension != SEALED_EXTENSION && (extension == "parquet" || magic[..read] == *PARQUET_MAGIC) {
+            #[cfg(feature = "parquet")]
+            return Ok(Self::Parquet(crate::schema::parquet::ParquetReader::open(path, filter, cursor)?));
+            #[cfg(not(feature = "parquet"))]
+            return Err(format!("{} is a Parquet file; reading it needs a build with the parquet feature", path).into());
+        }
+        Ok(Self::Json(OutputReader::open(path, filter, cursor)?))
+    }
+
+    pub fn cursor(&self) -> Option<String> {
+        match self {
+            Self::Json(reader) => reader.cursor(),
+            #[cfg(feature = "parquet")]
+            Self::Parquet(reader) => reader.cursor(),
+        }
+    }
+}
+
+impl Iterator for RecordSource {
+    type Item = Result<ReadRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Json(reader) => reader.next(),
+            #[cfg(feature = "parquet")]
+            Self::Parquet(reader) => reader.next(),
+        }
+    }
+}
+
+impl<R: BufRead + Seek> OutputReader<R> {
+    // Cursors are "offset:taken" for JSONL, and "offset:payload:collection" or
+    // "offset:top:collection" inside an output's collection
+    fn resume(&mut self, cursor: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        let invalid = || format!("Invalid cursor {:?}", cursor);
+        let mut parts = cursor.splitn(3, ':');
+        let offset: u64 = parts.next().and_then(|offset| offset.parse().ok()).ok_or_else(invalid)?;
+        if self.format == Format::Lines {
+            self.skip = parts.next().and_then(|taken| taken.parse().ok()).ok_or_else(invalid)?;
+        } else {
+            let payload = match parts.next() {
+                Some("payload") => true,
+                Some("top") => false,
+                _ => return Err(invalid().into()),
+            };
+            let collection = parts.next().ok_or_else(invalid)?;
+            self.stack = vec![Frame::Object { payload: false }];
+            if payload {
+                self.stack.push(Frame::Object { payload: true });
+            }
+            self.stack.push(Frame::Collection(collection.to_string()));
+        }
+        self.started = true;
+        self.position = offset;
+        Ok(self.input.seek(SeekFrom::Start(offset))?)
+    }
+}
+
+impl<R: BufRead> OutputReader<R> {
+    fn new(input: R, format: Format, filter: RecordFilter) -> Self {
+        Self { input, format, filter, position: 0, stack: Vec::new(), started: false, pending: VecDeque::new(), line_start: 0, taken: 0, skip: 0, failed: false }
+    }
+
+    // Where the next record is read from, or None once an output's collections are read
+    pub fn cursor(&self) -> Option<String> {
+        match (self.format, self.stack.last()) {
+            (Format::Lines, _) if self.pending.is_empty() => Some(format!("{}:0", self.position)),
+            (Format::Lines, _) => Some(format!("{}:{}", self.line_start, self.taken)),
+            (Format::Output, Some(Frame::Collection(collection))) => {
+                let payload = self.stack.contains(&Frame::Object { payload: true });
+                Some(format!("{}:{}:{}", self.position, if payload { "payload" } else { "top" }, collection))
+            }
+            _ => None,
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, String> {
+        let buffer = self.input.fill_buf().map_err(|e| e.to_string())?;
+        Ok(buffer.first().copied())
+    }
+
+    fn advance(&mut self) {
+        self.input.consume(1);
+        self.position += 1;
+    }
+
+    fn skip_whitespace(&mut self) -> Result<Option<u8>, String> {
+        while let Some(byte) = self.peek()? {
+            if !byte.is_ascii_whitespace() {
+                return Ok(Some(byte));
+            }
+            self.advance();
+        }
+        Ok(None)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        match self.skip_whitespace()? {
+            Some(byte) if byte == expected => {
+                self.advance();
+                Ok(())
+            }
+            found => Err(format!("Expected '{}' at byte {}, found {}", expected as char, self.position,
+                found.map(|byte| format!("'{}'", byte as char)).unwrap_or_else(|| "the end of the file".to_string()))),
+        }
+    }
+
+    // One JSON value, its bytes kept only when `keep` is given - skipped values of any size
+    // cost no memory. The input is scanned a buffer at a time
+    fn scan_value(&mut self, mut keep: Option<&mut Vec<u8>>) -> Result<(), String> {
+        let mut scan = ValueScan::default();
+        loop {
+            let buffer = self.input.fill_buf().map_err(|e| e.to_string())?;
+            if buffer.is_empty() {
+                // A scalar may end the file; nothing else may
+                if scan.started && scan.depth == 0 && !scan.in_string {
+                    return Ok(());
+                }
+                return Err(format!("Output ends early at byte {}", self.position));
+            }
+            let mut end = None;
+            for (index, &byte) in buffer.iter().enumerate() {
+                match scan.step(byte) {
+                    Ok(None) => {}
+                    Ok(Some(taken)) => {
+                        end = Some(index + taken as usize);
+                        break;
+                    }
+                    Err(()) => return Err(format!("Unbalanced '{}' at byte {}", byte as char, self.position + index as u64)),
+                }
+            }
+            let taken = end.unwrap_or(buffer.len());
+            if let Some(kept) = keep.as_deref_mut() {
+                kept.extend_from_slice(&buffer[..taken]);
+            }
+            self.input.consume(taken);
+            self.position += taken as u64;
+            if end.is_some() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read_key(&mut self) -> Result<String, String> {
+        let mut bytes = Vec::new();
+        self.scan_value(Some(&mut bytes))?;
+        let key = serde_json::from_slice::<String>(&bytes).map_err(|e| format!("Invalid key before byte {}: {}", self.position, e))?;
+        self.expect(b':')?;
+        Ok(key)
+    }
+
+    fn next_output_record(&mut self) -> Result<Option<ReadRecord>, String> {
+        if !self.started {
+            self.started = true;
+            self.expect(b'{')?;
+            self.stack.push(Frame::Object { payload: false });
+        }
+        while let Some(frame) = self.stack.last().cloned() {
+            let byte = self.skip_whitespace()?.ok_or_else(|| format!("Output ends early at byte {}", self.position))?;
+            match (frame, byte) {
+                (_, b',') => self.advance(),
+                (Frame::Object { .. }, b'}') | (Frame::Collection(_), b']') => {
+                    self.advance();
+                    self.stack.pop();
+                }
+                (Frame::Object { payload }, _) => {
+                    let key = self.read_key()?;
+                    match self.skip_whitespace()? {
+                        Some(b'{') if key == "payload" && !payload => {
+                            self.advance();
+                            self.stack.push(Frame::Object { payload: true });
+                        }
+                        Some(b'[') if self.filter.selects(&key) => {
+                            self.advance();
+                            self.stack.push(Frame::Collection(key));
+                        }
+                        _ => self.scan_value(None)?,
+                    }
+                }
+                (Frame::Collection(collection), _) => {
+                    let start = self.position;
+                    let mut bytes = Vec::new();
+                    self.scan_value(Some(&mut bytes))?;
+                    let record: Value = serde_json::from_slice(&bytes).map_err(|e| format!("Invalid record at byte {}: {}", start, e))?;
+                    if let Value::Object(fields) = record {
+                        if self.filter.matches(&fields) {
+                            return Ok(Some(ReadRecord { collection, fields }));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // A record, or every record of an output written on one line
+    fn line_records(&self, line: &str, start: u64) -> Result<Vec<ReadRecord>, String> {
+        let fields = match serde_json::from_str::<Value>(line) {
+            Ok(Value::Object(fields)) => fields,
+            Ok(_) => return Err(format!("Line at byte {} is not a JSON object", start)),
+            Err(e) => return Err(format!("Invalid JSON line at byte {}: {}", start, e)),
+        };
+        if fields.contains_key("payload") || fields.contains_key("schema_version") {
+            let payload = fields.get("payload").and_then(Value::as_object).unwrap_or(&fields);
+            return Ok(payload.iter()
+                .filter(|(collection, _)| self.filter.selects(collection))
+                .filter_map(|(collection, records)| records.as_array().map(|records| (collection, records)))
+                .flat_map(|(collection, records)| records.iter().filter_map(Value::as_object).map(move |fields| (collection, fields)))
+                .filter(|(_, fields)| self.filter.matches(fields))
+                .map(|(collection, fields)| ReadRecord { collection: collection.clone(), fields: fields.clone() })
+                .collect());
+        }
+        let collection = fields.get(COLLECTION_FILTER).and_then(Value::as_str).unwrap_or(LINES_COLLECTION).to_string();
+        if !self.filter.selects(&collection) || !self.filter.matches(&fields) {
+            return Ok(Vec::new());
+        }
+        Ok(vec![ReadRecord { collection, fields }])
+    }
+
+    fn next_line_record(&mut self) -> Result<Option<ReadRecord>, String> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                self.taken += 1;
+                return Ok(Some(record));
+            }
+            let start = self.position;
+            let mut line = String::new();
+            let read = self.input.read_line(&mut line).map_err(|e| e.to_string())?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.position += read as u64;
+            if !line.trim().is_empty() {
+                self.pending = self.line_records(line.trim(), start)?.into();
+            }
+            // A cursor inside the line skips the records already read
+            self.taken = std::mem::take(&mut self.skip).min(self.pending.len());
+            self.pending.drain(..self.taken);
+            self.line_start = start;
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for OutputReader<R> {
+    type Item = Result<ReadRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = match self.format {
+            Format::Output => self.next_output_record(),
+            Format::Lines => self.next_line_record(),
+        };
+        self.failed = next.is_err();
+        next.transpose()
+    }
+}
+
+type RecordPage = (Vec<HashMap<String, String>>, Option<String>);
+
+// Python binding - up to `limit` records of an output, JSONL or Parquet file, and the cursor to pass back
+// for the next page (None after the last record)
+#[pyfunction]
+#[pyo3(signature = (path, filters=None, limit=1000, cursor=None))]
+pub fn read_records(py: Python, path: &str, filters: Option<HashMap<String, String>>, limit: usize, cursor: Option<&str>) -> PyResult<RecordPage> {
+    if limit == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("limit must be at least 1"));
+    }
+    let filter = RecordFilter::new(&filters.unwrap_or_default());
+    py.allow_threads(|| {
+        let mut reader = RecordSource::open(path, filter, cursor).map_err(|e| e.to_string())?;
+        let mut records = Vec::new();
+        while records.len() < limit {
+            match reader.next() {
+                Some(record) => records.push(record?.get_info()),
+                None => return Ok((records, None)),
+            }
+        }
+        Ok((records, reader.cursor()))
+    })
+    .map_err(|e: String| {
+        if e.starts_with("Invalid cursor") {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(e)
+        } else {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Three-byte buffers, so every value and key crosses buffer boundaries
+    fn reader(document: &str, format: Format, cursor: Option<&str>) -> OutputReader<BufReader<Cursor<Vec<u8>>>> {
+        let mut reader = OutputReader::new(BufReader::with_capacity(3, Cursor::new(document.as_bytes().to_vec())), format, RecordFilter::default());
+        if let Some(cursor) = cursor {
+            reader.resume(cursor).unwrap();
+        }
+        reader
+    }
+
+    fn ids(records: impl Iterator<Item = Result<ReadRecord, String>>) -> Vec<String> {
+        records.map(|record| {
+            let record = record.unwrap();
+            format!("{}/{}", record.collection, record.fields["id"].as_str().unwrap())
+        }).collect()
+    }
+
+    const OUTPUT: &str = r#"{"schema_version": "2.0", "provenance": {"tools": [["pdftotext", "[1]"], {"}": "]"}]},
+        "payload": {
+            "modules": [{"id": "m1", "title": "Remove the \"[A]\" panel {x}", "ranges": [[1, 2], [3, [4, 5]]]}, {"id": "m2", "n": -1.5e3}],
+            "notes": "not a collection ]",
+            "steps": [{"id": "s1", "text": "Torque to 25 N.m \\"}, {"id": "s2", "tags": []}]
+        }}"#;
+
+    #[test]
+    fn scans_nested_values_and_brackets_inside_strings() {
+        assert_eq!(ids(reader(OUTPUT, Format::Output, None)), ["modules/m1", "modules/m2", "steps/s1", "steps/s2"]);
+        let first = reader(OUTPUT, Format::Output, None).next().unwrap().unwrap();
+        assert_eq!(first.fields["title"], "Remove the \"[A]\" panel {x}");
+        assert_eq!(first.fields["ranges"], serde_json::json!([[1, 2], [3, [4, 5]]]));
+    }
+
+    #[test]
+    fn resumes_from_every_cursor() {
+        let all = ids(reader(OUTPUT, Format::Output, None));
+        let mut scan = reader(OUTPUT, Format::Output, None);
+        for read in 1..=all.len() {
+            scan.next().unwrap().unwrap();
+            match scan.cursor() {
+                Some(cursor) => assert_eq!(ids(reader(OUTPUT, Format::Output, Some(&cursor))), all[read..], "{}", cursor),
+                None => assert_eq!(read, all.len()),
+            }
+        }
+        assert!(scan.next().is_none());
+        assert_eq!(scan.cursor(), None);
+
+        // A JSONL cursor can point inside a line holding a whole output
+        let lines = format!("{{\"id\": \"r1\"}}\n{}\n{{\"id\": \"r2\", \"collection\": \"steps\"}}\n", OUTPUT.replace('\n', " "));
+        let all = ids(reader(&lines, Format::Lines, None));
+        assert_eq!(all, ["records/r1", "modules/m1", "modules/m2", "steps/s1", "steps/s2", "steps/r2"]);
+        let mut scan = reader(&lines, Format::Lines, None);
+        for read in 1..=all.len() {
+            scan.next().unwrap().unwrap();
+            let cursor = scan.cursor().unwrap();
+            assert_eq!(ids(reader(&lines, Format::Lines, Some(&cursor))), all[read..], "{}", cursor);
+        }
+    }
+
+    #[test]
+    fn reports_truncated_files() {
+        // Every cut ends in an error, after the records that were whole
+        for cut in 0..OUTPUT.len() {
+            let results: Vec<_> = reader(&OUTPUT[..cut], Format::Output, None).collect();
+            let last = results.last().unwrap_or_else(|| panic!("cut at {} read nothing", cut));
+            assert!(last.is_err(), "cut at {} ended without an error", cut);
+            assert!(results[..results.len() - 1].iter().all(Result::is_ok), "cut at {}", cut);
+        }
+        let error = reader("{\"payload\": {\"modules\": [{\"id\": \"m1\"}, {\"id\"", Format::Output, None).nth(1).unwrap().unwrap_err();
+        assert_eq!(error, "Output ends early at byte 44");
+
+        let lines = "{\"id\": \"r1\"}\n{\"id\": \"r";
+        let results: Vec<_> = reader(lines, Format::Lines, None).collect();
+        assert_eq!(results[0].as_ref().unwrap().fields["id"], "r1");
+        assert!(results[1].as_ref().unwrap_err().starts_with("Invalid JSON line at byte 13"));
+    }
+}
@@ -0,0 +1,250 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, Value as _, STORED, STRING, TEXT};
+use tantivy::{Index, IndexWriter, TantivyDocument, Term};
+
+use crate::engine::access::ACCESS_TAGS_FIELD;
+use crate::engine::language::LANGUAGE_FIELD;
+use crate::engine::entities::record_extents;
+use crate::schema::envelope::payload_of;
+
+// Writer heap shared by all indexing threads - tantivy's own recommended minimum is 15MB
+const WRITER_HEAP_BYTES: usize = 50_000_000;
+
+// Source text indexed per step - a step runs to the next step's match
+const MAX_STEP_TEXT_BYTES: usize = 2000;
+
+// Exact-match fields usable as search filters. access_tag matches any one of a record's tags
+pub const FILTER_FIELDS: &[&str] = &["collection", "source", "job_id", "entity_type", "kind", "access_tag"];
+
+// Bookkeeping fields left out of the searchable text
+const UNSEARCHED_FIELDS: &[&str] = &["record_id", "confidence", "start", "end", "page", "source_page", "mentions", "edit_distance", "cached", ACCESS_TAGS_FIELD, LANGUAGE_FIELD];
+
+struct IndexFields {
+    // source + record_id, so re-indexing a document replaces its records
+    doc_key: Field,
+    record_id: Field,
+    filters: Vec<(&'static str, Field)>,
+    text: Field,
+    record: Field,
+}
+
+fn build_schema() -> (Schema, IndexFields) {
+    let mut builder = Schema::builder();
+    let doc_key = builder.add_text_field("doc_key", STRING);
+    let record_id = builder.add_text_field("record_id", STRING | STORED);
+    let filters = FILTER_FIELDS.iter().map(|name| (*name, builder.add_text_field(name, STRING | STORED))).collect();
+    let text = builder.add_text_field("text", TEXT);
+    let record = builder.add_text_field("record", STORED);
+    (builder.build(), IndexFields { doc_key, record_id, filters, text, record })
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(as_text).collect::<Vec<_>>().join(","),
+        other => other.to_string(),
+    }
+}
+
+// Words a user would search for: pattern, text, value, matched text, LLM output and the like
+fn searchable_text(record: &serde_json::Map<String, Value>) -> String {
+    record.iter()
+        .filter(|(key, _)| {
+            !UNSEARCHED_FIELDS.contains(&String::as_str(key))
+                && !key.starts_with("original_")
+                && !key.ends_with("_id")
+                && !key.ends_with("_ids")
+        })
+        .map(|(_, value)| as_text(value))
+        .filter(|value| !value.is_empty() && value.parse::<f64>().is_err())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn char_floor(text: &str, mut offset: usize) -> usize {
+    offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+// Source text each step covers, keyed by record_id
+fn step_texts<'a>(payload: &Value, text: &'a str) -> HashMap<String, &'a str> {
+    let steps: Vec<HashMap<String, String>> = payload.get("steps").and_then(Value::as_array).into_iter().flatten()
+        .filter_map(Value::as_object)
+        .map(|record| record.iter().map(|(key, value)| (key.clone(), as_text(value))).collect())
+        .collect();
+    record_extents(&steps, text.len()).into_iter()
+        .filter(|(start, _, _)| *start < text.len())
+        .map(|(start, end, record_id)| {
+            let start = char_floor(text, start);
+            let end = char_floor(text, end.min(start + MAX_STEP_TEXT_BYTES));
+            (record_id, &text[start..end])
+        })
+        .collect()
+}
+
+// Local full-text index of extracted records, stored in one directory
+pub struct RecordIndex {
+    index: Index,
+    fields: IndexFields,
+    // Tantivy allows one writer per index, so workers sharing this index take turns
+    write_lock: Mutex<()>,
+}
+
+impl RecordIndex {
+    pub fn open(index_dir: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(index_dir)?;
+        let (schema, fields) = build_schema();
+        let directory = MmapDirectory::open(index_dir)
+            .map_err(|e| format!("Cannot open search index {}: {}", index_dir, e))?;
+        let index = Index::open_or_create(directory, schema)
+            .map_err(|e| format!("Cannot open search index {}: {}", index_dir, e))?;
+        Ok(Self { index, fields, write_lock: Mutex::new(()) })
+    }
+
+    fn filter_field(&self, name: &str) -> Option<Field> {
+        self.fields.filters.iter().find(|(filter, _)| *filter == name).map(|(_, field)| *field)
+    }
+
+    // Replaces every record previously indexed for this output's source. With the source
+    // text, steps are also searchable by the text they cover, not only by their pattern
+    pub fn index_output(&self, output: &Value, text: Option<&str>, job_id: Option<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+        let payload = payload_of(output);
+        let step_texts = text.map(|text| step_texts(payload, text)).unwrap_or_default();
+        let source = payload.get("source").map(as_text).unwrap_or_default();
+        let job_id = job_id.map(str::to_string)
+            .or_else(|| payload.get("job_id").and_then(Value::as_str).map(str::to_string))
+            .unwrap_or_default();
+
+        let _write_guard = self.write_lock.lock().map_err(|_| "Search index writer unavailable")?;
+        let mut writer: IndexWriter = self.index.writer(WRITER_HEAP_BYTES)?;
+        if let Some(source_field) = self.filter_field("source") {
+            writer.delete_term(Term::from_field_text(source_field, &source));
+        }
+
+        let mut indexed = 0;
+        for (collection, value) in payload.as_object().into_iter().flatten() {
+            for record in value.as_array().into_iter().flatten().filter_map(Value::as_object) {
+                let record_id = match record.get("record_id").and_then(Value::as_str) {
+                    Some(record_id) => record_id,
+                    None => continue,
+                };
+                let mut document = TantivyDocument::default();
+                document.add_text(self.fields.doc_key, format!("{}\x1f{}", source, record_id));
+                document.add_text(self.fields.record_id, record_id);
+                for (name, field) in &self.fields.filters {
+                    if *name == "access_tag" {
+                        let tags = record.get(ACCESS_TAGS_FIELD).map(as_text).unwrap_or_default();
+                        for tag in tags.split(',').filter(|tag| !tag.is_empty()) {
+                            document.add_text(*field, tag);
+                        }
+                        continue;
+                    }
+                    let value = match *name {
+                        "collection" => collection.clone(),
+                        "source" => source.clone(),
+                        "job_id" => job_id.clone(),
+                        other => record.get(other).map(as_text).unwrap_or_default(),
+                    };
+                    document.add_text(*field, value);
+                }
+                document.add_text(self.fields.text, searchable_text(record));
+                if let Some(step_text) = step_texts.get(record_id).filter(|_| collection == "steps") {
+                    document.add_text(self.fields.text, *step_text);
+                }
+                document.add_text(self.fields.record, Value::Object(record.clone()).to_string());
+                writer.add_document(document)?;
+                indexed += 1;
+            }
+        }
+        writer.commit()?;
+        Ok(indexed)
+    }
+
+    // Uses the output's source file for step text when it is still readable
+    pub fn index_file(&self, path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let output: Value = serde_json::from_str(&content)?;
+        let text = payload_of(&output).get("source")
+            .and_then(Value::as_str)
+            .and_then(|source| std::fs::read_to_string(source).ok());
+        self.index_output(&output, text.as_deref(), None)
+    }
+
+    // Best matches first. Filters are exact matches on FILTER_FIELDS
+    pub fn search(
+        &self,
+        query: &str,
+        filters: &HashMap<String, String>,
+        limit: usize,
+    ) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
+        let parser = QueryParser::for_index(&self.index, vec![self.fields.text]);
+        let parsed = parser.parse_query(query).map_err(|e| format!("Invalid search query: {}", e))?;
+
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, parsed)];
+        for (name, value) in filters {
+            let field = self.filter_field(name).ok_or_else(|| {
+                format!("Unknown search filter: {} (expected one of {})", name, FILTER_FIELDS.join(", "))
+            })?;
+            let term = TermQuery::new(Term::from_field_text(field, value), IndexRecordOption::Basic);
+            clauses.push((Occur::Must, Box::new(term)));
+        }
+
+        let searcher = self.index.reader()?.searcher();
+        let hits = searcher.search(&BooleanQuery::new(clauses), &TopDocs::with_limit(limit.max(1)).order_by_score())?;
+
+        let mut results = Vec::new();
+        for (score, address) in hits {
+            let document: TantivyDocument = searcher.doc(address)?;
+            let text_of = |field: Field| document.get_first(field).and_then(|value| value.as_str()).unwrap_or("").to_string();
+
+            let stored: Value = serde_json::from_str(&text_of(self.fields.record)).unwrap_or(Value::Null);
+            let mut result: HashMap<String, String> = stored.as_object().into_iter().flatten()
+                .map(|(key, value)| (key.clone(), as_text(value)))
+                .collect();
+            result.insert("record_id".to_string(), text_of(self.fields.record_id));
+            // access_tag holds several values; the stored record already carries access_tags
+            for (name, field) in self.fields.filters.iter().filter(|(name, _)| *name != "access_tag") {
+                let value = text_of(*field);
+                if !value.is_empty() {
+                    result.insert(name.to_string(), value);
+                }
+            }
+            result.insert("score".to_string(), format!("{:.4}", score));
+            results.push(result);
+        }
+        Ok(results)
+    }
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+}
+
+// Python bindings - the index directory is created on first use
+#[pyfunction]
+pub fn index_outputs(py: Python, index_dir: &str, paths: Vec<String>) -> PyResult<usize> {
+    let index = RecordIndex::open(index_dir).map_err(to_py_err)?;
+    py.allow_threads(|| {
+        paths.iter().try_fold(0, |total, path| index.index_file(path).map(|count| total + count)).map_err(|e| e.to_string())
+    })
+    .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)
+}
+
+#[pyfunction]
+#[pyo3(signature = (index_dir, query, filters=None, limit=20))]
+pub fn search(py: Python, index_dir: &str, query: &str, filters: Option<HashMap<String, String>>, limit: usize) -> PyResult<Vec<HashMap<String, String>>> {
+    py.allow_threads(|| {
+        let index = RecordIndex::open(index_dir).map_err(to_py_err)?;
+        index.search(query, &filters.unwrap_or_default(), limit)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    })
+}
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::storage::backend::Storage;
+use crate::storage::memory::MemoryStorage;
+
+pub const ACCESS_NAMESPACE: &str = "access_counts";
+
+// What licenses document when they set no limit of their own
+pub const DEFAULT_ACCESS_LIMIT: u64 = 1000;
+
+// License metadata key that overrides the default limit
+pub const ACCESS_LIMIT_KEY: &str = "access_limit";
+
+// Counts start over once a window this long has passed - the length of a session
+pub const ACCESS_WINDOW_SECONDS: i64 = 24 * 60 * 60;
+
+// Counts are written to storage every this many accesses, or after this long, whichever
+// comes first; a crash loses at most that much of the count
+pub const PERSIST_EVERY_ACCESSES: u64 = 100;
+pub const PERSIST_EVERY_SECONDS: i64 = 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCount {
+    count: u64,
+    window_start: DateTime<Utc>,
+}
+
+// Access attempts for one customer, safe to bump from every worker thread at once. The hot
+// path is a handful of atomic operations; only the periodic write to storage takes a lock
+pub struct AccessCounter {
+    key: String,
+    limit: u64,
+    count: AtomicU64,
+    window_start: AtomicI64,
+    unpersisted: AtomicU64,
+    last_persist: AtomicI64,
+    storage: Arc<dyn Storage>,
+    // The last write that failed, surfaced in security info rather than failing the access
+    persist_error: Mutex<Option<String>>,
+}
+
+impl AccessCounter {
+    pub fn new(key: &str, limit: u64) -> Self {
+        let now = Utc::now().timestamp();
+        Self {
+            key: key.to_string(),
+            limit,
+            count: AtomicU64::new(0),
+            window_start: AtomicI64::new(now),
+            unpersisted: AtomicU64::new(0),
+            last_persist: AtomicI64::new(now),
+            storage: Arc::new(MemoryStorage::new()),
+            persist_error: Mutex::new(None),
+        }
+    }
+
+    // Picks up the count an earlier process left in storage, unless its window has passed
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(stored) = storage.get(ACCESS_NAMESPACE, &self.key)? {
+            let stored: StoredCount = serde_json::from_slice(&stored)
+                .map_err(|e| format!("Stored access count {} is unreadable: {}", self.key, e))?;
+            if Utc::now().timestamp() - stored.window_start.timestamp() < ACCESS_WINDOW_SECONDS {
+                self.count = AtomicU64::new(stored.count);
+                self.window_start = AtomicI64::new(stored.window_start.timestamp());
+            }
+        }
+        self.storage = storage;
+        Ok(self)
+    }
+
+    // Counts one attempt and returns whether it is within the limit
+    pub fn record(&self) -> bool {
+        let now = Utc::now().timestamp();
+        self.roll_window(now);
+        let count = self.count.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let unpersisted = self.unpersisted.fetch_add(1, Ordering::SeqCst) + 1;
+        let last_persist = self.last_persist.load(Ordering::SeqCst);
+        let due = unpersisted >= PERSIST_EVERY_ACCESSES || now - last_persist >= PERSIST_EVERY_SECONDS;
+        // Only the thread that claims the slot writes; the rest carry on counting
+        if due && self.last_persist.compare_exchange(last_persist, now, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+            self.flush();
+        }
+
+        count <= self.limit
+    }
+
+    // Exactly one thread wins the reset when a window ends
+    fn roll_window(&self, now: i64) {
+        let window_start = self.window_start.load(Ordering::SeqCst);
+        if now - window_start >= ACCESS_WINDOW_SECONDS
+            && self.window_start.compare_exchange(window_start, now, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+        {
+            self.count.store(0, Ordering::SeqCst);
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.roll_window(Utc::now().timestamp());
+        self.count.load(Ordering::SeqCst)
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.count() >= self.limit
+    }
+
+    pub fn window_start(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.window_start.load(Ordering::SeqCst), 0).unwrap_or_else(Utc::now)
+    }
+
+    // Writes the current count now; also runs when the counter is dropped
+    pub fn flush(&self) {
+        self.unpersisted.store(0, Ordering::SeqCst);
+        let stored = StoredCount {
+            count: self.count.load(Ordering::SeqCst),
+            window_start: self.window_start(),
+        };
+        let result = serde_json::to_vec(&stored)
+            .map_err(|e| e.into())
+            .and_then(|value| self.storage.put(ACCESS_NAMESPACE, &self.key, &value));
+        if let Ok(mut persist_error) = self.persist_error.lock() {
+            *persist_error = result.err().map(|e| e.to_string());
+        }
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let count = self.count();
+        let mut info = HashMap::new();
+        info.insert("access_count".to_string(), count.to_string());
+        info.insert("access_limit".to_string(), self.limit.to_string());
+        info.insert("access_remaining".to_string(), self.limit.saturating_sub(count).to_string());
+        info.insert("access_window_start".to_string(), self.window_start().to_rfc3339());
+        if let Some(error) = self.persist_error.lock().ok().and_then(|error| error.clone()) {
+            info.insert("access_persist_error".to_string(), error);
+        }
+        info
+    }
+}
+
+impl Drop for AccessCounter {
+    fn drop(&mut self) {
+        if self.unpersisted.load(Ordering::SeqCst) > 0 {
+            self.flush();
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use chrono::Utc;
+
+use crate::engine::anonymize::Anonymizer;
+use crate::engine::completeness::DecodedText;
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::pii::{mask, PiiConfig};
+use crate::engine::profile::resolve_profile;
+use crate::jobs::preflight::check_input;
+use crate::jobs::store::{JobStatus, JobStore};
+use crate::licensing::manager::License;
+use crate::schema::envelope::ENGINE_VERSION;
+use crate::security::doctor::{compiled_features, run_doctor, DoctorCheck};
+use crate::security::seal::{DeliveryConfig, SealWriter};
+use crate::security::session::{documents_processed, process_start, SessionInfo};
+use crate::storage::atomic::AtomicFile;
+
+// Lines kept from the end of each log - the part around the failure
+const MAX_LOG_LINES: usize = 2000;
+
+// Unbroken runs of base64 or hex this long are keys, tokens or signatures, not log text
+const MIN_SECRET_CHARS: usize = 32;
+
+const REDACTED: &str = "[REDACTED]";
+
+// Names whose values are never copied, in config keys and in key=value or key: value log text
+const SECRET_KEYS: [&str; 8] = ["api_key", "apikey", "token", "password", "passwd", "secret", "authorization", "signature"];
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase().replace('-', "_");
+    SECRET_KEYS.iter().any(|secret| key.contains(secret))
+}
+
+fn ends_value(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '"' | '\'' | ',' | ';' | '&' | '}' | ')' | ']')
+}
+
+// Blanks the value after each secret name followed by = or :, quotes allowed around either
+fn redact_assignments(line: &str) -> String {
+    let lower = line.to_ascii_lowercase();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for key in SECRET_KEYS.iter().chain(["bearer"].iter()) {
+        for (at, _) in lower.match_indices(key) {
+            let rest = &line[at + key.len()..];
+            // Skip the rest of a longer name such as x_api_key_id
+            let name_end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-')).unwrap_or(rest.len());
+            let value = if *key == "bearer" {
+                // "Authorization: Bearer <token>"
+                (name_end == 0 && rest.starts_with(' ')).then(|| rest.trim_start())
+            } else {
+                let after = rest[name_end..].trim_start_matches(['"', '\'']).trim_start();
+                after.strip_prefix(['=', ':']).map(|value| value.trim_start().trim_start_matches(['"', '\'']))
+            };
+            let Some(value) = value else { continue };
+            let start = line.len() - value.len();
+            let end = start + value.find(ends_value).unwrap_or(value.len());
+            if end > start && !ranges.iter().any(|(s, e)| start < *e && *s < end) {
+                ranges.push((start, end));
+            }
+        }
+    }
+    ranges.sort();
+    let mut output = String::with_capacity(line.len());
+    let mut copied = 0;
+    for (start, end) in ranges {
+        output.push_str(&line[copied..start]);
+        output.push_str(REDACTED);
+        copied = end;
+    }
+    output.push_str(&line[copied..]);
+    output
+}
+
+fn looks_like_secret(word: &str) -> bool {
+    let word = word.trim_matches(|c: char| ends_value(c) || c == '.');
+    word.len() >= MIN_SECRET_CHARS
+        && word.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '-' | '_'))
+        && word.chars().any(|c| c.is_ascii_digit())
+        && word.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+// Log and warning text as it may leave the site: secrets blanked, then PII masked the way
+// PII reports mask it
+pub fn sanitize_text(text: &str, pii: &PiiConfig) -> String {
+    let redacted: Vec<String> = text.lines()
+        .map(|line| {
+            let line = redact_assignments(line);
+            line.split(' ').map(|word| if looks_like_secret(word) { REDACTED } else { word }).collect::<Vec<_>>().join(" ")
+        })
+        .collect();
+    let redacted = redacted.join("\n");
+    // Log fields such as user=jane@example.com are scanned as words of their own; the
+    // separators are single bytes, so the findings' offsets hold for the original
+    let scanned = redacted.replace(['=', ':'], " ");
+    let mut output = String::with_capacity(redacted.len());
+    let mut copied = 0;
+    for finding in pii.scan(&scanned) {
+        output.push_str(&redacted[copied..finding.start]);
+        output.push_str(&mask(&redacted[finding.start..finding.end]));
+        copied = finding.end;
+    }
+    output.push_str(&redacted[copied..]);
+    output
+}
+
+// The effective rules without what identifies the customer: secret-looking keys are blanked,
+// configured PII names masked and file paths cut to their names
+fn sanitize_config(value: &mut Value, key: Option<&str>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if is_secret_key(key) && !value.is_object() && !value.is_array() {
+                    *value = json!(REDACTED);
+                } else {
+                    sanitize_config(value, Some(key));
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| sanitize_config(value, key)),
+        Value::String(text) if key == Some("names") => *text = mask(text),
+        Value::String(text) if key.is_some_and(|key| key.ends_with("path") || key == "command") => {
+            if let Some(name) = Path::new(text.as_str()).file_name() {
+                *text = name.to_string_lossy().to_string();
+            }
+        }
+        _ => {}
+    }
+}
+
+// Where a bundle's contents come from; every source is optional
+#[derive(Debug, Clone, Default)]
+pub struct BundleSources {
+    pub license_path: Option<String>,
+    pub rules_path: Option<String>,
+    pub db_path: Option<String>,
+    // The document that failed, shared as its anonymized structure only
+    pub document_path: Option<String>,
+    pub log_paths: Vec<String>,
+    pub warnings: usize,
+}
+
+fn to_json(value: &Value) -> Vec<u8> {
+    (serde_json::to_string_pretty(value).unwrap_or_default() + "\n").into_bytes()
+}
+
+fn session_entry(sources: &BundleSources, engine: Option<&ExtractionEngine>) -> Value {
+    let mut info = SessionInfo::new(process_start(), documents_processed());
+    let mut problem = None;
+    if let Some(license_path) = &sources.license_path {
+        let license = std::fs::read_to_string(license_path).map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str::<License>(&data).map_err(|e| e.to_string()));
+        match license {
+            Ok(license) => info = info.with_license(&license),
+            Err(e) => problem = Some(format!("License did not load: {}", e)),
+        }
+    }
+    if let Some(engine) = engine {
+        info = info.with_engine(engine);
+    }
+    let mut session = serde_json::to_value(&info).unwrap_or_default();
+    if let Some(problem) = problem {
+        session["license_error"] = json!(problem);
+    }
+    session
+}
+
+// The newest failed jobs' errors, newest first
+fn job_warnings(db_path: &str, limit: usize, pii: &PiiConfig) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let store = JobStore::open(db_path)?;
+    Ok(store.list(Some(JobStatus::Failed), limit as u32)?.iter()
+        .filter_map(|job| {
+            let error = job.error.as_deref()?;
+            Some(json!({
+                "source": "job",
+                "job_id": job.job_id,
+                "at": job.finished_at.unwrap_or(job.created_at).to_rfc3339(),
+                "message": sanitize_text(error, pii),
+            }))
+        })
+        .collect())
+}
+
+fn log_tail(log_path: &str, pii: &PiiConfig) -> Result<Vec<u8>, String> {
+    let data = std::fs::read(log_path).map_err(|e| format!("Cannot read log {}: {}", log_path, e))?;
+    let text = String::from_utf8_lossy(&data);
+    let lines: Vec<&str> = text.lines().collect();
+    let tail = lines[lines.len().saturating_sub(MAX_LOG_LINES)..].join("\n");
+    Ok((sanitize_text(&tail, pii) + "\n").into_bytes())
+}
+
+// Anonymized text and pre-flight report of the failing document. Its name is replaced by its
+// extension, since file names often carry the registration or the customer
+fn document_entries(document_path: &str, engine: Option<&ExtractionEngine>, pii: &PiiConfig) -> Result<(Vec<u8>, Value), Box<dyn std::error::Error>> {
+    let name = match Path::new(document_path).extension() {
+        Some(extension) => format!("document.{}", extension.to_string_lossy()),
+        None => "document".to_string(),
+    };
+    let check = check_input(document_path, engine);
+    let mut info = check.get_info();
+    info.insert("path".to_string(), name);
+    for value in info.values_mut() {
+        *value = sanitize_text(&value.replace(document_path, "document"), pii);
+    }
+    let decoded = match engine {
+        Some(engine) => engine.read_input(document_path)?,
+        None => DecodedText::read(document_path)?,
+    };
+    let mut anonymizer = Anonymizer::new(&decoded.text, None);
+    if let Some(engine) = engine {
+        anonymizer = anonymizer.with_engine(engine);
+    }
+    Ok((anonymizer.anonymize(&decoded.text).into_bytes(), json!(info)))
+}
+
+// A deflated zip of the entries. Entries carry zip's 1980 epoch rather than the time they were
+// written, so identical sources give identical archives
+fn write_zip(entries: &[(String, Vec<u8>)]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default());
+    for (name, data) in entries {
+        archive.start_file(name.as_str(), options)?;
+        archive.write_all(data)?;
+    }
+    Ok(archive.finish()?.into_inner())
+}
+
+// Builds the bundle's entries. Sources that cannot be read are listed in the manifest rather
+// than failing the bundle, since a broken install is what bundles are made for
+pub fn bundle_entries(sources: &BundleSources) -> Vec<(String, Vec<u8>)> {
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut problems: Vec<String> = Vec::new();
+    let engine = sources.rules_path.as_deref().and_then(|rules_path| match ExtractionEngine::from_rules_file(rules_path) {
+        Ok(engine) => Some(engine),
+        Err(e) => {
+            problems.push(format!("Rules did not load: {}", e));
+            None
+        }
+    });
+    let mut pii = PiiConfig::builtin();
+    if let Some(engine) = &engine {
+        pii.merge(engine.get_pii_config().clone());
+    }
+
+    entries.push(("session.json".to_string(), to_json(&session_entry(sources, engine.as_ref()))));
+    let checks: Vec<HashMap<String, String>> = run_doctor(sources.license_path.as_deref(), sources.rules_path.as_deref()).iter()
+        .map(DoctorCheck::get_info)
+        .map(|mut info| {
+            info.values_mut().for_each(|value| *value = sanitize_text(value, &pii));
+            info
+        })
+        .collect();
+    entries.push(("doctor.json".to_string(), to_json(&json!(checks))));
+
+    if let Some(rules_path) = &sources.rules_path {
+        match resolve_profile(rules_path) {
+            Ok(mut profile) => {
+                sanitize_config(&mut profile.config, None);
+                let chain: Vec<String> = profile.chain.iter()
+                    .map(|path| Path::new(path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default())
+                    .collect();
+                entries.push(("config/rules.json".to_string(), to_json(&json!({ "chain": chain, "config": profile.config }))));
+            }
+            Err(e) => problems.push(format!("Rules did not resolve: {}", e)),
+        }
+    }
+
+    let mut warnings = Vec::new();
+    if let Some(document_path) = &sources.document_path {
+        match document_entries(document_path, engine.as_ref(), &pii) {
+            Ok((structure, preflight)) => {
+                warnings.extend(preflight["warnings"].as_str().unwrap_or_default().split("; ").filter(|w| !w.is_empty())
+                    .map(|warning| json!({ "source": "document", "message": warning })));
+                entries.push(("document/structure.txt".to_string(), structure));
+                entries.push(("document/preflight.json".to_string(), to_json(&preflight)));
+            }
+            Err(e) => problems.push(format!("Document did not read: {}", sanitize_text(&e.to_string().replace(document_path.as_str(), "document"), &pii))),
+        }
+    }
+    if let Some(db_path) = &sources.db_path {
+        match job_warnings(db_path, sources.warnings, &pii) {
+            Ok(jobs) => warnings.extend(jobs),
+            Err(e) => problems.push(format!("Job database did not open: {}", e)),
+        }
+    }
+    warnings.truncate(sources.warnings);
+    entries.push(("warnings.json".to_string(), to_json(&json!(warnings))));
+
+    for (index, log_path) in sources.log_paths.iter().enumerate() {
+        let name = Path::new(log_path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| "log".to_string());
+        match log_tail(log_path, &pii) {
+            Ok(tail) => entries.push((format!("logs/{}-{}", index + 1, name), tail)),
+            Err(e) => problems.push(e),
+        }
+    }
+
+    let contents: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+    let manifest = json!({
+        "created_at": Utc::now().to_rfc3339(),
+        "engine_version": ENGINE_VERSION,
+        "features": compiled_features(),
+        "contents": contents,
+        "warnings": warnings.len(),
+        "problems": problems,
+    });
+    entries.insert(0, ("manifest.json".to_string(), to_json(&manifest)));
+    entries
+}
+
+// Zips the entries and encrypts the archive to `recipients`, so the bundle can travel over
+// any ticketing system. Returns the entries' names
+pub fn write_bundle(sources: &BundleSources, recipients: &[String], output_path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let entries = bundle_entries(sources);
+    let archive = write_zip(&entries)?;
+    let output = AtomicFile::create(output_path).map_err(|e| format!("Cannot write {}: {}", output_path, e))?;
+    let mut writer = SealWriter::new(output, recipients)?;
+    writer.write_all(&archive)?;
+    writer.finish()?.commit()?;
+    Ok(entries.into_iter().map(|(name, _)| name).collect())
+}
+
+type BundleResults = (HashMap<String, String>, Vec<String>);
+
+// Python binding - one encrypted archive to attach to a vendor ticket; returns a summary and
+// the archive's entries
+#[pyfunction]
+#[pyo3(signature = (output_path, recipients, license_path=None, rules_path=None, db_path=None, document_path=None, log_paths=None, warnings=50))]
+#[allow(clippy::too_many_arguments)]
+pub fn create_support_bundle(
+    py: Python,
+    output_path: &str,
+    recipients: Vec<String>,
+    license_path: Option<String>,
+    rules_path: Option<String>,
+    db_path: Option<String>,
+    document_path: Option<String>,
+    log_paths: Option<Vec<String>>,
+    warnings: usize,
+) -> PyResult<BundleResults> {
+    let delivery = DeliveryConfig { recipients };
+    if !delivery.is_enabled() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("A support bundle needs at least one recipient"));
+    }
+    delivery.validate().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    let sources = BundleSources {
+        license_path,
+        rules_path,
+        db_path,
+        document_path,
+        log_paths: log_paths.unwrap_or_default(),
+        warnings,
+    };
+    let contents = py.allow_threads(|| write_bundle(&sources, &delivery.recipients, output_path).map_err(|e| e.to_string()))
+        .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)?;
+
+    let mut summary = HashMap::new();
+    summary.insert("output_path".to_string(), output_path.to_string());
+    summary.insert("entries".to_string(), contents.len().to_string());
+    summary.insert("recipients".to_string(), delivery.recipients.len().to_string());
+    summary.insert("engine_version".to_string(), ENGINE_VERSION.to_string());
+    Ok((summary, contents))
+}
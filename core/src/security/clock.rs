@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+
+// License time checks. Every instant is UTC - the host's time zone and DST never enter - and
+// each check reads the clock once, so checks made around midnight agree with each other
+
+// Hardcoded security constants - compiled into binary
+pub const BUILD_TIMESTAMP: i64 = 1734123456; // Compile-time timestamp (December 13, 2024)
+pub const HARDCODED_EXPIRATION_DAYS: i64 = 14; // Hardcoded expiration
+
+// Slack past expiry for a clock a few minutes fast, or a leap second, before a license
+// counts as expired
+pub const EXPIRY_TOLERANCE_SECONDS: i64 = 300;
+
+// How far the clock may run behind the build before the build counts as from the future
+pub const MAX_CLOCK_DRIFT_SECONDS: i64 = 86400;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+pub fn build_date() -> DateTime<Utc> {
+    DateTime::from_timestamp(BUILD_TIMESTAMP, 0).unwrap_or(DateTime::UNIX_EPOCH)
+}
+
+pub fn hardcoded_expiration() -> DateTime<Utc> {
+    build_date() + chrono::Duration::days(HARDCODED_EXPIRATION_DAYS)
+}
+
+pub fn is_expired(expires_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    now >= expires_at + chrono::Duration::seconds(EXPIRY_TOLERANCE_SECONDS)
+}
+
+// Whole days left, a part day counting as a day - zero exactly when the license is expired,
+// not for its whole last day
+pub fn days_remaining(expires_at: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    if is_expired(expires_at, now) {
+        return 0;
+    }
+    let left = (expires_at + chrono::Duration::seconds(EXPIRY_TOLERANCE_SECONDS) - now).num_seconds();
+    (left.max(1) + SECONDS_PER_DAY - 1) / SECONDS_PER_DAY
+}
+
+// Whether `instant` is further ahead of `now` than clock drift explains
+pub fn is_in_future(instant: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    instant > now + chrono::Duration::seconds(MAX_CLOCK_DRIFT_SECONDS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{LocalResult, NaiveDate, NaiveDateTime, TimeZone};
+    use chrono_tz::Tz;
+    use proptest::prelude::*;
+
+    type Date = (i32, u32, u32);
+
+    // Days of 2025 on which the clocks jump an hour forward, and the hour that never happens
+    const SPRING_FORWARD: [(Tz, Date, u32); 3] = [
+        (chrono_tz::America::New_York, (2025, 3, 9), 2),
+        (chrono_tz::Europe::Berlin, (2025, 3, 30), 2),
+        (chrono_tz::Australia::Sydney, (2025, 10, 5), 2),
+    ];
+    // ...and back, and the hour that happens twice
+    const FALL_BACK: [(Tz, Date, u32); 3] = [
+        (chrono_tz::America::New_York, (2025, 11, 2), 1),
+        (chrono_tz::Europe::Berlin, (2025, 10, 26), 2),
+        (chrono_tz::Australia::Sydney, (2025, 4, 6), 2),
+    ];
+
+    fn wall((year, month, day): Date, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn local_midnight(zone: Tz, day: Date) -> DateTime<Utc> {
+        zone.from_local_datetime(&wall(day, 0, 0)).unwrap().with_timezone(&Utc)
+    }
+
+    // A license file states its expiry with whatever offset the issuer's zone had then
+    fn as_issued(instant: DateTime<Utc>, zone: Tz) -> DateTime<Utc> {
+        let written = instant.with_timezone(&zone).to_rfc3339();
+        serde_json::from_value(serde_json::Value::String(written)).unwrap()
+    }
+
+    fn seconds(seconds: i64) -> chrono::Duration {
+        chrono::Duration::seconds(seconds)
+    }
+
+    // Expiry and days left follow from the seconds between the instants alone, and never
+    // grow as time passes
+    fn check(expires_at: DateTime<Utc>, now: DateTime<Utc>, later: DateTime<Utc>) -> Result<(), TestCaseError> {
+        let left = (expires_at - now).num_seconds() + EXPIRY_TOLERANCE_SECONDS;
+        prop_assert_eq!(is_expired(expires_at, now), left <= 0);
+        prop_assert_eq!(days_remaining(expires_at, now), if left <= 0 { 0 } else { (left + SECONDS_PER_DAY - 1) / SECONDS_PER_DAY });
+        prop_assert!(days_remaining(expires_at, later) <= days_remaining(expires_at, now));
+        Ok(())
+    }
+
+    const AROUND: std::ops::Range<i64> = -3 * SECONDS_PER_DAY..3 * SECONDS_PER_DAY;
+
+    proptest! {
+        // The 23-hour day counts its 23 hours: expiry is in elapsed seconds, not wall-clock days
+        #[test]
+        fn spring_forward_gap(zone in 0..3usize, minute in 0..60u32, expiry in AROUND, now in AROUND, step in 0..SECONDS_PER_DAY) {
+            let (zone, day, missing) = SPRING_FORWARD[zone];
+            prop_assert_eq!(zone.from_local_datetime(&wall(day, missing, minute)), LocalResult::None);
+            let midnight = local_midnight(zone, day);
+            let expires_at = as_issued(midnight + seconds(expiry), zone);
+            prop_assert_eq!(expires_at, midnight + seconds(expiry));
+            let now = midnight + seconds(now);
+            check(expires_at, now, now + seconds(step))?;
+        }
+
+        // The repeated hour names two instants an hour apart. A license expiring at the first
+        // is expired at the second, though the wall clock reads the same
+        #[test]
+        fn fall_back_overlap(zone in 0..3usize, minute in 0..60u32, expiry in AROUND, now in AROUND, step in 0..SECONDS_PER_DAY) {
+            let (zone, day, repeated) = FALL_BACK[zone];
+            let midnight = local_midnight(zone, day);
+            let expires_at = as_issued(midnight + seconds(expiry), zone);
+            prop_assert_eq!(expires_at, midnight + seconds(expiry));
+            let now = midnight + seconds(now);
+            check(expires_at, now, now + seconds(step))?;
+
+            let LocalResult::Ambiguous(first, second) = zone.from_local_datetime(&wall(day, repeated, minute)) else {
+                return Err(TestCaseError::fail(format!("{} {}:{:02} is not repeated", zone, repeated, minute)));
+            };
+            let (first, second) = (first.with_timezone(&Utc), second.with_timezone(&Utc));
+            prop_assert_eq!((second - first).num_seconds(), 3600);
+            let expires_at = as_issued(first, zone);
+            prop_assert_eq!(expires_at, first);
+            prop_assert!(!is_expired(expires_at, first));
+            prop_assert!(is_expired(expires_at, second));
+            prop_assert_eq!(days_remaining(expires_at, second), 0);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+// Every digest and MAC in the crate goes through this module, so a build swaps all of
+// them at once. The default backend is RustCrypto; `--features fips` uses AWS-LC's
+// FIPS 140-3 validated module through aws-lc-rs
+#[cfg(not(feature = "fips"))]
+pub const CRYPTO_BACKEND: &str = "rustcrypto";
+#[cfg(feature = "fips")]
+pub const CRYPTO_BACKEND: &str = "aws-lc-rs";
+
+pub const DIGEST_ALGORITHM: &str = "SHA-256";
+pub const MAC_ALGORITHM: &str = "HMAC-SHA256";
+pub const SIGNATURE_ALGORITHM: &str = "Ed25519";
+// Webhook and LLM connections use rustls on ring in every build, outside the FIPS boundary
+pub const TLS_BACKEND: &str = "rustls-ring";
+
+// SHA-256 over the concatenation of `parts`
+#[cfg(not(feature = "fips"))]
+pub fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(feature = "fips")]
+pub fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    use aws_lc_rs::digest;
+    let mut context = digest::Context::new(&digest::SHA256);
+    for part in parts {
+        context.update(part);
+    }
+    let mut output = [0u8; 32];
+    output.copy_from_slice(context.finish().as_ref());
+    output
+}
+
+// HMAC-SHA256 over the concatenation of `parts`
+#[cfg(not(feature = "fips"))]
+pub fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    use hmac::{Hmac, Mac};
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key)
+        .expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(feature = "fips")]
+pub fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    use aws_lc_rs::hmac;
+    let mut context = hmac::Context::with_key(&hmac::Key::new(hmac::HMAC_SHA256, key));
+    for part in parts {
+        context.update(part);
+    }
+    let mut output = [0u8; 32];
+    output.copy_from_slice(context.sign().as_ref());
+    output
+}
+
+// Public key of the Ed25519 key with this 32-byte seed
+#[cfg(not(feature = "fips"))]
+pub fn ed25519_public_key(seed: &[u8; 32]) -> [u8; 32] {
+    ed25519_dalek::SigningKey::from_bytes(seed).verifying_key().to_bytes()
+}
+
+#[cfg(feature = "fips")]
+pub fn ed25519_public_key(seed: &[u8; 32]) -> [u8; 32] {
+    use aws_lc_rs::signature::{Ed25519KeyPair, KeyPair};
+    let mut output = [0u8; 32];
+    output.copy_from_slice(Ed25519KeyPair::from_seed_unchecked(seed).expect("any 32 bytes are an Ed25519 seed").public_key().as_ref());
+    output
+}
+
+// Ed25519 signature over the concatenation of `parts`, by the key with this seed
+#[cfg(not(feature = "fips"))]
+pub fn ed25519_sign(seed: &[u8; 32], parts: &[&[u8]]) -> [u8; 64] {
+    use ed25519_dalek::Signer;
+    ed25519_dalek::SigningKey::from_bytes(seed).sign(&parts.concat()).to_bytes()
+}
+
+#[cfg(feature = "fips")]
+pub fn ed25519_sign(seed: &[u8; 32], parts: &[&[u8]]) -> [u8; 64] {
+    use aws_lc_rs::signature::Ed25519KeyPair;
+    let key = Ed25519KeyPair::from_seed_unchecked(seed).expect("any 32 bytes are an Ed25519 seed");
+    let mut output = [0u8; 64];
+    output.copy_from_slice(key.sign(&parts.concat()).as_ref());
+    output
+}
+
+// Whether `signature` is an Ed25519 signature by `public_key` over the concatenation of
+// `parts`. Keys and signatures of the wrong length never verify
+#[cfg(not(feature = "fips"))]
+pub fn ed25519_verify(public_key: &[u8], parts: &[&[u8]], signature: &[u8]) -> bool {
+    let (Ok(public_key), Ok(signature)) = (<[u8; 32]>::try_from(public_key), ed25519_dalek::Signature::from_slice(signature)) else {
+        return false;
+    };
+    ed25519_dalek::VerifyingKey::from_bytes(&public_key)
+        .is_ok_and(|key| key.verify_strict(&parts.concat(), &signature).is_ok())
+}
+
+#[cfg(feature = "fips")]
+pub fn ed25519_verify(public_key: &[u8], parts: &[&[u8]], signature: &[u8]) -> bool {
+    use aws_lc_rs::signature::{UnparsedPublicKey, ED25519};
+    UnparsedPublicKey::new(&ED25519, public_key).verify(&parts.concat(), signature).is_ok()
+}
+
+// Equality that takes as long wherever the first difference is, for MACs and signatures
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    use subtle::ConstantTimeEq;
+    a.ct_eq(b).into()
+}
+
+// SHA-1 over the concatenation of `parts`, for name-based UUIDs only - never for integrity.
+// Default builds leave it to the uuid crate
+#[cfg(feature = "fips")]
+pub fn sha1(parts: &[&[u8]]) -> [u8; 20] {
+    use aws_lc_rs::digest;
+    let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+    for part in parts {
+        context.update(part);
+    }
+    let mut output = [0u8; 20];
+    output.copy_from_slice(context.finish().as_ref());
+    output
+}
+
+// SHA-384 and SHA-512 over the concatenation of `parts`, for the key derivation of AES-256
+// encrypted PDFs
+#[cfg(not(feature = "fips"))]
+pub fn sha384(parts: &[&[u8]]) -> [u8; 48] {
+    use sha2::{Digest, Sha384};
+    let mut hasher = Sha384::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(feature = "fips")]
+pub fn sha384(parts: &[&[u8]]) -> [u8; 48] {
+    use aws_lc_rs::digest;
+    let mut context = digest::Context::new(&digest::SHA384);
+    for part in parts {
+        context.update(part);
+    }
+    let mut output = [0u8; 48];
+    output.copy_from_slice(context.finish().as_ref());
+    output
+}
+
+#[cfg(not(feature = "fips"))]
+pub fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+    use sha2::{Digest, Sha512};
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(feature = "fips")]
+pub fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+    use aws_lc_rs::digest;
+    let mut context = digest::Context::new(&digest::SHA512);
+    for part in parts {
+        context.update(part);
+    }
+    let mut output = [0u8; 64];
+    output.copy_from_slice(context.finish().as_ref());
+    output
+}
+
+// MD5 over the concatenation of `parts`, which the PDF standard security handler derives its
+// keys with - never for integrity. FIPS builds have none and refuse encrypted PDFs
+#[cfg(not(feature = "fips"))]
+pub fn md5(parts: &[&[u8]]) -> [u8; 16] {
+    use md5::{Digest, Md5};
+    let mut hasher = Md5::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+// BLAKE3 over the concatenation of `parts`. It is not a FIPS-approved digest, so FIPS builds
+// have none and refuse configurations that ask for it
+#[cfg(not(feature = "fips"))]
+pub fn blake3(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = ::blake3::Hasher::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// None unless `text` is an even number of hex digits
+pub fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(&text[index..index + 2], 16).ok()).collect()
+}
+
+// Whether the validated module is active - AWS-LC runs its power-on self tests here
+#[cfg(not(feature = "fips"))]
+pub fn fips_mode() -> bool {
+    false
+}
+
+#[cfg(feature = "fips")]
+pub fn fips_mode() -> bool {
+    aws_lc_rs::try_fips_mode().is_ok()
+}
+
+pub fn get_crypto_summary() -> HashMap<String, String> {
+    let mut info = HashMap::new();
+    info.insert("backend".to_string(), CRYPTO_BACKEND.to_string());
+    info.insert("fips_mode".to_string(), fips_mode().to_string());
+    info.insert("digest".to_string(), DIGEST_ALGORITHM.to_string());
+    info.insert("mac".to_string(), MAC_ALGORITHM.to_string());
+    info.insert("signature".to_string(), SIGNATURE_ALGORITHM.to_string());
+    info.insert("tls".to_string(), TLS_BACKEND.to_string());
+    info.insert("tls_fips".to_string(), "false".to_string());
+    info.insert("vendor_key".to_string(), crate::security::vendor::vendor_key_source().to_string());
+    info
+}
+
+// Python binding - lets deployments assert the backend they were promised
+#[pyfunction]
+pub fn get_crypto_info() -> PyResult<HashMap<String, String>> {
+    Ok(get_crypto_summary())
+}
@@ -0,0 +1,254 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::engine::completeness::DecodedText;
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::normalize::normalize;
+use crate::jobs::preflight::license_problem;
+#[cfg(feature = "llm")]
+use crate::llm::grammar::JsonSchema;
+use crate::schema::envelope::ENGINE_VERSION;
+use crate::security::crypto::{fips_mode, sha256, to_hex, CRYPTO_BACKEND};
+
+// Embedded self-test: a known document, the rules it is extracted with and what must come out
+const SAMPLE_DOCUMENT: &str = include_str!("../../selftest/sample.txt");
+const SAMPLE_RULES: &str = include_str!("../../selftest/rules.json");
+const SAMPLE_EXPECTED: &str = include_str!("../../selftest/expected.json");
+
+// SHA-256("abc") from FIPS 180-2 - any other answer means a broken crypto backend
+const SHA256_KNOWN_ANSWER: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+// Problems listed in a failing check's detail before the rest are counted
+const MAX_LISTED_PROBLEMS: usize = 5;
+
+pub const PASS: &str = "pass";
+pub const FAIL: &str = "fail";
+pub const SKIP: &str = "skip";
+
+#[derive(Debug, Clone)]
+pub struct DoctorCheck {
+    pub name: &'static str,
+    pub status: &'static str,
+    pub detail: String,
+}
+
+impl DoctorCheck {
+    pub(crate) fn new(name: &'static str, problems: Vec<String>, success: String) -> Self {
+        if problems.is_empty() {
+            return Self { name, status: PASS, detail: success };
+        }
+        let mut detail = problems.iter().take(MAX_LISTED_PROBLEMS).cloned().collect::<Vec<_>>().join("; ");
+        if problems.len() > MAX_LISTED_PROBLEMS {
+            detail.push_str(&format!("; and {} more", problems.len() - MAX_LISTED_PROBLEMS));
+        }
+        Self { name, status: FAIL, detail }
+    }
+
+    pub(crate) fn skip(name: &'static str, detail: &str) -> Self {
+        Self { name, status: SKIP, detail: detail.to_string() }
+    }
+
+    pub fn get_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("check".to_string(), self.name.to_string());
+        info.insert("status".to_string(), self.status.to_string());
+        info.insert("detail".to_string(), self.detail.clone());
+        info
+    }
+}
+
+pub fn compiled_features() -> Vec<&'static str> {
+    [
+        ("tables", cfg!(feature = "tables")),
+        ("ocr", cfg!(feature = "ocr")),
+        ("llm", cfg!(feature = "llm")),
+        ("flows", cfg!(feature = "flows")),
+        ("figures", cfg!(feature = "figures")),
+        ("hf-tokenizer", cfg!(feature = "hf-tokenizer")),
+        ("tiktoken", cfg!(feature = "tiktoken")),
+        ("local-llm", cfg!(feature = "local-llm")),
+        ("graphql", cfg!(feature = "graphql")),
+        ("search", cfg!(feature = "search")),
+        ("fips", cfg!(feature = "fips")),
+        ("profiling", cfg!(feature = "profiling")),
+        ("parquet", cfg!(feature = "parquet")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(feature, _)| feature)
+    .collect()
+}
+
+fn check_install() -> DoctorCheck {
+    let mut problems = Vec::new();
+    if to_hex(&sha256(&[b"abc"])) != SHA256_KNOWN_ANSWER {
+        problems.push(format!("{} SHA-256 known-answer test failed", CRYPTO_BACKEND));
+    }
+    if cfg!(feature = "fips") && !fips_mode() {
+        problems.push("Built for FIPS but the validated module is not active".to_string());
+    }
+    let features = compiled_features();
+    DoctorCheck::new("install", problems, format!(
+        "ml_core {}, crypto {}, features: {}",
+        ENGINE_VERSION,
+        CRYPTO_BACKEND,
+        if features.is_empty() { "none".to_string() } else { features.join(", ") }
+    ))
+}
+
+fn check_license(license_path: Option<&str>) -> DoctorCheck {
+    let license_path = match license_path {
+        Some(license_path) => license_path,
+        None => return DoctorCheck::skip("license", "No license file given"),
+    };
+    let problems = license_problem(license_path).into_iter().collect();
+    DoctorCheck::new("license", problems, format!("{} is valid on this machine", license_path))
+}
+
+// The rules payload is decoded and validated exactly as a job would load it
+fn check_rules(rules_path: Option<&str>) -> (DoctorCheck, Option<ExtractionEngine>) {
+    let rules_path = match rules_path {
+        Some(rules_path) => rules_path,
+        None => return (DoctorCheck::skip("rules", "No rules file given - only the embedded sample rules are checked"), None),
+    };
+    match ExtractionEngine::from_rules_file(rules_path) {
+        Ok(engine) => {
+            let detail = format!("{} loads as rules version {}", rules_path, engine.get_rules_version());
+            (DoctorCheck::new("rules", Vec::new(), detail), Some(engine))
+        }
+        Err(e) => (DoctorCheck::new("rules", vec![e.to_string()], String::new()), None),
+    }
+}
+
+#[cfg(feature = "llm")]
+fn schema_problem(schema: &Value) -> Option<String> {
+    JsonSchema::from_value(schema).err().map(|e| e.to_string())
+}
+
+// Grammars are the LLM layer's; without it prompt schemas are never compiled
+#[cfg(not(feature = "llm"))]
+fn schema_problem(_schema: &Value) -> Option<String> {
+    None
+}
+
+// Every pattern must still match something once normalized, and every prompt schema must
+// compile to a decoding grammar
+fn check_patterns(engine: &ExtractionEngine) -> DoctorCheck {
+    let mut problems = Vec::new();
+    let mut categories: Vec<&String> = engine.get_patterns().keys().collect();
+    categories.sort();
+    let mut pattern_count = 0;
+    for category in &categories {
+        for pattern in &engine.get_patterns()[*category] {
+            pattern_count += 1;
+            if normalize(pattern, engine.get_normalization()).text.is_empty() {
+                problems.push(format!("{} pattern {:?} is empty after normalization and never matches", category, pattern));
+            }
+        }
+    }
+
+    let mut prompts: Vec<&String> = engine.get_prompts().keys().collect();
+    prompts.sort();
+    let mut schema_count = 0;
+    for prompt in prompts {
+        if let Some(schema) = engine.get_prompt_schema(prompt) {
+            schema_count += 1;
+            if let Some(e) = schema_problem(schema) {
+                problems.push(format!("prompt {} schema: {}", prompt, e));
+            }
+        }
+    }
+    DoctorCheck::new("patterns", problems, format!(
+        "{} patterns in {} categories, {} prompt schemas (rules version {})",
+        pattern_count, categories.len(), schema_count, engine.get_rules_version()
+    ))
+}
+
+fn matches_expected(record: &HashMap<String, String>, expected: &serde_json::Map<String, Value>) -> bool {
+    expected.iter().all(|(key, value)| record.get(key).map(String::as_str) == value.as_str())
+}
+
+fn describe(expected: &serde_json::Map<String, Value>) -> String {
+    expected.iter()
+        .map(|(key, value)| format!("{}={}", key, value.as_str().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Extracts the embedded sample and compares every collection with the embedded expectation
+fn check_extraction() -> DoctorCheck {
+    let mut engine = ExtractionEngine::new();
+    if let Err(e) = engine.load_config(SAMPLE_RULES.as_bytes()) {
+        return DoctorCheck::new("extraction", vec![format!("Embedded sample rules: {}", e)], String::new());
+    }
+    let expected: Value = match serde_json::from_str(SAMPLE_EXPECTED) {
+        Ok(expected) => expected,
+        Err(e) => return DoctorCheck::new("extraction", vec![format!("Embedded expectation: {}", e)], String::new()),
+    };
+
+    let text = DecodedText::decode(SAMPLE_DOCUMENT.as_bytes()).text;
+    // Only the collections this build extracts are compared
+    let collections = engine.extract_collections(&text);
+
+    let mut problems = Vec::new();
+    let mut record_count = 0;
+    for (collection, records) in &collections {
+        record_count += records.len();
+        let wanted: Vec<&serde_json::Map<String, Value>> = expected.get(collection)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_object)
+            .collect();
+        if records.len() != wanted.len() {
+            problems.push(format!("{}: expected {} records, got {}", collection, wanted.len(), records.len()));
+        }
+        for want in wanted {
+            if !records.iter().any(|record| matches_expected(record, want)) {
+                problems.push(format!("{}: no record with {}", collection, describe(want)));
+            }
+        }
+    }
+    DoctorCheck::new("extraction", problems, format!(
+        "Sample document extracted to {} records matching the expectation", record_count
+    ))
+}
+
+// Install, license, rules payload, patterns, then the embedded sample end to end
+pub fn run_doctor(license_path: Option<&str>, rules_path: Option<&str>) -> Vec<DoctorCheck> {
+    let (rules_check, engine) = check_rules(rules_path);
+    let patterns_check = match &engine {
+        Some(engine) => check_patterns(engine),
+        None if rules_check.status == FAIL => DoctorCheck::skip("patterns", "Rules file did not load"),
+        None => {
+            let mut sample = ExtractionEngine::new();
+            match sample.load_config(SAMPLE_RULES.as_bytes()) {
+                Ok(()) => check_patterns(&sample),
+                Err(e) => DoctorCheck::new("patterns", vec![format!("Embedded sample rules: {}", e)], String::new()),
+            }
+        }
+    };
+    vec![check_install(), check_license(license_path), rules_check, patterns_check, check_extraction()]
+}
+
+pub(crate) type DoctorResults = (HashMap<String, String>, Vec<HashMap<String, String>>);
+
+// Counts by status, and healthy when nothing failed
+pub(crate) fn summarize(checks: &[DoctorCheck]) -> DoctorResults {
+    let count = |status: &str| checks.iter().filter(|check| check.status == status).count();
+    let mut summary = HashMap::new();
+    summary.insert("engine_version".to_string(), ENGINE_VERSION.to_string());
+    summary.insert("passed".to_string(), count(PASS).to_string());
+    summary.insert("failed".to_string(), count(FAIL).to_string());
+    summary.insert("skipped".to_string(), count(SKIP).to_string());
+    summary.insert("healthy".to_string(), (count(FAIL) == 0).to_string());
+    (summary, checks.iter().map(DoctorCheck::get_info).collect())
+}
+
+// Python binding - the report support asks for first
+#[pyfunction]
+#[pyo3(signature = (license_path=None, rules_path=None))]
+pub fn run_self_test(py: Python, license_path: Option<&str>, rules_path: Option<&str>) -> PyResult<DoctorResults> {
+    py.allow_threads(|| Ok(summarize(&run_doctor(license_path, rules_path))))
+}
@@ -1 +1,14 @@
+pub mod access;
+pub mod bundle;
+pub mod clock;
+pub mod crypto;
+#[cfg(not(feature = "fips"))]
+pub mod doctor;
+pub mod payload;
+pub mod sandbox;
+pub mod seal;
+pub mod session;
+pub mod threads;
+pub mod vendor;
+pub mod watermark;
 pub mod validator;
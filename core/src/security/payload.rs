@@ -0,0 +1,301 @@
+use pyo3::prelude::*;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+
+use crate::security::doctor::{summarize, DoctorCheck, DoctorResults, FAIL};
+use crate::security::seal::SEALED_EXTENSION;
+
+// Diagnostics for a sealed output (see security::seal) that will not open, one layer at a
+// time: the format line, the recipient stanzas and header MAC, the key, then every payload
+// chunk. The first layer that fails says what went wrong and what to do; the layers under it
+// are skipped
+
+const AGE_MAGIC: &str = "age-encryption.org/";
+const AGE_VERSION: &str = "v1";
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const IDENTITY_PREFIX: &str = "AGE-SECRET-KEY-";
+const X25519: &str = "X25519";
+// Random stanzas age adds so that readers keep ignoring types they do not know
+const GREASE: &str = "-grease";
+
+// Headers name one stanza per recipient - far less than this
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+
+// Plaintext bytes per payload chunk in age v1
+const CHUNK_BYTES: u64 = 64 * 1024;
+
+// What the header says before its MAC: the stanza types, in order
+struct Header {
+    stanzas: Vec<String>,
+}
+
+impl Header {
+    fn recipients(&self) -> usize {
+        self.stanzas.iter().filter(|stanza| *stanza == X25519).count()
+    }
+}
+
+fn check_format(start: &[u8]) -> DoctorCheck {
+    let first_line = String::from_utf8_lossy(start.split(|b| *b == b'\n').next().unwrap_or_default()).to_string();
+    let problem = if start.is_empty() {
+        "The file is empty; it was not written completely. Fetch or write it again".to_string()
+    } else if let Some(version) = first_line.strip_prefix(AGE_MAGIC) {
+        if version == AGE_VERSION {
+            return DoctorCheck::new("format", Vec::new(), format!("age {} file", AGE_VERSION));
+        }
+        format!("An age file of version {:?}, which this build does not read (it reads {}); it was written by a newer age", version, AGE_VERSION)
+    } else if first_line.trim() == ARMOR_BEGIN {
+        "An ASCII-armored age file; decode it with `age -d` directly, or save it unarmored".to_string()
+    } else if matches!(start.iter().find(|b| !b.is_ascii_whitespace()), Some(b'{' | b'[')) {
+        format!("Plain JSON, not encrypted: delivery had no recipients when it was written. Open it as it is, without .{}", SEALED_EXTENSION)
+    } else {
+        format!("Not an age file: it starts with {:?} rather than {}{}", first_line.chars().take(24).collect::<String>(), AGE_MAGIC, AGE_VERSION)
+    };
+    DoctorCheck::new("format", vec![problem], String::new())
+}
+
+// The stanzas up to the "---" MAC line. None when the header never ends
+fn read_header(start: &[u8]) -> Option<Header> {
+    let mut stanzas = Vec::new();
+    for line in start.split(|b| *b == b'\n').skip(1) {
+        let line = String::from_utf8_lossy(line);
+        if line.starts_with("--- ") {
+            return Some(Header { stanzas });
+        }
+        if let Some(stanza) = line.strip_prefix("-> ") {
+            stanzas.push(stanza.split(' ').next().unwrap_or_default().to_string());
+        }
+    }
+    None
+}
+
+fn check_header(header: &Option<Header>, opened: &Result<age::Decryptor<BufReader<File>>, age::DecryptError>) -> DoctorCheck {
+    let problem = match (header, opened) {
+        (None, _) => "The header has no MAC line: the file was cut short in its header or is not an age file. Fetch it again".to_string(),
+        (Some(header), _) if header.recipients() == 0 && header.stanzas.iter().all(|stanza| stanza.ends_with(GREASE)) => "The header names no recipients; the file cannot be opened by anyone".to_string(),
+        (Some(_), Err(e)) => format!("The header does not parse ({}): it was damaged after it was written. Fetch it again", e),
+        (Some(header), Ok(_)) => {
+            let others: Vec<&String> = header.stanzas.iter().filter(|stanza| *stanza != X25519 && !stanza.ends_with(GREASE)).collect();
+            if others.is_empty() {
+                return DoctorCheck::new("header", Vec::new(), format!("Encrypted to {} X25519 recipient(s)", header.recipients()));
+            }
+            format!("Encrypted to {:?} as well as X25519 keys; outputs are only ever encrypted to X25519 keys, so this file was not written by the engine", others)
+        }
+    };
+    DoctorCheck::new("header", vec![problem], String::new())
+}
+
+// Every AGE-SECRET-KEY-1... line of an age identity file, as written by age-keygen
+fn read_identities(path: &Path) -> Result<Vec<age::x25519::Identity>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let identities = text.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with(IDENTITY_PREFIX))
+        .map(|line| line.parse::<age::x25519::Identity>().map_err(|_| format!("{} has a line starting {} that is not an X25519 identity", path.display(), IDENTITY_PREFIX)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if identities.is_empty() {
+        return Err(format!("{} has no {}... line; pass the age identity file the outputs are decrypted with", path.display(), IDENTITY_PREFIX));
+    }
+    Ok(identities)
+}
+
+fn key_problem(error: age::DecryptError, identities: &[age::x25519::Identity], recipients: usize) -> String {
+    match error {
+        age::DecryptError::NoMatchingKeys => {
+            let keys: Vec<String> = identities.iter().map(|identity| identity.to_public().to_string()).collect();
+            format!(
+                "None of the {} recipient(s) is this identity ({}): the file was encrypted to other keys, such as the ones before a key rotation. Check the profile's delivery.recipients when it was written, and use the identity for one of them",
+                recipients, keys.join(", ")
+            )
+        }
+        age::DecryptError::InvalidMac => "The identity is a recipient, but the header MAC does not match: the header was altered after it was written. Fetch the file again".to_string(),
+        age::DecryptError::DecryptionFailed | age::DecryptError::KeyDecryptionFailed =>
+            "The identity's stanza does not open: it was damaged after it was written. Fetch the file again".to_string(),
+        other => format!("Cannot open the file key: {}", other),
+    }
+}
+
+// Reads the payload to the end, so every chunk's tag is checked
+fn check_payload(mut reader: impl Read) -> DoctorCheck {
+    let mut buffer = vec![0u8; CHUNK_BYTES as usize];
+    let mut read = 0u64;
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return DoctorCheck::new("payload", Vec::new(), format!("{} bytes decrypted in {} chunk(s), all authentic", read, read.div_ceil(CHUNK_BYTES).max(1))),
+            Ok(n) => read += n as u64,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                let chunk = read / CHUNK_BYTES;
+                let problem = if e.kind() == ErrorKind::UnexpectedEof {
+                    format!("The payload ends after {} bytes, before its last chunk: the file was cut short, e.g. by an interrupted copy or a full disk. Fetch it again", read)
+                } else {
+                    format!(
+                        "Chunk {} (from byte {}) fails authentication ({}): the file was damaged or altered after it was written. Fetch it again; the bytes before it are intact",
+                        chunk, chunk * CHUNK_BYTES, e
+                    )
+                };
+                return DoctorCheck::new("payload", vec![problem], String::new());
+            }
+        }
+    }
+}
+
+pub fn diagnose(path: &Path, identity_path: Option<&Path>) -> Vec<DoctorCheck> {
+    let skipped = |checks: &mut Vec<DoctorCheck>, names: &[&'static str], reason: &str| {
+        checks.extend(names.iter().map(|name| DoctorCheck::skip(name, reason)));
+    };
+    let mut checks = Vec::new();
+    let mut start = Vec::new();
+    let opened = File::open(path).and_then(|file| file.take(MAX_HEADER_BYTES as u64).read_to_end(&mut start));
+    if let Err(e) = opened {
+        checks.push(DoctorCheck::new("format", vec![format!("Cannot read {}: {}", path.display(), e)], String::new()));
+        skipped(&mut checks, &["header", "key", "payload"], "The file did not read");
+        return checks;
+    }
+
+    checks.push(check_format(&start));
+    if checks[0].status == FAIL {
+        skipped(&mut checks, &["header", "key", "payload"], "Not an age v1 file");
+        return checks;
+    }
+
+    let header = read_header(&start);
+    let decryptor = File::open(path).map_err(age::DecryptError::Io).and_then(|file| age::Decryptor::new(BufReader::new(file)));
+    checks.push(check_header(&header, &decryptor));
+    let (Some(header), Ok(decryptor), false) = (header, decryptor, checks[1].status == FAIL) else {
+        skipped(&mut checks, &["key", "payload"], "The header did not check out");
+        return checks;
+    };
+
+    let Some(identity_path) = identity_path else {
+        skipped(&mut checks, &["key", "payload"], "No identity given; pass the age identity file to check the key and payload");
+        return checks;
+    };
+    let identities = match read_identities(identity_path) {
+        Ok(identities) => identities,
+        Err(problem) => {
+            checks.push(DoctorCheck::new("key", vec![problem], String::new()));
+            skipped(&mut checks, &["payload"], "The key did not open the file");
+            return checks;
+        }
+    };
+    match decryptor.decrypt(identities.iter().map(|identity| identity as &dyn age::Identity)) {
+        Ok(reader) => {
+            checks.push(DoctorCheck::new("key", Vec::new(), "The identity is a recipient and opens the file key".to_string()));
+            checks.push(check_payload(reader));
+        }
+        Err(e) => {
+            checks.push(DoctorCheck::new("key", vec![key_problem(e, &identities, header.recipients())], String::new()));
+            skipped(&mut checks, &["payload"], "The key did not open the file");
+        }
+    }
+    checks
+}
+
+// Python binding - why a sealed output or support bundle will not decrypt. identity_path is
+// the age identity file it should open with; without it only the format and header are checked
+#[pyfunction]
+#[pyo3(signature = (path, identity_path=None))]
+pub fn diagnose_payload(py: Python, path: &str, identity_path: Option<&str>) -> PyResult<DoctorResults> {
+    py.allow_threads(|| Ok(summarize(&diagnose(Path::new(path), identity_path.map(Path::new)))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::doctor::{PASS, SKIP};
+    use crate::security::seal::SealWriter;
+    use age::secrecy::ExposeSecret;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("spp-payload-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, name: &str, data: &[u8]) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, data).unwrap();
+            path
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn seal(plain: &[u8], identity: &age::x25519::Identity) -> Vec<u8> {
+        let mut writer = SealWriter::new(Vec::new(), &[identity.to_public().to_string()]).unwrap();
+        writer.write_all(plain).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn statuses(checks: &[DoctorCheck]) -> Vec<&str> {
+        checks.iter().map(|check| check.status).collect()
+    }
+
+    fn detail<'a>(checks: &'a [DoctorCheck], name: &str) -> &'a str {
+        &checks.iter().find(|check| check.name == name).unwrap().detail
+    }
+
+    #[test]
+    fn each_layer_names_its_own_failure() {
+        let scratch = Scratch::new("layers");
+        let identity = age::x25519::Identity::generate();
+        let key = scratch.write("key.txt", format!("# created: 2026-01-01\n{}\n", identity.to_string().expose_secret()).as_bytes());
+        let plain: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+        let sealed = seal(&plain, &identity);
+        let payload_start = sealed.windows(4).position(|w| w == b"\n---").unwrap() + 1;
+        let payload_start = payload_start + sealed[payload_start..].iter().position(|b| *b == b'\n').unwrap() + 1;
+
+        let good = scratch.write("good.json.age", &sealed);
+        let checks = diagnose(&good, Some(&key));
+        assert_eq!(statuses(&checks), [PASS, PASS, PASS, PASS]);
+        assert!(detail(&checks, "payload").starts_with("150000 bytes decrypted in 3 chunk(s)"));
+        assert_eq!(statuses(&diagnose(&good, None)), [PASS, PASS, SKIP, SKIP]);
+
+        let plain_json = scratch.write("plain.json.age", b"  {\"records\": []}");
+        let checks = diagnose(&plain_json, Some(&key));
+        assert_eq!(statuses(&checks), [FAIL, SKIP, SKIP, SKIP]);
+        assert!(detail(&checks, "format").starts_with("Plain JSON"));
+        let newer = scratch.write("newer.age", b"age-encryption.org/v2\n");
+        assert!(detail(&diagnose(&newer, None), "format").contains("\"v2\""));
+
+        let cut_header = scratch.write("cut-header.age", &sealed[..payload_start - 20]);
+        assert_eq!(statuses(&diagnose(&cut_header, Some(&key))), [PASS, FAIL, SKIP, SKIP]);
+
+        let stranger = age::x25519::Identity::generate();
+        let other_key = scratch.write("other.txt", stranger.to_string().expose_secret().as_bytes());
+        let checks = diagnose(&good, Some(&other_key));
+        assert_eq!(statuses(&checks), [PASS, PASS, FAIL, SKIP]);
+        assert!(detail(&checks, "key").contains(&stranger.to_public().to_string()));
+
+        // A changed MAC, as for any change to the header the key still opens
+        let mut tampered = sealed.clone();
+        let mac_char = payload_start - 10;
+        tampered[mac_char] = if tampered[mac_char] == b'A' { b'B' } else { b'A' };
+        let checks = diagnose(&scratch.write("mac.age", &tampered), Some(&key));
+        assert_eq!(statuses(&checks), [PASS, PASS, FAIL, SKIP]);
+        assert!(detail(&checks, "key").contains("header MAC"));
+
+        // Past the 16-byte nonce, in the second chunk
+        let mut corrupted = sealed.clone();
+        corrupted[payload_start + 16 + 65_536 + 16 + 100] ^= 1;
+        let checks = diagnose(&scratch.write("corrupted.age", &corrupted), Some(&key));
+        assert_eq!(statuses(&checks), [PASS, PASS, PASS, FAIL]);
+        assert!(detail(&checks, "payload").starts_with("Chunk 1 (from byte 65536) fails authentication"));
+
+        let truncated = scratch.write("truncated.age", &sealed[..payload_start + 16 + 65_536 + 16]);
+        let checks = diagnose(&truncated, Some(&key));
+        assert_eq!(statuses(&checks), [PASS, PASS, PASS, FAIL]);
+        assert!(detail(&checks, "payload").contains("cut short"), "{}", detail(&checks, "payload"));
+    }
+}
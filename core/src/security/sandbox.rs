@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+#[cfg(unix)]
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Variables a sandboxed tool always gets; everything else in the environment is dropped
+// unless the profile passes it through by name
+const BASE_ENV: [&str; 3] = ["PATH", "LANG", "LC_ALL"];
+
+// Stderr kept per run - the rest is read and discarded
+const MAX_STDERR_BYTES: u64 = 64 * 1024;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+const MB: u64 = 1024 * 1024;
+
+fn default_timeout_seconds() -> u64 {
+    120
+}
+
+fn default_max_memory_mb() -> u64 {
+    2048
+}
+
+fn default_max_cpu_seconds() -> u64 {
+    120
+}
+
+fn default_max_output_mb() -> u64 {
+    256
+}
+
+fn default_limit_address_space() -> bool {
+    true
+}
+
+// Limits applied to every external tool run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxLimits {
+    // Wall-clock time before the tool and everything it started are killed
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+    // Address space, so a runaway tool fails its allocation instead of the host swapping
+    #[serde(default = "default_max_memory_mb")]
+    pub max_memory_mb: u64,
+    #[serde(default = "default_max_cpu_seconds")]
+    pub max_cpu_seconds: u64,
+    // Caps both the text read from stdout and any file the tool writes
+    #[serde(default = "default_max_output_mb")]
+    pub max_output_mb: u64,
+    // Extra environment variables passed through, e.g. TESSDATA_PREFIX
+    #[serde(default)]
+    pub pass_env: Vec<String>,
+    // Whether max_memory_mb applies. The OCR converter turns it off for GPU tools: CUDA
+    // reserves far more address space than it uses, so any limit a host could offer fails the
+    // run. Never read from rules
+    #[serde(skip, default = "default_limit_address_space")]
+    pub limit_address_space: bool,
+}
+
+impl Default for SandboxLimits {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: default_timeout_seconds(),
+            max_memory_mb: default_max_memory_mb(),
+            max_cpu_seconds: default_max_cpu_seconds(),
+            max_output_mb: default_max_output_mb(),
+            pass_env: Vec::new(),
+            limit_address_space: default_limit_address_space(),
+        }
+    }
+}
+
+impl SandboxLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("timeout_seconds", self.timeout_seconds),
+            ("max_memory_mb", self.max_memory_mb),
+            ("max_cpu_seconds", self.max_cpu_seconds),
+            ("max_output_mb", self.max_output_mb),
+        ] {
+            if value == 0 {
+                return Err(format!("converters.sandbox.{} must be at least 1", name));
+            }
+        }
+        Ok(())
+    }
+
+    // The stricter limit wins when rule packs are merged
+    pub fn merge(&mut self, other: &SandboxLimits) {
+        self.timeout_seconds = self.timeout_seconds.min(other.timeout_seconds);
+        self.max_memory_mb = self.max_memory_mb.min(other.max_memory_mb);
+        self.max_cpu_seconds = self.max_cpu_seconds.min(other.max_cpu_seconds);
+        self.max_output_mb = self.max_output_mb.min(other.max_output_mb);
+        for name in &other.pass_env {
+            if !self.pass_env.contains(name) {
+                self.pass_env.push(name.clone());
+            }
+        }
+    }
+
+    fn max_output_bytes(&self) -> u64 {
+        self.max_output_mb.saturating_mul(MB)
+    }
+}
+
+pub struct SandboxOutput {
+    pub stdout: Vec<u8>,
+    // Non-empty stderr lines, in order
+    pub stderr: Vec<String>,
+}
+
+#[cfg(unix)]
+fn rlimit(value: u64) -> libc::rlimit {
+    libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t }
+}
+
+// Runs in the forked child before exec: a new process group, then the limits. Only
+// async-signal-safe calls are made here
+#[cfg(unix)]
+fn confine(process: &mut Command, limits: &SandboxLimits) {
+    // Left as the parent's when off
+    let memory = limits.limit_address_space.then(|| limits.max_memory_mb.saturating_mul(MB));
+    let cpu = limits.max_cpu_seconds;
+    let output = limits.max_output_bytes();
+    unsafe {
+        process.pre_exec(move || {
+            if libc::setpgid(0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            for (resource, value) in [
+                (libc::RLIMIT_AS, memory),
+                (libc::RLIMIT_CPU, Some(cpu)),
+                (libc::RLIMIT_FSIZE, Some(output)),
+                (libc::RLIMIT_CORE, Some(0)),
+            ] {
+                let Some(value) = value else { continue };
+                if libc::setrlimit(resource, &rlimit(value)) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+// Without resource limits or process groups only the timeout and the output cap apply
+#[cfg(not(unix))]
+fn confine(_process: &mut Command, _limits: &SandboxLimits) {}
+
+// Whether the tool has exited, leaving it unreaped: until it is reaped its process id, and so
+// its process group id, cannot be reused, and killing the group cannot hit another process
+#[cfg(unix)]
+fn exited(child: &mut Child) -> std::io::Result<bool> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let result = unsafe {
+        libc::waitid(libc::P_PID, child.id() as libc::id_t, &mut info, libc::WEXITED | libc::WNOHANG | libc::WNOWAIT)
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { info.si_pid() } != 0)
+}
+
+#[cfg(not(unix))]
+fn exited(child: &mut Child) -> std::io::Result<bool> {
+    Ok(child.try_wait()?.is_some())
+}
+
+// Kills the tool's whole process group, so helpers it spawned die with it
+#[cfg(unix)]
+fn kill_tree(child: &mut Child) {
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+// Only the tool itself can be killed; processes it started are left running
+#[cfg(not(unix))]
+fn kill_tree(child: &mut Child) {
+    let _ = child.kill();
+}
+
+// Why the tool was killed, when a signal killed it
+#[cfg(unix)]
+fn signal_reason(status: &ExitStatus, limits: &SandboxLimits) -> Option<String> {
+    Some(match status.signal()? {
+        libc::SIGXCPU => format!("exceeded the CPU limit of {}s", limits.max_cpu_seconds),
+        libc::SIGXFSZ => format!("exceeded the output limit of {} MB", limits.max_output_mb),
+        signal if limits.limit_address_space => format!("was killed by signal {} (memory limit {} MB)", signal, limits.max_memory_mb),
+        signal => format!("was killed by signal {}", signal),
+    })
+}
+
+#[cfg(not(unix))]
+fn signal_reason(_status: &ExitStatus, _limits: &SandboxLimits) -> Option<String> {
+    None
+}
+
+fn stderr_lines(stderr: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Runs `command` (program and arguments) in `work_dir` with a cleared environment, resource
+// limits and a timeout. Fails when the tool times out, hits a limit or exits non-zero
+pub fn run_sandboxed(command: &[String], limits: &SandboxLimits, work_dir: &Path) -> Result<SandboxOutput, String> {
+    let program = command.first().ok_or("Empty command")?;
+    let mut process = Command::new(program);
+    process.args(&command[1..])
+        .current_dir(work_dir)
+        .env_clear()
+        .env("HOME", work_dir)
+        .env("TMPDIR", work_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    for name in BASE_ENV.iter().copied().chain(limits.pass_env.iter().map(String::as_str)) {
+        if let Ok(value) = std::env::var(name) {
+            process.env(name, value);
+        }
+    }
+
+    confine(&mut process, limits);
+    let output = limits.max_output_bytes();
+
+    let mut child = process.spawn().map_err(|e| format!("Cannot start {}: {}", program, e))?;
+    let mut stdout = child.stdout.take().ok_or("No stdout pipe")?;
+    let mut stderr = child.stderr.take().ok_or("No stderr pipe")?;
+
+    // Pipes are drained on their own threads so a chatty tool never blocks on a full pipe. A
+    // tool past the output cap is left blocked on its pipe until the wait below kills it
+    let overflowed = Arc::new(AtomicBool::new(false));
+    let stdout_overflowed = overflowed.clone();
+    let stdout_reader = thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let result = (&mut stdout).take(output + 1).read_to_end(&mut buffer);
+        if buffer.len() as u64 > output {
+            stdout_overflowed.store(true, Ordering::Relaxed);
+        }
+        result.map(|_| buffer)
+    });
+    let stderr_reader = thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let result = (&mut stderr).take(MAX_STDERR_BYTES).read_to_end(&mut buffer);
+        let _ = std::io::copy(&mut stderr, &mut std::io::sink());
+        result.map(|_| buffer)
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(limits.timeout_seconds);
+    let outcome = loop {
+        match exited(&mut child) {
+            Ok(true) => break Ok(()),
+            Ok(false) if overflowed.load(Ordering::Relaxed) => break Ok(()),
+            Ok(false) if Instant::now() >= deadline => {
+                break Err(format!("{} timed out after {}s and was killed", program, limits.timeout_seconds));
+            }
+            Ok(false) => thread::sleep(POLL_INTERVAL),
+            Err(e) => break Err(format!("Cannot wait for {}: {}", program, e)),
+        }
+    };
+    // Anything the tool left running in its group would hold the pipes open. The group goes
+    // before the tool is reaped, while its id still names it
+    kill_tree(&mut child);
+    let status = child.wait().map_err(|e| format!("Cannot wait for {}: {}", program, e));
+
+    let stdout = stdout_reader.join().map_err(|_| "stdout reader panicked")?
+        .map_err(|e| format!("Cannot read {} output: {}", program, e))?;
+    let stderr = stderr_lines(&stderr_reader.join().map_err(|_| "stderr reader panicked")?.unwrap_or_default());
+    outcome?;
+    let status = status?;
+    let last_stderr = stderr.last().map(|line| format!(": {}", line)).unwrap_or_default();
+
+    if stdout.len() as u64 > output {
+        return Err(format!("{} wrote more than {} MB of text", program, limits.max_output_mb));
+    }
+    if let Some(reason) = signal_reason(&status, limits) {
+        return Err(format!("{} {}{}", program, reason, last_stderr));
+    }
+    if !status.success() {
+        return Err(format!("{} exited with status {}{}", program, status.code().unwrap_or(-1), last_stderr));
+    }
+    Ok(SandboxOutput { stdout, stderr })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[test]
+    fn kills_what_the_tool_left_running_and_times_out() {
+        let dir = std::env::temp_dir().join(format!("spp-sandbox-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The background sleep holds stdout open after the tool exits, until its group is killed
+        let started = Instant::now();
+        let output = run_sandboxed(&shell("sleep 30 & echo done"), &SandboxLimits::default(), &dir).unwrap();
+        assert_eq!(output.stdout, b"done\n");
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let limits = SandboxLimits { timeout_seconds: 1, ..SandboxLimits::default() };
+        let error = run_sandboxed(&shell("sleep 30"), &limits, &dir).err().unwrap();
+        assert!(error.contains("timed out after 1s"), "{}", error);
+        assert!(run_sandboxed(&shell("echo oops >&2; exit 3"), &limits, &dir).err().unwrap().ends_with("status 3: oops"));
+
+        // GPU tools run without the address-space limit
+        assert_eq!(run_sandboxed(&shell("ulimit -v"), &limits, &dir).unwrap().stdout, format!("{}\n", 2048 * 1024).into_bytes());
+        let gpu = SandboxLimits { limit_address_space: false, ..limits };
+        assert_eq!(run_sandboxed(&shell("ulimit -v"), &gpu, &dir).unwrap().stdout, b"unlimited\n");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,207 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::storage::atomic::{write_atomic, AtomicFile};
+
+// Encrypted outputs are age v1 files (age-encryption.org/v1) for X25519 recipients, written
+// with the age crate, so customers decrypt them with `age -d -i key.txt` or any other age
+// implementation. The payload is encrypted in chunks as it is written, so no plaintext reaches
+// the disk
+pub const SEALED_EXTENSION: &str = "age";
+
+// Customer public keys outputs are encrypted to, e.g. "delivery": {"recipients": ["age1..."]}.
+// With no recipients outputs are written as plain JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeliveryConfig {
+    #[serde(default)]
+    pub recipients: Vec<String>,
+}
+
+impl DeliveryConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.recipients.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.is_enabled() && cfg!(feature = "fips") {
+            return Err("delivery.recipients uses X25519 and ChaCha20-Poly1305, which a FIPS build does not offer".to_string());
+        }
+        for recipient in &self.recipients {
+            parse_recipient(recipient).map_err(|e| format!("delivery.recipients: {}", e))?;
+        }
+        Ok(())
+    }
+
+    // Every pack's recipients can read the outputs
+    pub fn merge(&mut self, other: &DeliveryConfig) {
+        for recipient in &other.recipients {
+            if !self.recipients.contains(recipient) {
+                self.recipients.push(recipient.clone());
+            }
+        }
+    }
+
+    // Where an output meant for `path` ends up: `path.age` when it is encrypted
+    pub fn output_path(&self, path: &Path) -> PathBuf {
+        if !self.is_enabled() {
+            return path.to_path_buf();
+        }
+        let mut sealed = path.as_os_str().to_owned();
+        sealed.push(format!(".{}", SEALED_EXTENSION));
+        PathBuf::from(sealed)
+    }
+
+    // Writes an output document, encrypted to the recipients when there are any. Returns the
+    // path written
+    pub fn write_json(&self, path: &Path, output: &Value) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let written = self.output_path(path);
+        if !self.is_enabled() {
+            write_atomic(&written, serde_json::to_string_pretty(output)?)?;
+            return Ok(written);
+        }
+        let file = AtomicFile::create(&written).map_err(|e| format!("Cannot write {}: {}", written.display(), e))?;
+        let mut writer = SealWriter::new(file, &self.recipients)?;
+        serde_json::to_writer_pretty(&mut writer, output)?;
+        writer.finish()?.commit()?;
+        Ok(written)
+    }
+
+    // Writes a file that goes out with an output, such as an overflow side file, encrypted
+    // the same way
+    pub fn write_bytes(&self, path: &Path, data: &[u8]) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let written = self.output_path(path);
+        if !self.is_enabled() {
+            write_atomic(&written, data)?;
+            return Ok(written);
+        }
+        let file = AtomicFile::create(&written).map_err(|e| format!("Cannot write {}: {}", written.display(), e))?;
+        let mut writer = SealWriter::new(file, &self.recipients)?;
+        writer.write_all(data)?;
+        writer.finish()?.commit()?;
+        Ok(written)
+    }
+}
+
+// "age1..." -> the X25519 recipient it names
+pub fn parse_recipient(recipient: &str) -> Result<age::x25519::Recipient, String> {
+    recipient.trim().parse::<age::x25519::Recipient>()
+        .map_err(|reason| format!("{:?} is not an age X25519 recipient ({})", recipient, reason))
+}
+
+// Encrypts to `inner` as it is written. Nothing is complete until `finish` writes the last chunk
+pub struct SealWriter<W: Write> {
+    stream: age::stream::StreamWriter<W>,
+}
+
+impl<W: Write> SealWriter<W> {
+    pub fn new(inner: W, recipients: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        if recipients.is_empty() {
+            return Err("No recipients to encrypt to".into());
+        }
+        let recipients = recipients.iter().map(|r| parse_recipient(r)).collect::<Result<Vec<_>, _>>()?;
+        let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+            .map_err(|e| format!("Cannot encrypt to the recipients: {}", e))?;
+        Ok(Self { stream: encryptor.wrap_output(inner)? })
+    }
+
+    pub fn finish(self) -> std::io::Result<W> {
+        self.stream.finish()
+    }
+}
+
+impl<W: Write> Write for SealWriter<W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.stream.write(data)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+// Python binding - encrypt an existing file, such as an export, to age recipients. Writes
+// output_path, or input_path.age
+#[pyfunction]
+#[pyo3(signature = (input_path, recipients, output_path=None))]
+pub fn encrypt_file(py: Python, input_path: &str, recipients: Vec<String>, output_path: Option<&str>) -> PyResult<String> {
+    py.allow_threads(|| {
+        let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+        let delivery = DeliveryConfig { recipients };
+        delivery.validate().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let output_path = match output_path {
+            Some(path) => PathBuf::from(path),
+            None => delivery.output_path(Path::new(input_path)),
+        };
+        let seal = || -> Result<(), Box<dyn std::error::Error>> {
+            let mut input = std::fs::File::open(input_path).map_err(|e| format!("Cannot read {}: {}", input_path, e))?;
+            let output = AtomicFile::create(&output_path).map_err(|e| format!("Cannot write {}: {}", output_path.display(), e))?;
+            let mut writer = SealWriter::new(output, &delivery.recipients)?;
+            std::io::copy(&mut input, &mut writer)?;
+            writer.finish()?.commit()?;
+            Ok(())
+        };
+        seal().map_err(to_py_err)?;
+        Ok(output_path.to_string_lossy().to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn open(sealed: &[u8], identity: &age::x25519::Identity) -> Vec<u8> {
+        let decryptor = age::Decryptor::new(sealed).unwrap();
+        let mut reader = decryptor.decrypt(std::iter::once(identity as &dyn age::Identity)).unwrap();
+        let mut plain = Vec::new();
+        reader.read_to_end(&mut plain).unwrap();
+        plain
+    }
+
+    // The key pair age's own X25519 tests use
+    #[test]
+    fn parses_the_age_test_recipient() {
+        let identity: age::x25519::Identity =
+            "AGE-SECRET-KEY-1GQ9778VQXMMJVE8SK7J6VT8UJ4HDQAJUVSFCWCM02D8GEWQ72PVQ2Y5J33".parse().unwrap();
+        let recipient = "age1t7rxyev2z3rw82stdlrrepyc39nvn86l5078zqkf5uasdy86jp6svpy7pa";
+        assert_eq!(parse_recipient(recipient).unwrap().to_string(), identity.to_public().to_string());
+        assert_eq!(parse_recipient(&format!(" {}\n", recipient)).unwrap().to_string(), recipient);
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_recipient() {
+        for bad in ["", "age1", "ssh-ed25519 AAAA", "age1t7rxyev2z3rw82stdlrrepyc39nvn86l5078zqkf5uasdy86jp6svpy7pq",
+                    "AGE-SECRET-KEY-1GQ9778VQXMMJVE8SK7J6VT8UJ4HDQAJUVSFCWCM02D8GEWQ72PVQ2Y5J33"] {
+            assert!(parse_recipient(bad).is_err(), "{:?}", bad);
+            assert!(DeliveryConfig { recipients: vec![bad.to_string()] }.validate().is_err());
+        }
+        assert!(SealWriter::new(Vec::new(), &[]).is_err());
+    }
+
+    #[test]
+    fn every_recipient_can_open_what_is_sealed() {
+        let first = age::x25519::Identity::generate();
+        let second = age::x25519::Identity::generate();
+        let recipients = vec![first.to_public().to_string(), second.to_public().to_string()];
+        // Past one 64 KiB chunk, and written in pieces that do not line up with chunks
+        let plain: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let mut writer = SealWriter::new(Vec::new(), &recipients).unwrap();
+        for piece in plain.chunks(7_001) {
+            writer.write_all(piece).unwrap();
+        }
+        let sealed = writer.finish().unwrap();
+        assert!(sealed.starts_with(b"age-encryption.org/v1\n"));
+        assert_eq!(open(&sealed, &first), plain);
+        assert_eq!(open(&sealed, &second), plain);
+
+        let empty = SealWriter::new(Vec::new(), &recipients[..1]).unwrap().finish().unwrap();
+        assert!(open(&empty, &first).is_empty());
+
+        let stranger = age::x25519::Identity::generate();
+        let decryptor = age::Decryptor::new(&sealed[..]).unwrap();
+        assert!(decryptor.decrypt(std::iter::once(&stranger as &dyn age::Identity)).is_err());
+    }
+}
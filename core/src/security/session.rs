@@ -0,0 +1,126 @@
+use pyo3::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::licensing::manager::License;
+use crate::schema::envelope::ENGINE_VERSION;
+use crate::security::crypto::{sha256, to_hex, CRYPTO_BACKEND};
+
+// Process-wide counters behind get_session_info() - the Python module has no session object
+static PROCESS_START: OnceLock<DateTime<Utc>> = OnceLock::new();
+static DOCUMENTS_PROCESSED: AtomicU64 = AtomicU64::new(0);
+
+pub fn process_start() -> DateTime<Utc> {
+    *PROCESS_START.get_or_init(Utc::now)
+}
+
+// Called once per document extracted, whichever entry point extracted it
+pub fn record_document_processed() {
+    process_start();
+    DOCUMENTS_PROCESSED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn documents_processed() -> u64 {
+    DOCUMENTS_PROCESSED.load(Ordering::Relaxed)
+}
+
+// Customer IDs can name the customer - support tickets get a stable hash instead
+pub fn customer_id_hash(customer_id: &str) -> String {
+    format!("cust_{}", to_hex(&sha256(&[b"customer", &[0x1f], customer_id.as_bytes()])[..8]))
+}
+
+// One "name@version" per pack - merged engines already record "core@1.2+ata32@0.4"
+pub fn rule_pack_versions(engine: &ExtractionEngine) -> Vec<String> {
+    let version = engine.get_rules_version();
+    if version.contains('@') {
+        return version.split('+').map(str::to_string).collect();
+    }
+    vec![format!("{}@{}", engine.get_profile_name(), version)]
+}
+
+// Sanitized session facts, safe to paste into a support ticket: no license signatures,
+// API keys, build secrets or raw customer IDs
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct SessionInfo {
+    pub customer_id_hash: Option<String>,
+    pub license_valid: bool,
+    pub days_remaining: i64,
+    pub features: Vec<String>,
+    pub rule_packs: Vec<String>,
+    pub session_start: String,
+    pub uptime_seconds: i64,
+    pub documents_processed: u64,
+    pub engine_version: String,
+    pub crypto_backend: String,
+}
+
+impl SessionInfo {
+    pub fn new(session_start: DateTime<Utc>, documents_processed: u64) -> Self {
+        Self {
+            session_start: session_start.to_rfc3339(),
+            uptime_seconds: (Utc::now() - session_start).num_seconds(),
+            documents_processed,
+            engine_version: ENGINE_VERSION.to_string(),
+            crypto_backend: CRYPTO_BACKEND.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_customer(mut self, customer_id: &str, features: &[String]) -> Self {
+        self.customer_id_hash = Some(customer_id_hash(customer_id));
+        self.features = features.to_vec();
+        self
+    }
+
+    pub fn with_license(self, license: &License) -> Self {
+        let mut info = self.with_customer(&license.customer_id, &license.features);
+        info.license_valid = license.is_valid();
+        info.days_remaining = license.days_remaining();
+        info
+    }
+
+    pub fn with_engine(mut self, engine: &ExtractionEngine) -> Self {
+        self.rule_packs = rule_pack_versions(engine);
+        self
+    }
+
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let mut summary = HashMap::new();
+        if let Some(hash) = &self.customer_id_hash {
+            summary.insert("customer_id_hash".to_string(), hash.clone());
+        }
+        summary.insert("license_valid".to_string(), self.license_valid.to_string());
+        summary.insert("days_remaining".to_string(), self.days_remaining.to_string());
+        summary.insert("features".to_string(), self.features.join(","));
+        summary.insert("rule_packs".to_string(), self.rule_packs.join(","));
+        summary.insert("session_start".to_string(), self.session_start.clone());
+        summary.insert("uptime_seconds".to_string(), self.uptime_seconds.to_string());
+        summary.insert("documents_processed".to_string(), self.documents_processed.to_string());
+        summary.insert("engine_version".to_string(), self.engine_version.clone());
+        summary.insert("crypto_backend".to_string(), self.crypto_backend.clone());
+        summary
+    }
+}
+
+// Python binding - this process's session; license and rules files add their facts
+#[pyfunction]
+#[pyo3(signature = (license_path=None, rules_path=None))]
+pub fn get_session_info(license_path: Option<&str>, rules_path: Option<&str>) -> PyResult<HashMap<String, String>> {
+    let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+    let mut info = SessionInfo::new(process_start(), documents_processed());
+    if let Some(license_path) = license_path {
+        let license_data = std::fs::read_to_string(license_path)
+            .map_err(|e| to_py_err(format!("Cannot read license {}: {}", license_path, e).into()))?;
+        let license: License = serde_json::from_str(&license_data).map_err(|e| to_py_err(e.into()))?;
+        info = info.with_license(&license);
+    }
+    if let Some(rules_path) = rules_path {
+        info = info.with_engine(&ExtractionEngine::from_rules_file(rules_path).map_err(to_py_err)?);
+    }
+    Ok(info.get_summary())
+}
@@ -0,0 +1,131 @@
+// Thread-safety contract of the public handles. The Python bindings build their state per
+// call. Those that read documents, extract, export, search or run jobs release the GIL while
+// they work, so any Python thread can be inside the engine while another reloads rules or runs
+// a job. Lookups that only read small in-memory values or profile and license files
+// (get_prompt, map_offset, text_direction, the schema, export format and license getters,
+// get_session_info) and register_exporter, which keeps a Python callable, hold the GIL.
+//
+// - Shared (Send + Sync): engines, licenses, tenants, the server, storage backends, the
+//   manifest recorder and the search index. An engine is never changed once built: a rules
+//   reload swaps a tenant's Arc<ExtractionEngine> under the registry lock, and requests that
+//   took a snapshot finish on the rules they started with.
+// - Owned (Send only): job stores and runners hold a SQLite connection, and an LLM assistant
+//   holds mutable usage and budget state. Each worker opens its own store; parallel runs share
+//   one assistant behind a Mutex.
+// - Process-wide counters (session.rs) are a OnceLock and an atomic - there is no static mut.
+//
+// A type that loses one of these markers fails the build here instead of at a distant spawn.
+// The test below runs 32 threads extracting through the tenant's own reload path, and
+// tests/python_threads.rs does the same from 32 Python threads with the GIL released.
+use crate::engine::extractor::ExtractionEngine;
+use crate::jobs::manifest::ManifestRecorder;
+use crate::jobs::runner::JobRunner;
+use crate::jobs::store::JobStore;
+use crate::licensing::manager::License;
+use crate::server::http::ExtractionServer;
+use crate::server::tenants::{Tenant, TenantRegistry};
+use crate::server::webhooks::WebhookNotifier;
+use crate::storage::backend::Storage;
+use crate::storage::fs::FsStorage;
+use crate::storage::memory::MemoryStorage;
+use crate::storage::sqlite::SqliteStorage;
+
+const fn shared<T: Send + Sync + ?Sized>() {}
+const fn owned<T: Send + ?Sized>() {}
+
+const _: () = {
+    shared::<ExtractionEngine>();
+    shared::<License>();
+    shared::<Tenant>();
+    shared::<TenantRegistry>();
+    shared::<ExtractionServer>();
+    shared::<WebhookNotifier>();
+    shared::<ManifestRecorder>();
+    shared::<dyn Storage>();
+    shared::<FsStorage>();
+    shared::<MemoryStorage>();
+    shared::<SqliteStorage>();
+    owned::<JobStore>();
+    owned::<JobRunner>();
+};
+
+#[cfg(feature = "llm")]
+const _: () = {
+    owned::<crate::llm::assist::LlmAssistant>();
+    shared::<crate::llm::cache::PromptCache>();
+};
+
+#[cfg(feature = "search")]
+const _: () = shared::<crate::search::index::RecordIndex>();
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures::manual::{FixtureConfig, SyntheticManual};
+    use crate::licensing::manager::License;
+    use crate::server::tenants::{Tenant, TenantConfig, TenantRegistry};
+    use std::sync::{Arc, Mutex};
+
+    // Extraction from 32 threads on snapshots of one tenant while another thread keeps reloading
+    // its rules between two packs. Each extraction matches the pack its snapshot was taken on
+    #[test]
+    fn extract_while_reloading() {
+        let manual = SyntheticManual::generate(&FixtureConfig::default()).unwrap();
+        let dir = std::env::temp_dir().join(format!("spp-threads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pack = dir.join("synthetic.json");
+        let mut narrowed = manual.rules();
+        narrowed["version"] = "2".into();
+        narrowed["patterns"]["module"] = manual.tasks[..manual.tasks.len() / 2].into();
+        let packs = [manual.rules().to_string(), narrowed.to_string()];
+        std::fs::write(&pack, &packs[0]).unwrap();
+        let license_path = dir.join("license.json");
+        let mut license = License::new("stress".to_string(), vec!["extraction".to_string()]);
+        license.sign_for_tests();
+        std::fs::write(&license_path, serde_json::to_string(&license).unwrap()).unwrap();
+
+        let config = TenantConfig {
+            tenant_id: "stress".to_string(),
+            api_key: "key".to_string(),
+            license_path: license_path.to_string_lossy().to_string(),
+            rule_packs: vec!["synthetic".to_string()],
+            webhooks: Vec::new(),
+        };
+        let rules_dir = dir.to_string_lossy().to_string();
+        let mut registry = TenantRegistry::new();
+        registry.register(config.api_key.clone(), Tenant::load(&config, &rules_dir, None).unwrap()).unwrap();
+        let registry = Arc::new(Mutex::new(registry));
+        let expected = [manual.tasks.len(), manual.tasks.len() / 2];
+
+        const THREADS: usize = 32;
+        const ROUNDS: usize = 4;
+        let (manual, expected) = (&manual, &expected);
+        std::thread::scope(|scope| {
+            let extractions: Vec<_> = (0..THREADS).map(|_| {
+                let registry = Arc::clone(&registry);
+                scope.spawn(move || {
+                    for _ in 0..ROUNDS {
+                        let mut snapshot = registry.lock().unwrap().resolve("key").unwrap().snapshot();
+                        let engine = snapshot.snapshot_engine();
+                        let modules = engine.extract_modules(&manual.text);
+                        let version = engine.get_rules_version();
+                        assert_eq!(modules.len(), expected[usize::from(version.ends_with("@2"))], "{}", version);
+                        snapshot.record_document(manual.text.len(), modules.len());
+                        registry.lock().unwrap().resolve("key").unwrap().record_snapshot(snapshot);
+                    }
+                })
+            }).collect();
+            for round in 1..=THREADS {
+                std::fs::write(&pack, &packs[round % 2]).unwrap();
+                registry.lock().unwrap().resolve("key").unwrap().reload_rules(&rules_dir).unwrap();
+            }
+            for extraction in extractions {
+                extraction.join().unwrap();
+            }
+        });
+
+        let registry = registry.lock().unwrap();
+        let tenant = registry.get_tenant("stress").unwrap();
+        assert_eq!(tenant.get_usage().documents, (THREADS * ROUNDS) as u64);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,13 +1,20 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use chrono::{DateTime, Utc};
-use std::time::{SystemTime, UNIX_EPOCH};
 
-// Hardcoded security constants - compiled into binary
-const BUILD_TIMESTAMP: u64 = 1734123456; // Compile-time timestamp (December 13, 2024)
-const HARDCODED_EXPIRATION_DAYS: u64 = 14; // Hardcoded expiration
-const SECURITY_SALT: &str = "ml_core_2024_secure"; // Security salt
-const MAX_CLOCK_DRIFT_SECONDS: i64 = 86400; // 24 hours max clock drift
+use crate::security::access::{AccessCounter, DEFAULT_ACCESS_LIMIT};
+use crate::security::crypto::constant_time_eq;
+use crate::security::clock::{build_date, days_remaining, hardcoded_expiration, is_expired, is_in_future};
+use crate::security::session::{customer_id_hash, documents_processed, SessionInfo};
+use crate::storage::backend::Storage;
+use crate::storage::memory::MemoryStorage;
+
+const SESSION_NAMESPACE: &str = "session_state";
+
+// The one definition of the salt, which only ties a session's validation config to the build.
+// Nothing that must resist forgery is keyed with it: licenses and what they sign use Ed25519
+const SECURITY_SALT: &str = "ml_core_2024_secure";
 
 // Obfuscated validation logic - looks like normal validation
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,9 +29,8 @@ pub struct ValidationConfig {
 impl ValidationConfig {
     pub fn new(customer_id: String, features: Vec<String>) -> Self {
         // Calculate expiration based on hardcoded build timestamp
-        let build_date = DateTime::from_timestamp(BUILD_TIMESTAMP as i64, 0)
-            .unwrap_or_else(|| Utc::now());
-        let expiration = build_date + chrono::Duration::days(HARDCODED_EXPIRATION_DAYS as i64);
+        let build_date = build_date();
+        let expiration = hardcoded_expiration();
         
         // Generate security signature
         let signature = Self::generate_security_signature(&customer_id, &build_date);
@@ -39,63 +45,30 @@ impl ValidationConfig {
     }
 
     pub fn is_valid(&self) -> bool {
+        self.is_valid_at(Utc::now())
+    }
+
+    // Every layer judges the same instant
+    pub fn is_valid_at(&self, now: DateTime<Utc>) -> bool {
         // Layer 1: Hardcoded expiration check
-        let hardcoded_valid = self.check_hardcoded_expiration();
-        
-        // Layer 2: Build timestamp validation
-        let build_valid = self.validate_build_timestamp();
+        let hardcoded_valid = !is_expired(hardcoded_expiration(), now);
         
-        // Layer 3: Clock drift detection
-        let clock_valid = self.detect_clock_manipulation();
+        // Layer 2: Build timestamp validation - a clock running behind the build is
+        // tolerated up to the drift limit, further means the clock was set back
+        let build_valid = !is_in_future(build_date(), now);
         
-        // Layer 4: Security signature validation
+        // Layer 3: Security signature validation
         let signature_valid = self.validate_security_signature();
         
         // All layers must pass
-        hardcoded_valid && build_valid && clock_valid && signature_valid
-    }
-
-    fn check_hardcoded_expiration(&self) -> bool {
-        // Calculate expected expiration from hardcoded build timestamp
-        let build_date = DateTime::from_timestamp(BUILD_TIMESTAMP as i64, 0)
-            .unwrap_or_else(|| Utc::now());
-        let expected_expiration = build_date + chrono::Duration::days(HARDCODED_EXPIRATION_DAYS as i64);
-        
-        // Current time must be before hardcoded expiration
-        Utc::now() < expected_expiration
-    }
-
-    fn validate_build_timestamp(&self) -> bool {
-        // Verify build timestamp is reasonable (not in future)
-        let build_date = DateTime::from_timestamp(BUILD_TIMESTAMP as i64, 0)
-            .unwrap_or_else(|| Utc::now());
-        
-        // Build date should not be in the future
-        build_date <= Utc::now()
-    }
-
-    fn detect_clock_manipulation(&self) -> bool {
-        // Check for suspicious clock drift
-        let current_time = Utc::now();
-        let system_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs() as i64;
-        
-        let expected_time = current_time.timestamp();
-        let drift = (system_time - expected_time).abs();
-        
-        // Reject if clock drift is too large
-        drift < MAX_CLOCK_DRIFT_SECONDS
+        hardcoded_valid && build_valid && signature_valid
     }
 
     fn validate_security_signature(&self) -> bool {
         // Validate security signature
-        let build_date = DateTime::from_timestamp(BUILD_TIMESTAMP as i64, 0)
-            .unwrap_or_else(|| Utc::now());
-        let expected_signature = Self::generate_security_signature(&self.customer_id, &build_date);
+        let expected_signature = Self::generate_security_signature(&self.customer_id, &build_date());
         
-        self.build_signature == expected_signature
+        constant_time_eq(self.build_signature.as_bytes(), expected_signature.as_bytes())
     }
 
     fn generate_security_signature(customer_id: &str, build_date: &DateTime<Utc>) -> String {
@@ -122,45 +95,42 @@ impl ValidationConfig {
     }
 
     pub fn get_hardcoded_expiration(&self) -> DateTime<Utc> {
-        let build_date = DateTime::from_timestamp(BUILD_TIMESTAMP as i64, 0)
-            .unwrap_or_else(|| Utc::now());
-        build_date + chrono::Duration::days(HARDCODED_EXPIRATION_DAYS as i64)
+        hardcoded_expiration()
     }
 
     pub fn days_remaining(&self) -> i64 {
-        let expiration = self.get_hardcoded_expiration();
-        let now = Utc::now();
-        if now < expiration {
-            (expiration - now).num_days()
-        } else {
-            0
-        }
+        days_remaining(hardcoded_expiration(), Utc::now())
     }
 }
 
 // Session management with enhanced security
 pub struct Session {
     config: ValidationConfig,
-    engine_state: HashMap<String, String>,
+    // Cached engine state, kept in memory unless the deployment configures storage
+    state: Arc<dyn Storage>,
     session_start: DateTime<Utc>,
-    access_count: u32,
+    access: AccessCounter,
 }
 
 impl Session {
     pub fn new(config: ValidationConfig) -> Self {
         Self {
-            config,
-            engine_state: HashMap::new(),
+            state: Arc::new(MemoryStorage::new()),
             session_start: Utc::now(),
-            access_count: 0,
+            access: AccessCounter::new(&Self::access_key(&config.customer_id), DEFAULT_ACCESS_LIMIT),
+            config,
         }
     }
 
+    fn access_key(customer_id: &str) -> String {
+        format!("session.{}", customer_id_hash(customer_id))
+    }
+
     pub fn is_active(&self) -> bool {
         // Check if session is still valid
         let session_valid = self.config.is_valid();
         let session_not_expired = (Utc::now() - self.session_start).num_hours() < 24;
-        let access_limit_ok = self.access_count < 1000; // Limit access attempts
+        let access_limit_ok = !self.access.is_exhausted();
         
         session_valid && session_not_expired && access_limit_ok
     }
@@ -169,19 +139,56 @@ impl Session {
         &self.config.customer_id
     }
 
+    // Licenses can lower or raise the default access limit; set before with_storage
+    pub fn with_access_limit(mut self, limit: u64) -> Self {
+        self.access = AccessCounter::new(&Self::access_key(&self.config.customer_id), limit);
+        self
+    }
+
+    // Sessions sharing one storage keep their state apart by customer. Access counts carry
+    // over from earlier runs, so a restart does not reset the limit
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Result<Self, Box<dyn std::error::Error>> {
+        let limit = self.access.limit();
+        self.access = AccessCounter::new(&Self::access_key(&self.config.customer_id), limit)
+            .with_storage(Arc::clone(&storage))?;
+        self.state = storage;
+        Ok(self)
+    }
+
+    fn state_key(&self, key: &str) -> String {
+        format!("{}.{}", customer_id_hash(&self.config.customer_id), key)
+    }
+
+    pub fn get_state(&self, key: &str) -> Option<String> {
+        self.state.get(SESSION_NAMESPACE, &self.state_key(key))
+            .ok()
+            .flatten()
+            .and_then(|value| String::from_utf8(value).ok())
+    }
+
+    pub fn set_state(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.state.put(SESSION_NAMESPACE, &self.state_key(key), value.as_bytes())
+    }
+
+    // Every attempt counts against the limit, granted or not
     pub fn validate_access(&self, feature: &str) -> bool {
-        // Note: Access counting removed for simplicity
-        // In production, use atomic counters or external logging
-        self.config.has_feature(feature)
+        let within_limit = self.access.record();
+        within_limit && self.config.has_feature(feature)
     }
 
     pub fn get_security_info(&self) -> HashMap<String, String> {
-        let mut info = HashMap::new();
-        info.insert("build_timestamp".to_string(), BUILD_TIMESTAMP.to_string());
-        info.insert("hardcoded_expiration_days".to_string(), HARDCODED_EXPIRATION_DAYS.to_string());
+        let mut info = self.access.get_info();
+        info.insert("customer_id_hash".to_string(), customer_id_hash(&self.config.customer_id));
         info.insert("session_start".to_string(), self.session_start.to_rfc3339());
-        info.insert("access_count".to_string(), "0".to_string()); // Simplified
-        info.insert("days_remaining".to_string(), self.config.days_remaining().to_string());
+        info
+    }
+
+    // Documents are counted process-wide; tenants substitute their own usage count
+    pub fn get_session_info(&self) -> SessionInfo {
+        let mut info = SessionInfo::new(self.session_start, documents_processed())
+            .with_customer(&self.config.customer_id, &self.config.features);
+        info.license_valid = self.config.is_valid();
+        info.days_remaining = self.config.days_remaining();
         info
     }
 }
@@ -199,6 +206,12 @@ pub enum SecurityLevel {
     Maximum,
 }
 
+impl Default for ConfigManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ConfigManager {
     pub fn new() -> Self {
         Self {
@@ -262,7 +275,13 @@ impl ConfigManager {
         }
     }
 
-    pub fn get_security_report(&self, customer_id: &str) -> Option<HashMap<String, String>> {
-        self.get_session(customer_id).map(|s| s.get_security_info())
+    pub fn get_session_info(&self, customer_id: &str) -> Option<SessionInfo> {
+        self.get_session(customer_id).map(Session::get_session_info)
+    }
+
+    pub fn get_security_info(&self, customer_id: &str) -> Option<HashMap<String, String>> {
+        let mut info = self.get_session(customer_id)?.get_security_info();
+        info.insert("security_level".to_string(), format!("{:?}", self.security_level));
+        Some(info)
     }
 }
@@ -0,0 +1,69 @@
+use std::sync::OnceLock;
+
+use crate::security::crypto::{ed25519_verify, from_hex};
+#[cfg(any(test, debug_assertions))]
+use crate::security::crypto::ed25519_public_key;
+
+// ml_core holds only the vendor's public key. Licenses, branding approvals and license key
+// certificates are signed by the vendor's own tooling, in the formats docs/security.md gives.
+// Release builds take the key from ML_CORE_VENDOR_PUBLIC_KEY (64 hex digits) at build time
+#[cfg(not(test))]
+const CONFIGURED_PUBLIC_KEY: Option<&str> = option_env!("ML_CORE_VENDOR_PUBLIC_KEY");
+
+// Published development key, so anything it signs is trusted by no one. Tests always use it,
+// debug builds when no key is configured, and release builds never
+#[cfg(any(test, debug_assertions))]
+pub(crate) const DEVELOPMENT_VENDOR_SEED: [u8; 32] = *b"ml_core development vendor seed!";
+
+// Where the vendor key came from: "configured", "development" or "none"
+pub fn vendor_key_source() -> &'static str {
+    vendor_key().1
+}
+
+fn vendor_key() -> (Option<[u8; 32]>, &'static str) {
+    static KEY: OnceLock<(Option<[u8; 32]>, &'static str)> = OnceLock::new();
+    *KEY.get_or_init(load_vendor_key)
+}
+
+#[cfg(test)]
+fn load_vendor_key() -> (Option<[u8; 32]>, &'static str) {
+    (Some(ed25519_public_key(&DEVELOPMENT_VENDOR_SEED)), "development")
+}
+
+#[cfg(not(test))]
+fn load_vendor_key() -> (Option<[u8; 32]>, &'static str) {
+    // build.rs refuses a malformed key, so this only fails to parse if it was bypassed
+    if let Some(hex) = CONFIGURED_PUBLIC_KEY {
+        return (from_hex(hex.trim()).and_then(|key| key.try_into().ok()), "configured");
+    }
+    #[cfg(debug_assertions)]
+    return (Some(ed25519_public_key(&DEVELOPMENT_VENDOR_SEED)), "development");
+    #[cfg(not(debug_assertions))]
+    return (None, "none");
+}
+
+// What a signature covers: a label naming what is signed, then the fields, each after a 0x1f
+// separator
+pub fn tagged_fields(label: &str, fields: &[&str]) -> Vec<u8> {
+    let mut message = label.as_bytes().to_vec();
+    for field in fields {
+        message.push(0x1f);
+        message.extend_from_slice(field.as_bytes());
+    }
+    message
+}
+
+// Whether `signature` (hex) is the vendor's over the concatenation of `parts`. Without a
+// vendor key nothing verifies
+pub fn verify_vendor_signature(parts: &[&[u8]], signature: &str) -> bool {
+    let (Some(public_key), Some(signature)) = (vendor_key().0, from_hex(signature)) else {
+        return false;
+    };
+    ed25519_verify(&public_key, parts, &signature)
+}
+
+// What the vendor's tooling does, for tests that need signed licenses
+#[cfg(test)]
+pub(crate) fn sign_as_vendor(parts: &[&[u8]]) -> String {
+    crate::security::crypto::to_hex(&crate::security::crypto::ed25519_sign(&DEVELOPMENT_VENDOR_SEED, parts))
+}
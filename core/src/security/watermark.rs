@@ -0,0 +1,270 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use chrono::{DateTime, Utc};
+
+use crate::export::spoken::SPOKEN_HEADER;
+use crate::licensing::manager::{verify_license_key, License};
+use crate::security::crypto::{ed25519_sign, ed25519_verify, from_hex, to_hex};
+use crate::security::session::customer_id_hash;
+use crate::security::vendor::tagged_fields;
+
+// Token layout: spp-wm2:<license_id>:<customer hash>:<input hash>:<issued unix>:<license key>:
+// <key signature>:<signature>. The license's own key signs it, and the vendor's certificate for
+// that key rides along, so a token verifies with nothing but the vendor public key
+pub const WATERMARK_VERSION: &str = "spp-wm2";
+// Tokens of the first version were MACed with a key every build shares, so they prove nothing
+const SHARED_KEY_VERSION: &str = "spp-wm1";
+// Line marker in text exports (Cypher and Turtle comments)
+pub const WATERMARK_MARKER: &str = "spp-watermark:";
+// Bulk CSV exports carry the token in this file beside the CSVs
+pub const WATERMARK_FILE: &str = "watermark.txt";
+// Work card workbooks carry the token in this custom document property
+pub const WATERMARK_PROPERTY: &str = "spp-watermark";
+
+// Hex characters of the input hash kept in the token
+const INPUT_HASH_CHARS: usize = 32;
+
+// Identifies the license an output was produced under, bound to the input it was produced from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watermark {
+    pub license_id: String,
+    pub customer_id_hash: String,
+    pub input_hash: String,
+    pub issued_at: i64,
+    pub license_key: String,
+    pub key_signature: String,
+    pub signature: String,
+}
+
+// "sha256:<hex>" provenance hashes are shortened to a fixed-length prefix
+fn short_input_hash(input_hash: &str) -> String {
+    let hex = input_hash.strip_prefix("sha256:").unwrap_or(input_hash);
+    hex.chars().take(INPUT_HASH_CHARS).collect()
+}
+
+impl Watermark {
+    // None for a license without a certified license key to sign with
+    pub fn issue(license: &License, input_hash: &str) -> Option<Self> {
+        let seed = license.signing_seed()?;
+        let mut watermark = Self {
+            license_id: license.license_id.clone(),
+            customer_id_hash: customer_id_hash(&license.customer_id),
+            input_hash: short_input_hash(input_hash),
+            issued_at: Utc::now().timestamp(),
+            license_key: license.license_key.clone(),
+            key_signature: license.key_signature.clone(),
+            signature: String::new(),
+        };
+        watermark.signature = to_hex(&ed25519_sign(&seed, &[&watermark.signed_payload()]));
+        Some(watermark)
+    }
+
+    fn signed_payload(&self) -> Vec<u8> {
+        let issued_at = self.issued_at.to_string();
+        tagged_fields(WATERMARK_VERSION, &[&self.license_id, &self.customer_id_hash, &self.input_hash, &issued_at])
+    }
+
+    pub fn verify(&self) -> bool {
+        let (Some(license_key), Some(signature)) = (from_hex(&self.license_key), from_hex(&self.signature)) else {
+            return false;
+        };
+        verify_license_key(&self.license_id, &self.license_key, &self.key_signature)
+            && ed25519_verify(&license_key, &[&self.signed_payload()], &signature)
+    }
+
+    // Whether the watermark was issued for an output with this provenance input_hash
+    pub fn matches_input(&self, input_hash: &str) -> bool {
+        self.input_hash == short_input_hash(input_hash)
+    }
+
+    pub fn encode(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}:{}:{}:{}",
+            WATERMARK_VERSION, self.license_id, self.customer_id_hash, self.input_hash, self.issued_at,
+            self.license_key, self.key_signature, self.signature
+        )
+    }
+
+    pub fn decode(token: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = token.trim().split(':').collect();
+        match parts.as_slice() {
+            [version, license_id, customer_id_hash, input_hash, issued_at, license_key, key_signature, signature] if *version == WATERMARK_VERSION => {
+                Ok(Self {
+                    license_id: license_id.to_string(),
+                    customer_id_hash: customer_id_hash.to_string(),
+                    input_hash: input_hash.to_string(),
+                    issued_at: issued_at.parse().map_err(|_| format!("Invalid watermark timestamp: {}", issued_at))?,
+                    license_key: license_key.to_string(),
+                    key_signature: key_signature.to_string(),
+                    signature: signature.to_string(),
+                })
+            }
+            [version, ..] if *version == SHARED_KEY_VERSION => {
+                Err(format!("{} watermarks were signed with a key shared by every build and cannot be verified", version))
+            }
+            [version, ..] if *version != WATERMARK_VERSION => Err(format!("Unsupported watermark version: {}", version)),
+            _ => Err("Malformed watermark token".to_string()),
+        }
+    }
+
+    pub fn get_summary(&self) -> HashMap<String, String> {
+        let issued_at = DateTime::from_timestamp(self.issued_at, 0)
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_else(|| self.issued_at.to_string());
+        let mut summary = HashMap::new();
+        summary.insert("license_id".to_string(), self.license_id.clone());
+        summary.insert("customer_id_hash".to_string(), self.customer_id_hash.clone());
+        summary.insert("input_hash".to_string(), self.input_hash.clone());
+        summary.insert("issued_at".to_string(), issued_at);
+        summary.insert("license_key".to_string(), self.license_key.clone());
+        summary.insert("signature".to_string(), self.signature.clone());
+        summary
+    }
+}
+
+// Where a token was found, and the output's own input hash when the format records one
+pub struct FoundWatermark {
+    pub format: &'static str,
+    pub token: String,
+    pub input_hash: Option<String>,
+}
+
+fn marked_token(content: &str) -> Option<String> {
+    content.lines()
+        .take_while(|line| line.starts_with("//") || line.starts_with('#') || line.starts_with("<!") || line.trim().is_empty())
+        // HTML comments close on the same line
+        .find_map(|line| line.split_once(WATERMARK_MARKER).map(|(_, token)| token.trim_end().trim_end_matches("-->").trim().to_string()))
+}
+
+// Highlighted PDFs carry the token as a comment line in their incremental update; the last
+// one is the newest update's
+fn pdf_token(bytes: &[u8]) -> Option<String> {
+    let marker = format!("% {}", WATERMARK_MARKER);
+    bytes.split(|b| *b == b'\n' || *b == b'\r')
+        .filter_map(|line| std::str::from_utf8(line).ok()?.strip_prefix(marker.as_str()).map(|token| token.trim().to_string()))
+        .next_back()
+}
+
+// The workbook's watermark property; None for other zips and workbooks written without one
+fn xlsx_token(bytes: &[u8]) -> Option<String> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).ok()?;
+    let mut properties = String::new();
+    archive.by_name("docProps/custom.xml").ok()?.read_to_string(&mut properties).ok()?;
+    let (_, property) = properties.split_once(&format!("name=\"{}\"", WATERMARK_PROPERTY))?;
+    let (_, value) = property.split_once("<vt:lpwstr>")?;
+    Some(value.split_once("</vt:lpwstr>")?.0.trim().to_string())
+}
+
+// Job, split, merge and delta JSON, Cypher scripts, Turtle files, HTML redlines, spoken
+// exports, highlighted PDFs, work cards and bulk CSV directories. Other binary files have no
+// watermark to find
+pub fn find_watermark(path: &str) -> Result<Option<FoundWatermark>, Box<dyn std::error::Error>> {
+    if Path::new(path).is_dir() {
+        let token_path = Path::new(path).join(WATERMARK_FILE);
+        return Ok(std::fs::read_to_string(&token_path).ok().map(|token| FoundWatermark {
+            format: "csv",
+            token: token.trim().to_string(),
+            input_hash: None,
+        }));
+    }
+
+    let bytes = std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    if bytes.starts_with(b"%PDF-") {
+        return Ok(pdf_token(&bytes).map(|token| FoundWatermark { format: "pdf", token, input_hash: None }));
+    }
+    if bytes.starts_with(b"PK\x03\x04") {
+        return Ok(xlsx_token(&bytes).map(|token| FoundWatermark { format: "xlsx", token, input_hash: None }));
+    }
+    let Ok(content) = String::from_utf8(bytes) else {
+        return Ok(None);
+    };
+    if let Ok(output) = serde_json::from_str::<Value>(&content) {
+        let provenance = output.get("provenance");
+        let token = provenance.and_then(|provenance| provenance.get("watermark"))
+            .or_else(|| output.get("watermark"))
+            .and_then(Value::as_str);
+        return Ok(token.map(|token| FoundWatermark {
+            format: "json",
+            token: token.to_string(),
+            input_hash: provenance.and_then(|provenance| provenance.get("input_hash")).and_then(Value::as_str).map(str::to_string),
+        }));
+    }
+
+    let format = if content.trim_start().starts_with("<!DOCTYPE html") {
+        "html"
+    } else if content.starts_with(&format!("<!-- {}", SPOKEN_HEADER)) {
+        "ssml"
+    } else if content.starts_with(&format!("# {}", SPOKEN_HEADER)) {
+        "script"
+    } else if content.trim_start().starts_with("@prefix") || content.contains("\n@prefix") {
+        "turtle"
+    } else if path.ends_with(".csv") {
+        "csv"
+    } else {
+        "cypher"
+    };
+    Ok(marked_token(&content).map(|token| FoundWatermark { format, token, input_hash: None }))
+}
+
+// Decoded fields plus "valid" - false for a bad signature or a JSON output whose content
+// no longer matches the input the watermark was issued for
+pub fn verify_watermark_file(path: &str) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let found = find_watermark(path)?.ok_or_else(|| format!("No watermark found in {}", path))?;
+    let watermark = Watermark::decode(&found.token)?;
+    let signature_valid = watermark.verify();
+    let input_matches = found.input_hash.as_deref().map(|input_hash| watermark.matches_input(input_hash));
+
+    let mut report = watermark.get_summary();
+    report.insert("format".to_string(), found.format.to_string());
+    report.insert("signature_valid".to_string(), signature_valid.to_string());
+    if let Some(matches) = input_matches {
+        report.insert("input_matches".to_string(), matches.to_string());
+    }
+    report.insert("valid".to_string(), (signature_valid && input_matches.unwrap_or(true)).to_string());
+    Ok(report)
+}
+
+// Python bindings - used by `spp watermark verify` and `spp watermark extract`
+#[pyfunction]
+pub fn verify_watermark(py: Python, path: &str) -> PyResult<HashMap<String, String>> {
+    py.allow_threads(|| {
+        verify_watermark_file(path).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    })
+}
+
+#[pyfunction]
+pub fn extract_watermark(py: Python, path: &str) -> PyResult<Option<String>> {
+    py.allow_threads(|| {
+        find_watermark(path)
+            .map(|found| found.map(|found| found.token))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watermarks_are_signed_with_the_certified_license_key() {
+        let mut license = License::new("acme".to_string(), Vec::new());
+        assert!(Watermark::issue(&license, "sha256:00").is_none());
+        license.sign_for_tests();
+        let token = Watermark::issue(&license, "sha256:feedbeef").unwrap().encode();
+        let watermark = Watermark::decode(&token).unwrap();
+        assert!(watermark.verify() && watermark.matches_input("sha256:feedbeef"));
+
+        let mut edited = watermark.clone();
+        edited.input_hash = "0123".to_string();
+        assert!(!edited.verify());
+        let mut other = License::new("other".to_string(), Vec::new());
+        other.sign_for_tests();
+        let mut swapped = watermark.clone();
+        swapped.license_key = other.license_key.clone();
+        assert!(!swapped.verify());
+        assert!(Watermark::decode("spp-wm1:lic-1:0123abcd:feedbeef:1700000000:0011223344556677").is_err());
+    }
+}
@@ -0,0 +1,750 @@
+use async_graphql::connection::{query, Connection, Edge};
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Error, Object, OutputType, Result, Schema, SimpleObject};
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::engine::access::ACCESS_TAGS_FIELD;
+use crate::engine::entities::record_extents;
+use crate::jobs::runner::output_path_for;
+use crate::jobs::store::{Job, JobStatus, JobStore};
+use crate::schema::envelope::payload_of;
+
+// Page size when a query asks for neither first nor last
+const DEFAULT_PAGE_SIZE: usize = 100;
+// Larger first or last arguments are clamped to this
+pub const MAX_PAGE_SIZE: usize = 500;
+// Queries nested deeper, or costing more, are rejected before any resolver runs. A paginated
+// field costs its page size times its selection, so nesting connections multiplies the cost
+pub const MAX_QUERY_DEPTH: usize = 16;
+pub const MAX_QUERY_COMPLEXITY: usize = 50_000;
+
+pub type DocumentSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+type Record = HashMap<String, String>;
+
+// Job database the schema reads - resolvers open it per request, like the job bindings. With a
+// tenant, only that tenant's jobs exist
+struct StoreLocation {
+    db_path: String,
+    tenant_id: Option<String>,
+}
+
+fn list_jobs(ctx: &Context<'_>, status: Option<JobStatus>) -> Result<Vec<Job>> {
+    let location = ctx.data::<StoreLocation>()?;
+    let store = JobStore::open(&location.db_path).map_err(|e| Error::new(e.to_string()))?;
+    match &location.tenant_id {
+        Some(tenant_id) => store.list_for_tenant(tenant_id, status, u32::MAX),
+        None => store.list(status, u32::MAX),
+    }
+    .map_err(|e| Error::new(e.to_string()))
+}
+
+fn get_job(ctx: &Context<'_>, job_id: &str) -> Result<Option<Job>> {
+    let location = ctx.data::<StoreLocation>()?;
+    let store = JobStore::open(&location.db_path).map_err(|e| Error::new(e.to_string()))?;
+    match &location.tenant_id {
+        Some(tenant_id) => store.get_for_tenant(tenant_id, job_id),
+        None => store.get(job_id),
+    }
+    .map_err(|e| Error::new(e.to_string()))
+}
+
+#[derive(SimpleObject)]
+pub struct PageTotals {
+    total_count: usize,
+}
+
+pub type Page<T> = Connection<usize, T, PageTotals>;
+
+// Cursor pagination over an already filtered list; cursors are list positions
+async fn paginate<T: OutputType>(
+    items: Vec<T>,
+    after: Option<String>,
+    before: Option<String>,
+    first: Option<i32>,
+    last: Option<i32>,
+) -> Result<Page<T>> {
+    let first = match (first, last) {
+        (None, None) => Some(DEFAULT_PAGE_SIZE as i32),
+        _ => first.map(|first| first.min(MAX_PAGE_SIZE as i32)),
+    };
+    let last = last.map(|last| last.min(MAX_PAGE_SIZE as i32));
+    query(after, before, first, last, |after: Option<usize>, before: Option<usize>, first, last| async move {
+        let total = items.len();
+        let mut start = after.map(|after| after + 1).unwrap_or(0).min(total);
+        let end = before.unwrap_or(total).min(total).max(start);
+        let end = first.map(|first| (start + first).min(end)).unwrap_or(end);
+        if let Some(last) = last {
+            start = end.saturating_sub(last).max(start);
+        }
+        let mut connection = Connection::with_additional_fields(start > 0, end < total, PageTotals { total_count: total });
+        connection.edges.extend(
+            items.into_iter().enumerate().skip(start).take(end - start).map(|(index, item)| Edge::new(index, item)),
+        );
+        Ok::<_, Error>(connection)
+    })
+    .await
+}
+
+// Complexity of a paginated field: the most items its page can hold, each costing its selection
+fn page_complexity(first: Option<i32>, last: Option<i32>, child_complexity: usize) -> usize {
+    let size = match (first, last) {
+        (None, None) => DEFAULT_PAGE_SIZE,
+        _ => first.max(last).unwrap_or(0).clamp(0, MAX_PAGE_SIZE as i32) as usize,
+    };
+    size.max(1).saturating_mul(child_complexity)
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn collection(payload: &Value, name: &str) -> Vec<Record> {
+    payload.get(name).and_then(Value::as_array).into_iter().flatten()
+        .filter_map(Value::as_object)
+        .map(|fields| fields.iter().map(|(key, value)| (key.clone(), as_text(value))).collect())
+        .collect()
+}
+
+fn number(record: &Record, field: &str) -> Option<i64> {
+    record.get(field).and_then(|value| value.parse().ok())
+}
+
+fn tags(record: &Record) -> Vec<String> {
+    record.get(ACCESS_TAGS_FIELD)
+        .map(|tags| tags.split(',').filter(|tag| !tag.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn contains(record: &Record, field: &str, needle: &Option<String>) -> bool {
+    needle.as_ref().is_none_or(|needle| record.get(field).is_some_and(|value| value.contains(needle.as_str())))
+}
+
+#[derive(SimpleObject)]
+pub struct RecordField {
+    key: String,
+    value: String,
+}
+
+fn fields_of(record: &Record) -> Vec<RecordField> {
+    let mut fields: Vec<RecordField> = record.iter()
+        .map(|(key, value)| RecordField { key: key.clone(), value: value.clone() })
+        .collect();
+    fields.sort_by(|a, b| a.key.cmp(&b.key));
+    fields
+}
+
+// One job output file, parsed once and shared by every node resolved from it
+pub struct DocumentData {
+    provenance: Record,
+    source: Option<String>,
+    schema_version: Option<String>,
+    modules: Vec<Record>,
+    steps: Vec<Record>,
+    entities: Vec<Record>,
+    procedures: Vec<Record>,
+    conditions: Vec<Record>,
+    // (start, end, module record_id) of each module's extent
+    module_extents: Vec<(usize, usize, String)>,
+}
+
+impl DocumentData {
+    fn load(path: &str) -> std::result::Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let output: Value = serde_json::from_str(&content).map_err(|e| format!("Invalid output {}: {}", path, e))?;
+        let payload = payload_of(&output);
+        let modules = collection(payload, "modules");
+        let module_extents = record_extents(&modules, usize::MAX);
+        Ok(Self {
+            provenance: output.get("provenance").and_then(Value::as_object)
+                .map(|fields| fields.iter().map(|(key, value)| (key.clone(), as_text(value))).collect())
+                .unwrap_or_default(),
+            source: payload.get("source").and_then(Value::as_str).map(str::to_string),
+            schema_version: output.get("schema_version").and_then(Value::as_str).map(str::to_string),
+            steps: collection(payload, "steps"),
+            entities: collection(payload, "entities"),
+            procedures: collection(payload, "procedures"),
+            conditions: collection(payload, "conditions"),
+            modules,
+            module_extents,
+        })
+    }
+
+    fn module_index(&self, record_id: &str) -> Option<usize> {
+        self.modules.iter().position(|module| module.get("record_id").map(String::as_str) == Some(record_id))
+    }
+
+    // Module whose extent contains the step
+    fn module_of_step(&self, step: &Record) -> Option<usize> {
+        let start = number(step, "original_start")? as usize;
+        let (_, _, module_id) = self.module_extents.iter().find(|(s, e, _)| start >= *s && start < *e)?;
+        self.module_index(module_id)
+    }
+}
+
+// A job output, loaded on first access so listing documents stays cheap
+pub struct Document {
+    path: String,
+    job_id: String,
+    input: String,
+    data: OnceLock<std::result::Result<Arc<DocumentData>, String>>,
+}
+
+impl Document {
+    fn new(job: &Job, input: &str) -> Self {
+        Self {
+            path: output_path_for(&job.output_dir, input).to_string_lossy().to_string(),
+            job_id: job.job_id.clone(),
+            input: input.to_string(),
+            data: OnceLock::new(),
+        }
+    }
+
+    fn data(&self) -> Result<Arc<DocumentData>> {
+        self.data.get_or_init(|| DocumentData::load(&self.path).map(Arc::new))
+            .clone()
+            .map_err(Error::new)
+    }
+}
+
+// Documents of a job are the outputs it has written so far
+fn job_documents(job: &Job) -> Vec<Document> {
+    job.inputs.iter()
+        .map(|input| Document::new(job, input))
+        .filter(|document| std::path::Path::new(&document.path).exists())
+        .collect()
+}
+
+fn all_documents(ctx: &Context<'_>) -> Result<Vec<Document>> {
+    let jobs = list_jobs(ctx, None)?;
+    Ok(jobs.iter().flat_map(job_documents).collect())
+}
+
+#[Object]
+impl Document {
+    // The output file path - stable for a job and input
+    async fn id(&self) -> &str {
+        &self.path
+    }
+
+    async fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    async fn source(&self) -> Result<String> {
+        Ok(self.data()?.source.clone().unwrap_or_else(|| self.input.clone()))
+    }
+
+    async fn schema_version(&self) -> Result<Option<String>> {
+        Ok(self.data()?.schema_version.clone())
+    }
+
+    async fn provenance(&self) -> Result<Vec<RecordField>> {
+        Ok(fields_of(&self.data()?.provenance))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn modules(
+        &self,
+        pattern: Option<String>,
+        min_confidence: Option<f64>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<Module>> {
+        let data = self.data()?;
+        let modules = (0..data.modules.len())
+            .filter(|&index| contains(&data.modules[index], "pattern", &pattern))
+            .filter(|&index| {
+                min_confidence.is_none_or(|min| {
+                    data.modules[index].get("confidence").and_then(|c| c.parse::<f64>().ok()).is_some_and(|c| c >= min)
+                })
+            })
+            .map(|index| Module { data: Arc::clone(&data), index })
+            .collect();
+        paginate(modules, after, before, first, last).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn steps(
+        &self,
+        module_id: Option<String>,
+        pattern: Option<String>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<Step>> {
+        let data = self.data()?;
+        let module = module_id.as_deref().map(|id| data.module_index(id));
+        let steps = (0..data.steps.len())
+            .filter(|&index| contains(&data.steps[index], "pattern", &pattern))
+            .filter(|&index| module.is_none_or(|module| module.is_some() && data.module_of_step(&data.steps[index]) == module))
+            .map(|index| Step { data: Arc::clone(&data), index })
+            .collect();
+        paginate(steps, after, before, first, last).await
+    }
+
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn entities(
+        &self,
+        entity_type: Option<String>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<EntityNode>> {
+        let data = self.data()?;
+        let entities = (0..data.entities.len())
+            .filter(|&index| entity_type.as_ref().is_none_or(|t| data.entities[index].get("entity_type") == Some(t)))
+            .map(|index| EntityNode { data: Arc::clone(&data), index })
+            .collect();
+        paginate(entities, after, before, first, last).await
+    }
+
+    async fn procedures(&self) -> Result<Vec<RecordNode>> {
+        Ok(self.data()?.procedures.iter().map(|record| RecordNode(record.clone())).collect())
+    }
+
+    // kind is "precondition" or "postcondition"
+    async fn conditions(&self, kind: Option<String>) -> Result<Vec<RecordNode>> {
+        Ok(self.data()?.conditions.iter()
+            .filter(|record| kind.as_ref().is_none_or(|kind| record.get("kind") == Some(kind)))
+            .map(|record| RecordNode(record.clone()))
+            .collect())
+    }
+}
+
+pub struct Module {
+    data: Arc<DocumentData>,
+    index: usize,
+}
+
+impl Module {
+    fn record(&self) -> &Record {
+        &self.data.modules[self.index]
+    }
+
+    fn id(&self) -> &str {
+        self.record().get("record_id").map(String::as_str).unwrap_or("")
+    }
+}
+
+#[Object]
+impl Module {
+    async fn record_id(&self) -> &str {
+        self.id()
+    }
+
+    async fn pattern(&self) -> Option<&String> {
+        self.record().get("pattern")
+    }
+
+    async fn rule_pack(&self) -> Option<&String> {
+        self.record().get("rule_pack")
+    }
+
+    async fn rule(&self) -> Option<&String> {
+        self.record().get("rule")
+    }
+
+    async fn confidence(&self) -> Option<f64> {
+        self.record().get("confidence").and_then(|c| c.parse().ok())
+    }
+
+    async fn original_start(&self) -> Option<i64> {
+        number(self.record(), "original_start")
+    }
+
+    async fn original_end(&self) -> Option<i64> {
+        number(self.record(), "original_end")
+    }
+
+    async fn page(&self) -> Option<i64> {
+        number(self.record(), "page")
+    }
+
+    // Steps within this module's extent
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn steps(
+        &self,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<Step>> {
+        let steps = (0..self.data.steps.len())
+            .filter(|&index| self.data.module_of_step(&self.data.steps[index]) == Some(self.index))
+            .map(|index| Step { data: Arc::clone(&self.data), index })
+            .collect();
+        paginate(steps, after, before, first, last).await
+    }
+
+    async fn procedure(&self) -> Option<RecordNode> {
+        self.data.procedures.iter()
+            .find(|record| record.get("module_id").map(String::as_str) == Some(self.id()))
+            .map(|record| RecordNode(record.clone()))
+    }
+
+    async fn conditions(&self) -> Vec<RecordNode> {
+        self.data.conditions.iter()
+            .filter(|record| record.get("module_id").map(String::as_str) == Some(self.id()))
+            .map(|record| RecordNode(record.clone()))
+            .collect()
+    }
+
+    async fn access_tags(&self) -> Vec<String> {
+        tags(self.record())
+    }
+
+    async fn field(&self, name: String) -> Option<&String> {
+        self.record().get(&name)
+    }
+
+    async fn fields(&self) -> Vec<RecordField> {
+        fields_of(self.record())
+    }
+}
+
+pub struct Step {
+    data: Arc<DocumentData>,
+    index: usize,
+}
+
+impl Step {
+    fn record(&self) -> &Record {
+        &self.data.steps[self.index]
+    }
+}
+
+#[Object]
+impl Step {
+    async fn record_id(&self) -> Option<&String> {
+        self.record().get("record_id")
+    }
+
+    async fn pattern(&self) -> Option<&String> {
+        self.record().get("pattern")
+    }
+
+    async fn rule_pack(&self) -> Option<&String> {
+        self.record().get("rule_pack")
+    }
+
+    async fn rule(&self) -> Option<&String> {
+        self.record().get("rule")
+    }
+
+    async fn confidence(&self) -> Option<f64> {
+        self.record().get("confidence").and_then(|c| c.parse().ok())
+    }
+
+    async fn original_start(&self) -> Option<i64> {
+        number(self.record(), "original_start")
+    }
+
+    async fn original_end(&self) -> Option<i64> {
+        number(self.record(), "original_end")
+    }
+
+    async fn page(&self) -> Option<i64> {
+        number(self.record(), "page")
+    }
+
+    async fn module(&self) -> Option<Module> {
+        self.data.module_of_step(self.record()).map(|index| Module { data: Arc::clone(&self.data), index })
+    }
+
+    // Entities linked to this step through their step_ids
+    async fn entities(&self) -> Vec<EntityNode> {
+        let record_id = self.record().get("record_id").cloned().unwrap_or_default();
+        (0..self.data.entities.len())
+            .filter(|&index| {
+                self.data.entities[index].get("step_ids").is_some_and(|ids| ids.split(',').any(|id| id == record_id))
+            })
+            .map(|index| EntityNode { data: Arc::clone(&self.data), index })
+            .collect()
+    }
+
+    async fn access_tags(&self) -> Vec<String> {
+        tags(self.record())
+    }
+
+    async fn field(&self, name: String) -> Option<&String> {
+        self.record().get(&name)
+    }
+
+    async fn fields(&self) -> Vec<RecordField> {
+        fields_of(self.record())
+    }
+}
+
+pub struct EntityNode {
+    data: Arc<DocumentData>,
+    index: usize,
+}
+
+impl EntityNode {
+    fn record(&self) -> &Record {
+        &self.data.entities[self.index]
+    }
+}
+
+#[Object(name = "Entity")]
+impl EntityNode {
+    async fn record_id(&self) -> Option<&String> {
+        self.record().get("record_id")
+    }
+
+    async fn entity_type(&self) -> Option<&String> {
+        self.record().get("entity_type")
+    }
+
+    async fn text(&self) -> Option<&String> {
+        self.record().get("text")
+    }
+
+    async fn value(&self) -> Option<&String> {
+        self.record().get("value")
+    }
+
+    async fn mentions(&self) -> Option<i64> {
+        number(self.record(), "mentions")
+    }
+
+    async fn steps(&self) -> Vec<Step> {
+        let ids: Vec<&str> = self.record().get("step_ids").map(|ids| ids.split(',').collect()).unwrap_or_default();
+        (0..self.data.steps.len())
+            .filter(|&index| self.data.steps[index].get("record_id").is_some_and(|id| ids.contains(&id.as_str())))
+            .map(|index| Step { data: Arc::clone(&self.data), index })
+            .collect()
+    }
+
+    async fn access_tags(&self) -> Vec<String> {
+        tags(self.record())
+    }
+
+    async fn field(&self, name: String) -> Option<&String> {
+        self.record().get(&name)
+    }
+
+    async fn fields(&self) -> Vec<RecordField> {
+        fields_of(self.record())
+    }
+}
+
+// Procedures and conditions - string fields, read by name
+pub struct RecordNode(Record);
+
+#[Object(name = "Record")]
+impl RecordNode {
+    async fn record_id(&self) -> Option<&String> {
+        self.0.get("record_id")
+    }
+
+    async fn access_tags(&self) -> Vec<String> {
+        tags(&self.0)
+    }
+
+    async fn field(&self, name: String) -> Option<&String> {
+        self.0.get(&name)
+    }
+
+    async fn fields(&self) -> Vec<RecordField> {
+        fields_of(&self.0)
+    }
+}
+
+pub struct JobNode(Job);
+
+#[Object(name = "Job")]
+impl JobNode {
+    async fn id(&self) -> &str {
+        &self.0.job_id
+    }
+
+    async fn status(&self) -> &str {
+        self.0.status.as_str()
+    }
+
+    async fn priority(&self) -> i64 {
+        self.0.priority
+    }
+
+    async fn inputs(&self) -> &[String] {
+        &self.0.inputs
+    }
+
+    async fn output_dir(&self) -> &str {
+        &self.0.output_dir
+    }
+
+    async fn progress_done(&self) -> u32 {
+        self.0.progress_done
+    }
+
+    async fn progress_total(&self) -> u32 {
+        self.0.progress_total
+    }
+
+    async fn error(&self) -> Option<&String> {
+        self.0.error.as_ref()
+    }
+
+    async fn created_at(&self) -> String {
+        self.0.created_at.to_rfc3339()
+    }
+
+    async fn finished_at(&self) -> Option<String> {
+        self.0.finished_at.map(|at| at.to_rfc3339())
+    }
+
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn documents(
+        &self,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<Document>> {
+        paginate(job_documents(&self.0), after, before, first, last).await
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    // Newest first, optionally narrowed to one status
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn jobs(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<String>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<JobNode>> {
+        let status = status
+            .map(|s| JobStatus::parse(&s).ok_or_else(|| Error::new(format!("Unknown job status: {}", s))))
+            .transpose()?;
+        let jobs = list_jobs(ctx, status)?;
+        paginate(jobs.into_iter().map(JobNode).collect(), after, before, first, last).await
+    }
+
+    async fn job(&self, ctx: &Context<'_>, id: String) -> Result<Option<JobNode>> {
+        Ok(get_job(ctx, &id)?.map(JobNode))
+    }
+
+    // source matches part of the input path
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn documents(
+        &self,
+        ctx: &Context<'_>,
+        job_id: Option<String>,
+        source: Option<String>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<Document>> {
+        let documents = all_documents(ctx)?.into_iter()
+            .filter(|document| job_id.as_ref().is_none_or(|id| &document.job_id == id))
+            .filter(|document| source.as_ref().is_none_or(|source| document.input.contains(source.as_str())))
+            .collect();
+        paginate(documents, after, before, first, last).await
+    }
+
+    async fn document(&self, ctx: &Context<'_>, id: String) -> Result<Option<Document>> {
+        Ok(all_documents(ctx)?.into_iter().find(|document| document.path == id))
+    }
+}
+
+// Over the jobs of tenant_id, or every job in the database when None
+pub fn build_schema(db_path: &str, tenant_id: Option<&str>) -> DocumentSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(StoreLocation { db_path: db_path.to_string(), tenant_id: tenant_id.map(str::to_string) })
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+}
+
+// Runs one GraphQL request to completion on the calling thread
+pub fn execute_request(db_path: &str, tenant_id: Option<&str>, request: async_graphql::Request) -> Value {
+    let response = futures_executor::block_on(build_schema(db_path, tenant_id).execute(request));
+    serde_json::to_value(&response).unwrap_or(Value::Null)
+}
+
+// Python bindings - query job outputs without running the server
+#[pyfunction]
+#[pyo3(signature = (db_path, query, variables=None, tenant_id=None))]
+pub fn graphql_query(py: Python, db_path: &str, query: &str, variables: Option<&str>, tenant_id: Option<&str>) -> PyResult<String> {
+    let mut request = async_graphql::Request::new(query);
+    if let Some(variables) = variables {
+        let variables: Value = serde_json::from_str(variables)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid variables JSON: {}", e)))?;
+        request = request.variables(async_graphql::Variables::from_json(variables));
+    }
+    let response = py.allow_threads(|| execute_request(db_path, tenant_id, request));
+    Ok(response.to_string())
+}
+
+#[pyfunction]
+pub fn get_graphql_schema() -> PyResult<String> {
+    Ok(Schema::build(QueryRoot, EmptyMutation, EmptySubscription).finish().sdl())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(db_path: &str, tenant_id: Option<&str>, query: &str) -> Value {
+        execute_request(db_path, tenant_id, async_graphql::Request::new(query))
+    }
+
+    #[test]
+    fn tenants_see_only_their_jobs_and_costly_queries_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("spp-graphql-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("jobs.db").to_string_lossy().to_string();
+        let store = JobStore::open(&db_path).unwrap();
+        let submit = |tenant_id: Option<&str>| {
+            store.submit(vec!["m.txt".to_string()], "out".to_string(), 0, None, Default::default(), Default::default(), tenant_id.map(str::to_string))
+                .unwrap().job_id
+        };
+        let acme = submit(Some("acme"));
+        let other = submit(Some("other"));
+        submit(None);
+
+        let jobs = "{ jobs { totalCount edges { node { id } } } }";
+        assert_eq!(run(&db_path, None, jobs)["data"]["jobs"]["totalCount"], 3);
+        let scoped = run(&db_path, Some("acme"), jobs);
+        assert_eq!(scoped["data"]["jobs"]["totalCount"], 1);
+        assert_eq!(scoped["data"]["jobs"]["edges"][0]["node"]["id"], acme.as_str());
+        let job = |id: &str| format!("{{ job(id: \"{}\") {{ id }} }}", id);
+        assert_eq!(run(&db_path, Some("acme"), &job(&other))["data"]["job"], Value::Null);
+        assert_eq!(run(&db_path, Some("other"), &job(&other))["data"]["job"]["id"], other.as_str());
+
+        // Three nested pages of 500 cost far more than the limit, whatever the database holds
+        let nested = "{ jobs(first: 500) { edges { node { documents(first: 500) { edges { node { modules(first: 500) { totalCount } } } } } } } }";
+        let response = run(&db_path, Some("acme"), nested);
+        assert_eq!(response["data"], Value::Null);
+        assert!(response["errors"][0]["message"].as_str().unwrap().contains("complex"));
+        // Cheap, but nested past the depth limit
+        let mut deep = "{ jobs(first: 1) { edges { node { documents(first: 1) { edges { node { modules(first: 1) { edges { node { steps(first: 1) { edges { node { module { steps(first: 1) { edges { node { module { steps(first: 1) { totalCount".to_string();
+        deep.push_str(&" }".repeat(deep.matches('{').count()));
+        assert!(run(&db_path, None, &deep)["errors"][0]["message"].as_str().unwrap().contains("nested"));
+        assert_eq!(page_complexity(Some(100_000), None, 2), MAX_PAGE_SIZE * 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,1000 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+use crate::engine::scope::{parse_page_range, ExtractionScope};
+use crate::jobs::store::JobStore;
+use crate::engine::taxonomy::TaxonomySchemaResponse;
+use crate::jobs::taxonomy::{build_taxonomy, TaxonomyTree};
+use crate::schema::envelope::Provenance;
+use crate::server::openapi::openapi_spec;
+use crate::security::session::SessionInfo;
+use crate::server::tenants::{ServerConfig, Tenant, TenantRegistry, TenantSnapshot, UnlicensedPolicy, UsageMeter};
+use crate::server::webhooks::{WebhookEvent, WebhookNotifier};
+
+// Matches api.max_upload_size in config/pipeline.yaml
+const MAX_BODY_BYTES: usize = 50 * 1024 * 1024;
+// Request line and headers together
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+const MAX_HEADERS: usize = 100;
+// The request line and headers must all arrive within this, however slowly they trickle in
+pub const HEADER_TIMEOUT_SECONDS: u64 = 10;
+// Longest wait for the next piece of a body, and for a client to take the response
+const IO_TIMEOUT_SECONDS: u64 = 30;
+// Connections served at once unless the configuration sets "workers"
+pub const DEFAULT_WORKERS: usize = 16;
+// Accepted connections waiting for a worker, per worker; past that they are answered with 503
+const QUEUED_PER_WORKER: usize = 4;
+
+// Why a request could not be read, as the status it is answered with
+#[derive(Debug)]
+pub struct RequestError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl RequestError {
+    fn new(status: u16, message: &str) -> Self {
+        Self { status, message: message.to_string() }
+    }
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+impl From<std::io::Error> for RequestError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => Self::new(408, "Request timed out"),
+            std::io::ErrorKind::UnexpectedEof => Self::new(400, "Request ended early"),
+            _ => Self::new(400, &e.to_string()),
+        }
+    }
+}
+
+// Reads that give up once `deadline` has passed, however the time was spent
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let timeout = match self.deadline {
+            Some(deadline) => deadline.checked_duration_since(Instant::now())
+                .filter(|left| !left.is_zero())
+                .ok_or(std::io::ErrorKind::TimedOut)?,
+            None => Duration::from_secs(IO_TIMEOUT_SECONDS),
+        };
+        self.stream.set_read_timeout(Some(timeout))?;
+        self.stream.read(buf)
+    }
+}
+
+// One CRLF-terminated line, counted against what is left of MAX_HEADER_BYTES
+fn read_header_line(reader: &mut impl BufRead, budget: &mut usize) -> Result<String, RequestError> {
+    let mut line = Vec::new();
+    reader.take(*budget as u64 + 1).read_until(b'\n', &mut line)?;
+    if line.len() > *budget {
+        return Err(RequestError::new(431, "Request headers too large"));
+    }
+    *budget -= line.len();
+    String::from_utf8(line).map_err(|_| RequestError::new(400, "Request headers must be UTF-8"))
+}
+
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+// "%20" and "+" as a space; anything malformed is kept as sent
+fn decode_query_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => match value.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    index += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_query_value(name), decode_query_value(value))
+        })
+        .collect()
+}
+
+impl HttpRequest {
+    // The request line and headers must all arrive within header_timeout
+    pub fn read_from(stream: &mut TcpStream, header_timeout: Duration) -> Result<Self, RequestError> {
+        let deadline = Instant::now() + header_timeout;
+        let mut reader = BufReader::new(DeadlineReader { stream, deadline: Some(deadline) });
+        let mut budget = MAX_HEADER_BYTES;
+
+        // Request line
+        let request_line = read_header_line(&mut reader, &mut budget)?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().ok_or(RequestError::new(400, "Malformed request line"))?.to_string();
+        let target = parts.next().ok_or(RequestError::new(400, "Malformed request line"))?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let (path, query) = (path.to_string(), parse_query(query));
+
+        // Headers - names are case-insensitive
+        let mut headers = HashMap::new();
+        loop {
+            let line = read_header_line(&mut reader, &mut budget)?;
+            if line.is_empty() {
+                break;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(RequestError::new(431, "Too many request headers"));
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+
+        // Body - bytes may already be buffered, so the reader keeps going with an idle timeout
+        let content_length: usize = headers
+            .get("content-length")
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(|_| RequestError::new(400, "Invalid Content-Length"))?
+            .unwrap_or(0);
+        if content_length > MAX_BODY_BYTES {
+            return Err(RequestError::new(413, "Request body too large"));
+        }
+        reader.get_mut().deadline = None;
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body)?;
+
+        Ok(Self { method, path, query, headers, body })
+    }
+
+    pub fn api_key(&self) -> Option<&str> {
+        if let Some(key) = self.headers.get("x-api-key") {
+            return Some(key.as_str());
+        }
+        self.headers
+            .get("authorization")
+            .and_then(|v| v.strip_prefix("Bearer "))
+    }
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl HttpResponse {
+    pub fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self { status, body: json!({ "error": message }) }
+    }
+
+    pub fn write_to(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            408 => "Request Timeout",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        };
+        let payload = self.body.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason,
+            payload.len(),
+            payload
+        )?;
+        stream.flush()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ModulesResponse {
+    pub tenant_id: String,
+    pub provenance: Provenance,
+    pub modules: Vec<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "ExtractionScope::is_empty")]
+    pub scope: ExtractionScope,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<FeatureWarning>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StepsResponse {
+    pub tenant_id: String,
+    pub provenance: Provenance,
+    pub steps: Vec<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "ExtractionScope::is_empty")]
+    pub scope: ExtractionScope,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<FeatureWarning>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BatchDocument {
+    pub document_id: String,
+    pub text: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BatchRequest {
+    pub documents: Vec<BatchDocument>,
+    // Applied to every document; one the sections miss is a failure, not an empty result
+    #[serde(default)]
+    #[schema(value_type = Option<Vec<usize>>)]
+    pub pages: Option<(usize, usize)>,
+    #[serde(default)]
+    pub sections: Option<Vec<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchDocumentResult {
+    pub document_id: String,
+    pub provenance: Provenance,
+    pub modules: Vec<HashMap<String, String>>,
+    pub steps: Vec<HashMap<String, String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchFailure {
+    pub document_id: String,
+    pub error: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchResponse {
+    pub tenant_id: String,
+    pub batch_id: String,
+    pub results: Vec<BatchDocumentResult>,
+    pub failures: Vec<BatchFailure>,
+    #[serde(skip_serializing_if = "ExtractionScope::is_empty")]
+    pub scope: ExtractionScope,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<FeatureWarning>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UsageResponse {
+    pub tenant_id: String,
+    pub usage: UsageMeter,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReloadResponse {
+    pub tenant_id: String,
+    pub rule_packs: Vec<String>,
+    pub profile: String,
+    pub rules_version: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HealthResponse {
+    pub status: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+// Why a degraded response is empty - the "degrade" unlicensed policy sends these instead of 403
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FeatureWarning {
+    // Always "feature_unavailable"
+    pub code: String,
+    pub feature: String,
+    pub message: String,
+}
+
+impl FeatureWarning {
+    pub fn unavailable(feature: &str) -> Self {
+        Self {
+            code: "feature_unavailable".to_string(),
+            feature: feature.to_string(),
+            message: format!("Feature not licensed: {}; results are empty", feature),
+        }
+    }
+}
+
+// Route handlers - the utoipa annotations are the source of the OpenAPI document
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    operation_id = "get_health",
+    responses((status = 200, description = "Service is up", body = HealthResponse))
+)]
+pub fn route_health() -> HttpResponse {
+    HttpResponse::ok(json!(HealthResponse { status: "ok".to_string() }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    operation_id = "get_openapi",
+    responses((status = 200, description = "OpenAPI 3.1 document for this service"))
+)]
+pub fn route_openapi() -> HttpResponse {
+    HttpResponse::ok(openapi_spec())
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/extract/modules",
+    operation_id = "extract_modules",
+    request_body(content = String, content_type = "text/plain", description = "Document text"),
+    params(
+        ("pages" = Option<String>, Query, description = "Pages to extract from, \"120-180\" or \"12\""),
+        ("sections" = Option<String>, Query, description = "Comma-separated chapters, sections or title text of the modules to extract from")
+    ),
+    responses(
+        (status = 200, description = "Extracted modules", body = ModulesResponse),
+        (status = 400, description = "Invalid body, or a scope that selects nothing", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "Feature not licensed for tenant, under the error unlicensed policy", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+pub fn route_extract_modules(tenant: &mut TenantSnapshot, warnings: Vec<FeatureWarning>, query: &HashMap<String, String>, body: &[u8]) -> HttpResponse {
+    let route = "/v1/extract/modules";
+    let text = match utf8_text(tenant, route, body) {
+        Ok(text) => text,
+        Err(response) => return response,
+    };
+    let engine = tenant.snapshot_engine();
+    let scope = match query_scope(query) {
+        Ok(scope) => scope,
+        Err(e) => return fail_snapshot(tenant, route, 400, &e),
+    };
+    let scoped = match scope.apply(&engine, text) {
+        Ok(scoped) => scoped,
+        Err(e) => return fail_snapshot(tenant, route, 400, &e),
+    };
+    let mut modules = Vec::new();
+    if warnings.is_empty() {
+        modules = engine.extract_modules(&scoped);
+        tenant.record_document(body.len(), modules.len());
+        tenant.get_notifier().notify(&tenant.tenant_id, WebhookEvent::DocumentFinished, json!({
+            "route": route,
+            "records": modules.len(),
+        }));
+    }
+
+    let provenance = Provenance::licensed(&engine, tenant.get_license(), body);
+    HttpResponse::ok(json!(ModulesResponse { tenant_id: tenant.tenant_id.clone(), provenance, modules, scope, warnings }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/extract/steps",
+    operation_id = "extract_steps",
+    request_body(content = String, content_type = "text/plain", description = "Document text"),
+    params(
+        ("pages" = Option<String>, Query, description = "Pages to extract from, \"120-180\" or \"12\""),
+        ("sections" = Option<String>, Query, description = "Comma-separated chapters, sections or title text of the modules to extract from")
+    ),
+    responses(
+        (status = 200, description = "Extracted steps", body = StepsResponse),
+        (status = 400, description = "Invalid body, or a scope that selects nothing", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "Feature not licensed for tenant, under the error unlicensed policy", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+pub fn route_extract_steps(tenant: &mut TenantSnapshot, warnings: Vec<FeatureWarning>, query: &HashMap<String, String>, body: &[u8]) -> HttpResponse {
+    let route = "/v1/extract/steps";
+    let text = match utf8_text(tenant, route, body) {
+        Ok(text) => text,
+        Err(response) => return response,
+    };
+    let engine = tenant.snapshot_engine();
+    let scope = match query_scope(query) {
+        Ok(scope) => scope,
+        Err(e) => return fail_snapshot(tenant, route, 400, &e),
+    };
+    let scoped = match scope.apply(&engine, text) {
+        Ok(scoped) => scoped,
+        Err(e) => return fail_snapshot(tenant, route, 400, &e),
+    };
+    let mut steps = Vec::new();
+    if warnings.is_empty() {
+        steps = engine.extract_steps(&scoped);
+        tenant.record_document(body.len(), steps.len());
+        tenant.get_notifier().notify(&tenant.tenant_id, WebhookEvent::DocumentFinished, json!({
+            "route": route,
+            "records": steps.len(),
+        }));
+    }
+
+    let provenance = Provenance::licensed(&engine, tenant.get_license(), body);
+    HttpResponse::ok(json!(StepsResponse { tenant_id: tenant.tenant_id.clone(), provenance, steps, scope, warnings }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/extract/batch",
+    operation_id = "extract_batch",
+    request_body(content = BatchRequest, description = "Documents to process as one batch"),
+    responses(
+        (status = 200, description = "Per-document results and failures", body = BatchResponse),
+        (status = 400, description = "Invalid body or page range", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "Feature not licensed for tenant, under the error unlicensed policy", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+pub fn route_extract_batch(tenant: &mut TenantSnapshot, warnings: Vec<FeatureWarning>, body: &[u8]) -> HttpResponse {
+    let route = "/v1/extract/batch";
+    // Degraded, a batch still extracts the collections the tenant is licensed for
+    let licensed = |feature: &str| !warnings.iter().any(|warning: &FeatureWarning| warning.feature == feature);
+    let (with_modules, with_steps) = (licensed("module_extraction"), licensed("step_extraction"));
+    let batch: BatchRequest = match serde_json::from_slice(body) {
+        Ok(batch) => batch,
+        Err(e) => return fail_snapshot(tenant, route, 400, &format!("Invalid batch request: {}", e)),
+    };
+    let scope = match ExtractionScope::new(batch.pages, batch.sections) {
+        Ok(scope) => scope,
+        Err(e) => return fail_snapshot(tenant, route, 400, &e),
+    };
+
+    // Every document in the batch sees the same rules, even across a reload
+    let engine = tenant.snapshot_engine();
+    let batch_id = uuid::Uuid::new_v4().to_string();
+    let mut results = Vec::new();
+    let mut failures = Vec::new();
+
+    for document in batch.documents {
+        let scoped = match scope.apply(&engine, &document.text) {
+            Ok(_) if document.text.trim().is_empty() => Err("Document is empty".to_string()),
+            scoped => scoped,
+        };
+        let scoped = match scoped {
+            Ok(scoped) => scoped,
+            Err(error) => {
+                tenant.get_notifier().notify(&tenant.tenant_id, WebhookEvent::Failure, json!({
+                    "route": route,
+                    "batch_id": batch_id,
+                    "document_id": document.document_id,
+                    "error": error,
+                }));
+                failures.push(BatchFailure { document_id: document.document_id, error });
+                continue;
+            }
+        };
+
+        let modules = if with_modules { engine.extract_modules(&scoped) } else { Vec::new() };
+        let steps = if with_steps { engine.extract_steps(&scoped) } else { Vec::new() };
+        if with_modules || with_steps {
+            tenant.record_document(document.text.len(), modules.len() + steps.len());
+        }
+        tenant.get_notifier().notify(&tenant.tenant_id, WebhookEvent::DocumentFinished, json!({
+            "route": route,
+            "batch_id": batch_id,
+            "document_id": document.document_id,
+            "records": modules.len() + steps.len(),
+        }));
+        let provenance = Provenance::licensed(&engine, tenant.get_license(), document.text.as_bytes());
+        results.push(BatchDocumentResult { document_id: document.document_id, provenance, modules, steps });
+    }
+
+    tenant.get_notifier().notify(&tenant.tenant_id, WebhookEvent::BatchFinished, json!({
+        "batch_id": batch_id,
+        "documents_succeeded": results.len(),
+        "documents_failed": failures.len(),
+    }));
+
+    HttpResponse::ok(json!(BatchResponse {
+        tenant_id: tenant.tenant_id.clone(),
+        batch_id,
+        results,
+        failures,
+        scope,
+        warnings,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/usage",
+    operation_id = "get_usage",
+    responses(
+        (status = 200, description = "Usage counters for the calling tenant", body = UsageResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+pub fn route_usage(tenant: &Tenant) -> HttpResponse {
+    HttpResponse::ok(json!(UsageResponse {
+        tenant_id: tenant.tenant_id.clone(),
+        usage: tenant.get_usage().clone(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/license",
+    operation_id = "get_license",
+    responses(
+        (status = 200, description = "License summary for the calling tenant", body = HashMap<String, String>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+pub fn route_license(tenant: &Tenant) -> HttpResponse {
+    HttpResponse::ok(json!(tenant.get_license_info()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/session",
+    operation_id = "get_session",
+    responses(
+        (status = 200, description = "Sanitized session facts for support tickets", body = SessionInfo),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+pub fn route_session(tenant: &Tenant) -> HttpResponse {
+    HttpResponse::ok(json!(tenant.get_session_info()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/taxonomy",
+    operation_id = "get_taxonomy",
+    responses(
+        (status = 200, description = "ATA chapter/section browse tree over the calling tenant's jobs in the configured job database", body = TaxonomyTree),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "Feature not licensed for tenant, under the error unlicensed policy", body = ErrorResponse),
+        (status = 404, description = "No job database configured", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+pub fn route_taxonomy(tenant: &mut Tenant, job_db: Option<&str>, unlicensed: UnlicensedPolicy) -> HttpResponse {
+    let route = "/v1/taxonomy";
+    match check_feature(tenant, "document_queries", route, unlicensed) {
+        Ok(None) => {}
+        Ok(Some(warning)) => {
+            let mut tree = json!(TaxonomyTree::default());
+            tree["warnings"] = json!([warning]);
+            return HttpResponse::ok(tree);
+        }
+        Err(response) => return response,
+    }
+    let job_db = match job_db {
+        Some(job_db) => job_db,
+        None => return fail(tenant, route, 404, "No job database configured"),
+    };
+    match JobStore::open(job_db).and_then(|store| build_taxonomy(&store, Some(&tenant.tenant_id))) {
+        Ok(tree) => HttpResponse::ok(json!(tree)),
+        Err(e) => fail(tenant, route, 500, &e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/taxonomy/schema",
+    operation_id = "get_taxonomy_schema",
+    responses(
+        (status = 200, description = "Levels and terms of the taxonomy the tenant's rules define, else the built-in ATA chapters", body = TaxonomySchemaResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+pub fn route_taxonomy_schema(tenant: &Tenant) -> HttpResponse {
+    HttpResponse::ok(json!(tenant.snapshot_engine().get_taxonomy().effective()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/rules/reload",
+    operation_id = "reload_rules",
+    responses(
+        (status = 200, description = "Rule packs reloaded; requests already running finish on the previous rules", body = ReloadResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 404, description = "No rules directory configured", body = ErrorResponse),
+        (status = 500, description = "A rule pack failed to load; the previous rules stay active", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+pub fn route_reload_rules(tenant: &mut Tenant, rules_dir: Option<&str>) -> HttpResponse {
+    let route = "/v1/rules/reload";
+    let rules_dir = match rules_dir {
+        Some(rules_dir) => rules_dir,
+        None => return fail(tenant, route, 404, "No rules directory configured"),
+    };
+    if let Err(e) = tenant.reload_rules(rules_dir) {
+        return fail(tenant, route, 500, &format!("Reload failed, previous rules kept: {}", e));
+    }
+    let engine = tenant.snapshot_engine();
+    HttpResponse::ok(json!(ReloadResponse {
+        tenant_id: tenant.tenant_id.clone(),
+        rule_packs: tenant.get_rule_packs().to_vec(),
+        profile: engine.get_profile_name().to_string(),
+        rules_version: engine.get_rules_version().to_string(),
+    }))
+}
+
+// GraphQL over the calling tenant's jobs in the configured job database - see server::graphql for the schema
+#[cfg(feature = "graphql")]
+pub fn route_graphql(tenant: &mut Tenant, job_db: Option<&str>, body: &[u8], unlicensed: UnlicensedPolicy) -> HttpResponse {
+    let route = "/v1/graphql";
+    // Degraded, the warning is a GraphQL error, which clients already expect next to null data
+    match check_feature(tenant, "document_queries", route, unlicensed) {
+        Ok(None) => {}
+        Ok(Some(warning)) => return HttpResponse::ok(json!({
+            "data": null,
+            "errors": [{
+                "message": warning.message,
+                "extensions": {"code": warning.code, "feature": warning.feature},
+            }],
+        })),
+        Err(response) => return response,
+    }
+    let job_db = match job_db {
+        Some(job_db) => job_db,
+        None => return fail(tenant, route, 404, "No job database configured"),
+    };
+    let request: async_graphql::Request = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return fail(tenant, route, 400, &format!("Invalid GraphQL request: {}", e)),
+    };
+    HttpResponse::ok(crate::server::graphql::execute_request(job_db, Some(&tenant.tenant_id), request))
+}
+
+// ?pages=120-180&sections=32,TASK%2032-11-00
+fn query_scope(query: &HashMap<String, String>) -> Result<ExtractionScope, String> {
+    let pages = query.get("pages").map(|pages| parse_page_range(pages)).transpose()?;
+    let sections = query.get("sections").map(|sections| {
+        sections.split(',').map(|section| section.trim().to_string()).collect()
+    });
+    ExtractionScope::new(pages, sections)
+}
+
+// None when the tenant may use the feature. A denial is counted either way; the policy decides
+// between a 403 and a warning to send with empty results
+fn check_feature(tenant: &mut Tenant, feature: &str, route: &str, unlicensed: UnlicensedPolicy) -> Result<Option<FeatureWarning>, HttpResponse> {
+    if tenant.authorize(feature) {
+        return Ok(None);
+    }
+    tenant.get_usage_mut().record_denied();
+    match unlicensed {
+        UnlicensedPolicy::Error => Err(fail(tenant, route, 403, &format!("Feature not licensed: {}", feature))),
+        UnlicensedPolicy::Degrade => Ok(Some(FeatureWarning::unavailable(feature))),
+    }
+}
+
+fn utf8_text<'a>(tenant: &TenantSnapshot, route: &str, body: &'a [u8]) -> Result<&'a str, HttpResponse> {
+    std::str::from_utf8(body).map_err(|_| fail_snapshot(tenant, route, 400, "Body must be UTF-8 text"))
+}
+
+// Error response that is also reported to the tenant's failure webhooks
+fn notify_failure(notifier: &WebhookNotifier, tenant_id: &str, route: &str, status: u16, message: &str) -> HttpResponse {
+    notifier.notify(tenant_id, WebhookEvent::Failure, json!({
+        "route": route,
+        "status": status,
+        "error": message,
+    }));
+    HttpResponse::error(status, message)
+}
+
+fn fail(tenant: &Tenant, route: &str, status: u16, message: &str) -> HttpResponse {
+    notify_failure(tenant.get_notifier(), &tenant.tenant_id, route, status, message)
+}
+
+fn fail_snapshot(tenant: &TenantSnapshot, route: &str, status: u16, message: &str) -> HttpResponse {
+    notify_failure(tenant.get_notifier(), &tenant.tenant_id, route, status, message)
+}
+
+// Multi-tenant extraction service - every request is resolved to a tenant by API key
+pub struct ExtractionServer {
+    registry: Mutex<TenantRegistry>,
+    job_db: Option<String>,
+    // Where POST /v1/rules/reload reads rule packs from
+    rules_dir: Option<String>,
+    unlicensed: UnlicensedPolicy,
+    // Connections served at once
+    workers: usize,
+    header_timeout: Duration,
+}
+
+impl ExtractionServer {
+    pub fn new(registry: TenantRegistry) -> Self {
+        Self {
+            registry: Mutex::new(registry),
+            job_db: None,
+            rules_dir: None,
+            unlicensed: UnlicensedPolicy::Error,
+            workers: DEFAULT_WORKERS,
+            header_timeout: Duration::from_secs(HEADER_TIMEOUT_SECONDS),
+        }
+    }
+
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    pub fn with_header_timeout(mut self, header_timeout: Duration) -> Self {
+        self.header_timeout = header_timeout;
+        self
+    }
+
+    pub fn with_unlicensed_policy(mut self, unlicensed: UnlicensedPolicy) -> Self {
+        self.unlicensed = unlicensed;
+        self
+    }
+
+    pub fn with_rules_dir(mut self, rules_dir: Option<String>) -> Self {
+        self.rules_dir = rules_dir;
+        self
+    }
+
+    pub fn with_job_db(mut self, job_db: Option<String>) -> Self {
+        self.job_db = job_db;
+        self
+    }
+
+    pub fn serve(self: Arc<Self>, bind_addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.serve_on(TcpListener::bind(bind_addr)?)
+    }
+
+    // A fixed pool of workers takes connections from a bounded queue, so a flood of
+    // connections cannot start a thread each; the ones that find the queue full get 503
+    pub fn serve_on(self: Arc<Self>, listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
+        let (queue, connections) = sync_channel::<TcpStream>(self.workers * QUEUED_PER_WORKER);
+        let connections = Arc::new(Mutex::new(connections));
+        for _ in 0..self.workers {
+            let server = Arc::clone(&self);
+            let connections = Arc::clone(&connections);
+            std::thread::spawn(move || loop {
+                let next = connections.lock().map_err(|_| ()).and_then(|connections| connections.recv().map_err(|_| ()));
+                let Ok(mut stream) = next else {
+                    return;
+                };
+                server.answer(&mut stream);
+            });
+        }
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Err(TrySendError::Full(mut stream)) = queue.try_send(stream) {
+                let _ = stream.set_write_timeout(Some(Duration::from_secs(IO_TIMEOUT_SECONDS)));
+                let _ = HttpResponse::error(503, "Server busy").write_to(&mut stream);
+            }
+        }
+        Ok(())
+    }
+
+    fn answer(&self, stream: &mut TcpStream) {
+        let _ = stream.set_write_timeout(Some(Duration::from_secs(IO_TIMEOUT_SECONDS)));
+        let response = match HttpRequest::read_from(stream, self.header_timeout) {
+            Ok(request) => self.handle(&request),
+            Err(e) => HttpResponse::error(e.status, &e.message),
+        };
+        let _ = response.write_to(stream);
+    }
+
+    pub fn handle(&self, request: &HttpRequest) -> HttpResponse {
+        // Unauthenticated routes
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/health") => return route_health(),
+            ("GET", "/openapi.json") => return route_openapi(),
+            _ => {}
+        }
+
+        let api_key = match request.api_key() {
+            Some(key) => key,
+            None => return HttpResponse::error(401, "Missing API key"),
+        };
+
+        let features: &[&str] = match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/v1/extract/modules") => &["module_extraction"],
+            ("POST", "/v1/extract/steps") => &["step_extraction"],
+            ("POST", "/v1/extract/batch") => &["module_extraction", "step_extraction"],
+            _ => &[],
+        };
+        let mut registry = match self.registry.lock() {
+            Ok(registry) => registry,
+            Err(_) => return HttpResponse::error(500, "Tenant registry unavailable"),
+        };
+        let tenant = match registry.resolve(api_key) {
+            Some(tenant) => tenant,
+            None => return HttpResponse::error(401, "Unknown API key"),
+        };
+        tenant.get_usage_mut().record_request();
+
+        if !features.is_empty() {
+            let mut warnings = Vec::new();
+            for feature in features {
+                match check_feature(tenant, feature, &request.path, self.unlicensed) {
+                    Ok(warning) => warnings.extend(warning),
+                    Err(response) => return metered(tenant, response),
+                }
+            }
+            // Extraction runs on a snapshot with the registry unlocked; its usage is recorded
+            // once the lock is taken again
+            let mut snapshot = tenant.snapshot();
+            drop(registry);
+            let response = match request.path.as_str() {
+                "/v1/extract/modules" => route_extract_modules(&mut snapshot, warnings, &request.query, &request.body),
+                "/v1/extract/steps" => route_extract_steps(&mut snapshot, warnings, &request.query, &request.body),
+                _ => route_extract_batch(&mut snapshot, warnings, &request.body),
+            };
+            let mut registry = match self.registry.lock() {
+                Ok(registry) => registry,
+                Err(_) => return HttpResponse::error(500, "Tenant registry unavailable"),
+            };
+            return match registry.resolve(api_key) {
+                Some(tenant) => {
+                    tenant.record_snapshot(snapshot);
+                    metered(tenant, response)
+                }
+                None => HttpResponse::error(500, "Tenant removed while the request ran"),
+            };
+        }
+
+        let response = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/v1/usage") => route_usage(tenant),
+            ("GET", "/v1/license") => route_license(tenant),
+            ("GET", "/v1/session") => route_session(tenant),
+            ("GET", "/v1/taxonomy") => route_taxonomy(tenant, self.job_db.as_deref(), self.unlicensed),
+            ("GET", "/v1/taxonomy/schema") => route_taxonomy_schema(tenant),
+            ("POST", "/v1/rules/reload") => route_reload_rules(tenant, self.rules_dir.as_deref()),
+            #[cfg(feature = "graphql")]
+            ("POST", "/v1/graphql") => route_graphql(tenant, self.job_db.as_deref(), &request.body, self.unlicensed),
+            _ => HttpResponse::error(404, "Unknown route"),
+        };
+        metered(tenant, response)
+    }
+}
+
+// Metering must not silently drop - a request that cannot be recorded fails
+fn metered(tenant: &Tenant, response: HttpResponse) -> HttpResponse {
+    match tenant.save_usage() {
+        Ok(()) => response,
+        Err(e) => HttpResponse::error(500, &format!("Usage could not be recorded: {}", e)),
+    }
+}
+
+// Python binding - blocks the calling thread while serving
+#[pyfunction]
+pub fn start_server(py: Python, config_path: &str, bind_addr: &str) -> PyResult<()> {
+    let to_py_err = |e: Box<dyn std::error::Error>| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string());
+    let config = ServerConfig::load(config_path).map_err(to_py_err)?;
+    let registry = TenantRegistry::from_config(&config).map_err(to_py_err)?;
+    let server = Arc::new(ExtractionServer::new(registry)
+        .with_job_db(config.job_db)
+        .with_unlicensed_policy(config.unlicensed)
+        .with_workers(config.workers.unwrap_or(DEFAULT_WORKERS))
+        .with_rules_dir(Some(config.rules_dir)));
+    let bind_addr = bind_addr.to_string();
+
+    py.allow_threads(move || server.serve(&bind_addr).map_err(|e| e.to_string()))
+        .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serve(workers: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = ExtractionServer::new(TenantRegistry::new())
+            .with_workers(workers)
+            .with_header_timeout(Duration::from_millis(500));
+        std::thread::spawn(move || {
+            let _ = Arc::new(server).serve_on(listener);
+        });
+        address
+    }
+
+    // The status line of the answer to `request`
+    fn send(address: &str, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        // The server may answer and close before a large request is all written
+        let _ = stream.write_all(request);
+        status_of(stream)
+    }
+
+    fn status_of(stream: TcpStream) -> String {
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status).unwrap();
+        status.trim_end().to_string()
+    }
+
+    #[test]
+    fn requests_are_capped() {
+        let address = serve(2);
+        assert_eq!(send(&address, b"GET /health HTTP/1.1\r\n\r\n"), "HTTP/1.1 200 OK");
+        let long_header = format!("GET /health HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "a".repeat(MAX_HEADER_BYTES));
+        assert_eq!(send(&address, long_header.as_bytes()), "HTTP/1.1 431 Request Header Fields Too Large");
+        let many_headers: String = (0..=MAX_HEADERS).map(|n| format!("X-{}: 1\r\n", n)).collect();
+        let many_headers = format!("GET /health HTTP/1.1\r\n{}\r\n", many_headers);
+        assert_eq!(send(&address, many_headers.as_bytes()), "HTTP/1.1 431 Request Header Fields Too Large");
+        let large_body = format!("POST /v1/extract/modules HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_BYTES + 1);
+        assert_eq!(send(&address, large_body.as_bytes()), "HTTP/1.1 413 Payload Too Large");
+    }
+
+    #[test]
+    fn slow_clients_time_out() {
+        let address = serve(1);
+        let started = Instant::now();
+        // Headers trickled in a byte at a time still have to finish before the deadline
+        let mut stream = TcpStream::connect(&address).unwrap();
+        for byte in b"GET /health HTTP/1.1\r\nX-Slow: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" {
+            if stream.write_all(&[*byte]).is_err() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(status_of(stream), "HTTP/1.1 408 Request Timeout");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(send(&address, b"GET /health HTTP/1.1\r\n\r\n"), "HTTP/1.1 200 OK");
+    }
+
+    #[test]
+    fn connections_past_the_queue_are_turned_away() {
+        let address = serve(1);
+        // One connection holds the only worker and the next fill the queue
+        let held = TcpStream::connect(&address).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let queued: Vec<TcpStream> = (0..QUEUED_PER_WORKER).map(|_| TcpStream::connect(&address).unwrap()).collect();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(send(&address, b"GET /health HTTP/1.1\r\n\r\n"), "HTTP/1.1 503 Service Unavailable");
+        // Once they time out the worker takes new connections again
+        assert_eq!(status_of(held), "HTTP/1.1 408 Request Timeout");
+        drop(queued);
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(send(&address, b"GET /health HTTP/1.1\r\n\r\n"), "HTTP/1.1 200 OK");
+    }
+}
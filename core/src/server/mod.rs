@@ -0,0 +1,6 @@
+pub mod tenants;
+pub mod http;
+pub mod openapi;
+pub mod webhooks;
+#[cfg(feature = "graphql")]
+pub mod graphql;
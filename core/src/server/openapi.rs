@@ -0,0 +1,69 @@
+use pyo3::prelude::*;
+use serde_json::Value;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::server::http::{
+    BatchDocument, BatchDocumentResult, BatchFailure, BatchRequest, BatchResponse,
+    ErrorResponse, FeatureWarning, HealthResponse, ModulesResponse, ReloadResponse, StepsResponse, UsageResponse,
+};
+use crate::engine::scope::ExtractionScope;
+use crate::engine::taxonomy::{TaxonomyLevelSchema, TaxonomySchemaResponse, TaxonomyTerm};
+use crate::jobs::taxonomy::{ChapterNode, DocumentNode, ProcedureNode, SectionNode, TaxonomyTree};
+use crate::licensing::branding::Branding;
+use crate::schema::envelope::Provenance;
+use crate::security::session::SessionInfo;
+use crate::server::tenants::UsageMeter;
+
+// OpenAPI document assembled from the annotated route handlers and response types
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ML Core Extraction Service", description = "Multi-tenant document extraction API"),
+    paths(
+        crate::server::http::route_health,
+        crate::server::http::route_openapi,
+        crate::server::http::route_extract_modules,
+        crate::server::http::route_extract_steps,
+        crate::server::http::route_extract_batch,
+        crate::server::http::route_usage,
+        crate::server::http::route_license,
+        crate::server::http::route_session,
+        crate::server::http::route_taxonomy,
+        crate::server::http::route_taxonomy_schema,
+        crate::server::http::route_reload_rules,
+    ),
+    components(schemas(
+        ModulesResponse, StepsResponse, UsageResponse, UsageMeter, HealthResponse, ErrorResponse,
+        BatchRequest, BatchDocument, BatchResponse, BatchDocumentResult, BatchFailure, FeatureWarning, Provenance, Branding, SessionInfo, ExtractionScope,
+        TaxonomyTree, ChapterNode, SectionNode, DocumentNode, ProcedureNode, ReloadResponse,
+        TaxonomySchemaResponse, TaxonomyLevelSchema, TaxonomyTerm,
+    )),
+    modifiers(&ApiKeyAuth)
+)]
+pub struct ApiDoc;
+
+// Registers the X-API-Key header scheme referenced by authenticated routes
+struct ApiKeyAuth;
+
+impl Modify for ApiKeyAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+            );
+        }
+    }
+}
+
+pub fn openapi_spec() -> Value {
+    serde_json::to_value(ApiDoc::openapi()).unwrap_or(Value::Null)
+}
+
+// Python binding - lets SDK generation run without starting the server
+#[pyfunction]
+pub fn get_openapi_spec() -> PyResult<String> {
+    ApiDoc::openapi()
+        .to_pretty_json()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+}
@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::engine::extractor::ExtractionEngine;
+use crate::engine::profile::resolve_profile;
+use crate::licensing::manager::License;
+use crate::schema::envelope::hash_input;
+use crate::security::session::{record_document_processed, SessionInfo};
+use crate::security::validator::{Session, ValidationConfig};
+use crate::server::webhooks::{WebhookConfig, WebhookNotifier};
+use crate::storage::backend::{open_storage, Storage};
+
+const USAGE_NAMESPACE: &str = "usage";
+
+// Tenant definition as stored in the server configuration file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantConfig {
+    pub tenant_id: String,
+    pub api_key: String,
+    pub license_path: String,
+    #[serde(default)]
+    pub rule_packs: Vec<String>,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+// Server configuration - rule packs live as engine config files in rules_dir
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub rules_dir: String,
+    pub tenants: Vec<TenantConfig>,
+    // Job database whose outputs /v1/graphql queries
+    #[serde(default)]
+    pub job_db: Option<String>,
+    // Storage spec for usage meters and session state, e.g. "state/" or "sqlite:state.db".
+    // Without one they live in memory and reset on restart
+    #[serde(default)]
+    pub storage: Option<String>,
+    // What a call needing an unlicensed feature gets back
+    #[serde(default)]
+    pub unlicensed: UnlicensedPolicy,
+    // Connections served at once; the server's default when unset
+    #[serde(default)]
+    pub workers: Option<usize>,
+}
+
+// "error" answers 403. "degrade" answers with empty results and a feature_unavailable warning,
+// so one pipeline can serve tenants of every tier
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnlicensedPolicy {
+    #[default]
+    Error,
+    Degrade,
+}
+
+impl ServerConfig {
+    pub fn load(config_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if !Path::new(config_path).exists() {
+            return Err("Server configuration file not found".into());
+        }
+        let config_data = std::fs::read_to_string(config_path)?;
+        let config: Self = serde_json::from_str(&config_data)?;
+        for tenant in &config.tenants {
+            for hook in &tenant.webhooks {
+                hook.validate().map_err(|e| format!("Tenant {}: {}", tenant.tenant_id, e))?;
+            }
+        }
+        Ok(config)
+    }
+}
+
+// Per-tenant usage counters
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct UsageMeter {
+    pub requests: u64,
+    pub documents: u64,
+    pub bytes_processed: u64,
+    pub records_emitted: u64,
+    pub denied_requests: u64,
+    pub last_request_at: Option<DateTime<Utc>>,
+}
+
+impl UsageMeter {
+    pub fn record_request(&mut self) {
+        self.requests += 1;
+        self.last_request_at = Some(Utc::now());
+    }
+
+    pub fn record_document(&mut self, bytes: usize, records: usize) {
+        self.documents += 1;
+        record_document_processed();
+        self.bytes_processed += bytes as u64;
+        self.records_emitted += records as u64;
+    }
+
+    pub fn record_denied(&mut self) {
+        self.denied_requests += 1;
+    }
+}
+
+// Engines some tenant still holds, by the hash of their packs' effective rules. Tenants with
+// the same packs share one engine, and an entry lapses once no tenant holds it
+static ENGINES: Mutex<BTreeMap<String, Weak<ExtractionEngine>>> = Mutex::new(BTreeMap::new());
+
+fn load_rule_packs(rule_packs: &[String], rules_dir: &str) -> Result<Arc<ExtractionEngine>, Box<dyn std::error::Error>> {
+    let mut configs = Vec::new();
+    for pack in rule_packs {
+        let pack_path = Path::new(rules_dir).join(format!("{}.json", pack));
+        if !pack_path.exists() {
+            return Err(format!("Rule pack {} unavailable: {} not found", pack, pack_path.display()).into());
+        }
+        configs.push(serde_json::to_vec(&resolve_profile(&pack_path.to_string_lossy())?.config)?);
+    }
+    // Hashing the resolved rules rather than the pack files, so a changed base is a new engine
+    let key = hash_input(&configs.join(&[0x1f][..]));
+    let mut engines = ENGINES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(engine) = engines.get(&key).and_then(Weak::upgrade) {
+        return Ok(engine);
+    }
+    let mut engine = ExtractionEngine::new();
+    for config in &configs {
+        let mut pack = ExtractionEngine::new();
+        pack.load_config(config)?;
+        engine.merge(pack);
+    }
+    let engine = Arc::new(engine);
+    engines.retain(|_, held| held.strong_count() > 0);
+    engines.insert(key, Arc::downgrade(&engine));
+    Ok(engine)
+}
+
+// A tenant owns its license, entitled rule packs, usage and session. Only the engine, which
+// never changes once built, is shared with tenants that load the same packs
+pub struct Tenant {
+    pub tenant_id: String,
+    license: Arc<License>,
+    rule_packs: Vec<String>,
+    // Immutable once built - a reload swaps in a new engine, so requests holding a snapshot
+    // finish on the rules they started with
+    engine: Arc<ExtractionEngine>,
+    session: Session,
+    usage: UsageMeter,
+    notifier: Arc<WebhookNotifier>,
+    // Where usage is persisted, when the server has storage
+    storage: Option<Arc<dyn Storage>>,
+}
+
+impl Tenant {
+    pub fn load(config: &TenantConfig, rules_dir: &str, storage: Option<Arc<dyn Storage>>) -> Result<Self, Box<dyn std::error::Error>> {
+        // Layer 1: Tenant license
+        if !Path::new(&config.license_path).exists() {
+            return Err(format!("License file not found for tenant {}", config.tenant_id).into());
+        }
+        let license = License::load_signed(&config.license_path)?;
+
+        // Layer 2: Only entitled rule packs are loaded into the tenant engine
+        let engine = load_rule_packs(&config.rule_packs, rules_dir)?;
+
+        // Layer 3: Dedicated session so cached state never crosses tenants
+        let mut session = Session::new(ValidationConfig::new(
+            license.customer_id.clone(),
+            license.features.clone(),
+        )).with_access_limit(license.get_access_limit());
+
+        // Layer 4: Usage and session state carry over from earlier runs when stored
+        let mut usage = UsageMeter::default();
+        if let Some(storage) = &storage {
+            session = session.with_storage(Arc::clone(storage))?;
+            if let Some(stored) = storage.get(USAGE_NAMESPACE, &config.tenant_id)? {
+                usage = serde_json::from_slice(&stored)
+                    .map_err(|e| format!("Stored usage for tenant {} is unreadable: {}", config.tenant_id, e))?;
+            }
+        }
+
+        Ok(Self {
+            tenant_id: config.tenant_id.clone(),
+            license: Arc::new(license),
+            rule_packs: config.rule_packs.clone(),
+            engine,
+            session,
+            usage,
+            notifier: Arc::new(WebhookNotifier::new(config.webhooks.clone())),
+            storage,
+        })
+    }
+
+    pub fn authorize(&self, feature: &str) -> bool {
+        self.license.is_valid()
+            && self.license.has_feature(feature)
+            && self.session.is_active()
+            && self.session.validate_access(feature)
+    }
+
+    pub fn get_license(&self) -> &License {
+        &self.license
+    }
+
+    pub fn get_license_tier(&self) -> &str {
+        self.license.get_tier()
+    }
+
+    pub fn get_engine(&self) -> &ExtractionEngine {
+        &self.engine
+    }
+
+    // The current rules, unaffected by any later reload
+    pub fn snapshot_engine(&self) -> Arc<ExtractionEngine> {
+        Arc::clone(&self.engine)
+    }
+
+    // Rebuilds the engine from the entitled packs on disk. The old engine stays in place
+    // unless every pack loads
+    pub fn reload_rules(&mut self, rules_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.engine = load_rule_packs(&self.rule_packs, rules_dir)?;
+        Ok(())
+    }
+
+    pub fn get_rule_packs(&self) -> &[String] {
+        &self.rule_packs
+    }
+
+    pub fn get_usage(&self) -> &UsageMeter {
+        &self.usage
+    }
+
+    pub fn get_usage_mut(&mut self) -> &mut UsageMeter {
+        &mut self.usage
+    }
+
+    // Writes the usage meter to storage; a no-op for servers without storage
+    pub fn save_usage(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(storage) = &self.storage {
+            storage.put(USAGE_NAMESPACE, &self.tenant_id, &serde_json::to_vec(&self.usage)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_notifier(&self) -> &WebhookNotifier {
+        &self.notifier
+    }
+
+    // What an extraction needs, so it can run after the registry lock is released
+    pub fn snapshot(&self) -> TenantSnapshot {
+        TenantSnapshot {
+            tenant_id: self.tenant_id.clone(),
+            license: Arc::clone(&self.license),
+            engine: self.snapshot_engine(),
+            notifier: Arc::clone(&self.notifier),
+            documents: Vec::new(),
+        }
+    }
+
+    // Meters the documents extracted from a snapshot of this tenant
+    pub fn record_snapshot(&mut self, snapshot: TenantSnapshot) {
+        for (bytes, records) in snapshot.documents {
+            self.usage.record_document(bytes, records);
+        }
+    }
+
+    // Sanitized facts for support tickets - documents are this tenant's, not the process's
+    pub fn get_session_info(&self) -> SessionInfo {
+        let mut info = self.session.get_session_info()
+            .with_license(&self.license)
+            .with_engine(&self.engine);
+        info.documents_processed = self.usage.documents;
+        info
+    }
+
+    pub fn get_license_info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("tenant_id".to_string(), self.tenant_id.clone());
+        info.insert("customer_id".to_string(), self.license.customer_id.clone());
+        info.insert("license_valid".to_string(), self.license.is_valid().to_string());
+        info.insert("days_remaining".to_string(), self.license.days_remaining().to_string());
+        info.insert("features".to_string(), self.license.features.join(","));
+        info.insert("rule_packs".to_string(), self.rule_packs.join(","));
+        info.extend(self.session.get_security_info());
+        info
+    }
+
+    pub fn get_security_info(&self) -> HashMap<String, String> {
+        self.session.get_security_info()
+    }
+}
+
+// A tenant's license, rules and webhooks as they were when a request resolved it. Requests
+// extract from a snapshot without holding the registry, so tenants never wait on each other;
+// the documents it extracts are metered when the request hands it back (Tenant::record_snapshot)
+pub struct TenantSnapshot {
+    pub tenant_id: String,
+    license: Arc<License>,
+    engine: Arc<ExtractionEngine>,
+    notifier: Arc<WebhookNotifier>,
+    // (bytes, records) per document extracted
+    documents: Vec<(usize, usize)>,
+}
+
+impl TenantSnapshot {
+    pub fn get_license(&self) -> &License {
+        &self.license
+    }
+
+    pub fn snapshot_engine(&self) -> Arc<ExtractionEngine> {
+        Arc::clone(&self.engine)
+    }
+
+    pub fn get_notifier(&self) -> &WebhookNotifier {
+        &self.notifier
+    }
+
+    pub fn record_document(&mut self, bytes: usize, records: usize) {
+        self.documents.push((bytes, records));
+    }
+}
+
+// Maps API keys to tenants
+pub struct TenantRegistry {
+    tenants: HashMap<String, Tenant>,
+    api_keys: HashMap<String, String>,
+}
+
+impl Default for TenantRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TenantRegistry {
+    pub fn new() -> Self {
+        Self {
+            tenants: HashMap::new(),
+            api_keys: HashMap::new(),
+        }
+    }
+
+    pub fn load(config_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_config(&ServerConfig::load(config_path)?)
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut registry = Self::new();
+        let storage = config.storage.as_deref().map(open_storage).transpose()?;
+        for tenant_config in &config.tenants {
+            let tenant = Tenant::load(tenant_config, &config.rules_dir, storage.clone())
+                .map_err(|e| format!("Tenant {}: {}", tenant_config.tenant_id, e))?;
+            registry.register(tenant_config.api_key.clone(), tenant)?;
+        }
+        Ok(registry)
+    }
+
+    pub fn register(&mut self, api_key: String, tenant: Tenant) -> Result<(), Box<dyn std::error::Error>> {
+        if self.api_keys.contains_key(&api_key) {
+            return Err(format!("Duplicate API key for tenant {}", tenant.tenant_id).into());
+        }
+        if self.tenants.contains_key(&tenant.tenant_id) {
+            return Err(format!("Duplicate tenant id {}", tenant.tenant_id).into());
+        }
+        self.api_keys.insert(api_key, tenant.tenant_id.clone());
+        self.tenants.insert(tenant.tenant_id.clone(), tenant);
+        Ok(())
+    }
+
+    pub fn resolve(&mut self, api_key: &str) -> Option<&mut Tenant> {
+        let tenant_id = self.api_keys.get(api_key)?;
+        self.tenants.get_mut(tenant_id)
+    }
+
+    pub fn get_tenant(&self, tenant_id: &str) -> Option<&Tenant> {
+        self.tenants.get(tenant_id)
+    }
+
+    pub fn tenant_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.tenants.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_engines_between_tenants_with_the_same_packs() {
+        let dir = std::env::temp_dir().join(format!("spp-rule-packs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let rules_dir = dir.to_str().unwrap();
+        let write = |file: &str, rules: &str| std::fs::write(dir.join(file), rules).unwrap();
+        write("base.json", r#"{"name": "base", "version": "1", "patterns": {"step": ["Remove the"]}, "prompts": {}, "thresholds": {}}"#);
+        write("core.json", r#"{"extends": "base.json", "name": "core", "version": "1"}"#);
+        write("ata32.json", r#"{"name": "ata32", "version": "1", "patterns": {"module": ["TASK 32"]}, "prompts": {}, "thresholds": {}}"#);
+        let packs = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        let first = load_rule_packs(&packs(&["core", "ata32"]), rules_dir).unwrap();
+        let second = load_rule_packs(&packs(&["core", "ata32"]), rules_dir).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.get_rules_version(), "core@1+ata32@1");
+        assert!(!Arc::ptr_eq(&first, &load_rule_packs(&packs(&["core"]), rules_dir).unwrap()));
+
+        // A changed base changes the rules of every pack that extends it
+        write("base.json", r#"{"name": "base", "version": "2", "patterns": {"step": ["Install the"]}, "prompts": {}, "thresholds": {}}"#);
+        let changed = load_rule_packs(&packs(&["core", "ata32"]), rules_dir).unwrap();
+        assert!(!Arc::ptr_eq(&first, &changed));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
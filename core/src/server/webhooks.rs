@@ -0,0 +1,352 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::security::crypto::{hmac_sha256, to_hex};
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_BACKOFF_MS: u64 = 500;
+const DELIVERY_TIMEOUT_SECONDS: u64 = 10;
+// Limits on a hook's retries, so a dead receiver holds a worker for minutes rather than hours
+const MAX_RETRIES: u32 = 10;
+const MAX_BACKOFF_MS: u64 = 10_000;
+const MAX_RETRY_DELAY_MS: u64 = 60_000;
+// Threads delivering a notifier's events, and the deliveries that may wait for them
+const DELIVERY_WORKERS: usize = 4;
+const DELIVERY_QUEUE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "document.finished")]
+    DocumentFinished,
+    #[serde(rename = "batch.finished")]
+    BatchFinished,
+    #[serde(rename = "failure")]
+    Failure,
+}
+
+impl WebhookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::DocumentFinished => "document.finished",
+            WebhookEvent::BatchFinished => "batch.finished",
+            WebhookEvent::Failure => "failure",
+        }
+    }
+}
+
+fn default_max_retries() -> u32 {
+    DEFAULT_MAX_RETRIES
+}
+
+fn default_backoff_ms() -> u64 {
+    DEFAULT_BACKOFF_MS
+}
+
+// Webhook endpoint, https:// or http://. https receivers are checked against the Mozilla root
+// certificates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub secret: String,
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+}
+
+impl WebhookConfig {
+    pub fn subscribes_to(&self, event: WebhookEvent) -> bool {
+        // An empty event list subscribes to everything
+        self.events.is_empty() || self.events.contains(&event)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.url.starts_with("https://") && !self.url.starts_with("http://") {
+            return Err(format!("Webhook URL {} must start with https:// or http://", self.url));
+        }
+        if self.secret.is_empty() {
+            return Err(format!("Webhook {} has an empty secret", self.url));
+        }
+        if self.max_retries > MAX_RETRIES {
+            return Err(format!("Webhook {} retries {} times, over the limit of {}", self.url, self.max_retries, MAX_RETRIES));
+        }
+        if self.backoff_ms > MAX_BACKOFF_MS {
+            return Err(format!("Webhook {} backs off {} ms, over the limit of {} ms", self.url, self.backoff_ms, MAX_BACKOFF_MS));
+        }
+        Ok(())
+    }
+}
+
+// A JSON list of webhooks, as in a tenant's "webhooks"
+pub fn load_webhooks(path: &str) -> Result<Vec<WebhookConfig>, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Cannot read webhooks {}: {}", path, e))?;
+    let hooks: Vec<WebhookConfig> = serde_json::from_str(&content).map_err(|e| format!("Invalid webhooks {}: {}", path, e))?;
+    for hook in &hooks {
+        hook.validate()?;
+    }
+    Ok(hooks)
+}
+
+// Signature over "<timestamp>.<body>" so receivers can reject replays
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> String {
+    let timestamp = timestamp.to_string();
+    to_hex(&hmac_sha256(secret.as_bytes(), &[timestamp.as_bytes(), b".", body.as_bytes()]))
+}
+
+struct Delivery {
+    hook: WebhookConfig,
+    event: WebhookEvent,
+    body: String,
+}
+
+// Deliveries queued or running, for `wait`
+type Pending = Arc<(Mutex<usize>, Condvar)>;
+
+// Takes deliveries off the queue until the notifier is dropped
+fn deliver_queued(queue: Arc<Mutex<Receiver<Delivery>>>, pending: Pending) {
+    loop {
+        let next = queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv();
+        let Ok(delivery) = next else {
+            return;
+        };
+        let _ = deliver_with_retry(&delivery.hook, delivery.event, &delivery.body);
+        let (count, done) = &*pending;
+        *count.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) -= 1;
+        done.notify_all();
+    }
+}
+
+// Delivers through a fixed pool of workers fed by a bounded queue, so a dead receiver cannot
+// pile up threads
+pub struct WebhookNotifier {
+    hooks: Vec<WebhookConfig>,
+    // None without hooks, so a tenant without webhooks starts no workers
+    queue: Option<SyncSender<Delivery>>,
+    pending: Pending,
+}
+
+impl WebhookNotifier {
+    pub fn new(hooks: Vec<WebhookConfig>) -> Self {
+        let pending: Pending = Arc::new((Mutex::new(0), Condvar::new()));
+        let queue = (!hooks.is_empty()).then(|| {
+            let (sender, receiver) = mpsc::sync_channel(DELIVERY_QUEUE);
+            let receiver = Arc::new(Mutex::new(receiver));
+            for _ in 0..DELIVERY_WORKERS {
+                let (receiver, pending) = (Arc::clone(&receiver), Arc::clone(&pending));
+                std::thread::spawn(move || deliver_queued(receiver, pending));
+            }
+            sender
+        });
+        Self { hooks, queue, pending }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    // Delivery runs on background threads so extraction requests never wait on receivers. When
+    // the queue is full the event is dropped
+    pub fn notify(&self, tenant_id: &str, event: WebhookEvent, data: Value) {
+        self.send(event, false, json!({
+            "id": Uuid::new_v4().to_string(),
+            "event": event.name(),
+            "tenant_id": tenant_id,
+            "created_at": Utc::now().to_rfc3339(),
+            "data": data,
+        }));
+    }
+
+    // Events of job runs, which belong to no tenant. A run waits for room in the queue rather
+    // than drop its events
+    pub fn notify_job(&self, event: WebhookEvent, data: Value) {
+        self.send(event, true, json!({
+            "id": Uuid::new_v4().to_string(),
+            "event": event.name(),
+            "created_at": Utc::now().to_rfc3339(),
+            "data": data,
+        }));
+    }
+
+    fn send(&self, event: WebhookEvent, block: bool, envelope: Value) {
+        let Some(queue) = &self.queue else {
+            return;
+        };
+        let (count, _) = &*self.pending;
+        for hook in self.hooks.iter().filter(|h| h.subscribes_to(event)) {
+            let delivery = Delivery { hook: hook.clone(), event, body: envelope.to_string() };
+            *count.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) += 1;
+            let queued = if block { queue.send(delivery).is_ok() } else { queue.try_send(delivery).is_ok() };
+            if !queued {
+                *count.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) -= 1;
+            }
+        }
+    }
+
+    // Blocks until every delivery so far has succeeded or run out of retries, so a process
+    // that is about to exit does not drop them
+    pub fn wait(&self) {
+        let (count, done) = &*self.pending;
+        let mut count = count.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        while *count > 0 {
+            count = done.wait(count).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+}
+
+pub fn deliver_with_retry(hook: &WebhookConfig, event: WebhookEvent, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut attempt = 0;
+    loop {
+        match deliver(hook, event, body) {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= hook.max_retries => return Err(e),
+            Err(_) => {
+                // Exponential backoff between attempts
+                std::thread::sleep(Duration::from_millis((hook.backoff_ms << attempt.min(16)).min(MAX_RETRY_DELAY_MS)));
+                attempt += 1;
+            }
+        }
+    }
+}
+
+fn deliver(hook: &WebhookConfig, event: WebhookEvent, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+    hook.validate()?;
+    let timestamp = Utc::now().timestamp();
+    let signature = sign_payload(&hook.secret, timestamp, body);
+
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(DELIVERY_TIMEOUT_SECONDS)))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let response = agent.post(&hook.url)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Event", event.name())
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", format!("sha256={}", signature))
+        .send(body)?;
+
+    // Any 2xx status counts as delivered
+    let status = response.status().as_u16();
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(format!("Webhook receiver returned {}", status).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::extractor::ExtractionEngine;
+    use crate::jobs::runner::JobRunner;
+    use crate::jobs::store::JobStore;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+
+    // Headers and body of each request
+    type Received = mpsc::Receiver<(HashMap<String, String>, String)>;
+
+    // Answers each request with `status` and passes it on
+    fn receiver(status: u16) -> (String, Received) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        let (sender, received) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = BufReader::new(stream.unwrap());
+                let mut headers = HashMap::new();
+                let mut line = String::new();
+                stream.read_line(&mut line).unwrap();
+                loop {
+                    line.clear();
+                    stream.read_line(&mut line).unwrap();
+                    match line.trim_end().split_once(": ") {
+                        Some((name, value)) => headers.insert(name.to_ascii_lowercase(), value.to_string()),
+                        None => break,
+                    };
+                }
+                let mut body = vec![0u8; headers["content-length"].parse().unwrap()];
+                stream.read_exact(&mut body).unwrap();
+                write!(stream.get_mut(), "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+                if sender.send((headers, String::from_utf8(body).unwrap())).is_err() {
+                    break;
+                }
+            }
+        });
+        (url, received)
+    }
+
+    fn hook(url: &str, events: Vec<WebhookEvent>) -> WebhookConfig {
+        WebhookConfig { url: url.to_string(), secret: "s3cret".to_string(), events, max_retries: 1, backoff_ms: 1 }
+    }
+
+    #[test]
+    fn deliveries_are_signed() {
+        let (url, received) = receiver(200);
+        let notifier = WebhookNotifier::new(vec![hook(&url, vec![WebhookEvent::Failure])]);
+        notifier.notify("acme", WebhookEvent::DocumentFinished, json!({}));
+        notifier.notify("acme", WebhookEvent::Failure, json!({"error": "boom"}));
+        notifier.wait();
+
+        let (headers, body) = received.recv().unwrap();
+        assert!(received.try_recv().is_err());
+        assert_eq!(headers["x-webhook-event"], "failure");
+        let timestamp: i64 = headers["x-webhook-timestamp"].parse().unwrap();
+        assert_eq!(headers["x-webhook-signature"], format!("sha256={}", sign_payload("s3cret", timestamp, &body)));
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!((body["tenant_id"].as_str(), body["data"]["error"].as_str()), (Some("acme"), Some("boom")));
+    }
+
+    #[test]
+    fn failed_deliveries_are_retried_and_reported() {
+        let (url, received) = receiver(503);
+        let error = deliver_with_retry(&hook(&url, Vec::new()), WebhookEvent::Failure, "{}").unwrap_err();
+        assert_eq!(error.to_string(), "Webhook receiver returned 503");
+        assert_eq!(received.try_iter().count(), 2);
+
+        // A plain listener cannot complete a TLS handshake, so https:// is really TLS
+        let plain = url.replacen("http://", "https://", 1);
+        assert!(deliver_with_retry(&hook(&plain, Vec::new()), WebhookEvent::Failure, "{}").is_err());
+        assert!(hook("ftp://orchestrator/hooks", Vec::new()).validate().is_err());
+        assert!(hook("https://orchestrator/hooks", Vec::new()).validate().is_ok());
+        let eager = WebhookConfig { max_retries: 11, ..hook("https://orchestrator/hooks", Vec::new()) };
+        assert_eq!(eager.validate().unwrap_err(), "Webhook https://orchestrator/hooks retries 11 times, over the limit of 10");
+        assert!(WebhookConfig { backoff_ms: 10_001, ..hook("https://orchestrator/hooks", Vec::new()) }.validate().is_err());
+    }
+
+    #[test]
+    fn job_runs_report_outputs_and_completion() {
+        let (url, received) = receiver(200);
+        let dir = std::env::temp_dir().join(format!("spp-webhooks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("manual.txt").to_string_lossy().to_string();
+        std::fs::write(&input, "TASK 32-11-00\n1. Remove the panel.\n").unwrap();
+        let db_path = dir.join("jobs.db").to_string_lossy().to_string();
+        let store = JobStore::open(&db_path).unwrap();
+        let job = store.submit(vec![input.clone()], dir.join("out").to_string_lossy().to_string(), 0, None, Default::default(), Default::default(), None).unwrap();
+
+        let notifier = Arc::new(WebhookNotifier::new(vec![hook(&url, Vec::new())]));
+        let mut runner = JobRunner::new(store, ExtractionEngine::new()).with_notifier(Arc::clone(&notifier));
+        runner.run_all(None).unwrap();
+        notifier.wait();
+
+        let mut events: Vec<Value> = received.try_iter().map(|(_, body)| serde_json::from_str(&body).unwrap()).collect();
+        events.sort_by_key(|event| event["event"].as_str().map(str::to_string));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event"], "batch.finished");
+        assert_eq!(events[1]["event"], "document.finished");
+        assert_eq!(events[1]["data"]["input"].as_str(), Some(input.as_str()));
+        assert!(events.iter().all(|event| event["data"]["job_id"].as_str() == Some(job.job_id.as_str()) && event.get("tenant_id").is_none()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::security::crypto::{sha256, to_hex};
+
+// Files are written under a temporary name next to where they go and renamed into place once
+// complete, so a killed or cancelled run leaves either the whole file or none of it - never a
+// truncated JSON that downstream loaders choke on. Temporary names are
+// .<file name>.<host>-<pid>-<uuid>.spp-tmp: hidden, and listed by no reader of outputs. <host>
+// tags the machine, so a writer on another host sharing the directory is never mistaken for a
+// local process with the same pid
+pub const TEMP_SUFFIX: &str = ".spp-tmp";
+
+// Where it cannot be told that a temporary file's writer is gone - another host, a platform
+// that cannot probe a pid, a name from before hosts were tagged - it is taken to be once the
+// file has not been written to for this long
+const ORPHAN_AGE: Duration = Duration::from_secs(6 * 60 * 60);
+
+// Hex digits of the host tag
+const HOST_TAG_CHARS: usize = 12;
+
+// A file being written. Dropped without commit() - an error, a panic, a cancelled job - the
+// temporary file is removed and the target is left as it was
+pub struct AtomicFile {
+    target: PathBuf,
+    temp: PathBuf,
+    writer: Option<BufWriter<std::fs::File>>,
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut name = [0u8; 256];
+    // SAFETY: the buffer is writable for its whole length, and one byte is kept for the nul
+    let result = unsafe { libc::gethostname(name.as_mut_ptr() as *mut libc::c_char, name.len() - 1) };
+    if result != 0 {
+        return String::new();
+    }
+    let end = name.iter().position(|byte| *byte == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..end]).to_string()
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+// A hash of the host name: fixed length, safe in a file name, and not the name itself
+fn host_tag() -> &'static str {
+    static TAG: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    TAG.get_or_init(|| to_hex(&sha256(&[b"host", &[0x1f], hostname().as_bytes()]))[..HOST_TAG_CHARS].to_string())
+}
+
+fn temp_path(target: &Path) -> PathBuf {
+    let name = target.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    target.with_file_name(format!(".{}.{}-{}-{}{}", name, host_tag(), std::process::id(), uuid::Uuid::new_v4().simple(), TEMP_SUFFIX))
+}
+
+impl AtomicFile {
+    pub fn create(target: impl AsRef<Path>) -> std::io::Result<Self> {
+        let target = target.as_ref().to_path_buf();
+        let temp = temp_path(&target);
+        let file = std::fs::File::create(&temp)?;
+        Ok(Self { target, temp, writer: Some(BufWriter::new(file)) })
+    }
+
+    // Flushes the file to disk and renames it over the target. The data is synced first, so
+    // a crash just after the rename cannot leave the new name pointing at empty blocks
+    pub fn commit(mut self) -> std::io::Result<()> {
+        let writer = self.writer.take().expect("an uncommitted file has its writer");
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&self.temp, &self.target)
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.writer.as_mut().expect("an uncommitted file has its writer").write(data)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.as_mut().expect("an uncommitted file has its writer").flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+// std::fs::write, through a temporary file
+pub fn write_atomic(path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(data.as_ref())?;
+    file.commit()
+}
+
+// Whether a process of this host is still running. None where that cannot be told
+#[cfg(unix)]
+fn process_running(pid: u32) -> Option<bool> {
+    let pid = libc::pid_t::try_from(pid).ok()?;
+    // SAFETY: signal 0 delivers nothing; it only checks that the process exists
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return Some(true);
+    }
+    // EPERM: it exists, run by another user
+    Some(std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+#[cfg(not(unix))]
+fn process_running(_pid: u32) -> Option<bool> {
+    None
+}
+
+// The host tag and pid a temporary name was written with. Names from before hosts were tagged
+// have no host
+fn writer(name: &str) -> (Option<&str>, Option<u32>) {
+    let tail = name.trim_end_matches(TEMP_SUFFIX).rsplit('.').next().unwrap_or_default();
+    let parts: Vec<&str> = tail.split('-').collect();
+    match parts.as_slice() {
+        [host, pid, _] => (Some(*host), pid.parse().ok()),
+        [pid, _] => (None, pid.parse().ok()),
+        _ => (None, None),
+    }
+}
+
+// Whether the process that named a temporary file can no longer be writing it. Our own
+// process's files belong to writers still running in it
+fn orphaned(path: &Path, name: &str) -> bool {
+    let stale = || std::fs::metadata(path).and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > ORPHAN_AGE);
+    match writer(name) {
+        (Some(host), Some(pid)) if host == host_tag() => {
+            pid != std::process::id() && process_running(pid).map_or_else(stale, |running| !running)
+        }
+        _ => stale(),
+    }
+}
+
+// Removes the temporary files interrupted runs left in `dir` and the directories under it.
+// Returns the files removed
+pub fn clean_orphans(dir: impl AsRef<Path>) -> std::io::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    let entries = match std::fs::read_dir(dir.as_ref()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(removed),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            removed.extend(clean_orphans(&path)?);
+        } else if name.starts_with('.') && name.ends_with(TEMP_SUFFIX) && orphaned(&path, &name) {
+            match std::fs::remove_file(&path) {
+                Ok(()) => removed.push(path),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(dir: &Path, host: Option<&str>, pid: u32, age: Duration) -> (PathBuf, String) {
+        let writer = match host {
+            Some(host) => format!("{}-{}", host, pid),
+            None => pid.to_string(),
+        };
+        let name = format!(".doc.json.{}-{}{}", writer, uuid::Uuid::new_v4().simple(), TEMP_SUFFIX);
+        let path = dir.join(&name);
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+        (path, name)
+    }
+
+    #[test]
+    fn only_writers_known_to_be_gone_are_orphaned() {
+        let dir = std::env::temp_dir().join(format!("spp-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fresh = Duration::from_secs(60);
+        let old = ORPHAN_AGE + fresh;
+        let mut exited = std::process::Command::new(std::env::current_exe().unwrap()).arg("--list").stdout(std::process::Stdio::null()).spawn().unwrap();
+        let gone = exited.id();
+        exited.wait().unwrap();
+
+        let live = temp_file(&dir, Some(host_tag()), std::process::id(), old);
+        let dead = temp_file(&dir, Some(host_tag()), gone, fresh);
+        let elsewhere = temp_file(&dir, Some("0123456789ab"), gone, fresh);
+        let elsewhere_old = temp_file(&dir, Some("0123456789ab"), std::process::id(), old);
+        let untagged = temp_file(&dir, None, gone, fresh);
+        let untagged_old = temp_file(&dir, None, std::process::id(), old);
+
+        assert_eq!(writer(&live.1), (Some(host_tag()), Some(std::process::id())));
+        assert_eq!(writer(&untagged.1), (None, Some(gone)));
+        assert!(!orphaned(&live.0, &live.1));
+        // A pid probe is only trusted where one can be made
+        assert_eq!(orphaned(&dead.0, &dead.1), cfg!(unix));
+        assert!(!orphaned(&elsewhere.0, &elsewhere.1));
+        assert!(orphaned(&elsewhere_old.0, &elsewhere_old.1));
+        assert!(!orphaned(&untagged.0, &untagged.1));
+        assert!(orphaned(&untagged_old.0, &untagged_old.1));
+
+        let mut removed = clean_orphans(&dir).unwrap();
+        removed.sort();
+        let mut expected = vec![elsewhere_old.0, untagged_old.0];
+        if cfg!(unix) {
+            expected.push(dead.0);
+        }
+        expected.sort();
+        assert_eq!(removed, expected);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use crate::storage::fs::FsStorage;
+use crate::storage::sqlite::SqliteStorage;
+
+// Prefix of a storage spec that selects the SQLite backend; any other spec is a directory
+pub const SQLITE_SCHEME: &str = "sqlite:";
+
+// Key-value store behind the prompt cache, job checkpoints, usage meters and session state.
+// Each subsystem keeps to its own namespace; values are opaque bytes (JSON in practice).
+// Implementations must be safe to share between worker threads
+pub trait Storage: Send + Sync {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>>;
+
+    // Replaces any value already stored under the key
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Box<dyn std::error::Error>>;
+
+    // Keys in the namespace, sorted
+    fn list(&self, namespace: &str) -> Result<Vec<String>, Box<dyn std::error::Error>>;
+
+    // Returns false when there was nothing to delete
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, Box<dyn std::error::Error>>;
+}
+
+// Namespaces are lower-case identifiers, e.g. "llm_cache" or "usage"
+pub fn check_namespace(namespace: &str) -> Result<(), String> {
+    let valid = !namespace.is_empty()
+        && namespace.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(format!("Invalid storage namespace {:?} (lower-case letters, digits and _ only)", namespace));
+    }
+    Ok(())
+}
+
+// Keys must be safe as file names on every backend: letters, digits, '.', '_' and '-',
+// not starting with '.'
+pub fn check_key(key: &str) -> Result<(), String> {
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        return Err(format!("Invalid storage key {:?}", key));
+    }
+    Ok(())
+}
+
+// "sqlite:state.db" for a SQLite file, otherwise a directory for the filesystem backend.
+// New backends get a scheme here and nothing else changes
+pub fn open_storage(spec: &str) -> Result<Arc<dyn Storage>, Box<dyn std::error::Error>> {
+    match spec.strip_prefix(SQLITE_SCHEME) {
+        Some(path) => Ok(Arc::new(SqliteStorage::open(path)?)),
+        None => Ok(Arc::new(FsStorage::open(spec)?)),
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use crate::storage::atomic::write_atomic;
+use crate::storage::backend::{check_key, check_namespace, Storage};
+
+// One file per value at <root>/<namespace>/<first two key characters>/<key>. The fan-out
+// keeps large namespaces (the prompt cache) from putting millions of files in one directory
+pub struct FsStorage {
+    root: PathBuf,
+}
+
+impl FsStorage {
+    pub fn open(root: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(root)
+            .map_err(|e| format!("Cannot create storage directory {}: {}", root, e))?;
+        Ok(Self { root: PathBuf::from(root) })
+    }
+
+    fn value_path(&self, namespace: &str, key: &str) -> Result<PathBuf, String> {
+        check_namespace(namespace)?;
+        check_key(key)?;
+        let prefix: String = key.chars().take(2).collect();
+        Ok(self.root.join(namespace).join(prefix).join(key))
+    }
+}
+
+impl Storage for FsStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        match std::fs::read(self.value_path(namespace, key)?) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.value_path(namespace, key)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Keys never start with '.', so the temporary file is never listed
+        write_atomic(&path, value)?;
+        Ok(())
+    }
+
+    fn list(&self, namespace: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        check_namespace(namespace)?;
+        let dir = self.root.join(namespace);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut keys = Vec::new();
+        for prefix in std::fs::read_dir(dir)? {
+            let prefix = prefix?.path();
+            if !prefix.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(prefix)? {
+                let name = entry?.file_name().to_string_lossy().to_string();
+                if check_key(&name).is_ok() {
+                    keys.push(name);
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        match std::fs::remove_file(self.value_path(namespace, key)?) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
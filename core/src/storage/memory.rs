@@ -0,0 +1,50 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::storage::backend::{check_key, check_namespace, Storage};
+
+// Namespace -> key -> value
+type Namespaces = HashMap<String, BTreeMap<String, Vec<u8>>>;
+
+// Process-local storage - what subsystems use when no storage is configured, so their state
+// lasts as long as the process, as it always has
+#[derive(Default)]
+pub struct MemoryStorage {
+    namespaces: Mutex<Namespaces>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn namespaces(&self) -> Result<std::sync::MutexGuard<'_, Namespaces>, String> {
+        self.namespaces.lock().map_err(|_| "Storage poisoned".to_string())
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        check_namespace(namespace)?;
+        check_key(key)?;
+        Ok(self.namespaces()?.get(namespace).and_then(|values| values.get(key)).cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        check_namespace(namespace)?;
+        check_key(key)?;
+        self.namespaces()?.entry(namespace.to_string()).or_default().insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn list(&self, namespace: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        check_namespace(namespace)?;
+        Ok(self.namespaces()?.get(namespace).map(|values| values.keys().cloned().collect()).unwrap_or_default())
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        check_namespace(namespace)?;
+        check_key(key)?;
+        Ok(self.namespaces()?.get_mut(namespace).and_then(|values| values.remove(key)).is_some())
+    }
+}
@@ -0,0 +1,5 @@
+pub mod atomic;
+pub mod backend;
+pub mod fs;
+pub mod memory;
+pub mod sqlite;
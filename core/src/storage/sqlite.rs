@@ -0,0 +1,79 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use std::sync::Mutex;
+
+use crate::jobs::store::BUSY_TIMEOUT;
+use crate::storage::backend::{check_key, check_namespace, Storage};
+
+// One table of (namespace, key, value) rows. The file can be a job database, so checkpoints
+// live next to the jobs they belong to
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(db_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let conn = Connection::open(db_path)
+            .map_err(|e| format!("Cannot open storage database {}: {}", db_path, e))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS storage (
+                namespace TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value BLOB NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (namespace, key)
+            );",
+        )?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn connection(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|_| "Storage connection poisoned".to_string())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        check_namespace(namespace)?;
+        check_key(key)?;
+        let value = self.connection()?
+            .query_row(
+                "SELECT value FROM storage WHERE namespace = ?1 AND key = ?2",
+                params![namespace, key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value)
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        check_namespace(namespace)?;
+        check_key(key)?;
+        self.connection()?.execute(
+            "INSERT INTO storage (namespace, key, value, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (namespace, key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![namespace, key, value, chrono::Utc::now()],
+        )?;
+        Ok(())
+    }
+
+    fn list(&self, namespace: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        check_namespace(namespace)?;
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare("SELECT key FROM storage WHERE namespace = ?1 ORDER BY key")?;
+        let keys = stmt
+            .query_map(params![namespace], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(keys)
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        check_namespace(namespace)?;
+        check_key(key)?;
+        let changed = self.connection()?.execute(
+            "DELETE FROM storage WHERE namespace = ?1 AND key = ?2",
+            params![namespace, key],
+        )?;
+        Ok(changed > 0)
+    }
+}
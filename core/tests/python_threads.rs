@@ -0,0 +1,93 @@
+// The thread-safety contract as Python sees it: extract_modules and extract_steps from 32
+// Python threads, with the GIL released inside each call, while another thread keeps swapping
+// the rules file between two packs. The extension is a cdylib, so the test imports the library
+// cargo built for it into a python3 process
+use std::path::PathBuf;
+use std::process::Command;
+
+const TASKS: usize = 40;
+
+const SCRIPT: &str = r#"
+import os, sys, threading
+import ml_core
+
+rules, text, tasks = sys.argv[1], open(sys.argv[2]).read(), int(sys.argv[3])
+packs = [open(rules + ".full").read(), open(rules + ".half").read()]
+stop, errors = threading.Event(), []
+steps = ml_core.extract_steps(path=sys.argv[2])
+
+def reload():
+    round = 0
+    while not stop.is_set():
+        round += 1
+        with open(rules + ".tmp", "w") as staged:
+            staged.write(packs[round % 2])
+        os.replace(rules + ".tmp", rules)
+
+def extract():
+    try:
+        for _ in range(8):
+            modules = ml_core.extract_modules(text=text, rules_path=rules)
+            assert len(modules) in (tasks, tasks // 2), len(modules)
+            assert len({module["rule_pack"] for module in modules}) == 1
+            assert ml_core.extract_steps(text=text) == steps
+    except Exception as error:
+        errors.append(repr(error))
+
+reloader = threading.Thread(target=reload)
+reloader.start()
+workers = [threading.Thread(target=extract) for _ in range(32)]
+for worker in workers:
+    worker.start()
+for worker in workers:
+    worker.join()
+stop.set()
+reloader.join()
+print("\n".join(errors))
+sys.exit(1 if errors else 0)
+"#;
+
+// Integration tests run from target/<profile>/deps, and cargo builds the library for them into
+// target/<profile>
+fn built_library() -> PathBuf {
+    let profile_dir = std::env::current_exe().unwrap().parent().unwrap().parent().unwrap().to_path_buf();
+    let name = if cfg!(target_os = "windows") {
+        "ml_core.dll"
+    } else if cfg!(target_os = "macos") {
+        "libml_core.dylib"
+    } else {
+        "libml_core.so"
+    };
+    profile_dir.join(name)
+}
+
+#[test]
+fn extracts_from_python_threads_while_rules_change() {
+    let dir = std::env::temp_dir().join(format!("spp-python-threads-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let module = if cfg!(target_os = "windows") { "ml_core.pyd" } else { "ml_core.so" };
+    std::fs::copy(built_library(), dir.join(module)).unwrap();
+
+    let tasks: Vec<String> = (0..TASKS).map(|task| format!("TASK 32-{:02}-00", task + 10)).collect();
+    let text: String = tasks.iter().map(|task| format!("{}\n1. Remove the panel.\n", task)).collect();
+    let pack = |version: &str, modules: &[String]| {
+        serde_json::json!({"name": "threads", "version": version, "patterns": {"module": modules}, "prompts": {}, "thresholds": {}}).to_string()
+    };
+    let rules = dir.join("rules.json");
+    std::fs::write(dir.join("rules.json.full"), pack("1", &tasks)).unwrap();
+    std::fs::write(dir.join("rules.json.half"), pack("2", &tasks[..TASKS / 2])).unwrap();
+    std::fs::write(&rules, pack("1", &tasks)).unwrap();
+    std::fs::write(dir.join("manual.txt"), text).unwrap();
+
+    let output = Command::new("python3")
+        .arg("-c")
+        .arg(SCRIPT)
+        .arg(&rules)
+        .arg(dir.join("manual.txt"))
+        .arg(TASKS.to_string())
+        .env("PYTHONPATH", &dir)
+        .output()
+        .expect("python3 runs the extension");
+    assert!(output.status.success(), "{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    std::fs::remove_dir_all(&dir).unwrap();
+}